@@ -0,0 +1,51 @@
+//! Compares the scalar and SIMD escape loops, run with `cargo bench`
+
+use std::hint::black_box;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use fun_images::{
+    FractalVariant,
+    mandelbrot::{FractalSet, ViewPort, get_cpu_escapes},
+};
+use num_complex::Complex64;
+
+const WIDTH: u32 = 400;
+const HEIGHT: u32 = 300;
+
+fn bench_escape_loops(c: &mut Criterion) {
+    let sets = [
+        (
+            "mandelbrot",
+            FractalSet::Mandelbrot,
+            ViewPort::normal_mandelbrot(),
+        ),
+        (
+            "julia",
+            FractalSet::Julia(Complex64::new(-0.8, 0.156)),
+            ViewPort::normal_julia(),
+        ),
+    ];
+
+    for (name, set, mut viewport) in sets {
+        viewport.fit_to_size(WIDTH, HEIGHT);
+        let mut group = c.benchmark_group(name);
+        for (loop_name, use_simd) in [("scalar", false), ("simd", true)] {
+            group.bench_function(loop_name, |b| {
+                b.iter(|| {
+                    get_cpu_escapes(
+                        set,
+                        FractalVariant::Standard,
+                        black_box(&viewport),
+                        WIDTH,
+                        HEIGHT,
+                        use_simd,
+                    )
+                })
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_escape_loops);
+criterion_main!(benches);
@@ -0,0 +1,301 @@
+//! Module for drawing aliquot sequences
+//!
+//! Each number goes to the sum of its proper divisors, the divisors other than itself,
+//! and that's repeated. Most sequences shrink down to a prime and then 1, some land on
+//! a perfect number like 6 or a loop of amicable numbers like 220 and 284, and some like
+//! 276 grow so big that nobody knows where they end up. Each sequence is drawn going
+//! across a step at a time, with the height of its value on a log scale, and coloured by
+//! how it ends. See <https://en.wikipedia.org/wiki/Aliquot_sequence> for more info
+
+use csscolorparser::Color;
+use image::{Rgba, RgbaImage};
+use imageproc::drawing::{draw_antialiased_line_segment_mut, draw_filled_circle_mut};
+use rayon::prelude::*;
+
+use crate::{
+    BlendMode,
+    compose::blend_over,
+    generator::{Generated, Generator, GeneratorError},
+    memory::MemoryEstimate,
+    multiplicative::get_divisor_sum,
+    parse_color, parse_fraction, parse_size,
+};
+
+/// The space around the plot, as a fraction of the image size
+const MARGIN: f64 = 0.05;
+
+/// How an aliquot sequence ends up
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AliquotFate {
+    /// Shrinks down to 1, as the number before it was a prime
+    Terminates,
+    /// Comes back round to a number it's already been, like a perfect number
+    Cycles,
+    /// Still going after all the steps, or got too big to follow
+    Open,
+}
+
+pub struct AliquotOptions {
+    /// The first and last starting values
+    starts: (u64, u64),
+    /// How many steps to follow each sequence for before giving up on it
+    step_num: u32,
+    /// How big a value can get before the sequence is given up on
+    max_value: u64,
+    terminate_color: Color,
+    cycle_color: Color,
+    open_color: Color,
+    /// How much each sequence covers the ones drawn before it, from 0 to 1
+    opacity: f64,
+    background_color: Color,
+    width: u32,
+    height: u32,
+}
+
+impl AliquotOptions {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        starts: (u64, u64),
+        step_num: u32,
+        max_value: u64,
+        terminate_color: Color,
+        cycle_color: Color,
+        open_color: Color,
+        opacity: f64,
+        background_color: Color,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        Self {
+            starts,
+            step_num,
+            max_value,
+            terminate_color,
+            cycle_color,
+            open_color,
+            opacity,
+            background_color,
+            width,
+            height,
+        }
+    }
+
+    fn get_color(&self, fate: AliquotFate) -> Rgba<u8> {
+        let color = match fate {
+            AliquotFate::Terminates => &self.terminate_color,
+            AliquotFate::Cycles => &self.cycle_color,
+            AliquotFate::Open => &self.open_color,
+        };
+        Rgba(color.to_rgba8())
+    }
+}
+
+/// Follows the aliquot sequence from the start for up to the number of steps, giving the
+/// values along it and how it ends. A sequence that cycles ends with the value it came
+/// back round to
+pub fn get_aliquot_sequence(start: u64, step_num: u32, max_value: u64) -> (Vec<u64>, AliquotFate) {
+    let mut sequence = vec![start];
+    for _ in 0..step_num {
+        let value = *sequence.last().expect("the sequence always has its start");
+        if value == 1 {
+            return (sequence, AliquotFate::Terminates);
+        }
+        let next = get_divisor_sum(value) - value;
+        if next > max_value {
+            return (sequence, AliquotFate::Open);
+        }
+        let has_cycled = sequence.contains(&next);
+        sequence.push(next);
+        if has_cycled {
+            return (sequence, AliquotFate::Cycles);
+        }
+    }
+
+    let fate = match sequence.last() {
+        Some(1) => AliquotFate::Terminates,
+        _ => AliquotFate::Open,
+    };
+    (sequence, fate)
+}
+
+pub fn generate_aliquot_image(options: AliquotOptions) -> RgbaImage {
+    let (width, height) = (options.width, options.height);
+    let mut image = RgbaImage::from_pixel(width, height, Rgba(options.background_color.to_rgba8()));
+
+    let (first, last) = options.starts;
+    let mut sequences: Vec<(Vec<u64>, AliquotFate)> = (first..=last)
+        .into_par_iter()
+        .map(|start| get_aliquot_sequence(start, options.step_num, options.max_value))
+        .collect();
+    // the rarer endings go on top so they don't get buried
+    sequences.sort_by_key(|(_, fate)| match fate {
+        AliquotFate::Terminates => 0,
+        AliquotFate::Cycles => 1,
+        AliquotFate::Open => 2,
+    });
+
+    let longest = sequences
+        .iter()
+        .map(|(sequence, _)| sequence.len() - 1)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let highest = sequences
+        .iter()
+        .flat_map(|(sequence, _)| sequence.iter())
+        .copied()
+        .max()
+        .unwrap_or(1)
+        .max(2);
+    let (margin_x, margin_y) = (width as f64 * MARGIN, height as f64 * MARGIN);
+    // the values go up on a log scale from 1 at the bottom
+    let to_pixel = |step: usize, value: u64| {
+        let amount_across = step as f64 / longest as f64;
+        let amount_up = (value.max(1) as f64).ln() / (highest as f64).ln();
+        (
+            (margin_x + amount_across * (width as f64 - 2.0 * margin_x)).round() as i32,
+            (height as f64 - margin_y - amount_up * (height as f64 - 2.0 * margin_y)).round()
+                as i32,
+        )
+    };
+
+    let radius = (width.min(height) / 300).max(2) as i32;
+    for (sequence, fate) in sequences.iter() {
+        let color = options.get_color(*fate);
+        for (step, values) in sequence.windows(2).enumerate() {
+            draw_antialiased_line_segment_mut(
+                &mut image,
+                to_pixel(step, values[0]),
+                to_pixel(step + 1, values[1]),
+                color,
+                |line, below, amount| {
+                    Rgba(blend_over(
+                        below.0,
+                        line.0,
+                        amount as f64 * options.opacity,
+                        BlendMode::Normal,
+                    ))
+                },
+            );
+        }
+        // a dot where it ends, which is where the cycles pile up
+        let end = sequence.len() - 1;
+        draw_filled_circle_mut(&mut image, to_pixel(end, sequence[end]), radius, color);
+    }
+
+    image
+}
+
+#[derive(clap::Args, Debug)]
+pub struct AliquotArgs {
+    /// The first starting value
+    #[arg(long, default_value = "2", value_parser = clap::value_parser!(u64).range(1..))]
+    from: u64,
+
+    /// The last starting value
+    #[arg(long, default_value = "300", value_parser = clap::value_parser!(u64).range(1..))]
+    to: u64,
+
+    /// How many steps to follow each sequence for before giving up on it
+    #[arg(short, long, default_value = "60", value_parser = parse_size)]
+    steps: u32,
+
+    /// How big a value can get before the sequence is given up on, as working out the
+    /// divisors of bigger numbers gets slow
+    #[arg(long, default_value = "10000000000", value_parser = clap::value_parser!(u64).range(2..=1_000_000_000_000_000))]
+    max_value: u64,
+
+    /// The colour of sequences that shrink down to 1
+    #[arg(long, default_value = "deepskyblue", value_parser = parse_color)]
+    terminate_color: Color,
+
+    /// The colour of sequences that come back round to a perfect number or a loop of
+    /// amicable or sociable numbers
+    #[arg(long, default_value = "gold", value_parser = parse_color)]
+    cycle_color: Color,
+
+    /// The colour of sequences that are still going at the end or got too big
+    #[arg(long, default_value = "deeppink", value_parser = parse_color)]
+    open_color: Color,
+
+    /// How see-through each sequence is, from just above 0 to 1 for solid
+    #[arg(long, default_value = "0.6", value_parser = parse_fraction)]
+    opacity: f64,
+
+    #[arg(short, long, alias = "background", default_value = "black", value_parser = parse_color)]
+    background_color: Color,
+
+    #[arg(long, default_value = "1200", value_parser = parse_size)]
+    width: u32,
+
+    #[arg(long, default_value = "800", value_parser = parse_size)]
+    height: u32,
+}
+
+pub struct AliquotGenerator;
+
+impl Generator for AliquotGenerator {
+    type Args = AliquotArgs;
+
+    fn name(&self) -> &'static str {
+        "aliquot"
+    }
+
+    fn is_animated(&self, _args: &AliquotArgs) -> bool {
+        false
+    }
+
+    fn estimate_memory(&self, args: &AliquotArgs) -> Option<MemoryEstimate> {
+        Some(MemoryEstimate::still(args.width, args.height))
+    }
+
+    fn generate(&self, args: AliquotArgs) -> Result<Generated, GeneratorError> {
+        if args.from > args.to {
+            return Err(GeneratorError::InvalidOptions(
+                "the first starting value can't be more than the last".to_string(),
+            ));
+        }
+
+        Ok(Generated::Still(generate_aliquot_image(
+            AliquotOptions::new(
+                (args.from, args.to),
+                args.steps,
+                args.max_value,
+                args.terminate_color,
+                args.cycle_color,
+                args.open_color,
+                args.opacity,
+                args.background_color,
+                args.width,
+                args.height,
+            ),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aliquot_sequences() {
+        assert_eq!(
+            get_aliquot_sequence(12, 20, 1000),
+            (vec![12, 16, 15, 9, 4, 3, 1], AliquotFate::Terminates)
+        );
+        // perfect and amicable numbers come straight back round
+        assert_eq!(
+            get_aliquot_sequence(6, 20, 1000),
+            (vec![6, 6], AliquotFate::Cycles)
+        );
+        assert_eq!(
+            get_aliquot_sequence(220, 20, 1000),
+            (vec![220, 284, 220], AliquotFate::Cycles)
+        );
+        // 95 goes to 25 and then lands on the perfect number 6
+        assert_eq!(get_aliquot_sequence(95, 20, 1000).1, AliquotFate::Cycles);
+        // 276 keeps growing, so it runs out of steps or room
+        assert_eq!(get_aliquot_sequence(276, 20, 1000).1, AliquotFate::Open);
+        assert_eq!(get_aliquot_sequence(276, 5, 1_000_000).1, AliquotFate::Open);
+    }
+}
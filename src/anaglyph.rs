@@ -0,0 +1,125 @@
+//! Module for combining views from each eye into red and cyan anaglyphs
+//!
+//! Looking through glasses with a red filter over the left eye and a cyan one over the
+//! right, each eye only sees its own view, so the image looks 3D. The red comes from how
+//! bright the left view is rather than its red, as a colour the cyan eye can't see would
+//! otherwise flicker between the eyes.
+//! See <https://en.wikipedia.org/wiki/Anaglyph_3D> for more info
+
+use image::{Rgba, RgbaImage};
+
+/// Combines the views from the left and right eye, which have to be the same size
+pub fn combine_views(left: &RgbaImage, right: &RgbaImage) -> RgbaImage {
+    RgbaImage::from_fn(left.width(), left.height(), |x, y| {
+        let [red, green, blue, left_alpha] = left.get_pixel(x, y).0;
+        let [_, right_green, right_blue, right_alpha] = right.get_pixel(x, y).0;
+        let brightness = 0.2126 * red as f64 + 0.7152 * green as f64 + 0.0722 * blue as f64;
+
+        Rgba([
+            brightness.round() as u8,
+            right_green,
+            right_blue,
+            left_alpha.max(right_alpha),
+        ])
+    })
+}
+
+/// Gets the views of a heightmap from each eye by moving each pixel sideways by its
+/// height, from 0 at the back to 1 at the front, with the parallax being how many
+/// pixels further apart the front is in the two views than the back
+pub fn get_parallax_views(
+    image: &RgbaImage,
+    heights: &[f64],
+    parallax: f64,
+) -> (RgbaImage, RgbaImage) {
+    // the middle height stays put so the image doesn't move overall
+    let get_view = |direction: f64| {
+        let (width, height) = image.dimensions();
+        let mut view = RgbaImage::new(width, height);
+        for y in 0..height {
+            let row = &heights[(y * width) as usize..((y + 1) * width) as usize];
+            // the highest pixel wins where more than one lands on the same spot
+            let mut nearest = vec![f64::NEG_INFINITY; width as usize];
+            let mut is_filled = vec![false; width as usize];
+            for (x, &pixel_height) in row.iter().enumerate() {
+                let shift = direction * (pixel_height - 0.5) * parallax / 2.0;
+                let target = (x as f64 + shift).round();
+                if target < 0.0 || target >= width as f64 {
+                    continue;
+                }
+                let target = target as usize;
+                if pixel_height > nearest[target] {
+                    nearest[target] = pixel_height;
+                    is_filled[target] = true;
+                    view.put_pixel(target as u32, y, *image.get_pixel(x as u32, y));
+                }
+            }
+
+            // gaps open up where the ground behind something comes into view, which
+            // get filled in by the pixel next to them further back
+            let mut start = 0;
+            while start < width as usize {
+                if is_filled[start] {
+                    start += 1;
+                    continue;
+                }
+                let end = (start..width as usize)
+                    .find(|&x| is_filled[x])
+                    .unwrap_or(width as usize);
+                let sides = [start.checked_sub(1), (end < width as usize).then_some(end)];
+                let filler = sides
+                    .into_iter()
+                    .flatten()
+                    .min_by(|&a, &b| nearest[a].total_cmp(&nearest[b]));
+                if let Some(filler) = filler {
+                    let color = *view.get_pixel(filler as u32, y);
+                    for x in start..end {
+                        view.put_pixel(x as u32, y, color);
+                    }
+                }
+                start = end;
+            }
+        }
+
+        view
+    };
+
+    // the front comes out of the screen when it's further right in the left eye's view
+    (get_view(1.0), get_view(-1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parallax_views() {
+        // a bright raised stripe on a dark flat background
+        let image = RgbaImage::from_fn(40, 4, |x, _| match (18..22).contains(&x) {
+            true => Rgba([255, 255, 255, 255]),
+            false => Rgba([0, 0, 0, 255]),
+        });
+        let heights: Vec<f64> = image
+            .pixels()
+            .map(|pixel| pixel[0] as f64 / 255.0)
+            .collect();
+        let (left, right) = get_parallax_views(&image, &heights, 8.0);
+
+        // the stripe moves right in the left view and left in the right view, with the
+        // background moving the other way
+        assert_eq!(left[(23, 0)], Rgba([255, 255, 255, 255]));
+        assert_eq!(left[(19, 0)], Rgba([0, 0, 0, 255]));
+        assert_eq!(right[(16, 0)], Rgba([255, 255, 255, 255]));
+        assert_eq!(right[(20, 0)], Rgba([0, 0, 0, 255]));
+        // and nothing is left empty
+        assert!(
+            left.pixels()
+                .chain(right.pixels())
+                .all(|pixel| pixel[3] == 255)
+        );
+
+        let anaglyph = combine_views(&left, &right);
+        assert_eq!(anaglyph[(23, 0)], Rgba([255, 0, 0, 255]));
+        assert_eq!(anaglyph[(16, 0)], Rgba([0, 255, 255, 255]));
+    }
+}
@@ -0,0 +1,54 @@
+//! Animating any image by changing one of its options from frame to frame
+//!
+//! Each frame is made from the image's recipe with the option overridden, the same way
+//! reproduce changes options, so it works for every image type without them knowing
+//! about it
+
+use clap::FromArgMatches;
+use fun_images::cancel::render_frames;
+use image::RgbaImage;
+
+use crate::{
+    Args, ImageFormat,
+    error::Error,
+    gallery::render_image_type,
+    recipe::{apply_override, parse_recipe},
+    sweep::SweepAxis,
+};
+
+/// Renders a frame for each value of the option, using the middle frame of images
+/// which are already animated
+pub fn render_param_frames(
+    recipe: &[String],
+    param: &SweepAxis,
+    frames: u32,
+) -> Result<Vec<RgbaImage>, Error> {
+    let mut recipe = recipe.to_vec();
+    // the frames are still images of their own, so they mustn't animate again
+    apply_override(&mut recipe, "animate-param=false")?;
+    apply_override(&mut recipe, "animate-frames=false")?;
+
+    let frame_args = param
+        .get_values(frames)
+        .iter()
+        .map(|value| {
+            let mut frame_recipe = recipe.clone();
+            apply_override(&mut frame_recipe, &param.get_override(value))?;
+            let matches = parse_recipe(&frame_recipe).map_err(|error| match error {
+                Error::Reproduce(message) => Error::AnimateParam(message),
+                error => error,
+            })?;
+            Args::from_arg_matches(&matches)
+                .map_err(|clap_error| Error::AnimateParam(clap_error.to_string()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    render_frames(frame_args, |args| match args.image_type.get_format() {
+        ImageFormat::Static | ImageFormat::Animated => render_image_type(args.image_type),
+        _ => Err(Error::AnimateParam(
+            "only images can be animated".to_string(),
+        )),
+    })
+    .into_iter()
+    .collect()
+}
@@ -0,0 +1,91 @@
+//! Converts images to ASCII art for printing in the terminal
+//!
+//! Each character covers a block of pixels and is picked by how bright they are,
+//! optionally coloured with ANSI escape codes
+
+use std::fmt::Write;
+
+use image::{RgbaImage, imageops::FilterType};
+
+use crate::AsciiColor;
+
+/// Characters from darkest to brightest, assuming a dark terminal background
+const CHARACTER_RAMP: &[char] = &[' ', '.', ':', '-', '=', '+', '*', '#', '%', '@'];
+/// Terminal characters are about twice as tall as they are wide
+const CHARACTER_ASPECT_RATIO: f64 = 0.5;
+
+pub fn image_to_ascii(image: &RgbaImage, width: u32, color: AsciiColor) -> String {
+    let width = width.clamp(1, image.width().max(1));
+    let height = ((image.height() as f64 * width as f64 / image.width() as f64)
+        * CHARACTER_ASPECT_RATIO)
+        .round()
+        .max(1.0) as u32;
+    let resized = image::imageops::resize(image, width, height, FilterType::Triangle);
+
+    let mut output = String::new();
+    for row in resized.rows() {
+        for pixel in row {
+            let [r, g, b, a] = pixel.0;
+            let alpha = a as f64 / 255.0;
+            let luminance =
+                (0.2126 * r as f64 + 0.7152 * g as f64 + 0.0722 * b as f64) / 255.0 * alpha;
+            let index = (luminance * (CHARACTER_RAMP.len() - 1) as f64).round() as usize;
+            let character = CHARACTER_RAMP[index];
+
+            match color {
+                AsciiColor::None => output.push(character),
+                AsciiColor::Ansi256 => {
+                    let _ = write!(
+                        output,
+                        "\x1b[38;5;{}m{}",
+                        get_ansi_256_color(r, g, b),
+                        character
+                    );
+                }
+                AsciiColor::Truecolor => {
+                    let _ = write!(output, "\x1b[38;2;{};{};{}m{}", r, g, b, character);
+                }
+            }
+        }
+        if !matches!(color, AsciiColor::None) {
+            // reset the colour so it doesn't carry over to anything printed afterwards
+            output.push_str("\x1b[0m");
+        }
+        output.push('\n');
+    }
+
+    output
+}
+
+/// Gets the closest colour in the 6x6x6 colour cube of the 256 colour palette
+/// <https://en.wikipedia.org/wiki/ANSI_escape_code#8-bit>
+fn get_ansi_256_color(r: u8, g: u8, b: u8) -> u8 {
+    let to_cube = |value: u8| (value as u16 * 5 / 255) as u8;
+    16 + 36 * to_cube(r) + 6 * to_cube(g) + to_cube(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ansi_256_color() {
+        assert_eq!(get_ansi_256_color(0, 0, 0), 16);
+        assert_eq!(get_ansi_256_color(255, 255, 255), 231);
+        assert_eq!(get_ansi_256_color(255, 0, 0), 196);
+    }
+
+    #[test]
+    fn test_ascii_brightness() {
+        let image = RgbaImage::from_fn(4, 2, |x, _| {
+            if x < 2 {
+                image::Rgba([0, 0, 0, 255])
+            } else {
+                image::Rgba([255, 255, 255, 255])
+            }
+        });
+        // two rows of pixels become one row of characters because of the aspect ratio
+        let ascii = image_to_ascii(&image, 4, AsciiColor::None);
+        assert_eq!(ascii, "  @@\n");
+    }
+}
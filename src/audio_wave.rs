@@ -0,0 +1,486 @@
+//! Module for drawing sounds from WAV files
+//!
+//! The samples are mixed down to one channel and split up between the columns of the
+//! image, and each column is drawn from the highest to the lowest sample under it, or
+//! mirrored about the middle by how loud it is on average. The colours go through the
+//! palette from the middle out, so the loud parts reach the end of it. The spectrogram
+//! instead splits the samples around each column into frequencies with a fast Fourier
+//! transform and colours each one by how loud it is. It can also scroll along the sound
+//! a frame at a time, to line up with it being played.
+//! See <https://en.wikipedia.org/wiki/Spectrogram> for more info
+
+use std::f64::consts::TAU;
+
+use csscolorparser::Color;
+use image::{Rgba, RgbaImage};
+use rustfft::{Fft, FftPlanner, num_complex::Complex};
+
+use crate::{
+    AudioWaveMode, PaletteType,
+    cancel::render_frames,
+    generator::{Generated, Generator, GeneratorError},
+    memory::MemoryEstimate,
+    palette::get_palette_color,
+    parse_color, parse_size,
+};
+
+/// The space above and below the loudest part, as a fraction of the image height
+const MARGIN: f64 = 0.05;
+
+/// How many decibels below the loudest a sine wave can be that the spectrogram goes
+/// down to, with anything quieter the darkest colour of the palette
+const DYNAMIC_RANGE: f64 = 90.0;
+
+/// The lowest frequency the spectrogram shows with the log scale, around the lowest
+/// people can hear
+const LOWEST_FREQUENCY: f64 = 20.0;
+
+pub struct AudioWaveOptions {
+    /// The samples mixed down to one channel, from -1 to 1
+    samples: Vec<f32>,
+    sample_rate: u32,
+    mode: AudioWaveMode,
+    /// How many seconds each frame shows when scrolling, with the whole sound shown in
+    /// one image if not given
+    window: Option<f64>,
+    /// How many frames there are a second when scrolling
+    fps: f64,
+    /// How many samples go into the Fourier transform for each column of the
+    /// spectrogram, where more picks out the frequencies better but smears them in time
+    fft_size: usize,
+    /// Whether the frequencies of the spectrogram go up on a log scale, which spreads
+    /// the low ones out like the notes on a piano
+    log_frequency: bool,
+    palette: PaletteType,
+    background_color: Color,
+    width: u32,
+    height: u32,
+}
+
+impl AudioWaveOptions {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        samples: Vec<f32>,
+        sample_rate: u32,
+        mode: AudioWaveMode,
+        window: Option<f64>,
+        fps: f64,
+        fft_size: usize,
+        log_frequency: bool,
+        palette: PaletteType,
+        background_color: Color,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        Self {
+            samples,
+            sample_rate,
+            mode,
+            window,
+            fps,
+            fft_size,
+            log_frequency,
+            palette,
+            background_color,
+            width,
+            height,
+        }
+    }
+}
+
+/// The samples under a column of the image
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AudioLevel {
+    lowest: f32,
+    highest: f32,
+    /// The root mean square of the samples, which is how loud they are on average
+    rms: f32,
+}
+
+impl AudioLevel {
+    fn from_samples(samples: &[f32]) -> Self {
+        let (mut lowest, mut highest, mut square_sum) = (0.0f32, 0.0f32, 0.0);
+        for &sample in samples {
+            lowest = lowest.min(sample);
+            highest = highest.max(sample);
+            square_sum += sample as f64 * sample as f64;
+        }
+        let rms = (square_sum / samples.len().max(1) as f64).sqrt() as f32;
+        Self {
+            lowest,
+            highest,
+            rms,
+        }
+    }
+}
+
+/// Reads the samples of a WAV file mixed down to one channel, along with how many
+/// there are a second
+pub fn read_wav_samples(path: &str) -> Result<(Vec<f32>, u32), GeneratorError> {
+    let wav_error = |error| match error {
+        hound::Error::IoError(source) => GeneratorError::ReadFile {
+            path: path.to_string(),
+            source,
+        },
+        error => GeneratorError::Failed(format!("reading {}: {}", path, error)),
+    };
+    let reader = hound::WavReader::open(path).map_err(wav_error)?;
+    let spec = reader.spec();
+
+    let samples = match spec.sample_format {
+        hound::SampleFormat::Float => reader.into_samples::<f32>().collect::<Result<Vec<_>, _>>(),
+        hound::SampleFormat::Int => {
+            let scale = (1u64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .into_samples::<i32>()
+                .map(|sample| sample.map(|sample| sample as f32 / scale))
+                .collect::<Result<Vec<_>, _>>()
+        }
+    }
+    .map_err(wav_error)?;
+
+    let channel_num = spec.channels.max(1) as usize;
+    let samples = samples
+        .chunks(channel_num)
+        .map(|channels| channels.iter().sum::<f32>() / channel_num as f32)
+        .collect();
+    Ok((samples, spec.sample_rate))
+}
+
+/// Gets the levels under each column, for the columns spread evenly from the first
+/// sample position to the last. Columns before the start or past the end are silent
+pub fn get_column_levels(
+    samples: &[f32],
+    (first, last): (f64, f64),
+    width: u32,
+) -> Vec<Option<AudioLevel>> {
+    let per_column = (last - first) / width as f64;
+    (0..width)
+        .map(|x| {
+            let start = (first + x as f64 * per_column).floor();
+            // at least one sample when zoomed in past a sample a column
+            let end = (first + (x + 1) as f64 * per_column)
+                .floor()
+                .max(start + 1.0);
+            let start = start.max(0.0) as usize;
+            let end = end.clamp(0.0, samples.len() as f64) as usize;
+            (start < end).then(|| AudioLevel::from_samples(&samples[start..end]))
+        })
+        .collect()
+}
+
+/// Gets how loud each frequency is in the samples around a position, in decibels
+/// below a sine wave going from -1 to 1, for each bin of the Fourier transform up to
+/// half the sample rate
+pub fn get_spectrum(samples: &[f32], centre: f64, fft: &dyn Fft<f32>) -> Vec<f64> {
+    let size = fft.len();
+    let start = centre.round() as i64 - size as i64 / 2;
+    let mut buffer: Vec<Complex<f32>> = (0..size)
+        .map(|index| {
+            let sample = usize::try_from(start + index as i64)
+                .ok()
+                .and_then(|position| samples.get(position))
+                .copied()
+                .unwrap_or(0.0);
+            // the Hann window fades the ends out so the cut doesn't add frequencies
+            let window = 0.5 - 0.5 * (TAU * index as f64 / size as f64).cos();
+            Complex::new(sample * window as f32, 0.0)
+        })
+        .collect();
+    fft.process(&mut buffer);
+
+    // a sine wave going from -1 to 1 comes out at a quarter of the size with the window
+    buffer[..=size / 2]
+        .iter()
+        .map(|value| {
+            20.0 * (value.norm() as f64 / (size as f64 / 4.0))
+                .max(1e-12)
+                .log10()
+        })
+        .collect()
+}
+
+/// Draws the spectrogram from the first sample position to the last
+fn draw_spectrogram(image: &mut RgbaImage, options: &AudioWaveOptions, (first, last): (f64, f64)) {
+    let (width, height) = image.dimensions();
+    let fft = FftPlanner::new().plan_fft_forward(options.fft_size);
+    let sample_rate = options.sample_rate as f64;
+    let bin_width = sample_rate / options.fft_size as f64;
+    let highest = sample_rate / 2.0;
+    let lowest = LOWEST_FREQUENCY.max(bin_width).min(highest);
+
+    // which bin each row is, going up from the bottom
+    let row_bins: Vec<f64> = (0..height)
+        .map(|y| {
+            let amount_up = (height - y) as f64 / height as f64 - 0.5 / height as f64;
+            let frequency = match options.log_frequency {
+                true => lowest * (highest / lowest).powf(amount_up),
+                false => amount_up * highest,
+            };
+            frequency / bin_width
+        })
+        .collect();
+
+    let per_column = (last - first) / width as f64;
+    for x in 0..width {
+        let centre = first + (x as f64 + 0.5) * per_column;
+        if centre < 0.0 || centre >= options.samples.len() as f64 {
+            continue;
+        }
+        let spectrum = get_spectrum(&options.samples, centre, fft.as_ref());
+        for (y, &bin) in row_bins.iter().enumerate() {
+            let lower = (bin.floor() as usize).min(spectrum.len() - 1);
+            let upper = (lower + 1).min(spectrum.len() - 1);
+            let amount = bin - lower as f64;
+            let decibels = spectrum[lower] * (1.0 - amount) + spectrum[upper] * amount;
+            let loudness = ((decibels + DYNAMIC_RANGE) / DYNAMIC_RANGE).clamp(0.0, 1.0);
+            // the palettes are darkest half way through, so quiet goes from there up to
+            // bright for loud
+            image.put_pixel(
+                x,
+                y as u32,
+                Rgba(get_palette_color(options.palette, 0.5 + loudness * 0.5)),
+            );
+        }
+    }
+}
+
+/// Draws the levels from the first sample position to the last, scaled so the loudest
+/// level there could be reaches the top
+fn draw_levels(
+    image: &mut RgbaImage,
+    options: &AudioWaveOptions,
+    positions: (f64, f64),
+    loudest: f32,
+) {
+    let (width, height) = image.dimensions();
+    let middle = height as f64 / 2.0;
+    let scale = middle * (1.0 - 2.0 * MARGIN) / loudest.max(f32::EPSILON) as f64;
+    let levels = get_column_levels(&options.samples, positions, width);
+    for (x, level) in levels.into_iter().enumerate() {
+        let Some(level) = level else {
+            continue;
+        };
+        let (top, bottom) = match options.mode {
+            AudioWaveMode::Waveform => (level.highest, level.lowest),
+            AudioWaveMode::Envelope => (level.rms, -level.rms),
+            AudioWaveMode::Spectrogram => unreachable!("the spectrogram has its own colours"),
+        };
+        // at least a pixel high so silence still shows up as a line
+        let top = (middle - top as f64 * scale).floor().max(0.0) as u32;
+        let bottom = (middle - bottom as f64 * scale).ceil().min(height as f64) as u32;
+        for y in top..bottom.max(top + 1).min(height) {
+            let distance = (y as f64 + 0.5 - middle).abs() / (middle * (1.0 - 2.0 * MARGIN));
+            image.put_pixel(
+                x as u32,
+                y,
+                Rgba(get_palette_color(options.palette, distance.min(1.0) * 0.8)),
+            );
+        }
+    }
+}
+
+fn generate_audio_wave_image(
+    options: &AudioWaveOptions,
+    positions: (f64, f64),
+    loudest: f32,
+) -> RgbaImage {
+    let background_color = Rgba(options.background_color.to_rgba8());
+    let mut image = RgbaImage::from_pixel(options.width, options.height, background_color);
+    match options.mode {
+        AudioWaveMode::Spectrogram => draw_spectrogram(&mut image, options, positions),
+        _ => draw_levels(&mut image, options, positions, loudest),
+    }
+
+    image
+}
+
+/// Draws the whole sound, or a frame for each step along it when scrolling with the
+/// time each frame is at in the middle
+pub fn generate_audio_wave_images(options: AudioWaveOptions) -> Vec<RgbaImage> {
+    let samples = &options.samples;
+    // the same scale for every frame, so the quiet parts look quiet as they go past
+    let loudest = match options.mode {
+        AudioWaveMode::Waveform => samples
+            .iter()
+            .fold(0.0f32, |loudest, sample| loudest.max(sample.abs())),
+        AudioWaveMode::Envelope => {
+            // the loudest column the same size as the ones drawn, going across the
+            // whole sound
+            let per_column = match options.window {
+                Some(window) => window * options.sample_rate as f64 / options.width as f64,
+                None => samples.len() as f64 / options.width as f64,
+            };
+            let column_num = (samples.len() as f64 / per_column).ceil().max(1.0) as u32;
+            get_column_levels(samples, (0.0, samples.len() as f64), column_num)
+                .into_iter()
+                .flatten()
+                .fold(0.0f32, |loudest, level| loudest.max(level.rms))
+        }
+        // which is measured against a full sine wave instead
+        AudioWaveMode::Spectrogram => 1.0,
+    };
+
+    let Some(window) = options.window else {
+        return vec![generate_audio_wave_image(
+            &options,
+            (0.0, samples.len() as f64),
+            loudest,
+        )];
+    };
+    let sample_rate = options.sample_rate as f64;
+    let duration = samples.len() as f64 / sample_rate;
+    let frame_num = (duration * options.fps).ceil().max(1.0) as u32;
+    render_frames(0..frame_num, |frame| {
+        let middle = frame as f64 / options.fps * sample_rate;
+        let half_window = window * sample_rate / 2.0;
+        generate_audio_wave_image(
+            &options,
+            (middle - half_window, middle + half_window),
+            loudest,
+        )
+    })
+}
+
+fn parse_seconds(text: &str) -> Result<f64, String> {
+    match text.parse::<f64>() {
+        Ok(seconds) if seconds > 0.0 && seconds.is_finite() => Ok(seconds),
+        Ok(_) => Err("must be more than 0".to_string()),
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+#[derive(clap::Args, Debug)]
+pub struct AudioWaveArgs {
+    /// The WAV file to draw
+    #[arg(long)]
+    file: String,
+
+    #[arg(short, long, default_value = "waveform")]
+    mode: AudioWaveMode,
+
+    /// Scrolls along the sound showing this many seconds a frame, with the time of each
+    /// frame in the middle
+    #[arg(long, value_parser = parse_seconds)]
+    window: Option<f64>,
+
+    /// How many frames a second to scroll at. Animations are saved at 30 frames a second,
+    /// so other rates are for putting the frames together yourself
+    #[arg(long, default_value = "30", value_parser = parse_seconds)]
+    fps: f64,
+
+    /// How many samples go into the Fourier transform for each column of the
+    /// spectrogram, where more picks out the frequencies better but smears them in time
+    #[arg(long, default_value = "2048", value_parser = clap::value_parser!(u32).range(16..=65536))]
+    fft_size: u32,
+
+    /// Puts the frequencies of the spectrogram on a log scale, which spreads the low
+    /// ones out like the notes on a piano
+    #[arg(long)]
+    log_frequency: bool,
+
+    /// The palette the sound is coloured by from the middle out, or by how loud each
+    /// frequency is for the spectrogram
+    #[arg(short, long, default_value = "ocean")]
+    palette: PaletteType,
+
+    #[arg(short, long, alias = "background", default_value = "black", value_parser = parse_color)]
+    background_color: Color,
+
+    #[arg(long, default_value = "1200", value_parser = parse_size)]
+    width: u32,
+
+    #[arg(long, default_value = "300", value_parser = parse_size)]
+    height: u32,
+}
+
+pub struct AudioWaveGenerator;
+
+impl Generator for AudioWaveGenerator {
+    type Args = AudioWaveArgs;
+
+    fn name(&self) -> &'static str {
+        "audio-wave"
+    }
+
+    fn is_animated(&self, args: &AudioWaveArgs) -> bool {
+        args.window.is_some()
+    }
+
+    fn estimate_memory(&self, args: &AudioWaveArgs) -> Option<MemoryEstimate> {
+        // the frame count comes from how long the file is, so only stills are known
+        match args.window {
+            Some(_) => None,
+            None => Some(MemoryEstimate::still(args.width, args.height)),
+        }
+    }
+
+    fn generate(&self, args: AudioWaveArgs) -> Result<Generated, GeneratorError> {
+        let (samples, sample_rate) = read_wav_samples(&args.file)?;
+        if samples.is_empty() {
+            return Err(GeneratorError::Failed(format!(
+                "{} has no samples in it",
+                args.file
+            )));
+        }
+
+        let is_animated = self.is_animated(&args);
+        let mut images = generate_audio_wave_images(AudioWaveOptions::new(
+            samples,
+            sample_rate,
+            args.mode,
+            args.window,
+            args.fps,
+            args.fft_size as usize,
+            args.log_frequency,
+            args.palette,
+            args.background_color,
+            args.width,
+            args.height,
+        ));
+        Ok(match is_animated {
+            true => Generated::Frames(images),
+            false => Generated::Still(images.remove(0)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_column_levels() {
+        let samples = [0.5, -0.5, 1.0, -1.0, 0.0, 0.0];
+        let levels = get_column_levels(&samples, (0.0, 6.0), 3);
+        assert_eq!(levels[0].unwrap().highest, 0.5);
+        assert_eq!(levels[1].unwrap().lowest, -1.0);
+        assert_eq!(levels[1].unwrap().rms, 1.0);
+        assert_eq!(levels[2].unwrap().rms, 0.0);
+
+        // scrolled half off the start, and zoomed in past a sample a column
+        let levels = get_column_levels(&samples, (-3.0, 3.0), 3);
+        assert!(levels[0].is_none());
+        assert_eq!(levels[2].unwrap().highest, 1.0);
+        let levels = get_column_levels(&samples, (2.0, 3.0), 4);
+        assert!(levels.iter().all(|level| level.unwrap().highest == 1.0));
+    }
+
+    #[test]
+    fn test_spectrum() {
+        // a full sine wave 8 bins up comes out loudest there at close to 0 decibels
+        let size = 256;
+        let samples: Vec<f32> = (0..1000)
+            .map(|index| (TAU * 8.0 * index as f64 / size as f64).sin() as f32)
+            .collect();
+        let fft = FftPlanner::new().plan_fft_forward(size);
+        let spectrum = get_spectrum(&samples, 500.0, fft.as_ref());
+        assert_eq!(spectrum.len(), size / 2 + 1);
+        let loudest = (0..spectrum.len())
+            .max_by(|&first, &second| spectrum[first].total_cmp(&spectrum[second]))
+            .unwrap();
+        assert_eq!(loudest, 8);
+        assert!(spectrum[8].abs() < 0.1);
+        assert!(spectrum[40] < -60.0);
+    }
+}
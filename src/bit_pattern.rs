@@ -0,0 +1,294 @@
+//! Module for drawing bitmaps of numbers, one bit per cell
+//!
+//! The bits of a constant like π can be read across the rows to see how random they
+//! look, or each cell can be worked out from its row n and column k. Combining n and k
+//! with a bitwise and, or or xor makes nested triangles and woven squares, and colouring
+//! n choose k by whether it's a multiple of 2 makes Pascal's triangle into the
+//! Sierpinski triangle. Taking the bitwise results modulo a number like 5 or 7 makes
+//! more patterns. See <https://en.wikipedia.org/wiki/Lucas%27s_theorem> for more info
+
+use csscolorparser::Color;
+use image::{Rgba, RgbaImage};
+
+use crate::{
+    BitPattern,
+    digit_walk::{get_e_digits, get_pi_digits},
+    generator::{Generated, Generator, GeneratorError},
+    memory::MemoryEstimate,
+    parse_color, parse_size,
+};
+
+/// How many bits are worked out at a time for the constants, which each take as long
+/// as a single bit with the spigot algorithms
+const CHUNK_BITS: u32 = 32;
+
+/// The most cells the constants can fill, as working out their bits slows down with
+/// the square of how many there are and takes a few seconds by then
+pub const MAX_CONSTANT_BITS: u64 = 1 << 17;
+
+pub struct BitPatternOptions {
+    pattern: BitPattern,
+    /// What the row and column functions are taken modulo before checking whether
+    /// they're 0, with the bitwise ones taken as they are if not given and the binomial
+    /// coefficients taken modulo 2
+    modulus: Option<u64>,
+    /// How many cells go across and down
+    columns: u32,
+    rows: u32,
+    /// The width and height of each cell in pixels
+    cell_size: u32,
+    color: Color,
+    background_color: Color,
+}
+
+impl BitPatternOptions {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        pattern: BitPattern,
+        modulus: Option<u64>,
+        columns: u32,
+        rows: u32,
+        cell_size: u32,
+        color: Color,
+        background_color: Color,
+    ) -> Self {
+        Self {
+            pattern,
+            modulus,
+            columns,
+            rows,
+            cell_size,
+            color,
+            background_color,
+        }
+    }
+}
+
+/// Gets the first bits of a number from its digits in base 2 to the chunk size, with
+/// the whole part written out without leading zeros
+fn get_bits_from_chunks(chunks: &[u64], bit_num: usize) -> Vec<bool> {
+    let Some((&whole, fraction)) = chunks.split_first() else {
+        return Vec::new();
+    };
+    let whole_bits = (u64::BITS - whole.leading_zeros()).max(1);
+    let whole_bits = (0..whole_bits).rev().map(|bit| whole >> bit & 1 == 1);
+    let fraction_bits = fraction
+        .iter()
+        .flat_map(|&chunk| (0..CHUNK_BITS).rev().map(move |bit| chunk >> bit & 1 == 1));
+
+    whole_bits.chain(fraction_bits).take(bit_num).collect()
+}
+
+/// Gets the first bits of π or e in binary, starting with the whole part
+pub fn get_constant_bits(pattern: BitPattern, bit_num: usize) -> Vec<bool> {
+    // one chunk for the whole part, which has at least one bit
+    let chunk_num = bit_num.div_ceil(CHUNK_BITS as usize) + 1;
+    let base = 1 << CHUNK_BITS;
+    let chunks = match pattern {
+        BitPattern::E => get_e_digits(chunk_num, base),
+        _ => get_pi_digits(chunk_num, base),
+    };
+    get_bits_from_chunks(&chunks, bit_num)
+}
+
+/// Gets n choose k modulo a prime using Lucas's theorem, which splits it into the
+/// binomial coefficients of the digits of n and k in base p
+pub fn get_binomial_mod(mut n: u64, mut k: u64, prime: u64) -> u64 {
+    let mut result = 1;
+    while k > 0 {
+        let (n_digit, k_digit) = (n % prime, k % prime);
+        if k_digit > n_digit {
+            return 0;
+        }
+        // the digits are below the prime so the factorial on the bottom can be divided
+        // out by Fermat's little theorem
+        let (mut top, mut bottom) = (1, 1);
+        for index in 0..k_digit {
+            top = top * (n_digit - index) % prime;
+            bottom = bottom * (index + 1) % prime;
+        }
+        result = result * top % prime * get_power_mod(bottom, prime - 2, prime) % prime;
+        (n, k) = (n / prime, k / prime);
+    }
+    result
+}
+
+fn get_power_mod(mut base: u64, mut exponent: u64, modulus: u64) -> u64 {
+    let mut result = 1;
+    base %= modulus;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = result * base % modulus;
+        }
+        base = base * base % modulus;
+        exponent >>= 1;
+    }
+    result
+}
+
+/// Whether the cell at row n and column k is set for the patterns that are functions of
+/// them, which is when the function isn't 0 modulo the modulus
+fn is_cell_set(pattern: BitPattern, modulus: Option<u64>, n: u64, k: u64) -> bool {
+    let value = match pattern {
+        BitPattern::And => n & k,
+        BitPattern::Or => n | k,
+        BitPattern::Xor => n ^ k,
+        BitPattern::Binomial => return get_binomial_mod(n, k, modulus.unwrap_or(2)) != 0,
+        BitPattern::Pi | BitPattern::E => unreachable!("the constants don't have rows and columns"),
+    };
+    match modulus {
+        Some(modulus) => !value.is_multiple_of(modulus),
+        None => value != 0,
+    }
+}
+
+pub fn generate_bit_pattern_image(options: BitPatternOptions) -> RgbaImage {
+    let (columns, rows, cell_size) = (options.columns, options.rows, options.cell_size);
+    let mut image = RgbaImage::from_pixel(
+        columns * cell_size,
+        rows * cell_size,
+        Rgba(options.background_color.to_rgba8()),
+    );
+    let color = Rgba(options.color.to_rgba8());
+
+    let constant_bits = match options.pattern {
+        BitPattern::Pi | BitPattern::E => Some(get_constant_bits(
+            options.pattern,
+            (columns * rows) as usize,
+        )),
+        _ => None,
+    };
+
+    for y in 0..rows {
+        for x in 0..columns {
+            let is_set = match &constant_bits {
+                Some(bits) => bits[(y * columns + x) as usize],
+                None => is_cell_set(options.pattern, options.modulus, y as u64, x as u64),
+            };
+            if !is_set {
+                continue;
+            }
+            for pixel_y in y * cell_size..(y + 1) * cell_size {
+                for pixel_x in x * cell_size..(x + 1) * cell_size {
+                    image.put_pixel(pixel_x, pixel_y, color);
+                }
+            }
+        }
+    }
+
+    image
+}
+
+#[derive(clap::Args, Debug)]
+pub struct BitPatternArgs {
+    /// What to draw, the bits of a constant or a function of the row and column
+    #[arg(short, long, default_value = "binomial")]
+    pattern: BitPattern,
+
+    /// Sets the cells where the function of the row and column isn't a multiple of this,
+    /// rather than where it isn't 0. Must be a prime for binomial, which uses 2 if not
+    /// given
+    #[arg(short, long, value_parser = clap::value_parser!(u64).range(2..=u32::MAX as u64))]
+    modulus: Option<u64>,
+
+    /// How many cells go across, with the bits of the constants read along each row
+    #[arg(long, default_value = "256", value_parser = parse_size)]
+    columns: u32,
+
+    /// How many cells go down
+    #[arg(long, default_value = "256", value_parser = parse_size)]
+    rows: u32,
+
+    /// The width and height of each cell in pixels
+    #[arg(long, default_value = "3", value_parser = parse_size)]
+    cell_size: u32,
+
+    /// The colour of the cells that are set
+    #[arg(short, long, default_value = "white", value_parser = parse_color)]
+    color: Color,
+
+    #[arg(short, long, alias = "background", default_value = "black", value_parser = parse_color)]
+    background_color: Color,
+}
+
+pub struct BitPatternGenerator;
+
+impl Generator for BitPatternGenerator {
+    type Args = BitPatternArgs;
+
+    fn name(&self) -> &'static str {
+        "bit-pattern"
+    }
+
+    fn is_animated(&self, _args: &BitPatternArgs) -> bool {
+        false
+    }
+
+    fn estimate_memory(&self, args: &BitPatternArgs) -> Option<MemoryEstimate> {
+        Some(MemoryEstimate::still(
+            args.columns.saturating_mul(args.cell_size),
+            args.rows.saturating_mul(args.cell_size),
+        ))
+    }
+
+    fn generate(&self, args: BitPatternArgs) -> Result<Generated, GeneratorError> {
+        if let (BitPattern::Binomial, Some(modulus)) = (args.pattern, args.modulus)
+            && !primal::is_prime(modulus)
+        {
+            return Err(GeneratorError::InvalidOptions(format!(
+                "the modulus for binomial has to be a prime, and {} isn't",
+                modulus
+            )));
+        }
+        if matches!(args.pattern, BitPattern::Pi | BitPattern::E)
+            && args.columns as u64 * args.rows as u64 > MAX_CONSTANT_BITS
+        {
+            return Err(GeneratorError::InvalidOptions(format!(
+                "working out more than {} bits of the constants takes too long, use fewer \
+                 --rows or --columns",
+                MAX_CONSTANT_BITS
+            )));
+        }
+
+        Ok(Generated::Still(generate_bit_pattern_image(
+            BitPatternOptions::new(
+                args.pattern,
+                args.modulus,
+                args.columns,
+                args.rows,
+                args.cell_size,
+                args.color,
+                args.background_color,
+            ),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bit_patterns() {
+        // π is 11.001001000011111101101010100010..., and e is 10.1011011111100001...
+        let to_bits = |text: &str| text.chars().map(|bit| bit == '1').collect::<Vec<_>>();
+        assert_eq!(
+            get_constant_bits(BitPattern::Pi, 34),
+            to_bits("1100100100001111110110101010001000")
+        );
+        assert_eq!(
+            get_constant_bits(BitPattern::E, 18),
+            to_bits("101011011111100001")
+        );
+
+        // row 4 of Pascal's triangle is 1 4 6 4 1, and 10 choose 3 is 120
+        let row: Vec<u64> = (0..6).map(|k| get_binomial_mod(4, k, 2)).collect();
+        assert_eq!(row, [1, 0, 0, 0, 1, 0]);
+        assert_eq!(get_binomial_mod(10, 3, 7), 120 % 7);
+        assert_eq!(get_binomial_mod(10, 3, 5), 0);
+
+        assert!(!is_cell_set(BitPattern::And, None, 4, 3));
+        assert!(is_cell_set(BitPattern::Xor, Some(5), 4, 3));
+        assert!(!is_cell_set(BitPattern::Or, Some(7), 4, 3));
+    }
+}
@@ -0,0 +1,43 @@
+//! Stopping long renders part way through
+//!
+//! Once cancelled, the animated generators stop starting new frames and return the
+//! ones they've finished, so whatever's been made so far can still be saved. The
+//! slower still images stop part way through too, leaving the rest of the image blank
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use rayon::prelude::*;
+
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+/// Asks every render that's running to stop as soon as it can
+pub fn cancel() {
+    CANCELLED.store(true, Ordering::Relaxed);
+}
+
+pub fn is_cancelled() -> bool {
+    CANCELLED.load(Ordering::Relaxed)
+}
+
+/// Renders the frames in parallel until cancelled, keeping the frames before the first
+/// one that was skipped so the animation doesn't have gaps. A frame that was still
+/// rendering when cancelled counts as skipped, as it may have stopped part way through
+pub fn render_frames<I, F, T>(frames: I, render_frame: F) -> Vec<T>
+where
+    I: IntoParallelIterator,
+    F: Fn(I::Item) -> T + Sync + Send,
+    T: Send,
+{
+    let frames: Vec<Option<T>> = frames
+        .into_par_iter()
+        .map(|frame| match is_cancelled() {
+            true => None,
+            false => {
+                let frame = render_frame(frame);
+                (!is_cancelled()).then_some(frame)
+            }
+        })
+        .collect();
+
+    frames.into_iter().map_while(|frame| frame).collect()
+}
@@ -0,0 +1,191 @@
+//! Saving the progress of images rendered in tiles, so an interrupted render can carry
+//! on from where it stopped instead of starting over
+//!
+//! The checkpoint file starts with a line saying which image it's for, then each row
+//! of tiles is added to the end as a PNG after its length in bytes as soon as it's
+//! finished. A row that was only half written when the render stopped is thrown away
+//! when resuming
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use image::{ImageFormat, RgbaImage};
+use log::info;
+use serde_json::json;
+
+use crate::{error::Error, output::write_png};
+
+pub struct Checkpoint {
+    path: String,
+    file: File,
+    /// Where each finished row's PNG starts in the file and how long it is
+    rows: Vec<(u64, u64)>,
+}
+
+impl Checkpoint {
+    /// Starts a new checkpoint for the image, or carries on with the one that's
+    /// already there when resuming
+    pub fn open(
+        path: &str,
+        recipe: &[String],
+        width: u32,
+        height: u32,
+        row_num: usize,
+        resume: bool,
+    ) -> Result<Self, Error> {
+        // the rows have to be split the same way for the saved ones to fit
+        let header = json!({ "recipe": recipe, "width": width, "height": height, "rows": row_num })
+            .to_string();
+        let exists = Path::new(path).exists();
+        if exists && !resume {
+            return Err(get_error(
+                path,
+                "it already exists, use --resume to carry on from it or delete it to start over",
+            ));
+        }
+        if !exists {
+            if resume {
+                info!("No checkpoint at {}, starting from the beginning", path);
+            }
+            let mut file = File::create_new(path).map_err(|io_error| get_error(path, io_error))?;
+            writeln!(file, "{}", header).map_err(|io_error| get_error(path, io_error))?;
+            return Ok(Self {
+                path: path.to_string(),
+                file,
+                rows: Vec::new(),
+            });
+        }
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .map_err(|io_error| get_error(path, io_error))?;
+        let mut checkpoint = Self {
+            path: path.to_string(),
+            file,
+            rows: Vec::new(),
+        };
+        checkpoint
+            .read_rows(&header)
+            .map_err(|io_error| get_error(path, io_error))?;
+        Ok(checkpoint)
+    }
+
+    /// Finds the rows already in the file, after checking it's for the same image
+    fn read_rows(&mut self, header: &str) -> io::Result<()> {
+        let file_length = self.file.metadata()?.len();
+        let mut reader = BufReader::new(&self.file);
+        let mut saved_header = String::new();
+        reader.read_line(&mut saved_header)?;
+        if saved_header.trim_end() != header {
+            return Err(io::Error::other(
+                "it was made for a different image, delete it to start over",
+            ));
+        }
+
+        let mut offset = saved_header.len() as u64;
+        let mut length_bytes = [0; 8];
+        while reader.read_exact(&mut length_bytes).is_ok() {
+            let length = u64::from_le_bytes(length_bytes);
+            let start = offset + length_bytes.len() as u64;
+            if start + length > file_length {
+                break;
+            }
+            reader.seek_relative(length as i64)?;
+            self.rows.push((start, length));
+            offset = start + length;
+        }
+        // anything after the last whole row was cut off part way through writing it
+        self.file.set_len(offset)
+    }
+
+    pub fn finished_rows(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn read_row(&mut self, index: usize) -> Result<RgbaImage, Error> {
+        let (start, length) = self.rows[index];
+        let mut bytes = vec![0; length as usize];
+        self.file
+            .seek(SeekFrom::Start(start))
+            .and_then(|_| self.file.read_exact(&mut bytes))
+            .map_err(|io_error| get_error(&self.path, io_error))?;
+
+        image::load_from_memory_with_format(&bytes, ImageFormat::Png)
+            .map(|image| image.into_rgba8())
+            .map_err(|image_error| get_error(&self.path, image_error))
+    }
+
+    /// Adds a finished row to the end of the file, making sure it's on disk before
+    /// moving on
+    pub fn save_row(&mut self, image: &RgbaImage) -> Result<(), Error> {
+        let mut bytes = Vec::new();
+        write_png(&mut bytes, image, &[])
+            .map_err(|encoding_error| get_error(&self.path, encoding_error))?;
+
+        let write_row = |file: &mut File| {
+            let end = file.seek(SeekFrom::End(0))?;
+            file.write_all(&(bytes.len() as u64).to_le_bytes())?;
+            file.write_all(&bytes)?;
+            file.sync_data()?;
+            Ok::<_, io::Error>(end + 8)
+        };
+        let start =
+            write_row(&mut self.file).map_err(|io_error| get_error(&self.path, io_error))?;
+        self.rows.push((start, bytes.len() as u64));
+
+        Ok(())
+    }
+
+    /// Deletes the checkpoint once the image it was for is finished
+    pub fn remove(self) -> Result<(), Error> {
+        drop(self.file);
+        fs::remove_file(&self.path).map_err(|io_error| get_error(&self.path, io_error))
+    }
+}
+
+fn get_error(path: &str, message: impl ToString) -> Error {
+    Error::Checkpoint {
+        path: path.to_string(),
+        message: message.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resume_checkpoint() {
+        let path =
+            std::env::temp_dir().join(format!("fun-images-{}.checkpoint", std::process::id()));
+        let path = path.to_str().unwrap();
+        let recipe = vec!["mandelbrot".to_string()];
+        let rows = [
+            RgbaImage::from_pixel(4, 2, image::Rgba([255, 0, 0, 255])),
+            RgbaImage::from_pixel(4, 2, image::Rgba([0, 0, 255, 255])),
+        ];
+
+        let mut checkpoint = Checkpoint::open(path, &recipe, 4, 4, 2, false).unwrap();
+        for row in rows.iter() {
+            checkpoint.save_row(row).unwrap();
+        }
+        // as if it stopped part way through writing the second row
+        let length = checkpoint.file.metadata().unwrap().len();
+        checkpoint.file.set_len(length - 10).unwrap();
+        drop(checkpoint);
+
+        assert!(Checkpoint::open(path, &recipe, 4, 4, 2, false).is_err());
+        assert!(Checkpoint::open(path, &recipe, 4, 4, 3, true).is_err());
+        let mut checkpoint = Checkpoint::open(path, &recipe, 4, 4, 2, true).unwrap();
+        assert_eq!(checkpoint.finished_rows(), 1);
+        assert_eq!(checkpoint.read_row(0).unwrap(), rows[0]);
+        checkpoint.save_row(&rows[1]).unwrap();
+        assert_eq!(checkpoint.read_row(1).unwrap(), rows[1]);
+        checkpoint.remove().unwrap();
+    }
+}
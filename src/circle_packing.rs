@@ -0,0 +1,338 @@
+//! Module for packing circles into a shape
+//!
+//! Circles are placed greedily: a random point and radius are picked, and the circle
+//! is shrunk to fit between the circles already there and the edge of the shape. If it
+//! would end up smaller than the smallest radius allowed it's skipped, so the gaps fill
+//! up with smaller and smaller circles until nothing else fits.
+//! See <https://en.wikipedia.org/wiki/Circle_packing> for more info
+
+use std::f64::consts::PI;
+
+use csscolorparser::Color;
+use image::{Rgba, RgbaImage};
+use imageproc::drawing::draw_filled_circle_mut;
+
+use crate::{
+    PackingColoring, PackingShape, PaletteType,
+    cancel::is_cancelled,
+    generator::{Generated, Generator, GeneratorError},
+    memory::MemoryEstimate,
+    palette::get_palette_color,
+    parse_color, parse_size,
+};
+
+pub struct CirclePackingOptions {
+    width: u32,
+    height: u32,
+    shape: PackingShape,
+    min_radius: f64,
+    max_radius: f64,
+    /// The space left between neighbouring circles
+    gap: f64,
+    /// How many random circles are tried, most of which won't fit once it fills up
+    attempt_num: u32,
+    coloring: PackingColoring,
+    palette: PaletteType,
+    background_color: Color,
+    seed: Option<u64>,
+}
+
+impl CirclePackingOptions {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        width: u32,
+        height: u32,
+        shape: PackingShape,
+        min_radius: f64,
+        max_radius: f64,
+        gap: f64,
+        attempt_num: u32,
+        coloring: PackingColoring,
+        palette: PaletteType,
+        background_color: Color,
+        seed: Option<u64>,
+    ) -> Self {
+        Self {
+            width,
+            height,
+            shape,
+            min_radius,
+            max_radius: max_radius.max(min_radius),
+            gap,
+            attempt_num,
+            coloring,
+            palette,
+            background_color,
+            seed,
+        }
+    }
+
+    /// How far a point is inside the shape, negative if it's outside
+    fn get_inside_distance(&self, (x, y): (f64, f64)) -> f64 {
+        let (width, height) = (self.width as f64, self.height as f64);
+        let (centre_x, centre_y) = (width / 2.0, height / 2.0);
+        let radius = width.min(height) / 2.0;
+        let polygon_distance = |side_num: u32, radius: f64, centre_y: f64| {
+            // the distance from the middle to the furthest edge, with the bottom edge flat
+            let apothem = radius * (PI / side_num as f64).cos();
+            let furthest = (0..side_num)
+                .map(|side| {
+                    let angle = PI / 2.0 + 2.0 * PI * side as f64 / side_num as f64;
+                    (x - centre_x) * angle.cos() + (y - centre_y) * angle.sin()
+                })
+                .fold(f64::MIN, f64::max);
+            apothem - furthest
+        };
+        // a triangle is 1.5 times its radius tall, with its middle a third of the way up,
+        // so it's moved down to leave the same space above and below it
+        let triangle_radius = (width / 3.0f64.sqrt()).min(height / 1.5);
+
+        match self.shape {
+            PackingShape::Canvas => x.min(y).min(width - x).min(height - y),
+            PackingShape::Circle => radius - f64::hypot(x - centre_x, y - centre_y),
+            PackingShape::Triangle => {
+                polygon_distance(3, triangle_radius, centre_y + triangle_radius / 4.0)
+            }
+            PackingShape::Hexagon => polygon_distance(6, radius, centre_y),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct PackedCircle {
+    pub centre: (f64, f64),
+    pub radius: f64,
+    pub color: Rgba<u8>,
+}
+
+/// Packs the circles, in the order they were placed
+pub fn pack_circles(options: &CirclePackingOptions) -> Vec<PackedCircle> {
+    let mut rng = match options.seed {
+        Some(seed) => fastrand::Rng::with_seed(seed),
+        None => fastrand::Rng::new(),
+    };
+    let radius_range = options.max_radius - options.min_radius;
+
+    let mut circles: Vec<PackedCircle> = Vec::new();
+    for _ in 0..options.attempt_num {
+        if is_cancelled() {
+            break;
+        }
+        let centre = (
+            rng.f64() * options.width as f64,
+            rng.f64() * options.height as f64,
+        );
+        let wanted_radius = options.min_radius + rng.f64() * radius_range;
+        let space = circles
+            .iter()
+            .map(|circle| {
+                f64::hypot(centre.0 - circle.centre.0, centre.1 - circle.centre.1)
+                    - circle.radius
+                    - options.gap
+            })
+            .fold(options.get_inside_distance(centre), f64::min);
+        if space < options.min_radius {
+            continue;
+        }
+
+        let radius = wanted_radius.min(space);
+        let position = match options.coloring {
+            // not quite all the way round, as palettes like rainbow wrap back to the
+            // same colour at the end
+            PackingColoring::Radius if radius_range > 0.0 => {
+                (radius - options.min_radius) / radius_range * 0.8
+            }
+            PackingColoring::Radius => 0.0,
+            PackingColoring::Random => rng.f64(),
+        };
+        circles.push(PackedCircle {
+            centre,
+            radius,
+            color: Rgba(get_palette_color(options.palette, position)),
+        });
+    }
+
+    circles
+}
+
+fn draw_circles_mut(image: &mut RgbaImage, circles: &[PackedCircle]) {
+    for circle in circles.iter() {
+        draw_filled_circle_mut(
+            image,
+            (circle.centre.0 as i32, circle.centre.1 as i32),
+            circle.radius as i32,
+            circle.color,
+        );
+    }
+}
+
+pub fn generate_circle_packing_image(options: CirclePackingOptions) -> RgbaImage {
+    let circles = pack_circles(&options);
+    let mut image = RgbaImage::from_pixel(
+        options.width,
+        options.height,
+        Rgba(options.background_color.to_rgba8()),
+    );
+    draw_circles_mut(&mut image, &circles);
+
+    image
+}
+
+/// Animates the circles going in, in the order they were packed
+pub fn generate_circle_packing_images(
+    options: CirclePackingOptions,
+    circles_per_frame: u32,
+) -> Vec<RgbaImage> {
+    let circles = pack_circles(&options);
+    let mut image = RgbaImage::from_pixel(
+        options.width,
+        options.height,
+        Rgba(options.background_color.to_rgba8()),
+    );
+
+    let mut images = vec![image.clone()];
+    for frame_circles in circles.chunks(circles_per_frame.max(1) as usize) {
+        if is_cancelled() {
+            break;
+        }
+        draw_circles_mut(&mut image, frame_circles);
+        images.push(image.clone());
+    }
+
+    images
+}
+
+#[derive(clap::Args, Debug)]
+pub struct CirclePackingArgs {
+    #[arg(long, default_value = "800", value_parser = parse_size)]
+    width: u32,
+
+    #[arg(long, default_value = "800", value_parser = parse_size)]
+    height: u32,
+
+    /// The shape the circles are packed into
+    #[arg(long, default_value = "canvas")]
+    shape: PackingShape,
+
+    #[arg(long, default_value = "2.0")]
+    min_radius: f64,
+
+    #[arg(long, default_value = "60.0")]
+    max_radius: f64,
+
+    /// The space left between neighbouring circles
+    #[arg(long, default_value = "1.0")]
+    gap: f64,
+
+    /// How many random circles are tried, more fills the gaps with smaller circles
+    #[arg(long, default_value = "20000", value_parser = parse_size)]
+    attempts: u32,
+
+    #[arg(long, default_value = "radius")]
+    coloring: PackingColoring,
+
+    #[arg(short, long, default_value = "ocean")]
+    palette: PaletteType,
+
+    /// The colour behind the circles, which can be transparent
+    #[arg(short, long, alias = "background", default_value = "white", value_parser = parse_color)]
+    background_color: Color,
+
+    /// The seed used for the circles, random if not given
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Animates the circles going in one batch at a time
+    #[arg(long)]
+    animate: bool,
+
+    /// How many circles go in on each frame of the animation
+    #[arg(long, default_value = "20", value_parser = parse_size)]
+    circles_per_frame: u32,
+}
+
+pub struct CirclePackingGenerator;
+
+impl Generator for CirclePackingGenerator {
+    type Args = CirclePackingArgs;
+
+    fn name(&self) -> &'static str {
+        "circle-packing"
+    }
+
+    fn is_animated(&self, args: &CirclePackingArgs) -> bool {
+        args.animate
+    }
+
+    fn estimate_memory(&self, args: &CirclePackingArgs) -> Option<MemoryEstimate> {
+        // the frame count depends on how many circles fit, so only stills are known
+        match args.animate {
+            true => None,
+            false => Some(MemoryEstimate::still(args.width, args.height)),
+        }
+    }
+
+    fn generate(&self, args: CirclePackingArgs) -> Result<Generated, GeneratorError> {
+        if args.min_radius <= 0.0 {
+            return Err(GeneratorError::InvalidOptions(
+                "the min radius has to be more than 0".to_string(),
+            ));
+        }
+        let options = CirclePackingOptions::new(
+            args.width,
+            args.height,
+            args.shape,
+            args.min_radius,
+            args.max_radius,
+            args.gap,
+            args.attempts,
+            args.coloring,
+            args.palette,
+            args.background_color,
+            args.seed,
+        );
+
+        Ok(match args.animate {
+            true => Generated::Frames(generate_circle_packing_images(
+                options,
+                args.circles_per_frame,
+            )),
+            false => Generated::Still(generate_circle_packing_image(options)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_circles_fit() {
+        let options = CirclePackingOptions::new(
+            200,
+            150,
+            PackingShape::Triangle,
+            2.0,
+            20.0,
+            1.0,
+            2000,
+            PackingColoring::Radius,
+            PaletteType::Ocean,
+            Color::new(1.0, 1.0, 1.0, 1.0),
+            Some(3),
+        );
+        let circles = pack_circles(&options);
+        assert!(circles.len() > 10);
+
+        for (index, circle) in circles.iter().enumerate() {
+            assert!(options.get_inside_distance(circle.centre) >= circle.radius);
+            for other in circles[..index].iter() {
+                let distance = f64::hypot(
+                    circle.centre.0 - other.centre.0,
+                    circle.centre.1 - other.centre.1,
+                );
+                assert!(distance >= circle.radius + other.radius + options.gap - 1e-9);
+            }
+        }
+    }
+}
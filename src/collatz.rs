@@ -0,0 +1,249 @@
+//! Module for drawing how long numbers take to get to 1 in the Collatz problem
+//!
+//! Even numbers are halved and odd numbers go to three times themselves plus one, and
+//! every number anyone has tried eventually falls to 1. How many steps that takes jumps
+//! around a lot from one number to the next, so laying the numbers out in a grid and
+//! colouring each one by its stopping time shows up streaks where neighbours share most
+//! of their path. See <https://en.wikipedia.org/wiki/Collatz_conjecture> for more info
+
+use csscolorparser::Color;
+use image::{Rgba, RgbaImage};
+
+use crate::{
+    GridLayout, PaletteType,
+    generator::{Generated, Generator, GeneratorError},
+    memory::MemoryEstimate,
+    palette::get_palette_color,
+    parse_color, parse_size,
+    ulam_spiral::get_spiral_index,
+};
+
+pub struct CollatzOptions {
+    /// How many numbers to go up to, starting from 1
+    size: u32,
+    layout: GridLayout,
+    /// How many numbers go across each row when laying them out in rows, with as many as
+    /// makes a square if not given
+    column_num: Option<u32>,
+    /// The width and height of each number's square in pixels
+    cell_size: u32,
+    palette: PaletteType,
+    background_color: Color,
+}
+
+impl CollatzOptions {
+    pub fn new(
+        size: u32,
+        layout: GridLayout,
+        column_num: Option<u32>,
+        cell_size: u32,
+        palette: PaletteType,
+        background_color: Color,
+    ) -> Self {
+        Self {
+            size,
+            layout,
+            column_num,
+            cell_size,
+            palette,
+            background_color,
+        }
+    }
+
+    /// Gets how many numbers go across and down the grid
+    fn get_grid_size(&self) -> (u32, u32) {
+        // the square root rounded up, so the numbers all fit in a square
+        let mut side = self.size.isqrt();
+        if side * side != self.size {
+            side += 1;
+        }
+        match self.layout {
+            GridLayout::RowMajor => {
+                let column_num = self.column_num.unwrap_or(side);
+                (column_num, self.size.div_ceil(column_num))
+            }
+            // odd so 1 goes in the middle
+            GridLayout::Spiral => {
+                let side = side | 1;
+                (side, side)
+            }
+        }
+    }
+
+    /// Gets the number in a cell of the grid, which might be past the size
+    fn get_number(&self, (x, y): (u32, u32)) -> u64 {
+        let (columns, rows) = self.get_grid_size();
+        match self.layout {
+            GridLayout::RowMajor => y as u64 * columns as u64 + x as u64 + 1,
+            GridLayout::Spiral => {
+                let centre = ((columns / 2) as i64, (rows / 2) as i64);
+                get_spiral_index(x as i64 - centre.0, y as i64 - centre.1) + 1
+            }
+        }
+    }
+}
+
+/// Gets how many steps each number up to the limit takes to get to 1, indexed by the
+/// number with 0 for 0
+pub fn get_stopping_times(limit: u32) -> Vec<u32> {
+    let mut times = vec![0; limit as usize + 1];
+    for number in 2..=limit as usize {
+        // follows it down until it gets below where it started, which is already known
+        let (mut value, mut step_num) = (number as u64, 0);
+        while value >= number as u64 {
+            value = match value.is_multiple_of(2) {
+                true => value / 2,
+                false => 3 * value + 1,
+            };
+            step_num += 1;
+        }
+        times[number] = step_num + times[value as usize];
+    }
+
+    times
+}
+
+pub fn generate_collatz_image(options: CollatzOptions) -> RgbaImage {
+    let (columns, rows) = options.get_grid_size();
+    let cell_size = options.cell_size;
+    let background_color = Rgba(options.background_color.to_rgba8());
+    let mut image = RgbaImage::from_pixel(columns * cell_size, rows * cell_size, background_color);
+
+    let times = get_stopping_times(options.size);
+    let longest = times.iter().copied().max().unwrap_or(0).max(1);
+
+    for y in 0..rows {
+        for x in 0..columns {
+            let number = options.get_number((x, y));
+            if number > options.size as u64 {
+                continue;
+            }
+            let position = times[number as usize] as f64 / longest as f64;
+            let color = Rgba(get_palette_color(options.palette, position * 0.8));
+            for pixel_y in y * cell_size..(y + 1) * cell_size {
+                for pixel_x in x * cell_size..(x + 1) * cell_size {
+                    image.put_pixel(pixel_x, pixel_y, color);
+                }
+            }
+        }
+    }
+
+    image
+}
+
+#[derive(clap::Args, Debug)]
+pub struct CollatzArgs {
+    /// How many numbers to go up to, defaults to 201 squared
+    #[arg(short, long, default_value = "40401", value_parser = parse_size)]
+    size: u32,
+
+    /// How the numbers are laid out
+    #[arg(short, long, default_value = "spiral")]
+    layout: GridLayout,
+
+    /// How many numbers go across each row for the row-major layout, where powers of 2 and
+    /// multiples of 3 line up the numbers with similar paths. Makes a square if not given
+    #[arg(long, value_parser = parse_size)]
+    columns: Option<u32>,
+
+    /// The width and height of each number's square in pixels
+    #[arg(long, default_value = "3", value_parser = parse_size)]
+    cell_size: u32,
+
+    /// The palette the numbers are coloured by how many steps they take to get to 1
+    #[arg(short, long, default_value = "ocean")]
+    palette: PaletteType,
+
+    /// The colour of the cells past the last number, which can be transparent
+    #[arg(short, long, alias = "background", default_value = "black", value_parser = parse_color)]
+    background_color: Color,
+}
+
+pub struct CollatzGenerator;
+
+impl Generator for CollatzGenerator {
+    type Args = CollatzArgs;
+
+    fn name(&self) -> &'static str {
+        "collatz"
+    }
+
+    fn is_animated(&self, _args: &CollatzArgs) -> bool {
+        false
+    }
+
+    fn estimate_memory(&self, args: &CollatzArgs) -> Option<MemoryEstimate> {
+        let options = CollatzOptions::new(
+            args.size,
+            args.layout,
+            args.columns,
+            args.cell_size,
+            args.palette,
+            args.background_color.clone(),
+        );
+        let (columns, rows) = options.get_grid_size();
+        Some(
+            MemoryEstimate::still(
+                columns.saturating_mul(args.cell_size),
+                rows.saturating_mul(args.cell_size),
+            )
+            .with_working(args.size as u64 * 4),
+        )
+    }
+
+    fn generate(&self, args: CollatzArgs) -> Result<Generated, GeneratorError> {
+        Ok(Generated::Still(generate_collatz_image(
+            CollatzOptions::new(
+                args.size,
+                args.layout,
+                args.columns,
+                args.cell_size,
+                args.palette,
+                args.background_color,
+            ),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stopping_times() {
+        let times = get_stopping_times(30);
+        // 6, 3, 10, 5, 16, 8, 4, 2, 1
+        assert_eq!(times[6], 8);
+        assert_eq!(times[1], 0);
+        assert_eq!(times[27], 111);
+
+        let options = CollatzOptions::new(
+            10,
+            GridLayout::Spiral,
+            None,
+            1,
+            PaletteType::Rainbow,
+            Color::new(0.0, 0.0, 0.0, 1.0),
+        );
+        // 1 goes in the middle, and 2 just to the right of it
+        assert_eq!(options.get_grid_size(), (5, 5));
+        assert_eq!(options.get_number((2, 2)), 1);
+        assert_eq!(options.get_number((3, 2)), 2);
+
+        let options = CollatzOptions::new(
+            10,
+            GridLayout::RowMajor,
+            None,
+            1,
+            PaletteType::Rainbow,
+            Color::new(0.0, 0.0, 0.0, 1.0),
+        );
+        assert_eq!(options.get_grid_size(), (4, 3));
+        assert_eq!(options.get_number((1, 1)), 6);
+        let options = CollatzOptions {
+            column_num: Some(2),
+            ..options
+        };
+        assert_eq!(options.get_grid_size(), (2, 5));
+    }
+}
@@ -0,0 +1,290 @@
+//! Stacking images on top of each other, like the layers in an image editor
+
+use csscolorparser::Color;
+use image::{ImageBuffer, Rgba, RgbaImage, imageops};
+
+use crate::{BlendMode, Placement, cancel::render_frames};
+
+/// How much the frame just before shows through with onion skinning, with the ones
+/// before that fading out
+const ONION_SKIN_OPACITY: f64 = 0.4;
+
+/// An image to draw over the ones below it
+#[derive(Clone, Debug)]
+pub struct Layer {
+    image: RgbaImage,
+    /// How much of the layer shows, from 0 to 1
+    opacity: f64,
+    /// Where the top left of the layer goes, which can be outside the image
+    offset: (i64, i64),
+    /// How the layer's colours mix with the colours below
+    blend_mode: BlendMode,
+}
+
+impl Layer {
+    pub fn new(image: RgbaImage, opacity: f64, offset: (i64, i64), blend_mode: BlendMode) -> Self {
+        Self {
+            image,
+            opacity: opacity.clamp(0.0, 1.0),
+            offset,
+            blend_mode,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.image.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.image.height()
+    }
+}
+
+/// Draws the layers in order from the bottom up over the background, cutting off
+/// anything outside the image
+pub fn compose_layers(
+    width: u32,
+    height: u32,
+    background_color: &Color,
+    layers: &[Layer],
+) -> RgbaImage {
+    let mut image = ImageBuffer::from_pixel(width, height, Rgba(background_color.to_rgba8()));
+
+    for layer in layers.iter() {
+        draw_layer(&mut image, layer);
+    }
+
+    image
+}
+
+fn draw_layer(image: &mut RgbaImage, layer: &Layer) {
+    let (width, height) = image.dimensions();
+    for (x, y, pixel) in layer.image.enumerate_pixels() {
+        let x = x as i64 + layer.offset.0;
+        let y = y as i64 + layer.offset.1;
+        if x < 0 || y < 0 || x >= width as i64 || y >= height as i64 {
+            continue;
+        }
+        let below = image.get_pixel_mut(x as u32, y as u32);
+        *below = Rgba(blend_over(
+            below.0,
+            pixel.0,
+            layer.opacity,
+            layer.blend_mode,
+        ));
+    }
+}
+
+/// Mixes another image into the image, stretching it to the same size first
+pub fn blend_images(
+    image: &RgbaImage,
+    other: &RgbaImage,
+    blend_mode: BlendMode,
+    opacity: f64,
+) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let other = if other.dimensions() == (width, height) {
+        other.clone()
+    } else {
+        imageops::resize(other, width, height, imageops::FilterType::Triangle)
+    };
+
+    compose_layers(
+        width,
+        height,
+        &Color::new(0.0, 0.0, 0.0, 0.0),
+        &[
+            Layer::new(image.clone(), 1.0, (0, 0), BlendMode::Normal),
+            Layer::new(other, opacity, (0, 0), blend_mode),
+        ],
+    )
+}
+
+/// Draws faint copies of the frames before each frame over it, like animators' onion
+/// skinning, so the movement from one to the next shows
+pub fn apply_onion_skin(frames: &[RgbaImage], skin_num: u32) -> Vec<RgbaImage> {
+    render_frames(0..frames.len() as u32, |index| {
+        let index = index as usize;
+        let (width, height) = frames[index].dimensions();
+        let mut layers = vec![Layer::new(
+            frames[index].clone(),
+            1.0,
+            (0, 0),
+            BlendMode::Normal,
+        )];
+        // the furthest back goes on first so the nearest is the clearest
+        for back in (1..=index.min(skin_num as usize)).rev() {
+            layers.push(Layer::new(
+                frames[index - back].clone(),
+                ONION_SKIN_OPACITY / back as f64,
+                (0, 0),
+                BlendMode::Normal,
+            ));
+        }
+        compose_layers(width, height, &Color::new(0.0, 0.0, 0.0, 0.0), &layers)
+    })
+}
+
+/// Draws a smaller image like a logo over the image in the placement, shrinking it if
+/// it doesn't fit
+pub fn stamp_image(image: &mut RgbaImage, stamp: &RgbaImage, placement: Placement, opacity: f64) {
+    let (width, height) = image.dimensions();
+    let margin = width.min(height) / 50;
+    let (max_width, max_height) = (
+        width.saturating_sub(margin * 2).max(1),
+        height.saturating_sub(margin * 2).max(1),
+    );
+    let stamp = if stamp.width() > max_width || stamp.height() > max_height {
+        let scale = f64::min(
+            max_width as f64 / stamp.width() as f64,
+            max_height as f64 / stamp.height() as f64,
+        );
+        imageops::resize(
+            stamp,
+            ((stamp.width() as f64 * scale) as u32).max(1),
+            ((stamp.height() as f64 * scale) as u32).max(1),
+            imageops::FilterType::Triangle,
+        )
+    } else {
+        stamp.clone()
+    };
+
+    let offset = get_placement_offset(
+        placement,
+        (width, height),
+        stamp.dimensions(),
+        margin as i64,
+    );
+    draw_layer(
+        image,
+        &Layer::new(stamp, opacity, offset, BlendMode::Normal),
+    );
+}
+
+/// Gets where the top left of something this size goes to put it in the placement,
+/// keeping the margin from the edges it's next to
+pub(crate) fn get_placement_offset(
+    placement: Placement,
+    (width, height): (u32, u32),
+    (inner_width, inner_height): (u32, u32),
+    margin: i64,
+) -> (i64, i64) {
+    let (width, height) = (width as i64, height as i64);
+    let (inner_width, inner_height) = (inner_width as i64, inner_height as i64);
+
+    let left = match placement {
+        Placement::TopLeft | Placement::BottomLeft => margin,
+        Placement::Top | Placement::Centre | Placement::Bottom => (width - inner_width) / 2,
+        Placement::TopRight | Placement::BottomRight => width - margin - inner_width,
+    };
+    let top = match placement {
+        Placement::TopLeft | Placement::Top | Placement::TopRight => margin,
+        Placement::Centre => (height - inner_height) / 2,
+        Placement::BottomLeft | Placement::Bottom | Placement::BottomRight => {
+            height - margin - inner_height
+        }
+    };
+
+    (left, top)
+}
+
+/// Puts one colour over another using their alphas, the usual "over" compositing, with
+/// the colours mixed by the blend mode where both are there
+pub(crate) fn blend_over(
+    below: [u8; 4],
+    above: [u8; 4],
+    opacity: f64,
+    blend_mode: BlendMode,
+) -> [u8; 4] {
+    let above_alpha = above[3] as f64 / 255.0 * opacity;
+    let below_alpha = below[3] as f64 / 255.0;
+    let alpha = above_alpha + below_alpha * (1.0 - above_alpha);
+    if alpha == 0.0 {
+        return [0, 0, 0, 0];
+    }
+
+    let mut color = [0; 4];
+    for channel in 0..3 {
+        let below_value = below[channel] as f64 / 255.0;
+        let above_value = above[channel] as f64 / 255.0;
+        // where there's nothing below, the layer's own colour shows
+        let mixed = (1.0 - below_alpha) * above_value
+            + below_alpha * mix(blend_mode, below_value, above_value);
+        let value = (mixed * above_alpha + below_value * below_alpha * (1.0 - above_alpha)) / alpha;
+        color[channel] = (value * 255.0).round() as u8;
+    }
+    color[3] = (alpha * 255.0).round() as u8;
+
+    color
+}
+
+/// Mixes two colour values from 0 to 1
+fn mix(blend_mode: BlendMode, below: f64, above: f64) -> f64 {
+    match blend_mode {
+        BlendMode::Normal => above,
+        BlendMode::Multiply => below * above,
+        BlendMode::Screen => below + above - below * above,
+        BlendMode::Overlay => {
+            if below <= 0.5 {
+                2.0 * below * above
+            } else {
+                1.0 - 2.0 * (1.0 - below) * (1.0 - above)
+            }
+        }
+        BlendMode::Add => (below + above).min(1.0),
+        BlendMode::Difference => (below - above).abs(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compose_layers() {
+        let red = ImageBuffer::from_pixel(2, 2, Rgba([255, 0, 0, 255]));
+        let blue = ImageBuffer::from_pixel(2, 2, Rgba([0, 0, 255, 255]));
+        let image = compose_layers(
+            3,
+            3,
+            &Color::new(0.0, 0.0, 0.0, 0.0),
+            &[
+                Layer::new(red.clone(), 1.0, (0, 0), BlendMode::Normal),
+                Layer::new(blue, 0.5, (1, 1), BlendMode::Normal),
+            ],
+        );
+
+        assert_eq!(image[(0, 0)], Rgba([255, 0, 0, 255]));
+        assert_eq!(image[(1, 1)], Rgba([128, 0, 128, 255]));
+        assert_eq!(image[(2, 2)], Rgba([0, 0, 255, 128]));
+        assert_eq!(image[(2, 0)], Rgba([0, 0, 0, 0]));
+
+        let grey = ImageBuffer::from_pixel(1, 1, Rgba([128, 128, 128, 255]));
+        for (blend_mode, expected) in [
+            (BlendMode::Multiply, [128, 0, 0, 255]),
+            (BlendMode::Screen, [255, 128, 128, 255]),
+            (BlendMode::Add, [255, 128, 128, 255]),
+            (BlendMode::Difference, [127, 128, 128, 255]),
+        ] {
+            let image = blend_images(&red, &grey, blend_mode, 1.0);
+            assert_eq!(image[(0, 0)], Rgba(expected), "{:?}", blend_mode);
+        }
+
+        let mut image = ImageBuffer::new(100, 100);
+        stamp_image(&mut image, &red, Placement::BottomRight, 1.0);
+        assert_eq!(image[(96, 96)], Rgba([255, 0, 0, 255]));
+        assert_eq!(image[(97, 97)], Rgba([255, 0, 0, 255]));
+        assert_eq!(image[(98, 98)], Rgba([0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn test_onion_skin() {
+        let black = ImageBuffer::from_pixel(1, 1, Rgba([0, 0, 0, 255]));
+        let white = ImageBuffer::from_pixel(1, 1, Rgba([255, 255, 255, 255]));
+        let frames = apply_onion_skin(&[white.clone(), black.clone(), black], 1);
+        // the first frame has nothing before it, and only one frame back shows
+        assert_eq!(frames[0], white);
+        assert_eq!(frames[1][(0, 0)], Rgba([102, 102, 102, 255]));
+        assert_eq!(frames[2][(0, 0)], Rgba([0, 0, 0, 255]));
+    }
+}
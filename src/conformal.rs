@@ -0,0 +1,191 @@
+//! Module for warping images through complex maps
+//!
+//! Each pixel is a point w in the complex plane, and its colour comes from wherever the
+//! map sends z to w, so the inverse of the map is worked out for every pixel rather than
+//! pushing the source pixels forward, which would leave gaps. Conformal maps keep the
+//! angles between lines the same, so the squares of a checkerboard stay square however
+//! much they get bent and stretched.
+//! See <https://en.wikipedia.org/wiki/Conformal_map> for more info
+
+use csscolorparser::Color;
+use image::{Rgba, RgbaImage};
+use num_complex::Complex64;
+
+use crate::ConformalMap;
+
+/// How wide the part of the plane shown is when not zoomed in, and how wide the source
+/// image is when placed on the plane
+const PLANE_WIDTH: f64 = 4.0;
+
+/// What gets warped by the map
+pub enum ConformalSource {
+    /// A checkerboard covering the whole plane, with squares of this size in the plane
+    Checkerboard {
+        color: Color,
+        background_color: Color,
+        square_size: f64,
+    },
+    /// An image centred on 0, repeated across the plane
+    Image(RgbaImage),
+}
+
+/// The coefficients of a Möbius transformation, (az + b) / (cz + d)
+#[derive(Clone, Copy, Debug)]
+pub struct Mobius {
+    pub a: Complex64,
+    pub b: Complex64,
+    pub c: Complex64,
+    pub d: Complex64,
+}
+
+impl Mobius {
+    /// The inverse of a Möbius transformation is another one, (dw - b) / (-cw + a)
+    fn invert(&self, w: Complex64) -> Complex64 {
+        (self.d * w - self.b) / (-self.c * w + self.a)
+    }
+}
+
+pub struct ConformalOptions {
+    map: ConformalMap,
+    /// Only used by the Möbius map
+    mobius: Mobius,
+    source: ConformalSource,
+    centre: Complex64,
+    zoom: f64,
+    width: u32,
+    height: u32,
+}
+
+impl ConformalOptions {
+    pub fn new(
+        map: ConformalMap,
+        mobius: Mobius,
+        source: ConformalSource,
+        centre: Complex64,
+        zoom: f64,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        Self {
+            map,
+            mobius,
+            source,
+            centre,
+            zoom,
+            width,
+            height,
+        }
+    }
+}
+
+pub fn generate_conformal_image(options: ConformalOptions) -> RgbaImage {
+    let (width, height) = (options.width, options.height);
+    let scale = PLANE_WIDTH / options.zoom / width as f64;
+
+    RgbaImage::from_fn(width, height, |x, y| {
+        // the middle of the pixel, with the imaginary axis going up
+        let w = options.centre
+            + Complex64::new(
+                (x as f64 + 0.5 - width as f64 / 2.0) * scale,
+                (height as f64 / 2.0 - y as f64 - 0.5) * scale,
+            );
+        let z = invert_map(options.map, &options.mobius, w);
+        match z.is_finite() {
+            true => sample_source(&options.source, z),
+            false => Rgba([0, 0, 0, 0]),
+        }
+    })
+}
+
+/// Gets the z the map sends to w, using the principal branch for the maps that send
+/// more than one z there
+fn invert_map(map: ConformalMap, mobius: &Mobius, w: Complex64) -> Complex64 {
+    match map {
+        ConformalMap::Reciprocal => w.inv(),
+        ConformalMap::Square => w.sqrt(),
+        ConformalMap::Exp => w.ln(),
+        ConformalMap::Mobius => mobius.invert(w),
+    }
+}
+
+fn sample_source(source: &ConformalSource, z: Complex64) -> Rgba<u8> {
+    match source {
+        ConformalSource::Checkerboard {
+            color,
+            background_color,
+            square_size,
+        } => {
+            // worked out exactly rather than from an image, so it never runs out of detail
+            let square = (z.re / square_size).floor() + (z.im / square_size).floor();
+            match square.rem_euclid(2.0) == 0.0 {
+                true => Rgba(color.to_rgba8()),
+                false => Rgba(background_color.to_rgba8()),
+            }
+        }
+        ConformalSource::Image(image) => {
+            let (image_width, image_height) = image.dimensions();
+            let scale = image_width as f64 / PLANE_WIDTH;
+            let x = z.re * scale + image_width as f64 / 2.0 - 0.5;
+            let y = image_height as f64 / 2.0 - z.im * scale - 0.5;
+            sample_bilinear(image, x, y)
+        }
+    }
+}
+
+/// Blends the 4 pixels around a point, wrapping around the edges so the image repeats
+fn sample_bilinear(image: &RgbaImage, x: f64, y: f64) -> Rgba<u8> {
+    let (width, height) = (image.width() as i64, image.height() as i64);
+    let (left, top) = (x.floor(), y.floor());
+    let (x_amount, y_amount) = (x - left, y - top);
+    let get_pixel = |x: i64, y: i64| {
+        image
+            .get_pixel(x.rem_euclid(width) as u32, y.rem_euclid(height) as u32)
+            .0
+            .map(|channel| channel as f64)
+    };
+
+    let (left, top) = (left as i64, top as i64);
+    let corners = [
+        (get_pixel(left, top), (1.0 - x_amount) * (1.0 - y_amount)),
+        (get_pixel(left + 1, top), x_amount * (1.0 - y_amount)),
+        (get_pixel(left, top + 1), (1.0 - x_amount) * y_amount),
+        (get_pixel(left + 1, top + 1), x_amount * y_amount),
+    ];
+    Rgba(std::array::from_fn(|channel| {
+        corners
+            .iter()
+            .map(|(pixel, weight)| pixel[channel] * weight)
+            .sum::<f64>()
+            .round() as u8
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inverse_maps() {
+        let mobius = Mobius {
+            a: Complex64::new(1.0, 0.0),
+            b: Complex64::new(-1.0, 0.0),
+            c: Complex64::new(1.0, 0.0),
+            d: Complex64::new(1.0, 0.0),
+        };
+        let z = Complex64::new(0.3, -0.7);
+        let maps = [
+            (ConformalMap::Reciprocal, z.inv()),
+            (ConformalMap::Square, z * z),
+            (ConformalMap::Exp, z.exp()),
+            (ConformalMap::Mobius, (z - 1.0) / (z + 1.0)),
+        ];
+
+        for (map, w) in maps {
+            assert!(
+                (invert_map(map, &mobius, w) - z).norm() < 1e-12,
+                "{:?}",
+                map
+            );
+        }
+    }
+}
@@ -0,0 +1,139 @@
+//! Module for drawing contour lines over images made from a value at each pixel
+//!
+//! Like the lines on a topographic map, each contour goes through the points where the
+//! value is the same. They're found with marching squares, which looks at each square
+//! of four neighbouring pixels, works out which of its corners are above the level and
+//! so which of its sides the line crosses, then joins those crossings up.
+//! See <https://en.wikipedia.org/wiki/Marching_squares> for more info
+
+use csscolorparser::Color;
+use image::{Rgba, RgbaImage};
+use imageproc::{drawing::draw_antialiased_line_segment_mut, pixelops::interpolate};
+
+type Segment = ((f64, f64), (f64, f64));
+
+#[derive(Clone, Debug)]
+pub struct ContourOptions {
+    /// How many contours there are, evenly spaced between the lowest and highest values
+    level_num: u32,
+    color: Color,
+}
+
+impl ContourOptions {
+    pub fn new(level_num: u32, color: Color) -> Self {
+        Self { level_num, color }
+    }
+
+    /// Gets the values the contours go through, leaving out the lowest and highest
+    /// values as there's nothing below or above them to go around
+    pub fn get_levels(&self, lowest: f64, highest: f64) -> Vec<f64> {
+        (1..=self.level_num)
+            .map(|level| lowest + (highest - lowest) * level as f64 / (self.level_num + 1) as f64)
+            .collect()
+    }
+
+    /// Draws the contours between the lowest and highest values over an image, with
+    /// the values given for each pixel in row order
+    pub fn draw_mut(&self, image: &mut RgbaImage, values: &[f64], lowest: f64, highest: f64) {
+        let (width, height) = image.dimensions();
+        let color = Rgba(self.color.to_rgba8());
+        for level in self.get_levels(lowest, highest) {
+            for (start, end) in get_segments(values, width, height, level) {
+                draw_antialiased_line_segment_mut(
+                    image,
+                    (start.0.round() as i32, start.1.round() as i32),
+                    (end.0.round() as i32, end.1.round() as i32),
+                    color,
+                    interpolate,
+                );
+            }
+        }
+    }
+}
+
+/// Gets where along a side from one value to another the level is, from 0 to 1
+fn get_crossing(from: f64, to: f64, level: f64) -> f64 {
+    (level - from) / (to - from)
+}
+
+/// Gets the pieces of the contour at a level with marching squares, going between the
+/// middles of the pixels
+pub fn get_segments(values: &[f64], width: u32, height: u32, level: f64) -> Vec<Segment> {
+    let get_value = |x: u32, y: u32| values[(y * width + x) as usize];
+    let mut segments = vec![];
+    for y in 0..height.saturating_sub(1) {
+        for x in 0..width.saturating_sub(1) {
+            let corners = [
+                get_value(x, y),
+                get_value(x + 1, y),
+                get_value(x + 1, y + 1),
+                get_value(x, y + 1),
+            ];
+            // places where the value can't be worked out are left without contours
+            if corners.iter().any(|value| !value.is_finite()) {
+                continue;
+            }
+            let is_above = corners.map(|value| value >= level);
+            let [top_left, top_right, bottom_right, bottom_left] = corners;
+            let (x, y) = (x as f64, y as f64);
+
+            // where the contour crosses each side, going clockwise from the top
+            let crossings = [
+                (is_above[0] != is_above[1])
+                    .then(|| (x + get_crossing(top_left, top_right, level), y)),
+                (is_above[1] != is_above[2])
+                    .then(|| (x + 1.0, y + get_crossing(top_right, bottom_right, level))),
+                (is_above[3] != is_above[2])
+                    .then(|| (x + get_crossing(bottom_left, bottom_right, level), y + 1.0)),
+                (is_above[0] != is_above[3])
+                    .then(|| (x, y + get_crossing(top_left, bottom_left, level))),
+            ];
+            match crossings {
+                [Some(top), Some(right), Some(bottom), Some(left)] => {
+                    // opposite corners are above, so the middle decides whether they're
+                    // joined up, with the contours going around the other two corners
+                    let middle = (top_left + top_right + bottom_right + bottom_left) / 4.0;
+                    match (middle >= level) == is_above[0] {
+                        true => segments.extend([(top, right), (bottom, left)]),
+                        false => segments.extend([(top, left), (right, bottom)]),
+                    }
+                }
+                _ => {
+                    let mut crossings = crossings.into_iter().flatten();
+                    if let (Some(start), Some(end)) = (crossings.next(), crossings.next()) {
+                        segments.push((start, end));
+                    }
+                }
+            }
+        }
+    }
+
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_segments_follow_circle() {
+        // the distance from the middle, so the contours are circles
+        let size = 41;
+        let values: Vec<f64> = (0..size * size)
+            .map(|index| f64::hypot((index % size) as f64 - 20.0, (index / size) as f64 - 20.0))
+            .collect();
+
+        let segments = get_segments(&values, size, size, 10.5);
+        assert!(!segments.is_empty());
+        for (start, end) in segments {
+            for (x, y) in [start, end] {
+                // the crossings are linearly interpolated, which is a little off on a curve
+                assert!((f64::hypot(x - 20.0, y - 20.0) - 10.5).abs() < 0.1);
+            }
+            assert!(f64::hypot(end.0 - start.0, end.1 - start.1) < 1.5);
+        }
+
+        let options = ContourOptions::new(3, Color::new(0.0, 0.0, 0.0, 1.0));
+        assert_eq!(options.get_levels(0.0, 4.0), vec![1.0, 2.0, 3.0]);
+    }
+}
@@ -0,0 +1,313 @@
+//! Module for drawing walks that follow the digits of a number like π
+//!
+//! Each digit turns the walker or points it in one of 10 directions, then it takes a
+//! step. The digits of π and e look random, so the walk wanders off like a random walk,
+//! and the colour along it shows how far through the digits it is. The digits come from
+//! spigot algorithms, which pull out one digit at a time using only whole numbers.
+//! See <https://en.wikipedia.org/wiki/Spigot_algorithm> for more info
+
+use std::f64::consts::TAU;
+
+use csscolorparser::Color;
+use image::{Rgba, RgbaImage};
+use imageproc::{drawing::draw_antialiased_line_segment_mut, pixelops::interpolate};
+
+use crate::{
+    DigitSource, DigitWalkMode, PaletteType,
+    generator::{Generated, Generator, GeneratorError},
+    memory::MemoryEstimate,
+    palette::get_palette_color,
+    parse_color, parse_size,
+};
+
+/// The space around the walk, as a fraction of the image size
+const MARGIN: f64 = 0.05;
+
+/// The most digits worked out with the spigots, which take a few seconds by then and
+/// slow down with the square of the number of digits
+pub const MAX_COMPUTED_DIGITS: u32 = 20000;
+
+pub struct DigitWalkOptions {
+    digits: Vec<u64>,
+    mode: DigitWalkMode,
+    palette: PaletteType,
+    background_color: Color,
+    size: u32,
+}
+
+impl DigitWalkOptions {
+    pub fn new(
+        digits: Vec<u64>,
+        mode: DigitWalkMode,
+        palette: PaletteType,
+        background_color: Color,
+        size: u32,
+    ) -> Self {
+        Self {
+            digits,
+            mode,
+            palette,
+            background_color,
+            size,
+        }
+    }
+}
+
+/// Gets the first digits of π in a base, starting with the 3, using the spigot
+/// algorithm of Rabinowitz and Wagon
+pub fn get_pi_digits(digit_num: usize, base: u64) -> Vec<u64> {
+    // a few extra, as a run of the highest digit isn't known until the digit after it
+    let step_num = digit_num + 10;
+    // each term adds about a bit, so there need to be enough for every digit
+    let length = (step_num as f64 * (base as f64).log2()) as usize + 2;
+    let mut remainders = vec![2u64; length];
+    let mut digits = Vec::with_capacity(step_num);
+    // the digit held back in case the next one carries into it, and how many of the
+    // highest digit there have been since
+    let (mut held, mut highest_num) = (0, 0);
+
+    for _ in 0..step_num {
+        let mut carry = 0;
+        for index in (1..=length as u64).rev() {
+            let value = base * remainders[index as usize - 1] + carry * index;
+            remainders[index as usize - 1] = value % (2 * index - 1);
+            carry = value / (2 * index - 1);
+        }
+        remainders[0] = carry % base;
+        let digit = carry / base;
+
+        match digit {
+            _ if digit == base - 1 => highest_num += 1,
+            _ if digit == base => {
+                digits.push(held + 1);
+                digits.extend(std::iter::repeat_n(0, highest_num));
+                (held, highest_num) = (0, 0);
+            }
+            _ => {
+                digits.push(held);
+                digits.extend(std::iter::repeat_n(base - 1, highest_num));
+                (held, highest_num) = (digit, 0);
+            }
+        }
+    }
+
+    // the first digit held back is a 0 before the 3
+    digits.into_iter().skip(1).take(digit_num).collect()
+}
+
+/// Gets the first digits of e in a base, starting with the 2, by writing it as
+/// 2 + 1/2(1 + 1/3(1 + 1/4(...))) and multiplying the part after the point by the base
+/// for each digit
+pub fn get_e_digits(digit_num: usize, base: u64) -> Vec<u64> {
+    // enough terms that the factorial of the last is much bigger than the base to the
+    // number of digits, so the terms left off can't change any of them
+    let (mut term_num, mut factorial_digits) = (0, 0.0);
+    while factorial_digits < digit_num as f64 + 10.0 {
+        term_num += 1;
+        factorial_digits += (term_num as f64 + 1.0).log(base as f64);
+    }
+    let mut remainders = vec![1u64; term_num];
+    let mut digits = vec![2];
+
+    while digits.len() < digit_num {
+        let mut carry = 0;
+        for (index, remainder) in remainders.iter_mut().enumerate().rev() {
+            let term_base = index as u64 + 2;
+            let value = *remainder * base + carry;
+            *remainder = value % term_base;
+            carry = value / term_base;
+        }
+        digits.push(carry);
+    }
+
+    digits.truncate(digit_num);
+    digits
+}
+
+/// Gets the digits in some text, skipping anything else like the decimal point
+pub fn parse_digits(text: &str) -> Vec<u64> {
+    text.chars()
+        .filter_map(|character| character.to_digit(10))
+        .map(|digit| digit as u64)
+        .collect()
+}
+
+/// Gets the points the walk goes through, a step apart, starting at the origin facing up
+fn get_walk_points(digits: &[u64], mode: DigitWalkMode) -> Vec<(f64, f64)> {
+    let mut point = (0.0, 0.0);
+    let mut angle: f64 = 0.0;
+    let mut points = Vec::with_capacity(digits.len() + 1);
+    points.push(point);
+
+    for &digit in digits {
+        let digit_angle = digit as f64 * TAU / 10.0;
+        angle = match mode {
+            DigitWalkMode::Turn => angle + digit_angle,
+            DigitWalkMode::Heading => digit_angle,
+        };
+        // clockwise from straight up, with the y axis going down
+        point = (point.0 + angle.sin(), point.1 - angle.cos());
+        points.push(point);
+    }
+
+    points
+}
+
+pub fn generate_digit_walk_image(options: DigitWalkOptions) -> RgbaImage {
+    let size = options.size;
+    let mut image = RgbaImage::from_pixel(size, size, Rgba(options.background_color.to_rgba8()));
+
+    let points = get_walk_points(&options.digits, options.mode);
+    let (mut lowest, mut highest) = ((0.0f64, 0.0f64), (0.0f64, 0.0f64));
+    for &(x, y) in points.iter() {
+        lowest = (lowest.0.min(x), lowest.1.min(y));
+        highest = (highest.0.max(x), highest.1.max(y));
+    }
+    // fits the walk in the image keeping it in the middle
+    let span = (highest.0 - lowest.0).max(highest.1 - lowest.1).max(1.0);
+    let scale = size as f64 * (1.0 - 2.0 * MARGIN) / span;
+    let middle = ((lowest.0 + highest.0) / 2.0, (lowest.1 + highest.1) / 2.0);
+    let to_pixel = |(x, y): (f64, f64)| {
+        (
+            (size as f64 / 2.0 + (x - middle.0) * scale).round() as i32,
+            (size as f64 / 2.0 + (y - middle.1) * scale).round() as i32,
+        )
+    };
+
+    let step_num = points.len() - 1;
+    for (index, step) in points.windows(2).enumerate() {
+        let position = index as f64 / step_num.max(1) as f64;
+        draw_antialiased_line_segment_mut(
+            &mut image,
+            to_pixel(step[0]),
+            to_pixel(step[1]),
+            Rgba(get_palette_color(options.palette, position * 0.8)),
+            interpolate,
+        );
+    }
+
+    image
+}
+
+#[derive(clap::Args, Debug)]
+pub struct DigitWalkArgs {
+    /// The number whose digits are walked
+    #[arg(long, default_value = "pi")]
+    source: DigitSource,
+
+    /// A file of digits to walk instead, where anything that isn't a digit is skipped
+    #[arg(long)]
+    file: Option<String>,
+
+    /// How many digits to walk, working them out gets slower the more there are so at
+    /// most 20000 can be worked out, with any more needing a --file
+    #[arg(short = 'n', long, default_value = "5000", value_parser = parse_size)]
+    digits: u32,
+
+    /// How the digits move the walker
+    #[arg(short, long, default_value = "turn")]
+    mode: DigitWalkMode,
+
+    /// The palette the walk goes through from start to finish
+    #[arg(short, long, default_value = "rainbow")]
+    palette: PaletteType,
+
+    #[arg(short, long, alias = "background", default_value = "black", value_parser = parse_color)]
+    background_color: Color,
+
+    #[arg(short, long, default_value = "1000", value_parser = parse_size)]
+    size: u32,
+}
+
+pub struct DigitWalkGenerator;
+
+impl Generator for DigitWalkGenerator {
+    type Args = DigitWalkArgs;
+
+    fn name(&self) -> &'static str {
+        "digit-walk"
+    }
+
+    fn is_animated(&self, _args: &DigitWalkArgs) -> bool {
+        false
+    }
+
+    fn estimate_memory(&self, args: &DigitWalkArgs) -> Option<MemoryEstimate> {
+        Some(MemoryEstimate::still(args.size, args.size))
+    }
+
+    fn generate(&self, args: DigitWalkArgs) -> Result<Generated, GeneratorError> {
+        if args.file.is_none() && args.digits > MAX_COMPUTED_DIGITS {
+            return Err(GeneratorError::InvalidOptions(format!(
+                "working out more than {} digits takes too long, use fewer --digits or a \
+                 --file with the digits in it",
+                MAX_COMPUTED_DIGITS
+            )));
+        }
+
+        let digit_num = args.digits as usize;
+        let digits = match &args.file {
+            Some(path) => {
+                let text =
+                    std::fs::read_to_string(path).map_err(|source| GeneratorError::ReadFile {
+                        path: path.clone(),
+                        source,
+                    })?;
+                let mut digits = parse_digits(&text);
+                if digits.is_empty() {
+                    return Err(GeneratorError::Failed(format!(
+                        "{} has no digits in it",
+                        path
+                    )));
+                }
+                digits.truncate(digit_num);
+                digits
+            }
+            None => match args.source {
+                DigitSource::Pi => get_pi_digits(digit_num, 10),
+                DigitSource::E => get_e_digits(digit_num, 10),
+            },
+        };
+
+        Ok(Generated::Still(generate_digit_walk_image(
+            DigitWalkOptions::new(
+                digits,
+                args.mode,
+                args.palette,
+                args.background_color,
+                args.size,
+            ),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_digits() {
+        assert_eq!(
+            get_pi_digits(40, 10),
+            parse_digits("3.141592653589793238462643383279502884197")
+        );
+        // the run of six 9s at the Feynman point needs the held back digits to be right
+        let digits = get_pi_digits(800, 10);
+        assert_eq!(digits.len(), 800);
+        assert_eq!(digits[762..768], [9; 6]);
+        assert_eq!(digits[768..770], [8, 3]);
+
+        assert_eq!(
+            get_e_digits(30, 10),
+            parse_digits("2.71828182845904523536028747135")
+        );
+        // and in hexadecimal
+        assert_eq!(get_pi_digits(5, 16), [3, 2, 4, 3, 15]);
+        assert_eq!(get_e_digits(5, 16), [2, 11, 7, 14, 1]);
+
+        // turning by 5 tenths each step goes back and forth
+        let points = get_walk_points(&[0, 5, 5], DigitWalkMode::Turn);
+        assert!((points[1].1 + 1.0).abs() < 1e-9);
+        assert!(points[2].1.abs() < 1e-9 && (points[3].1 + 1.0).abs() < 1e-9);
+    }
+}
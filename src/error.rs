@@ -0,0 +1,159 @@
+//! Errors that stop the command line from making an image
+//!
+//! Each kind of error exits with its own code so scripts can tell them apart, with 2
+//! for usage errors, whether clap reports them for bad arguments or the options can't
+//! make an image together
+
+use std::{io, process::ExitCode};
+
+use fun_images::generator::GeneratorError;
+use image::ImageError;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("opening image {path}: {source}")]
+    OpenImage { path: String, source: ImageError },
+
+    #[error(
+        "saving to {path}: can't tell the format from the extension, use png, apng, gif, webp, bmp or tiff"
+    )]
+    UnknownFormat { path: String },
+
+    #[error("saving to {path}: {format} files can't hold animations, use png, apng or gif")]
+    NotAnimatable { path: String, format: &'static str },
+
+    #[error("reading frames from {path}: {message}")]
+    ReadFrames { path: String, message: String },
+
+    #[error("saving to {path}: the file already exists, use --force to overwrite it")]
+    OutputExists { path: String },
+
+    #[error("saving image to {path}: {source}")]
+    SaveImage { path: String, source: ImageError },
+
+    #[error("creating {path}: {source}")]
+    CreateFile { path: String, source: io::Error },
+
+    #[error("writing {path}: {source}")]
+    WritePng {
+        path: String,
+        source: png::EncodingError,
+    },
+
+    #[error("rendering in tiles: {0}")]
+    Tiles(String),
+
+    #[error("composing image: {0}")]
+    Compose(String),
+
+    #[error("sweeping image: {0}")]
+    Sweep(String),
+
+    #[error("warping image: {0}")]
+    Conformal(String),
+
+    #[error("animating option: {0}")]
+    AnimateParam(String),
+
+    #[error("applying effects: {0}")]
+    PostEffects(String),
+
+    #[error("generating {name}: {source}")]
+    Generator {
+        name: String,
+        source: GeneratorError,
+    },
+
+    #[error("reading recipe from {path}: {message}")]
+    ReadRecipe { path: String, message: String },
+
+    #[error("reproducing image: {0}")]
+    Reproduce(String),
+
+    #[error("starting server on {address}: {message}")]
+    StartServer { address: String, message: String },
+
+    #[error("reading {path} for the report: {source}")]
+    Report { path: String, source: io::Error },
+
+    #[error("setting wallpaper: {0}")]
+    SetWallpaper(String),
+
+    #[error("checkpoint {path}: {message}")]
+    Checkpoint { path: String, message: String },
+
+    #[error(
+        "making the image takes about {needed} of memory, more than the {budget} budget. \
+         {suggestion}, raise --memory-budget or use --yes to make it anyway"
+    )]
+    MemoryBudget {
+        needed: String,
+        budget: String,
+        suggestion: &'static str,
+    },
+
+    #[error("cancelled before the image was finished")]
+    Cancelled,
+
+    #[error("running explorer: {0}")]
+    Explorer(io::Error),
+
+    #[error("printing presets: {0}")]
+    PrintPresets(io::Error),
+
+    #[error("setting up threads: {0}")]
+    Threads(#[from] rayon::ThreadPoolBuildError),
+
+    #[cfg(feature = "gui")]
+    #[error("opening gui: only still images are supported")]
+    GuiAnimation,
+
+    #[cfg(feature = "gui")]
+    #[error("running gui: {0}")]
+    Gui(eframe::Error),
+}
+
+impl Error {
+    pub fn exit_code(&self) -> ExitCode {
+        let code = match self {
+            Error::UnknownFormat { .. }
+            | Error::NotAnimatable { .. }
+            | Error::Reproduce(_)
+            | Error::Tiles(_)
+            | Error::Compose(_)
+            | Error::Sweep(_)
+            | Error::Conformal(_)
+            | Error::AnimateParam(_)
+            | Error::PostEffects(_)
+            | Error::Generator {
+                source: GeneratorError::InvalidOptions(_),
+                ..
+            } => 2,
+            Error::OpenImage { .. }
+            | Error::ReadFrames { .. }
+            | Error::ReadRecipe { .. }
+            | Error::Generator {
+                source: GeneratorError::ReadFile { .. },
+                ..
+            } => 3,
+            Error::SaveImage { .. } | Error::CreateFile { .. } | Error::WritePng { .. } => 4,
+            Error::StartServer { .. } => 5,
+            Error::OutputExists { .. } => 6,
+            Error::MemoryBudget { .. } => 7,
+            // what shells use for programs stopped by Ctrl-C
+            Error::Cancelled => 130,
+            Error::Explorer(_)
+            | Error::PrintPresets(_)
+            | Error::Threads(_)
+            | Error::Generator { .. }
+            | Error::SetWallpaper(_)
+            | Error::Checkpoint { .. }
+            | Error::Report { .. } => 1,
+            #[cfg(feature = "gui")]
+            Error::GuiAnimation | Error::Gui(_) => 1,
+        };
+
+        ExitCode::from(code)
+    }
+}
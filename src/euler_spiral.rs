@@ -0,0 +1,275 @@
+//! Module for drawing Euler spirals, also known as clothoids or Cornu spirals
+//!
+//! An Euler spiral is the curve whose curvature goes up at the same rate as its length,
+//! so it starts out straight and winds tighter and tighter into a point at each end.
+//! The points along it are the Fresnel integrals, which don't have a closed form so
+//! they're added up a small step at a time. Roads and railways use them to ease into
+//! bends without a sudden jerk.
+//! See <https://en.wikipedia.org/wiki/Euler_spiral> for more info
+
+use std::f64::consts::PI;
+
+use csscolorparser::Color;
+use image::{Rgba, RgbaImage};
+use imageproc::drawing::draw_filled_circle_mut;
+
+use crate::{
+    generator::{Generated, Generator, GeneratorError},
+    memory::MemoryEstimate,
+    palette::interpolate_color,
+    parse_color, parse_size,
+};
+
+/// The space around the spirals, as a fraction of the image size
+const MARGIN: f64 = 0.08;
+
+/// The furthest along the spiral can go, which is wound in far tighter than a pixel by
+/// then, and keeps the number of points it's drawn with bounded
+const MAX_LENGTH: f64 = 100.0;
+
+pub struct EulerSpiralOptions {
+    /// How far along the spiral to go on each side of the middle
+    length: f64,
+    curve_num: u32,
+    /// How many degrees each curve is turned from the one before
+    twist: f64,
+    /// How big the last curve is compared to the first, with the ones between shrinking
+    /// evenly
+    min_scale: f64,
+    color: Color,
+    /// The colour at the far end of each curve, fading from color along the way
+    end_color: Color,
+    thickness: u32,
+    background_color: Color,
+    size: u32,
+}
+
+impl EulerSpiralOptions {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        length: f64,
+        curve_num: u32,
+        twist: f64,
+        min_scale: f64,
+        color: Color,
+        end_color: Color,
+        thickness: u32,
+        background_color: Color,
+        size: u32,
+    ) -> Self {
+        Self {
+            length,
+            curve_num,
+            twist,
+            min_scale,
+            color,
+            end_color,
+            thickness,
+            background_color,
+            size,
+        }
+    }
+}
+
+/// Gets the points along the spiral from the middle out to the length, a step apart.
+/// These are the normalised Fresnel integrals, the integrals of cos(πs²/2) and
+/// sin(πs²/2), which wind into (0.5, 0.5)
+pub fn get_clothoid_points(length: f64, step: f64) -> Vec<(f64, f64)> {
+    let step_num = (length / step).ceil() as usize;
+    let step = length / step_num as f64;
+    let mut point = (0.0, 0.0);
+    let mut points = Vec::with_capacity(step_num + 1);
+    points.push(point);
+    for index in 0..step_num {
+        // the angle halfway through the step is a lot more accurate than at the start
+        let distance = (index as f64 + 0.5) * step;
+        let angle = PI * distance * distance / 2.0;
+        point = (point.0 + angle.cos() * step, point.1 + angle.sin() * step);
+        points.push(point);
+    }
+
+    points
+}
+
+pub fn generate_euler_spiral_image(options: EulerSpiralOptions) -> RgbaImage {
+    let size = options.size;
+    let mut image = RgbaImage::from_pixel(size, size, Rgba(options.background_color.to_rgba8()));
+
+    // about half a pixel apart, so the dots along it join up into a line
+    let half = get_clothoid_points(options.length, 1.0 / size as f64);
+    // the spiral is the same turned half way round on the other side of the middle
+    let spiral: Vec<(f64, f64)> = half
+        .iter()
+        .skip(1)
+        .rev()
+        .map(|&(x, y)| (-x, -y))
+        .chain(half.iter().copied())
+        .collect();
+
+    let curves: Vec<Vec<(f64, f64)>> = (0..options.curve_num)
+        .map(|index| {
+            let amount = match options.curve_num {
+                1 => 0.0,
+                curve_num => index as f64 / (curve_num - 1) as f64,
+            };
+            let scale = 1.0 + (options.min_scale - 1.0) * amount;
+            let (sin, cos) = (index as f64 * options.twist).to_radians().sin_cos();
+            spiral
+                .iter()
+                .map(|&(x, y)| (scale * (x * cos - y * sin), scale * (x * sin + y * cos)))
+                .collect()
+        })
+        .collect();
+
+    // fits the furthest point from the middle, so the middle of the spiral stays in the
+    // middle of the image
+    let furthest = curves
+        .iter()
+        .flatten()
+        .map(|&(x, y)| x.abs().max(y.abs()))
+        .fold(f64::EPSILON, f64::max);
+    let centre = size as f64 / 2.0;
+    let scale = centre * (1.0 - 2.0 * MARGIN) / furthest;
+
+    let (start_color, end_color) = (options.color.to_rgba8(), options.end_color.to_rgba8());
+    let radius = options.thickness as i32 / 2;
+    for curve in curves.iter() {
+        for (index, &(x, y)) in curve.iter().enumerate() {
+            let amount = index as f64 / (curve.len() - 1).max(1) as f64;
+            let color = Rgba(interpolate_color(end_color, start_color, amount));
+            // the y axis goes up
+            let position = (
+                (centre + x * scale).round() as i32,
+                (centre - y * scale).round() as i32,
+            );
+            draw_filled_circle_mut(&mut image, position, radius, color);
+        }
+    }
+
+    image
+}
+
+/// Parses how far along the spiral to go, which has to be more than 0 and at most
+/// MAX_LENGTH
+fn parse_length(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(length) if length > 0.0 && length <= MAX_LENGTH => Ok(length),
+        Ok(_) => Err(format!("must be more than 0 and at most {}", MAX_LENGTH)),
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+#[derive(clap::Args, Debug)]
+pub struct EulerSpiralArgs {
+    /// How far along the spiral to go on each side of the middle, it's wound most of the
+    /// way in by 4
+    #[arg(short, long, default_value = "4.0", value_parser = parse_length)]
+    length: f64,
+
+    /// How many spirals to draw, each turned and shrunk from the one before
+    #[arg(short = 'n', long, default_value = "1", value_parser = parse_size)]
+    curves: u32,
+
+    /// How many degrees each spiral is turned from the one before
+    #[arg(short, long, default_value = "0.0", allow_negative_numbers = true)]
+    twist: f64,
+
+    /// How big the last spiral is compared to the first
+    #[arg(long, default_value = "1.0")]
+    min_scale: f64,
+
+    /// The colour at the start of each spiral
+    #[arg(short, long, default_value = "deepskyblue", value_parser = parse_color)]
+    color: Color,
+
+    /// The colour at the end of each spiral, fading from the first colour along its length
+    #[arg(short, long, default_value = "deeppink", value_parser = parse_color)]
+    end_color: Color,
+
+    /// How thick the spirals are in pixels
+    #[arg(long, default_value = "3", value_parser = parse_size)]
+    thickness: u32,
+
+    /// The colour behind the spirals, which can be transparent
+    #[arg(short, long, alias = "background", default_value = "black", value_parser = parse_color)]
+    background_color: Color,
+
+    #[arg(short, long, default_value = "1000", value_parser = parse_size)]
+    size: u32,
+}
+
+pub struct EulerSpiralGenerator;
+
+impl Generator for EulerSpiralGenerator {
+    type Args = EulerSpiralArgs;
+
+    fn name(&self) -> &'static str {
+        "euler-spiral"
+    }
+
+    fn is_animated(&self, _args: &EulerSpiralArgs) -> bool {
+        false
+    }
+
+    fn estimate_memory(&self, args: &EulerSpiralArgs) -> Option<MemoryEstimate> {
+        // the half of the spiral, both halves joined and a copy of that for each curve,
+        // with the points half a pixel apart
+        let half_points = (args.length * args.size as f64).ceil() as u64 + 1;
+        let spiral_points = half_points * 2 - 1;
+        let points =
+            half_points.saturating_add(spiral_points.saturating_mul(args.curves as u64 + 1));
+        let point_bytes = std::mem::size_of::<(f64, f64)>() as u64;
+        Some(
+            MemoryEstimate::still(args.size, args.size)
+                .with_working(points.saturating_mul(point_bytes)),
+        )
+    }
+
+    fn generate(&self, args: EulerSpiralArgs) -> Result<Generated, GeneratorError> {
+        if args.min_scale <= 0.0 {
+            return Err(GeneratorError::InvalidOptions(
+                "the min scale has to be more than 0".to_string(),
+            ));
+        }
+
+        Ok(Generated::Still(generate_euler_spiral_image(
+            EulerSpiralOptions::new(
+                args.length,
+                args.curves,
+                args.twist,
+                args.min_scale,
+                args.color,
+                args.end_color,
+                args.thickness,
+                args.background_color,
+                args.size,
+            ),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fresnel_integrals() {
+        // C(1) and S(1)
+        let &(x, y) = get_clothoid_points(1.0, 0.001).last().unwrap();
+        assert!((x - 0.779_893_4).abs() < 1e-6);
+        assert!((y - 0.438_259_1).abs() < 1e-6);
+
+        // it winds in towards (0.5, 0.5), going round about 1 / πs away from it
+        let &(x, y) = get_clothoid_points(20.0, 0.001).last().unwrap();
+        assert!((f64::hypot(x - 0.5, y - 0.5) - 1.0 / (PI * 20.0)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_parse_length() {
+        assert_eq!(parse_length("4"), Ok(4.0));
+        assert!(parse_length("0").is_err());
+        assert!(parse_length("1e9").is_err());
+        assert!(parse_length("inf").is_err());
+        assert!(parse_length("NaN").is_err());
+    }
+}
@@ -0,0 +1,208 @@
+//! An interactive explorer for the Mandelbrot and Julia sets in the terminal
+//!
+//! Each terminal cell shows two pixels using the upper half block character, with the
+//! top pixel as the foreground colour and the bottom pixel as the background colour
+
+use std::io;
+
+use image::{DynamicImage, RgbaImage};
+use ratatui::{
+    DefaultTerminal, Frame,
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::Rect,
+    style::{Color as TerminalColor, Style},
+    widgets::Paragraph,
+};
+
+use fun_images::{
+    mandelbrot::{FractalSet, MandelbrotImageOptions, ViewPort, render_fractal},
+    post::{PostArgs, apply_post_processing},
+};
+
+/// How far the arrow keys move the view, as a fraction of its size
+const PAN_AMOUNT: f64 = 0.1;
+const ZOOM_FACTOR: f64 = 1.5;
+const HELP_TEXT: &str = "arrows pan, +/- zoom, j toggle julia, r reset, e export, q quit";
+
+pub struct ExploreOptions {
+    image_options: MandelbrotImageOptions,
+    export_width: u32,
+    export_height: u32,
+    output: String,
+}
+
+impl ExploreOptions {
+    pub fn new(
+        image_options: MandelbrotImageOptions,
+        export_width: u32,
+        export_height: u32,
+        output: String,
+    ) -> Self {
+        Self {
+            image_options,
+            export_width,
+            export_height,
+            output,
+        }
+    }
+}
+
+struct ExplorerState {
+    set: FractalSet,
+    viewport: ViewPort,
+    /// Shown in place of the help text after something happens, like an export
+    status: Option<String>,
+}
+
+pub fn run_explorer(options: ExploreOptions, post_args: &PostArgs) -> io::Result<()> {
+    let mut terminal = ratatui::init();
+    let result = explore(&mut terminal, &options, post_args);
+    ratatui::restore();
+    result
+}
+
+fn explore(
+    terminal: &mut DefaultTerminal,
+    options: &ExploreOptions,
+    post_args: &PostArgs,
+) -> io::Result<()> {
+    let mut state = ExplorerState {
+        set: FractalSet::Mandelbrot,
+        viewport: ViewPort::normal_mandelbrot(),
+        status: None,
+    };
+
+    loop {
+        terminal.draw(|frame| draw(frame, options, &mut state))?;
+
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        state.status = None;
+
+        match key.code {
+            KeyCode::Left => state.viewport.pan(-PAN_AMOUNT, 0.0),
+            KeyCode::Right => state.viewport.pan(PAN_AMOUNT, 0.0),
+            KeyCode::Up => state.viewport.pan(0.0, -PAN_AMOUNT),
+            KeyCode::Down => state.viewport.pan(0.0, PAN_AMOUNT),
+            KeyCode::Char('+') | KeyCode::Char('=') => state.viewport.zoom(ZOOM_FACTOR),
+            KeyCode::Char('-') | KeyCode::Char('_') => state.viewport.zoom(1.0 / ZOOM_FACTOR),
+            KeyCode::Char('j') => {
+                // the julia set for the point in the middle of the view looks similar to
+                // the mandelbrot set around that point
+                (state.set, state.viewport) = match state.set {
+                    FractalSet::Mandelbrot => (
+                        FractalSet::Julia(state.viewport.centre()),
+                        ViewPort::normal_julia(),
+                    ),
+                    _ => (FractalSet::Mandelbrot, ViewPort::normal_mandelbrot()),
+                };
+            }
+            KeyCode::Char('r') => {
+                state.viewport = match state.set {
+                    FractalSet::Julia(_) => ViewPort::normal_julia(),
+                    _ => ViewPort::normal_mandelbrot(),
+                };
+            }
+            KeyCode::Char('e') => {
+                state.status = Some(export(options, post_args, &state));
+            }
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            _ => {}
+        }
+    }
+}
+
+fn draw(frame: &mut Frame, options: &ExploreOptions, state: &mut ExplorerState) {
+    let area = frame.area();
+    let fractal_area = Rect {
+        height: area.height.saturating_sub(1),
+        ..area
+    };
+    let status_area = Rect {
+        y: area.y + fractal_area.height,
+        height: area.height - fractal_area.height,
+        ..area
+    };
+
+    // two pixels per cell vertically, which makes the pixels about square
+    let width = fractal_area.width as u32;
+    let height = fractal_area.height as u32 * 2;
+    if width > 0 && height > 0 {
+        state.viewport.fit_to_size(width, height);
+        let image = render_fractal(
+            &options.image_options,
+            state.set,
+            &state.viewport,
+            width,
+            height,
+        );
+        draw_image(frame, fractal_area, &image);
+    }
+
+    let centre = state.viewport.centre();
+    let set_name = match state.set {
+        FractalSet::Mandelbrot => "mandelbrot".to_string(),
+        FractalSet::Julia(constant) => format!("julia {:.4}{:+.4}i", constant.re, constant.im),
+        FractalSet::Phoenix { c, p } => format!(
+            "phoenix {:.4}{:+.4}i, p {:.4}{:+.4}i",
+            c.re, c.im, p.re, p.im
+        ),
+        FractalSet::Magnet(magnet_type) => format!("magnet {:?}", magnet_type).to_lowercase(),
+    };
+    let status = format!(
+        "{} | centre {:.6}{:+.6}i, width {:.3e} | {}",
+        set_name,
+        centre.re,
+        centre.im,
+        state.viewport.real_diameter(),
+        state.status.as_deref().unwrap_or(HELP_TEXT),
+    );
+    frame.render_widget(Paragraph::new(status), status_area);
+}
+
+fn draw_image(frame: &mut Frame, area: Rect, image: &RgbaImage) {
+    let to_terminal_color = |x: u32, y: u32| {
+        let [r, g, b, _] = image[(x, y)].0;
+        TerminalColor::Rgb(r, g, b)
+    };
+
+    let buffer = frame.buffer_mut();
+    for row in 0..area.height {
+        for column in 0..area.width {
+            let (x, y) = (column as u32, row as u32 * 2);
+            buffer[(area.x + column, area.y + row)]
+                .set_char('▀')
+                .set_style(
+                    Style::new()
+                        .fg(to_terminal_color(x, y))
+                        .bg(to_terminal_color(x, y + 1)),
+                );
+        }
+    }
+}
+
+/// Renders the current view at full size, returning a message saying how it went
+fn export(options: &ExploreOptions, post_args: &PostArgs, state: &ExplorerState) -> String {
+    let mut viewport = state.viewport;
+    viewport.fit_to_size(options.export_width, options.export_height);
+    let image = render_fractal(
+        &options.image_options,
+        state.set,
+        &viewport,
+        options.export_width,
+        options.export_height,
+    );
+    let image = match apply_post_processing(image, post_args) {
+        Ok(image) => DynamicImage::ImageRgba8(image),
+        Err(message) => return format!("Error applying effects: {}", message),
+    };
+
+    match image.save(&options.output) {
+        Ok(()) => format!("Saved image to {}", options.output),
+        Err(image_error) => format!("Error saving image: {}", image_error),
+    }
+}
@@ -15,12 +15,27 @@ use imageproc::{
     point::Point,
 };
 
+use crate::{
+    generator::{Generated, Generator, GeneratorError},
+    memory::MemoryEstimate,
+    parse_color,
+};
+
 const SIZE: u32 = 1024;
 const LINE_THICKNESS: i32 = 6;
 const CIRCLE_SIZE: i32 = 20;
 
-pub fn generate_farey_sunburst(color: Color, n: i32, fill_color: Option<Color>) -> DynamicImage {
-    let mut image = RgbaImage::new(SIZE, SIZE);
+/// The biggest n the sunburst fits in the image for, past which it's scaled down to
+/// nothing and then turned inside out
+pub const MAX_N: i32 = 24;
+
+pub fn generate_farey_sunburst(
+    color: Color,
+    n: i32,
+    fill_color: Option<Color>,
+    background_color: Color,
+) -> DynamicImage {
+    let mut image = RgbaImage::from_pixel(SIZE, SIZE, Rgba(background_color.to_rgba8()));
 
     let scale = SIZE as i32 / n / 2 - 20;
 
@@ -115,7 +130,8 @@ where
     }
 }
 
-fn draw_thick_line<C>(
+/// Draws a line as a polygon so it can be thicker than a pixel
+pub(crate) fn draw_thick_line<C>(
     canvas: &mut C,
     color: C::Pixel,
     point1: Point<i32>,
@@ -220,6 +236,7 @@ impl FareyIterator {
 
 impl Iterator for FareyIterator {
     type Item = Fraction;
+    #[allow(clippy::needless_return)]
     fn next(&mut self) -> Option<Self::Item> {
         if let Some(last_frac) = self.last_fraction {
             if !self.descending && last_frac.0 == 1 && last_frac.1 == 1 {
@@ -269,6 +286,49 @@ impl Iterator for FareyIterator {
     }
 }
 
+#[derive(clap::Args, Debug)]
+pub struct FareyArgs {
+    #[arg(short, long, default_value = "black", value_parser = parse_color)]
+    color: Color,
+
+    /// The biggest denominator of the fractions, which makes the sunburst smaller as
+    /// it goes up
+    #[arg(long, default_value = "6", value_parser = clap::value_parser!(i32).range(1..=MAX_N as i64))]
+    n: i32,
+
+    #[arg(short, long, value_parser = parse_color)]
+    fill_color: Option<Color>,
+
+    /// The colour behind the image, which can be transparent
+    #[arg(short, long, alias = "background", default_value = "transparent", value_parser = parse_color)]
+    background_color: Color,
+}
+
+pub struct FareyGenerator;
+
+impl Generator for FareyGenerator {
+    type Args = FareyArgs;
+
+    fn name(&self) -> &'static str {
+        "farey"
+    }
+
+    fn is_animated(&self, _args: &FareyArgs) -> bool {
+        false
+    }
+
+    fn estimate_memory(&self, _args: &FareyArgs) -> Option<MemoryEstimate> {
+        Some(MemoryEstimate::still(SIZE, SIZE))
+    }
+
+    fn generate(&self, args: FareyArgs) -> Result<Generated, GeneratorError> {
+        Ok(Generated::Still(
+            generate_farey_sunburst(args.color, args.n, args.fill_color, args.background_color)
+                .into_rgba8(),
+        ))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,161 @@
+//! The old school demoscene fire effect
+//!
+//! The bottom row is seeded with random heat each frame, then every pixel takes the
+//! average of the pixels below it minus a bit of decay, so the heat rises and cools
+//! down, see <https://lodev.org/cgtutor/fire.html>
+
+use image::{Rgba, RgbaImage};
+
+use crate::{
+    cancel::is_cancelled,
+    generator::{Generated, Generator, GeneratorError},
+    memory::MemoryEstimate,
+    palette::get_gradient_color,
+    parse_size,
+};
+
+const FIRE_RAMP: &[[u8; 4]] = &[
+    [0, 0, 0, 255],
+    [120, 10, 0, 255],
+    [230, 50, 0, 255],
+    [255, 140, 0, 255],
+    [255, 220, 60, 255],
+    [255, 255, 255, 255],
+];
+
+pub struct FireOptions {
+    width: u32,
+    height: u32,
+    frame_num: u32,
+    intensity: f64,
+    seed: Option<u64>,
+}
+
+impl FireOptions {
+    pub fn new(width: u32, height: u32, frame_num: u32, intensity: f64, seed: Option<u64>) -> Self {
+        Self {
+            width,
+            height,
+            frame_num,
+            intensity,
+            seed,
+        }
+    }
+}
+
+pub fn generate_fire_images(options: FireOptions) -> Vec<RgbaImage> {
+    let FireOptions {
+        width,
+        height,
+        frame_num,
+        intensity,
+        seed,
+    } = options;
+    let mut rng = match seed {
+        Some(seed) => fastrand::Rng::with_seed(seed),
+        None => fastrand::Rng::new(),
+    };
+
+    let width = width as usize;
+    let height = height as usize;
+    // the bottom row seeds only come from this list, which repeats every loop
+    let seed_rows: Vec<Vec<f64>> = (0..frame_num)
+        .map(|_| {
+            (0..width)
+                .map(|_| if rng.f64() < intensity { 1.0 } else { 0.0 })
+                .collect()
+        })
+        .collect();
+
+    // the flames should reach around two thirds of the way up
+    let decay = 1.0 / height as f64;
+    let mut heat = vec![0.0; width * height];
+    let mut images = Vec::new();
+    // every row only depends on the rows below it in the last frame, so after running
+    // for the height of the image, the heat only depends on the seeds. Since the seeds
+    // repeat each loop, the animation will then loop seamlessly
+    let warm_up = height as u32;
+    for step in 0..warm_up + frame_num {
+        if is_cancelled() {
+            break;
+        }
+        let seed_row = &seed_rows[(step % frame_num) as usize];
+        heat[(height - 1) * width..].copy_from_slice(seed_row);
+
+        for y in 0..height - 1 {
+            for x in 0..width {
+                let below = heat[(y + 1) * width + x];
+                let below_left = heat[(y + 1) * width + (x + width - 1) % width];
+                let below_right = heat[(y + 1) * width + (x + 1) % width];
+                let two_below = if y + 2 < height {
+                    heat[(y + 2) * width + x]
+                } else {
+                    below
+                };
+
+                heat[y * width + x] =
+                    ((below + below_left + below_right + two_below) / 4.0 - decay).max(0.0);
+            }
+        }
+
+        if step >= warm_up {
+            let mut image = RgbaImage::new(width as u32, height as u32);
+            for (pixel, &heat_value) in image.pixels_mut().zip(heat.iter()) {
+                *pixel = Rgba(get_gradient_color(FIRE_RAMP, heat_value));
+            }
+            images.push(image);
+        }
+    }
+
+    images
+}
+
+#[derive(clap::Args, Debug)]
+pub struct FireArgs {
+    #[arg(long, default_value = "320", value_parser = parse_size)]
+    width: u32,
+
+    #[arg(long, default_value = "200", value_parser = parse_size)]
+    height: u32,
+
+    /// The number of frames in the loop
+    #[arg(short, long, default_value = "60", value_parser = parse_size)]
+    frames: u32,
+
+    /// The chance of each pixel on the bottom row being hot, from 0 to 1
+    #[arg(short, long, default_value = "0.6")]
+    intensity: f64,
+
+    /// The seed used for the flames, random if not given
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+pub struct FireGenerator;
+
+impl Generator for FireGenerator {
+    type Args = FireArgs;
+
+    fn name(&self) -> &'static str {
+        "fire"
+    }
+
+    fn is_animated(&self, _args: &FireArgs) -> bool {
+        true
+    }
+
+    fn estimate_memory(&self, args: &FireArgs) -> Option<MemoryEstimate> {
+        Some(MemoryEstimate::frames(args.width, args.height, args.frames))
+    }
+
+    fn generate(&self, args: FireArgs) -> Result<Generated, GeneratorError> {
+        let options = FireOptions::new(
+            args.width,
+            args.height,
+            args.frames,
+            args.intensity,
+            args.seed,
+        );
+        Ok(Generated::Frames(generate_fire_images(options)))
+    }
+}
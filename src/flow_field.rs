@@ -0,0 +1,258 @@
+//! Module for drawing flow fields
+//!
+//! Perlin noise gives an angle at every point, and particles dropped at random points
+//! follow those angles a small step at a time. Each step leaves a faint mark, so the
+//! paths that lots of particles end up sharing build up into bright streams.
+//! See <https://www.tylerxhobbs.com/words/flow-fields> for more info
+
+use std::f64::consts::PI;
+
+use csscolorparser::Color;
+use image::{Rgba, RgbaImage};
+
+use crate::{
+    PaletteType,
+    cancel::is_cancelled,
+    generator::{Generated, Generator, GeneratorError},
+    memory::MemoryEstimate,
+    palette::get_palette_color,
+    parse_color, parse_fraction, parse_size,
+    perlin::Noise,
+};
+
+pub struct FlowFieldOptions {
+    width: u32,
+    height: u32,
+    particle_num: u32,
+    /// How many steps each particle takes, unless it leaves the image first
+    step_num: u32,
+    /// How far apart in pixels the noise changes direction, bigger gives smoother flows
+    noise_scale: f64,
+    /// How many half turns the noise can turn the flow by either way
+    turbulence: f64,
+    palette: PaletteType,
+    /// How much each step covers what's under it, from 0 to 1
+    opacity: f64,
+    background_color: Color,
+    seed: Option<u64>,
+}
+
+impl FlowFieldOptions {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        width: u32,
+        height: u32,
+        particle_num: u32,
+        step_num: u32,
+        noise_scale: f64,
+        turbulence: f64,
+        palette: PaletteType,
+        opacity: f64,
+        background_color: Color,
+        seed: Option<u64>,
+    ) -> Self {
+        Self {
+            width,
+            height,
+            particle_num,
+            step_num,
+            noise_scale,
+            turbulence,
+            palette,
+            opacity,
+            background_color,
+            seed,
+        }
+    }
+
+    /// The angle the flow goes in at a point
+    fn get_angle(&self, noise: &Noise, (x, y): (f64, f64)) -> f64 {
+        noise.get(x / self.noise_scale, y / self.noise_scale) * PI * self.turbulence
+    }
+}
+
+/// Follows the flow from a point a pixel at a time, stopping if it leaves the image
+pub fn trace_path(options: &FlowFieldOptions, noise: &Noise, start: (f64, f64)) -> Vec<(f64, f64)> {
+    follow_angles(
+        start,
+        options.step_num,
+        (options.width, options.height),
+        |point| options.get_angle(noise, point),
+    )
+}
+
+/// Follows the angles from a point a pixel at a time for up to the number of steps,
+/// stopping if it leaves the image
+pub(crate) fn follow_angles(
+    start: (f64, f64),
+    step_num: u32,
+    (width, height): (u32, u32),
+    get_angle: impl Fn((f64, f64)) -> f64,
+) -> Vec<(f64, f64)> {
+    let (width, height) = (width as f64, height as f64);
+    let mut point = start;
+    let mut path = vec![point];
+    for _ in 0..step_num {
+        let angle = get_angle(point);
+        point = (point.0 + angle.cos(), point.1 + angle.sin());
+        if point.0 < 0.0 || point.1 < 0.0 || point.0 >= width || point.1 >= height {
+            break;
+        }
+        path.push(point);
+    }
+
+    path
+}
+
+pub fn generate_flow_field_image(options: FlowFieldOptions) -> RgbaImage {
+    let mut rng = match options.seed {
+        Some(seed) => fastrand::Rng::with_seed(seed),
+        None => fastrand::Rng::new(),
+    };
+    let noise = Noise::new(&mut rng);
+    let (width, height) = (options.width, options.height);
+
+    // the colours are added up as floats so faint steps aren't lost to rounding
+    let background = options
+        .background_color
+        .to_rgba8()
+        .map(|channel| channel as f64);
+    let mut colors = vec![background; (width * height) as usize];
+    for _ in 0..options.particle_num {
+        if is_cancelled() {
+            break;
+        }
+        let start = (rng.f64() * width as f64, rng.f64() * height as f64);
+        // particles starting where the flow goes the same way get the same colour, so
+        // the colours run along the streams
+        let position = (options.get_angle(&noise, start) / (PI * options.turbulence) + 1.0) / 2.0;
+        let color =
+            get_palette_color(options.palette, position * 0.8).map(|channel| channel as f64);
+
+        for (x, y) in trace_path(&options, &noise, start) {
+            let pixel = &mut colors[y as usize * width as usize + x as usize];
+            for channel in 0..4 {
+                pixel[channel] += (color[channel] - pixel[channel]) * options.opacity;
+            }
+        }
+    }
+
+    let mut image = RgbaImage::new(width, height);
+    for (pixel, color) in image.pixels_mut().zip(colors) {
+        *pixel = Rgba(color.map(|channel| channel.round() as u8));
+    }
+
+    image
+}
+
+#[derive(clap::Args, Debug)]
+pub struct FlowFieldArgs {
+    #[arg(long, default_value = "800", value_parser = parse_size)]
+    width: u32,
+
+    #[arg(long, default_value = "800", value_parser = parse_size)]
+    height: u32,
+
+    /// How many particles are dropped into the flow
+    #[arg(short = 'n', long, default_value = "5000", value_parser = parse_size)]
+    particles: u32,
+
+    /// How many pixels each particle goes before stopping
+    #[arg(long, default_value = "300", value_parser = parse_size)]
+    steps: u32,
+
+    /// How far apart in pixels the flow changes direction, bigger gives smoother flows
+    #[arg(long, default_value = "250.0")]
+    scale: f64,
+
+    /// How far the flow can turn, in half turns either way
+    #[arg(short, long, default_value = "1.0")]
+    turbulence: f64,
+
+    #[arg(short, long, default_value = "rainbow")]
+    palette: PaletteType,
+
+    /// How much each step of a particle shows, lower needs more particles going the same
+    /// way to show up brightly
+    #[arg(long, default_value = "0.1", value_parser = parse_fraction)]
+    opacity: f64,
+
+    /// The colour behind the flows, which can be transparent
+    #[arg(short, long, alias = "background", default_value = "black", value_parser = parse_color)]
+    background_color: Color,
+
+    /// The seed used for the flow and the particles, random if not given
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+pub struct FlowFieldGenerator;
+
+impl Generator for FlowFieldGenerator {
+    type Args = FlowFieldArgs;
+
+    fn name(&self) -> &'static str {
+        "flow-field"
+    }
+
+    fn is_animated(&self, _args: &FlowFieldArgs) -> bool {
+        false
+    }
+
+    fn estimate_memory(&self, args: &FlowFieldArgs) -> Option<MemoryEstimate> {
+        Some(MemoryEstimate::still(args.width, args.height))
+    }
+
+    fn generate(&self, args: FlowFieldArgs) -> Result<Generated, GeneratorError> {
+        if args.scale <= 0.0 || args.turbulence <= 0.0 {
+            return Err(GeneratorError::InvalidOptions(
+                "the scale and turbulence have to be more than 0".to_string(),
+            ));
+        }
+
+        Ok(Generated::Still(generate_flow_field_image(
+            FlowFieldOptions::new(
+                args.width,
+                args.height,
+                args.particles,
+                args.steps,
+                args.scale,
+                args.turbulence,
+                args.palette,
+                args.opacity,
+                args.background_color,
+                args.seed,
+            ),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace_path() {
+        let options = FlowFieldOptions::new(
+            100,
+            80,
+            1,
+            500,
+            40.0,
+            1.0,
+            PaletteType::Rainbow,
+            0.1,
+            Color::new(0.0, 0.0, 0.0, 1.0),
+            None,
+        );
+        let noise = Noise::new(&mut fastrand::Rng::with_seed(2));
+        let path = trace_path(&options, &noise, (50.0, 40.0));
+        assert!(path.len() > 1 && path.len() <= 501);
+
+        for window in path.windows(2) {
+            let (point, next) = (window[0], window[1]);
+            assert!((f64::hypot(next.0 - point.0, next.1 - point.1) - 1.0).abs() < 1e-9);
+            assert!(next.0 >= 0.0 && next.1 >= 0.0 && next.0 < 100.0 && next.1 < 80.0);
+        }
+    }
+}
@@ -0,0 +1,401 @@
+//! Formulas for escape-time fractals, like `z^3 + c*sin(z)`
+//!
+//! A formula is parsed once into a tree of operations, then worked out over the
+//! complex numbers on every iteration with `z` as the current value and `c` as the
+//! constant, which is the pixel's point for Mandelbrot style sets. Other variables can
+//! be used instead, like `x` and `y` for vector fields
+
+use std::fmt;
+
+use num_complex::Complex64;
+
+/// A parsed formula ready to be worked out
+#[derive(Clone, Debug, PartialEq)]
+pub struct Formula {
+    root: Expression,
+    text: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Expression {
+    Number(Complex64),
+    /// The index of the variable in the values it's worked out with
+    Variable(usize),
+    Negate(Box<Expression>),
+    Binary(Operator, Box<Expression>, Box<Expression>),
+    Call(Function, Box<Expression>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Power,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Function {
+    Sin,
+    Cos,
+    Tan,
+    Sinh,
+    Cosh,
+    Tanh,
+    Exp,
+    Log,
+    Sqrt,
+    Abs,
+    Conj,
+    Re,
+    Im,
+}
+
+impl Function {
+    const ALL: [(&'static str, Function); 13] = [
+        ("sin", Function::Sin),
+        ("cos", Function::Cos),
+        ("tan", Function::Tan),
+        ("sinh", Function::Sinh),
+        ("cosh", Function::Cosh),
+        ("tanh", Function::Tanh),
+        ("exp", Function::Exp),
+        ("log", Function::Log),
+        ("sqrt", Function::Sqrt),
+        ("abs", Function::Abs),
+        ("conj", Function::Conj),
+        ("re", Function::Re),
+        ("im", Function::Im),
+    ];
+
+    fn apply(self, value: Complex64) -> Complex64 {
+        match self {
+            Function::Sin => value.sin(),
+            Function::Cos => value.cos(),
+            Function::Tan => value.tan(),
+            Function::Sinh => value.sinh(),
+            Function::Cosh => value.cosh(),
+            Function::Tanh => value.tanh(),
+            Function::Exp => value.exp(),
+            Function::Log => value.ln(),
+            Function::Sqrt => value.sqrt(),
+            Function::Abs => Complex64::new(value.norm(), 0.0),
+            Function::Conj => value.conj(),
+            Function::Re => Complex64::new(value.re, 0.0),
+            Function::Im => Complex64::new(value.im, 0.0),
+        }
+    }
+}
+
+impl Formula {
+    /// Parses a formula using z, c, i, pi, e, numbers, + - * / ^, brackets and the
+    /// functions sin, cos, tan, sinh, cosh, tanh, exp, log, sqrt, abs, conj, re and im
+    pub fn parse(text: &str) -> Result<Self, String> {
+        Self::parse_with_variables(text, &["z", "c"])
+    }
+
+    /// Parses a formula with its own variables rather than z and c, given to
+    /// [`Formula::evaluate_with`] in the same order
+    pub fn parse_with_variables(text: &str, variables: &[&str]) -> Result<Self, String> {
+        let tokens = tokenise(text)?;
+        let mut parser = Parser {
+            tokens: &tokens,
+            position: 0,
+            variables,
+        };
+        let root = parser.parse_sum()?;
+        if let Some(token) = parser.peek() {
+            return Err(format!("unexpected {} in the formula", token));
+        }
+
+        Ok(Self {
+            root,
+            text: text.trim().to_string(),
+        })
+    }
+
+    /// Works out the next value of z
+    pub fn evaluate(&self, z: Complex64, c: Complex64) -> Complex64 {
+        evaluate(&self.root, &[z, c])
+    }
+
+    /// Works out the formula with the values of the variables it was parsed with
+    pub fn evaluate_with(&self, values: &[Complex64]) -> Complex64 {
+        evaluate(&self.root, values)
+    }
+}
+
+impl fmt::Display for Formula {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(&self.text)
+    }
+}
+
+fn evaluate(expression: &Expression, values: &[Complex64]) -> Complex64 {
+    match expression {
+        Expression::Number(number) => *number,
+        Expression::Variable(index) => values[*index],
+        Expression::Negate(inner) => -evaluate(inner, values),
+        Expression::Binary(operator, left, right) => {
+            let left = evaluate(left, values);
+            // whole number powers are the common case and much faster than powc
+            if let (Operator::Power, Expression::Number(power)) = (operator, right.as_ref())
+                && power.im == 0.0
+                && power.re.fract() == 0.0
+                && power.re.abs() <= i32::MAX as f64
+            {
+                return left.powi(power.re as i32);
+            }
+            let right = evaluate(right, values);
+            match operator {
+                Operator::Add => left + right,
+                Operator::Subtract => left - right,
+                Operator::Multiply => left * right,
+                Operator::Divide => left / right,
+                Operator::Power => left.powc(right),
+            }
+        }
+        Expression::Call(function, argument) => function.apply(evaluate(argument, values)),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Operator(char),
+    Open,
+    Close,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Number(number) => write!(formatter, "number {}", number),
+            Token::Name(name) => write!(formatter, "\"{}\"", name),
+            Token::Operator(operator) => write!(formatter, "\"{}\"", operator),
+            Token::Open => formatter.write_str("\"(\""),
+            Token::Close => formatter.write_str("\")\""),
+        }
+    }
+}
+
+fn tokenise(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut characters = text.chars().peekable();
+
+    while let Some(&character) = characters.peek() {
+        if character.is_whitespace() {
+            characters.next();
+        } else if character.is_ascii_digit() || character == '.' {
+            let mut number = String::new();
+            while let Some(&digit) = characters
+                .peek()
+                .filter(|c| c.is_ascii_digit() || **c == '.')
+            {
+                number.push(digit);
+                characters.next();
+            }
+            let number = number
+                .parse()
+                .map_err(|_| format!("{} isn't a number", number))?;
+            tokens.push(Token::Number(number));
+        } else if character.is_ascii_alphabetic() {
+            let mut name = String::new();
+            while let Some(&letter) = characters.peek().filter(|c| c.is_ascii_alphanumeric()) {
+                name.push(letter);
+                characters.next();
+            }
+            tokens.push(Token::Name(name.to_lowercase()));
+        } else {
+            tokens.push(match character {
+                '+' | '-' | '*' | '/' | '^' => Token::Operator(character),
+                '(' => Token::Open,
+                ')' => Token::Close,
+                _ => return Err(format!("unexpected \"{}\" in the formula", character)),
+            });
+            characters.next();
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// A recursive descent parser, with a function for each level of precedence
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+    variables: &'a [&'a str],
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<&Token> {
+        self.position += 1;
+        self.tokens.get(self.position - 1)
+    }
+
+    fn parse_sum(&mut self) -> Result<Expression, String> {
+        let mut expression = self.parse_product()?;
+        while let Some(Token::Operator(operator @ ('+' | '-'))) = self.peek() {
+            let operator = match operator {
+                '+' => Operator::Add,
+                _ => Operator::Subtract,
+            };
+            self.position += 1;
+            let right = self.parse_product()?;
+            expression = Expression::Binary(operator, Box::new(expression), Box::new(right));
+        }
+
+        Ok(expression)
+    }
+
+    fn parse_product(&mut self) -> Result<Expression, String> {
+        let mut expression = self.parse_unary()?;
+        loop {
+            let operator = match self.peek() {
+                Some(Token::Operator('*')) => Operator::Multiply,
+                Some(Token::Operator('/')) => Operator::Divide,
+                // a number next to something else multiplies, like 2z or 3sin(z)
+                Some(Token::Name(_) | Token::Open)
+                    if matches!(expression, Expression::Number(_)) =>
+                {
+                    let right = self.parse_unary()?;
+                    expression = Expression::Binary(
+                        Operator::Multiply,
+                        Box::new(expression),
+                        Box::new(right),
+                    );
+                    continue;
+                }
+                _ => break,
+            };
+            self.position += 1;
+            let right = self.parse_unary()?;
+            expression = Expression::Binary(operator, Box::new(expression), Box::new(right));
+        }
+
+        Ok(expression)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expression, String> {
+        match self.peek() {
+            Some(Token::Operator('-')) => {
+                self.position += 1;
+                Ok(Expression::Negate(Box::new(self.parse_unary()?)))
+            }
+            Some(Token::Operator('+')) => {
+                self.position += 1;
+                self.parse_unary()
+            }
+            _ => self.parse_power(),
+        }
+    }
+
+    fn parse_power(&mut self) -> Result<Expression, String> {
+        let base = self.parse_atom()?;
+        if let Some(Token::Operator('^')) = self.peek() {
+            self.position += 1;
+            // powers go from right to left, so z^2^3 is z^(2^3)
+            let power = self.parse_unary()?;
+            return Ok(Expression::Binary(
+                Operator::Power,
+                Box::new(base),
+                Box::new(power),
+            ));
+        }
+
+        Ok(base)
+    }
+
+    fn parse_atom(&mut self) -> Result<Expression, String> {
+        match self.next().cloned() {
+            Some(Token::Number(number)) => {
+                // a number straight before i is imaginary, like 0.5i
+                if let Some(Token::Name(name)) = self.peek()
+                    && name == "i"
+                {
+                    self.position += 1;
+                    return Ok(Expression::Number(Complex64::new(0.0, number)));
+                }
+                Ok(Expression::Number(Complex64::new(number, 0.0)))
+            }
+            Some(Token::Name(name)) => match name.as_str() {
+                _ if let Some(index) =
+                    self.variables.iter().position(|variable| *variable == name) =>
+                {
+                    Ok(Expression::Variable(index))
+                }
+                "i" => Ok(Expression::Number(Complex64::i())),
+                "pi" => Ok(Expression::Number(Complex64::new(
+                    std::f64::consts::PI,
+                    0.0,
+                ))),
+                "e" => Ok(Expression::Number(Complex64::new(std::f64::consts::E, 0.0))),
+                _ => {
+                    let function = Function::ALL
+                        .iter()
+                        .find(|(function_name, _)| *function_name == name)
+                        .map(|(_, function)| *function)
+                        .ok_or_else(|| format!("unknown name \"{}\" in the formula", name))?;
+                    if self.next() != Some(&Token::Open) {
+                        return Err(format!("expected \"(\" after {}", name));
+                    }
+                    let argument = self.parse_sum()?;
+                    self.expect_close()?;
+                    Ok(Expression::Call(function, Box::new(argument)))
+                }
+            },
+            Some(Token::Open) => {
+                let expression = self.parse_sum()?;
+                self.expect_close()?;
+                Ok(expression)
+            }
+            Some(token) => Err(format!("unexpected {} in the formula", token)),
+            None => Err("the formula ended too soon".to_string()),
+        }
+    }
+
+    fn expect_close(&mut self) -> Result<(), String> {
+        match self.next() {
+            Some(Token::Close) => Ok(()),
+            _ => Err("a bracket is never closed in the formula".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formulas() {
+        let z = Complex64::new(0.5, -1.0);
+        let c = Complex64::new(-0.3, 0.2);
+        let check = |text: &str, expected: Complex64| {
+            let value = Formula::parse(text).unwrap().evaluate(z, c);
+            assert!((value - expected).norm() < 1e-12, "{}: {}", text, value);
+        };
+
+        check("z^2 + c", z * z + c);
+        check("z^3 + c*sin(z)", z * z * z + c * z.sin());
+        check("-z^2", -(z * z));
+        check("2z - 0.5i", 2.0 * z - Complex64::new(0.0, 0.5));
+        check("(z + 1) / (z - 1)", (z + 1.0) / (z - 1.0));
+        check("z^0.5", z.powc(Complex64::new(0.5, 0.0)));
+        check("exp(conj(z)) + 2^2^3 * c", z.conj().exp() + 256.0 * c);
+
+        for text in ["", "z +", "(z", "z)", "foo(z)", "sin z", "z # c", "1.2.3"] {
+            assert!(Formula::parse(text).is_err(), "{}", text);
+        }
+
+        let formula = Formula::parse_with_variables("y - x^2", &["x", "y"]).unwrap();
+        let value = formula.evaluate_with(&[Complex64::new(3.0, 0.0), Complex64::new(1.0, 0.0)]);
+        assert_eq!(value, Complex64::new(-8.0, 0.0));
+        assert!(Formula::parse_with_variables("z + x", &["x", "y"]).is_err());
+    }
+}
@@ -0,0 +1,199 @@
+//! Rendering every image type with its default options, to show what can be made
+//!
+//! Each image type gets a thumbnail saved as a PNG with its recipe, so reproduce can
+//! make it again at full size. The thumbnails are also put together into a montage
+//! with their names under them, and listed in an index.html page
+
+use std::{fmt::Write, fs, path::Path, time::Instant};
+
+use clap::{CommandFactory, FromArgMatches};
+use image::{DynamicImage, Rgba, RgbaImage, imageops};
+use log::{debug, info};
+use rayon::prelude::*;
+
+use fun_images::{
+    Fit,
+    post::resize,
+    text::{draw_text_on_image, get_text_size},
+};
+
+use crate::{
+    Args, ImageFormat, ImageType,
+    error::Error,
+    generate_animated_images, generate_static_image,
+    output::{FileFormat, get_output_format, save_image},
+    recipe::get_recipe,
+};
+
+const LABEL_FONT_SIZE: f32 = 16.0;
+const SPACING: u32 = 8;
+
+/// A thumbnail of an image type, with the recipe for the full size image
+struct Thumbnail {
+    name: String,
+    image: RgbaImage,
+    recipe: Vec<String>,
+}
+
+/// Renders the gallery into the directory, making it if needed
+pub fn render_gallery(out: &str, thumbnail_size: u32, force: bool) -> Result<(), Error> {
+    fs::create_dir_all(out).map_err(|source| Error::CreateFile {
+        path: out.to_string(),
+        source,
+    })?;
+    let montage_path = format!("{}/gallery.png", out);
+    let index_path = format!("{}/index.html", out);
+    let image_types = get_default_image_types();
+    // checked before rendering so an existing gallery isn't half overwritten
+    get_output_format(&montage_path, false, force)?;
+    for (name, _) in image_types.iter() {
+        get_output_format(&get_thumbnail_path(out, name), false, force)?;
+    }
+    if !force && Path::new(&index_path).exists() {
+        return Err(Error::OutputExists { path: index_path });
+    }
+
+    let start = Instant::now();
+    let thumbnails = image_types
+        .into_par_iter()
+        .map(|(name, args)| {
+            let recipe = args.recipe.clone();
+            let image = flatten_image(&render_image_type(args.image_type)?);
+            debug!("Rendered {}", name);
+            Ok(Thumbnail {
+                name,
+                image: resize(&image, (thumbnail_size, thumbnail_size), Fit::Contain),
+                recipe,
+            })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    info!(
+        "Generated {} images in {}ms",
+        thumbnails.len(),
+        start.elapsed().as_millis()
+    );
+
+    for thumbnail in thumbnails.iter() {
+        save_image(
+            &DynamicImage::ImageRgba8(thumbnail.image.clone()),
+            &get_thumbnail_path(out, &thumbnail.name),
+            FileFormat::Png,
+            &thumbnail.recipe,
+        )?;
+    }
+    let montage = make_montage(&thumbnails, thumbnail_size);
+    save_image(
+        &DynamicImage::ImageRgba8(montage),
+        &montage_path,
+        FileFormat::Png,
+        &[],
+    )?;
+    fs::write(&index_path, make_index(&thumbnails)).map_err(|source| Error::CreateFile {
+        path: index_path.clone(),
+        source,
+    })?;
+    info!("Saved gallery to {}", index_path);
+
+    Ok(())
+}
+
+/// Gets the args for every image type that can be made with no options given, which
+/// leaves out the ones needing an input like compose and script
+fn get_default_image_types() -> Vec<(String, Args)> {
+    let command = Args::command();
+    command
+        .get_subcommands()
+        .filter_map(|subcommand| {
+            let name = subcommand.get_name();
+            let matches = Args::command()
+                .try_get_matches_from(["fun-images", name])
+                .ok()?;
+            let mut args = Args::from_arg_matches(&matches).ok()?;
+            match args.image_type.get_format() {
+                ImageFormat::Static | ImageFormat::Animated => {
+                    args.recipe = get_recipe(&matches);
+                    Some((name.to_string(), args))
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Renders the image, using the middle frame of animations as they often start blank
+pub fn render_image_type(image_type: ImageType) -> Result<RgbaImage, Error> {
+    match image_type.get_format() {
+        ImageFormat::Animated => {
+            let mut frames = generate_animated_images(image_type)?;
+            Ok(frames.swap_remove(frames.len() / 2))
+        }
+        _ => Ok(generate_static_image(image_type)?.into_rgba8()),
+    }
+}
+
+/// Puts a transparent image on white so it shows up against the dark background
+pub fn flatten_image(image: &RgbaImage) -> RgbaImage {
+    let mut flattened = RgbaImage::from_pixel(image.width(), image.height(), Rgba([255; 4]));
+    imageops::overlay(&mut flattened, image, 0, 0);
+    flattened
+}
+
+/// Puts the thumbnails in a grid on a dark background, with each name underneath
+fn make_montage(thumbnails: &[Thumbnail], thumbnail_size: u32) -> RgbaImage {
+    let columns = (thumbnails.len() as f64).sqrt().ceil().max(1.0) as u32;
+    let rows = (thumbnails.len() as u32).div_ceil(columns);
+    let label_height = LABEL_FONT_SIZE.ceil() as u32 + SPACING;
+    let cell_width = thumbnail_size + SPACING;
+    let cell_height = thumbnail_size + label_height + SPACING;
+
+    let mut montage = RgbaImage::from_pixel(
+        columns * cell_width + SPACING,
+        rows * cell_height + SPACING,
+        Rgba([24, 24, 28, 255]),
+    );
+    for (index, thumbnail) in thumbnails.iter().enumerate() {
+        let x = SPACING + (index as u32 % columns) * cell_width;
+        let y = SPACING + (index as u32 / columns) * cell_height;
+        imageops::overlay(&mut montage, &thumbnail.image, x as i64, y as i64);
+
+        let (label_width, _) = get_text_size(&thumbnail.name, LABEL_FONT_SIZE);
+        let label_x = x as i32 + (thumbnail_size as i32 - label_width as i32) / 2;
+        draw_text_on_image(
+            &mut montage,
+            &thumbnail.name,
+            (label_x, (y + thumbnail_size + SPACING / 2) as i32),
+            LABEL_FONT_SIZE,
+            Rgba([230, 230, 230, 255]),
+        );
+    }
+
+    montage
+}
+
+/// Makes a page showing each thumbnail with the command that makes it
+fn make_index(thumbnails: &[Thumbnail]) -> String {
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>fun-images gallery</title>\n\
+         <style>\nbody { background: #18181c; color: #e6e6e6; font-family: sans-serif; }\n\
+         main { display: flex; flex-wrap: wrap; gap: 16px; }\n\
+         figure { margin: 0; }\ncode { display: block; margin-top: 4px; }\n</style>\n\
+         </head>\n<body>\n<h1>fun-images gallery</h1>\n<main>\n",
+    );
+    for thumbnail in thumbnails.iter() {
+        let _ = writeln!(
+            html,
+            "<figure><img src=\"{}.png\" alt=\"{}\"><figcaption>{}<code>fun-images {}</code></figcaption></figure>",
+            thumbnail.name,
+            thumbnail.name,
+            thumbnail.name,
+            thumbnail.recipe.join(" ")
+        );
+    }
+    html.push_str("</main>\n</body>\n</html>\n");
+
+    html
+}
+
+fn get_thumbnail_path(out: &str, name: &str) -> String {
+    format!("{}/{}.png", out, name)
+}
@@ -0,0 +1,217 @@
+//! A registry of image generators keyed by name, so new image types can be added
+//! without changing the command line
+//!
+//! Each generator declares its options as clap args, which become the options of its
+//! subcommand. Register a generator with [`GeneratorRegistry::register`], and make the
+//! registry global with [`GeneratorRegistry::set_global`] before the command line is
+//! parsed to have it show up there
+
+use std::{io, sync::OnceLock};
+
+use clap::{ArgMatches, Command, FromArgMatches};
+use image::RgbaImage;
+use thiserror::Error;
+
+use crate::memory::MemoryEstimate;
+
+/// The image or frames a generator made
+pub enum Generated {
+    Still(RgbaImage),
+    Frames(Vec<RgbaImage>),
+}
+
+impl Generated {
+    /// Gets the frames, with a still image being a single frame
+    pub fn into_frames(self) -> Vec<RgbaImage> {
+        match self {
+            Generated::Still(image) => vec![image],
+            Generated::Frames(frames) => frames,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum GeneratorError {
+    #[error("reading {path}: {source}")]
+    ReadFile { path: String, source: io::Error },
+
+    /// The options can't make an image together, with what to change
+    #[error("{0}")]
+    InvalidOptions(String),
+
+    #[error("{0}")]
+    Failed(String),
+}
+
+/// An image type that can be made from its options
+pub trait Generator: Send + Sync + 'static {
+    /// The options for the image, used as the options of its subcommand
+    type Args: clap::Args;
+
+    /// The name of the image type, which is also its subcommand
+    fn name(&self) -> &'static str;
+
+    /// Whether the options make an animation rather than a still image
+    fn is_animated(&self, args: &Self::Args) -> bool;
+
+    /// Roughly how much memory making the image takes, or None if the options don't say
+    fn estimate_memory(&self, _args: &Self::Args) -> Option<MemoryEstimate> {
+        None
+    }
+
+    fn generate(&self, args: Self::Args) -> Result<Generated, GeneratorError>;
+}
+
+/// A generator with its options still to be parsed, which is how the registry holds them
+pub trait RegisteredGenerator: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Gets the subcommand with the generator's options
+    fn command(&self) -> Command;
+
+    /// Whether the options make an animation, which is false if they can't be parsed so
+    /// the error comes from generating
+    fn is_animated(&self, matches: &ArgMatches) -> bool;
+
+    /// Roughly how much memory making the image takes, which is None if the options
+    /// can't be parsed
+    fn estimate_memory(&self, matches: &ArgMatches) -> Option<MemoryEstimate>;
+
+    fn generate(&self, matches: &ArgMatches) -> Result<Generated, GeneratorError>;
+}
+
+impl<G: Generator> RegisteredGenerator for G {
+    fn name(&self) -> &'static str {
+        Generator::name(self)
+    }
+
+    fn command(&self) -> Command {
+        <G::Args as clap::Args>::augment_args(Command::new(Generator::name(self)))
+    }
+
+    fn is_animated(&self, matches: &ArgMatches) -> bool {
+        G::Args::from_arg_matches(matches).is_ok_and(|args| Generator::is_animated(self, &args))
+    }
+
+    fn estimate_memory(&self, matches: &ArgMatches) -> Option<MemoryEstimate> {
+        G::Args::from_arg_matches(matches)
+            .ok()
+            .and_then(|args| Generator::estimate_memory(self, &args))
+    }
+
+    fn generate(&self, matches: &ArgMatches) -> Result<Generated, GeneratorError> {
+        let args = G::Args::from_arg_matches(matches)
+            .map_err(|clap_error| GeneratorError::Failed(clap_error.to_string()))?;
+        Generator::generate(self, args)
+    }
+}
+
+/// The generators that can be used, in the order they're listed
+#[derive(Default)]
+pub struct GeneratorRegistry {
+    generators: Vec<Box<dyn RegisteredGenerator>>,
+}
+
+static GLOBAL_REGISTRY: OnceLock<GeneratorRegistry> = OnceLock::new();
+
+impl GeneratorRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A registry with the generators from this crate, including the ones behind
+    /// features that are turned on
+    pub fn with_builtin() -> Self {
+        let mut registry = Self::new();
+        registry.register(crate::mandelbrot::PhoenixGenerator);
+        registry.register(crate::mandelbrot::MagnetGenerator);
+        registry.register(crate::perlin::PerlinGenerator);
+        registry.register(crate::farey::FareyGenerator);
+        registry.register(crate::waves::WaveGenerator);
+        registry.register(crate::plasma::PlasmaGenerator);
+        registry.register(crate::tunnel::TunnelGenerator);
+        registry.register(crate::starfield::StarfieldGenerator);
+        registry.register(crate::matrix_rain::MatrixRainGenerator);
+        registry.register(crate::fire::FireGenerator);
+        registry.register(crate::ising::IsingGenerator);
+        registry.register(crate::hyperbolic::HyperbolicGenerator);
+        registry.register(crate::circle_packing::CirclePackingGenerator);
+        registry.register(crate::theodorus::TheodorusGenerator);
+        registry.register(crate::euler_spiral::EulerSpiralGenerator);
+        registry.register(crate::prime_race::PrimeRaceGenerator);
+        registry.register(crate::aliquot::AliquotGenerator);
+        registry.register(crate::goldbach::GoldbachGenerator);
+        registry.register(crate::collatz::CollatzGenerator);
+        registry.register(crate::digit_walk::DigitWalkGenerator);
+        registry.register(crate::bit_pattern::BitPatternGenerator);
+        registry.register(crate::xor_pattern::XorPatternGenerator);
+        registry.register(crate::times_table::TimesTableGenerator);
+        registry.register(crate::audio_wave::AudioWaveGenerator);
+        registry.register(crate::stylize::StylizeGenerator);
+        registry.register(crate::mandala::MandalaGenerator);
+        registry.register(crate::truchet::TruchetGenerator);
+        registry.register(crate::flow_field::FlowFieldGenerator);
+        registry.register(crate::particles::ParticlesGenerator);
+        registry.register(crate::vector_field::VectorFieldGenerator);
+        registry.register(crate::isometric::IsometricGenerator);
+        registry.register(crate::mandelbulb::MandelbulbGenerator);
+        registry.register(crate::quat_julia::QuaternionJuliaGenerator);
+        registry.register(crate::maze::MazeGenerator);
+        registry.register(crate::reaction_diffusion::ReactionDiffusionGenerator);
+        #[cfg(feature = "script")]
+        registry.register(crate::script::ScriptGenerator);
+
+        registry
+    }
+
+    /// Adds a generator, replacing any already registered with the same name
+    pub fn register(&mut self, generator: impl RegisteredGenerator + 'static) {
+        self.generators
+            .retain(|registered| registered.name() != generator.name());
+        self.generators.push(Box::new(generator));
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn RegisteredGenerator> {
+        self.generators
+            .iter()
+            .find(|generator| generator.name() == name)
+            .map(|generator| generator.as_ref())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &dyn RegisteredGenerator> {
+        self.generators.iter().map(|generator| generator.as_ref())
+    }
+
+    /// Gets the registry the command line uses, which has the builtin generators unless
+    /// set_global was called first
+    pub fn global() -> &'static Self {
+        GLOBAL_REGISTRY.get_or_init(Self::with_builtin)
+    }
+
+    /// Makes this the registry the command line uses, which only works before the
+    /// global registry is first used. Gives the registry back if it's too late
+    pub fn set_global(self) -> Result<(), Self> {
+        GLOBAL_REGISTRY.set(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry() {
+        let registry = GeneratorRegistry::with_builtin();
+        let generator = registry.get("fire").unwrap();
+        let matches = generator
+            .command()
+            .try_get_matches_from(["fire", "--width", "20", "--height", "10", "--frames", "2"])
+            .unwrap();
+
+        assert!(generator.is_animated(&matches));
+        let frames = generator.generate(&matches).unwrap().into_frames();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].dimensions(), (20, 10));
+        assert!(registry.get("maze").is_some());
+    }
+}
@@ -0,0 +1,181 @@
+//! Module for drawing Goldbach's comet
+//!
+//! Goldbach's conjecture says every even number above 2 is the sum of two primes. Going
+//! through the even numbers and counting how many ways each one can be split up, the
+//! counts spread out into bands that grow like the tail of a comet, with the multiples
+//! of 3 on the upper band as they can be split more ways. Each even number is a point,
+//! and the points are coloured by how many of them land on the same pixel.
+//! See <https://en.wikipedia.org/wiki/Goldbach%27s_comet> for more info
+
+use csscolorparser::Color;
+use image::{Rgba, RgbaImage};
+
+use crate::{
+    PaletteType,
+    generator::{Generated, Generator, GeneratorError},
+    memory::MemoryEstimate,
+    palette::get_palette_color,
+    parse_color, parse_size,
+    ulam_spiral::get_prime_sieve,
+};
+
+/// The space around the comet, as a fraction of the image size
+const MARGIN: f64 = 0.05;
+
+pub struct GoldbachOptions {
+    /// The biggest even number to split up
+    limit: u64,
+    palette: PaletteType,
+    background_color: Color,
+    width: u32,
+    height: u32,
+}
+
+impl GoldbachOptions {
+    pub fn new(
+        limit: u64,
+        palette: PaletteType,
+        background_color: Color,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        Self {
+            limit,
+            palette,
+            background_color,
+            width,
+            height,
+        }
+    }
+}
+
+/// Gets how many ways each number up to the limit is the sum of two primes, not
+/// counting the same pair the other way round, indexed by the number
+pub fn get_goldbach_partitions(limit: u64) -> Vec<u32> {
+    let sieve = get_prime_sieve(limit);
+    let primes: Vec<u64> = sieve
+        .primes_from(0)
+        .map(|prime| prime as u64)
+        .take_while(|&prime| prime <= limit)
+        .collect();
+
+    let mut partitions = vec![0; limit as usize + 1];
+    for (index, &prime) in primes.iter().enumerate() {
+        for &other in primes[index..].iter() {
+            let sum = prime + other;
+            if sum > limit {
+                break;
+            }
+            partitions[sum as usize] += 1;
+        }
+    }
+
+    partitions
+}
+
+pub fn generate_goldbach_image(options: GoldbachOptions) -> RgbaImage {
+    let (width, height) = (options.width, options.height);
+    let mut image = RgbaImage::from_pixel(width, height, Rgba(options.background_color.to_rgba8()));
+
+    let partitions = get_goldbach_partitions(options.limit);
+    let highest = partitions.iter().copied().max().unwrap_or(0).max(1);
+    let (margin_x, margin_y) = (width as f64 * MARGIN, height as f64 * MARGIN);
+
+    // counts how many even numbers land on each pixel
+    let mut density = vec![0u32; (width * height) as usize];
+    for number in (4..=options.limit).step_by(2) {
+        let amount_across = number as f64 / options.limit as f64;
+        let amount_up = partitions[number as usize] as f64 / highest as f64;
+        let x = (margin_x + amount_across * (width as f64 - 2.0 * margin_x)) as u32;
+        let y = (height as f64 - margin_y - amount_up * (height as f64 - 2.0 * margin_y)) as u32;
+        density[(y.min(height - 1) * width + x.min(width - 1)) as usize] += 1;
+    }
+
+    // on a log scale, as the dense bottom left has a lot more points than the tail
+    let densest = density.iter().copied().max().unwrap_or(0).max(2);
+    for (index, &count) in density.iter().enumerate() {
+        if count == 0 {
+            continue;
+        }
+        let position = (count as f64).ln() / (densest as f64).ln();
+        let (x, y) = (index as u32 % width, index as u32 / width);
+        image.put_pixel(
+            x,
+            y,
+            Rgba(get_palette_color(options.palette, 0.1 + position * 0.7)),
+        );
+    }
+
+    image
+}
+
+#[derive(clap::Args, Debug)]
+pub struct GoldbachArgs {
+    /// The biggest even number to split up into two primes
+    #[arg(short = 'n', long, default_value = "50000", value_parser = clap::value_parser!(u64).range(4..))]
+    limit: u64,
+
+    /// The palette the points are coloured by how many land on the same pixel
+    #[arg(short, long, default_value = "fire")]
+    palette: PaletteType,
+
+    #[arg(short, long, alias = "background", default_value = "black", value_parser = parse_color)]
+    background_color: Color,
+
+    #[arg(long, default_value = "1200", value_parser = parse_size)]
+    width: u32,
+
+    #[arg(long, default_value = "800", value_parser = parse_size)]
+    height: u32,
+}
+
+pub struct GoldbachGenerator;
+
+impl Generator for GoldbachGenerator {
+    type Args = GoldbachArgs;
+
+    fn name(&self) -> &'static str {
+        "goldbach"
+    }
+
+    fn is_animated(&self, _args: &GoldbachArgs) -> bool {
+        false
+    }
+
+    fn estimate_memory(&self, args: &GoldbachArgs) -> Option<MemoryEstimate> {
+        // a count for every number up to the limit and for every pixel
+        Some(
+            MemoryEstimate::still(args.width, args.height)
+                .with_pixel_buffer(args.width, args.height, 4)
+                .with_working(args.limit.saturating_mul(4)),
+        )
+    }
+
+    fn generate(&self, args: GoldbachArgs) -> Result<Generated, GeneratorError> {
+        Ok(Generated::Still(generate_goldbach_image(
+            GoldbachOptions::new(
+                args.limit,
+                args.palette,
+                args.background_color,
+                args.width,
+                args.height,
+            ),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_goldbach_partitions() {
+        let partitions = get_goldbach_partitions(100);
+        // 2 + 2, 3 + 7 and 5 + 5, then 3 + 97, 11 + 89, 17 + 83, 29 + 71, 41 + 59 and
+        // 47 + 53
+        assert_eq!(partitions[4], 1);
+        assert_eq!(partitions[10], 2);
+        assert_eq!(partitions[100], 6);
+        assert!((4..=100).step_by(2).all(|number| partitions[number] > 0));
+    }
+}
@@ -0,0 +1,295 @@
+//! Runs the generators as compute shaders using wgpu
+//!
+//! The shaders only work out a value for each pixel, the colouring is still done on
+//! the CPU so the output matches the CPU path. The GPU works in single precision, so
+//! very deep Mandelbrot zooms get blocky sooner than on the CPU
+//!
+//! Everything here returns None when there's no usable GPU so the caller can fall back
+//! to the CPU
+
+use wgpu::util::DeviceExt;
+
+use crate::{
+    mandelbrot::{FractalSet, MAX_ITER_NUM, ViewPort},
+    mandelbulb::BAILOUT,
+    raymarch::{Camera, HIT_FRACTION, MAX_STEP_NUM},
+};
+
+/// Has to match the workgroup size in the shaders
+const WORKGROUP_SIZE: u32 = 8;
+/// Written by the fractal shader for points that never escape
+const NOT_ESCAPED: u32 = u32::MAX;
+/// Written by the mandelbulb shader for rays that miss
+const MISSED: f32 = -1.0;
+
+/// Gets the iteration each pixel escaped on, the same as the CPU escape loop
+pub(crate) fn get_fractal_escapes(
+    set: FractalSet,
+    viewport: &ViewPort,
+    width: u32,
+    height: u32,
+) -> Option<Vec<Option<u32>>> {
+    let origin = viewport.get_point(0, 0, width, height);
+    let next = viewport.get_point(1, 1, width, height);
+    let (is_julia, constant) = match set {
+        FractalSet::Mandelbrot => (0, Default::default()),
+        FractalSet::Julia(constant) => (1, constant),
+        FractalSet::Phoenix { .. } | FractalSet::Magnet(_) => {
+            unreachable!("only the mandelbrot and julia sets have a shader")
+        }
+    };
+
+    let mut params = Vec::new();
+    for value in [
+        origin.re,
+        origin.im,
+        next.re - origin.re,
+        next.im - origin.im,
+        constant.re,
+        constant.im,
+    ] {
+        params.extend((value as f32).to_ne_bytes());
+    }
+    for value in [width, height, is_julia, MAX_ITER_NUM, 0, 0] {
+        params.extend(value.to_ne_bytes());
+    }
+
+    let output = run_pixel_shader(
+        include_str!("gpu/fractal.wgsl"),
+        &params,
+        None,
+        width,
+        height,
+    )?;
+
+    Some(
+        output
+            .chunks_exact(4)
+            .map(
+                |bytes| match u32::from_ne_bytes(bytes.try_into().unwrap()) {
+                    NOT_ESCAPED => None,
+                    escape => Some(escape),
+                },
+            )
+            .collect(),
+    )
+}
+
+/// Gets the noise value for each pixel from the grid of gradients
+pub(crate) fn get_perlin_values(
+    size: u32,
+    grid_size: u32,
+    cell_size: u32,
+    gradients: &[(f64, f64)],
+) -> Option<Vec<f64>> {
+    let params: Vec<u8> = [size, grid_size, cell_size, 0]
+        .into_iter()
+        .flat_map(u32::to_ne_bytes)
+        .collect();
+    let input: Vec<u8> = gradients
+        .iter()
+        .flat_map(|&(x, y)| [x as f32, y as f32])
+        .flat_map(f32::to_ne_bytes)
+        .collect();
+
+    let output = run_pixel_shader(
+        include_str!("gpu/perlin.wgsl"),
+        &params,
+        Some(&input),
+        size,
+        size,
+    )?;
+
+    Some(
+        output
+            .chunks_exact(4)
+            .map(|bytes| f32::from_ne_bytes(bytes.try_into().unwrap()) as f64)
+            .collect(),
+    )
+}
+
+/// Gets how far each pixel's ray goes before hitting the Mandelbulb, None if it missed
+pub(crate) fn get_mandelbulb_distances(
+    camera: &Camera,
+    power: f64,
+    iteration_num: u32,
+    width: u32,
+    height: u32,
+) -> Option<Vec<Option<f64>>> {
+    let pixel_size = 2.0 * camera.view_scale / height as f64;
+    let mut params = Vec::new();
+    for (vector, extra) in [
+        (camera.position, camera.view_scale),
+        (camera.forward, power),
+        (camera.right, pixel_size * HIT_FRACTION),
+        (camera.up, BAILOUT),
+    ] {
+        for value in [vector[0], vector[1], vector[2], extra] {
+            params.extend((value as f32).to_ne_bytes());
+        }
+    }
+    for value in [width, height, iteration_num, MAX_STEP_NUM] {
+        params.extend(value.to_ne_bytes());
+    }
+
+    let output = run_pixel_shader(
+        include_str!("gpu/mandelbulb.wgsl"),
+        &params,
+        None,
+        width,
+        height,
+    )?;
+
+    Some(
+        output
+            .chunks_exact(4)
+            .map(
+                |bytes| match f32::from_ne_bytes(bytes.try_into().unwrap()) {
+                    MISSED => None,
+                    distance => Some(distance as f64),
+                },
+            )
+            .collect(),
+    )
+}
+
+/// Runs a shader once per pixel, returning the 4 bytes it wrote for each one
+///
+/// The shader gets the params at binding 0, the output at binding 1 and the input, if
+/// there is one, at binding 2
+fn run_pixel_shader(
+    shader: &str,
+    params: &[u8],
+    input: Option<&[u8]>,
+    width: u32,
+    height: u32,
+) -> Option<Vec<u8>> {
+    pollster::block_on(run_pixel_shader_async(shader, params, input, width, height))
+}
+
+async fn run_pixel_shader_async(
+    shader: &str,
+    params: &[u8],
+    input: Option<&[u8]>,
+    width: u32,
+    height: u32,
+) -> Option<Vec<u8>> {
+    let instance = wgpu::Instance::default();
+    let adapter = instance
+        .request_adapter(&wgpu::RequestAdapterOptions::default())
+        .await
+        .ok()?;
+    let (device, queue) = adapter
+        .request_device(&wgpu::DeviceDescriptor::default())
+        .await
+        .ok()?;
+
+    let output_size = width as u64 * height as u64 * 4;
+    if output_size == 0 || output_size > device.limits().max_storage_buffer_binding_size {
+        return None;
+    }
+
+    let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: None,
+        source: wgpu::ShaderSource::Wgsl(shader.into()),
+    });
+    let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: None,
+        layout: None,
+        module: &module,
+        entry_point: Some("main"),
+        compilation_options: Default::default(),
+        cache: None,
+    });
+
+    let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: None,
+        contents: params,
+        usage: wgpu::BufferUsages::UNIFORM,
+    });
+    let output_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: output_size,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+    // storage buffers can't be read from directly, so the output gets copied here
+    let read_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: output_size,
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let input_buffer = input.map(|input| {
+        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: input,
+            usage: wgpu::BufferUsages::STORAGE,
+        })
+    });
+
+    let mut entries = vec![
+        wgpu::BindGroupEntry {
+            binding: 0,
+            resource: params_buffer.as_entire_binding(),
+        },
+        wgpu::BindGroupEntry {
+            binding: 1,
+            resource: output_buffer.as_entire_binding(),
+        },
+    ];
+    if let Some(input_buffer) = &input_buffer {
+        entries.push(wgpu::BindGroupEntry {
+            binding: 2,
+            resource: input_buffer.as_entire_binding(),
+        });
+    }
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout: &pipeline.get_bind_group_layout(0),
+        entries: &entries,
+    });
+
+    let mut encoder = device.create_command_encoder(&Default::default());
+    {
+        let mut pass = encoder.begin_compute_pass(&Default::default());
+        pass.set_pipeline(&pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.dispatch_workgroups(
+            width.div_ceil(WORKGROUP_SIZE),
+            height.div_ceil(WORKGROUP_SIZE),
+            1,
+        );
+    }
+    encoder.copy_buffer_to_buffer(&output_buffer, 0, &read_buffer, 0, output_size);
+    queue.submit([encoder.finish()]);
+
+    let slice = read_buffer.slice(..);
+    slice.map_async(wgpu::MapMode::Read, |_| {});
+    device.poll(wgpu::PollType::wait_indefinitely()).ok()?;
+    let output = slice.get_mapped_range().ok()?.to_vec();
+
+    Some(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use wgpu::naga::{
+        front::wgsl,
+        valid::{Capabilities, ValidationFlags, Validator},
+    };
+
+    #[test]
+    fn test_shaders_are_valid() {
+        for shader in [
+            include_str!("gpu/fractal.wgsl"),
+            include_str!("gpu/perlin.wgsl"),
+            include_str!("gpu/mandelbulb.wgsl"),
+        ] {
+            let module = wgsl::parse_str(shader).unwrap();
+            Validator::new(ValidationFlags::all(), Capabilities::empty())
+                .validate(&module)
+                .unwrap();
+        }
+    }
+}
@@ -0,0 +1,44 @@
+// Escape time for the Mandelbrot and Julia sets, matching check_escape
+// in mandelbrot.rs but in single precision
+
+struct Params {
+    // the point for the top left pixel and how far each pixel moves it
+    origin: vec2<f32>,
+    step: vec2<f32>,
+    constant: vec2<f32>,
+    size: vec2<u32>,
+    is_julia: u32,
+    max_iterations: u32,
+    _padding: vec2<u32>,
+}
+
+const NOT_ESCAPED: u32 = 0xffffffffu;
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read_write> escapes: array<u32>;
+
+@compute @workgroup_size(8, 8)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= params.size.x || id.y >= params.size.y) {
+        return;
+    }
+
+    let point = params.origin + vec2<f32>(f32(id.x), f32(id.y)) * params.step;
+    var z = point;
+    var c = params.constant;
+    if (params.is_julia == 0u) {
+        z = vec2<f32>(0.0, 0.0);
+        c = point;
+    }
+
+    var escape = NOT_ESCAPED;
+    for (var i = 0u; i <= params.max_iterations + 1u; i++) {
+        z = vec2<f32>(z.x * z.x - z.y * z.y, 2.0 * z.x * z.y) + c;
+        if (abs(z.x) > 20.0 || abs(z.y) > 20.0) {
+            escape = i;
+            break;
+        }
+    }
+
+    escapes[id.y * params.size.x + id.x] = escape;
+}
@@ -0,0 +1,78 @@
+// How far each pixel's ray goes before hitting the Mandelbulb, matching march in
+// mandelbulb.rs but in single precision
+
+struct Params {
+    // the w of each is packed with one of the other values to keep it in vec4s
+    // w: how far the edge of the view is from the middle at one unit in front
+    position: vec4<f32>,
+    // w: the power
+    forward: vec4<f32>,
+    // w: how close a ray has to get to hit, per unit it's gone
+    right: vec4<f32>,
+    // w: the radius of the sphere everything's inside of
+    up: vec4<f32>,
+    size: vec2<u32>,
+    iterations: u32,
+    max_steps: u32,
+}
+
+const MISSED: f32 = -1.0;
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read_write> distances: array<f32>;
+
+fn get_distance(point: vec3<f32>) -> f32 {
+    let power = params.forward.w;
+    var z = point;
+    var derivative = 1.0;
+    var radius = length(z);
+    for (var i = 0u; i < params.iterations; i++) {
+        if (radius > params.up.w) {
+            break;
+        }
+        let theta = acos(z.y / radius) * power;
+        let phi = atan2(z.z, z.x) * power;
+        derivative = pow(radius, power - 1.0) * power * derivative + 1.0;
+        z = pow(radius, power) * vec3<f32>(sin(theta) * cos(phi), cos(theta), sin(theta) * sin(phi)) + point;
+        radius = length(z);
+    }
+
+    return 0.5 * log(radius) * radius / derivative;
+}
+
+@compute @workgroup_size(8, 8)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= params.size.x || id.y >= params.size.y) {
+        return;
+    }
+
+    let size = vec2<f32>(params.size);
+    let view_scale = params.position.w;
+    let across = (2.0 * (f32(id.x) + 0.5) / size.x - 1.0) * view_scale * size.x / size.y;
+    let down = (1.0 - 2.0 * (f32(id.y) + 0.5) / size.y) * view_scale;
+    let direction = normalize(params.forward.xyz + params.right.xyz * across + params.up.xyz * down);
+
+    let origin = params.position.xyz;
+    let bailout = params.up.w;
+    let middle = -dot(origin, direction);
+    let closest = dot(origin, origin) - middle * middle;
+    var result = MISSED;
+    if (closest <= bailout * bailout) {
+        let half_chord = sqrt(bailout * bailout - closest);
+        var distance = max(middle - half_chord, 0.0);
+        let end = middle + half_chord;
+        for (var i = 0u; i < params.max_steps; i++) {
+            let step = get_distance(origin + direction * distance);
+            if (step < distance * params.right.w) {
+                result = distance;
+                break;
+            }
+            distance += step;
+            if (distance > end) {
+                break;
+            }
+        }
+    }
+
+    distances[id.y * params.size.x + id.x] = result;
+}
@@ -0,0 +1,42 @@
+// Perlin noise value for each pixel, matching the loop in perlin.rs
+
+struct Params {
+    size: u32,
+    grid_size: u32,
+    // how many pixels each grid cell covers
+    cell_size: u32,
+    _padding: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read_write> values: array<f32>;
+@group(0) @binding(2) var<storage, read> gradients: array<vec2<f32>>;
+
+fn fade(value: f32) -> f32 {
+    return value * value * value * (value * (value * 6.0 - 15.0) + 10.0);
+}
+
+fn corner_dot(grid_x: u32, grid_y: u32, point: vec2<f32>) -> f32 {
+    let gradient = gradients[grid_y * params.grid_size + grid_x];
+    return dot(gradient, vec2<f32>(f32(grid_x), f32(grid_y)) - point);
+}
+
+@compute @workgroup_size(8, 8)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= params.size || id.y >= params.size) {
+        return;
+    }
+
+    let left = id.x / params.cell_size;
+    let top = id.y / params.cell_size;
+    let right = min(left + 1u, params.grid_size - 1u);
+    let bottom = min(top + 1u, params.grid_size - 1u);
+    let point = vec2<f32>(f32(id.x), f32(id.y)) / f32(params.cell_size);
+
+    let frac_x = fade(f32(id.x % params.cell_size) / f32(params.cell_size));
+    let frac_y = fade(f32(id.y % params.cell_size) / f32(params.cell_size));
+
+    let top_value = mix(corner_dot(left, top, point), corner_dot(right, top, point), frac_x);
+    let bottom_value = mix(corner_dot(left, bottom, point), corner_dot(right, bottom, point), frac_x);
+    values[id.y * params.size + id.x] = mix(top_value, bottom_value, frac_y);
+}
@@ -0,0 +1,239 @@
+//! A window showing the image next to controls for its key parameters
+//!
+//! Works on the parsed image type directly, so tweaking a parameter is just changing
+//! the field and generating the image again the same way as from the command line
+
+use clap::ValueEnum;
+use csscolorparser::Color;
+use eframe::egui::{self, ColorImage, TextureHandle, TextureOptions};
+use image::{DynamicImage, RgbaImage};
+
+use fun_images::post::{PostArgs, apply_post_processing};
+
+use crate::{ImageFormat, ImageType, error::Error, generate_static_image};
+
+const WINDOW_SIZE: [f32; 2] = [1200.0, 800.0];
+
+/// A parameter of an image type that can be changed in the window
+enum Parameter<'a> {
+    Slider(egui::Slider<'a>),
+    Toggle(&'static str, &'a mut bool),
+    Color(&'static str, &'a mut Color),
+    Choice {
+        name: &'static str,
+        options: Vec<String>,
+        selected: usize,
+        set: Box<dyn FnOnce(usize) + 'a>,
+    },
+}
+
+struct GuiApp {
+    image_type: ImageType,
+    post_args: PostArgs,
+    output: String,
+    image: Option<RgbaImage>,
+    texture: Option<TextureHandle>,
+    status: String,
+}
+
+pub fn run_gui(image_type: ImageType, post_args: PostArgs, output: String) -> Result<(), Error> {
+    if !matches!(image_type.get_format(), ImageFormat::Static) {
+        return Err(Error::GuiAnimation);
+    }
+
+    let native_options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default().with_inner_size(WINDOW_SIZE),
+        ..Default::default()
+    };
+    eframe::run_native(
+        "fun-images",
+        native_options,
+        Box::new(|creation_context| {
+            let mut app = GuiApp {
+                image_type,
+                post_args,
+                output,
+                image: None,
+                texture: None,
+                status: String::new(),
+            };
+            app.render(&creation_context.egui_ctx);
+            Ok(Box::new(app))
+        }),
+    )
+    .map_err(Error::Gui)
+}
+
+impl GuiApp {
+    fn render(&mut self, ctx: &egui::Context) {
+        let image = match generate_static_image(self.image_type.clone()) {
+            Ok(image) => image,
+            Err(error) => {
+                self.status = format!("Error {}", error);
+                return;
+            }
+        };
+        let image = match apply_post_processing(image.into_rgba8(), &self.post_args) {
+            Ok(image) => image,
+            Err(message) => {
+                self.status = format!("Error applying effects: {}", message);
+                return;
+            }
+        };
+
+        let color_image = ColorImage::from_rgba_unmultiplied(
+            [image.width() as usize, image.height() as usize],
+            image.as_raw(),
+        );
+        match &mut self.texture {
+            Some(texture) => texture.set(color_image, TextureOptions::LINEAR),
+            None => {
+                self.texture = Some(ctx.load_texture("image", color_image, TextureOptions::LINEAR))
+            }
+        }
+        self.image = Some(image);
+        self.status.clear();
+    }
+
+    fn save(&mut self) {
+        let Some(image) = &self.image else {
+            return;
+        };
+        self.status = match DynamicImage::ImageRgba8(image.clone()).save(&self.output) {
+            Ok(()) => format!("Saved image to {}", self.output),
+            Err(image_error) => format!("Error saving image: {}", image_error),
+        };
+    }
+}
+
+impl eframe::App for GuiApp {
+    fn ui(&mut self, ui: &mut egui::Ui, _frame: &mut eframe::Frame) {
+        egui::Panel::left("parameters").show(ui, |ui| {
+            ui.heading("Parameters");
+            let mut changed = false;
+            for parameter in get_parameters(&mut self.image_type) {
+                changed |= show_parameter(ui, parameter);
+            }
+            if changed {
+                self.render(ui.ctx());
+            }
+
+            ui.separator();
+            if ui.button("Save").clicked() {
+                self.save();
+            }
+            ui.label(&self.status);
+        });
+
+        egui::CentralPanel::default().show(ui, |ui| {
+            if let Some(texture) = &self.texture {
+                ui.add(egui::Image::new(texture).shrink_to_fit());
+            }
+        });
+    }
+}
+
+/// Shows the control for the parameter, returning whether the image needs rendering again
+fn show_parameter(ui: &mut egui::Ui, parameter: Parameter) -> bool {
+    match parameter {
+        Parameter::Slider(slider) => {
+            let response = ui.add(slider);
+            // wait until the slider is let go as some images take a while
+            response.drag_stopped() || (response.changed() && !response.dragged())
+        }
+        Parameter::Toggle(name, value) => ui.checkbox(value, name).changed(),
+        Parameter::Color(name, value) => {
+            ui.horizontal(|ui| {
+                let mut rgba = value.to_rgba8();
+                let changed = ui.color_edit_button_srgba_unmultiplied(&mut rgba).changed();
+                ui.label(name);
+                if changed {
+                    *value = Color::from_rgba8(rgba[0], rgba[1], rgba[2], rgba[3]);
+                }
+                changed
+            })
+            .inner
+        }
+        Parameter::Choice {
+            name,
+            options,
+            selected,
+            set,
+        } => {
+            let mut new_selected = selected;
+            egui::ComboBox::from_label(name)
+                .selected_text(&options[selected])
+                .show_ui(ui, |ui| {
+                    for (index, option) in options.iter().enumerate() {
+                        ui.selectable_value(&mut new_selected, index, option);
+                    }
+                });
+            if new_selected != selected {
+                set(new_selected);
+            }
+            new_selected != selected
+        }
+    }
+}
+
+fn choice<'a, T: ValueEnum>(name: &'static str, value: &'a mut T) -> Parameter<'a> {
+    let get_name = |value: &T| {
+        value
+            .to_possible_value()
+            .map(|possible_value| possible_value.get_name().to_string())
+            .unwrap_or_default()
+    };
+    let options: Vec<String> = T::value_variants().iter().map(get_name).collect();
+    let selected = options
+        .iter()
+        .position(|option| *option == get_name(value))
+        .unwrap_or(0);
+
+    Parameter::Choice {
+        name,
+        options,
+        selected,
+        set: Box::new(move |index| *value = T::value_variants()[index].clone()),
+    }
+}
+
+fn get_parameters(image_type: &mut ImageType) -> Vec<Parameter<'_>> {
+    match image_type {
+        ImageType::UlamSpiral {
+            size,
+            color,
+            mode,
+            background_color,
+            palette,
+            ..
+        } => vec![
+            Parameter::Slider(egui::Slider::new(size, 1..=250_000).text("size")),
+            choice("mode", mode),
+            choice("palette", palette),
+            Parameter::Color("color", color),
+            Parameter::Color("background color", background_color),
+        ],
+        ImageType::Mandelbrot {
+            color,
+            background_color,
+            gradient,
+            zoom,
+            ..
+        } => vec![
+            Parameter::Slider(
+                egui::Slider::new(zoom, 1.0..=1000.0)
+                    .logarithmic(true)
+                    .text("zoom"),
+            ),
+            Parameter::Toggle("gradient", gradient),
+            Parameter::Color("color", color),
+            Parameter::Color("background color", background_color),
+        ],
+        ImageType::Sierpinski { color, size, .. } => vec![
+            Parameter::Slider(egui::Slider::new(size, 100..=4000).text("size")),
+            Parameter::Color("color", color),
+        ],
+        // only still images are shown in the window
+        _ => Vec::new(),
+    }
+}
@@ -0,0 +1,99 @@
+//! Module for shading images made from heights as if they were hills lit from the side
+//!
+//! The slope at each pixel gives which way the surface faces, and with Lambertian
+//! shading the surface is as bright as the cosine of the angle between that and the
+//! direction to the light, so slopes facing the light are bright and the ones facing
+//! away are dark. See <https://en.wikipedia.org/wiki/Lambertian_reflectance> for more info
+
+use image::RgbaImage;
+
+/// Where the light comes from, in degrees
+#[derive(Clone, Copy, Debug)]
+pub struct Hillshade {
+    /// Which way round the light is, going clockwise from the top of the image
+    azimuth: f64,
+    /// How high up the light is, from 0 on the horizon to 90 straight overhead
+    altitude: f64,
+}
+
+impl Hillshade {
+    pub fn new(azimuth: f64, altitude: f64) -> Self {
+        Self { azimuth, altitude }
+    }
+
+    /// Gets how bright the surface is from 0 to 1 given its slope, which is how much
+    /// higher it gets for every pixel right and down
+    pub fn get_brightness(&self, (slope_x, slope_y): (f64, f64)) -> f64 {
+        let (azimuth, altitude) = (self.azimuth.to_radians(), self.altitude.to_radians());
+        // with y going down the image, so up is towards negative y
+        let light = (
+            azimuth.sin() * altitude.cos(),
+            -azimuth.cos() * altitude.cos(),
+            altitude.sin(),
+        );
+        let normal = (-slope_x, -slope_y, 1.0);
+        let normal_length = f64::hypot(f64::hypot(normal.0, normal.1), normal.2);
+
+        ((normal.0 * light.0 + normal.1 * light.1 + normal.2 * light.2) / normal_length).max(0.0)
+    }
+
+    /// Shades an image given the height of each pixel in row order, in pixels so the
+    /// slopes come out right
+    pub fn apply_mut(&self, image: &mut RgbaImage, heights: &[f64]) {
+        let (width, height) = image.dimensions();
+        let get_height = |x: u32, y: u32| heights[(y * width + x) as usize];
+        for (x, y, pixel) in image.enumerate_pixels_mut() {
+            // the pixels either side, or the pixel itself at the edges
+            let (left, right) = (x.saturating_sub(1), (x + 1).min(width - 1));
+            let (up, down) = (y.saturating_sub(1), (y + 1).min(height - 1));
+            let slope = (
+                (get_height(right, y) - get_height(left, y)) / (right - left).max(1) as f64,
+                (get_height(x, down) - get_height(x, up)) / (down - up).max(1) as f64,
+            );
+            let brightness = self.get_brightness(slope);
+            for channel in pixel.0.iter_mut().take(3) {
+                *channel = (*channel as f64 * brightness).round() as u8;
+            }
+        }
+    }
+}
+
+/// Parses where the light comes from, given as azimuth,altitude in degrees
+pub fn parse_hillshade(value: &str) -> Result<Hillshade, String> {
+    let (azimuth, altitude) = value
+        .split_once(',')
+        .ok_or_else(|| "expected azimuth,altitude like 315,45".to_string())?;
+    let parse_part = |part: &str| {
+        part.trim()
+            .parse::<f64>()
+            .map_err(|error| error.to_string())
+    };
+    let altitude = parse_part(altitude)?;
+    if !(0.0..=90.0).contains(&altitude) {
+        return Err("the altitude has to be from 0 to 90 degrees".to_string());
+    }
+
+    Ok(Hillshade::new(parse_part(azimuth)?, altitude))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_brightness() {
+        let hillshade = parse_hillshade("315,45").unwrap();
+        // flat ground gets the light at the altitude's angle
+        let flat = hillshade.get_brightness((0.0, 0.0));
+        assert!((flat - 45f64.to_radians().sin()).abs() < 1e-9);
+
+        // a 45 degree slope going down towards the top left faces the light head on
+        let slope = 1.0 / 2f64.sqrt();
+        assert!((hillshade.get_brightness((slope, slope)) - 1.0).abs() < 1e-9);
+        // and facing away from it is in shadow
+        assert!(hillshade.get_brightness((-2.0, -2.0)) == 0.0);
+
+        assert!(parse_hillshade("315,95").is_err());
+        assert!(parse_hillshade("315").is_err());
+    }
+}
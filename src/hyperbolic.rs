@@ -0,0 +1,263 @@
+//! Module for generating tilings of the hyperbolic plane
+//!
+//! A {p,q} tiling has regular p sided polygons with q of them meeting at every corner,
+//! which only fits in the hyperbolic plane when (p - 2)(q - 2) > 4. The plane is drawn
+//! in the Poincaré disk, where the tiles shrink towards the edge of the disk.
+//!
+//! Each polygon splits into 2p triangles with angles π/p, π/q and π/2, and every point
+//! of the plane can be reflected back into the triangle with a corner in the middle of
+//! the disk. Neighbouring triangles are mirror images, so counting the reflections on
+//! the way there gives the two alternating colours. The polygons themselves can only be
+//! coloured that way when q is even, so their edges are drawn over the triangles.
+//! See <https://en.wikipedia.org/wiki/Uniform_tilings_in_hyperbolic_plane> for more info
+
+use std::f64::consts::PI;
+
+use csscolorparser::Color;
+use image::{Rgba, RgbaImage};
+use num_complex::Complex64;
+
+use crate::{
+    generator::{Generated, Generator, GeneratorError},
+    memory::MemoryEstimate,
+    parse_color, parse_size,
+};
+
+/// Points right by the edge of the disk can take a lot of reflections to bring back,
+/// so they give up at some point and get the background colour
+const MAX_REFLECTION_NUM: u32 = 1000;
+
+pub struct HyperbolicOptions {
+    p: u32,
+    q: u32,
+    /// How many polygons out from the middle to draw
+    depth: u32,
+    color: Color,
+    alternate_color: Color,
+    edge_color: Color,
+    background_color: Color,
+    size: u32,
+}
+
+impl HyperbolicOptions {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        p: u32,
+        q: u32,
+        depth: u32,
+        color: Color,
+        alternate_color: Color,
+        edge_color: Color,
+        background_color: Color,
+        size: u32,
+    ) -> Self {
+        Self {
+            p,
+            q,
+            depth,
+            color,
+            alternate_color,
+            edge_color,
+            background_color,
+            size,
+        }
+    }
+}
+
+/// Whether {p,q} tiles the hyperbolic plane rather than the sphere or the flat plane
+pub fn is_hyperbolic(p: u32, q: u32) -> bool {
+    p >= 3 && q >= 3 && (p - 2) * (q - 2) > 4
+}
+
+/// The mirrors of the triangle everything gets reflected back into, which are the real
+/// axis, the line through 0 at an angle of π/p, and a circle that meets the edge of the
+/// disk at right angles and the other line at an angle of π/q
+struct FundamentalTriangle {
+    angle: f64,
+    /// e^(2i π/p), used to reflect in the angled line
+    rotation: Complex64,
+    circle_centre: Complex64,
+    circle_radius: f64,
+}
+
+impl FundamentalTriangle {
+    fn new(p: u32, q: u32) -> Self {
+        let angle = PI / p as f64;
+        let (sin_p, cos_q) = (angle.sin(), (PI / q as f64).cos());
+        let circle_radius = sin_p / (cos_q * cos_q - sin_p * sin_p).sqrt();
+        // orthogonal to the unit circle, so the centre is √(1 + r²) from 0
+        let circle_centre = Complex64::new((1.0 + circle_radius * circle_radius).sqrt(), 0.0);
+
+        Self {
+            angle,
+            rotation: Complex64::from_polar(1.0, 2.0 * angle),
+            circle_centre,
+            circle_radius,
+        }
+    }
+
+    /// Reflects the point back into the triangle, or gives None if it has to cross more
+    /// polygon edges than the depth to get there
+    fn fold(&self, mut point: Complex64, depth: u32) -> Option<Folded> {
+        let (mut reflection_num, mut edge_num) = (0, 0);
+        for _ in 0..MAX_REFLECTION_NUM {
+            if point.im < 0.0 {
+                point = point.conj();
+            } else if point.arg() > self.angle {
+                point = self.rotation * point.conj();
+            } else if (point - self.circle_centre).norm() < self.circle_radius {
+                let offset = point - self.circle_centre;
+                point = self.circle_centre + self.circle_radius.powi(2) / offset.conj();
+                edge_num += 1;
+                if edge_num > depth {
+                    return None;
+                }
+            } else {
+                return Some(Folded {
+                    point,
+                    reflection_num,
+                });
+            }
+            reflection_num += 1;
+        }
+        None
+    }
+
+    /// How far the point is from the polygon's edge, in the triangle in the middle
+    fn get_edge_distance(&self, point: Complex64) -> f64 {
+        (point - self.circle_centre).norm() - self.circle_radius
+    }
+}
+
+/// Where a point ended up after being reflected back into the fundamental triangle
+struct Folded {
+    point: Complex64,
+    reflection_num: u32,
+}
+
+pub fn generate_hyperbolic_image(options: HyperbolicOptions) -> RgbaImage {
+    let triangle = FundamentalTriangle::new(options.p, options.q);
+    let colors = [
+        Rgba(options.color.to_rgba8()),
+        Rgba(options.alternate_color.to_rgba8()),
+    ];
+    let edge_color = Rgba(options.edge_color.to_rgba8());
+    let background_color = Rgba(options.background_color.to_rgba8());
+    let radius = options.size as f64 / 2.0;
+    // a pixel wide in the middle of the disk, the edges further out get reflected from
+    // there so they thin out towards the edge of the disk the same way as the polygons
+    let edge_width = 1.0 / radius;
+
+    RgbaImage::from_fn(options.size, options.size, |x, y| {
+        let point = Complex64::new(
+            (x as f64 + 0.5 - radius) / radius,
+            (y as f64 + 0.5 - radius) / radius,
+        );
+        if point.norm() >= 1.0 {
+            return background_color;
+        }
+        match triangle.fold(point, options.depth) {
+            Some(folded) if triangle.get_edge_distance(folded.point) < edge_width => edge_color,
+            Some(folded) => colors[folded.reflection_num as usize % 2],
+            None => background_color,
+        }
+    })
+}
+
+#[derive(clap::Args, Debug)]
+pub struct HyperbolicArgs {
+    /// How many sides each polygon has
+    #[arg(long, default_value = "7")]
+    p: u32,
+
+    /// How many polygons meet at each corner
+    #[arg(long, default_value = "3")]
+    q: u32,
+
+    /// How many polygons out from the middle to draw
+    #[arg(short, long, default_value = "12")]
+    depth: u32,
+
+    #[arg(short, long, default_value = "black", value_parser = parse_color)]
+    color: Color,
+
+    /// The colour of every other triangle
+    #[arg(short, long, default_value = "white", value_parser = parse_color)]
+    alternate_color: Color,
+
+    /// The colour of the polygons' edges
+    #[arg(short, long, default_value = "red", value_parser = parse_color)]
+    edge_color: Color,
+
+    /// The colour outside the disk and past the depth, which can be transparent
+    #[arg(short, long, alias = "background", default_value = "transparent", value_parser = parse_color)]
+    background_color: Color,
+
+    #[arg(short, long, default_value = "1000", value_parser = parse_size)]
+    size: u32,
+}
+
+pub struct HyperbolicGenerator;
+
+impl Generator for HyperbolicGenerator {
+    type Args = HyperbolicArgs;
+
+    fn name(&self) -> &'static str {
+        "hyperbolic"
+    }
+
+    fn is_animated(&self, _args: &HyperbolicArgs) -> bool {
+        false
+    }
+
+    fn estimate_memory(&self, args: &HyperbolicArgs) -> Option<MemoryEstimate> {
+        Some(MemoryEstimate::still(args.size, args.size))
+    }
+
+    fn generate(&self, args: HyperbolicArgs) -> Result<Generated, GeneratorError> {
+        if !is_hyperbolic(args.p, args.q) {
+            return Err(GeneratorError::InvalidOptions(format!(
+                "{{{},{}}} isn't a hyperbolic tiling, (p - 2)(q - 2) has to be more than 4",
+                args.p, args.q
+            )));
+        }
+
+        Ok(Generated::Still(generate_hyperbolic_image(
+            HyperbolicOptions::new(
+                args.p,
+                args.q,
+                args.depth,
+                args.color,
+                args.alternate_color,
+                args.edge_color,
+                args.background_color,
+                args.size,
+            ),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fundamental_triangle() {
+        assert!(is_hyperbolic(7, 3) && is_hyperbolic(4, 5));
+        assert!(!is_hyperbolic(4, 4) && !is_hyperbolic(6, 3) && !is_hyperbolic(2, 9));
+
+        // the middle of the disk is in the first polygon, and going out along the real
+        // axis crosses into the next one where the circle meets it, which is only drawn
+        // with a depth of at least 1
+        let triangle = FundamentalTriangle::new(7, 3);
+        let edge = triangle.circle_centre.re - triangle.circle_radius;
+        let is_drawn = |x: f64, depth: u32| triangle.fold(Complex64::new(x, 0.0), depth).is_some();
+        assert!(is_drawn(0.01, 0));
+        assert!(!is_drawn(edge + 0.01, 0));
+        assert!(is_drawn(edge + 0.01, 1));
+
+        // crossing an edge lands just inside it again
+        let folded = triangle.fold(Complex64::new(edge + 1e-6, 0.0), 5).unwrap();
+        assert!(triangle.get_edge_distance(folded.point).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,216 @@
+//! Simulates the 2D Ising model of magnetism
+//!
+//! Each cell on the lattice is a spin which is either up or down, and neighbouring spins
+//! prefer to line up. Updates use the Metropolis algorithm, flipping a spin if it lowers
+//! the energy, or randomly with a chance based on the temperature if it doesn't.
+//! Below the critical temperature of about 2.269 large domains form, above it the
+//! lattice stays noisy. See <https://en.wikipedia.org/wiki/Ising_model>
+
+use csscolorparser::Color;
+use image::{Rgba, RgbaImage};
+
+use crate::{
+    cancel::is_cancelled,
+    generator::{Generated, Generator, GeneratorError},
+    memory::MemoryEstimate,
+    parse_color, parse_size,
+};
+
+pub struct IsingOptions {
+    size: u32,
+    scale: u32,
+    frame_num: u32,
+    sweeps_per_frame: u32,
+    temperature: TemperatureSchedule,
+    seed: Option<u64>,
+    up_color: Color,
+    down_color: Color,
+}
+
+impl IsingOptions {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        size: u32,
+        scale: u32,
+        frame_num: u32,
+        sweeps_per_frame: u32,
+        temperature: TemperatureSchedule,
+        seed: Option<u64>,
+        up_color: Color,
+        down_color: Color,
+    ) -> Self {
+        Self {
+            size,
+            scale,
+            frame_num,
+            sweeps_per_frame,
+            temperature,
+            seed,
+            up_color,
+            down_color,
+        }
+    }
+
+    pub fn get_image_size(&self) -> u32 {
+        self.size * self.scale
+    }
+}
+
+/// The temperature used for each frame of the simulation
+#[derive(Clone, Copy, Debug)]
+pub enum TemperatureSchedule {
+    /// The same temperature throughout
+    Constant(f64),
+    /// Moves linearly from the first temperature to the second over the animation
+    Sweep(f64, f64),
+}
+
+impl TemperatureSchedule {
+    fn get_temperature(&self, frame: u32, frame_num: u32) -> f64 {
+        match *self {
+            TemperatureSchedule::Constant(temperature) => temperature,
+            TemperatureSchedule::Sweep(start, end) => {
+                let amount = frame as f64 / (frame_num.max(2) - 1) as f64;
+                start + (end - start) * amount
+            }
+        }
+    }
+}
+
+pub fn generate_ising_images(options: IsingOptions) -> Vec<RgbaImage> {
+    let mut rng = match options.seed {
+        Some(seed) => fastrand::Rng::with_seed(seed),
+        None => fastrand::Rng::new(),
+    };
+
+    let size = options.size as usize;
+    // start from a random hot state
+    let mut spins: Vec<i8> = (0..size * size)
+        .map(|_| if rng.bool() { 1 } else { -1 })
+        .collect();
+
+    let up_color = Rgba(options.up_color.to_rgba8());
+    let down_color = Rgba(options.down_color.to_rgba8());
+    let image_size = options.get_image_size();
+    let mut images = Vec::new();
+    for frame in 0..options.frame_num {
+        if is_cancelled() {
+            break;
+        }
+        let temperature = options
+            .temperature
+            .get_temperature(frame, options.frame_num)
+            .max(f64::EPSILON);
+        // the energy change of a flip is 2 * spin * (sum of neighbours) which can only
+        // be positive as 4 or 8, so precalculate the chance of accepting those
+        let acceptance = [f64::exp(-4.0 / temperature), f64::exp(-8.0 / temperature)];
+
+        for _ in 0..options.sweeps_per_frame * (size * size) as u32 {
+            let x = rng.usize(0..size);
+            let y = rng.usize(0..size);
+            let spin = spins[y * size + x];
+            // wraps around at the edges
+            let neighbour_sum = spins[y * size + (x + 1) % size]
+                + spins[y * size + (x + size - 1) % size]
+                + spins[((y + 1) % size) * size + x]
+                + spins[((y + size - 1) % size) * size + x];
+            let energy_change = 2 * spin * neighbour_sum;
+
+            let flip = match energy_change {
+                ..=0 => true,
+                4 => rng.f64() < acceptance[0],
+                _ => rng.f64() < acceptance[1],
+            };
+            if flip {
+                spins[y * size + x] = -spin;
+            }
+        }
+
+        let mut image = RgbaImage::new(image_size, image_size);
+        for (x, y, pixel) in image.enumerate_pixels_mut() {
+            let spin_x = (x / options.scale) as usize;
+            let spin_y = (y / options.scale) as usize;
+            *pixel = if spins[spin_y * size + spin_x] > 0 {
+                up_color
+            } else {
+                down_color
+            };
+        }
+        images.push(image);
+    }
+
+    images
+}
+
+#[derive(clap::Args, Debug)]
+pub struct IsingArgs {
+    /// The number of spins along each side of the lattice
+    #[arg(short, long, default_value = "200", value_parser = parse_size)]
+    size: u32,
+
+    /// The size of each spin in pixels
+    #[arg(long, default_value = "2", value_parser = parse_size)]
+    scale: u32,
+
+    #[arg(short, long, default_value = "100", value_parser = parse_size)]
+    frames: u32,
+
+    /// The number of Metropolis sweeps over the whole lattice between frames
+    #[arg(long, default_value = "1")]
+    sweeps_per_frame: u32,
+
+    /// The temperature, the phase transition is at about 2.269
+    #[arg(short, long, default_value = "2.0")]
+    temperature: f64,
+
+    /// Sweep the temperature to this value over the animation
+    #[arg(long)]
+    sweep_to: Option<f64>,
+
+    /// The seed used for the simulation, random if not given
+    #[arg(long)]
+    seed: Option<u64>,
+
+    #[arg(long, default_value = "white", value_parser = parse_color)]
+    up_color: Color,
+
+    #[arg(long, default_value = "black", value_parser = parse_color)]
+    down_color: Color,
+}
+
+pub struct IsingGenerator;
+
+impl Generator for IsingGenerator {
+    type Args = IsingArgs;
+
+    fn name(&self) -> &'static str {
+        "ising"
+    }
+
+    fn is_animated(&self, _args: &IsingArgs) -> bool {
+        true
+    }
+
+    fn estimate_memory(&self, args: &IsingArgs) -> Option<MemoryEstimate> {
+        let image_size = args.size.saturating_mul(args.scale);
+        Some(MemoryEstimate::frames(image_size, image_size, args.frames))
+    }
+
+    fn generate(&self, args: IsingArgs) -> Result<Generated, GeneratorError> {
+        let temperature = match args.sweep_to {
+            Some(end_temperature) => TemperatureSchedule::Sweep(args.temperature, end_temperature),
+            None => TemperatureSchedule::Constant(args.temperature),
+        };
+        let options = IsingOptions::new(
+            args.size,
+            args.scale,
+            args.frames,
+            args.sweeps_per_frame,
+            temperature,
+            args.seed,
+            args.up_color,
+            args.down_color,
+        );
+        Ok(Generated::Frames(generate_ising_images(options)))
+    }
+}
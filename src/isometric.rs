@@ -0,0 +1,367 @@
+//! Module for drawing noise as isometric voxel terrain
+//!
+//! A few layers of Perlin noise give a height for each square of a grid, which is
+//! stacked up with that many blocks. The blocks are drawn in isometric projection from
+//! the back to the front so the nearer ones cover the ones behind, with each block
+//! coloured by how high up it is and the sides shaded darker than the tops. Anything
+//! below the water level is flooded.
+//! See <https://en.wikipedia.org/wiki/Isometric_video_game_graphics> for more info
+
+use csscolorparser::Color;
+use image::{Rgba, RgbaImage};
+use imageproc::{drawing::draw_polygon_mut, point::Point};
+
+use crate::{
+    PaletteType,
+    generator::{Generated, Generator, GeneratorError},
+    memory::MemoryEstimate,
+    palette::get_palette_color,
+    parse_color, parse_size,
+    perlin::Noise,
+};
+
+/// How many layers of noise go into the heights, each half the size of the one before
+const OCTAVE_NUM: u32 = 3;
+
+/// The colours going up from the shore to the peaks
+const TERRAIN_COLORS: [[u8; 4]; 5] = [
+    [222, 204, 140, 255],
+    [116, 176, 72, 255],
+    [64, 124, 54, 255],
+    [128, 118, 108, 255],
+    [240, 242, 248, 255],
+];
+
+const WATER_COLOR: [u8; 4] = [62, 124, 204, 255];
+
+/// How bright the sides are compared to the tops, as if lit from the top left
+const LEFT_SHADE: f64 = 0.78;
+const RIGHT_SHADE: f64 = 0.58;
+
+pub struct IsometricOptions {
+    /// How many squares there are along each side of the grid
+    grid_size: u32,
+    /// The most blocks a column can have
+    max_height: u32,
+    /// How many squares across the hills are, bigger gives wider hills
+    noise_scale: f64,
+    /// How wide each block is in pixels
+    block_size: u32,
+    /// How high the water goes, from 0 for none to 1 for everything flooded
+    water_level: f64,
+    /// Colours the blocks by height instead of using the terrain colours when given
+    palette: Option<PaletteType>,
+    background_color: Color,
+    seed: Option<u64>,
+}
+
+impl IsometricOptions {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        grid_size: u32,
+        max_height: u32,
+        noise_scale: f64,
+        block_size: u32,
+        water_level: f64,
+        palette: Option<PaletteType>,
+        background_color: Color,
+        seed: Option<u64>,
+    ) -> Self {
+        Self {
+            grid_size,
+            max_height,
+            noise_scale,
+            block_size,
+            water_level,
+            palette,
+            background_color,
+            seed,
+        }
+    }
+
+    /// How many blocks of water there are in the lowest places
+    fn get_water_height(&self) -> u32 {
+        (self.water_level * self.max_height as f64).round() as u32
+    }
+
+    /// Gets the width and height of the image the terrain fits in
+    fn get_image_size(&self) -> (u32, u32) {
+        let block_size = self.block_size as f64;
+        let tallest = self.max_height.max(self.get_water_height()) as f64;
+        // a block of margin each side, with the grid's diagonals going across and down
+        let width = 2.0 * block_size + self.grid_size as f64 * block_size;
+        let height = 2.0 * block_size
+            + tallest * block_size / 2.0
+            + (2 * self.grid_size) as f64 * block_size / 4.0;
+        (width.ceil() as u32, height.ceil() as u32)
+    }
+
+    /// Gets the colour of the top of a block of land, which starts at level
+    fn get_land_color(&self, level: u32) -> [u8; 4] {
+        // measured from the water up, so the shore is always the lowest colour
+        let water_height = self.get_water_height();
+        let position = (level.saturating_sub(water_height) as f64 + 0.5)
+            / (self.max_height - water_height.min(self.max_height - 1)) as f64;
+        match self.palette {
+            Some(palette) => get_palette_color(palette, position.min(1.0) * 0.8),
+            None => {
+                let index = (position * TERRAIN_COLORS.len() as f64) as usize;
+                TERRAIN_COLORS[index.min(TERRAIN_COLORS.len() - 1)]
+            }
+        }
+    }
+}
+
+/// Gets how many blocks high each column is in row order, stretched so the lowest is 1
+/// block and the highest is the max height
+pub fn get_heights(options: &IsometricOptions, noise: &Noise) -> Vec<u32> {
+    let size = options.grid_size;
+    let values: Vec<f64> = (0..size * size)
+        .map(|index| {
+            let (x, y) = (
+                (index % size) as f64 / options.noise_scale,
+                (index / size) as f64 / options.noise_scale,
+            );
+            // smaller and smaller bumps on top of the hills
+            (0..OCTAVE_NUM)
+                .map(|octave| {
+                    let frequency = 2f64.powi(octave as i32);
+                    noise.get(x * frequency, y * frequency) / frequency
+                })
+                .sum()
+        })
+        .collect();
+
+    // the noise rarely gets near its limits, so it'd be mostly flat without stretching
+    let lowest = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let highest = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let range = (highest - lowest).max(f64::EPSILON);
+    values
+        .into_iter()
+        .map(|value| {
+            let height = 1.0 + (value - lowest) / range * (options.max_height - 1) as f64;
+            height.round() as u32
+        })
+        .collect()
+}
+
+fn shade(color: [u8; 4], amount: f64) -> Rgba<u8> {
+    let [red, green, blue, alpha] = color;
+    let shade_channel = |channel: u8| (channel as f64 * amount).round() as u8;
+    Rgba([
+        shade_channel(red),
+        shade_channel(green),
+        shade_channel(blue),
+        alpha,
+    ])
+}
+
+/// Draws a block with the middle of its top at a point
+fn draw_block(image: &mut RgbaImage, (x, y): (f64, f64), block_size: u32, color: [u8; 4]) {
+    // the top is a diamond twice as wide as it is high, and the sides are as tall as
+    // the block is wide to make it a cube
+    let half_width = block_size as f64 / 2.0;
+    let half_height = block_size as f64 / 4.0;
+    let side_height = block_size as f64 / 2.0;
+    let to_points = |points: [(f64, f64); 4]| {
+        points.map(|(x, y)| Point::new(x.round() as i32, y.round() as i32))
+    };
+
+    let top = to_points([
+        (x, y - half_height),
+        (x + half_width, y),
+        (x, y + half_height),
+        (x - half_width, y),
+    ]);
+    let left = to_points([
+        (x - half_width, y),
+        (x, y + half_height),
+        (x, y + half_height + side_height),
+        (x - half_width, y + side_height),
+    ]);
+    let right = to_points([
+        (x, y + half_height),
+        (x + half_width, y),
+        (x + half_width, y + side_height),
+        (x, y + half_height + side_height),
+    ]);
+    draw_polygon_mut(image, &left, shade(color, LEFT_SHADE));
+    draw_polygon_mut(image, &right, shade(color, RIGHT_SHADE));
+    draw_polygon_mut(image, &top, shade(color, 1.0));
+}
+
+pub fn generate_isometric_image(options: IsometricOptions) -> RgbaImage {
+    let mut rng = match options.seed {
+        Some(seed) => fastrand::Rng::with_seed(seed),
+        None => fastrand::Rng::new(),
+    };
+    let noise = Noise::new(&mut rng);
+    let heights = get_heights(&options, &noise);
+    let water_height = options.get_water_height();
+
+    let size = options.grid_size;
+    let half_width = options.block_size as f64 / 2.0;
+    let half_height = options.block_size as f64 / 4.0;
+    let side_height = options.block_size as f64 / 2.0;
+    // a block of space around the terrain, which goes from the back corner at the top to
+    // the front corner at the bottom
+    let margin = options.block_size as f64;
+    let tallest = options.max_height.max(water_height) as f64;
+    let origin = (
+        margin + size as f64 * half_width,
+        margin + tallest * side_height + half_height,
+    );
+    let (image_width, image_height) = options.get_image_size();
+    let mut image = RgbaImage::from_pixel(
+        image_width,
+        image_height,
+        Rgba(options.background_color.to_rgba8()),
+    );
+
+    // each diagonal going across the image is in front of the ones before it, and the
+    // columns along a diagonal don't overlap
+    for diagonal in 0..2 * size - 1 {
+        for x in diagonal.saturating_sub(size - 1)..=diagonal.min(size - 1) {
+            let y = diagonal - x;
+            let height = heights[(y * size + x) as usize];
+            for level in 0..height.max(water_height) {
+                let color = match level < height {
+                    true => options.get_land_color(level),
+                    false => WATER_COLOR,
+                };
+                let top = (
+                    origin.0 + (x as f64 - y as f64) * half_width,
+                    origin.1 + diagonal as f64 * half_height - (level + 1) as f64 * side_height,
+                );
+                draw_block(&mut image, top, options.block_size, color);
+            }
+        }
+    }
+
+    image
+}
+
+/// Parses how high the water goes, from 0 to 1
+fn parse_water_level(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(level) if (0.0..=1.0).contains(&level) => Ok(level),
+        Ok(_) => Err("must be from 0 to 1".to_string()),
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+#[derive(clap::Args, Debug)]
+pub struct IsometricArgs {
+    /// How many squares there are along each side of the terrain
+    #[arg(short, long, default_value = "40", value_parser = parse_size)]
+    size: u32,
+
+    /// The most blocks high the terrain gets
+    #[arg(long, default_value = "12", value_parser = parse_size)]
+    max_height: u32,
+
+    /// How many squares across the hills are, bigger gives wider hills
+    #[arg(long, default_value = "16.0")]
+    scale: f64,
+
+    /// How wide each block is in pixels, at least 4
+    #[arg(long, default_value = "16", value_parser = parse_size)]
+    block_size: u32,
+
+    /// How high the water goes, from 0 for none to 1 for everything flooded
+    #[arg(long, default_value = "0.35", value_parser = parse_water_level)]
+    water_level: f64,
+
+    /// Colours the blocks by height, from the start of the palette at the shore,
+    /// instead of going from sand through grass and rock to snow
+    #[arg(short, long)]
+    palette: Option<PaletteType>,
+
+    /// The colour behind the terrain, which can be transparent
+    #[arg(short, long, alias = "background", default_value = "white", value_parser = parse_color)]
+    background_color: Color,
+
+    /// The seed used for the terrain, random if not given
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+pub struct IsometricGenerator;
+
+impl Generator for IsometricGenerator {
+    type Args = IsometricArgs;
+
+    fn name(&self) -> &'static str {
+        "isometric"
+    }
+
+    fn is_animated(&self, _args: &IsometricArgs) -> bool {
+        false
+    }
+
+    fn estimate_memory(&self, args: &IsometricArgs) -> Option<MemoryEstimate> {
+        let options = IsometricOptions::new(
+            args.size,
+            args.max_height,
+            args.scale,
+            args.block_size,
+            args.water_level,
+            args.palette,
+            args.background_color.clone(),
+            args.seed,
+        );
+        let (width, height) = options.get_image_size();
+        // a height for every column of the grid
+        Some(MemoryEstimate::still(width, height).with_pixel_buffer(args.size, args.size, 8))
+    }
+
+    fn generate(&self, args: IsometricArgs) -> Result<Generated, GeneratorError> {
+        if args.scale <= 0.0 || args.block_size < 4 {
+            return Err(GeneratorError::InvalidOptions(
+                "the scale has to be more than 0 and the block size at least 4".to_string(),
+            ));
+        }
+
+        Ok(Generated::Still(generate_isometric_image(
+            IsometricOptions::new(
+                args.size,
+                args.max_height,
+                args.scale,
+                args.block_size,
+                args.water_level,
+                args.palette,
+                args.background_color,
+                args.seed,
+            ),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heights() {
+        let options = IsometricOptions::new(
+            30,
+            10,
+            8.0,
+            16,
+            0.3,
+            None,
+            Color::new(1.0, 1.0, 1.0, 1.0),
+            None,
+        );
+        let noise = Noise::new(&mut fastrand::Rng::with_seed(5));
+        let heights = get_heights(&options, &noise);
+        assert_eq!(heights.len(), 900);
+        // stretched to go all the way from 1 block to the max height
+        assert_eq!(heights.iter().min(), Some(&1));
+        assert_eq!(heights.iter().max(), Some(&10));
+
+        // the shore is sand and the tallest blocks are snow
+        assert_eq!(options.get_land_color(3), TERRAIN_COLORS[0]);
+        assert_eq!(options.get_land_color(9), TERRAIN_COLORS[4]);
+    }
+}
@@ -0,0 +1,233 @@
+//! Reading the layers given to compose from the command line
+//!
+//! Each layer is written the same way as the command line for a still image, like
+//! `sierpinski --color red`, and can start with options for the layer itself given as
+//! name=value, like `opacity=0.5 offset=100,50 blend=multiply sierpinski --color red`
+
+use clap::{FromArgMatches, ValueEnum};
+use fun_images::{
+    BlendMode, compose::Layer, memory::MemoryEstimate, parse_fraction, post::apply_post_processing,
+};
+use image::RgbaImage;
+use rayon::prelude::*;
+
+use crate::{Args, ImageFormat, error::Error, generate_static_image, presets::get_preset_matches};
+
+/// Renders each layer, all at the same time as they don't depend on each other
+pub fn render_layers(layers: &[String]) -> Result<Vec<Layer>, Error> {
+    let layer_args = layers
+        .iter()
+        .map(|layer| parse_layer(layer))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    layer_args
+        .into_par_iter()
+        .map(|(args, layer_options)| {
+            let image = generate_static_image(args.image_type)?;
+            let image = apply_post_processing(image.into_rgba8(), &args.post)
+                .map_err(Error::PostEffects)?;
+            Ok(Layer::new(
+                image,
+                layer_options.opacity,
+                layer_options.offset,
+                layer_options.blend_mode,
+            ))
+        })
+        .collect()
+}
+
+/// Roughly how much memory each layer takes, all of which are held at once to be
+/// composed, or None when a layer can't be parsed
+pub fn estimate_layers_memory(layers: &[String]) -> Option<Vec<MemoryEstimate>> {
+    layers
+        .iter()
+        .map(|layer| {
+            let (args, _) = parse_layer(layer).ok()?;
+            Some(args.image_type.estimate_memory().unwrap_or_default())
+        })
+        .collect()
+}
+
+/// The options for how a layer is drawn, rather than the image in it
+#[derive(Debug)]
+struct LayerOptions {
+    opacity: f64,
+    offset: (i64, i64),
+    blend_mode: BlendMode,
+}
+
+/// Parses a layer into the args for its image and the options for the layer
+fn parse_layer(layer: &str) -> Result<(Args, LayerOptions), Error> {
+    let words = split_words(layer).map_err(|message| layer_error(layer, message))?;
+    let mut layer_options = LayerOptions {
+        opacity: 1.0,
+        offset: (0, 0),
+        blend_mode: BlendMode::Normal,
+    };
+
+    let mut words = words.as_slice();
+    while let Some((name, value)) = words
+        .first()
+        .filter(|word| !word.starts_with('-'))
+        .and_then(|word| word.split_once('='))
+    {
+        match name {
+            "opacity" => {
+                layer_options.opacity = parse_fraction(value)
+                    .map_err(|_| layer_error(layer, "opacity should be a number from 0 to 1"))?;
+            }
+            "offset" => {
+                layer_options.offset = value
+                    .split_once(',')
+                    .and_then(|(x, y)| Some((x.trim().parse().ok()?, y.trim().parse().ok()?)))
+                    .ok_or_else(|| layer_error(layer, "offset should be given as x,y"))?;
+            }
+            "blend" => {
+                layer_options.blend_mode = BlendMode::from_str(value, true).map_err(|_| {
+                    let blend_modes: Vec<_> = BlendMode::value_variants()
+                        .iter()
+                        .filter_map(|blend_mode| blend_mode.to_possible_value())
+                        .map(|value| value.get_name().to_string())
+                        .collect();
+                    layer_error(
+                        layer,
+                        &format!("blend should be one of {}", blend_modes.join(", ")),
+                    )
+                })?;
+            }
+            _ => {
+                return Err(layer_error(
+                    layer,
+                    &format!(
+                        "unknown layer option {}, expected opacity, offset or blend",
+                        name
+                    ),
+                ));
+            }
+        }
+        words = &words[1..];
+    }
+
+    let args = parse_still_image(words).map_err(|message| layer_error(layer, &message))?;
+
+    Ok((args, layer_options))
+}
+
+/// Renders a still image written like a layer without the layer options, for the
+/// commands that warp or filter another image
+pub fn render_pattern(pattern: &str) -> Result<RgbaImage, Error> {
+    let pattern_error =
+        |message: &str| Error::Conformal(format!("pattern \"{}\": {}", pattern, message));
+    let words = split_words(pattern).map_err(pattern_error)?;
+    let args = parse_still_image(&words).map_err(|message| pattern_error(&message))?;
+
+    let image = generate_static_image(args.image_type)?;
+    apply_post_processing(image.into_rgba8(), &args.post).map_err(Error::PostEffects)
+}
+
+/// Roughly how much memory rendering the pattern takes, or None when it can't be parsed
+pub fn estimate_pattern_memory(pattern: &str) -> Option<MemoryEstimate> {
+    let words = split_words(pattern).ok()?;
+    parse_still_image(&words).ok()?.image_type.estimate_memory()
+}
+
+/// Parses the words of the command line for a still image
+fn parse_still_image(words: &[String]) -> Result<Args, String> {
+    let command_line: Vec<String> = std::iter::once("fun-images".to_string())
+        .chain(words.iter().cloned())
+        .collect();
+    let args = get_preset_matches(&command_line)
+        .and_then(|matches| Args::from_arg_matches(&matches))
+        .map_err(|clap_error| {
+            let message = clap_error.to_string();
+            let message = message.lines().next().unwrap_or_default();
+            message.trim_start_matches("error: ").to_string()
+        })?;
+    if !matches!(args.image_type.get_format(), ImageFormat::Static) {
+        return Err("only still images can be used".to_string());
+    }
+
+    Ok(args)
+}
+
+/// Splits a layer or pattern into words on spaces, keeping anything in quotes together so colours
+/// like "rgb(255, 0, 0)" can be used
+fn split_words(line: &str) -> Result<Vec<String>, &'static str> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+
+    for character in line.chars() {
+        match (quote, character) {
+            (Some(open), character) if character == open => quote = None,
+            (Some(_), character) => word.push(character),
+            (None, '"' | '\'') => {
+                quote = Some(character);
+                in_word = true;
+            }
+            (None, character) if character.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (None, character) => {
+                word.push(character);
+                in_word = true;
+            }
+        }
+    }
+    if quote.is_some() {
+        return Err("a quote is never closed");
+    }
+    if in_word {
+        words.push(word);
+    }
+
+    Ok(words)
+}
+
+fn layer_error(layer: &str, message: &str) -> Error {
+    Error::Compose(format!("layer \"{}\": {}", layer, message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_layer() {
+        let (args, layer_options) = parse_layer(
+            "opacity=0.5 offset=-10,20 blend=screen sierpinski --color 'rgb(255, 0, 0)'",
+        )
+        .unwrap();
+        assert!(matches!(
+            args.image_type,
+            crate::ImageType::Sierpinski { .. }
+        ));
+        assert_eq!(layer_options.opacity, 0.5);
+        assert_eq!(layer_options.offset, (-10, 20));
+        assert!(matches!(layer_options.blend_mode, BlendMode::Screen));
+
+        assert!(parse_layer("blur=2 sierpinski").is_err());
+        assert!(parse_layer("blend=burn sierpinski").is_err());
+        assert!(parse_layer("plasma-effect").is_err());
+        assert!(parse_layer("sierpinski --color 'red").is_err());
+    }
+
+    #[test]
+    fn test_estimate_layers_memory() {
+        let estimates = estimate_layers_memory(&[
+            "mandelbrot --width 100 --height 50".to_string(),
+            "opacity=0.5 phoenix --width 20 --height 10".to_string(),
+        ])
+        .unwrap();
+        assert_eq!(estimates[0].output, 100 * 50 * 4);
+        assert_eq!(estimates[1].output, 20 * 10 * 4);
+        assert!(estimate_layers_memory(&["plasma-effect".to_string()]).is_none());
+
+        let estimate = estimate_pattern_memory("phoenix --width 20 --height 10").unwrap();
+        assert_eq!(estimate.output, 20 * 10 * 4);
+    }
+}
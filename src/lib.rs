@@ -0,0 +1,455 @@
+//! Generators for fun images
+//!
+//! Everything here works on images in memory, so it runs anywhere including the browser,
+//! while the command line in main.rs deals with files and the terminal
+
+use clap::ValueEnum;
+
+pub mod aliquot;
+pub mod anaglyph;
+pub mod audio_wave;
+pub mod bit_pattern;
+pub mod cancel;
+pub mod circle_packing;
+pub mod collatz;
+pub mod compose;
+pub mod conformal;
+pub mod contours;
+pub mod digit_walk;
+pub mod euler_spiral;
+pub mod farey;
+pub mod fire;
+pub mod flow_field;
+pub mod formula;
+pub mod generator;
+pub mod goldbach;
+#[cfg(feature = "gpu")]
+mod gpu;
+pub mod hillshade;
+pub mod hyperbolic;
+pub mod ising;
+pub mod isometric;
+pub mod mandala;
+pub mod mandelbrot;
+pub mod mandelbulb;
+pub mod matrix_rain;
+pub mod maze;
+pub mod memory;
+pub mod multiplicative;
+pub mod palette;
+pub mod particles;
+pub mod perlin;
+pub mod plasma;
+pub mod post;
+pub mod prime_race;
+pub mod quat_julia;
+pub mod raymarch;
+pub mod reaction_diffusion;
+#[cfg(feature = "script")]
+pub mod script;
+pub mod sierpinski;
+pub mod starfield;
+pub mod stylize;
+pub mod text;
+pub mod theodorus;
+pub mod tiles;
+pub mod times_table;
+pub mod truchet;
+pub mod tunnel;
+pub mod ulam_spiral;
+pub mod vector_field;
+#[cfg(feature = "wasm")]
+mod wasm;
+pub mod waves;
+pub mod xor_pattern;
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum UlamSpiralMode {
+    /// Generates pixels for the primes only
+    PrimeOnly,
+    /// Generates circles based on how many divisors a number has
+    Divisor,
+}
+
+/// Where the digits for a digit walk come from
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum DigitSource {
+    /// The digits of π, 3.14159...
+    Pi,
+    /// The digits of e, 2.71828...
+    E,
+}
+
+/// How each digit moves the walker in a digit walk
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum DigitWalkMode {
+    /// Turns by the digit times a tenth of the way round before each step, so 0 keeps
+    /// going straight on
+    Turn,
+    /// Steps in the direction the digit points, like the hours on a clock with 10 of them
+    Heading,
+}
+
+/// What gets drawn as a bitmap, one bit per cell
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum BitPattern {
+    /// The bits of π in binary, 11.00100100..., read across each row in turn
+    Pi,
+    /// The bits of e in binary, 10.10110111..., read across each row in turn
+    E,
+    /// Row n and column k combined with a bitwise and
+    And,
+    /// Row n and column k combined with a bitwise or
+    Or,
+    /// Row n and column k combined with a bitwise xor
+    Xor,
+    /// The binomial coefficient n choose k, which is Pascal's triangle down the rows
+    Binomial,
+}
+
+/// How a sound is drawn across the image
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum AudioWaveMode {
+    /// The highest and lowest sample under each column, like an audio editor shows
+    Waveform,
+    /// How loud each column is on average, mirrored above and below the middle
+    Envelope,
+    /// How loud each frequency is over time, from a Fourier transform of the samples
+    /// around each column, with the low frequencies at the bottom
+    Spectrogram,
+}
+
+/// How a photo is redrawn by stylize
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum StylizeMode {
+    /// A grid of dots like the divisor spiral, bigger where the photo is brighter
+    Dots,
+    /// Truchet tiles turned so their bands run along the edges in the photo
+    Truchet,
+    /// Flow field strokes following the edges in the photo
+    Flow,
+}
+
+/// What the xor pattern shows
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum XorPatternMode {
+    /// Colours each pixel by x xor y modulo a number
+    Modulo,
+    /// The munching squares display hack, which fills in the pixels where x xor y is
+    /// below a threshold that sweeps up and then eats them away again
+    Munch,
+}
+
+/// How the numbers are laid out in a grid
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum GridLayout {
+    /// Left to right along each row, then on to the next row down
+    RowMajor,
+    /// Spiralling out from 1 in the middle like the Ulam spiral
+    Spiral,
+}
+
+/// A function of each number that can be plotted on the spiral instead
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum ArithmeticFunction {
+    /// Euler's totient φ(n), how many numbers up to n share no factors with it, shown
+    /// as a fraction of n
+    Totient,
+    /// The sum of the divisors σ(n), shown as how much more than n it is
+    Sigma,
+    /// How many different primes divide the number, ω(n)
+    Omega,
+    /// The Möbius function μ(n), 0 if a square divides the number and otherwise 1 or -1
+    /// for an even or odd number of prime factors
+    Mobius,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum WaveType {
+    /// Generates a sine wave
+    Sine,
+    /// Generates a cosine wave
+    Cosine,
+    /// Generates a tangent wave
+    Tangent,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum WaveMode {
+    /// Sweeps the dot across the image following y = f(x)
+    Sweep,
+    /// Moves the dot along x(t) and y(t), each a separate wave
+    Parametric,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum ConformalMap {
+    /// 1/z, which turns the plane inside out around the unit circle
+    Reciprocal,
+    /// z^2, which doubles every angle around 0
+    Square,
+    /// e^z, which wraps horizontal lines into rays and vertical lines into circles
+    Exp,
+    /// (az + b) / (cz + d), which sends circles and lines to other circles and lines
+    Mobius,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum FractalVariant {
+    /// z = z^2 + c
+    #[default]
+    Standard,
+    /// Also known as the Mandelbar set, z = conj(z)^2 + c
+    Tricorn,
+    /// z^2 + c with the real part of z^2 made positive first
+    Celtic,
+    /// z = (re(z) - i|im(z)|)^2 + c
+    PerpendicularBurningShip,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum MagnetType {
+    /// z = ((z^2 + c - 1) / (2z + c - 2))^2
+    One,
+    /// The cubic version of type one, which has more detail around its edges
+    Two,
+}
+
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum FractalColoring {
+    /// Colours the points outside the set by how many iterations they took to escape
+    #[default]
+    Iterations,
+    /// Only colours the points where z ended up below the real axis when it escaped,
+    /// which shows the external field lines of the set
+    BinaryDecomposition,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum VectorFieldMode {
+    /// An arrow on a grid of points, longer where the field is stronger
+    Arrows,
+    /// Lines which follow the field from a grid of starting points
+    Streamlines,
+    /// Line integral convolution, noise smeared along the field so the whole image
+    /// shows which way it goes
+    Lic,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum QuaternionJuliaMode {
+    /// The 3D slice raymarched and lit like the Mandelbulb
+    Surface,
+    /// A flat cross-section through the 3D slice, coloured by how fast points escape
+    Section,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum SierpinskiMode {
+    /// Draws the outlines of the triangles inside each other
+    Lines,
+    /// Plays the chaos game, jumping halfway to a random corner and plotting a point
+    /// each time
+    ChaosGame,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum PaletteType {
+    /// Cycles through all the hues
+    Rainbow,
+    /// Reds, oranges and yellows fading to black
+    Fire,
+    /// Deep blues and teals
+    Ocean,
+    /// Fast changing, high contrast colours
+    Psychedelic,
+    /// Black through to white
+    Grayscale,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum PackingShape {
+    /// Fills the whole image
+    Canvas,
+    Circle,
+    /// A triangle pointing up
+    Triangle,
+    Hexagon,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum PackingColoring {
+    /// Picks the colour from the palette by how big the circle is
+    Radius,
+    /// Picks a random colour from the palette for each circle
+    Random,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum TunnelTexture {
+    /// Alternating squares of the two colours
+    Checkerboard,
+    /// Smooth random noise blending between the two colours
+    Noise,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum MazeAlgorithm {
+    /// Depth first search, gives long winding corridors
+    RecursiveBacktracker,
+    /// Randomised Prim's algorithm, gives lots of short dead ends
+    Prims,
+    /// Randomised Kruskal's algorithm, joins up random areas of the maze
+    Kruskals,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum MazeSolver {
+    /// Breadth first search, spreads out evenly from the start
+    Bfs,
+    /// Depth first search, follows one path at a time
+    Dfs,
+    /// A* search, heads towards the goal
+    Astar,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum TuringPreset {
+    /// Spots which keep splitting in two
+    Spots,
+    /// Coral like stripes
+    Stripes,
+    /// Winding maze like lines
+    Maze,
+    /// Pulsing waves
+    Waves,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum BlendMode {
+    /// Draws the colours on top, hiding what's below
+    Normal,
+    /// Multiplies the colours together, which darkens
+    Multiply,
+    /// Multiplies the inverted colours and inverts them back, which lightens
+    Screen,
+    /// Multiplies the dark parts and screens the light parts, which adds contrast
+    Overlay,
+    /// Adds the colours together
+    Add,
+    /// Takes the difference between the colours
+    Difference,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum ImageStyle {
+    /// Leaves the image as it was generated
+    Normal,
+    /// Redraws the image as a grid of dots which change size with the darkness
+    Halftone,
+}
+
+/// Which way the runs of pixels go when pixel sorting
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum SortDirection {
+    /// Sorts along each row, smearing the image sideways
+    Horizontal,
+    /// Sorts down each column, so it looks like it's dripping
+    Vertical,
+}
+
+/// What the pixels are put in order by when pixel sorting
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum SortKey {
+    /// From dark to bright
+    Luminance,
+    /// Around the colour wheel from red through green and blue
+    Hue,
+}
+
+/// How the image is dithered when it's reduced to pure black and white
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum DitherMethod {
+    /// Every pixel is black or white depending on whether it's brighter than halfway,
+    /// which gives flat areas with hard edges
+    Threshold,
+    /// Uses a repeating grid of thresholds, which gives a regular crosshatch pattern
+    /// like old computer graphics
+    Bayer,
+    /// Uses thresholds spread out evenly at random, which gives a fine grain with no
+    /// visible pattern
+    BlueNoise,
+}
+
+/// How an image is scaled to a size with a different aspect ratio
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Fit {
+    /// Stretches the image to exactly the size
+    Stretch,
+    /// Scales the image to fit inside the size, leaving the rest transparent
+    Contain,
+    /// Scales the image to cover the size, cutting off whatever doesn't fit
+    Cover,
+}
+
+/// Where something drawn over the image goes
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Placement {
+    TopLeft,
+    Top,
+    TopRight,
+    Centre,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum HalftoneInk {
+    /// Cyan, magenta, yellow and black dots at different angles, like a printed page
+    Cmyk,
+    /// Dots of a single colour
+    Single,
+}
+
+/// Parses a size, which has to be at least 1 as nothing can be drawn at size 0
+pub fn parse_size(value: &str) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(0) => Err("must be at least 1".to_string()),
+        Ok(size) => Ok(size),
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+/// Parses a fraction like an opacity or how much of an effect to use, from 0 to 1
+pub fn parse_fraction(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(number) if (0.0..=1.0).contains(&number) => Ok(number),
+        Ok(_) => Err("must be from 0 to 1".to_string()),
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+/// Parses a complex number given as real,imaginary
+pub fn parse_complex(value: &str) -> Result<num_complex::Complex64, String> {
+    let (real, imaginary) = value
+        .split_once(',')
+        .ok_or_else(|| "expected real,imaginary".to_string())?;
+    let parse_part = |part: &str| {
+        part.trim()
+            .parse::<f64>()
+            .map_err(|error| error.to_string())
+    };
+
+    Ok(num_complex::Complex64::new(
+        parse_part(real)?,
+        parse_part(imaginary)?,
+    ))
+}
+
+/// Parses a colour argument, with a hint at what's accepted when it can't be parsed
+pub fn parse_color(value: &str) -> Result<csscolorparser::Color, String> {
+    csscolorparser::parse(value).map_err(|_| {
+        "expected a colour name like red, a hex code like #ff0000 or rgb(255, 0, 0)".to_string()
+    })
+}
@@ -0,0 +1,54 @@
+//! Messages about what's happening, written to stderr so stdout is left for output
+//! like ASCII art and reports
+//!
+//! Info and warnings are shown by default, `--quiet` only shows errors and each `-v`
+//! shows more detail, like how long each stage took. Other crates' messages are only
+//! shown if they're warnings or errors, as their debug output is very noisy
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+struct StderrLogger {
+    level: LevelFilter,
+}
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        let is_ours = metadata.target().starts_with("fun_images");
+        metadata.level() <= self.level && (is_ours || metadata.level() <= Level::Warn)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        match record.level() {
+            Level::Error => eprintln!("Error {}", record.args()),
+            Level::Warn | Level::Info => eprintln!("{}", record.args()),
+            Level::Debug | Level::Trace => {
+                eprintln!(
+                    "[{}] {}",
+                    record.level().as_str().to_lowercase(),
+                    record.args()
+                )
+            }
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Starts logging at the level the flags ask for
+pub fn init_logging(verbose: u8, quiet: bool) {
+    let level = match (quiet, verbose) {
+        (true, _) => LevelFilter::Error,
+        (false, 0) => LevelFilter::Info,
+        (false, 1) => LevelFilter::Debug,
+        (false, _) => LevelFilter::Trace,
+    };
+
+    // this can only fail if a logger was already set, which would be a bug
+    let logger = Box::leak(Box::new(StderrLogger { level }));
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(level);
+    }
+}
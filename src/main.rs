@@ -1,137 +1,1161 @@
-use std::{fs::File, io::BufWriter, time::Instant};
+use std::{
+    fs,
+    io::Write,
+    process::ExitCode,
+    time::{Duration, Instant},
+};
 
-use clap::{Parser, Subcommand, ValueEnum};
+use clap::{ArgMatches, Command, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
 use csscolorparser::Color;
-use image::RgbaImage;
+use image::{DynamicImage, Rgba, RgbaImage, imageops};
+use log::{debug, error, info, warn};
+use num_complex::Complex64;
+use rayon::prelude::*;
+
+use fun_images::{
+    ArithmeticFunction, ConformalMap, FractalColoring, FractalVariant, PaletteType, SierpinskiMode,
+    UlamSpiralMode,
+    cancel::{cancel, is_cancelled},
+    compose::{apply_onion_skin, compose_layers},
+    conformal::{ConformalOptions, ConformalSource, Mobius, generate_conformal_image},
+    contours::ContourOptions,
+    formula::Formula,
+    generator::{Generated, GeneratorError, GeneratorRegistry},
+    mandelbrot::{
+        FractalSet, MandelbrotImageOptions, OrbitOverlay, ViewPort, generate_fractal_indexed_image,
+        generate_julia_atlas, generate_mandelbrot_image, generate_mandelbrot_tile, get_orbit,
+    },
+    memory::{MemoryEstimate, format_bytes, parse_memory},
+    palette::generate_palette_cycle_images,
+    parse_color, parse_complex, parse_size,
+    post::{PostArgs, apply_framing, apply_post_effects},
+    sierpinski::{
+        ChaosGameOptions, check_sierpinski_size, generate_chaos_game_image,
+        generate_chaos_game_images, generate_sierpinski_image, generate_sierpinski_rotate_images,
+        generate_sierpinski_zoom_images,
+    },
+    tiles::{Tile, TileGrid},
+    ulam_spiral::{
+        UlamSpiralOptions, UlamZoomOptions, generate_ulam_spiral_image, generate_ulam_spiral_tile,
+        generate_ulam_spiral_zoom_images,
+    },
+};
 
 use crate::{
-    farey::generate_farey_sunburst,
-    mandelbrot::{MandelbrotImageOptions, generate_mandelbrot_image},
-    perlin::{PerlinNoiseOptions, generate_perlin_noise},
-    sierpinski::{generate_sierpinski_image, generate_sierpinski_zoom_images},
-    ulam_spiral::{UlamSpiralOptions, generate_ulam_spiral_image},
-    waves::{WaveOptions, generate_wave_images},
+    animate::render_param_frames,
+    ascii::image_to_ascii,
+    checkpoint::Checkpoint,
+    error::Error,
+    explore::{ExploreOptions, run_explorer},
+    gallery::render_gallery,
+    layers::{estimate_layers_memory, estimate_pattern_memory, render_layers, render_pattern},
+    logging::init_logging,
+    output::{
+        FileFormat, Playback, get_output_format, open_frames, save_animation, save_frames,
+        save_image, save_tiled_png,
+    },
+    overlay::{OverlayArgs, Overlays},
+    presets::{add_preset_args, print_presets},
+    preview::preview_image,
+    random::{Date, get_random_recipe, parse_date},
+    recipe::{
+        apply_override, apply_width, get_clap_error, get_recipe, get_seeded_matches, parse_recipe,
+        read_recipe,
+    },
+    report::{Report, ReportFormat, get_parameters},
+    schema::print_schema,
+    serve::{ServeOptions, run_server},
+    sweep::{SweepAxis, estimate_sweep_memory, parse_sweep_axis, render_sweep},
+    wallpaper::set_wallpaper,
 };
 
-mod farey;
-mod mandelbrot;
-mod perlin;
-mod sierpinski;
-mod ulam_spiral;
-mod waves;
+mod animate;
+mod ascii;
+mod checkpoint;
+mod error;
+mod explore;
+mod gallery;
+#[cfg(feature = "gui")]
+mod gui;
+mod layers;
+mod logging;
+mod output;
+mod overlay;
+
+mod presets;
+mod preview;
+mod random;
+mod recipe;
+mod report;
+mod schema;
+mod serve;
+mod sweep;
+mod wallpaper;
+
+/// Roughly how many pixels of a streamed PNG are rendered at once, 16MB of RGBA
+const STREAM_BAND_PIXELS: u64 = 1 << 22;
+
+fn main() -> ExitCode {
+    let mut words: Vec<String> = std::env::args().collect();
+    let matches = get_seeded_matches(&mut words).unwrap_or_else(|clap_error| clap_error.exit());
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|clap_error| clap_error.exit());
+    args.recipe = get_recipe(&matches);
+    args.parameters = get_parameters(&matches);
+    init_logging(args.verbose, args.quiet);
 
-fn main() {
-    let args = Args::parse();
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            error!("{}", error);
+            error.exit_code()
+        }
+    }
+}
+
+fn run(args: Args) -> Result<(), Error> {
+    if let Some(threads) = args.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()?;
+    }
+
+    #[cfg(feature = "gui")]
+    if args.gui {
+        let output = args.get_output();
+        return gui::run_gui(args.image_type, args.post, output);
+    }
+
+    let format = args.get_format();
+    let report = match (args.report, &format) {
+        (Some(ReportFormat::Json), ImageFormat::Static | ImageFormat::Animated) => {
+            Some(Report::start(&args))
+        }
+        _ => None,
+    };
+
+    run_format(args, format)?;
+    if let Some(report) = report {
+        report.finish()?;
+    }
+
+    Ok(())
+}
+
+/// Makes whatever the format is, whether that's saving an image or running a server
+fn run_format(args: Args, format: ImageFormat) -> Result<(), Error> {
+    if let ImageFormat::Static | ImageFormat::Animated = format {
+        handle_ctrl_c(args.save_partial);
+    }
+    if let Some(tile_grid) = args.tiles
+        && args.animate_param.is_none()
+    {
+        return save_tiled_image(args, tile_grid);
+    }
 
-    let format = args.image_type.get_format();
     match format {
-        ImageFormat::Static => {
-            save_static_image(args);
+        ImageFormat::Static => save_static_image(args),
+        ImageFormat::Animated => save_animated_image(args),
+        ImageFormat::Interactive => explore_fractal(args),
+        ImageFormat::Server => {
+            let ImageType::Serve {
+                host,
+                port,
+                cache_size,
+            } = args.image_type
+            else {
+                unreachable!()
+            };
+            run_server(ServeOptions::new(host, port, cache_size))
         }
-        ImageFormat::Animated => {
-            save_animated_image(args);
+        ImageFormat::Reproduce => reproduce(args),
+        ImageFormat::Presets => {
+            let ImageType::Presets { image_type } = &args.image_type else {
+                unreachable!()
+            };
+            print_presets(image_type.as_deref()).or_else(|io_error| match io_error.kind() {
+                // the output was closed early, like when it's piped into head
+                std::io::ErrorKind::BrokenPipe => Ok(()),
+                _ => Err(Error::PrintPresets(io_error)),
+            })
+        }
+        ImageFormat::Completions => {
+            let ImageType::Completions { shell } = args.image_type else {
+                unreachable!()
+            };
+            let mut command = Args::command();
+            let name = command.get_name().to_string();
+            clap_complete::generate(shell, &mut command, name, &mut std::io::stdout());
+            Ok(())
+        }
+        ImageFormat::Schema => {
+            let ImageType::Schema { json } = args.image_type else {
+                unreachable!()
+            };
+            print_schema(json);
+            Ok(())
+        }
+        ImageFormat::Random => make_random_image(args),
+        ImageFormat::Gallery => {
+            let ImageType::Gallery {
+                out,
+                thumbnail_size,
+            } = args.image_type
+            else {
+                unreachable!()
+            };
+            render_gallery(&out, thumbnail_size, args.force)
         }
     }
 }
 
-fn save_static_image(args: Args) {
+/// Makes an image again from the recipe stored in it, with any changes asked for
+fn reproduce(args: Args) -> Result<(), Error> {
+    let ImageType::Reproduce {
+        input,
+        width,
+        overrides,
+    } = &args.image_type
+    else {
+        unreachable!()
+    };
+
+    let mut recipe = read_recipe(input)?;
+    if let Some(width) = width {
+        apply_width(&mut recipe, *width)?;
+    }
+    for option in overrides.iter() {
+        apply_override(&mut recipe, option)?;
+    }
+
+    run_recipe(&recipe, args)
+}
+
+/// Makes a random image, printing the command line for it so it can be made again
+fn make_random_image(args: Args) -> Result<(), Error> {
+    let (seed, animated) = match &args.image_type {
+        ImageType::Random { seed, animated } => {
+            (seed.unwrap_or_else(|| fastrand::u64(..)), *animated)
+        }
+        ImageType::Daily { date } => {
+            let date = date.unwrap_or_else(Date::today);
+            info!("The image for {}", date);
+            (date.get_seed(), false)
+        }
+        _ => unreachable!(),
+    };
+
+    let mut recipe = get_random_recipe(seed, animated);
+    // other options like post effects are kept for the image that was picked, leaving
+    // out the ones for picking it
+    let command = Args::command();
+    let own_options: Vec<_> = command
+        .find_subcommand(args.recipe.first().map(String::as_str).unwrap_or_default())
+        .into_iter()
+        .flat_map(|subcommand| subcommand.get_arguments())
+        .filter_map(|arg| arg.get_long())
+        .map(|long| format!("--{}", long))
+        .collect();
+    recipe.extend(
+        args.recipe
+            .iter()
+            .skip(1)
+            .filter(|arg| {
+                !own_options
+                    .iter()
+                    .any(|option| *arg == option || arg.starts_with(&format!("{}=", option)))
+            })
+            .cloned(),
+    );
+    println!("fun-images {}", recipe.join(" "));
+
+    run_recipe(&recipe, args)
+}
+
+/// Makes the image from the recipe, saving it where this run's args say
+fn run_recipe(recipe: &[String], args: Args) -> Result<(), Error> {
+    let matches = parse_recipe(recipe)?;
+    let mut reproduced_args = Args::from_arg_matches(&matches).map_err(get_clap_error)?;
+    if let ImageFormat::Reproduce | ImageFormat::Random = reproduced_args.image_type.get_format() {
+        return Err(Error::Reproduce(
+            "the recipe can't be another reproduce".to_string(),
+        ));
+    }
+    reproduced_args.recipe = get_recipe(&matches);
+    reproduced_args.parameters = get_parameters(&matches);
+    // where the image goes comes from this run rather than the original
+    reproduced_args.output = args.output;
+    reproduced_args.frames_dir = args.frames_dir;
+    reproduced_args.force = args.force;
+    reproduced_args.preview = args.preview;
+    reproduced_args.set_wallpaper = args.set_wallpaper;
+    reproduced_args.save_partial = args.save_partial;
+    reproduced_args.memory_budget = args.memory_budget;
+    reproduced_args.yes = args.yes;
+    reproduced_args.checkpoint = args.checkpoint;
+    reproduced_args.resume = args.resume;
+    reproduced_args.report = args.report;
+    reproduced_args.report_file = args.report_file;
+    reproduced_args.output_format = args.output_format;
+    reproduced_args.ascii_width = args.ascii_width;
+    reproduced_args.ascii_color = args.ascii_color;
+    reproduced_args.tiles = args.tiles;
+
+    run(reproduced_args)
+}
+
+/// Stops the render on the first Ctrl-C so it can finish cleanly, and quits straight
+/// away on the second
+fn handle_ctrl_c(save_partial: bool) {
+    let result = ctrlc::set_handler(move || {
+        if is_cancelled() {
+            std::process::exit(130);
+        }
+        cancel();
+        match save_partial {
+            true => warn!("Stopping to save what's finished, press Ctrl-C again to quit now"),
+            false => warn!("Stopping, press Ctrl-C again to quit now"),
+        }
+    });
+    if let Err(ctrlc_error) = result {
+        debug!("Not handling Ctrl-C: {}", ctrlc_error);
+    }
+}
+
+fn save_static_image(args: Args) -> Result<(), Error> {
+    let output = args.get_output();
+    if args.frames_dir.is_some() {
+        warn!("Only animations have frames, ignoring --frames-dir");
+    }
+    // checked before generating so a bad file name doesn't waste a long render
+    let file_format = match args.output_format {
+        OutputFormat::File => Some(get_output_format(
+            &output,
+            false,
+            args.force || args.resume,
+        )?),
+        OutputFormat::Ascii => None,
+    };
+
+    // PNGs are written a band of rows at a time when the generator can render part of
+    // the image, so big images don't have to fit in memory all at once
+    if let Some(FileFormat::Png | FileFormat::Apng) = file_format
+        && !args.has_effects()
+        && let Some(renderer) = get_tile_renderer(&args.image_type)?
+    {
+        let band_height = (STREAM_BAND_PIXELS / renderer.width as u64).max(1) as u32;
+        let tile_grid = TileGrid::new(1, renderer.height.div_ceil(band_height));
+        return save_streamed_png(&args, &output, renderer, tile_grid);
+    }
+    if let Some(path) = &args.checkpoint {
+        return Err(Error::Checkpoint {
+            path: path.clone(),
+            message: "only mandelbrot and ulam-spiral images saved as PNG without post effects \
+                      can be checkpointed"
+                .to_string(),
+        });
+    }
+    check_memory(&args)?;
+    let overlays = Overlays::open(&args.overlay)?;
+    let start = Instant::now();
+
+    let image = generate_static_image(args.image_type)?;
+    debug!("Generation took {}ms", start.elapsed().as_millis());
+    let post_start = Instant::now();
+    let image = DynamicImage::ImageRgba8(apply_effects(image.into_rgba8(), &args.post, &overlays)?);
+    debug!(
+        "Post processing took {}ms",
+        post_start.elapsed().as_millis()
+    );
+    let end = Instant::now();
+    // the slower still images stop part way through when cancelled, so they're only
+    // saved like that if asked for
+    if is_cancelled() {
+        if !args.save_partial {
+            return Err(Error::Cancelled);
+        }
+        warn!("The image was cancelled part way through, so some of it may be missing");
+    }
+
+    let Some(file_format) = file_format else {
+        print!(
+            "{}",
+            image_to_ascii(
+                image.as_rgba8().unwrap(),
+                args.ascii_width,
+                args.ascii_color
+            )
+        );
+        return Ok(());
+    };
+    info!("Generated image in {}ms", (end - start).as_millis());
+
+    let encode_start = Instant::now();
+    save_image(&image, &output, file_format, &args.recipe)?;
+    debug!("Encoding took {}ms", encode_start.elapsed().as_millis());
+    info!("Saved image to {}", &output);
+    if args.preview {
+        preview_image(image.as_rgba8().unwrap(), &output);
+    }
+    if args.set_wallpaper {
+        set_wallpaper(&output)?;
+    }
+
+    Ok(())
+}
+
+/// Renders and saves an image as a grid of tiles given with --tiles
+fn save_tiled_image(args: Args, tile_grid: TileGrid) -> Result<(), Error> {
+    let output = args.get_output();
+    if let OutputFormat::Ascii = args.output_format {
+        return Err(Error::Tiles(
+            "tiled images can only be saved to a file".to_string(),
+        ));
+    }
+    if args.has_effects() {
+        return Err(Error::Tiles(
+            "post effects need the whole image, so can't be used with tiles".to_string(),
+        ));
+    }
+    if !matches!(
+        get_output_format(&output, false, args.force || args.resume)?,
+        FileFormat::Png | FileFormat::Apng
+    ) {
+        return Err(Error::Tiles(format!(
+            "saving to {}: tiled images can only be saved as png",
+            output
+        )));
+    }
+    let renderer = get_tile_renderer(&args.image_type)?.ok_or_else(|| {
+        Error::Tiles("only mandelbrot and ulam-spiral images can be rendered in tiles".to_string())
+    })?;
+
+    save_streamed_png(&args, &output, renderer, tile_grid)
+}
+
+/// Saves a PNG a row of tiles at a time, so only that much of the image is ever in
+/// memory
+fn save_streamed_png(
+    args: &Args,
+    output: &str,
+    renderer: TileRenderer,
+    tile_grid: TileGrid,
+) -> Result<(), Error> {
     let start = Instant::now();
 
-    let image = match args.image_type {
+    let tile_rows = tile_grid.get_tile_rows(renderer.width, renderer.height);
+    let mut checkpoint = match &args.checkpoint {
+        Some(path) => Some(Checkpoint::open(
+            path,
+            &args.recipe,
+            renderer.width,
+            renderer.height,
+            tile_rows.len(),
+            args.resume,
+        )?),
+        None => None,
+    };
+    if let Some(checkpoint) = &checkpoint
+        && checkpoint.finished_rows() > 0
+    {
+        info!(
+            "Resuming with {} of {} rows already rendered",
+            checkpoint.finished_rows(),
+            tile_rows.len()
+        );
+    }
+
+    let mut row_index = 0;
+    let render_row = |tiles: &[Tile]| {
+        let index = row_index;
+        row_index += 1;
+        if let Some(checkpoint) = &mut checkpoint
+            && index < checkpoint.finished_rows()
+        {
+            return checkpoint.read_row(index).map(Some);
+        }
+        // once cancelled the rest of the rows are left transparent, or the file is
+        // abandoned if the partial image isn't wanted. A row cancelled part way through
+        // counts as not done, so it never goes in the checkpoint
+        let cancelled_row = || {
+            args.save_partial
+                .then(|| RgbaImage::new(renderer.width, tiles[0].height))
+        };
+        if is_cancelled() {
+            return Ok(cancelled_row());
+        }
+
+        let row = render_tile_row(&renderer, tiles);
+        if is_cancelled() {
+            return Ok(cancelled_row());
+        }
+        if let Some(checkpoint) = &mut checkpoint {
+            checkpoint.save_row(&row)?;
+        }
+        Ok(Some(row))
+    };
+    save_tiled_png(
+        output,
+        renderer.width,
+        renderer.height,
+        &tile_rows,
+        render_row,
+        &args.recipe,
+    )?;
+    if is_cancelled() {
+        if let Some(path) = &args.checkpoint {
+            info!(
+                "The finished rows are saved in {}, use --resume to carry on",
+                path
+            );
+        }
+        if !args.save_partial {
+            let _ = fs::remove_file(output);
+            return Err(Error::Cancelled);
+        }
+        warn!("Only the rows finished before cancelling were rendered, the rest are transparent");
+    } else if let Some(checkpoint) = checkpoint {
+        checkpoint.remove()?;
+    }
+    info!(
+        "Generated and saved {}x{} image in {}ms",
+        renderer.width,
+        renderer.height,
+        start.elapsed().as_millis()
+    );
+    info!("Saved image to {}", output);
+    if args.preview {
+        // the image was never all in memory, so it has to be read back to show it
+        let image = image::open(output).map_err(|image_error| Error::OpenImage {
+            path: output.to_string(),
+            source: image_error,
+        })?;
+        preview_image(&image.into_rgba8(), output);
+    }
+    if args.set_wallpaper {
+        set_wallpaper(output)?;
+    }
+
+    Ok(())
+}
+
+/// Renders the tiles in a row together and puts them side by side
+fn render_tile_row(renderer: &TileRenderer, tiles: &[Tile]) -> RgbaImage {
+    let images: Vec<RgbaImage> = tiles
+        .par_iter()
+        .map(|&tile| (renderer.render_tile)(tile))
+        .collect();
+
+    let mut row = RgbaImage::new(renderer.width, tiles[0].height);
+    for (tile, image) in tiles.iter().zip(images.iter()) {
+        imageops::replace(&mut row, image, tile.x as i64, 0);
+    }
+    row
+}
+
+/// Renders any part of an image, for the generators that can draw a piece of the image
+/// without the rest of it
+struct TileRenderer {
+    width: u32,
+    height: u32,
+    render_tile: Box<dyn Fn(Tile) -> RgbaImage + Sync>,
+}
+
+fn get_tile_renderer(image_type: &ImageType) -> Result<Option<TileRenderer>, Error> {
+    let renderer = match image_type.clone() {
+        ImageType::Mandelbrot {
+            color,
+            background_color,
+            gradient,
+            center,
+            zoom,
+            width,
+            height,
+            gpu,
+            formula,
+            variant,
+            coloring,
+            show_orbit,
+            orbit_color,
+            // each cell of an atlas needs the whole of its julia set
+            julia_atlas: None,
+            contours,
+            contour_color,
+            palette_cycle: false,
+            ..
+        } => {
+            let viewport = ViewPort::new(center, zoom);
+            let orbit_overlay = get_orbit_overlay(
+                show_orbit,
+                formula.as_ref(),
+                variant,
+                viewport,
+                width,
+                height,
+                orbit_color,
+            );
+            let options = MandelbrotImageOptions::new(
+                color,
+                background_color,
+                gradient,
+                gpu,
+                formula,
+                coloring,
+                variant,
+                contours.map(|level_num| ContourOptions::new(level_num, contour_color)),
+            );
+            Some(TileRenderer {
+                width,
+                height,
+                render_tile: Box::new(move |tile| {
+                    let mut image =
+                        generate_mandelbrot_tile(&options, viewport, width, height, tile);
+                    if let Some(orbit_overlay) = &orbit_overlay {
+                        orbit_overlay.draw_mut(&mut image, (tile.x, tile.y));
+                    }
+                    image
+                }),
+            })
+        }
         ImageType::UlamSpiral {
             size,
             color,
             mode,
             background_color,
+            value_fn,
+            palette,
+            zoom_to: None,
+            ..
         } => {
-            generate_ulam_spiral_image(UlamSpiralOptions::new(size, color, mode, background_color))
+            let options =
+                UlamSpiralOptions::new(size, color, mode, background_color, value_fn, palette);
+            check_options("ulam-spiral", options.check())?;
+            let dimension = options.get_image_dimension();
+            Some(TileRenderer {
+                width: dimension,
+                height: dimension,
+                render_tile: Box::new(move |tile| generate_ulam_spiral_tile(&options, tile)),
+            })
+        }
+        _ => None,
+    };
+
+    Ok(renderer)
+}
+
+/// Stops before generating when the image would take more memory than the budget, or
+/// just warns about it with --yes
+fn check_memory(args: &Args) -> Result<(), Error> {
+    let Some(estimate) = args.get_memory_estimate() else {
+        return Ok(());
+    };
+    let needed = estimate.total();
+    debug!("Making the image takes about {}", format_bytes(needed));
+    if needed <= args.memory_budget {
+        return Ok(());
+    }
+    if args.yes {
+        warn!(
+            "Making the image takes about {}, more than the {} budget",
+            format_bytes(needed),
+            format_bytes(args.memory_budget)
+        );
+        return Ok(());
+    }
+
+    Err(Error::MemoryBudget {
+        needed: format_bytes(needed),
+        budget: format_bytes(args.memory_budget),
+        suggestion: args.image_type.get_memory_suggestion(),
+    })
+}
+
+/// Turns what's wrong with an image type's options into the error for it
+fn check_options(name: &str, check: Result<(), String>) -> Result<(), Error> {
+    check.map_err(|message| Error::Generator {
+        name: name.to_string(),
+        source: GeneratorError::InvalidOptions(message),
+    })
+}
+
+/// Generates a still image
+fn generate_static_image(image_type: ImageType) -> Result<DynamicImage, Error> {
+    let image = match image_type {
+        ImageType::UlamSpiral {
+            size,
+            color,
+            mode,
+            background_color,
+            value_fn,
+            palette,
+            ..
+        } => {
+            let options =
+                UlamSpiralOptions::new(size, color, mode, background_color, value_fn, palette);
+            check_options("ulam-spiral", options.check())?;
+            generate_ulam_spiral_image(options)
         }
         ImageType::Mandelbrot {
             color,
             background_color,
             gradient,
-        } => generate_mandelbrot_image(MandelbrotImageOptions::new(
+            center,
+            zoom,
+            width,
+            height,
+            gpu,
+            formula,
+            variant,
+            coloring,
+            julia_atlas: Some(grid),
+            contours,
+            contour_color,
+            ..
+        } => generate_julia_atlas(
+            MandelbrotImageOptions::new(
+                color,
+                background_color,
+                gradient,
+                gpu,
+                formula,
+                coloring,
+                variant,
+                contours.map(|level_num| ContourOptions::new(level_num, contour_color)),
+            ),
+            ViewPort::new(center, zoom),
+            width,
+            height,
+            grid,
+        ),
+        ImageType::Mandelbrot {
             color,
             background_color,
             gradient,
-        )),
-        ImageType::Wave { .. } => unreachable!(),
+            center,
+            zoom,
+            width,
+            height,
+            gpu,
+            formula,
+            variant,
+            coloring,
+            show_orbit,
+            orbit_color,
+            julia_atlas: None,
+            contours,
+            contour_color,
+            ..
+        } => {
+            let viewport = ViewPort::new(center, zoom);
+            let orbit_overlay = get_orbit_overlay(
+                show_orbit,
+                formula.as_ref(),
+                variant,
+                viewport,
+                width,
+                height,
+                orbit_color,
+            );
+            let mut image = generate_mandelbrot_image(
+                MandelbrotImageOptions::new(
+                    color,
+                    background_color,
+                    gradient,
+                    gpu,
+                    formula,
+                    coloring,
+                    variant,
+                    contours.map(|level_num| ContourOptions::new(level_num, contour_color)),
+                ),
+                viewport,
+                width,
+                height,
+            )
+            .into_rgba8();
+            if let Some(orbit_overlay) = orbit_overlay {
+                orbit_overlay.draw_mut(&mut image, (0, 0));
+            }
+            DynamicImage::ImageRgba8(image)
+        }
         ImageType::Sierpinski {
             color,
             size,
-            zoom: _,
-        } => generate_sierpinski_image(color, size),
-        ImageType::Perlin {
-            color1,
-            color2,
-            size,
-        } => generate_perlin_noise(PerlinNoiseOptions::new(size, color1, color2)),
-        ImageType::Farey {
+            mode,
+            points,
+            seed,
+            background_color,
+            ..
+        } => match mode {
+            SierpinskiMode::Lines => {
+                check_options("sierpinski", check_sierpinski_size(size))?;
+                generate_sierpinski_image(color, background_color, size)
+            }
+            SierpinskiMode::ChaosGame => {
+                let options = ChaosGameOptions::new(color, background_color, size, points, seed);
+                check_options("sierpinski", options.check())?;
+                generate_chaos_game_image(options)
+            }
+        },
+        ImageType::Compose {
+            layers,
+            width,
+            height,
+            background_color,
+        } => {
+            let layers = render_layers(&layers)?;
+            // the layers are never empty, clap makes sure there's at least one
+            let width = width.unwrap_or(layers[0].width());
+            let height = height.unwrap_or(layers[0].height());
+            DynamicImage::ImageRgba8(compose_layers(width, height, &background_color, &layers))
+        }
+        ImageType::Sweep {
+            x,
+            y,
+            steps,
+            cell_size,
+            image,
+        } => DynamicImage::ImageRgba8(render_sweep(&image, &x, y.as_ref(), steps, cell_size)?),
+        ImageType::Conformal {
+            map,
+            mobius_a,
+            mobius_b,
+            mobius_c,
+            mobius_d,
+            input,
+            pattern,
             color,
-            n,
-            fill_color,
-        } => generate_farey_sunburst(color, n, fill_color),
+            background_color,
+            square_size,
+            center,
+            zoom,
+            width,
+            height,
+        } => {
+            let source = match (open_optional_image(input)?, pattern) {
+                (Some(image), _) => ConformalSource::Image(image.into_rgba8()),
+                (None, Some(pattern)) => ConformalSource::Image(render_pattern(&pattern)?),
+                (None, None) => ConformalSource::Checkerboard {
+                    color,
+                    background_color,
+                    square_size,
+                },
+            };
+            let mobius = Mobius {
+                a: mobius_a,
+                b: mobius_b,
+                c: mobius_c,
+                d: mobius_d,
+            };
+            DynamicImage::ImageRgba8(generate_conformal_image(ConformalOptions::new(
+                map, mobius, source, center, zoom, width, height,
+            )))
+        }
+        ImageType::FramesToAnim { .. } => unreachable!(),
+        ImageType::Registered(image) => match image.generate()? {
+            Generated::Still(image) => DynamicImage::ImageRgba8(image),
+            Generated::Frames(mut frames) => DynamicImage::ImageRgba8(frames.remove(0)),
+        },
+        ImageType::Explore { .. } => unreachable!(),
+        ImageType::Serve { .. } => unreachable!(),
+        ImageType::Reproduce { .. } => unreachable!(),
+        ImageType::Gallery { .. } => unreachable!(),
+        ImageType::Presets { .. } => unreachable!(),
+        ImageType::Completions { .. } => unreachable!(),
+        ImageType::Schema { .. } => unreachable!(),
+        ImageType::Random { .. } => unreachable!(),
+        ImageType::Daily { .. } => unreachable!(),
     };
-    let end = Instant::now();
-    println!("Generated image in {}ms", (end - start).as_millis());
 
-    if let Err(image_error) = image.save(&args.output) {
-        eprintln!("Error saving image: {:?}", image_error);
-    } else {
-        println!("Saved image to {}", &args.output);
+    Ok(image)
+}
+
+fn save_animated_image(args: Args) -> Result<(), Error> {
+    let output = args.get_output();
+    if args.set_wallpaper {
+        return Err(Error::SetWallpaper(
+            "animations can't be wallpapers".to_string(),
+        ));
+    }
+    if let Some(path) = &args.checkpoint {
+        return Err(Error::Checkpoint {
+            path: path.clone(),
+            message: "animations can't be checkpointed".to_string(),
+        });
+    }
+    let file_format = match args.output_format {
+        OutputFormat::File => Some(get_output_format(&output, true, args.force)?),
+        OutputFormat::Ascii => None,
+    };
+    check_memory(&args)?;
+    let overlays = Overlays::open(&args.overlay)?;
+    let playback = args.image_type.get_playback();
+    let start = Instant::now();
+    let images = match &args.animate_param {
+        Some(param) => render_param_frames(&args.recipe, param, args.animate_frames)?,
+        None => generate_animated_images(args.image_type)?,
+    };
+    debug!(
+        "Generating {} frames took {}ms",
+        images.len(),
+        start.elapsed().as_millis()
+    );
+    if is_cancelled() {
+        if !args.save_partial || images.is_empty() {
+            return Err(Error::Cancelled);
+        }
+        warn!(
+            "Saving the {} frames finished before cancelling",
+            images.len()
+        );
+    }
+
+    let post_start = Instant::now();
+    let images: Vec<RgbaImage> = images
+        .into_par_iter()
+        .map(|image| apply_effects(image, &args.post, &overlays))
+        .collect::<Result<_, _>>()?;
+    debug!(
+        "Post processing took {}ms",
+        post_start.elapsed().as_millis()
+    );
+
+    if let Some(directory) = &args.frames_dir {
+        save_frames(&images, directory, args.force, &args.recipe)?;
+        info!("Saved {} frames to {}", images.len(), directory);
+    }
+    let Some(file_format) = file_format else {
+        print_ascii_animation(&images, args.ascii_width, args.ascii_color);
+        return Ok(());
+    };
+    let encode_start = Instant::now();
+    save_animation(&images, &output, file_format, &args.recipe, playback)?;
+    debug!("Encoding took {}ms", encode_start.elapsed().as_millis());
+    info!("Generated animation in {}ms", start.elapsed().as_millis());
+    info!("Saved animation to {}", &output);
+    if args.preview {
+        // the terminal protocols can only show a still image so just show the first frame
+        preview_image(&images[0], &output);
     }
+
+    Ok(())
 }
 
-fn save_animated_image(args: Args) {
-    match args.image_type {
-        ImageType::UlamSpiral { .. } => unreachable!(),
-        ImageType::Mandelbrot { .. } => unreachable!(),
-        ImageType::Wave { color, wave_type } => {
-            let width = 500;
-            let height = 500;
-            let wave_images =
-                generate_wave_images(WaveOptions::new(color, wave_type, width, height));
+fn explore_fractal(args: Args) -> Result<(), Error> {
+    let output = args.get_output();
+    let ImageType::Explore {
+        color,
+        background_color,
+        gradient,
+        export_width,
+        export_height,
+    } = args.image_type
+    else {
+        unreachable!()
+    };
+
+    let options = ExploreOptions::new(
+        MandelbrotImageOptions::new(
+            color,
+            background_color,
+            gradient,
+            false,
+            None,
+            FractalColoring::Iterations,
+            FractalVariant::Standard,
+            None,
+        ),
+        export_width,
+        export_height,
+        output,
+    );
+    run_explorer(options, &args.post).map_err(Error::Explorer)
+}
 
-            save_animated_images_to_file(&args.output, &wave_images, width, height);
+/// Generates the frames of an animation
+fn generate_animated_images(image_type: ImageType) -> Result<Vec<RgbaImage>, Error> {
+    let images = match image_type {
+        ImageType::UlamSpiral {
+            size,
+            color,
+            mode,
+            background_color,
+            value_fn,
+            palette,
+            zoom_to,
+            pan,
+            frames,
+        } => {
+            // the spiral is grown to reach the number if it's past the end
+            let number = zoom_to.expect("only animated when zooming");
+            let options = UlamSpiralOptions::new(
+                size.max(number.saturating_add(1)),
+                color,
+                mode,
+                background_color,
+                value_fn,
+                palette,
+            );
+            check_options("ulam-spiral", options.check())?;
+            generate_ulam_spiral_zoom_images(&options, &UlamZoomOptions::new(number, pan, frames))
+        }
+        ImageType::Mandelbrot {
+            color,
+            background_color,
+            center,
+            zoom,
+            width,
+            height,
+            gpu,
+            formula,
+            variant,
+            coloring,
+            palette,
+            frames,
+            ..
+        } => {
+            let converted_background_color = background_color.to_rgba8();
+            let options = MandelbrotImageOptions::new(
+                color,
+                background_color,
+                false,
+                gpu,
+                formula,
+                coloring,
+                variant,
+                None,
+            );
+            let image = generate_fractal_indexed_image(
+                &options,
+                FractalSet::Mandelbrot,
+                ViewPort::new(center, zoom),
+                width,
+                height,
+            );
+            generate_palette_cycle_images(&image, palette, converted_background_color, frames, 1)
         }
         ImageType::Sierpinski {
             color,
             size,
+            mode,
             zoom: _,
+            rotate,
+            frames,
+            points,
+            animate: _,
+            points_per_frame,
+            seed,
+            background_color,
+        } => match (mode, rotate) {
+            (SierpinskiMode::ChaosGame, _) => {
+                let options = ChaosGameOptions::new(color, background_color, size, points, seed);
+                check_options("sierpinski", options.check())?;
+                generate_chaos_game_images(options, points_per_frame)
+            }
+            (SierpinskiMode::Lines, rotate) => {
+                check_options("sierpinski", check_sierpinski_size(size))?;
+                match rotate {
+                    true => {
+                        generate_sierpinski_rotate_images(color, background_color, size, frames)
+                    }
+                    false => generate_sierpinski_zoom_images(color, background_color, size),
+                }
+            }
+        },
+        ImageType::Registered(image) => image.generate()?.into_frames(),
+        ImageType::Compose { .. } => unreachable!(),
+        ImageType::FramesToAnim {
+            directory,
+            onion_skin,
+            ..
         } => {
-            let sierpinski_images = generate_sierpinski_zoom_images(color, size);
-
-            save_animated_images_to_file(&args.output, &sierpinski_images, size, size);
+            let frames = open_frames(&directory)?;
+            match onion_skin {
+                0 => frames,
+                _ => apply_onion_skin(&frames, onion_skin),
+            }
         }
-        ImageType::Perlin { .. } => unreachable!(),
-        ImageType::Farey { .. } => unreachable!(),
+        ImageType::Sweep { .. } => unreachable!(),
+        ImageType::Conformal { .. } => unreachable!(),
+        ImageType::Explore { .. } => unreachable!(),
+        ImageType::Serve { .. } => unreachable!(),
+        ImageType::Reproduce { .. } => unreachable!(),
+        ImageType::Gallery { .. } => unreachable!(),
+        ImageType::Presets { .. } => unreachable!(),
+        ImageType::Completions { .. } => unreachable!(),
+        ImageType::Schema { .. } => unreachable!(),
+        ImageType::Random { .. } => unreachable!(),
+        ImageType::Daily { .. } => unreachable!(),
+    };
+
+    Ok(images)
+}
+
+/// Plays the animation in the terminal by redrawing over the last frame
+fn print_ascii_animation(images: &[RgbaImage], width: u32, color: AsciiColor) {
+    const FRAME_DELAY: Duration = Duration::from_millis(50);
+
+    // clear the screen once, then just move the cursor back to the top for each frame
+    print!("\x1b[2J");
+    for image in images.iter() {
+        print!("\x1b[H{}", image_to_ascii(image, width, color));
+        let _ = std::io::stdout().flush();
+        std::thread::sleep(FRAME_DELAY);
     }
 }
 
-fn save_animated_images_to_file(file_path: &str, images: &[RgbaImage], width: u32, height: u32) {
-    let file_name = if file_path.ends_with(".png") {
-        file_path.to_string()
-    } else {
-        format!("{}.png", file_path)
-    };
+/// Parses a grid of tiles given as columnsxrows
+fn parse_tiles(value: &str) -> Result<TileGrid, String> {
+    let (columns, rows) = value
+        .split_once(['x', 'X'])
+        .ok_or_else(|| "expected columnsxrows like 4x4".to_string())?;
+
+    Ok(TileGrid::new(
+        parse_size(columns.trim())?,
+        parse_size(rows.trim())?,
+    ))
+}
 
-    let file = File::create(file_name).unwrap();
-    let writer = &mut BufWriter::new(file);
+/// Gets the orbit given with --show-orbit ready to draw over the image
+fn get_orbit_overlay(
+    show_orbit: Option<Complex64>,
+    formula: Option<&Formula>,
+    variant: FractalVariant,
+    viewport: ViewPort,
+    width: u32,
+    height: u32,
+    color: Color,
+) -> Option<OrbitOverlay> {
+    show_orbit.map(|c| {
+        OrbitOverlay::new(
+            &get_orbit(c, formula, variant),
+            viewport,
+            width,
+            height,
+            Rgba(color.to_rgba8()),
+        )
+    })
+}
 
-    let mut png_encoder = png::Encoder::new(writer, width, height);
-    png_encoder.set_color(png::ColorType::Rgba);
-    png_encoder.set_depth(png::BitDepth::Eight);
+/// Applies the post effects and overlays, then frames the image so nothing is drawn
+/// over the frame
+fn apply_effects(
+    image: RgbaImage,
+    post_args: &PostArgs,
+    overlays: &Overlays,
+) -> Result<RgbaImage, Error> {
+    let image = apply_post_effects(image, post_args).map_err(Error::PostEffects)?;
+    Ok(apply_framing(overlays.apply(image), post_args))
+}
 
-    png_encoder
-        .set_animated(images.len() as u32, 0)
-        .expect("Couldn't set animated");
-    let mut writer = png_encoder.write_header().expect("Couldn't write header");
-    for wave_image in images.iter() {
-        writer
-            .write_image_data(&wave_image)
-            .expect("Couldn't write image data");
-    }
-    writer.finish().expect("Couldn't finish writing");
+/// Opens an image given as an argument
+fn open_optional_image(path: Option<String>) -> Result<Option<DynamicImage>, Error> {
+    path.map(|path| {
+        image::open(&path).map_err(|image_error| Error::OpenImage {
+            path,
+            source: image_error,
+        })
+    })
+    .transpose()
 }
 
 /// Args for the program
@@ -142,112 +1166,830 @@ struct Args {
     #[command(subcommand)]
     image_type: ImageType,
 
-    /// The image output file name
-    #[arg(short, long, default_value = "image.webp")]
-    output: String,
+    /// The image output file name, the extension picks the format. Defaults to image.webp
+    /// for still images and image.png for animations
+    #[arg(short, long)]
+    output: Option<String>,
+
+    /// Writes each frame of animations to this directory too, as frame_0001.png and so on,
+    /// for post processing or encoding with other tools
+    #[arg(long, global = true)]
+    frames_dir: Option<String>,
+
+    /// Shows more detail about what's happening, like how long each stage takes. Give
+    /// it twice to show even more
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Only shows errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Overwrites the output file if it already exists
+    #[arg(long, global = true)]
+    force: bool,
+
+    /// The options this image was made with, stored in PNG files for reproduce
+    #[arg(skip)]
+    recipe: Vec<String>,
+
+    /// Every option the image is made with including the defaults, for the report
+    #[arg(skip)]
+    parameters: serde_json::Map<String, serde_json::Value>,
+
+    /// Writes a report of what was made once it's saved, with the options, seed, time
+    /// taken, output file and a hash of it
+    #[arg(long, global = true)]
+    report: Option<ReportFormat>,
+
+    /// Where to write the report, defaults to its own line at the end of the output
+    #[arg(long, global = true)]
+    report_file: Option<String>,
+
+    /// Where the image goes, either saved to the output file or printed in the terminal
+    #[arg(long, default_value = "file", global = true)]
+    output_format: OutputFormat,
+
+    /// The width in characters when printing as ASCII
+    #[arg(long, default_value = "80", global = true, value_parser = parse_size)]
+    ascii_width: u32,
+
+    /// How to colour the characters when printing as ASCII
+    #[arg(long, default_value = "none", global = true)]
+    ascii_color: AsciiColor,
+
+    /// Shows the image in the terminal after it's saved, using the sixel, kitty or iTerm
+    /// graphics protocols if supported, otherwise opens it in the system image viewer
+    #[arg(long, global = true)]
+    preview: bool,
+
+    /// Sets the saved image as the desktop wallpaper, on Linux, macOS and Windows
+    #[arg(long, global = true)]
+    set_wallpaper: bool,
+
+    /// Saves each row of tiles to this file as it's finished, so the render can be carried
+    /// on with --resume if it's stopped. Only for images rendered in tiles
+    #[arg(long, global = true)]
+    checkpoint: Option<String>,
+
+    /// Carries on the render saved in the --checkpoint file instead of starting over
+    #[arg(long, global = true, requires = "checkpoint")]
+    resume: bool,
+
+    /// When cancelled with Ctrl-C, saves the frames finished so far, or for still
+    /// images the rows finished so far
+    #[arg(long, global = true)]
+    save_partial: bool,
+
+    /// How many threads to render animation frames on, defaults to one per CPU core
+    #[arg(long, global = true)]
+    threads: Option<usize>,
+
+    /// Stops before making images that would take more memory than this, like 4GB or
+    /// 512MB, going by an estimate from the options
+    #[arg(long, default_value = "4GB", global = true, value_parser = parse_memory)]
+    memory_budget: u64,
+
+    /// Makes the image even if it takes more memory than --memory-budget, only warning
+    /// about it
+    #[arg(long, global = true)]
+    yes: bool,
+
+    /// Renders the image as a grid of tiles like 8x8, writing each row of tiles out
+    /// before starting the next so huge images fit in memory. Only for mandelbrot and
+    /// ulam-spiral images saved as PNG
+    #[arg(long, global = true, value_parser = parse_tiles)]
+    tiles: Option<TileGrid>,
+
+    /// Animates the image by changing one of its options over the frames, given as
+    /// name=start..end like zoom=1..100, or as a list of values like color=red,green,blue
+    #[arg(long, global = true, value_parser = parse_sweep_axis, allow_hyphen_values = true)]
+    animate_param: Option<SweepAxis>,
+
+    /// How many frames a start..end range for --animate-param is split into
+    #[arg(long, default_value = "30", global = true, value_parser = parse_size)]
+    animate_frames: u32,
+
+    /// Opens a window showing the image with controls for tweaking its parameters
+    #[cfg(feature = "gui")]
+    #[arg(long, global = true)]
+    gui: bool,
+
+    #[command(flatten)]
+    post: PostArgs,
+
+    #[command(flatten)]
+    overlay: OverlayArgs,
+}
+
+impl Args {
+    /// Whether anything changes the image after it's been generated
+    fn has_effects(&self) -> bool {
+        self.post.has_effects() || self.overlay.has_effects()
+    }
+
+    /// Gets the format of the output, which is an animation when an option is animated
+    fn get_format(&self) -> ImageFormat {
+        match (&self.animate_param, self.image_type.get_format()) {
+            (Some(_), ImageFormat::Static) => ImageFormat::Animated,
+            (_, format) => format,
+        }
+    }
+
+    /// Roughly how much memory making the image takes, with every frame of an
+    /// --animate-param animation held at once
+    fn get_memory_estimate(&self) -> Option<MemoryEstimate> {
+        let estimate = self.image_type.estimate_memory()?;
+        Some(match (&self.animate_param, self.image_type.get_format()) {
+            (Some(_), ImageFormat::Static) => MemoryEstimate::new(
+                estimate.output.saturating_mul(self.animate_frames as u64),
+                estimate.working,
+            ),
+            _ => estimate,
+        })
+    }
+
+    fn get_output(&self) -> String {
+        self.output.clone().unwrap_or_else(|| {
+            match self.get_format() {
+                ImageFormat::Animated => "image.png",
+                _ => "image.webp",
+            }
+            .to_string()
+        })
+    }
 }
 
 /// The image type to generate
-#[derive(Debug, Subcommand)]
+#[derive(Clone, Debug, Subcommand)]
 enum ImageType {
     UlamSpiral {
         /// The size of the spiral to go up to, defaults to 201 squared
-        #[arg(short, long, default_value = "40401")]
+        #[arg(short, long, default_value = "40401", value_parser = parse_size)]
         size: u32,
 
-        #[arg(short, long, default_value = "black")]
+        #[arg(short, long, default_value = "black", value_parser = parse_color)]
         color: Color,
 
         #[arg(short, long, default_value = "prime-only")]
         mode: UlamSpiralMode,
 
-        #[arg(short, long, default_value = "white")]
+        /// The colour behind the image, which can be transparent
+        #[arg(short, long, alias = "background", default_value = "white", value_parser = parse_color)]
         background_color: Color,
+
+        /// Colours every number by this function of it using the palette, instead of
+        /// drawing the mode
+        #[arg(long)]
+        value_fn: Option<ArithmeticFunction>,
+
+        /// The palette for the values when using --value-fn
+        #[arg(short, long, default_value = "rainbow")]
+        palette: PaletteType,
+
+        /// Animates zooming in from the whole spiral to the numbers around this one,
+        /// growing the spiral to reach it if it's past the size
+        #[arg(long, value_parser = parse_size)]
+        zoom_to: Option<u32>,
+
+        /// Pans out from the centre along the line through the --zoom-to number close up
+        /// instead of zooming, which follows a diagonal of primes for a number on one
+        #[arg(long, requires = "zoom_to")]
+        pan: bool,
+
+        /// The number of frames for --zoom-to
+        #[arg(long, default_value = "120", value_parser = parse_size)]
+        frames: u32,
     },
     Mandelbrot {
-        #[arg(short, long, default_value = "black")]
+        #[arg(short, long, default_value = "black", value_parser = parse_color)]
         color: Color,
 
-        #[arg(short, long, default_value = "white")]
+        /// The colour behind the image, which can be transparent
+        #[arg(short, long, alias = "background", default_value = "white", value_parser = parse_color)]
         background_color: Color,
 
         #[arg(short, long, default_value = "false")]
         gradient: bool,
-    },
-    Wave {
-        #[arg(short, long, default_value = "black")]
-        color: Color,
 
-        #[arg(short, long, default_value = "sine")]
-        wave_type: WaveType,
+        /// The point in the middle of the image, given as real,imaginary
+        #[arg(long, default_value = "-0.7,0.0", value_parser = parse_complex, allow_hyphen_values = true)]
+        center: Complex64,
+
+        /// How far to zoom in around the center
+        #[arg(long, default_value = "1.0")]
+        zoom: f64,
+
+        #[arg(long, default_value = "1600", value_parser = parse_size)]
+        width: u32,
+
+        #[arg(long, default_value = "1200", value_parser = parse_size)]
+        height: u32,
+
+        /// Renders on the GPU when built with the gpu feature, falling back to the CPU
+        #[arg(long)]
+        gpu: bool,
+
+        /// A formula used in place of z^2 + c on every iteration, like "z^3 + c*sin(z)",
+        /// using z, c, i, numbers, + - * / ^ and functions like sin, exp and log. z starts
+        /// at c, the point for the pixel
+        #[arg(long, value_parser = Formula::parse)]
+        formula: Option<Formula>,
+
+        /// Changes z^2 + c to draw one of the Mandelbrot set's relatives instead, which
+        /// the Julia sets use too
+        #[arg(long, default_value = "standard", conflicts_with = "formula")]
+        variant: FractalVariant,
+
+        /// How the points outside the set are coloured, binary-decomposition shows its
+        /// field lines
+        #[arg(long, default_value = "iterations")]
+        coloring: FractalColoring,
+
+        /// Draws the orbit of this point, given as real,imaginary, over the set to show
+        /// where z goes on each iteration before it escapes
+        #[arg(long, value_parser = parse_complex, allow_hyphen_values = true)]
+        show_orbit: Option<Complex64>,
+
+        #[arg(long, default_value = "red", value_parser = parse_color)]
+        orbit_color: Color,
+
+        /// Renders a grid of small Julia sets like 16x12 instead, each one using the point
+        /// in the middle of its cell as c
+        #[arg(long, value_parser = parse_tiles, conflicts_with = "show_orbit")]
+        julia_atlas: Option<TileGrid>,
+
+        /// Draws this many contour lines over the image like a topographic map, going
+        /// through evenly spaced iteration counts on a log scale
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+        contours: Option<u32>,
+
+        #[arg(long, default_value = "red", value_parser = parse_color)]
+        contour_color: Color,
+
+        /// Colours the iterations with the palette and animates it cycling round, which
+        /// only renders the set once
+        #[arg(long, conflicts_with_all = ["show_orbit", "julia_atlas", "contours"])]
+        palette_cycle: bool,
+
+        /// The palette for --palette-cycle
+        #[arg(short, long, default_value = "rainbow")]
+        palette: PaletteType,
+
+        /// The number of frames for the palette to go round once with --palette-cycle
+        #[arg(long, default_value = "60", value_parser = parse_size)]
+        frames: u32,
     },
     Sierpinski {
-        #[arg(short, long, default_value = "black")]
+        #[arg(short, long, default_value = "black", value_parser = parse_color)]
         color: Color,
 
-        #[arg(short, long, default_value = "1000")]
+        #[arg(short, long, default_value = "1000", value_parser = parse_size)]
         size: u32,
 
-        #[arg(short, long, default_value = "false")]
+        #[arg(long, default_value = "lines")]
+        mode: SierpinskiMode,
+
+        /// Zooms into the bottom left triangle, only for the lines mode
+        #[arg(short, long, default_value = "false", conflicts_with = "rotate")]
         zoom: bool,
+
+        /// Animates the triangle turning a full circle around its centroid, only for the
+        /// lines mode
+        #[arg(long)]
+        rotate: bool,
+
+        /// The number of frames for a full turn with --rotate
+        #[arg(long, default_value = "60", value_parser = parse_size)]
+        frames: u32,
+
+        /// How many points the chaos game plots
+        #[arg(long, default_value = "200000", value_parser = parse_size)]
+        points: u32,
+
+        /// Animates the chaos game, adding a frame every --points-per-frame points
+        #[arg(long, conflicts_with_all = ["zoom", "rotate"])]
+        animate: bool,
+
+        #[arg(long, default_value = "10000", value_parser = parse_size)]
+        points_per_frame: u32,
+
+        /// The seed used to pick the corners in the chaos game, random if not given
+        #[arg(long)]
+        seed: Option<u64>,
+
+        /// The colour behind the image, which can be transparent
+        #[arg(short, long, alias = "background", default_value = "transparent", value_parser = parse_color)]
+        background_color: Color,
     },
-    Perlin {
-        #[arg(long, default_value = "black")]
-        color1: Color,
+    #[command(flatten)]
+    Registered(RegisteredImage),
+    /// Stack still images on top of each other, like
+    /// compose --layer "perlin --size 600" --layer "opacity=0.8 sierpinski --size 600"
+    Compose {
+        /// A layer, written like the command line for a still image, which can start
+        /// with opacity=0.5 and offset=x,y for the layer. The first one is at the bottom
+        #[arg(short, long = "layer", required = true)]
+        layers: Vec<String>,
 
-        #[arg(long, default_value = "white")]
-        color2: Color,
+        /// The width of the image, defaults to the width of the bottom layer
+        #[arg(long, value_parser = parse_size)]
+        width: Option<u32>,
 
-        #[arg(short, long, default_value = "500")]
-        size: u32,
+        /// The height of the image, defaults to the height of the bottom layer
+        #[arg(long, value_parser = parse_size)]
+        height: Option<u32>,
+
+        #[arg(short, long, default_value = "transparent", value_parser = parse_color)]
+        background_color: Color,
     },
-    Farey {
-        #[arg(short, long, default_value = "black")]
-        color: Color,
+    /// Turn a directory of images into an animation, like frames-to-anim frames/ --fps 12
+    FramesToAnim {
+        /// The directory of frames, which go in order of their names with the numbers in
+        /// them compared by value, so frame_2.png comes before frame_10.png
+        directory: String,
+
+        /// How many frames show each second. GIFs can only time frames in hundredths of
+        /// a second so fast ones get rounded
+        #[arg(long, default_value = "30", value_parser = clap::value_parser!(u32).range(1..=100))]
+        fps: u32,
+
+        /// How many times the animation plays through, looping forever if not given
+        #[arg(long, value_parser = parse_size)]
+        loops: Option<u32>,
+
+        /// Draws this many of the frames before each one faintly over it, like
+        /// animators' onion skinning
+        #[arg(long, default_value = "0")]
+        onion_skin: u32,
+    },
+    /// Render an image over a range of values for one or two of its options, tiled into
+    /// a contact sheet, like sweep --x zoom=1..50 --y color=red,blue mandelbrot --gradient
+    Sweep {
+        /// The option changed across the columns, given as name=start..end or name=a,b,c.
+        /// Options with more than one number like center can be swept as -1,0..0,0.5
+        #[arg(long, value_parser = parse_sweep_axis, allow_hyphen_values = true)]
+        x: SweepAxis,
+
+        /// The option changed down the rows, given the same way as --x
+        #[arg(long, value_parser = parse_sweep_axis, allow_hyphen_values = true)]
+        y: Option<SweepAxis>,
+
+        /// How many values a start..end range is split into
+        #[arg(long, default_value = "5", value_parser = parse_size)]
+        steps: u32,
+
+        /// The width and height each image is shrunk to fit on the sheet
+        #[arg(long, default_value = "256", value_parser = parse_size)]
+        cell_size: u32,
+
+        /// The image to sweep, written like its command line
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        image: Vec<String>,
+    },
+    /// Warp a checkerboard, a generated pattern or an image through a complex map, like
+    /// conformal --map exp --pattern "perlin --size 600"
+    Conformal {
+        #[arg(short, long, default_value = "reciprocal")]
+        map: ConformalMap,
 
-        #[arg(long, default_value = "6")]
-        n: i32,
+        /// a in the mobius map (az + b) / (cz + d), given as real,imaginary
+        #[arg(long, default_value = "1.0,0.0", value_parser = parse_complex, allow_hyphen_values = true)]
+        mobius_a: Complex64,
 
+        #[arg(long, default_value = "-1.0,0.0", value_parser = parse_complex, allow_hyphen_values = true)]
+        mobius_b: Complex64,
+
+        #[arg(long, default_value = "1.0,0.0", value_parser = parse_complex, allow_hyphen_values = true)]
+        mobius_c: Complex64,
+
+        #[arg(long, default_value = "1.0,0.0", value_parser = parse_complex, allow_hyphen_values = true)]
+        mobius_d: Complex64,
+
+        /// An image to warp instead of the checkerboard, repeated across the plane
+        #[arg(short, long, conflicts_with = "pattern")]
+        input: Option<String>,
+
+        /// A still image to warp instead of the checkerboard, written like its command
+        /// line, like "perlin --size 600"
         #[arg(short, long)]
-        fill_color: Option<Color>,
+        pattern: Option<String>,
+
+        /// The colour of every other square of the checkerboard
+        #[arg(short, long, default_value = "black", value_parser = parse_color)]
+        color: Color,
+
+        /// The colour of the rest of the squares
+        #[arg(short, long, alias = "background", default_value = "white", value_parser = parse_color)]
+        background_color: Color,
+
+        /// How big the squares of the checkerboard are, with the image being 4 wide
+        /// before zooming
+        #[arg(long, default_value = "0.25")]
+        square_size: f64,
+
+        /// The point in the middle of the image, given as real,imaginary
+        #[arg(long, default_value = "0.0,0.0", value_parser = parse_complex, allow_hyphen_values = true)]
+        center: Complex64,
+
+        /// How far to zoom in around the center
+        #[arg(long, default_value = "1.0")]
+        zoom: f64,
+
+        #[arg(long, default_value = "1600", value_parser = parse_size)]
+        width: u32,
+
+        #[arg(long, default_value = "1200", value_parser = parse_size)]
+        height: u32,
+    },
+    /// Explore the Mandelbrot and Julia sets interactively in the terminal
+    Explore {
+        #[arg(short, long, default_value = "black", value_parser = parse_color)]
+        color: Color,
+
+        #[arg(short, long, default_value = "white", value_parser = parse_color)]
+        background_color: Color,
+
+        #[arg(short, long, default_value = "false")]
+        gradient: bool,
+
+        /// The width of images exported from the explorer
+        #[arg(long, default_value = "1600", value_parser = parse_size)]
+        export_width: u32,
+
+        /// The height of images exported from the explorer
+        #[arg(long, default_value = "1200", value_parser = parse_size)]
+        export_height: u32,
+    },
+    /// Serve images over HTTP, where a request like /mandelbrot?zoom=20&gradient=true
+    /// takes the same options as the command line
+    Serve {
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+
+        #[arg(short, long, default_value = "8080")]
+        port: u16,
+
+        /// How many rendered images to keep in memory
+        #[arg(long, default_value = "64")]
+        cache_size: usize,
+    },
+    /// Make an image again from the recipe stored in a PNG made by fun-images
+    Reproduce {
+        /// The PNG file to read the recipe from
+        input: String,
+
+        /// Renders at a different width, or size for square images, keeping the
+        /// aspect ratio
+        #[arg(long, value_parser = parse_size)]
+        width: Option<u32>,
+
+        /// Changes one of the options, given as name=value
+        #[arg(long = "override")]
+        overrides: Vec<String>,
+    },
+    /// Make a surprise image, with the image type and its options picked at random. The
+    /// command line which makes the same image is printed
+    Random {
+        /// The seed used to pick the image, random if not given
+        #[arg(long)]
+        seed: Option<u64>,
+
+        /// Picks an animation rather than a still image
+        #[arg(long)]
+        animated: bool,
+    },
+    /// Make the image for the day, picked at random with the date as the seed so it's
+    /// the same all day, wherever it's made
+    Daily {
+        /// The day to make the image for like 2024-07-01, defaults to today in UTC
+        #[arg(long, value_parser = parse_date)]
+        date: Option<Date>,
+    },
+    /// Render a thumbnail of every image type with its default options, along with
+    /// a montage of them all and an index.html page
+    Gallery {
+        /// The directory to put the gallery in, made if it doesn't exist
+        #[arg(long)]
+        out: String,
+
+        /// The width and height of each thumbnail
+        #[arg(long, default_value = "256", value_parser = parse_size)]
+        thumbnail_size: u32,
+    },
+    /// List the presets picked with --preset, like presets mandelbrot for the ones for
+    /// the Mandelbrot set
+    Presets {
+        /// The image type to list the presets for, all of them if not given
+        image_type: Option<String>,
+    },
+    /// Print the script for tab completing the command line in a shell, to be sourced
+    /// from the shell's startup file
+    Completions {
+        /// The shell to complete in
+        shell: Shell,
+    },
+    /// Describe every subcommand's options with their types, defaults and ranges, for
+    /// generating GUIs and scripts against the command line
+    Schema {
+        /// Prints it as JSON rather than a line per option
+        #[arg(long)]
+        json: bool,
     },
 }
 
 impl ImageType {
     fn get_format(&self) -> ImageFormat {
         match self {
-            ImageType::UlamSpiral { .. } => ImageFormat::Static,
-            ImageType::Mandelbrot { .. } => ImageFormat::Static,
-            ImageType::Wave { .. } => ImageFormat::Animated,
-            ImageType::Sierpinski { zoom, .. } => match *zoom {
+            ImageType::UlamSpiral { zoom_to, .. } => match zoom_to {
+                Some(_) => ImageFormat::Animated,
+                None => ImageFormat::Static,
+            },
+            ImageType::Mandelbrot { palette_cycle, .. } => match *palette_cycle {
                 true => ImageFormat::Animated,
                 false => ImageFormat::Static,
             },
-            ImageType::Perlin { .. } => ImageFormat::Static,
-            ImageType::Farey { .. } => ImageFormat::Static,
+            ImageType::Sierpinski {
+                mode,
+                zoom,
+                rotate,
+                animate,
+                ..
+            } => match (mode, *zoom || *rotate, *animate) {
+                (SierpinskiMode::Lines, true, _) | (SierpinskiMode::ChaosGame, _, true) => {
+                    ImageFormat::Animated
+                }
+                _ => ImageFormat::Static,
+            },
+            ImageType::Registered(image) => match image.is_animated() {
+                true => ImageFormat::Animated,
+                false => ImageFormat::Static,
+            },
+            ImageType::Compose { .. } => ImageFormat::Static,
+            ImageType::FramesToAnim { .. } => ImageFormat::Animated,
+            ImageType::Sweep { .. } => ImageFormat::Static,
+            ImageType::Conformal { .. } => ImageFormat::Static,
+            ImageType::Explore { .. } => ImageFormat::Interactive,
+            ImageType::Serve { .. } => ImageFormat::Server,
+            ImageType::Reproduce { .. } => ImageFormat::Reproduce,
+            ImageType::Gallery { .. } => ImageFormat::Gallery,
+            ImageType::Presets { .. } => ImageFormat::Presets,
+            ImageType::Completions { .. } => ImageFormat::Completions,
+            ImageType::Schema { .. } => ImageFormat::Schema,
+            ImageType::Random { .. } | ImageType::Daily { .. } => ImageFormat::Random,
+        }
+    }
+
+    /// Roughly how much memory making the image takes, or None when it depends on files
+    /// or isn't an image
+    fn estimate_memory(&self) -> Option<MemoryEstimate> {
+        match self {
+            ImageType::UlamSpiral {
+                size,
+                color,
+                mode,
+                background_color,
+                value_fn,
+                palette,
+                zoom_to,
+                frames,
+                ..
+            } => {
+                // the spiral is grown to reach the number zoomed to, as when it's made
+                let size = (*size).max(zoom_to.map_or(0, |number| number.saturating_add(1)));
+                let options = UlamSpiralOptions::new(
+                    size,
+                    color.clone(),
+                    *mode,
+                    background_color.clone(),
+                    *value_fn,
+                    *palette,
+                );
+                let dimension = options.get_image_dimension();
+                let frame_num = zoom_to.map_or(1, |_| *frames);
+                // a value for each number, or a bit for each in the prime sieve
+                let working = match value_fn {
+                    Some(_) => size as u64 * 8,
+                    None => size as u64 / 8,
+                };
+                Some(MemoryEstimate::frames(dimension, dimension, frame_num).with_working(working))
+            }
+            ImageType::Mandelbrot {
+                width,
+                height,
+                contours,
+                palette_cycle,
+                frames,
+                ..
+            } => {
+                let frame_num = match palette_cycle {
+                    true => *frames,
+                    false => 1,
+                };
+                let estimate = MemoryEstimate::frames(*width, *height, frame_num);
+                // cycling the palette and drawing contours keep a value for every pixel
+                Some(match *palette_cycle || contours.is_some() {
+                    true => estimate.with_pixel_buffer(*width, *height, 16),
+                    false => estimate,
+                })
+            }
+            ImageType::Conformal {
+                pattern,
+                width,
+                height,
+                ..
+            } => {
+                let estimate = MemoryEstimate::still(*width, *height);
+                // the pattern is rendered whole before it's warped
+                match pattern {
+                    Some(pattern) => {
+                        Some(estimate.with_working(estimate_pattern_memory(pattern)?.total()))
+                    }
+                    None => Some(estimate),
+                }
+            }
+            ImageType::Compose {
+                layers,
+                width,
+                height,
+                ..
+            } => {
+                let layer_estimates = estimate_layers_memory(layers)?;
+                // the canvas is the size of the first layer unless it's given
+                let canvas = match (width, height) {
+                    (Some(width), Some(height)) => MemoryEstimate::still(*width, *height),
+                    _ => MemoryEstimate::new(layer_estimates.first()?.output, 0),
+                };
+                let working = layer_estimates.iter().fold(0u64, |total, estimate| {
+                    total.saturating_add(estimate.total())
+                });
+                Some(canvas.with_working(working))
+            }
+            ImageType::Sweep {
+                x,
+                y,
+                steps,
+                cell_size,
+                image,
+            } => estimate_sweep_memory(image, x, y.as_ref(), *steps, *cell_size),
+            ImageType::Sierpinski {
+                size,
+                mode,
+                zoom,
+                rotate,
+                frames,
+                points,
+                animate,
+                points_per_frame,
+                ..
+            } => {
+                let frame_num = match (mode, *zoom || *rotate, *animate) {
+                    (SierpinskiMode::Lines, true, _) => *frames,
+                    (SierpinskiMode::ChaosGame, _, true) => {
+                        points.div_ceil((*points_per_frame).max(1))
+                    }
+                    _ => 1,
+                };
+                Some(MemoryEstimate::frames(*size, *size, frame_num))
+            }
+            ImageType::Registered(image) => image.estimate_memory(),
+            _ => None,
+        }
+    }
+
+    /// What to change when the image takes too much memory
+    fn get_memory_suggestion(&self) -> &'static str {
+        match (self, self.get_format()) {
+            (
+                ImageType::Mandelbrot {
+                    julia_atlas: None,
+                    palette_cycle: false,
+                    ..
+                }
+                | ImageType::UlamSpiral { zoom_to: None, .. },
+                _,
+            ) => {
+                "Save it as a png without post effects, or with --tiles 8x8, to render it a band \
+                 of rows at a time"
+            }
+            (_, ImageFormat::Animated) => "Use a smaller size or fewer frames",
+            _ => "Use a smaller size",
+        }
+    }
+
+    /// How fast the animation plays and how many times, which only frames-to-anim changes
+    fn get_playback(&self) -> Playback {
+        match self {
+            ImageType::FramesToAnim { fps, loops, .. } => Playback::new(*fps, *loops),
+            _ => Playback::default(),
+        }
+    }
+}
+
+/// An image type from the generator registry, kept as its name and unparsed options
+#[derive(Clone, Debug)]
+struct RegisteredImage {
+    name: String,
+    matches: ArgMatches,
+}
+
+impl RegisteredImage {
+    fn is_animated(&self) -> bool {
+        GeneratorRegistry::global()
+            .get(&self.name)
+            .is_some_and(|generator| generator.is_animated(&self.matches))
+    }
+
+    fn estimate_memory(&self) -> Option<MemoryEstimate> {
+        GeneratorRegistry::global()
+            .get(&self.name)
+            .and_then(|generator| generator.estimate_memory(&self.matches))
+    }
+
+    fn generate(&self) -> Result<Generated, Error> {
+        let generator =
+            GeneratorRegistry::global()
+                .get(&self.name)
+                .ok_or_else(|| Error::Generator {
+                    name: self.name.clone(),
+                    source: GeneratorError::Failed("no generator has this name".to_string()),
+                })?;
+        generator
+            .generate(&self.matches)
+            .map_err(|source| Error::Generator {
+                name: self.name.clone(),
+                source,
+            })
+    }
+}
+
+impl FromArgMatches for RegisteredImage {
+    fn from_arg_matches(matches: &ArgMatches) -> Result<Self, clap::Error> {
+        match matches.subcommand() {
+            Some((name, sub_matches)) if GeneratorRegistry::global().get(name).is_some() => {
+                Ok(Self {
+                    name: name.to_string(),
+                    matches: sub_matches.clone(),
+                })
+            }
+            _ => Err(clap::Error::new(clap::error::ErrorKind::InvalidSubcommand)),
         }
     }
+
+    fn update_from_arg_matches(&mut self, matches: &ArgMatches) -> Result<(), clap::Error> {
+        *self = Self::from_arg_matches(matches)?;
+        Ok(())
+    }
+}
+
+impl Subcommand for RegisteredImage {
+    fn augment_subcommands(command: Command) -> Command {
+        let command = command.subcommands(
+            GeneratorRegistry::global()
+                .iter()
+                .map(|generator| generator.command()),
+        );
+        // the image types with presets are all on the command by now, the ones before
+        // Registered in ImageType and the generators
+        add_preset_args(command)
+    }
+
+    fn augment_subcommands_for_update(command: Command) -> Command {
+        Self::augment_subcommands(command)
+    }
+
+    fn has_subcommand(name: &str) -> bool {
+        GeneratorRegistry::global().get(name).is_some()
+    }
 }
 
 enum ImageFormat {
     Static,
     Animated,
+    Interactive,
+    Server,
+    Reproduce,
+    Gallery,
+    Presets,
+    Completions,
+    Schema,
+    Random,
 }
 
 #[derive(Clone, Copy, Debug, ValueEnum)]
-pub(crate) enum UlamSpiralMode {
-    /// Generates pixels for the primes only
-    PrimeOnly,
-    /// Generates circles based on how many divisors a number has
-    Divisor,
+enum OutputFormat {
+    /// Saves the image to the output file
+    File,
+    /// Prints the image to the terminal as ASCII art
+    Ascii,
 }
 
 #[derive(Clone, Copy, Debug, ValueEnum)]
-pub(crate) enum WaveType {
-    /// Generates a sine wave
-    Sine,
-    /// Generates a cosine wave
-    Cosine,
-    /// Generates a tangent wave
-    Tangent,
+pub(crate) enum AsciiColor {
+    /// Plain characters with no colour
+    None,
+    /// Colours from the 256 colour ANSI palette
+    Ansi256,
+    /// 24 bit colours for terminals which support it
+    Truecolor,
 }
@@ -0,0 +1,350 @@
+//! Module for generating mandalas
+//!
+//! The mandala is built up in rings from the outside in, each ring being a random shape
+//! like petals, dots or arcs repeated all the way round. The number of repeats is always
+//! a multiple of the symmetry, so the whole thing looks the same when turned by a k-th
+//! of a full turn. Inner rings are drawn over the outer ones so the shapes can overlap.
+//! See <https://en.wikipedia.org/wiki/Mandala> for more info
+
+use std::f64::consts::PI;
+
+use csscolorparser::Color;
+use image::{Rgba, RgbaImage};
+use imageproc::{
+    drawing::{draw_filled_circle_mut, draw_polygon_mut},
+    point::Point,
+};
+
+use crate::{
+    PaletteType,
+    generator::{Generated, Generator, GeneratorError},
+    memory::MemoryEstimate,
+    palette::get_palette_color,
+    parse_color, parse_size,
+};
+
+/// The space around the mandala, as a fraction of the image size
+const MARGIN: f64 = 0.04;
+/// How many points each curved edge is made of
+const CURVE_POINT_NUM: usize = 24;
+
+pub struct MandalaOptions {
+    /// How many times the pattern repeats around the middle
+    symmetry: u32,
+    layer_num: u32,
+    palette: PaletteType,
+    background_color: Color,
+    size: u32,
+    seed: Option<u64>,
+}
+
+impl MandalaOptions {
+    pub fn new(
+        symmetry: u32,
+        layer_num: u32,
+        palette: PaletteType,
+        background_color: Color,
+        size: u32,
+        seed: Option<u64>,
+    ) -> Self {
+        Self {
+            symmetry,
+            layer_num,
+            palette,
+            background_color,
+            size,
+            seed,
+        }
+    }
+}
+
+/// The shapes a layer can be made of
+#[derive(Clone, Copy, Debug)]
+enum LayerShape {
+    /// A band all the way round
+    Ring,
+    /// Pointed petals from the inside of the layer to the outside
+    Petals,
+    /// Circles around the middle of the layer, with smaller circles inside them
+    Dots,
+    /// Pieces of the band with gaps between them
+    Arcs,
+    /// Triangles pointing out
+    Spikes,
+}
+
+const LAYER_SHAPES: [LayerShape; 5] = [
+    LayerShape::Ring,
+    LayerShape::Petals,
+    LayerShape::Dots,
+    LayerShape::Arcs,
+    LayerShape::Spikes,
+];
+
+/// Where to draw a layer, with the radii and angles in pixels and radians
+struct Layer {
+    centre: f64,
+    inner_radius: f64,
+    outer_radius: f64,
+    repeat_num: u32,
+    /// The angle of the first repeat, with the rest evenly spaced after it
+    offset: f64,
+}
+
+impl Layer {
+    fn get_angles(&self) -> impl Iterator<Item = f64> + '_ {
+        let step = 2.0 * PI / self.repeat_num as f64;
+        (0..self.repeat_num).map(move |index| self.offset + index as f64 * step)
+    }
+
+    /// Gets the pixel at a radius and angle from the middle
+    fn to_pixel(&self, radius: f64, angle: f64) -> (f64, f64) {
+        (
+            self.centre + radius * angle.cos(),
+            self.centre + radius * angle.sin(),
+        )
+    }
+}
+
+pub fn generate_mandala_image(options: MandalaOptions) -> RgbaImage {
+    let mut rng = match options.seed {
+        Some(seed) => fastrand::Rng::with_seed(seed),
+        None => fastrand::Rng::new(),
+    };
+    let size = options.size;
+    let background_color = Rgba(options.background_color.to_rgba8());
+    let mut image = RgbaImage::from_pixel(size, size, background_color);
+
+    // shapes are drawn on whole pixels, so this is the middle of the middle pixel
+    let centre = (size as f64 - 1.0) / 2.0;
+    let radius = size as f64 / 2.0 * (1.0 - 2.0 * MARGIN);
+    let band = radius / options.layer_num as f64;
+    for layer_index in 0..options.layer_num {
+        let repeat_num = options.symmetry * (1 + rng.u32(..2));
+        let layer = Layer {
+            centre,
+            inner_radius: radius - band * (layer_index + 1) as f64,
+            outer_radius: radius - band * layer_index as f64,
+            repeat_num,
+            // either lined up with the layer outside or halfway between
+            offset: rng.u32(..2) as f64 * PI / repeat_num as f64,
+        };
+        let color = Rgba(get_palette_color(options.palette, rng.f64()));
+        let accent_color = Rgba(get_palette_color(options.palette, rng.f64()));
+
+        match LAYER_SHAPES[rng.usize(..LAYER_SHAPES.len())] {
+            LayerShape::Ring => {
+                draw_circle(&mut image, centre, layer.outer_radius, color);
+                let thickness = 0.3 + rng.f64() * 0.4;
+                let inner_radius = layer.outer_radius - band * thickness;
+                draw_circle(&mut image, centre, inner_radius, background_color);
+            }
+            LayerShape::Petals => {
+                let width = 0.6 + rng.f64() * 0.4;
+                for angle in layer.get_angles() {
+                    draw_petal(&mut image, &layer, angle, width, color);
+                    draw_petal(&mut image, &layer, angle, width * 0.4, accent_color);
+                }
+            }
+            LayerShape::Dots => {
+                let middle_radius = (layer.inner_radius + layer.outer_radius) / 2.0;
+                // small enough to fit in the layer and not touch the next dot
+                let spacing = middle_radius * PI / repeat_num as f64;
+                let dot_radius = (band / 2.0).min(spacing) * 0.8;
+                for angle in layer.get_angles() {
+                    let (x, y) = layer.to_pixel(middle_radius, angle);
+                    draw_circle_at(&mut image, (x, y), dot_radius, color);
+                    draw_circle_at(&mut image, (x, y), dot_radius * 0.4, accent_color);
+                }
+            }
+            LayerShape::Arcs => {
+                let fill = 0.5 + rng.f64() * 0.4;
+                let inner_radius = layer.inner_radius + band * rng.f64() * 0.5;
+                for angle in layer.get_angles() {
+                    draw_arc(&mut image, &layer, angle, fill, inner_radius, color);
+                }
+            }
+            LayerShape::Spikes => {
+                let step = PI / repeat_num as f64;
+                for angle in layer.get_angles() {
+                    let points = [
+                        layer.to_pixel(layer.inner_radius, angle - step),
+                        layer.to_pixel(layer.outer_radius, angle),
+                        layer.to_pixel(layer.inner_radius, angle + step),
+                    ];
+                    draw_shape(&mut image, &points, color);
+                }
+                draw_circle(&mut image, centre, layer.inner_radius, accent_color);
+            }
+        }
+    }
+
+    image
+}
+
+/// Draws a petal pointing out from the middle, its width being how much of the space to
+/// the next petal it takes up
+fn draw_petal(image: &mut RgbaImage, layer: &Layer, angle: f64, width: f64, color: Rgba<u8>) {
+    let length = layer.outer_radius - layer.inner_radius;
+    let middle_radius = (layer.inner_radius + layer.outer_radius) / 2.0;
+    let half_width = width * middle_radius * PI / layer.repeat_num as f64;
+    let (sin, cos) = angle.sin_cos();
+
+    // goes out along one side and back along the other
+    let points: Vec<(f64, f64)> = (0..=CURVE_POINT_NUM)
+        .chain((1..CURVE_POINT_NUM).rev())
+        .enumerate()
+        .map(|(index, step)| {
+            let amount = step as f64 / CURVE_POINT_NUM as f64;
+            let along = layer.inner_radius + length * amount;
+            let side = match index <= CURVE_POINT_NUM {
+                true => half_width,
+                false => -half_width,
+            } * (PI * amount).sin();
+            (
+                layer.centre + along * cos - side * sin,
+                layer.centre + along * sin + side * cos,
+            )
+        })
+        .collect();
+    draw_shape(image, &points, color);
+}
+
+/// Draws a piece of the band around an angle, taking up a fraction of the space to the
+/// next piece
+fn draw_arc(
+    image: &mut RgbaImage,
+    layer: &Layer,
+    angle: f64,
+    fill: f64,
+    inner_radius: f64,
+    color: Rgba<u8>,
+) {
+    let half_angle = fill * PI / layer.repeat_num as f64;
+    let get_angle =
+        |step: usize| angle - half_angle + 2.0 * half_angle * step as f64 / CURVE_POINT_NUM as f64;
+    let points: Vec<(f64, f64)> = (0..=CURVE_POINT_NUM)
+        .map(|step| layer.to_pixel(layer.outer_radius, get_angle(step)))
+        .chain(
+            (0..=CURVE_POINT_NUM)
+                .rev()
+                .map(|step| layer.to_pixel(inner_radius, get_angle(step))),
+        )
+        .collect();
+    draw_shape(image, &points, color);
+}
+
+/// Draws a circle around the middle of the image
+fn draw_circle(image: &mut RgbaImage, centre: f64, radius: f64, color: Rgba<u8>) {
+    draw_circle_at(image, (centre, centre), radius, color);
+}
+
+fn draw_circle_at(image: &mut RgbaImage, (x, y): (f64, f64), radius: f64, color: Rgba<u8>) {
+    if radius >= 0.5 {
+        draw_filled_circle_mut(
+            image,
+            (x.round() as i32, y.round() as i32),
+            radius.round() as i32,
+            color,
+        );
+    }
+}
+
+/// Draws a filled polygon, leaving out the points which round to the same pixel as the
+/// one before as the polygon can't start and end on the same point
+fn draw_shape(image: &mut RgbaImage, points: &[(f64, f64)], color: Rgba<u8>) {
+    let mut polygon: Vec<Point<i32>> = Vec::with_capacity(points.len());
+    for &(x, y) in points {
+        let point = Point::new(x.round() as i32, y.round() as i32);
+        if polygon.last() != Some(&point) {
+            polygon.push(point);
+        }
+    }
+    while polygon.len() > 1 && polygon.first() == polygon.last() {
+        polygon.pop();
+    }
+    if polygon.len() >= 3 {
+        draw_polygon_mut(image, &polygon, color);
+    }
+}
+
+#[derive(clap::Args, Debug)]
+pub struct MandalaArgs {
+    /// How many times the pattern repeats around the middle
+    #[arg(short = 'k', long, default_value = "12", value_parser = parse_size)]
+    symmetry: u32,
+
+    /// How many rings of shapes there are
+    #[arg(short, long, default_value = "8", value_parser = parse_size)]
+    layers: u32,
+
+    #[arg(short, long, default_value = "rainbow")]
+    palette: PaletteType,
+
+    /// The colour behind the mandala, which can be transparent
+    #[arg(short, long, alias = "background", default_value = "white", value_parser = parse_color)]
+    background_color: Color,
+
+    #[arg(short, long, default_value = "1000", value_parser = parse_size)]
+    size: u32,
+
+    /// The seed used for the shapes and colours, random if not given
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+pub struct MandalaGenerator;
+
+impl Generator for MandalaGenerator {
+    type Args = MandalaArgs;
+
+    fn name(&self) -> &'static str {
+        "mandala"
+    }
+
+    fn is_animated(&self, _args: &MandalaArgs) -> bool {
+        false
+    }
+
+    fn estimate_memory(&self, args: &MandalaArgs) -> Option<MemoryEstimate> {
+        Some(MemoryEstimate::still(args.size, args.size))
+    }
+
+    fn generate(&self, args: MandalaArgs) -> Result<Generated, GeneratorError> {
+        Ok(Generated::Still(generate_mandala_image(
+            MandalaOptions::new(
+                args.symmetry,
+                args.layers,
+                args.palette,
+                args.background_color,
+                args.size,
+                args.seed,
+            ),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mandala_symmetry() {
+        // with a symmetry of 4 turning it a quarter turn should give the same image,
+        // apart from the odd pixel where the shapes round differently
+        let size = 201;
+        let image = generate_mandala_image(MandalaOptions::new(
+            4,
+            6,
+            PaletteType::Rainbow,
+            Color::new(1.0, 1.0, 1.0, 1.0),
+            size,
+            Some(7),
+        ));
+        let matching_num = image
+            .enumerate_pixels()
+            .filter(|&(x, y, pixel)| image.get_pixel(size - 1 - y, x) == pixel)
+            .count();
+        assert!(matching_num as f64 > 0.95 * (size * size) as f64);
+    }
+}
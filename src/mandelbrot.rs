@@ -4,111 +4,1072 @@
 //! See <https://en.wikipedia.org/wiki/Mandelbrot_set> for more info
 
 use csscolorparser::Color;
-use image::{DynamicImage, ImageBuffer, Rgba};
+use image::{DynamicImage, ImageBuffer, Rgba, RgbaImage, imageops};
+use imageproc::drawing::{draw_filled_circle_mut, draw_line_segment_mut};
+use log::warn;
 use num_complex::Complex64;
+use rayon::prelude::*;
+
+use crate::{
+    FractalColoring, FractalVariant, MagnetType,
+    cancel::is_cancelled,
+    contours::ContourOptions,
+    formula::Formula,
+    generator::{Generated, Generator, GeneratorError},
+    memory::MemoryEstimate,
+    palette::IndexedImage,
+    parse_color, parse_complex, parse_size,
+    tiles::{Tile, TileGrid},
+};
+
+mod simd;
 
 pub struct MandelbrotImageOptions {
     color: Color,
     background_color: Color,
     use_gradient: bool,
+    use_gpu: bool,
+    /// Used in place of z^2 + c on every iteration
+    formula: Option<Formula>,
+    coloring: FractalColoring,
+    variant: FractalVariant,
+    /// Contour lines drawn over the set through evenly spaced iteration counts
+    contours: Option<ContourOptions>,
 }
 
 impl MandelbrotImageOptions {
-    pub fn new(color: Color, background_color: Color, use_gradient: bool) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        color: Color,
+        background_color: Color,
+        use_gradient: bool,
+        use_gpu: bool,
+        formula: Option<Formula>,
+        coloring: FractalColoring,
+        variant: FractalVariant,
+        contours: Option<ContourOptions>,
+    ) -> Self {
         Self {
             color,
             background_color,
             use_gradient,
+            use_gpu,
+            formula,
+            coloring,
+            variant,
+            contours,
         }
     }
 }
 
-const MAX_ITER_NUM: u32 = 200;
+pub(crate) const MAX_ITER_NUM: u32 = 200;
+
+const ORBIT_DOT_RADIUS: i32 = 3;
+
+/// How many iterations go by for each time round the palette when it's cycled
+const ITERATIONS_PER_CYCLE: f64 = 20.0;
+
+/// How many pixels around a tile are rendered too so its contours match the full image
+const CONTOUR_MARGIN: u32 = 2;
+
+/// Where a point escaped, with the iteration it happened on and the value of z then
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Escape {
+    pub iteration: u32,
+    pub z: Complex64,
+}
 
-pub fn generate_mandelbrot_image(options: MandelbrotImageOptions) -> DynamicImage {
-    const IMAGE_WIDTH: u32 = 1600;
-    const IMAGE_HEIGHT: u32 = 1200;
-    let mut image = ImageBuffer::new(IMAGE_WIDTH, IMAGE_HEIGHT);
+/// Which set is being drawn
+#[derive(Clone, Copy, Debug)]
+pub enum FractalSet {
+    Mandelbrot,
+    /// The Julia set for the given constant
+    Julia(Complex64),
+    /// z = z^2 + c + p * the z before it, with the pixel as the first z like a Julia set
+    Phoenix {
+        c: Complex64,
+        p: Complex64,
+    },
+    /// Models from physics of magnets, with the pixel as c. Points that settle on 1
+    /// count as escaping too, as that's where most of the plane ends up
+    Magnet(MagnetType),
+}
+
+pub fn generate_mandelbrot_image(
+    options: MandelbrotImageOptions,
+    viewport: ViewPort,
+    width: u32,
+    height: u32,
+) -> DynamicImage {
+    generate_fractal_image(options, FractalSet::Mandelbrot, viewport, width, height)
+}
+
+/// Renders any of the sets, with the viewport fitted to the image size
+pub fn generate_fractal_image(
+    options: MandelbrotImageOptions,
+    set: FractalSet,
+    mut viewport: ViewPort,
+    width: u32,
+    height: u32,
+) -> DynamicImage {
+    viewport.fit_to_size(width, height);
+
+    DynamicImage::ImageRgba8(render_fractal(&options, set, &viewport, width, height))
+}
+
+/// Renders one tile of the image generate_mandelbrot_image would make at this size
+pub fn generate_mandelbrot_tile(
+    options: &MandelbrotImageOptions,
+    mut viewport: ViewPort,
+    width: u32,
+    height: u32,
+    tile: Tile,
+) -> RgbaImage {
+    viewport.fit_to_size(width, height);
+    // contours are drawn between pixels and can spill into the pixels next to them, so
+    // a margin around the tile is rendered too for them to join up with the tiles
+    // around it, then cut off
+    let rendered_tile = match options.contours {
+        Some(_) => {
+            let (x, y) = (
+                tile.x.saturating_sub(CONTOUR_MARGIN),
+                tile.y.saturating_sub(CONTOUR_MARGIN),
+            );
+            Tile {
+                x,
+                y,
+                width: (tile.x + tile.width + CONTOUR_MARGIN).min(width) - x,
+                height: (tile.y + tile.height + CONTOUR_MARGIN).min(height) - y,
+            }
+        }
+        None => tile,
+    };
+    let tile_viewport = viewport.get_tile(rendered_tile, width, height);
+
+    let image = render_fractal(
+        options,
+        FractalSet::Mandelbrot,
+        &tile_viewport,
+        rendered_tile.width,
+        rendered_tile.height,
+    );
+    match rendered_tile == tile {
+        true => image,
+        false => imageops::crop_imm(
+            &image,
+            tile.x - rendered_tile.x,
+            tile.y - rendered_tile.y,
+            tile.width,
+            tile.height,
+        )
+        .to_image(),
+    }
+}
+
+/// Renders a grid of small Julia sets, each using the point in the middle of its cell
+/// as c, which maps out how the Julia sets change across the Mandelbrot set
+pub fn generate_julia_atlas(
+    options: MandelbrotImageOptions,
+    mut viewport: ViewPort,
+    width: u32,
+    height: u32,
+    grid: TileGrid,
+) -> DynamicImage {
+    viewport.fit_to_size(width, height);
+    let cells: Vec<Tile> = grid
+        .get_tile_rows(width, height)
+        .into_iter()
+        .flatten()
+        .collect();
+
+    let julia_images: Vec<RgbaImage> = cells
+        .par_iter()
+        .map(|cell| {
+            let c = viewport.get_point(
+                cell.x + cell.width / 2,
+                cell.y + cell.height / 2,
+                width,
+                height,
+            );
+            let mut julia_viewport = ViewPort::normal_julia();
+            julia_viewport.fit_to_size(cell.width, cell.height);
+            render_fractal(
+                &options,
+                FractalSet::Julia(c),
+                &julia_viewport,
+                cell.width,
+                cell.height,
+            )
+        })
+        .collect();
+
+    let mut image = RgbaImage::new(width, height);
+    for (cell, julia_image) in cells.iter().zip(julia_images.iter()) {
+        imageops::replace(&mut image, julia_image, cell.x as i64, cell.y as i64);
+    }
+    DynamicImage::ImageRgba8(image)
+}
 
-    let viewport = ViewPort::normal_mandelbrot();
+/// Renders the part of the set inside the viewport at the given size
+pub fn render_fractal(
+    options: &MandelbrotImageOptions,
+    set: FractalSet,
+    viewport: &ViewPort,
+    width: u32,
+    height: u32,
+) -> RgbaImage {
+    let mut image = ImageBuffer::new(width, height);
 
     let converted_color = options.color.to_rgba8();
     let converted_background_color = options.background_color.to_rgba8();
 
-    for y in 0..IMAGE_HEIGHT {
-        for x in 0..IMAGE_WIDTH {
-            let real = (x as f64) / (IMAGE_WIDTH as f64) * viewport.real_diameter
-                - viewport.real_diameter / 2.0
-                + viewport.centre.re;
-            let imaginary = (y as f64) / (IMAGE_HEIGHT as f64) * viewport.imaginary_diameter
-                - viewport.imaginary_diameter / 2.0
-                + viewport.centre.im;
-
-            let complex = Complex64::new(real, imaginary);
-
-            if let Some(iter_num) = check_mandelbrot(complex) {
-                if options.use_gradient {
-                    let grad_color = get_interp(
-                        converted_background_color,
-                        converted_color,
-                        iter_num as f64 / MAX_ITER_NUM as f64,
+    let escapes = get_fractal_escapes(options, set, viewport, width, height);
+    for (pixel, &escape) in image.pixels_mut().zip(escapes.iter()) {
+        let is_colored = match (escape, options.coloring) {
+            (None, _) => false,
+            (Some(_), FractalColoring::Iterations) => true,
+            (Some(escape), FractalColoring::BinaryDecomposition) => escape.z.im >= 0.0,
+        };
+        if let Some(escape) = escape
+            && is_colored
+        {
+            if options.use_gradient {
+                let grad_color = get_interp(
+                    converted_background_color,
+                    converted_color,
+                    escape.iteration as f64 / MAX_ITER_NUM as f64,
+                );
+                *pixel = Rgba(grad_color);
+            } else {
+                *pixel = Rgba(converted_color);
+            }
+        } else {
+            *pixel = Rgba(converted_background_color);
+        }
+    }
+
+    if let Some(contours) = &options.contours {
+        // most of the plane escapes within a few iterations, so the contours go through
+        // the log of the iterations to spread them out rather than bunching them up
+        // around the set. The levels don't depend on the pixels so tiles line up
+        let values: Vec<f64> = escapes
+            .iter()
+            .map(|escape| match escape {
+                Some(escape) => (escape.iteration as f64 + 1.0).ln(),
+                None => (MAX_ITER_NUM as f64 + 2.0).ln(),
+            })
+            .collect();
+        contours.draw_mut(&mut image, &values, 0.0, (MAX_ITER_NUM as f64 + 2.0).ln());
+    }
+
+    image
+}
+
+/// Gets where each pixel escaped with the formula or the variant, on the GPU if it can
+fn get_fractal_escapes(
+    options: &MandelbrotImageOptions,
+    set: FractalSet,
+    viewport: &ViewPort,
+    width: u32,
+    height: u32,
+) -> Vec<Option<Escape>> {
+    match &options.formula {
+        Some(formula) => {
+            if options.use_gpu {
+                warn!("Custom formulas can't run on the GPU, rendering on the CPU instead");
+            }
+            get_formula_escapes(formula, set, viewport, width, height)
+        }
+        None => {
+            // the GPU only has the standard mandelbrot and julia sets, and only sends
+            // back the iterations, not where z ended up
+            let gpu_problem = match (set, options.variant, options.coloring) {
+                (FractalSet::Phoenix { .. } | FractalSet::Magnet(_), _, _) => {
+                    Some("Phoenix and magnet fractals")
+                }
+                (_, FractalVariant::Standard, FractalColoring::BinaryDecomposition) => {
+                    Some("Binary decomposition")
+                }
+                (_, FractalVariant::Standard, FractalColoring::Iterations) => None,
+                (_, _, _) => Some("Fractal variants"),
+            };
+            let use_gpu = match gpu_problem {
+                Some(problem) if options.use_gpu => {
+                    warn!(
+                        "{} can't run on the GPU, rendering on the CPU instead",
+                        problem
                     );
-                    image[(x, y)] = Rgba(grad_color);
-                } else {
-                    image[(x, y)] = Rgba(converted_color);
+                    false
                 }
-            } else {
-                image[(x, y)] = Rgba(converted_background_color);
+                Some(_) => false,
+                None => options.use_gpu,
+            };
+            get_escapes(use_gpu, set, options.variant, viewport, width, height)
+        }
+    }
+}
+
+/// Renders the set to positions along a palette rather than colours, so the palette can be
+/// cycled through it. The palette goes round once every few iterations so the bands next
+/// to each other stand out, and the points in the set are left as the background
+pub fn generate_fractal_indexed_image(
+    options: &MandelbrotImageOptions,
+    set: FractalSet,
+    mut viewport: ViewPort,
+    width: u32,
+    height: u32,
+) -> IndexedImage {
+    viewport.fit_to_size(width, height);
+    let escapes = get_fractal_escapes(options, set, &viewport, width, height);
+    let positions = escapes
+        .into_iter()
+        .map(|escape| match (escape, options.coloring) {
+            (Some(escape), FractalColoring::BinaryDecomposition) if escape.z.im < 0.0 => None,
+            (Some(escape), _) => Some(escape.iteration as f64 / ITERATIONS_PER_CYCLE),
+            (None, _) => None,
+        })
+        .collect();
+    IndexedImage::new(width, height, positions)
+}
+
+/// Gets where each pixel escaped in row order, None if it never escaped
+fn get_escapes(
+    use_gpu: bool,
+    set: FractalSet,
+    variant: FractalVariant,
+    viewport: &ViewPort,
+    width: u32,
+    height: u32,
+) -> Vec<Option<Escape>> {
+    if use_gpu {
+        #[cfg(feature = "gpu")]
+        match crate::gpu::get_fractal_escapes(set, viewport, width, height) {
+            // only the iteration comes back, which is all that's used unless the
+            // coloring needs z, in which case the GPU isn't used
+            Some(escapes) => {
+                return escapes
+                    .into_iter()
+                    .map(|escape| {
+                        escape.map(|iteration| Escape {
+                            iteration,
+                            z: Complex64::new(f64::NAN, f64::NAN),
+                        })
+                    })
+                    .collect();
             }
+            None => warn!("No GPU available, rendering on the CPU instead"),
         }
+        #[cfg(not(feature = "gpu"))]
+        warn!("Built without the gpu feature, rendering on the CPU instead");
     }
 
-    DynamicImage::ImageRgba8(image)
+    get_cpu_escapes(set, variant, viewport, width, height, true)
 }
 
-fn check_mandelbrot(complex: Complex64) -> Option<u32> {
-    let z = Complex64::new(0.0, 0.0);
+/// Runs the escape loop on the CPU, 4 pixels at a time with SIMD unless turned off
+///
+/// Only public so the benchmarks can compare the two
+#[doc(hidden)]
+pub fn get_cpu_escapes(
+    set: FractalSet,
+    variant: FractalVariant,
+    viewport: &ViewPort,
+    width: u32,
+    height: u32,
+    use_simd: bool,
+) -> Vec<Option<Escape>> {
+    // only the standard mandelbrot and julia loops have SIMD versions
+    let has_simd = matches!(set, FractalSet::Mandelbrot | FractalSet::Julia(_))
+        && variant == FractalVariant::Standard;
+    if use_simd && has_simd {
+        return simd::get_escapes(set, viewport, width, height);
+    }
+    let step = get_step(variant);
+
+    let mut escapes: Vec<_> = (0..height)
+        .take_while(|_| !is_cancelled())
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let complex = viewport.get_point(x, y, width, height);
 
-    check_mandelbrot_recursion(z, complex, 0)
+            match set {
+                FractalSet::Mandelbrot => check_escape(Complex64::new(0.0, 0.0), complex, step),
+                FractalSet::Julia(constant) => check_escape(complex, constant, step),
+                FractalSet::Phoenix { c, p } => check_phoenix(complex, c, p),
+                FractalSet::Magnet(magnet_type) => check_magnet(complex, magnet_type),
+            }
+        })
+        .collect();
+    // the rows that didn't get done when cancelled are left inside the set
+    escapes.resize(width as usize * height as usize, None);
+    escapes
 }
 
-fn check_mandelbrot_recursion(z: Complex64, c: Complex64, iteration_num: u32) -> Option<u32> {
-    let new_z = z * z + c;
-    if new_z.re.abs() > 20.0 || new_z.im.abs() > 20.0 {
-        // return the iteration number for gradient
-        return Some(iteration_num);
+/// Runs the escape loop with a custom formula, which only works on the CPU
+fn get_formula_escapes(
+    formula: &Formula,
+    set: FractalSet,
+    viewport: &ViewPort,
+    width: u32,
+    height: u32,
+) -> Vec<Option<Escape>> {
+    let mut escapes: Vec<_> = (0..height)
+        .take_while(|_| !is_cancelled())
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let point = viewport.get_point(x, y, width, height);
+            // z starts at the point rather than 0, as plenty of formulas like
+            // z^3 + c*sin(z) would stay at 0 forever. The formula takes over from the
+            // set's own sum, so the other sets only say whether c is the point
+            let (mut z, c) = match set {
+                FractalSet::Mandelbrot | FractalSet::Magnet(_) => (point, point),
+                FractalSet::Julia(constant) | FractalSet::Phoenix { c: constant, .. } => {
+                    (point, constant)
+                }
+            };
+
+            for iteration_num in 0..=MAX_ITER_NUM + 1 {
+                z = formula.evaluate(z, c);
+                // formulas like 1/z can blow up to infinity or NaN, which counts as escaping
+                if !(z.re.abs() <= 20.0 && z.im.abs() <= 20.0) {
+                    return Some(Escape {
+                        iteration: iteration_num,
+                        z,
+                    });
+                }
+            }
+            None
+        })
+        .collect();
+    escapes.resize(width as usize * height as usize, None);
+    escapes
+}
+
+/// One iteration of a Mandelbrot-like fractal, going from z to the next z
+type Step = fn(Complex64, Complex64) -> Complex64;
+
+/// Gets the iteration for the variant, which the mandelbrot and julia sets both use
+pub(crate) fn get_step(variant: FractalVariant) -> Step {
+    match variant {
+        FractalVariant::Standard => |z, c| z * z + c,
+        FractalVariant::Tricorn => |z, c| z.conj() * z.conj() + c,
+        FractalVariant::Celtic => |z, c| {
+            let squared = z * z;
+            Complex64::new(squared.re.abs(), squared.im) + c
+        },
+        FractalVariant::PerpendicularBurningShip => |z, c| {
+            let folded = Complex64::new(z.re, -z.im.abs());
+            folded * folded + c
+        },
+    }
+}
+
+fn check_escape(mut z: Complex64, c: Complex64, step: Step) -> Option<Escape> {
+    for iteration_num in 0..=MAX_ITER_NUM + 1 {
+        z = step(z, c);
+        if z.re.abs() > 20.0 || z.im.abs() > 20.0 {
+            // return the iteration number for gradient
+            return Some(Escape {
+                iteration: iteration_num,
+                z,
+            });
+        }
+    }
+    None
+}
+
+fn check_phoenix(point: Complex64, c: Complex64, p: Complex64) -> Option<Escape> {
+    let (mut z, mut previous_z) = (point, Complex64::new(0.0, 0.0));
+    for iteration_num in 0..=MAX_ITER_NUM + 1 {
+        (z, previous_z) = (z * z + c + p * previous_z, z);
+        if z.re.abs() > 20.0 || z.im.abs() > 20.0 {
+            return Some(Escape {
+                iteration: iteration_num,
+                z,
+            });
+        }
+    }
+    None
+}
+
+fn check_magnet(c: Complex64, magnet_type: MagnetType) -> Option<Escape> {
+    let one = Complex64::new(1.0, 0.0);
+    let mut z = Complex64::new(0.0, 0.0);
+    for iteration_num in 0..=MAX_ITER_NUM + 1 {
+        let ratio = match magnet_type {
+            MagnetType::One => (z * z + c - 1.0) / (2.0 * z + c - 2.0),
+            MagnetType::Two => {
+                (z * z * z + 3.0 * (c - 1.0) * z + (c - 1.0) * (c - 2.0))
+                    / (3.0 * z * z + 3.0 * (c - 2.0) * z + (c - 1.0) * (c - 2.0) + 1.0)
+            }
+        };
+        z = ratio * ratio;
+        // dividing by 0 gives NaN, which counts as escaping
+        let has_escaped = !(z.re.abs() <= 20.0 && z.im.abs() <= 20.0);
+        if has_escaped || (z - one).norm_sqr() < 1e-6 {
+            return Some(Escape {
+                iteration: iteration_num,
+                z,
+            });
+        }
+    }
+    None
+}
+
+/// Gets the points z goes through for c until it escapes or runs out of iterations,
+/// starting at 0 or at c for custom formulas like the escapes do
+pub fn get_orbit(
+    c: Complex64,
+    formula: Option<&Formula>,
+    variant: FractalVariant,
+) -> Vec<Complex64> {
+    let step = get_step(variant);
+    let mut z = match formula {
+        Some(_) => c,
+        None => Complex64::new(0.0, 0.0),
+    };
+    let mut orbit = vec![z];
+    for _ in 0..=MAX_ITER_NUM {
+        z = match formula {
+            Some(formula) => formula.evaluate(z, c),
+            None => step(z, c),
+        };
+        if !z.is_finite() {
+            break;
+        }
+        orbit.push(z);
+        if z.re.abs() > 20.0 || z.im.abs() > 20.0 {
+            break;
+        }
+    }
+
+    orbit
+}
+
+/// An orbit turned into dots joined by lines in the full image, which can be drawn
+/// onto the whole image or any tile of it
+pub struct OrbitOverlay {
+    dots: Vec<(f32, f32)>,
+    lines: Vec<((f32, f32), (f32, f32))>,
+    color: Rgba<u8>,
+}
+
+impl OrbitOverlay {
+    pub fn new(
+        orbit: &[Complex64],
+        mut viewport: ViewPort,
+        width: u32,
+        height: u32,
+        color: Rgba<u8>,
+    ) -> Self {
+        viewport.fit_to_size(width, height);
+        let pixels: Vec<(f32, f32)> = orbit
+            .iter()
+            .map(|&point| viewport.get_pixel(point, width, height))
+            .collect();
+
+        // escaping points can be millions of pixels away, so the lines are cut down to
+        // just past the edges rather than drawn all the way there. This happens in the
+        // full image so the lines go through the same pixels on every tile
+        let margin = ORBIT_DOT_RADIUS as f32;
+        let bounds = (-margin, width.max(height) as f32 + margin);
+        let is_inside = |value: f32| (bounds.0..bounds.1).contains(&value);
+        // rounded to whole pixels, as moving a fraction into the negatives when drawing
+        // onto a tile would round it the other way
+        let round = |(x, y): (f32, f32)| (x.round(), y.round());
+        Self {
+            dots: pixels
+                .iter()
+                .copied()
+                .filter(|&(x, y)| is_inside(x) && is_inside(y))
+                .map(round)
+                .collect(),
+            lines: pixels
+                .windows(2)
+                .filter_map(|line| clip_line(line[0], line[1], bounds))
+                .map(|(start, end)| (round(start), round(end)))
+                .collect(),
+            color,
+        }
+    }
+
+    /// Draws the orbit, with the offset being where the image starts in the full image
+    pub fn draw_mut(&self, image: &mut RgbaImage, offset: (u32, u32)) {
+        let shift = |(x, y): (f32, f32)| (x - offset.0 as f32, y - offset.1 as f32);
+        for &(start, end) in self.lines.iter() {
+            draw_line_segment_mut(image, shift(start), shift(end), self.color);
+        }
+        for &dot in self.dots.iter() {
+            let (x, y) = shift(dot);
+            draw_filled_circle_mut(image, (x as i32, y as i32), ORBIT_DOT_RADIUS, self.color);
+        }
     }
+}
 
-    if iteration_num > MAX_ITER_NUM {
+/// Cuts a line down to the part inside a square, using the Liang-Barsky algorithm
+fn clip_line(
+    start: (f32, f32),
+    end: (f32, f32),
+    (min, max): (f32, f32),
+) -> Option<((f32, f32), (f32, f32))> {
+    let delta = (end.0 - start.0, end.1 - start.1);
+    let (mut enter, mut exit) = (0.0f32, 1.0f32);
+    for (step, distance) in [
+        (-delta.0, start.0 - min),
+        (delta.0, max - start.0),
+        (-delta.1, start.1 - min),
+        (delta.1, max - start.1),
+    ] {
+        if step == 0.0 {
+            if distance < 0.0 {
+                return None;
+            }
+            continue;
+        }
+        let amount = distance / step;
+        if step < 0.0 {
+            enter = enter.max(amount);
+        } else {
+            exit = exit.min(amount);
+        }
+    }
+    if enter > exit {
         return None;
     }
 
-    check_mandelbrot_recursion(new_z, c, iteration_num + 1)
+    let point = |amount: f32| (start.0 + delta.0 * amount, start.1 + delta.1 * amount);
+    Some((point(enter), point(exit)))
 }
 
 fn get_interp(color1: [u8; 4], color2: [u8; 4], amount: f64) -> [u8; 4] {
-    let interp = |x: u8, y: u8| ((x as f64) * amount + (y as f64) * (1.0 - amount)) as u8;
+    let interp = |x: f64, y: f64| x * amount + y * (1.0 - amount);
+    let alpha = interp(color1[3] as f64, color2[3] as f64);
+    // when the alphas differ the colours are weighted by them, so fading into a
+    // transparent background doesn't fade to black on the way
+    let (weight1, weight2) = match color1[3] == color2[3] || alpha == 0.0 {
+        true => (1.0, 1.0),
+        false => (color1[3] as f64 / alpha, color2[3] as f64 / alpha),
+    };
+    let interp_channel = |channel: usize| {
+        interp(
+            color1[channel] as f64 * weight1,
+            color2[channel] as f64 * weight2,
+        ) as u8
+    };
 
     [
-        interp(color1[0], color2[0]),
-        interp(color1[1], color2[1]),
-        interp(color1[2], color2[2]),
-        interp(color1[3], color2[3]),
+        interp_channel(0),
+        interp_channel(1),
+        interp_channel(2),
+        alpha as u8,
     ]
 }
 
-struct ViewPort {
+/// The region of the complex plane that gets drawn
+#[derive(Clone, Copy, Debug)]
+pub struct ViewPort {
     centre: Complex64,
     real_diameter: f64,
     imaginary_diameter: f64,
 }
 
 impl ViewPort {
-    fn normal_mandelbrot() -> Self {
+    pub fn normal_mandelbrot() -> Self {
         Self {
             centre: Complex64::new(-0.7, 0.0),
             real_diameter: 3.0769,
             imaginary_diameter: 2.307675,
         }
     }
+
+    /// The same size as the normal mandelbrot view but zoomed in around the centre
+    pub fn new(centre: Complex64, zoom: f64) -> Self {
+        let mut viewport = Self {
+            centre,
+            ..Self::normal_mandelbrot()
+        };
+        viewport.zoom(zoom);
+        viewport
+    }
+
+    pub fn normal_julia() -> Self {
+        Self {
+            centre: Complex64::new(0.0, 0.0),
+            real_diameter: 3.6,
+            imaginary_diameter: 2.7,
+        }
+    }
+
+    pub fn centre(&self) -> Complex64 {
+        self.centre
+    }
+
+    pub fn real_diameter(&self) -> f64 {
+        self.real_diameter
+    }
+
+    /// Changes the imaginary diameter so the viewport isn't stretched at this image size
+    pub fn fit_to_size(&mut self, width: u32, height: u32) {
+        self.imaginary_diameter = self.real_diameter * height as f64 / width.max(1) as f64;
+    }
+
+    /// Moves the centre by a fraction of the viewport's size
+    pub fn pan(&mut self, real_amount: f64, imaginary_amount: f64) {
+        self.centre.re += real_amount * self.real_diameter;
+        self.centre.im += imaginary_amount * self.imaginary_diameter;
+    }
+
+    /// Zooms in around the centre, factors above 1 zoom in and below 1 zoom out
+    pub fn zoom(&mut self, factor: f64) {
+        self.real_diameter /= factor;
+        self.imaginary_diameter /= factor;
+    }
+
+    /// Gets the viewport covering just the tile of an image this size
+    pub fn get_tile(&self, tile: Tile, width: u32, height: u32) -> Self {
+        let real_diameter = self.real_diameter * tile.width as f64 / width as f64;
+        let imaginary_diameter = self.imaginary_diameter * tile.height as f64 / height as f64;
+        // the top left of the tile has to land on the same point as that pixel in the
+        // full image, so work out the centre from there
+        let corner = self.get_point(tile.x, tile.y, width, height);
+
+        Self {
+            centre: Complex64::new(
+                corner.re + real_diameter / 2.0,
+                corner.im + imaginary_diameter / 2.0,
+            ),
+            real_diameter,
+            imaginary_diameter,
+        }
+    }
+
+    /// Gets the point in the complex plane for a pixel of an image this size
+    pub(crate) fn get_point(&self, x: u32, y: u32, width: u32, height: u32) -> Complex64 {
+        let real = (x as f64) / (width as f64) * self.real_diameter - self.real_diameter / 2.0
+            + self.centre.re;
+        let imaginary = (y as f64) / (height as f64) * self.imaginary_diameter
+            - self.imaginary_diameter / 2.0
+            + self.centre.im;
+
+        Complex64::new(real, imaginary)
+    }
+
+    /// Gets the pixel a point in the complex plane lands on in an image this size, the
+    /// opposite of get_point
+    pub fn get_pixel(&self, point: Complex64, width: u32, height: u32) -> (f32, f32) {
+        let x = (point.re - self.centre.re + self.real_diameter / 2.0) / self.real_diameter
+            * width as f64;
+        let y = (point.im - self.centre.im + self.imaginary_diameter / 2.0)
+            / self.imaginary_diameter
+            * height as f64;
+
+        (x as f32, y as f32)
+    }
+}
+
+/// The Phoenix fractal, where each z also takes in the z before it
+#[derive(clap::Args, Debug)]
+pub struct PhoenixArgs {
+    #[arg(short, long, default_value = "black", value_parser = parse_color)]
+    color: Color,
+
+    /// The colour behind the image, which can be transparent
+    #[arg(short, long, alias = "background", default_value = "white", value_parser = parse_color)]
+    background_color: Color,
+
+    #[arg(short, long, default_value = "false")]
+    gradient: bool,
+
+    /// The constant added on every iteration, given as real,imaginary
+    #[arg(long, default_value = "0.5667,0.0", value_parser = parse_complex, allow_hyphen_values = true)]
+    c: Complex64,
+
+    /// How much of the z before it goes into each z, given as real,imaginary
+    #[arg(long, default_value = "-0.5,0.0", value_parser = parse_complex, allow_hyphen_values = true)]
+    p: Complex64,
+
+    /// The point in the middle of the image, given as real,imaginary
+    #[arg(long, default_value = "0.0,0.0", value_parser = parse_complex, allow_hyphen_values = true)]
+    center: Complex64,
+
+    /// How far to zoom in around the center
+    #[arg(long, default_value = "1.0")]
+    zoom: f64,
+
+    #[arg(long, default_value = "1600", value_parser = parse_size)]
+    width: u32,
+
+    #[arg(long, default_value = "1200", value_parser = parse_size)]
+    height: u32,
+
+    /// How the points outside the set are coloured, binary-decomposition shows its
+    /// field lines
+    #[arg(long, default_value = "iterations")]
+    coloring: FractalColoring,
+}
+
+pub struct PhoenixGenerator;
+
+impl Generator for PhoenixGenerator {
+    type Args = PhoenixArgs;
+
+    fn name(&self) -> &'static str {
+        "phoenix"
+    }
+
+    fn is_animated(&self, _args: &PhoenixArgs) -> bool {
+        false
+    }
+
+    fn estimate_memory(&self, args: &PhoenixArgs) -> Option<MemoryEstimate> {
+        Some(MemoryEstimate::still(args.width, args.height))
+    }
+
+    fn generate(&self, args: PhoenixArgs) -> Result<Generated, GeneratorError> {
+        let options = get_cpu_only_options(
+            args.color,
+            args.background_color,
+            args.gradient,
+            args.coloring,
+        );
+        let set = FractalSet::Phoenix {
+            c: args.c,
+            p: args.p,
+        };
+        let viewport = ViewPort::new(args.center, args.zoom);
+
+        Ok(Generated::Still(
+            generate_fractal_image(options, set, viewport, args.width, args.height).into_rgba8(),
+        ))
+    }
+}
+
+/// The magnet fractals, which come from models of magnets in physics
+#[derive(clap::Args, Debug)]
+pub struct MagnetArgs {
+    #[arg(short, long, default_value = "black", value_parser = parse_color)]
+    color: Color,
+
+    /// The colour behind the image, which can be transparent
+    #[arg(short, long, alias = "background", default_value = "white", value_parser = parse_color)]
+    background_color: Color,
+
+    #[arg(short, long, default_value = "false")]
+    gradient: bool,
+
+    #[arg(long = "type", default_value = "one")]
+    magnet_type: MagnetType,
+
+    /// The point in the middle of the image, given as real,imaginary
+    #[arg(long, default_value = "1.4,0.0", value_parser = parse_complex, allow_hyphen_values = true)]
+    center: Complex64,
+
+    /// How far to zoom in around the center, the set is about twice as wide as the
+    /// mandelbrot set
+    #[arg(long, default_value = "0.5")]
+    zoom: f64,
+
+    #[arg(long, default_value = "1600", value_parser = parse_size)]
+    width: u32,
+
+    #[arg(long, default_value = "1200", value_parser = parse_size)]
+    height: u32,
+
+    /// How the points outside the set are coloured, binary-decomposition shows its
+    /// field lines
+    #[arg(long, default_value = "iterations")]
+    coloring: FractalColoring,
+}
+
+pub struct MagnetGenerator;
+
+impl Generator for MagnetGenerator {
+    type Args = MagnetArgs;
+
+    fn name(&self) -> &'static str {
+        "magnet"
+    }
+
+    fn is_animated(&self, _args: &MagnetArgs) -> bool {
+        false
+    }
+
+    fn estimate_memory(&self, args: &MagnetArgs) -> Option<MemoryEstimate> {
+        Some(MemoryEstimate::still(args.width, args.height))
+    }
+
+    fn generate(&self, args: MagnetArgs) -> Result<Generated, GeneratorError> {
+        let options = get_cpu_only_options(
+            args.color,
+            args.background_color,
+            args.gradient,
+            args.coloring,
+        );
+        let set = FractalSet::Magnet(args.magnet_type);
+        let viewport = ViewPort::new(args.center, args.zoom);
+
+        Ok(Generated::Still(
+            generate_fractal_image(options, set, viewport, args.width, args.height).into_rgba8(),
+        ))
+    }
+}
+
+/// The options for the sets which only have the standard sum and can't go on the GPU
+fn get_cpu_only_options(
+    color: Color,
+    background_color: Color,
+    gradient: bool,
+    coloring: FractalColoring,
+) -> MandelbrotImageOptions {
+    MandelbrotImageOptions::new(
+        color,
+        background_color,
+        gradient,
+        false,
+        None,
+        coloring,
+        FractalVariant::Standard,
+        None,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simd_matches_scalar() {
+        // an odd width so the last lanes of each row are thrown away
+        let (width, height) = (101, 60);
+        let mut viewport = ViewPort::new(Complex64::new(-0.745, 0.11), 20.0);
+        viewport.fit_to_size(width, height);
+
+        for set in [
+            FractalSet::Mandelbrot,
+            FractalSet::Julia(Complex64::new(-0.8, 0.156)),
+        ] {
+            assert_eq!(
+                get_cpu_escapes(
+                    set,
+                    FractalVariant::Standard,
+                    &viewport,
+                    width,
+                    height,
+                    true
+                ),
+                get_cpu_escapes(
+                    set,
+                    FractalVariant::Standard,
+                    &viewport,
+                    width,
+                    height,
+                    false
+                ),
+            );
+        }
+    }
+
+    #[test]
+    fn test_phoenix_without_p_is_julia() {
+        let (width, height) = (40, 30);
+        let mut viewport = ViewPort::normal_julia();
+        viewport.fit_to_size(width, height);
+        let c = Complex64::new(-0.8, 0.156);
+
+        let phoenix = FractalSet::Phoenix {
+            c,
+            p: Complex64::new(0.0, 0.0),
+        };
+        assert_eq!(
+            get_cpu_escapes(
+                phoenix,
+                FractalVariant::Standard,
+                &viewport,
+                width,
+                height,
+                false
+            ),
+            get_cpu_escapes(
+                FractalSet::Julia(c),
+                FractalVariant::Standard,
+                &viewport,
+                width,
+                height,
+                false
+            ),
+        );
+    }
+
+    #[test]
+    fn test_tile_points() {
+        let (width, height) = (400, 300);
+        let viewport = ViewPort::new(Complex64::new(-0.745, 0.11), 20.0);
+        let tile = Tile {
+            x: 150,
+            y: 100,
+            width: 90,
+            height: 70,
+        };
+        let tile_viewport = viewport.get_tile(tile, width, height);
+
+        for (x, y) in [(0, 0), (45, 12), (89, 69)] {
+            let point = viewport.get_point(tile.x + x, tile.y + y, width, height);
+            let tile_point = tile_viewport.get_point(x, y, tile.width, tile.height);
+            assert!((point - tile_point).norm() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_contour_tiles_match_image() {
+        let (width, height) = (120, 90);
+        let get_options = || {
+            MandelbrotImageOptions::new(
+                Color::new(0.0, 0.0, 0.0, 1.0),
+                Color::new(1.0, 1.0, 1.0, 1.0),
+                false,
+                false,
+                None,
+                FractalColoring::Iterations,
+                FractalVariant::Standard,
+                Some(ContourOptions::new(10, Color::new(1.0, 0.0, 0.0, 1.0))),
+            )
+        };
+        let options = get_options();
+        let viewport = ViewPort::normal_mandelbrot();
+        let image = generate_mandelbrot_image(get_options(), viewport, width, height).into_rgba8();
+
+        // the contours crossing between tiles have to join up like they do in one go
+        for tile in TileGrid::new(3, 2)
+            .get_tile_rows(width, height)
+            .into_iter()
+            .flatten()
+        {
+            let tile_image = generate_mandelbrot_tile(&options, viewport, width, height, tile);
+            let expected = imageops::crop_imm(&image, tile.x, tile.y, tile.width, tile.height);
+            assert!(tile_image == expected.to_image());
+        }
+    }
+
+    #[test]
+    fn test_orbit() {
+        let (width, height) = (400, 300);
+        let viewport = ViewPort::new(Complex64::new(-0.745, 0.11), 20.0);
+        let (x, y) = viewport.get_pixel(viewport.get_point(123, 45, width, height), width, height);
+        assert!((x - 123.0).abs() < 1e-3 && (y - 45.0).abs() < 1e-3);
+
+        // inside the set it never escapes, outside it's gone after a few steps
+        assert_eq!(
+            get_orbit(Complex64::new(-1.0, 0.0), None, FractalVariant::Standard).len(),
+            MAX_ITER_NUM as usize + 2
+        );
+        let orbit = get_orbit(Complex64::new(2.0, 2.0), None, FractalVariant::Standard);
+        assert_eq!(orbit[1], Complex64::new(2.0, 2.0));
+        assert_eq!(orbit.len(), 4);
+
+        assert_eq!(
+            clip_line((-10.0, 5.0), (30.0, 5.0), (0.0, 20.0)),
+            Some(((0.0, 5.0), (20.0, 5.0)))
+        );
+        assert_eq!(clip_line((-10.0, -5.0), (30.0, -5.0), (0.0, 20.0)), None);
+    }
 }
@@ -0,0 +1,237 @@
+//! The escape loop for 4 pixels at once using SIMD
+//!
+//! On x86_64 this checks at runtime whether the CPU has AVX2, and if so runs all 4
+//! lanes in a single 256 bit register. Otherwise the lanes go through `wide`, which
+//! uses whatever the crate was compiled for, like a pair of SSE2 registers. Each lane
+//! does exactly the same sums as the scalar loop so the images come out identical
+
+use num_complex::Complex64;
+use wide::f64x4;
+
+use super::{Escape, FractalSet, MAX_ITER_NUM, ViewPort};
+use crate::cancel::is_cancelled;
+
+const LANES: usize = 4;
+
+pub(super) fn get_escapes(
+    set: FractalSet,
+    viewport: &ViewPort,
+    width: u32,
+    height: u32,
+) -> Vec<Option<Escape>> {
+    #[cfg(target_arch = "x86_64")]
+    if std::arch::is_x86_feature_detected!("avx2") {
+        // safe as we've just checked the cpu supports avx2
+        return unsafe { get_escapes_avx2(set, viewport, width, height) };
+    }
+
+    get_escapes_with(set, viewport, width, height, check_lanes)
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+fn get_escapes_avx2(
+    set: FractalSet,
+    viewport: &ViewPort,
+    width: u32,
+    height: u32,
+) -> Vec<Option<Escape>> {
+    // closures inside take on avx2 too, so they can call the avx2 lanes directly
+    get_escapes_with(set, viewport, width, height, |set, points| {
+        check_lanes_avx2(set, points)
+    })
+}
+
+/// Goes along each row a few pixels at a time, checking them with the lanes
+#[inline(always)]
+fn get_escapes_with(
+    set: FractalSet,
+    viewport: &ViewPort,
+    width: u32,
+    height: u32,
+    check_lanes: impl Fn(FractalSet, [Complex64; LANES]) -> [Option<Escape>; LANES],
+) -> Vec<Option<Escape>> {
+    let mut escapes = Vec::with_capacity(width as usize * height as usize);
+
+    for y in 0..height {
+        if is_cancelled() {
+            break;
+        }
+        for x in (0..width).step_by(LANES) {
+            // the lanes past the end of the row get worked out but then thrown away
+            let points: [Complex64; LANES] =
+                std::array::from_fn(|lane| viewport.get_point(x + lane as u32, y, width, height));
+            let lane_escapes = check_lanes(set, points);
+
+            let pixel_count = (width - x).min(LANES as u32) as usize;
+            escapes.extend_from_slice(&lane_escapes[..pixel_count]);
+        }
+    }
+    // the rows that didn't get done when cancelled are left inside the set
+    escapes.resize(width as usize * height as usize, None);
+
+    escapes
+}
+
+#[inline(always)]
+fn check_lanes(set: FractalSet, points: [Complex64; LANES]) -> [Option<Escape>; LANES] {
+    let point_re = f64x4::from(points.map(|point| point.re));
+    let point_im = f64x4::from(points.map(|point| point.im));
+    let (mut z_re, mut z_im, c_re, c_im) = match set {
+        FractalSet::Mandelbrot => (f64x4::ZERO, f64x4::ZERO, point_re, point_im),
+        FractalSet::Julia(constant) => (
+            point_re,
+            point_im,
+            f64x4::splat(constant.re),
+            f64x4::splat(constant.im),
+        ),
+        FractalSet::Phoenix { .. } | FractalSet::Magnet(_) => {
+            unreachable!("only the mandelbrot and julia sets are worked out with SIMD")
+        }
+    };
+    let limit = f64x4::splat(20.0);
+    let all_escaped = (1 << LANES) - 1;
+
+    let mut escapes = [None; LANES];
+    let mut escaped = 0;
+    for iteration_num in 0..=MAX_ITER_NUM + 1 {
+        // z * z + c, written out the same way as Complex64 does it
+        let new_re = z_re * z_re - z_im * z_im + c_re;
+        let new_im = z_re * z_im + z_im * z_re + c_im;
+        (z_re, z_im) = (new_re, new_im);
+
+        let outside = (z_re.abs().simd_gt(limit) | z_im.abs().simd_gt(limit)).to_bitmask();
+        let newly_escaped = outside & !escaped;
+        if newly_escaped != 0 {
+            let (re_lanes, im_lanes) = (z_re.to_array(), z_im.to_array());
+            set_escapes(
+                &mut escapes,
+                newly_escaped,
+                iteration_num,
+                re_lanes,
+                im_lanes,
+            );
+            escaped |= newly_escaped;
+            if escaped == all_escaped {
+                break;
+            }
+        }
+    }
+
+    escapes
+}
+
+/// The same as check_lanes with the lanes in an AVX register
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+fn check_lanes_avx2(set: FractalSet, points: [Complex64; LANES]) -> [Option<Escape>; LANES] {
+    use std::arch::x86_64::*;
+
+    let point_re = _mm256_setr_pd(points[0].re, points[1].re, points[2].re, points[3].re);
+    let point_im = _mm256_setr_pd(points[0].im, points[1].im, points[2].im, points[3].im);
+    let (mut z_re, mut z_im, c_re, c_im) = match set {
+        FractalSet::Mandelbrot => (_mm256_setzero_pd(), _mm256_setzero_pd(), point_re, point_im),
+        FractalSet::Julia(constant) => (
+            point_re,
+            point_im,
+            _mm256_set1_pd(constant.re),
+            _mm256_set1_pd(constant.im),
+        ),
+        FractalSet::Phoenix { .. } | FractalSet::Magnet(_) => {
+            unreachable!("only the mandelbrot and julia sets are worked out with SIMD")
+        }
+    };
+    let limit = _mm256_set1_pd(20.0);
+    // clearing the sign bit gives the absolute value
+    let sign_bit = _mm256_set1_pd(-0.0);
+    let all_escaped = (1 << LANES) - 1;
+
+    let mut escapes = [None; LANES];
+    let mut escaped = 0;
+    for iteration_num in 0..=MAX_ITER_NUM + 1 {
+        // z * z + c, written out the same way as Complex64 does it
+        let new_re = _mm256_add_pd(
+            _mm256_sub_pd(_mm256_mul_pd(z_re, z_re), _mm256_mul_pd(z_im, z_im)),
+            c_re,
+        );
+        let new_im = _mm256_add_pd(
+            _mm256_add_pd(_mm256_mul_pd(z_re, z_im), _mm256_mul_pd(z_im, z_re)),
+            c_im,
+        );
+        (z_re, z_im) = (new_re, new_im);
+
+        let outside = _mm256_or_pd(
+            _mm256_cmp_pd::<_CMP_GT_OQ>(_mm256_andnot_pd(sign_bit, z_re), limit),
+            _mm256_cmp_pd::<_CMP_GT_OQ>(_mm256_andnot_pd(sign_bit, z_im), limit),
+        );
+        let newly_escaped = _mm256_movemask_pd(outside) as u32 & !escaped;
+        if newly_escaped != 0 {
+            let (mut re_lanes, mut im_lanes) = ([0.0; LANES], [0.0; LANES]);
+            // safe as the arrays hold exactly the 4 lanes
+            unsafe {
+                _mm256_storeu_pd(re_lanes.as_mut_ptr(), z_re);
+                _mm256_storeu_pd(im_lanes.as_mut_ptr(), z_im);
+            }
+            set_escapes(
+                &mut escapes,
+                newly_escaped,
+                iteration_num,
+                re_lanes,
+                im_lanes,
+            );
+            escaped |= newly_escaped;
+            if escaped == all_escaped {
+                break;
+            }
+        }
+    }
+
+    escapes
+}
+
+/// Fills in the escapes of the lanes set in the mask, with where z got to
+#[inline(always)]
+fn set_escapes(
+    escapes: &mut [Option<Escape>; LANES],
+    lane_mask: u32,
+    iteration_num: u32,
+    re_lanes: [f64; LANES],
+    im_lanes: [f64; LANES],
+) {
+    for (lane, escape) in escapes.iter_mut().enumerate() {
+        if lane_mask & (1 << lane) != 0 {
+            *escape = Some(Escape {
+                iteration: iteration_num,
+                z: Complex64::new(re_lanes[lane], im_lanes[lane]),
+            });
+        }
+    }
+}
+
+#[cfg(all(test, target_arch = "x86_64"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_avx2_matches_baseline() {
+        if !std::arch::is_x86_feature_detected!("avx2") {
+            return;
+        }
+        // an odd width so the last lanes of each row are thrown away
+        let (width, height) = (101, 60);
+        let mut viewport = ViewPort::new(Complex64::new(-0.745, 0.11), 20.0);
+        viewport.fit_to_size(width, height);
+
+        for set in [
+            FractalSet::Mandelbrot,
+            FractalSet::Julia(Complex64::new(-0.8, 0.156)),
+        ] {
+            // safe as the cpu was checked for avx2 above
+            let avx2_escapes = unsafe { get_escapes_avx2(set, &viewport, width, height) };
+            assert_eq!(
+                avx2_escapes,
+                get_escapes_with(set, &viewport, width, height, check_lanes)
+            );
+        }
+    }
+}
@@ -0,0 +1,208 @@
+//! Module for rendering the Mandelbulb, a 3D relative of the Mandelbrot set
+//!
+//! Squaring a complex number doubles its angle and squares its length. The Mandelbulb
+//! does the same to points in 3D with two angles instead of one, raising to the power
+//! of 8 rather than 2 as that gives the nicest shape. There's no surface to hit, so
+//! rays are marched towards it using an estimate of how far away it is.
+//! See <https://en.wikipedia.org/wiki/Mandelbulb> for more info
+
+use image::RgbaImage;
+use log::warn;
+
+use crate::{
+    generator::{Generated, Generator, GeneratorError},
+    memory::MemoryEstimate,
+    parse_size,
+    raymarch::{DistanceEstimator, RaymarchArgs, RaymarchOptions, Vec3, add, dot, scale},
+};
+
+/// Points further out than this always escape
+pub(crate) const BAILOUT: f64 = 2.0;
+
+/// The Mandelbulb itself, giving how far points are from it
+pub struct Mandelbulb {
+    power: f64,
+    iteration_num: u32,
+}
+
+impl Mandelbulb {
+    pub fn new(power: f64, iteration_num: u32) -> Self {
+        Self {
+            power,
+            iteration_num,
+        }
+    }
+}
+
+impl DistanceEstimator for Mandelbulb {
+    /// Gets an estimate of how far a point is from the surface along with how close its
+    /// orbit got to the middle
+    fn get_distance(&self, point: Vec3) -> (f64, f64) {
+        let mut z = point;
+        // how fast the orbit is getting further away as the point moves
+        let mut derivative = 1.0;
+        let mut radius = dot(z, z).sqrt();
+        let mut trap = radius;
+        for _ in 0..self.iteration_num {
+            if radius > BAILOUT {
+                break;
+            }
+            // the 3D version of raising to a power, multiplying both angles by it. The
+            // angles are measured from the y axis so the bulb stands upright
+            let theta = (z[1] / radius).acos() * self.power;
+            let phi = z[2].atan2(z[0]) * self.power;
+            derivative = radius.powf(self.power - 1.0) * self.power * derivative + 1.0;
+            let length = radius.powf(self.power);
+            z = add(
+                scale(
+                    [
+                        theta.sin() * phi.cos(),
+                        theta.cos(),
+                        theta.sin() * phi.sin(),
+                    ],
+                    length,
+                ),
+                point,
+            );
+            radius = dot(z, z).sqrt();
+            trap = trap.min(radius);
+        }
+
+        (0.5 * radius.ln() * radius / derivative, trap)
+    }
+}
+
+pub struct MandelbulbOptions {
+    mandelbulb: Mandelbulb,
+    raymarch: RaymarchOptions,
+    use_gpu: bool,
+}
+
+impl MandelbulbOptions {
+    pub fn new(mandelbulb: Mandelbulb, raymarch: RaymarchOptions, use_gpu: bool) -> Self {
+        Self {
+            mandelbulb,
+            raymarch,
+            use_gpu,
+        }
+    }
+}
+
+/// Gets how far each pixel's ray went before hitting the surface in row order, None if
+/// it missed
+fn get_distances(options: &MandelbulbOptions, raymarch: &RaymarchOptions) -> Vec<Option<f64>> {
+    if options.use_gpu {
+        #[cfg(feature = "gpu")]
+        {
+            let (width, height) = raymarch.size();
+            match crate::gpu::get_mandelbulb_distances(
+                raymarch.camera(),
+                options.mandelbulb.power,
+                options.mandelbulb.iteration_num,
+                width,
+                height,
+            ) {
+                Some(distances) => return distances,
+                None => warn!("No GPU available, rendering on the CPU instead"),
+            }
+        }
+        #[cfg(not(feature = "gpu"))]
+        warn!("Built without the gpu feature, rendering on the CPU instead");
+    }
+
+    raymarch.get_distances(&options.mandelbulb)
+}
+
+pub fn generate_mandelbulb_image(options: MandelbulbOptions) -> RgbaImage {
+    options.raymarch.render_views(|raymarch| {
+        raymarch.render(&options.mandelbulb, get_distances(&options, raymarch))
+    })
+}
+
+#[derive(clap::Args, Debug)]
+pub struct MandelbulbArgs {
+    /// The power the points are raised to, 8 gives the classic bulb
+    #[arg(long, default_value = "8.0")]
+    power: f64,
+
+    /// How many times each point is raised to the power, more gives finer detail
+    #[arg(short, long, default_value = "12", value_parser = parse_size)]
+    iterations: u32,
+
+    #[command(flatten)]
+    view: RaymarchArgs,
+
+    /// Marches the rays on the GPU when built with the gpu feature, falling back to the
+    /// CPU
+    #[arg(long)]
+    gpu: bool,
+}
+
+pub struct MandelbulbGenerator;
+
+impl Generator for MandelbulbGenerator {
+    type Args = MandelbulbArgs;
+
+    fn name(&self) -> &'static str {
+        "mandelbulb"
+    }
+
+    fn is_animated(&self, _args: &MandelbulbArgs) -> bool {
+        false
+    }
+
+    fn estimate_memory(&self, args: &MandelbulbArgs) -> Option<MemoryEstimate> {
+        Some(args.view.estimate_memory(1))
+    }
+
+    fn generate(&self, args: MandelbulbArgs) -> Result<Generated, GeneratorError> {
+        if args.power < 2.0 {
+            return Err(GeneratorError::InvalidOptions(
+                "the power has to be at least 2".to_string(),
+            ));
+        }
+        args.view.check().map_err(GeneratorError::InvalidOptions)?;
+
+        Ok(Generated::Still(generate_mandelbulb_image(
+            MandelbulbOptions::new(
+                Mandelbulb::new(args.power, args.iterations),
+                args.view.into_options(),
+                args.gpu,
+            ),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use csscolorparser::Color;
+
+    use super::*;
+    use crate::raymarch::Camera;
+
+    #[test]
+    fn test_march() {
+        let mandelbulb = Mandelbulb::new(8.0, 12);
+        let options = RaymarchOptions::new(
+            64,
+            64,
+            Camera::orbit(0.0, 0.0, 3.0, 40.0),
+            [1.0, 2.0, 2.0],
+            Color::new(1.0, 1.0, 1.0, 1.0),
+            None,
+            Color::new(0.0, 0.0, 0.0, 1.0),
+            None,
+        );
+        // the estimate is never more than the real distance to the bulb
+        let (outside, _) = mandelbulb.get_distance([0.0, 0.0, 1.5]);
+        assert!(outside > 0.0 && outside < 1.5);
+
+        // straight at the middle hits the surface between the sphere and the middle
+        let hit = options.march(&mandelbulb, [0.0, 0.0, -1.0]).unwrap();
+        assert!(hit > 3.0 - BAILOUT && hit < 3.0);
+        // and the surface really is there
+        let (distance, _) = mandelbulb.get_distance([0.0, 0.0, 3.0 - hit]);
+        assert!(distance.abs() < 0.01);
+        assert!(options.march(&mandelbulb, [0.0, 0.0, 1.0]).is_none());
+    }
+}
@@ -0,0 +1,216 @@
+//! The falling green glyphs from The Matrix
+//!
+//! Each column of the image has a drop falling down it, with a trail of glyphs behind
+//! the head which fade out the further they are from it
+
+use csscolorparser::Color;
+use image::{Rgba, RgbaImage};
+
+use crate::{
+    cancel::render_frames,
+    generator::{Generated, Generator, GeneratorError},
+    memory::MemoryEstimate,
+    palette::interpolate_color,
+    parse_color, parse_size,
+    text::draw_text_on_image,
+};
+
+const GLYPHS: &[char] = &[
+    '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', 'A', 'B', 'C', 'D', 'E', 'F', 'H', 'K', 'M',
+    'N', 'R', 'T', 'X', 'Z', '@', '#', '$', '%', '&', '*', '+', '=', '<', '>', '?', 'λ', 'Σ', 'Ж',
+    'Ψ', 'Ω',
+];
+/// The most times a glyph can change during the loop
+const MAX_GLYPH_CHANGES: u32 = 6;
+/// The most times a drop can fall down the screen during the loop
+const MAX_DROP_SPEED: u32 = 3;
+
+pub struct MatrixRainOptions {
+    width: u32,
+    height: u32,
+    frame_num: u32,
+    font_size: u32,
+    trail_length: u32,
+    seed: Option<u64>,
+    color: Color,
+    background_color: Color,
+}
+
+impl MatrixRainOptions {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        width: u32,
+        height: u32,
+        frame_num: u32,
+        font_size: u32,
+        trail_length: u32,
+        seed: Option<u64>,
+        color: Color,
+        background_color: Color,
+    ) -> Self {
+        Self {
+            width,
+            height,
+            frame_num,
+            font_size,
+            trail_length,
+            seed,
+            color,
+            background_color,
+        }
+    }
+}
+
+struct Drop {
+    /// The row the head starts at
+    offset: u32,
+    /// The number of times it goes through the whole column per loop
+    speed: u32,
+}
+
+struct Cell {
+    glyph_index: usize,
+    /// The number of times this glyph changes per loop
+    change_num: u32,
+}
+
+pub fn generate_matrix_rain_images(options: MatrixRainOptions) -> Vec<RgbaImage> {
+    let mut rng = match options.seed {
+        Some(seed) => fastrand::Rng::with_seed(seed),
+        None => fastrand::Rng::new(),
+    };
+
+    let columns = options.width / options.font_size;
+    let rows = options.height / options.font_size;
+    // the drop goes fully off the bottom before coming back round to the top
+    let column_period = rows + options.trail_length;
+
+    let drops: Vec<Drop> = (0..columns)
+        .map(|_| Drop {
+            offset: rng.u32(0..column_period),
+            speed: rng.u32(1..=MAX_DROP_SPEED),
+        })
+        .collect();
+    let cells: Vec<Cell> = (0..columns * rows)
+        .map(|_| Cell {
+            glyph_index: rng.usize(0..GLYPHS.len()),
+            change_num: rng.u32(0..=MAX_GLYPH_CHANGES),
+        })
+        .collect();
+
+    let color = options.color.to_rgba8();
+    let background_color = options.background_color.to_rgba8();
+    // the head of the drop is drawn brighter than the rest of the trail
+    let head_color = interpolate_color([255, 255, 255, 255], color, 0.7);
+
+    let render_frame = |frame: u32| {
+        let mut image =
+            RgbaImage::from_pixel(options.width, options.height, Rgba(background_color));
+
+        for (column, drop) in drops.iter().enumerate() {
+            let column = column as u32;
+            // whole numbers of periods per loop mean the animation loops seamlessly
+            let head = (drop.offset + frame * column_period * drop.speed / options.frame_num)
+                % column_period;
+
+            for distance in 0..options.trail_length {
+                let Some(row) = head.checked_sub(distance) else {
+                    break;
+                };
+                if row >= rows {
+                    continue;
+                }
+
+                let cell = &cells[(row * columns + column) as usize];
+                let glyph_index =
+                    cell.glyph_index + (frame * cell.change_num / options.frame_num) as usize;
+                let glyph = GLYPHS[glyph_index % GLYPHS.len()];
+
+                let glyph_color = if distance == 0 {
+                    head_color
+                } else {
+                    let brightness = 1.0 - distance as f64 / options.trail_length as f64;
+                    interpolate_color(color, background_color, brightness)
+                };
+
+                draw_text_on_image(
+                    &mut image,
+                    &glyph.to_string(),
+                    (
+                        (column * options.font_size) as i32,
+                        (row * options.font_size) as i32,
+                    ),
+                    options.font_size as f32,
+                    Rgba(glyph_color),
+                );
+            }
+        }
+
+        image
+    };
+
+    render_frames(0..options.frame_num, render_frame)
+}
+
+#[derive(clap::Args, Debug)]
+pub struct MatrixRainArgs {
+    #[arg(long, default_value = "640", value_parser = parse_size)]
+    width: u32,
+
+    #[arg(long, default_value = "480", value_parser = parse_size)]
+    height: u32,
+
+    /// The number of frames in the loop
+    #[arg(short, long, default_value = "60", value_parser = parse_size)]
+    frames: u32,
+
+    /// The size of each glyph, which is also the size of each cell in the grid
+    #[arg(long, default_value = "16", value_parser = parse_size)]
+    font_size: u32,
+
+    /// The number of glyphs in the trail behind each drop
+    #[arg(short, long, default_value = "12")]
+    trail_length: u32,
+
+    /// The seed used for the glyphs and drops, random if not given
+    #[arg(long)]
+    seed: Option<u64>,
+
+    #[arg(short, long, default_value = "#00ff41", value_parser = parse_color)]
+    color: Color,
+
+    #[arg(short, long, default_value = "black", value_parser = parse_color)]
+    background_color: Color,
+}
+
+pub struct MatrixRainGenerator;
+
+impl Generator for MatrixRainGenerator {
+    type Args = MatrixRainArgs;
+
+    fn name(&self) -> &'static str {
+        "matrix-rain"
+    }
+
+    fn is_animated(&self, _args: &MatrixRainArgs) -> bool {
+        true
+    }
+
+    fn estimate_memory(&self, args: &MatrixRainArgs) -> Option<MemoryEstimate> {
+        Some(MemoryEstimate::frames(args.width, args.height, args.frames))
+    }
+
+    fn generate(&self, args: MatrixRainArgs) -> Result<Generated, GeneratorError> {
+        let options = MatrixRainOptions::new(
+            args.width,
+            args.height,
+            args.frames,
+            args.font_size,
+            args.trail_length,
+            args.seed,
+            args.color,
+            args.background_color,
+        );
+        Ok(Generated::Frames(generate_matrix_rain_images(options)))
+    }
+}
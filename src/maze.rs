@@ -0,0 +1,935 @@
+//! Generates mazes using a few different algorithms
+//!
+//! See <https://en.wikipedia.org/wiki/Maze_generation_algorithm> for the algorithms.
+//! All of them make perfect mazes where there's exactly one path between any two cells.
+//! The order the passages were carved in is kept so the generation can be animated.
+//!
+//! The mazes can also be solved from the top left to the bottom right with a few
+//! different searches, animating the cells they visit and the frontier as they go.
+
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, VecDeque},
+};
+
+use csscolorparser::Color;
+use image::{DynamicImage, Rgba, RgbaImage};
+use imageproc::{drawing::draw_filled_rect_mut, rect::Rect};
+
+use crate::{
+    MazeAlgorithm, MazeSolver,
+    cancel::is_cancelled,
+    generator::{Generated, Generator, GeneratorError},
+    memory::MemoryEstimate,
+    parse_color, parse_size,
+};
+
+pub struct MazeOptions {
+    columns: u32,
+    rows: u32,
+    cell_size: u32,
+    wall_thickness: u32,
+    algorithm: MazeAlgorithm,
+    seed: Option<u64>,
+    wall_color: Color,
+    path_color: Color,
+}
+
+impl MazeOptions {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        columns: u32,
+        rows: u32,
+        cell_size: u32,
+        wall_thickness: u32,
+        algorithm: MazeAlgorithm,
+        seed: Option<u64>,
+        wall_color: Color,
+        path_color: Color,
+    ) -> Self {
+        Self {
+            columns,
+            rows,
+            cell_size,
+            wall_thickness,
+            algorithm,
+            seed,
+            wall_color,
+            path_color,
+        }
+    }
+
+    /// Checks the walls fit between the cells and the image isn't too big to make, giving
+    /// what to change if not
+    pub fn check(&self) -> Result<(), String> {
+        if self.columns == 0 || self.rows == 0 {
+            return Err("the maze has to have at least 1 column and 1 row".to_string());
+        }
+        if self.wall_thickness == 0 {
+            return Err("the walls have to be at least 1 pixel thick".to_string());
+        }
+        if self.cell_size <= self.wall_thickness {
+            return Err(format!(
+                "the cells have to be bigger than the walls to leave room for the paths, use a \
+                 --cell-size of at least {} or a thinner --wall-thickness",
+                self.wall_thickness + 1
+            ));
+        }
+        let fits = |cell_num: u32| {
+            cell_num
+                .checked_mul(self.cell_size)
+                .and_then(|length| length.checked_add(self.wall_thickness))
+                .is_some()
+        };
+        if !fits(self.columns) || !fits(self.rows) {
+            return Err(format!(
+                "a maze of {} by {} cells of {} pixels is too big for an image, use fewer \
+                 cells or a smaller --cell-size",
+                self.columns, self.rows, self.cell_size
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn get_image_size(&self) -> (u32, u32) {
+        (
+            self.columns * self.cell_size + self.wall_thickness,
+            self.rows * self.cell_size + self.wall_thickness,
+        )
+    }
+}
+
+pub struct MazeSolveOptions {
+    solver: MazeSolver,
+    visited_color: Color,
+    frontier_color: Color,
+    solution_color: Color,
+}
+
+impl MazeSolveOptions {
+    pub fn new(
+        solver: MazeSolver,
+        visited_color: Color,
+        frontier_color: Color,
+        solution_color: Color,
+    ) -> Self {
+        Self {
+            solver,
+            visited_color,
+            frontier_color,
+            solution_color,
+        }
+    }
+}
+
+/// The number of frames the solution is shown for at the end of the animation
+const SOLUTION_HOLD_FRAMES: usize = 20;
+
+/// A passage between two neighbouring cells, the first cell is always the lower index
+type Passage = (usize, usize);
+
+/// A grid of cells with walls between them, the cells are indexed row by row
+#[derive(Clone, Debug)]
+struct Maze {
+    columns: usize,
+    rows: usize,
+    /// Whether each cell has a wall on its right
+    east_walls: Vec<bool>,
+    /// Whether each cell has a wall below it
+    south_walls: Vec<bool>,
+    /// Whether each cell has been carved into yet
+    carved: Vec<bool>,
+}
+
+impl Maze {
+    fn new(columns: usize, rows: usize) -> Self {
+        Self {
+            columns,
+            rows,
+            east_walls: vec![true; columns * rows],
+            south_walls: vec![true; columns * rows],
+            carved: vec![false; columns * rows],
+        }
+    }
+
+    fn carve(&mut self, passage: Passage) {
+        let (first, second) = passage;
+        if second == first + 1 {
+            self.east_walls[first] = false;
+        } else {
+            self.south_walls[first] = false;
+        }
+        self.carved[first] = true;
+        self.carved[second] = true;
+    }
+
+    fn has_passage(&self, cell1: usize, cell2: usize) -> bool {
+        let (first, second) = make_passage(cell1, cell2);
+        if second == first + 1 {
+            !self.east_walls[first]
+        } else {
+            !self.south_walls[first]
+        }
+    }
+
+    /// The neighbouring cells which can be walked to from the cell
+    fn open_neighbours(&self, cell: usize) -> Vec<usize> {
+        self.neighbours(cell)
+            .into_iter()
+            .filter(|&neighbour| self.has_passage(cell, neighbour))
+            .collect()
+    }
+
+    fn neighbours(&self, cell: usize) -> Vec<usize> {
+        let (x, y) = (cell % self.columns, cell / self.columns);
+        let mut neighbours = Vec::with_capacity(4);
+        if x > 0 {
+            neighbours.push(cell - 1);
+        }
+        if x + 1 < self.columns {
+            neighbours.push(cell + 1);
+        }
+        if y > 0 {
+            neighbours.push(cell - self.columns);
+        }
+        if y + 1 < self.rows {
+            neighbours.push(cell + self.columns);
+        }
+        neighbours
+    }
+
+    /// All the passages which could be carved, i.e. every internal wall
+    fn all_walls(&self) -> Vec<Passage> {
+        (0..self.columns * self.rows)
+            .flat_map(|cell| {
+                self.neighbours(cell)
+                    .into_iter()
+                    .filter(move |&neighbour| neighbour > cell)
+                    .map(move |neighbour| (cell, neighbour))
+            })
+            .collect()
+    }
+}
+
+fn make_passage(cell1: usize, cell2: usize) -> Passage {
+    (cell1.min(cell2), cell1.max(cell2))
+}
+
+pub fn generate_maze_image(options: MazeOptions) -> DynamicImage {
+    let (maze, _) = generate_maze(&options);
+
+    DynamicImage::ImageRgba8(draw_maze(&maze, &options))
+}
+
+/// Generates frames of the maze being carved out and/or solved
+pub fn generate_maze_images(
+    options: MazeOptions,
+    animate_carving: bool,
+    solve_options: Option<MazeSolveOptions>,
+    frame_num: u32,
+) -> Vec<RgbaImage> {
+    let (maze, passages) = generate_maze(&options);
+
+    let mut images = Vec::new();
+    if animate_carving {
+        let mut carving_maze = Maze::new(options.columns as usize, options.rows as usize);
+        let passages_per_frame = get_steps_per_frame(passages.len(), frame_num);
+        images.push(draw_maze(&carving_maze, &options));
+        for passage_chunk in passages.chunks(passages_per_frame) {
+            if is_cancelled() {
+                break;
+            }
+            for &passage in passage_chunk {
+                carving_maze.carve(passage);
+            }
+            images.push(draw_maze(&carving_maze, &options));
+        }
+    }
+
+    if let Some(solve_options) = solve_options {
+        images.extend(generate_solving_images(
+            &maze,
+            &options,
+            &solve_options,
+            frame_num,
+        ));
+    } else if images.is_empty() {
+        images.push(draw_maze(&maze, &options));
+    }
+
+    images
+}
+
+fn get_steps_per_frame(step_num: usize, frame_num: u32) -> usize {
+    step_num.div_ceil(frame_num.max(1) as usize).max(1)
+}
+
+fn generate_maze(options: &MazeOptions) -> (Maze, Vec<Passage>) {
+    let mut rng = match options.seed {
+        Some(seed) => fastrand::Rng::with_seed(seed),
+        None => fastrand::Rng::new(),
+    };
+    let mut maze = Maze::new(options.columns as usize, options.rows as usize);
+
+    let passages = match options.algorithm {
+        MazeAlgorithm::RecursiveBacktracker => recursive_backtracker(&maze, &mut rng),
+        MazeAlgorithm::Prims => prims(&maze, &mut rng),
+        MazeAlgorithm::Kruskals => kruskals(&maze, &mut rng),
+    };
+    for &passage in passages.iter() {
+        maze.carve(passage);
+    }
+
+    (maze, passages)
+}
+
+/// Randomised depth first search, wanders as far as it can and backs up when stuck
+/// which gives long winding corridors
+fn recursive_backtracker(maze: &Maze, rng: &mut fastrand::Rng) -> Vec<Passage> {
+    let cell_num = maze.columns * maze.rows;
+    let mut visited = vec![false; cell_num];
+    let mut passages = Vec::with_capacity(cell_num - 1);
+
+    // done with an explicit stack rather than recursion so big mazes don't overflow
+    let start = rng.usize(0..cell_num);
+    visited[start] = true;
+    let mut stack = vec![start];
+    while let Some(&cell) = stack.last() {
+        let unvisited: Vec<usize> = maze
+            .neighbours(cell)
+            .into_iter()
+            .filter(|&neighbour| !visited[neighbour])
+            .collect();
+        if unvisited.is_empty() {
+            stack.pop();
+            continue;
+        }
+
+        let next = unvisited[rng.usize(0..unvisited.len())];
+        visited[next] = true;
+        passages.push(make_passage(cell, next));
+        stack.push(next);
+    }
+
+    passages
+}
+
+/// Randomised Prim's algorithm, grows the maze outwards from a single cell by picking
+/// random walls on its frontier, which gives lots of short dead ends
+fn prims(maze: &Maze, rng: &mut fastrand::Rng) -> Vec<Passage> {
+    let cell_num = maze.columns * maze.rows;
+    let mut in_maze = vec![false; cell_num];
+    let mut passages = Vec::with_capacity(cell_num - 1);
+
+    let start = rng.usize(0..cell_num);
+    in_maze[start] = true;
+    // pairs of (cell in the maze, cell which might not be)
+    let mut frontier: Vec<(usize, usize)> = maze
+        .neighbours(start)
+        .into_iter()
+        .map(|neighbour| (start, neighbour))
+        .collect();
+    while !frontier.is_empty() {
+        let (cell, next) = frontier.swap_remove(rng.usize(0..frontier.len()));
+        if in_maze[next] {
+            continue;
+        }
+
+        in_maze[next] = true;
+        passages.push(make_passage(cell, next));
+        frontier.extend(
+            maze.neighbours(next)
+                .into_iter()
+                .filter(|&neighbour| !in_maze[neighbour])
+                .map(|neighbour| (next, neighbour)),
+        );
+    }
+
+    passages
+}
+
+/// Randomised Kruskal's algorithm, knocks down random walls as long as they join two
+/// areas which aren't connected yet
+fn kruskals(maze: &Maze, rng: &mut fastrand::Rng) -> Vec<Passage> {
+    let cell_num = maze.columns * maze.rows;
+    let mut sets = DisjointSet::new(cell_num);
+    let mut passages = Vec::with_capacity(cell_num - 1);
+
+    let mut walls = maze.all_walls();
+    rng.shuffle(&mut walls);
+    for (cell1, cell2) in walls {
+        if sets.union(cell1, cell2) {
+            passages.push((cell1, cell2));
+        }
+    }
+
+    passages
+}
+
+/// Union find structure to track which cells are connected
+/// <https://en.wikipedia.org/wiki/Disjoint-set_data_structure>
+struct DisjointSet {
+    parents: Vec<usize>,
+}
+
+impl DisjointSet {
+    fn new(size: usize) -> Self {
+        Self {
+            parents: (0..size).collect(),
+        }
+    }
+
+    fn find(&mut self, item: usize) -> usize {
+        let mut root = item;
+        while self.parents[root] != root {
+            root = self.parents[root];
+        }
+        // point everything on the way straight to the root to keep lookups fast
+        let mut current = item;
+        while self.parents[current] != root {
+            let next = self.parents[current];
+            self.parents[current] = root;
+            current = next;
+        }
+        root
+    }
+
+    /// Joins the sets of the two items, returns false if they were already joined
+    fn union(&mut self, item1: usize, item2: usize) -> bool {
+        let root1 = self.find(item1);
+        let root2 = self.find(item2);
+        if root1 == root2 {
+            return false;
+        }
+        self.parents[root1] = root2;
+        true
+    }
+}
+
+/// A single step of a search, the cell which was visited and the cells which were
+/// added to the frontier from it
+struct SearchStep {
+    visited: usize,
+    added_to_frontier: Vec<usize>,
+}
+
+struct SearchResult {
+    steps: Vec<SearchStep>,
+    solution: Vec<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum CellState {
+    Unseen,
+    Frontier,
+    Visited,
+}
+
+fn generate_solving_images(
+    maze: &Maze,
+    options: &MazeOptions,
+    solve_options: &MazeSolveOptions,
+    frame_num: u32,
+) -> Vec<RgbaImage> {
+    let start = 0;
+    let goal = maze.columns * maze.rows - 1;
+    let result = match solve_options.solver {
+        MazeSolver::Bfs => breadth_first_search(maze, start, goal),
+        MazeSolver::Dfs => depth_first_search(maze, start, goal),
+        MazeSolver::Astar => a_star_search(maze, start, goal),
+    };
+
+    let base_image = draw_maze(maze, options);
+    let visited_color = Rgba(solve_options.visited_color.to_rgba8());
+    let frontier_color = Rgba(solve_options.frontier_color.to_rgba8());
+    let solution_color = Rgba(solve_options.solution_color.to_rgba8());
+
+    let mut images = Vec::new();
+    let mut states = vec![CellState::Unseen; maze.columns * maze.rows];
+    let steps_per_frame = get_steps_per_frame(result.steps.len(), frame_num);
+    for step_chunk in result.steps.chunks(steps_per_frame) {
+        if is_cancelled() {
+            break;
+        }
+        for step in step_chunk {
+            states[step.visited] = CellState::Visited;
+            for &cell in step.added_to_frontier.iter() {
+                states[cell] = CellState::Frontier;
+            }
+        }
+
+        let mut image = base_image.clone();
+        for (cell, state) in states.iter().enumerate() {
+            match state {
+                CellState::Unseen => {}
+                CellState::Frontier => fill_cell(&mut image, maze, options, cell, frontier_color),
+                CellState::Visited => {
+                    fill_cell(&mut image, maze, options, cell, visited_color);
+                    // join up visited cells so the explored area looks like corridors
+                    for neighbour in maze.open_neighbours(cell) {
+                        if neighbour > cell && states[neighbour] == CellState::Visited {
+                            fill_passage(&mut image, maze, options, cell, neighbour, visited_color);
+                        }
+                    }
+                }
+            }
+        }
+        images.push(image);
+    }
+
+    let mut solution_image = images.last().cloned().unwrap_or(base_image);
+    for (index, &cell) in result.solution.iter().enumerate() {
+        fill_cell(&mut solution_image, maze, options, cell, solution_color);
+        if let Some(&next) = result.solution.get(index + 1) {
+            fill_passage(
+                &mut solution_image,
+                maze,
+                options,
+                cell,
+                next,
+                solution_color,
+            );
+        }
+    }
+    images.extend(std::iter::repeat_n(solution_image, SOLUTION_HOLD_FRAMES));
+
+    images
+}
+
+/// Walks back through the parents from the goal to get the path from the start
+fn reconstruct_path(parents: &[Option<usize>], start: usize, goal: usize) -> Vec<usize> {
+    let mut path = vec![goal];
+    let mut current = goal;
+    while current != start {
+        match parents[current] {
+            Some(parent) => {
+                path.push(parent);
+                current = parent;
+            }
+            None => return Vec::new(),
+        }
+    }
+    path.reverse();
+    path
+}
+
+/// Explores outwards evenly from the start, so finds the shortest path
+fn breadth_first_search(maze: &Maze, start: usize, goal: usize) -> SearchResult {
+    let mut parents = vec![None; maze.columns * maze.rows];
+    let mut seen = vec![false; maze.columns * maze.rows];
+    let mut steps = Vec::new();
+
+    let mut queue = VecDeque::from([start]);
+    seen[start] = true;
+    while let Some(cell) = queue.pop_front() {
+        let mut step = SearchStep {
+            visited: cell,
+            added_to_frontier: Vec::new(),
+        };
+        if cell == goal {
+            steps.push(step);
+            break;
+        }
+        for neighbour in maze.open_neighbours(cell) {
+            if !seen[neighbour] {
+                seen[neighbour] = true;
+                parents[neighbour] = Some(cell);
+                step.added_to_frontier.push(neighbour);
+                queue.push_back(neighbour);
+            }
+        }
+        steps.push(step);
+    }
+
+    SearchResult {
+        steps,
+        solution: reconstruct_path(&parents, start, goal),
+    }
+}
+
+/// Follows a single path as far as it can before backing up
+fn depth_first_search(maze: &Maze, start: usize, goal: usize) -> SearchResult {
+    let mut parents = vec![None; maze.columns * maze.rows];
+    let mut seen = vec![false; maze.columns * maze.rows];
+    let mut steps = Vec::new();
+
+    let mut stack = vec![start];
+    seen[start] = true;
+    while let Some(cell) = stack.pop() {
+        let mut step = SearchStep {
+            visited: cell,
+            added_to_frontier: Vec::new(),
+        };
+        if cell == goal {
+            steps.push(step);
+            break;
+        }
+        for neighbour in maze.open_neighbours(cell) {
+            if !seen[neighbour] {
+                seen[neighbour] = true;
+                parents[neighbour] = Some(cell);
+                step.added_to_frontier.push(neighbour);
+                stack.push(neighbour);
+            }
+        }
+        steps.push(step);
+    }
+
+    SearchResult {
+        steps,
+        solution: reconstruct_path(&parents, start, goal),
+    }
+}
+
+/// Always expands the cell with the lowest distance so far plus the manhattan distance
+/// to the goal, which heads towards the goal rather than exploring evenly
+/// <https://en.wikipedia.org/wiki/A*_search_algorithm>
+fn a_star_search(maze: &Maze, start: usize, goal: usize) -> SearchResult {
+    let cell_num = maze.columns * maze.rows;
+    let mut parents = vec![None; cell_num];
+    let mut distances = vec![usize::MAX; cell_num];
+    let mut visited = vec![false; cell_num];
+    let mut steps = Vec::new();
+
+    let heuristic = |cell: usize| {
+        (cell % maze.columns).abs_diff(goal % maze.columns)
+            + (cell / maze.columns).abs_diff(goal / maze.columns)
+    };
+
+    let mut open = BinaryHeap::new();
+    distances[start] = 0;
+    open.push(Reverse((heuristic(start), 0, start)));
+    while let Some(Reverse((_, distance, cell))) = open.pop() {
+        if visited[cell] {
+            continue;
+        }
+        visited[cell] = true;
+
+        let mut step = SearchStep {
+            visited: cell,
+            added_to_frontier: Vec::new(),
+        };
+        if cell == goal {
+            steps.push(step);
+            break;
+        }
+        for neighbour in maze.open_neighbours(cell) {
+            let new_distance = distance + 1;
+            if !visited[neighbour] && new_distance < distances[neighbour] {
+                distances[neighbour] = new_distance;
+                parents[neighbour] = Some(cell);
+                step.added_to_frontier.push(neighbour);
+                open.push(Reverse((
+                    new_distance + heuristic(neighbour),
+                    new_distance,
+                    neighbour,
+                )));
+            }
+        }
+        steps.push(step);
+    }
+
+    SearchResult {
+        steps,
+        solution: reconstruct_path(&parents, start, goal),
+    }
+}
+
+fn get_cell_position(maze: &Maze, options: &MazeOptions, cell: usize) -> (i32, i32) {
+    (
+        ((cell % maze.columns) as u32 * options.cell_size + options.wall_thickness) as i32,
+        ((cell / maze.columns) as u32 * options.cell_size + options.wall_thickness) as i32,
+    )
+}
+
+fn get_inner_size(options: &MazeOptions) -> u32 {
+    options
+        .cell_size
+        .saturating_sub(options.wall_thickness)
+        .max(1)
+}
+
+fn fill_cell(
+    image: &mut RgbaImage,
+    maze: &Maze,
+    options: &MazeOptions,
+    cell: usize,
+    color: Rgba<u8>,
+) {
+    let (x, y) = get_cell_position(maze, options, cell);
+    let inner_size = get_inner_size(options);
+    draw_filled_rect_mut(image, Rect::at(x, y).of_size(inner_size, inner_size), color);
+}
+
+/// Fills in the gap in the wall between two neighbouring cells
+fn fill_passage(
+    image: &mut RgbaImage,
+    maze: &Maze,
+    options: &MazeOptions,
+    cell1: usize,
+    cell2: usize,
+    color: Rgba<u8>,
+) {
+    let (first, second) = make_passage(cell1, cell2);
+    let (x, y) = get_cell_position(maze, options, first);
+    let inner_size = get_inner_size(options);
+    let gap = options.cell_size - inner_size;
+    let rect = if second == first + 1 {
+        Rect::at(x + inner_size as i32, y).of_size(gap, inner_size)
+    } else {
+        Rect::at(x, y + inner_size as i32).of_size(inner_size, gap)
+    };
+    draw_filled_rect_mut(image, rect, color);
+}
+
+fn draw_maze(maze: &Maze, options: &MazeOptions) -> RgbaImage {
+    let (width, height) = options.get_image_size();
+    let wall_color = Rgba(options.wall_color.to_rgba8());
+    let path_color = Rgba(options.path_color.to_rgba8());
+    let mut image = RgbaImage::from_pixel(width, height, wall_color);
+
+    for cell in 0..maze.columns * maze.rows {
+        if !maze.carved[cell] {
+            continue;
+        }
+
+        fill_cell(&mut image, maze, options, cell, path_color);
+        // fill in the wall gap to the neighbouring cell if there's a passage
+        let x = cell % maze.columns;
+        if x + 1 < maze.columns && !maze.east_walls[cell] {
+            fill_passage(&mut image, maze, options, cell, cell + 1, path_color);
+        }
+        if cell + maze.columns < maze.columns * maze.rows && !maze.south_walls[cell] {
+            fill_passage(
+                &mut image,
+                maze,
+                options,
+                cell,
+                cell + maze.columns,
+                path_color,
+            );
+        }
+    }
+
+    image
+}
+
+#[derive(clap::Args, Debug)]
+pub struct MazeArgs {
+    /// The number of cells across
+    #[arg(long, default_value = "30", value_parser = parse_size)]
+    columns: u32,
+
+    /// The number of cells down
+    #[arg(long, default_value = "30", value_parser = parse_size)]
+    rows: u32,
+
+    /// The size of each cell in pixels, including one wall
+    #[arg(long, default_value = "20", value_parser = parse_size)]
+    cell_size: u32,
+
+    #[arg(long, default_value = "4")]
+    wall_thickness: u32,
+
+    #[arg(short, long, default_value = "recursive-backtracker")]
+    algorithm: MazeAlgorithm,
+
+    /// The seed used to carve the maze, random if not given
+    #[arg(long)]
+    seed: Option<u64>,
+
+    #[arg(short, long, default_value = "black", value_parser = parse_color)]
+    wall_color: Color,
+
+    #[arg(short, long, default_value = "white", value_parser = parse_color)]
+    path_color: Color,
+
+    /// Animate the maze being carved out
+    #[arg(long, default_value = "false")]
+    animate: bool,
+
+    /// The number of frames when animating, used for both carving and solving
+    #[arg(short, long, default_value = "100", value_parser = parse_size)]
+    frames: u32,
+
+    /// Animate solving the maze from the top left to the bottom right
+    #[arg(long)]
+    solve: Option<MazeSolver>,
+
+    /// The colour of cells the solver has visited
+    #[arg(long, default_value = "lightblue", value_parser = parse_color)]
+    visited_color: Color,
+
+    /// The colour of cells the solver is about to visit
+    #[arg(long, default_value = "orange", value_parser = parse_color)]
+    frontier_color: Color,
+
+    /// The colour of the final path
+    #[arg(long, default_value = "red", value_parser = parse_color)]
+    solution_color: Color,
+}
+
+pub struct MazeGenerator;
+
+impl Generator for MazeGenerator {
+    type Args = MazeArgs;
+
+    fn name(&self) -> &'static str {
+        "maze"
+    }
+
+    fn is_animated(&self, args: &MazeArgs) -> bool {
+        args.animate || args.solve.is_some()
+    }
+
+    fn estimate_memory(&self, args: &MazeArgs) -> Option<MemoryEstimate> {
+        let get_length = |cell_num: u32| {
+            cell_num
+                .saturating_mul(args.cell_size)
+                .saturating_add(args.wall_thickness)
+        };
+        let frame_num = match self.is_animated(args) {
+            true => args.frames,
+            false => 1,
+        };
+        // the walls and what's been visited for every cell
+        let cell_num = args.columns as u64 * args.rows as u64;
+        Some(
+            MemoryEstimate::frames(get_length(args.columns), get_length(args.rows), frame_num)
+                .with_working(cell_num * 24),
+        )
+    }
+
+    fn generate(&self, args: MazeArgs) -> Result<Generated, GeneratorError> {
+        let is_animated = self.is_animated(&args);
+        let options = MazeOptions::new(
+            args.columns,
+            args.rows,
+            args.cell_size,
+            args.wall_thickness,
+            args.algorithm,
+            args.seed,
+            args.wall_color,
+            args.path_color,
+        );
+        options.check().map_err(GeneratorError::InvalidOptions)?;
+
+        Ok(match is_animated {
+            true => {
+                let solve_options = args.solve.map(|solver| {
+                    MazeSolveOptions::new(
+                        solver,
+                        args.visited_color,
+                        args.frontier_color,
+                        args.solution_color,
+                    )
+                });
+                Generated::Frames(generate_maze_images(
+                    options,
+                    args.animate,
+                    solve_options,
+                    args.frames,
+                ))
+            }
+            false => Generated::Still(generate_maze_image(options).into_rgba8()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count_reachable(maze: &Maze) -> usize {
+        let mut visited = vec![false; maze.columns * maze.rows];
+        let mut stack = vec![0];
+        visited[0] = true;
+        let mut count = 0;
+        while let Some(cell) = stack.pop() {
+            count += 1;
+            for neighbour in maze.open_neighbours(cell) {
+                if !visited[neighbour] {
+                    visited[neighbour] = true;
+                    stack.push(neighbour);
+                }
+            }
+        }
+        count
+    }
+
+    #[test]
+    fn test_perfect_mazes() {
+        for algorithm in [
+            MazeAlgorithm::RecursiveBacktracker,
+            MazeAlgorithm::Prims,
+            MazeAlgorithm::Kruskals,
+        ] {
+            let options = MazeOptions::new(
+                12,
+                7,
+                10,
+                2,
+                algorithm,
+                Some(42),
+                Color::new(0.0, 0.0, 0.0, 1.0),
+                Color::new(1.0, 1.0, 1.0, 1.0),
+            );
+            let (maze, passages) = generate_maze(&options);
+
+            // a perfect maze is a spanning tree so it has one less passage than cells
+            assert_eq!(passages.len(), 12 * 7 - 1);
+            assert_eq!(count_reachable(&maze), 12 * 7);
+        }
+    }
+
+    #[test]
+    fn test_solvers_find_same_path() {
+        let options = MazeOptions::new(
+            15,
+            10,
+            10,
+            2,
+            MazeAlgorithm::Kruskals,
+            Some(7),
+            Color::new(0.0, 0.0, 0.0, 1.0),
+            Color::new(1.0, 1.0, 1.0, 1.0),
+        );
+        let (maze, _) = generate_maze(&options);
+        let goal = 15 * 10 - 1;
+
+        // there's only one path in a perfect maze so every search should find it
+        let bfs = breadth_first_search(&maze, 0, goal).solution;
+        let dfs = depth_first_search(&maze, 0, goal).solution;
+        let a_star = a_star_search(&maze, 0, goal).solution;
+        assert_eq!(bfs.first(), Some(&0));
+        assert_eq!(bfs.last(), Some(&goal));
+        assert!(
+            bfs.windows(2)
+                .all(|cells| maze.has_passage(cells[0], cells[1]))
+        );
+        assert_eq!(bfs, dfs);
+        assert_eq!(bfs, a_star);
+    }
+
+    #[test]
+    fn test_check() {
+        let options = |cell_size, wall_thickness, columns| {
+            MazeOptions::new(
+                columns,
+                10,
+                cell_size,
+                wall_thickness,
+                MazeAlgorithm::RecursiveBacktracker,
+                None,
+                Color::new(0.0, 0.0, 0.0, 1.0),
+                Color::new(1.0, 1.0, 1.0, 1.0),
+            )
+        };
+        assert!(options(10, 2, 10).check().is_ok());
+        assert!(options(3, 2, 10).check().is_ok());
+        // the paths would have no room, which used to panic drawing them
+        assert!(options(2, 2, 10).check().is_err());
+        assert!(options(10, 0, 10).check().is_err());
+        assert!(options(100_000, 2, 100_000).check().is_err());
+    }
+}
@@ -0,0 +1,123 @@
+//! Rough estimates of how much memory an image takes to make
+//!
+//! Big images and long animations are held in memory whole, so a huge size can run out
+//! of it part way through. Estimating from the options first means that can be caught
+//! before anything is allocated. The estimates only count the big buffers, the image or
+//! frames that come out and anything sized by them on the way
+
+/// Every pixel is RGBA with a byte for each
+pub const BYTES_PER_PIXEL: u64 = 4;
+
+/// How much memory making an image takes, split into what comes out and what's used on
+/// the way
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MemoryEstimate {
+    /// The bytes of the image or all the frames
+    pub output: u64,
+    /// The bytes of the other buffers used while making it, like grids of values
+    pub working: u64,
+}
+
+impl MemoryEstimate {
+    pub fn new(output: u64, working: u64) -> Self {
+        Self { output, working }
+    }
+
+    /// A still image of the size, with nothing else big
+    pub fn still(width: u32, height: u32) -> Self {
+        Self::frames(width, height, 1)
+    }
+
+    /// An animation with all of its frames held at once, which is how they're saved
+    pub fn frames(width: u32, height: u32, frame_num: u32) -> Self {
+        Self::new(get_image_bytes(width, height) * frame_num as u64, 0)
+    }
+
+    /// Adds a buffer of so many bytes for each pixel of an image of the size
+    pub fn with_pixel_buffer(self, width: u32, height: u32, bytes_per_pixel: u64) -> Self {
+        self.with_working(width as u64 * height as u64 * bytes_per_pixel)
+    }
+
+    pub fn with_working(self, bytes: u64) -> Self {
+        Self::new(self.output, self.working.saturating_add(bytes))
+    }
+
+    pub fn total(&self) -> u64 {
+        self.output.saturating_add(self.working)
+    }
+}
+
+pub fn get_image_bytes(width: u32, height: u32) -> u64 {
+    width as u64 * height as u64 * BYTES_PER_PIXEL
+}
+
+const UNITS: [(&str, u64); 4] = [
+    ("TB", 1 << 40),
+    ("GB", 1 << 30),
+    ("MB", 1 << 20),
+    ("KB", 1 << 10),
+];
+
+/// Writes the bytes in the biggest unit they make at least 1 of, like 1.5GB
+pub fn format_bytes(bytes: u64) -> String {
+    match UNITS.iter().find(|(_, size)| bytes >= *size) {
+        Some((unit, size)) => {
+            let amount = bytes as f64 / *size as f64;
+            let amount = match amount < 10.0 {
+                true => format!("{:.1}", amount),
+                false => format!("{:.0}", amount),
+            };
+            format!("{}{}", amount.trim_end_matches(".0"), unit)
+        }
+        None => format!("{}B", bytes),
+    }
+}
+
+/// Parses an amount of memory like 4GB, 512MB or 2g, with plain numbers being bytes
+pub fn parse_memory(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| c.is_ascii_alphabetic())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number
+        .trim()
+        .parse()
+        .map_err(|_| "expected an amount of memory like 4GB or 512MB".to_string())?;
+    let unit = unit.trim().to_ascii_uppercase();
+    let size = match unit.as_str() {
+        "" | "B" => 1,
+        _ => UNITS
+            .iter()
+            .find(|(name, _)| *name == unit || name[..1] == unit)
+            .map(|(_, size)| *size)
+            .ok_or_else(|| format!("{} isn't a unit of memory, use KB, MB, GB or TB", unit))?,
+    };
+    match number > 0.0 {
+        true => Ok((number * size as f64) as u64),
+        false => Err("must be more than 0".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory() {
+        assert_eq!(parse_memory("4GB"), Ok(4 << 30));
+        assert_eq!(parse_memory("512mb"), Ok(512 << 20));
+        assert_eq!(parse_memory("1.5g"), Ok(3 << 29));
+        assert_eq!(parse_memory("1000"), Ok(1000));
+        assert!(parse_memory("4PB").is_err());
+        assert!(parse_memory("0GB").is_err());
+
+        assert_eq!(format_bytes(3 << 29), "1.5GB");
+        assert_eq!(format_bytes(300 << 20), "300MB");
+        assert_eq!(format_bytes(4 << 30), "4GB");
+        assert_eq!(format_bytes(12), "12B");
+
+        let estimate = MemoryEstimate::frames(100, 50, 3).with_pixel_buffer(100, 50, 8);
+        assert_eq!(estimate.total(), 100 * 50 * 4 * 3 + 100 * 50 * 8);
+    }
+}
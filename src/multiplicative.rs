@@ -0,0 +1,139 @@
+//! Module for working out arithmetic functions of every number up to a limit at once
+//!
+//! A sieve finds the smallest prime factor of every number, so each number splits into
+//! the power of that prime and a smaller number that's already been worked out. The
+//! totient, divisor sum and Möbius function are multiplicative, so their value is the
+//! value for the prime power times the value for the rest, while the number of distinct
+//! primes just goes up by one.
+//! See <https://en.wikipedia.org/wiki/Multiplicative_function> for more info
+
+use crate::ArithmeticFunction;
+
+/// Gets the smallest prime factor of every number below the limit, with 0 for 0 and 1
+pub fn get_smallest_prime_factors(limit: usize) -> Vec<u32> {
+    let mut factors = vec![0; limit];
+    for number in 2..limit {
+        if factors[number] != 0 {
+            continue;
+        }
+        // the multiples below its square have a smaller factor already
+        for multiple in (number.saturating_mul(number)..limit).step_by(number) {
+            if factors[multiple] == 0 {
+                factors[multiple] = number as u32;
+            }
+        }
+        factors[number] = number as u32;
+    }
+
+    factors
+}
+
+/// Gets the function of a prime raised to a power
+fn get_prime_power_value(function: ArithmeticFunction, prime: i64, power: u32) -> i64 {
+    match function {
+        ArithmeticFunction::Totient => prime.pow(power) - prime.pow(power - 1),
+        ArithmeticFunction::Sigma => (prime.pow(power + 1) - 1) / (prime - 1),
+        ArithmeticFunction::Omega => 1,
+        ArithmeticFunction::Mobius => match power {
+            1 => -1,
+            _ => 0,
+        },
+    }
+}
+
+/// Gets the function of every number below the limit, with 0 for 0
+pub fn get_function_values(function: ArithmeticFunction, limit: usize) -> Vec<i64> {
+    let factors = get_smallest_prime_factors(limit);
+    let mut values = vec![0; limit];
+    if limit > 1 {
+        values[1] = match function {
+            ArithmeticFunction::Omega => 0,
+            _ => 1,
+        };
+    }
+
+    for number in 2..limit {
+        let prime = factors[number] as usize;
+        let (mut rest, mut power) = (number, 0);
+        while rest.is_multiple_of(prime) {
+            rest /= prime;
+            power += 1;
+        }
+        let prime_power_value = get_prime_power_value(function, prime as i64, power);
+        values[number] = match function {
+            ArithmeticFunction::Omega => values[rest] + prime_power_value,
+            _ => values[rest] * prime_power_value,
+        };
+    }
+
+    values
+}
+
+/// Gets the sum of the divisors of a single number, σ(n), by trial division, for when
+/// it's too big to sieve up to
+pub fn get_divisor_sum(number: u64) -> u64 {
+    let (mut rest, mut sum) = (number, 1);
+    let mut prime = 2;
+    while prime * prime <= rest {
+        if rest.is_multiple_of(prime) {
+            // 1 + p + p² + ... for the power of the prime that divides it
+            let (mut power, mut power_sum) = (1, 1);
+            while rest.is_multiple_of(prime) {
+                rest /= prime;
+                power *= prime;
+                power_sum += power;
+            }
+            sum *= power_sum;
+        }
+        prime += if prime == 2 { 1 } else { 2 };
+    }
+    // whatever's left over is a prime that only divides it once
+    if rest > 1 {
+        sum *= rest + 1;
+    }
+
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_function_values() {
+        assert_eq!(
+            get_smallest_prime_factors(10),
+            [0, 0, 2, 3, 2, 5, 2, 7, 2, 3]
+        );
+
+        // checked against counting straight from the definitions
+        let limit = 200;
+        let totients = get_function_values(ArithmeticFunction::Totient, limit);
+        let sigmas = get_function_values(ArithmeticFunction::Sigma, limit);
+        let omegas = get_function_values(ArithmeticFunction::Omega, limit);
+        let mobius = get_function_values(ArithmeticFunction::Mobius, limit);
+        for number in 1..limit as i64 {
+            let divisors: Vec<i64> = (1..=number).filter(|d| number % d == 0).collect();
+            let primes: Vec<i64> = divisors
+                .iter()
+                .copied()
+                .filter(|&d| primal::is_prime(d as u64))
+                .collect();
+            let has_square = primes.iter().any(|p| number % (p * p) == 0);
+            let totient = (1..=number)
+                .filter(|&other| (1..=other).all(|d| other % d != 0 || number % d != 0 || d == 1))
+                .count() as i64;
+
+            let index = number as usize;
+            assert_eq!(totients[index], totient, "φ({})", number);
+            assert_eq!(sigmas[index], divisors.iter().sum::<i64>(), "σ({})", number);
+            assert_eq!(get_divisor_sum(number as u64) as i64, sigmas[index]);
+            assert_eq!(omegas[index], primes.len() as i64, "ω({})", number);
+            let expected_mobius = match has_square {
+                true => 0,
+                false => (-1i64).pow(primes.len() as u32),
+            };
+            assert_eq!(mobius[index], expected_mobius, "μ({})", number);
+        }
+    }
+}
@@ -0,0 +1,444 @@
+//! Writing images to the output file, with the format picked from its extension, and
+//! reading directories of frames back in
+
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::Path,
+    time::Duration,
+};
+
+use fun_images::tiles::Tile;
+use image::{
+    Delay, DynamicImage, Frame, ImageFormat, RgbaImage,
+    codecs::gif::{GifEncoder, Repeat},
+};
+use rayon::prelude::*;
+
+use crate::{error::Error, recipe::RECIPE_KEYWORD};
+
+/// How many frames show each second unless the animation says otherwise
+const DEFAULT_FPS: u32 = 30;
+
+/// How fast an animation plays and how many times it goes round
+#[derive(Clone, Copy, Debug)]
+pub struct Playback {
+    fps: u32,
+    /// How many times it plays through, None to loop forever
+    loop_num: Option<u32>,
+}
+
+impl Playback {
+    pub fn new(fps: u32, loop_num: Option<u32>) -> Self {
+        Self { fps, loop_num }
+    }
+}
+
+impl Default for Playback {
+    fn default() -> Self {
+        Self::new(DEFAULT_FPS, None)
+    }
+}
+
+/// The formats images can be saved as
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FileFormat {
+    Png,
+    /// An animated PNG, which is a normal PNG for still images
+    Apng,
+    Gif,
+    Webp,
+    Bmp,
+    Tiff,
+}
+
+impl FileFormat {
+    pub fn from_path(path: &str) -> Result<Self, Error> {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_ascii_lowercase());
+
+        match extension.as_deref() {
+            Some("png") => Ok(FileFormat::Png),
+            Some("apng") => Ok(FileFormat::Apng),
+            Some("gif") => Ok(FileFormat::Gif),
+            Some("webp") => Ok(FileFormat::Webp),
+            Some("bmp") => Ok(FileFormat::Bmp),
+            Some("tif") | Some("tiff") => Ok(FileFormat::Tiff),
+            _ => Err(Error::UnknownFormat {
+                path: path.to_string(),
+            }),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            FileFormat::Png => "png",
+            FileFormat::Apng => "apng",
+            FileFormat::Gif => "gif",
+            FileFormat::Webp => "webp",
+            FileFormat::Bmp => "bmp",
+            FileFormat::Tiff => "tiff",
+        }
+    }
+}
+
+/// Works out the format for the output, checking it can hold the image and won't
+/// overwrite anything unless forced
+pub fn get_output_format(path: &str, animated: bool, force: bool) -> Result<FileFormat, Error> {
+    let format = FileFormat::from_path(path)?;
+    if animated && !matches!(format, FileFormat::Png | FileFormat::Apng | FileFormat::Gif) {
+        return Err(Error::NotAnimatable {
+            path: path.to_string(),
+            format: format.name(),
+        });
+    }
+    if !force && Path::new(path).exists() {
+        return Err(Error::OutputExists {
+            path: path.to_string(),
+        });
+    }
+
+    Ok(format)
+}
+
+/// Saves a still image, with the recipe stored in it for PNGs
+pub fn save_image(
+    image: &DynamicImage,
+    path: &str,
+    format: FileFormat,
+    recipe: &[String],
+) -> Result<(), Error> {
+    let image_format = match format {
+        FileFormat::Png | FileFormat::Apng => {
+            let file = create_file(path)?;
+            return write_png(BufWriter::new(file), &image.to_rgba8(), recipe).map_err(
+                |encoding_error| Error::WritePng {
+                    path: path.to_string(),
+                    source: encoding_error,
+                },
+            );
+        }
+        FileFormat::Gif => ImageFormat::Gif,
+        FileFormat::Webp => ImageFormat::WebP,
+        FileFormat::Bmp => ImageFormat::Bmp,
+        FileFormat::Tiff => ImageFormat::Tiff,
+    };
+
+    image
+        .save_with_format(path, image_format)
+        .map_err(|image_error| Error::SaveImage {
+            path: path.to_string(),
+            source: image_error,
+        })
+}
+
+/// Saves an animation, with the recipe stored in it for animated PNGs
+pub fn save_animation(
+    images: &[RgbaImage],
+    path: &str,
+    format: FileFormat,
+    recipe: &[String],
+    playback: Playback,
+) -> Result<(), Error> {
+    let writer = BufWriter::new(create_file(path)?);
+
+    match format {
+        FileFormat::Gif => {
+            write_animated_gif(writer, images, playback).map_err(|image_error| Error::SaveImage {
+                path: path.to_string(),
+                source: image_error,
+            })
+        }
+        _ => write_animated_png(writer, images, recipe, playback).map_err(|encoding_error| {
+            Error::WritePng {
+                path: path.to_string(),
+                source: encoding_error,
+            }
+        }),
+    }
+}
+
+/// Gets the path for a frame in the frames directory, numbered from 1 with enough
+/// digits that the files sort in order
+pub fn get_frame_path(directory: &str, index: usize, frame_num: usize) -> String {
+    let digit_num = frame_num.to_string().len().max(4);
+    Path::new(directory)
+        .join(format!(
+            "frame_{:0width$}.png",
+            index + 1,
+            width = digit_num
+        ))
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Saves each frame of an animation as its own PNG in the directory, making it if it
+/// isn't there, for post processing or encoding with other tools
+pub fn save_frames(
+    images: &[RgbaImage],
+    directory: &str,
+    force: bool,
+    recipe: &[String],
+) -> Result<(), Error> {
+    fs::create_dir_all(directory).map_err(|io_error| Error::CreateFile {
+        path: directory.to_string(),
+        source: io_error,
+    })?;
+    let paths: Vec<String> = (0..images.len())
+        .map(|index| get_frame_path(directory, index, images.len()))
+        .collect();
+    // checked before writing any so a run doesn't leave a mix of old and new frames
+    if !force && let Some(path) = paths.iter().find(|path| Path::new(path).exists()) {
+        return Err(Error::OutputExists { path: path.clone() });
+    }
+
+    images.par_iter().zip(&paths).try_for_each(|(image, path)| {
+        write_png(BufWriter::new(create_file(path)?), image, recipe).map_err(|encoding_error| {
+            Error::WritePng {
+                path: path.clone(),
+                source: encoding_error,
+            }
+        })
+    })
+}
+
+/// Gets the name with every run of digits padded out, so sorting by it puts frame_2
+/// before frame_10
+fn get_frame_sort_key(name: &str) -> String {
+    let mut key = String::new();
+    let mut digits = String::new();
+    for character in name.chars().chain(std::iter::once('\0')) {
+        if character.is_ascii_digit() {
+            digits.push(character);
+            continue;
+        }
+        if !digits.is_empty() {
+            key.push_str(&format!("{:0>20}", digits));
+            digits.clear();
+        }
+        key.push(character);
+    }
+    key
+}
+
+/// Opens every image in the directory as the frames of an animation, in order of their
+/// names with numbers in them compared by value. They all have to be the same size
+pub fn open_frames(directory: &str) -> Result<Vec<RgbaImage>, Error> {
+    let frames_error = |message: String| Error::ReadFrames {
+        path: directory.to_string(),
+        message,
+    };
+    let entries = fs::read_dir(directory).map_err(|io_error| frames_error(io_error.to_string()))?;
+    let mut paths = Vec::new();
+    for entry in entries {
+        let path = entry
+            .map_err(|io_error| frames_error(io_error.to_string()))?
+            .path();
+        // anything that isn't an image like a text file of notes is left out
+        if path.is_file() && ImageFormat::from_path(&path).is_ok() {
+            paths.push(path);
+        }
+    }
+    paths.sort_by_cached_key(|path| get_frame_sort_key(&path.to_string_lossy()));
+    if paths.is_empty() {
+        return Err(frames_error("there aren't any images in it".to_string()));
+    }
+
+    let frames = paths
+        .par_iter()
+        .map(|path| {
+            image::open(path)
+                .map(|image| image.into_rgba8())
+                .map_err(|image_error| Error::OpenImage {
+                    path: path.to_string_lossy().into_owned(),
+                    source: image_error,
+                })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let dimensions = frames[0].dimensions();
+    if let Some(index) = frames
+        .iter()
+        .position(|frame| frame.dimensions() != dimensions)
+    {
+        return Err(frames_error(format!(
+            "{} is {}x{} but the first frame is {}x{}",
+            paths[index].display(),
+            frames[index].width(),
+            frames[index].height(),
+            dimensions.0,
+            dimensions.1
+        )));
+    }
+
+    Ok(frames)
+}
+
+/// Saves a PNG from rows of tiles, getting the image for each row and writing its
+/// lines out before moving on to the next row. If a row comes back empty it stops
+/// there, leaving the file unfinished
+pub fn save_tiled_png<F>(
+    path: &str,
+    width: u32,
+    height: u32,
+    tile_rows: &[Vec<Tile>],
+    mut render_row: F,
+    recipe: &[String],
+) -> Result<(), Error>
+where
+    F: FnMut(&[Tile]) -> Result<Option<RgbaImage>, Error>,
+{
+    let png_error = |encoding_error| Error::WritePng {
+        path: path.to_string(),
+        source: encoding_error,
+    };
+    let writer = BufWriter::new(create_file(path)?);
+    let png_encoder = get_png_encoder(writer, width, height, recipe).map_err(png_error)?;
+    let mut writer = png_encoder.write_header().map_err(png_error)?;
+    let mut stream_writer = writer.stream_writer().map_err(png_error)?;
+
+    for tile_row in tile_rows.iter() {
+        let Some(row) = render_row(tile_row)? else {
+            return Ok(());
+        };
+        stream_writer
+            .write_all(row.as_raw())
+            .map_err(|io_error| png_error(io_error.into()))?;
+    }
+
+    stream_writer.finish().map_err(png_error)
+}
+
+fn create_file(path: &str) -> Result<File, Error> {
+    File::create(path).map_err(|io_error| Error::CreateFile {
+        path: path.to_string(),
+        source: io_error,
+    })
+}
+
+pub fn write_png<W: Write>(
+    writer: W,
+    image: &RgbaImage,
+    recipe: &[String],
+) -> Result<(), png::EncodingError> {
+    let (width, height) = image.dimensions();
+    let png_encoder = get_png_encoder(writer, width, height, recipe)?;
+    let mut writer = png_encoder.write_header()?;
+    writer.write_image_data(image)?;
+    writer.finish()
+}
+
+pub fn write_animated_png<W: Write>(
+    writer: W,
+    images: &[RgbaImage],
+    recipe: &[String],
+    playback: Playback,
+) -> Result<(), png::EncodingError> {
+    let (width, height) = images[0].dimensions();
+    let mut png_encoder = get_png_encoder(writer, width, height, recipe)?;
+    // 0 plays loops forever
+    png_encoder.set_animated(images.len() as u32, playback.loop_num.unwrap_or(0))?;
+    png_encoder.set_frame_delay(1, playback.fps.min(u16::MAX as u32) as u16)?;
+    let mut writer = png_encoder.write_header()?;
+    for image in images.iter() {
+        writer.write_image_data(image)?;
+    }
+    writer.finish()
+}
+
+fn get_png_encoder<'a, W: Write>(
+    writer: W,
+    width: u32,
+    height: u32,
+    recipe: &[String],
+) -> Result<png::Encoder<'a, W>, png::EncodingError> {
+    let mut png_encoder = png::Encoder::new(writer, width, height);
+    png_encoder.set_color(png::ColorType::Rgba);
+    png_encoder.set_depth(png::BitDepth::Eight);
+    if !recipe.is_empty() {
+        png_encoder.add_itxt_chunk(RECIPE_KEYWORD.to_string(), recipe.join("\n"))?;
+    }
+
+    Ok(png_encoder)
+}
+
+fn write_animated_gif<W: Write>(
+    writer: W,
+    images: &[RgbaImage],
+    playback: Playback,
+) -> image::ImageResult<()> {
+    let mut gif_encoder = GifEncoder::new(writer);
+    gif_encoder.set_repeat(match playback.loop_num {
+        Some(loop_num) => Repeat::Finite(loop_num.min(u16::MAX as u32) as u16),
+        None => Repeat::Infinite,
+    })?;
+    // GIFs time frames in hundredths of a second, so fast ones get rounded
+    let frame_delay = Duration::from_secs(1) / playback.fps;
+    gif_encoder.encode_frames(images.iter().map(|image| {
+        Frame::from_parts(
+            image.clone(),
+            0,
+            0,
+            Delay::from_saturating_duration(frame_delay),
+        )
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_format() {
+        assert_eq!(FileFormat::from_path("out.PNG").unwrap(), FileFormat::Png);
+        assert_eq!(FileFormat::from_path("a/b.tif").unwrap(), FileFormat::Tiff);
+        assert!(FileFormat::from_path("image").is_err());
+        assert!(FileFormat::from_path("image.jpg").is_err());
+
+        assert!(matches!(
+            get_output_format("doesnt-exist.webp", true, false),
+            Err(Error::NotAnimatable { .. })
+        ));
+        assert_eq!(
+            get_output_format("doesnt-exist.gif", true, false).unwrap(),
+            FileFormat::Gif
+        );
+
+        let directory_path =
+            std::env::temp_dir().join(format!("fun-images-existing-{}", std::process::id()));
+        std::fs::create_dir_all(&directory_path).unwrap();
+        let existing_path = directory_path.join("existing.png");
+        File::create(&existing_path).unwrap();
+        let existing = existing_path.to_str().unwrap();
+        assert!(matches!(
+            get_output_format(existing, false, false),
+            Err(Error::OutputExists { .. })
+        ));
+        assert!(get_output_format(existing, false, true).is_ok());
+        std::fs::remove_dir_all(&directory_path).unwrap();
+    }
+
+    #[test]
+    fn test_save_frames() {
+        assert_eq!(get_frame_path("out", 0, 30), "out/frame_0001.png");
+        assert_eq!(get_frame_path("out", 12344, 12345), "out/frame_12345.png");
+
+        let directory_path =
+            std::env::temp_dir().join(format!("fun-images-frames-{}", std::process::id()));
+        let directory = directory_path.to_str().unwrap();
+        let images = vec![RgbaImage::new(2, 2); 3];
+        save_frames(&images, directory, true, &[]).unwrap();
+        assert!(Path::new(&get_frame_path(directory, 2, 3)).exists());
+        assert!(matches!(
+            save_frames(&images, directory, false, &[]),
+            Err(Error::OutputExists { .. })
+        ));
+        assert_eq!(open_frames(directory).unwrap(), images);
+        std::fs::remove_dir_all(&directory_path).unwrap();
+
+        let mut names = vec!["frame_10.png", "frame_2.png", "frame_1.png"];
+        names.sort_by_key(|name| get_frame_sort_key(name));
+        assert_eq!(names, ["frame_1.png", "frame_2.png", "frame_10.png"]);
+    }
+}
@@ -0,0 +1,102 @@
+//! Images from files mixed into the output after the post effects, like a watermark
+//!
+//! These live with the command line rather than the post effects in the library as
+//! they need to open files
+
+use fun_images::{
+    BlendMode, Placement,
+    compose::{blend_images, stamp_image},
+    parse_fraction,
+};
+use image::RgbaImage;
+
+use crate::{error::Error, open_optional_image};
+
+/// Args for mixing images from files into the output
+#[derive(clap::Args, Debug)]
+pub struct OverlayArgs {
+    /// Mixes an existing image into the generated one, stretched to the same size
+    #[arg(long, global = true)]
+    blend_with: Option<String>,
+
+    /// How the --blend-with image mixes with the generated one
+    #[arg(long, default_value = "multiply", global = true)]
+    blend_mode: BlendMode,
+
+    /// How much of the --blend-with image shows, from 0 to 1
+    #[arg(long, default_value = "1.0", global = true, value_parser = parse_fraction)]
+    blend_opacity: f64,
+
+    /// An image like a logo or signature stamped onto the output, and onto every frame
+    /// of animations
+    #[arg(long, global = true)]
+    watermark: Option<String>,
+
+    /// Which corner or edge of the image the watermark goes in
+    #[arg(
+        long,
+        alias = "watermark-pos",
+        default_value = "bottom-right",
+        global = true
+    )]
+    watermark_position: Placement,
+
+    /// How much of the watermark shows, from 0 to 1
+    #[arg(long, default_value = "1.0", global = true, value_parser = parse_fraction)]
+    watermark_opacity: f64,
+}
+
+impl OverlayArgs {
+    /// Whether any image will be mixed in
+    pub fn has_effects(&self) -> bool {
+        self.blend_with.is_some() || self.watermark.is_some()
+    }
+}
+
+/// The opened images ready to be mixed into each output image
+pub struct Overlays {
+    blend_image: Option<RgbaImage>,
+    blend_mode: BlendMode,
+    blend_opacity: f64,
+    watermark: Option<RgbaImage>,
+    watermark_position: Placement,
+    watermark_opacity: f64,
+}
+
+impl Overlays {
+    /// Opens the images, done before generating so a missing file doesn't waste a render
+    pub fn open(overlay_args: &OverlayArgs) -> Result<Self, Error> {
+        let open = |path: &Option<String>| {
+            open_optional_image(path.clone()).map(|image| image.map(|image| image.into_rgba8()))
+        };
+
+        Ok(Self {
+            blend_image: open(&overlay_args.blend_with)?,
+            blend_mode: overlay_args.blend_mode,
+            blend_opacity: overlay_args.blend_opacity,
+            watermark: open(&overlay_args.watermark)?,
+            watermark_position: overlay_args.watermark_position,
+            watermark_opacity: overlay_args.watermark_opacity,
+        })
+    }
+
+    pub fn apply(&self, image: RgbaImage) -> RgbaImage {
+        let mut image = match &self.blend_image {
+            Some(blend_image) => {
+                blend_images(&image, blend_image, self.blend_mode, self.blend_opacity)
+            }
+            None => image,
+        };
+        // the watermark goes on last so it's never blended away
+        if let Some(watermark) = &self.watermark {
+            stamp_image(
+                &mut image,
+                watermark,
+                self.watermark_position,
+                self.watermark_opacity,
+            );
+        }
+
+        image
+    }
+}
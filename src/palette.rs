@@ -0,0 +1,165 @@
+//! Shared colour palettes for generators that map a value to a colour
+//!
+//! Palettes are cosine gradients as described at <https://iquilezles.org/articles/palettes/>
+//! which wrap around smoothly, so shifting the position gives nice palette cycling
+
+use std::f64;
+
+use image::{Rgba, RgbaImage};
+
+use crate::{PaletteType, cancel::render_frames};
+
+/// colour = a + b * cos(2π(c * t + d)) per channel
+struct CosinePalette {
+    a: [f64; 3],
+    b: [f64; 3],
+    c: [f64; 3],
+    d: [f64; 3],
+}
+
+impl CosinePalette {
+    fn for_type(palette_type: PaletteType) -> Self {
+        match palette_type {
+            PaletteType::Rainbow => Self {
+                a: [0.5, 0.5, 0.5],
+                b: [0.5, 0.5, 0.5],
+                c: [1.0, 1.0, 1.0],
+                d: [0.0, 0.33, 0.67],
+            },
+            PaletteType::Fire => Self {
+                a: [0.5, 0.25, 0.05],
+                b: [0.5, 0.25, 0.05],
+                c: [1.0, 1.0, 1.0],
+                d: [0.0, 0.1, 0.2],
+            },
+            PaletteType::Ocean => Self {
+                a: [0.1, 0.4, 0.6],
+                b: [0.1, 0.3, 0.4],
+                c: [1.0, 1.0, 1.0],
+                d: [0.0, 0.1, 0.2],
+            },
+            PaletteType::Psychedelic => Self {
+                a: [0.5, 0.5, 0.5],
+                b: [0.5, 0.5, 0.5],
+                c: [2.0, 1.0, 0.0],
+                d: [0.5, 0.2, 0.25],
+            },
+            PaletteType::Grayscale => Self {
+                a: [0.5, 0.5, 0.5],
+                b: [0.5, 0.5, 0.5],
+                c: [1.0, 1.0, 1.0],
+                d: [0.0, 0.0, 0.0],
+            },
+        }
+    }
+
+    fn sample(&self, t: f64) -> [u8; 4] {
+        let channel = |i: usize| {
+            let value = self.a[i]
+                + self.b[i] * f64::cos(2.0 * f64::consts::PI * (self.c[i] * t + self.d[i]));
+            (value.clamp(0.0, 1.0) * 255.0) as u8
+        };
+        [channel(0), channel(1), channel(2), 255]
+    }
+}
+
+/// Gets the colour of the palette at a position, the position wraps around every 1.0
+pub fn get_palette_color(palette_type: PaletteType, position: f64) -> [u8; 4] {
+    CosinePalette::for_type(palette_type).sample(position)
+}
+
+/// Linearly interpolates between two colours, an amount of 1.0 gives the first colour
+pub fn interpolate_color(color1: [u8; 4], color2: [u8; 4], amount: f64) -> [u8; 4] {
+    let interp = |x: u8, y: u8| ((x as f64) * amount + (y as f64) * (1.0 - amount)) as u8;
+
+    [
+        interp(color1[0], color2[0]),
+        interp(color1[1], color2[1]),
+        interp(color1[2], color2[2]),
+        interp(color1[3], color2[3]),
+    ]
+}
+
+/// Gets the colour at a position along a gradient of evenly spaced colour stops,
+/// positions outside of 0 to 1 are clamped
+pub fn get_gradient_color(stops: &[[u8; 4]], position: f64) -> [u8; 4] {
+    let scaled = position.clamp(0.0, 1.0) * (stops.len() - 1) as f64;
+    let index = (scaled as usize).min(stops.len() - 2);
+    // interpolate_color takes the first colour at 1.0 so go from the next stop back
+    interpolate_color(stops[index + 1], stops[index], scaled - index as f64)
+}
+
+/// An image kept as positions along a palette rather than colours, so it can be coloured
+/// again with the palette shifted along without rendering it again. This is how the old
+/// colour cycling animations worked, see <https://en.wikipedia.org/wiki/Color_cycling>
+pub struct IndexedImage {
+    width: u32,
+    height: u32,
+    /// The position of each pixel in row order, None for the pixels left as the background
+    positions: Vec<Option<f64>>,
+}
+
+impl IndexedImage {
+    pub fn new(width: u32, height: u32, positions: Vec<Option<f64>>) -> Self {
+        assert_eq!(positions.len(), (width * height) as usize);
+        Self {
+            width,
+            height,
+            positions,
+        }
+    }
+
+    /// Colours the image with every position moved along the palette by the shift
+    pub fn to_image(
+        &self,
+        palette_type: PaletteType,
+        background_color: [u8; 4],
+        shift: f64,
+    ) -> RgbaImage {
+        let palette = CosinePalette::for_type(palette_type);
+        let mut image = RgbaImage::new(self.width, self.height);
+        for (pixel, position) in image.pixels_mut().zip(&self.positions) {
+            *pixel = Rgba(match position {
+                Some(position) => palette.sample(position + shift),
+                None => background_color,
+            });
+        }
+        image
+    }
+}
+
+/// Cycles the palette through the image a number of times over the frames, which loop
+/// since the palettes wrap round
+pub fn generate_palette_cycle_images(
+    image: &IndexedImage,
+    palette_type: PaletteType,
+    background_color: [u8; 4],
+    frame_num: u32,
+    speed: u32,
+) -> Vec<RgbaImage> {
+    render_frames(0..frame_num, |frame| {
+        let shift = frame as f64 / frame_num as f64 * speed as f64;
+        image.to_image(palette_type, background_color, shift)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_palette_cycle() {
+        let image = IndexedImage::new(2, 1, vec![Some(0.25), None]);
+        let frames = generate_palette_cycle_images(&image, PaletteType::Rainbow, [0; 4], 4, 1);
+        // each frame moves the palette a quarter of the way along, and the background stays
+        for (frame, image) in frames.iter().enumerate() {
+            let position = 0.25 + frame as f64 / 4.0;
+            assert_eq!(
+                image[(0, 0)].0,
+                get_palette_color(PaletteType::Rainbow, position)
+            );
+            assert_eq!(image[(1, 0)].0, [0; 4]);
+        }
+        assert_ne!(frames[0], frames[1]);
+    }
+}
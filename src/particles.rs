@@ -0,0 +1,289 @@
+//! Module for animating particles carried along by curl noise
+//!
+//! The flow comes from the curl of Perlin noise, going along the contours of the noise
+//! rather than up or down them like the gradient would, so it swirls around without
+//! ever bunching up or spreading out, like an incompressible fluid. Each particle lives
+//! for exactly as many frames as the animation has, starting at a random point in its
+//! life, so on the last frame everything is back where it started and the animation
+//! loops.
+//! See <https://en.wikipedia.org/wiki/Curl_(mathematics)> for more info
+
+use std::f64::consts::PI;
+
+use csscolorparser::Color;
+use image::{Rgba, RgbaImage};
+use imageproc::drawing::draw_antialiased_line_segment_mut;
+
+use crate::{
+    BlendMode, PaletteType,
+    cancel::render_frames,
+    compose::blend_over,
+    generator::{Generated, Generator, GeneratorError},
+    memory::MemoryEstimate,
+    palette::get_palette_color,
+    parse_color, parse_size,
+    perlin::Noise,
+};
+
+/// How far apart the noise is sampled to work out its slope, in noise units
+const SLOPE_STEP: f64 = 1e-4;
+
+pub struct ParticlesOptions {
+    width: u32,
+    height: u32,
+    particle_num: u32,
+    frame_num: u32,
+    /// How many frames back the trail behind each particle goes
+    trail_length: u32,
+    /// How far apart in pixels the flow changes direction, bigger gives wider swirls
+    noise_scale: f64,
+    /// Roughly how many pixels the particles go each frame
+    speed: f64,
+    palette: PaletteType,
+    background_color: Color,
+    seed: Option<u64>,
+}
+
+impl ParticlesOptions {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        width: u32,
+        height: u32,
+        particle_num: u32,
+        frame_num: u32,
+        trail_length: u32,
+        noise_scale: f64,
+        speed: f64,
+        palette: PaletteType,
+        background_color: Color,
+        seed: Option<u64>,
+    ) -> Self {
+        Self {
+            width,
+            height,
+            particle_num,
+            frame_num,
+            trail_length,
+            noise_scale,
+            speed,
+            palette,
+            background_color,
+            seed,
+        }
+    }
+
+    /// How far a particle moves in a frame at a point, which is the curl of the noise,
+    /// its slope turned a quarter turn
+    fn get_velocity(&self, noise: &Noise, (x, y): (f64, f64)) -> (f64, f64) {
+        let (x, y) = (x / self.noise_scale, y / self.noise_scale);
+        let slope_x =
+            (noise.get(x + SLOPE_STEP, y) - noise.get(x - SLOPE_STEP, y)) / (2.0 * SLOPE_STEP);
+        let slope_y =
+            (noise.get(x, y + SLOPE_STEP) - noise.get(x, y - SLOPE_STEP)) / (2.0 * SLOPE_STEP);
+
+        (slope_y * self.speed, -slope_x * self.speed)
+    }
+}
+
+struct Particle {
+    /// Where the particle is on each frame of its life
+    path: Vec<(f64, f64)>,
+    /// How far through its life the particle is on the first frame
+    age_offset: u32,
+    color: [u8; 4],
+}
+
+/// Follows the flow from a point for a number of frames, giving where it is on each
+pub fn trace_particle(
+    options: &ParticlesOptions,
+    noise: &Noise,
+    start: (f64, f64),
+) -> Vec<(f64, f64)> {
+    let mut point = start;
+    let mut path = Vec::with_capacity(options.frame_num as usize);
+    path.push(point);
+    for _ in 1..options.frame_num {
+        // moving with the velocity halfway along the step keeps it on the contour much
+        // better than the velocity at the start
+        let velocity = options.get_velocity(noise, point);
+        let middle = (point.0 + velocity.0 / 2.0, point.1 + velocity.1 / 2.0);
+        let velocity = options.get_velocity(noise, middle);
+        point = (point.0 + velocity.0, point.1 + velocity.1);
+        path.push(point);
+    }
+
+    path
+}
+
+pub fn generate_particles_images(options: ParticlesOptions) -> Vec<RgbaImage> {
+    let mut rng = match options.seed {
+        Some(seed) => fastrand::Rng::with_seed(seed),
+        None => fastrand::Rng::new(),
+    };
+    let noise = Noise::new(&mut rng);
+    let particles: Vec<Particle> = (0..options.particle_num)
+        .map(|_| {
+            let start = (
+                rng.f64() * options.width as f64,
+                rng.f64() * options.height as f64,
+            );
+            Particle {
+                path: trace_particle(&options, &noise, start),
+                age_offset: rng.u32(..options.frame_num),
+                color: get_palette_color(options.palette, rng.f64()),
+            }
+        })
+        .collect();
+
+    let background_color = Rgba(options.background_color.to_rgba8());
+    let render_frame = |frame: u32| {
+        let mut image = RgbaImage::from_pixel(options.width, options.height, background_color);
+        for particle in particles.iter() {
+            let age = (frame + particle.age_offset) % options.frame_num;
+            // fades in and out over its life so particles don't pop in and out
+            let life = (PI * (age as f64 + 0.5) / options.frame_num as f64).sin();
+            let trail_start = age.saturating_sub(options.trail_length);
+            for trail_age in trail_start..age {
+                // the end of the trail fades away
+                let fade = (trail_age + 1 - trail_start) as f64 / (age - trail_start) as f64;
+                let opacity = life * fade;
+                let (start, end) = (
+                    particle.path[trail_age as usize],
+                    particle.path[trail_age as usize + 1],
+                );
+                draw_antialiased_line_segment_mut(
+                    &mut image,
+                    (start.0.round() as i32, start.1.round() as i32),
+                    (end.0.round() as i32, end.1.round() as i32),
+                    Rgba(particle.color),
+                    |line, below, amount| {
+                        Rgba(blend_over(
+                            below.0,
+                            line.0,
+                            amount as f64 * opacity,
+                            BlendMode::Normal,
+                        ))
+                    },
+                );
+            }
+        }
+
+        image
+    };
+
+    render_frames(0..options.frame_num, render_frame)
+}
+
+#[derive(clap::Args, Debug)]
+pub struct ParticlesArgs {
+    #[arg(long, default_value = "500", value_parser = parse_size)]
+    width: u32,
+
+    #[arg(long, default_value = "500", value_parser = parse_size)]
+    height: u32,
+
+    /// How many particles there are
+    #[arg(short = 'n', long, default_value = "1500", value_parser = parse_size)]
+    particles: u32,
+
+    /// The number of frames in the loop, which is also how long each particle lives
+    #[arg(short, long, default_value = "90", value_parser = parse_size)]
+    frames: u32,
+
+    /// How many frames back the trail behind each particle goes
+    #[arg(short, long, default_value = "12")]
+    trail_length: u32,
+
+    /// How far apart in pixels the flow changes direction, bigger gives wider swirls
+    #[arg(long, default_value = "150.0")]
+    scale: f64,
+
+    /// Roughly how many pixels the particles go each frame
+    #[arg(long, default_value = "3.0")]
+    speed: f64,
+
+    #[arg(short, long, default_value = "ocean")]
+    palette: PaletteType,
+
+    /// The colour behind the particles, which can be transparent
+    #[arg(short, long, alias = "background", default_value = "black", value_parser = parse_color)]
+    background_color: Color,
+
+    /// The seed used for the flow and the particles, random if not given
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+pub struct ParticlesGenerator;
+
+impl Generator for ParticlesGenerator {
+    type Args = ParticlesArgs;
+
+    fn name(&self) -> &'static str {
+        "particles"
+    }
+
+    fn is_animated(&self, _args: &ParticlesArgs) -> bool {
+        true
+    }
+
+    fn estimate_memory(&self, args: &ParticlesArgs) -> Option<MemoryEstimate> {
+        Some(MemoryEstimate::frames(args.width, args.height, args.frames))
+    }
+
+    fn generate(&self, args: ParticlesArgs) -> Result<Generated, GeneratorError> {
+        if args.scale <= 0.0 {
+            return Err(GeneratorError::InvalidOptions(
+                "the scale has to be more than 0".to_string(),
+            ));
+        }
+
+        Ok(Generated::Frames(generate_particles_images(
+            ParticlesOptions::new(
+                args.width,
+                args.height,
+                args.particles,
+                args.frames,
+                args.trail_length,
+                args.scale,
+                args.speed,
+                args.palette,
+                args.background_color,
+                args.seed,
+            ),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_particles_follow_contours() {
+        // the curl goes along the contours of the noise, so a particle should stay at
+        // about the same noise value the whole way
+        let options = ParticlesOptions::new(
+            200,
+            200,
+            1,
+            200,
+            10,
+            50.0,
+            2.0,
+            PaletteType::Ocean,
+            Color::new(0.0, 0.0, 0.0, 1.0),
+            None,
+        );
+        let noise = Noise::new(&mut fastrand::Rng::with_seed(4));
+        let get_value = |(x, y): (f64, f64)| noise.get(x / 50.0, y / 50.0);
+
+        let path = trace_particle(&options, &noise, (100.0, 100.0));
+        assert_eq!(path.len(), 200);
+        let start_value = get_value(path[0]);
+        for &point in path.iter() {
+            assert!((get_value(point) - start_value).abs() < 0.02);
+        }
+        assert!(f64::hypot(path[1].0 - path[0].0, path[1].1 - path[0].1) > 0.0);
+    }
+}
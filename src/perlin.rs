@@ -5,6 +5,17 @@ use std::f64;
 
 use csscolorparser::Color;
 use image::{DynamicImage, Rgba, RgbaImage};
+use log::warn;
+
+use crate::{
+    BlendMode, anaglyph,
+    compose::blend_over,
+    contours::ContourOptions,
+    generator::{Generated, Generator, GeneratorError},
+    hillshade::{Hillshade, parse_hillshade},
+    memory::{BYTES_PER_PIXEL, MemoryEstimate},
+    parse_color, parse_size,
+};
 
 type Vec2 = (f64, f64);
 
@@ -12,36 +23,128 @@ pub struct PerlinNoiseOptions {
     size: u32,
     color1: Color,
     color2: Color,
+    /// Shows through wherever the colours are see-through
+    background_color: Color,
+    seed: Option<u64>,
+    use_gpu: bool,
+    /// Shades the noise as if it were hills lit from the side
+    hillshade: Option<Hillshade>,
+    /// Contour lines drawn over the noise, through evenly spaced noise values
+    contours: Option<ContourOptions>,
+    /// Makes a red and cyan anaglyph with the noise as heights, with the peaks this many
+    /// more pixels apart between the eyes than the valleys
+    parallax: Option<f64>,
 }
 
 impl PerlinNoiseOptions {
-    pub fn new(size: u32, color1: Color, color2: Color) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        size: u32,
+        color1: Color,
+        color2: Color,
+        background_color: Color,
+        seed: Option<u64>,
+        use_gpu: bool,
+        hillshade: Option<Hillshade>,
+        contours: Option<ContourOptions>,
+        parallax: Option<f64>,
+    ) -> Self {
         Self {
             size,
             color1,
             color2,
+            background_color,
+            seed,
+            use_gpu,
+            hillshade,
+            contours,
+            parallax,
         }
     }
 }
 
+const GRID_SIZE: usize = 20;
+
 pub fn generate_perlin_noise(options: PerlinNoiseOptions) -> DynamicImage {
     let PerlinNoiseOptions {
         size,
         color1,
         color2,
+        background_color,
+        seed,
+        use_gpu,
+        hillshade,
+        contours,
+        parallax,
     } = options;
+    let background_color = background_color.to_rgba8();
     let mut image = RgbaImage::new(size, size);
+    let mut rng = match seed {
+        Some(seed) => fastrand::Rng::with_seed(seed),
+        None => fastrand::Rng::new(),
+    };
 
     // generate grid
-    const GRID_SIZE: usize = 20;
     let grid_size = size as usize / GRID_SIZE + 1;
-    let mut grid = vec![(0.0, 0.0); (grid_size * grid_size) as usize];
+    let mut grid = vec![(0.0, 0.0); grid_size * grid_size];
 
     grid.iter_mut().for_each(|x| {
-        *x = random_vec2();
+        *x = random_vec2(&mut rng);
     });
 
-    let grid_iter = (0..grid_size).flat_map(|x| std::iter::repeat(x).take(GRID_SIZE as usize));
+    let values = get_values(use_gpu, size, &grid, grid_size);
+    for (pixel, &value) in image.pixels_mut().zip(values.iter()) {
+        let value = (value as f32 + 1.0) / 2.0;
+        let color = Color {
+            r: color1.r * value + color2.r * (1.0 - value),
+            g: color1.g * value + color2.g * (1.0 - value),
+            b: color1.b * value + color2.b * (1.0 - value),
+            a: color1.a * value + color2.a * (1.0 - value),
+        };
+
+        *pixel = Rgba(blend_over(
+            background_color,
+            color.to_rgba8(),
+            1.0,
+            BlendMode::Normal,
+        ));
+    }
+
+    if let Some(hillshade) = hillshade {
+        // the noise goes up and down by about a grid square, which gives hills about
+        // as steep as they are wide
+        let heights: Vec<f64> = values
+            .iter()
+            .map(|value| value * GRID_SIZE as f64 / 4.0)
+            .collect();
+        hillshade.apply_mut(&mut image, &heights);
+    }
+    if let Some(contours) = contours {
+        contours.draw_mut(&mut image, &values, -1.0, 1.0);
+    }
+    if let Some(parallax) = parallax {
+        let heights: Vec<f64> = values.iter().map(|value| (value + 1.0) / 2.0).collect();
+        let (left, right) = anaglyph::get_parallax_views(&image, &heights, parallax);
+        image = anaglyph::combine_views(&left, &right);
+    }
+
+    DynamicImage::ImageRgba8(image)
+}
+
+/// Gets the noise value of each pixel in row order, between -1 and 1
+fn get_values(use_gpu: bool, size: u32, grid: &[Vec2], grid_size: usize) -> Vec<f64> {
+    if use_gpu {
+        #[cfg(feature = "gpu")]
+        match crate::gpu::get_perlin_values(size, grid_size as u32, GRID_SIZE as u32, grid) {
+            Some(values) => return values,
+            None => warn!("No GPU available, rendering on the CPU instead"),
+        }
+        #[cfg(not(feature = "gpu"))]
+        warn!("Built without the gpu feature, rendering on the CPU instead");
+    }
+
+    let mut values = Vec::with_capacity(size as usize * size as usize);
+    let grid_iter = (0..grid_size).flat_map(|x| std::iter::repeat_n(x, GRID_SIZE));
     for (y, grid_y) in (0..size).zip(grid_iter.clone()) {
         for (x, grid_x) in (0..size).zip(grid_iter.clone()) {
             // top left, top right, bottom left, bottom right vecs
@@ -94,21 +197,11 @@ pub fn generate_perlin_noise(options: PerlinNoiseOptions) -> DynamicImage {
 
             let val1 = interpolate(dot_1, dot_2, frac_x);
             let val2 = interpolate(dot_3, dot_4, frac_x);
-            let value = interpolate(val1, val2, frac_y);
-
-            let value = (value as f32 + 1.0) / 2.0;
-            let color = Color {
-                r: color1.r * value + color2.r * (1.0 - value),
-                g: color1.g * value + color2.g * (1.0 - value),
-                b: color1.b * value + color2.b * (1.0 - value),
-                a: color1.a * value + color2.a * (1.0 - value),
-            };
-
-            image[(x, y)] = Rgba(color.to_rgba8());
+            values.push(interpolate(val1, val2, frac_y));
         }
     }
 
-    DynamicImage::ImageRgba8(image)
+    values
 }
 
 fn interpolate(a: f64, b: f64, x: f64) -> f64 {
@@ -120,8 +213,8 @@ fn fade(val: f64) -> f64 {
 }
 
 // random unit length 2d vector
-fn random_vec2() -> Vec2 {
-    let angle = fastrand::f64() * 2.0 * f64::consts::PI;
+fn random_vec2(rng: &mut fastrand::Rng) -> Vec2 {
+    let angle = rng.f64() * 2.0 * f64::consts::PI;
     (f64::cos(angle), f64::sin(angle))
 }
 
@@ -145,3 +238,155 @@ fn grid_distance(grid_pos: (u32, u32), point_pos: (u32, u32), grid_size: u32) ->
 
     offset(point_pos, grid_pos)
 }
+
+/// Perlin noise from a shuffled table which can be sampled anywhere, giving values from
+/// about -1 to 1 which change smoothly over about one unit and repeat every 256
+pub struct Noise {
+    permutation: [u8; 512],
+}
+
+impl Noise {
+    pub fn new(rng: &mut fastrand::Rng) -> Self {
+        let mut table: Vec<u8> = (0..=255).collect();
+        rng.shuffle(&mut table);
+
+        Self {
+            permutation: std::array::from_fn(|index| table[index % 256]),
+        }
+    }
+
+    pub fn get(&self, x: f64, y: f64) -> f64 {
+        let (cell_x, cell_y) = (x.floor(), y.floor());
+        let (x, y) = (x - cell_x, y - cell_y);
+        let (cell_x, cell_y) = (
+            cell_x.rem_euclid(256.0) as usize,
+            cell_y.rem_euclid(256.0) as usize,
+        );
+
+        let corner = |offset_x: usize, offset_y: usize| {
+            let hash =
+                self.permutation[self.permutation[cell_x + offset_x] as usize + cell_y + offset_y];
+            let (gradient_x, gradient_y) = GRADIENTS[hash as usize % GRADIENTS.len()];
+            gradient_x * (x - offset_x as f64) + gradient_y * (y - offset_y as f64)
+        };
+        let fade = |value: f64| value * value * value * (value * (value * 6.0 - 15.0) + 10.0);
+        let lerp = |a: f64, b: f64, amount: f64| a + (b - a) * amount;
+
+        let top = lerp(corner(0, 0), corner(1, 0), fade(x));
+        let bottom = lerp(corner(0, 1), corner(1, 1), fade(x));
+        lerp(top, bottom, fade(y)) * std::f64::consts::SQRT_2
+    }
+}
+
+const GRADIENTS: [(f64, f64); 8] = [
+    (1.0, 0.0),
+    (-1.0, 0.0),
+    (0.0, 1.0),
+    (0.0, -1.0),
+    (
+        std::f64::consts::FRAC_1_SQRT_2,
+        std::f64::consts::FRAC_1_SQRT_2,
+    ),
+    (
+        -std::f64::consts::FRAC_1_SQRT_2,
+        std::f64::consts::FRAC_1_SQRT_2,
+    ),
+    (
+        std::f64::consts::FRAC_1_SQRT_2,
+        -std::f64::consts::FRAC_1_SQRT_2,
+    ),
+    (
+        -std::f64::consts::FRAC_1_SQRT_2,
+        -std::f64::consts::FRAC_1_SQRT_2,
+    ),
+];
+
+#[derive(clap::Args, Debug)]
+pub struct PerlinArgs {
+    #[arg(long, default_value = "black", value_parser = parse_color)]
+    color1: Color,
+
+    #[arg(long, default_value = "white", value_parser = parse_color)]
+    color2: Color,
+
+    /// Shows through wherever the colours are see-through
+    #[arg(short, long, alias = "background", default_value = "transparent", value_parser = parse_color)]
+    background_color: Color,
+
+    #[arg(short, long, default_value = "500", value_parser = parse_size)]
+    size: u32,
+
+    /// The seed used for the gradients, random if not given
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Renders on the GPU when built with the gpu feature, falling back to the CPU
+    #[arg(long)]
+    gpu: bool,
+
+    /// Shades the noise as if it were hills lit from a direction, given as
+    /// azimuth,altitude in degrees with the azimuth going clockwise from the top, like
+    /// 315,45 for light from the top left
+    #[arg(long, value_parser = parse_hillshade, allow_hyphen_values = true)]
+    hillshade: Option<Hillshade>,
+
+    /// Draws this many contour lines over the noise like a topographic map, going
+    /// through evenly spaced noise values
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    contours: Option<u32>,
+
+    #[arg(long, default_value = "red", value_parser = parse_color)]
+    contour_color: Color,
+
+    /// Renders a red and cyan anaglyph with the noise as heights, which stand out
+    /// in 3D through the glasses
+    #[arg(long)]
+    anaglyph: bool,
+
+    /// How many more pixels apart the peaks are than the valleys between the two
+    /// eyes of the anaglyph
+    #[arg(long, default_value = "16.0")]
+    parallax: f64,
+}
+
+pub struct PerlinGenerator;
+
+impl Generator for PerlinGenerator {
+    type Args = PerlinArgs;
+
+    fn name(&self) -> &'static str {
+        "perlin"
+    }
+
+    fn is_animated(&self, _args: &PerlinArgs) -> bool {
+        false
+    }
+
+    fn estimate_memory(&self, args: &PerlinArgs) -> Option<MemoryEstimate> {
+        // a noise value for every pixel, and the anaglyph renders both eyes first
+        let estimate =
+            MemoryEstimate::still(args.size, args.size).with_pixel_buffer(args.size, args.size, 8);
+        Some(match args.anaglyph {
+            true => estimate.with_pixel_buffer(args.size, args.size, 2 * BYTES_PER_PIXEL),
+            false => estimate,
+        })
+    }
+
+    fn generate(&self, args: PerlinArgs) -> Result<Generated, GeneratorError> {
+        Ok(Generated::Still(
+            generate_perlin_noise(PerlinNoiseOptions::new(
+                args.size,
+                args.color1,
+                args.color2,
+                args.background_color,
+                args.seed,
+                args.gpu,
+                args.hillshade,
+                args.contours
+                    .map(|level_num| ContourOptions::new(level_num, args.contour_color)),
+                args.anaglyph.then_some(args.parallax),
+            ))
+            .into_rgba8(),
+        ))
+    }
+}
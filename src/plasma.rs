@@ -0,0 +1,147 @@
+//! The old school demoscene plasma effect
+//!
+//! Sums a few sine fields which shift over time and maps the result through a palette
+//! which also cycles, see <https://lodev.org/cgtutor/plasma.html> for a good explanation
+
+use std::f64;
+
+use image::{Rgba, RgbaImage};
+
+use crate::{
+    PaletteType,
+    cancel::render_frames,
+    generator::{Generated, Generator, GeneratorError},
+    memory::MemoryEstimate,
+    palette::{IndexedImage, generate_palette_cycle_images, get_palette_color},
+    parse_size,
+};
+
+pub struct PlasmaOptions {
+    size: u32,
+    frame_num: u32,
+    palette: PaletteType,
+    speed: u32,
+}
+
+impl PlasmaOptions {
+    pub fn new(size: u32, frame_num: u32, palette: PaletteType, speed: u32) -> Self {
+        Self {
+            size,
+            frame_num,
+            palette,
+            speed,
+        }
+    }
+}
+
+/// Gets the sum of the sine fields at a pixel, brought back to between 0 and 1. The time
+/// goes round once per loop
+fn get_plasma_value(size: u32, (x, y): (u32, u32), time: f64) -> f64 {
+    // scale everything relative to the size so the pattern looks the same at any size
+    let scale = size as f64 / 16.0;
+    let centre = size as f64 / 2.0;
+    let x_pos = x as f64 / scale;
+    let y_pos = y as f64 / scale;
+    let centre_distance = f64::hypot(x as f64 - centre, y as f64 - centre) / scale;
+
+    let value = f64::sin(x_pos + time)
+        + f64::sin(y_pos / 2.0 - time)
+        + f64::sin((x_pos + y_pos) / 2.0 + time)
+        + f64::sin(centre_distance - time);
+
+    // value is between -4 and 4 so bring it back to 0 to 1
+    (value + 4.0) / 8.0
+}
+
+pub fn generate_plasma_images(options: PlasmaOptions) -> Vec<RgbaImage> {
+    let PlasmaOptions {
+        size,
+        frame_num,
+        palette,
+        speed,
+    } = options;
+
+    let render_frame = |frame: u32| {
+        let mut image = RgbaImage::new(size, size);
+        // how far through the loop we are, all the time based terms are whole multiples
+        // of this so the last frame runs smoothly into the first
+        let loop_amount = frame as f64 / frame_num as f64;
+        let time = 2.0 * f64::consts::PI * loop_amount;
+
+        for (x, y, pixel) in image.enumerate_pixels_mut() {
+            let position = get_plasma_value(size, (x, y), time) + loop_amount * speed as f64;
+            *pixel = Rgba(get_palette_color(palette, position));
+        }
+
+        image
+    };
+
+    render_frames(0..frame_num, render_frame)
+}
+
+/// Keeps the sine fields where they start and only cycles the palette through them
+pub fn generate_plasma_cycle_images(options: PlasmaOptions) -> Vec<RgbaImage> {
+    let size = options.size;
+    let positions = (0..size)
+        .flat_map(|y| (0..size).map(move |x| Some(get_plasma_value(size, (x, y), 0.0))))
+        .collect();
+    generate_palette_cycle_images(
+        &IndexedImage::new(size, size, positions),
+        options.palette,
+        [0, 0, 0, 255],
+        options.frame_num,
+        options.speed,
+    )
+}
+
+#[derive(clap::Args, Debug)]
+pub struct PlasmaArgs {
+    #[arg(short, long, default_value = "400", value_parser = parse_size)]
+    size: u32,
+
+    /// The number of frames in the loop
+    #[arg(short, long, default_value = "60", value_parser = parse_size)]
+    frames: u32,
+
+    #[arg(short, long, default_value = "rainbow")]
+    palette: PaletteType,
+
+    /// How many times the palette cycles through per loop
+    #[arg(long, default_value = "1")]
+    speed: u32,
+
+    /// Keeps the plasma still and only cycles the palette through it
+    #[arg(long)]
+    palette_cycle: bool,
+}
+
+pub struct PlasmaGenerator;
+
+impl Generator for PlasmaGenerator {
+    type Args = PlasmaArgs;
+
+    fn name(&self) -> &'static str {
+        "plasma-effect"
+    }
+
+    fn is_animated(&self, _args: &PlasmaArgs) -> bool {
+        true
+    }
+
+    fn estimate_memory(&self, args: &PlasmaArgs) -> Option<MemoryEstimate> {
+        let estimate = MemoryEstimate::frames(args.size, args.size, args.frames);
+        // cycling the palette keeps a position in it for every pixel
+        Some(match args.palette_cycle {
+            true => estimate.with_pixel_buffer(args.size, args.size, 16),
+            false => estimate,
+        })
+    }
+
+    fn generate(&self, args: PlasmaArgs) -> Result<Generated, GeneratorError> {
+        let options = PlasmaOptions::new(args.size, args.frames, args.palette, args.speed);
+        Ok(Generated::Frames(match args.palette_cycle {
+            true => generate_plasma_cycle_images(options),
+            false => generate_plasma_images(options),
+        }))
+    }
+}
@@ -0,0 +1,465 @@
+//! Effects applied to images after they've been generated
+//!
+//! These work on any generator's output, and for animations they're applied to
+//! every frame
+
+use csscolorparser::Color;
+use image::RgbaImage;
+
+use crate::{
+    DitherMethod, Fit, HalftoneInk, ImageStyle, Placement, SortDirection, SortKey, parse_color,
+    parse_fraction, tiles::Tile,
+};
+
+mod adjust;
+mod caption;
+mod crt;
+mod film;
+mod frame;
+mod glitch;
+mod glow;
+mod halftone;
+mod kaleidoscope;
+mod little_planet;
+mod monochrome;
+mod pixel_sort;
+mod resize;
+mod sample;
+mod stereogram;
+mod transform;
+
+pub use resize::resize;
+use transform::{CoordinateTransform, parse_transform};
+
+/// Args for effects applied to the image after it's been generated
+#[derive(clap::Args, Debug)]
+pub struct PostArgs {
+    /// Cuts the image down to the rectangle given as x,y,width,height, before any
+    /// resizing. The rectangle has to fit inside the image
+    #[arg(long, global = true, value_parser = parse_rectangle)]
+    crop: Option<Tile>,
+
+    /// Scales the image to the size given as widthxheight
+    #[arg(long, global = true, value_parser = parse_dimensions)]
+    resize: Option<(u32, u32)>,
+
+    /// How the image is scaled when resizing to a different aspect ratio
+    #[arg(long, default_value = "stretch", global = true)]
+    fit: Fit,
+
+    /// Repeats the wedge at the top of the image this many times around the middle,
+    /// mirroring every other copy. The corners further out than the top edge are left
+    /// transparent
+    #[arg(long, global = true, value_parser = clap::value_parser!(u32).range(2..))]
+    kaleidoscope: Option<u32>,
+
+    /// Bends the image with polar, log-polar or swirl(angle), which turn rows into rings,
+    /// slanted lines into spirals or twist the middle round by the angle in degrees
+    #[arg(long, global = true, value_parser = parse_transform)]
+    transform: Option<CoordinateTransform>,
+
+    /// Wraps the image round into a tiny planet with a stereographic projection, with
+    /// the bottom edge as the ground in the middle and the top edge as the sky round it.
+    /// Works best on images that tile from left to right, like noise
+    #[arg(long, global = true)]
+    little_planet: bool,
+
+    /// How far the little planet view goes from edge to edge in degrees, where more
+    /// makes the planet smaller
+    #[arg(long, default_value = "240", global = true, value_parser = parse_field_of_view)]
+    planet_fov: f64,
+
+    /// Swaps every colour for its opposite
+    #[arg(long, global = true)]
+    invert: bool,
+
+    /// Turns the hue of every colour around the colour wheel by this many degrees
+    #[arg(
+        long,
+        default_value = "0",
+        global = true,
+        allow_negative_numbers = true
+    )]
+    hue_rotate: f64,
+
+    /// How colourful the image is, 0 is greyscale and above 1 is more colourful
+    #[arg(long, default_value = "1.0", global = true, value_parser = parse_non_negative)]
+    saturate: f64,
+
+    /// Brightens the midtones above 1 and darkens them below 1
+    #[arg(long, default_value = "1.0", global = true, value_parser = parse_positive)]
+    gamma: f64,
+
+    /// Sorts runs of pixels along each row or column by brightness or hue, which smears
+    /// the image into streaks like a glitch
+    #[arg(long, global = true)]
+    pixel_sort: Option<SortDirection>,
+
+    /// What the pixel sort puts the pixels in order by
+    #[arg(long, default_value = "luminance", global = true)]
+    sort_by: SortKey,
+
+    /// The brightness from 0 to 1 that pixels have to be between to get sorted, so the
+    /// darkest and brightest parts are left alone, like --sort-threshold 0.25 0.8
+    #[arg(long, num_args = 2, value_names = ["LOW", "HIGH"], default_values_t = [0.25, 0.8], global = true, value_parser = parse_fraction)]
+    sort_threshold: Vec<f64>,
+
+    /// Turns the image into a random dot autostereogram, with the brightness of each
+    /// pixel as its height, so the brightest parts come out of the picture when viewed
+    /// cross-eyed or wall-eyed
+    #[arg(long, global = true)]
+    stereogram: bool,
+
+    /// How far apart the viewer's eyes are in pixels for the stereogram, with the dots
+    /// in the background repeating every half of it
+    #[arg(long, default_value = "200", global = true, value_parser = clap::value_parser!(u32).range(8..))]
+    eye_separation: u32,
+
+    /// How far the brightest parts of the stereogram come out, as a fraction of the
+    /// distance from the background to the viewer
+    #[arg(long, default_value = "0.33", global = true, value_parser = parse_fraction)]
+    depth_scale: f64,
+
+    /// The seed for the stereogram's dots, which is picked at random and stored in the
+    /// recipe if not given. The dots stay the same on every frame of animations
+    #[arg(long, global = true)]
+    stereogram_seed: Option<u64>,
+
+    /// Re-renders the image in a different style
+    #[arg(long, default_value = "normal", global = true)]
+    style: ImageStyle,
+
+    /// The inks used for the halftone style
+    #[arg(long, default_value = "cmyk", global = true)]
+    halftone_ink: HalftoneInk,
+
+    /// The spacing of the dots in the halftone style
+    #[arg(long, default_value = "8", global = true, value_parser = clap::value_parser!(u32).range(1..))]
+    halftone_cell_size: u32,
+
+    /// The ink colour when using a single ink for the halftone style
+    #[arg(long, default_value = "black", global = true, value_parser = parse_color)]
+    halftone_color: Color,
+
+    /// Makes the bright parts of the image glow, given as the radius of the glow in
+    /// pixels then how strong it is, like --glow 12 1.5
+    #[arg(long, num_args = 2, value_names = ["RADIUS", "INTENSITY"], global = true, value_parser = parse_non_negative)]
+    glow: Option<Vec<f64>>,
+
+    /// Splits the red and blue channels apart sideways by this many pixels, like the
+    /// coloured fringes of a cheap lens
+    #[arg(long, default_value = "0", global = true)]
+    channel_offset: u32,
+
+    /// Glitches the image by knocking bands of rows sideways and corrupting blocks, from
+    /// 0 for none to 1 for the most
+    #[arg(long, default_value = "0", global = true, value_parser = parse_fraction)]
+    glitch: f64,
+
+    /// The seed for the glitches, which is picked at random and stored in the recipe if
+    /// not given. The glitches stay the same on every frame of animations
+    #[arg(long, global = true)]
+    glitch_seed: Option<u64>,
+
+    /// Makes the image look like it's on an old CRT monitor, with scanlines, a phosphor
+    /// mask, bloom and curved glass
+    #[arg(long, global = true)]
+    crt: bool,
+
+    /// How much the glass of the CRT bulges out, 0 for flat
+    #[arg(long, default_value = "0.08", global = true, value_parser = parse_non_negative)]
+    crt_curvature: f64,
+
+    /// Darkens the image towards the corners, from 0 for none to 1 for black corners
+    #[arg(long, default_value = "0", global = true, value_parser = parse_fraction)]
+    vignette: f64,
+
+    /// Adds film grain noise, from 0 for none to 1 for the most
+    #[arg(long, default_value = "0", global = true, value_parser = parse_fraction)]
+    grain: f64,
+
+    /// The seed for the grain, which is picked at random and stored in the recipe if not
+    /// given. The grain stays the same on every frame of animations
+    #[arg(long, global = true)]
+    grain_seed: Option<u64>,
+
+    /// Reduces the image to pure black and white like newspaper print, with the greys
+    /// made out of dots using the dithering method
+    #[arg(long, global = true)]
+    monochrome: Option<DitherMethod>,
+
+    /// Text drawn over the image, like the coordinates of a Mandelbrot zoom
+    #[arg(long, global = true)]
+    caption: Option<String>,
+
+    /// The height of the caption text in pixels
+    #[arg(long, default_value = "24", global = true, value_parser = clap::value_parser!(u32).range(1..))]
+    caption_size: u32,
+
+    #[arg(long, default_value = "black", global = true, value_parser = parse_color)]
+    caption_color: Color,
+
+    /// A box drawn behind the caption so it can be read over busy images
+    #[arg(long, default_value = "transparent", global = true, value_parser = parse_color)]
+    caption_background: Color,
+
+    /// Which corner or edge of the image the caption goes in
+    #[arg(long, default_value = "bottom-left", global = true)]
+    caption_position: Placement,
+
+    /// Space added around the image, in pixels
+    #[arg(long, default_value = "0", global = true)]
+    padding: u32,
+
+    #[arg(long, default_value = "white", global = true, value_parser = parse_color)]
+    padding_color: Color,
+
+    /// The width of a border drawn around the image and its padding, in pixels
+    #[arg(long, default_value = "0", global = true)]
+    border: u32,
+
+    #[arg(long, default_value = "black", global = true, value_parser = parse_color)]
+    border_color: Color,
+
+    /// Adds a soft drop shadow below and to the right of the image
+    #[arg(long, global = true)]
+    shadow: bool,
+}
+
+impl PostArgs {
+    /// Whether any effect changes the image
+    pub fn has_effects(&self) -> bool {
+        self.crop.is_some()
+            || self.resize.is_some()
+            || self.kaleidoscope.is_some()
+            || self.transform.is_some()
+            || self.little_planet
+            || !self.get_color_adjustments().is_identity()
+            || self.pixel_sort.is_some()
+            || self.stereogram
+            || !matches!(self.style, ImageStyle::Normal)
+            || self.glow.is_some()
+            || self.channel_offset > 0
+            || self.glitch > 0.0
+            || self.crt
+            || self.vignette > 0.0
+            || self.grain > 0.0
+            || self.monochrome.is_some()
+            || self.caption.is_some()
+            || self.padding > 0
+            || self.border > 0
+            || self.shadow
+    }
+
+    /// The seed options of the random effects that are on without a seed given, which
+    /// the command line picks so the image can be made the same again
+    pub fn get_missing_seeds(&self) -> Vec<&'static str> {
+        [
+            (self.stereogram, self.stereogram_seed, "stereogram-seed"),
+            (self.glitch > 0.0, self.glitch_seed, "glitch-seed"),
+            (self.grain > 0.0, self.grain_seed, "grain-seed"),
+        ]
+        .into_iter()
+        .filter(|(is_on, seed, _)| *is_on && seed.is_none())
+        .map(|(_, _, name)| name)
+        .collect()
+    }
+
+    fn get_color_adjustments(&self) -> adjust::ColorAdjustments {
+        adjust::ColorAdjustments {
+            invert: self.invert,
+            hue_rotate: self.hue_rotate,
+            saturate: self.saturate,
+            gamma: self.gamma,
+        }
+    }
+}
+
+/// Applies the effects then the framing, which fails if the effects' options don't
+/// fit the image, like a crop off its edge
+pub fn apply_post_processing(image: RgbaImage, post_args: &PostArgs) -> Result<RgbaImage, String> {
+    Ok(apply_framing(
+        apply_post_effects(image, post_args)?,
+        post_args,
+    ))
+}
+
+/// Parses a size given as widthxheight, both at least 1
+fn parse_dimensions(value: &str) -> Result<(u32, u32), String> {
+    let (width, height) = value
+        .split_once(['x', 'X'])
+        .ok_or_else(|| "expected widthxheight like 800x600".to_string())?;
+    let parse_part = |part: &str| match part.trim().parse::<u32>() {
+        Ok(0) => Err("must be at least 1".to_string()),
+        Ok(part) => Ok(part),
+        Err(error) => Err(error.to_string()),
+    };
+
+    Ok((parse_part(width)?, parse_part(height)?))
+}
+
+fn parse_non_negative(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(number) if number >= 0.0 => Ok(number),
+        Ok(_) => Err("must not be negative".to_string()),
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+fn parse_positive(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(number) if number > 0.0 => Ok(number),
+        Ok(_) => Err("must be more than 0".to_string()),
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+/// Parses a field of view in degrees, which can't go all the way round
+fn parse_field_of_view(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(degrees) if degrees > 0.0 && degrees < 360.0 => Ok(degrees),
+        Ok(_) => Err("must be more than 0 and less than 360".to_string()),
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+/// Parses a rectangle given as x,y,width,height
+fn parse_rectangle(value: &str) -> Result<Tile, String> {
+    let parts = value
+        .split(',')
+        .map(|part| {
+            part.trim()
+                .parse::<u32>()
+                .map_err(|error| error.to_string())
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let [x, y, width, height] = parts[..] else {
+        return Err("expected x,y,width,height like 0,0,400,300".to_string());
+    };
+    if width == 0 || height == 0 {
+        return Err("the width and height must be at least 1".to_string());
+    }
+
+    Ok(Tile {
+        x,
+        y,
+        width,
+        height,
+    })
+}
+
+/// Applies the effects which change what's in the image, leaving out the framing
+pub fn apply_post_effects(image: RgbaImage, post_args: &PostArgs) -> Result<RgbaImage, String> {
+    // the size is sorted out first so the other effects work at the final size
+    let image = match post_args.crop {
+        Some(rectangle) => resize::crop(&image, rectangle)?,
+        None => image,
+    };
+    let image = match post_args.resize {
+        Some(size) => resize::resize(&image, size, post_args.fit),
+        None => image,
+    };
+    let image = match post_args.kaleidoscope {
+        Some(segment_num) => kaleidoscope::apply_kaleidoscope(&image, segment_num),
+        None => image,
+    };
+    let image = match post_args.transform {
+        Some(transform) => transform::apply_transform(&image, transform),
+        None => image,
+    };
+    let image = match post_args.little_planet {
+        true => little_planet::apply_little_planet(&image, post_args.planet_fov as f32),
+        false => image,
+    };
+
+    let mut image = image;
+    let color_adjustments = post_args.get_color_adjustments();
+    if !color_adjustments.is_identity() {
+        adjust::adjust_colors(&mut image, color_adjustments);
+    }
+
+    if let Some(direction) = post_args.pixel_sort {
+        let [low, high] = post_args.sort_threshold[..] else {
+            unreachable!("the sort threshold always has two values")
+        };
+        pixel_sort::apply_pixel_sort(&mut image, direction, post_args.sort_by, (low, high));
+    }
+
+    // uses the adjusted brightness, so inverting the image flips which parts come out
+    if post_args.stereogram {
+        image = stereogram::apply_stereogram(
+            &image,
+            post_args.eye_separation,
+            post_args.depth_scale,
+            post_args.stereogram_seed,
+        );
+    }
+
+    let mut image = match post_args.style {
+        ImageStyle::Normal => image,
+        ImageStyle::Halftone => halftone::apply_halftone(
+            &image,
+            post_args.halftone_ink,
+            post_args.halftone_cell_size,
+            &post_args.halftone_color,
+        ),
+    };
+
+    if let Some([radius, intensity]) = post_args.glow.as_deref() {
+        image = glow::add_glow(&image, *radius as f32, *intensity as f32);
+    }
+
+    if post_args.channel_offset > 0 {
+        image = glitch::offset_channels(&image, post_args.channel_offset);
+    }
+    if post_args.glitch > 0.0 {
+        glitch::add_glitches(&mut image, post_args.glitch, post_args.glitch_seed);
+    }
+
+    if post_args.crt {
+        image = crt::apply_crt(&image, post_args.crt_curvature as f32);
+    }
+
+    if post_args.vignette > 0.0 {
+        film::add_vignette(&mut image, post_args.vignette);
+    }
+    if post_args.grain > 0.0 {
+        film::add_grain(&mut image, post_args.grain, post_args.grain_seed);
+    }
+
+    // goes after everything that changes the colours so the output stays black and white
+    if let Some(method) = post_args.monochrome {
+        monochrome::apply_monochrome(&mut image, method);
+    }
+
+    // the caption goes on last so the other effects don't change it
+    if let Some(caption) = &post_args.caption {
+        caption::draw_caption(
+            &mut image,
+            caption,
+            post_args.caption_size,
+            &post_args.caption_color,
+            &post_args.caption_background,
+            post_args.caption_position,
+        );
+    }
+
+    Ok(image)
+}
+
+/// Adds the padding, border and shadow around the image, which should be the last thing
+/// done to it so nothing gets drawn over the frame
+pub fn apply_framing(image: RgbaImage, post_args: &PostArgs) -> RgbaImage {
+    let mut image = image;
+    if post_args.padding > 0 {
+        image = frame::add_edge(&image, post_args.padding, &post_args.padding_color);
+    }
+    if post_args.border > 0 {
+        image = frame::add_edge(&image, post_args.border, &post_args.border_color);
+    }
+    if post_args.shadow {
+        image = frame::add_shadow(&image);
+    }
+
+    image
+}
@@ -0,0 +1,108 @@
+//! Colour adjustments for tuning a palette without regenerating the image
+//!
+//! The hue rotation and saturation use the same matrices as the CSS filters of the
+//! same names, see <https://www.w3.org/TR/filter-effects-1/#feColorMatrixElement>
+
+use image::RgbaImage;
+
+/// How the colours of the image get changed, applied in the order of the fields
+#[derive(Clone, Copy, Debug)]
+pub struct ColorAdjustments {
+    pub invert: bool,
+    /// Degrees to turn the hue around the colour wheel
+    pub hue_rotate: f64,
+    /// 0 is greyscale, 1 leaves the image as it is and above 1 is more colourful
+    pub saturate: f64,
+    /// Above 1 brightens the midtones and below 1 darkens them
+    pub gamma: f64,
+}
+
+impl ColorAdjustments {
+    /// Whether the adjustments leave the image the same
+    pub fn is_identity(&self) -> bool {
+        !self.invert && self.hue_rotate % 360.0 == 0.0 && self.saturate == 1.0 && self.gamma == 1.0
+    }
+}
+
+pub fn adjust_colors(image: &mut RgbaImage, adjustments: ColorAdjustments) {
+    let matrix = multiply(
+        get_saturate_matrix(adjustments.saturate),
+        get_hue_rotate_matrix(adjustments.hue_rotate),
+    );
+    // the gamma only depends on the channel value so it can be worked out up front
+    let gamma_table: [u8; 256] = std::array::from_fn(|value| {
+        let value = (value as f64 / 255.0).powf(1.0 / adjustments.gamma);
+        (value * 255.0).round() as u8
+    });
+
+    for pixel in image.pixels_mut() {
+        let mut color = [0.0; 3];
+        for (channel, value) in color.iter_mut().enumerate() {
+            *value = pixel[channel] as f64 / 255.0;
+            if adjustments.invert {
+                *value = 1.0 - *value;
+            }
+        }
+        for channel in 0..3 {
+            let value = (0..3)
+                .map(|index| matrix[channel][index] * color[index])
+                .sum::<f64>();
+            let value = (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+            pixel[channel] = gamma_table[value as usize];
+        }
+    }
+}
+
+type Matrix = [[f64; 3]; 3];
+
+fn get_hue_rotate_matrix(degrees: f64) -> Matrix {
+    let (sin, cos) = degrees.to_radians().sin_cos();
+
+    [
+        [
+            0.213 + cos * 0.787 - sin * 0.213,
+            0.715 - cos * 0.715 - sin * 0.715,
+            0.072 - cos * 0.072 + sin * 0.928,
+        ],
+        [
+            0.213 - cos * 0.213 + sin * 0.143,
+            0.715 + cos * 0.285 + sin * 0.140,
+            0.072 - cos * 0.072 - sin * 0.283,
+        ],
+        [
+            0.213 - cos * 0.213 - sin * 0.787,
+            0.715 - cos * 0.715 + sin * 0.715,
+            0.072 + cos * 0.928 + sin * 0.072,
+        ],
+    ]
+}
+
+fn get_saturate_matrix(amount: f64) -> Matrix {
+    [
+        [
+            0.213 + 0.787 * amount,
+            0.715 - 0.715 * amount,
+            0.072 - 0.072 * amount,
+        ],
+        [
+            0.213 - 0.213 * amount,
+            0.715 + 0.285 * amount,
+            0.072 - 0.072 * amount,
+        ],
+        [
+            0.213 - 0.213 * amount,
+            0.715 - 0.715 * amount,
+            0.072 + 0.928 * amount,
+        ],
+    ]
+}
+
+fn multiply(first: Matrix, second: Matrix) -> Matrix {
+    std::array::from_fn(|row| {
+        std::array::from_fn(|column| {
+            (0..3)
+                .map(|index| first[row][index] * second[index][column])
+                .sum()
+        })
+    })
+}
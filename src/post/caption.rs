@@ -0,0 +1,87 @@
+//! Text drawn over the image, with each line of the caption lined up on the side of the
+//! image it's placed on
+
+use csscolorparser::Color;
+use image::{Rgba, RgbaImage};
+
+use crate::{
+    BlendMode, Placement,
+    compose::{blend_over, get_placement_offset},
+    text::{draw_text_on_image, get_text_size},
+};
+
+/// Draws the caption in the position, a margin of half the font size away from the edges
+pub fn draw_caption(
+    image: &mut RgbaImage,
+    caption: &str,
+    font_size: u32,
+    color: &Color,
+    background_color: &Color,
+    position: Placement,
+) {
+    let lines: Vec<&str> = caption.lines().collect();
+    let font_size = font_size as f32;
+    let line_height = font_size.ceil() as i32;
+    let margin = line_height / 2;
+    let sizes: Vec<(u32, u32)> = lines
+        .iter()
+        .map(|line| get_text_size(line, font_size))
+        .collect();
+    let text_width = sizes.iter().map(|&(width, _)| width).max().unwrap_or(0) as i32;
+    let text_height = line_height * lines.len() as i32;
+
+    let dimensions = image.dimensions();
+    let (width, height) = (dimensions.0 as i32, dimensions.1 as i32);
+    let get_offset = |line_width: i32| {
+        let (left, top) = get_placement_offset(
+            position,
+            dimensions,
+            (line_width as u32, text_height as u32),
+            margin as i64,
+        );
+        (left as i32, top as i32)
+    };
+    let top = get_offset(text_width).1;
+    let get_left = |line_width: i32| get_offset(line_width).0;
+
+    let background_color = background_color.to_rgba8();
+    if background_color[3] > 0 {
+        // the box spreads into the margin a bit so the text isn't right at its edge
+        let padding = margin / 2;
+        let left = get_left(text_width) - padding;
+        let (x_range, y_range) = (
+            left.max(0)..(left + text_width + padding * 2).min(width),
+            (top - padding).max(0)..(top + text_height + padding).min(height),
+        );
+        for y in y_range {
+            for x in x_range.clone() {
+                let pixel = image.get_pixel_mut(x as u32, y as u32);
+                *pixel = Rgba(blend_over(
+                    pixel.0,
+                    background_color,
+                    1.0,
+                    BlendMode::Normal,
+                ));
+            }
+        }
+    }
+
+    let color = Rgba(color.to_rgba8());
+    for (index, (line, (line_width, _))) in lines.iter().zip(sizes).enumerate() {
+        let left = match position {
+            // lines on the left and right are lined up with each other rather than centred
+            Placement::TopLeft | Placement::BottomLeft => get_left(text_width),
+            Placement::TopRight | Placement::BottomRight => {
+                get_left(text_width) + text_width - line_width as i32
+            }
+            _ => get_left(line_width as i32),
+        };
+        draw_text_on_image(
+            image,
+            line,
+            (left, top + line_height * index as i32),
+            font_size,
+            color,
+        );
+    }
+}
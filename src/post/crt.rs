@@ -0,0 +1,85 @@
+//! Makes the image look like it's on an old CRT monitor
+//!
+//! The picture bulges out like the curved glass at the front of the tube, with black
+//! where it curves away past the edges. Every few rows there's a dark gap between the
+//! scanlines, and each column only lets through mostly red, green or blue like the
+//! stripes of phosphor on the screen. The bright parts then bloom over the gaps.
+//! See <https://en.wikipedia.org/wiki/Cathode-ray_tube> for more info
+
+use image::{Rgba, RgbaImage};
+
+use super::{glow::add_glow, sample::sample_bilinear};
+
+/// How many rows each scanline and the gap after it take up
+const SCANLINE_PERIOD: u32 = 3;
+/// How bright the gap between the scanlines is
+const SCANLINE_GAP_BRIGHTNESS: f32 = 0.55;
+/// How bright the other two channels are in each column of the phosphor mask
+const MASK_BRIGHTNESS: f32 = 0.7;
+
+/// Gets where a point on the screen comes from in the image, both going from -1 to 1
+/// across it, with the middle bulging out more the bigger the curvature
+fn get_barrel_source((x, y): (f32, f32), curvature: f32) -> (f32, f32) {
+    let stretch = 1.0 + curvature * (x * x + y * y);
+    (x * stretch, y * stretch)
+}
+
+/// Makes the image look like it's on a CRT, with the curvature being how much the
+/// glass bulges out, 0 for flat
+pub fn apply_crt(image: &RgbaImage, curvature: f32) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let screen = RgbaImage::from_fn(width, height, |x, y| {
+        let across = (2.0 * x as f32 + 1.0) / width as f32 - 1.0;
+        let down = (2.0 * y as f32 + 1.0) / height as f32 - 1.0;
+        let (source_x, source_y) = get_barrel_source((across, down), curvature);
+        if source_x.abs() > 1.0 || source_y.abs() > 1.0 {
+            return Rgba([0, 0, 0, 255]);
+        }
+
+        let pixel = sample_bilinear(
+            image,
+            ((source_x + 1.0) * width as f32 - 1.0) / 2.0,
+            ((source_y + 1.0) * height as f32 - 1.0) / 2.0,
+        );
+        let scanline = match y % SCANLINE_PERIOD == SCANLINE_PERIOD - 1 {
+            true => SCANLINE_GAP_BRIGHTNESS,
+            false => 1.0,
+        };
+        // the column's own colour comes through fully and the other two are dimmed
+        let lit = (x % 3) as usize;
+        let channel = |index: usize| {
+            let mask = match index == lit {
+                true => 1.0,
+                false => MASK_BRIGHTNESS,
+            };
+            (pixel[index] as f32 * scanline * mask).round() as u8
+        };
+
+        Rgba([channel(0), channel(1), channel(2), pixel[3]])
+    });
+
+    // the glow is a little bigger on bigger images so it still covers the gaps
+    let radius = (height as f32 / 300.0).max(1.5);
+    add_glow(&screen, radius, 0.8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crt() {
+        // the middle stays put and the edges get pushed out
+        assert_eq!(get_barrel_source((0.0, 0.0), 0.2), (0.0, 0.0));
+        let (x, y) = get_barrel_source((1.0, 0.0), 0.2);
+        assert!((x - 1.2).abs() < 1e-6 && y == 0.0);
+
+        let image = RgbaImage::from_pixel(30, 30, Rgba([100, 100, 100, 255]));
+        let crt = apply_crt(&image, 0.1);
+        // black round the edges where the glass curves away
+        assert_eq!(crt[(0, 15)], Rgba([0, 0, 0, 255]));
+        // the scanline gaps are darker, and each column is mostly one colour
+        assert!(crt[(15, 14)][0] < crt[(15, 13)][0]);
+        assert!(crt[(15, 13)][0] > crt[(15, 13)][1]);
+    }
+}
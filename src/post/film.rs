@@ -0,0 +1,61 @@
+//! Effects that make the image look like it was taken on an old camera
+
+use image::RgbaImage;
+
+/// Darkens the image towards the corners, from nothing at a strength of 0 to black
+/// corners at 1
+pub fn add_vignette(image: &mut RgbaImage, strength: f64) {
+    let (width, height) = image.dimensions();
+    let centre = (width as f64 / 2.0, height as f64 / 2.0);
+    let corner_distance = centre.0.hypot(centre.1);
+
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        let distance = (x as f64 + 0.5 - centre.0).hypot(y as f64 + 0.5 - centre.1);
+        // the middle is left alone, then it fades smoothly down to the corners
+        let amount = ((distance / corner_distance - 0.3) / 0.7).clamp(0.0, 1.0);
+        let amount = amount * amount * (3.0 - 2.0 * amount);
+        let brightness = 1.0 - strength * amount;
+        for channel in 0..3 {
+            pixel[channel] = (pixel[channel] as f64 * brightness).round() as u8;
+        }
+    }
+}
+
+/// Adds random noise to the brightness of each pixel, with the amount from 0 to 1 being
+/// the most it can change by
+pub fn add_grain(image: &mut RgbaImage, amount: f64, seed: Option<u64>) {
+    let mut rng = match seed {
+        Some(seed) => fastrand::Rng::with_seed(seed),
+        None => fastrand::Rng::new(),
+    };
+
+    for pixel in image.pixels_mut() {
+        // the average of two gives noise that's mostly small, which looks more like film
+        let noise = (rng.f64() + rng.f64() - 1.0) * amount * 255.0;
+        for channel in 0..3 {
+            pixel[channel] = (pixel[channel] as f64 + noise).clamp(0.0, 255.0).round() as u8;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_film_effects() {
+        let image = RgbaImage::from_pixel(20, 20, image::Rgba([128, 128, 128, 255]));
+
+        let mut vignetted = image.clone();
+        add_vignette(&mut vignetted, 1.0);
+        assert_eq!(vignetted[(10, 10)], image[(10, 10)]);
+        assert!(vignetted[(0, 0)][0] < 10);
+
+        let mut grainy = image.clone();
+        let mut same_grainy = image.clone();
+        add_grain(&mut grainy, 0.2, Some(7));
+        add_grain(&mut same_grainy, 0.2, Some(7));
+        assert_eq!(grainy, same_grainy);
+        assert_ne!(grainy, image);
+    }
+}
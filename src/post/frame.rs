@@ -0,0 +1,62 @@
+//! Framing the image for showing it off, with padding, a border and a drop shadow
+
+use csscolorparser::Color;
+use image::{GrayImage, ImageBuffer, Luma, Rgba, RgbaImage, imageops};
+
+use crate::{
+    BlendMode,
+    compose::{Layer, compose_layers},
+};
+
+/// How dark the shadow is where the image is solid
+const SHADOW_OPACITY: f64 = 0.5;
+
+/// Puts the image in the middle of a bigger image of the colour, which shows through
+/// any see-through parts of the image
+pub fn add_edge(image: &RgbaImage, size: u32, color: &Color) -> RgbaImage {
+    compose_layers(
+        image.width() + size * 2,
+        image.height() + size * 2,
+        color,
+        &[Layer::new(
+            image.clone(),
+            1.0,
+            (size as i64, size as i64),
+            BlendMode::Normal,
+        )],
+    )
+}
+
+/// Adds a blurred shadow in the shape of the image, below and to the right of it
+pub fn add_shadow(image: &RgbaImage) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let offset = (width.min(height) / 40).max(2);
+    // room for the blur to fade out on every side
+    let spread = offset * 2;
+    let shadow_width = width + spread * 2 + offset;
+    let shadow_height = height + spread * 2 + offset;
+
+    let mut mask = GrayImage::new(shadow_width, shadow_height);
+    for (x, y, pixel) in image.enumerate_pixels() {
+        mask.put_pixel(x + spread + offset, y + spread + offset, Luma([pixel[3]]));
+    }
+    let mask = imageops::blur(&mask, offset as f32);
+    let shadow = ImageBuffer::from_fn(shadow_width, shadow_height, |x, y| {
+        Rgba([0, 0, 0, mask[(x, y)][0]])
+    });
+
+    compose_layers(
+        shadow_width,
+        shadow_height,
+        &Color::new(0.0, 0.0, 0.0, 0.0),
+        &[
+            Layer::new(shadow, SHADOW_OPACITY, (0, 0), BlendMode::Normal),
+            Layer::new(
+                image.clone(),
+                1.0,
+                (spread as i64, spread as i64),
+                BlendMode::Normal,
+            ),
+        ],
+    )
+}
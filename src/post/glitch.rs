@@ -0,0 +1,130 @@
+//! Effects that make the image look like it got corrupted on the way
+//!
+//! The colour channels can be split apart sideways like a cheap lens, bands of rows get
+//! knocked sideways like a bad video signal, and square blocks get flattened or smeared
+//! like a JPEG with missing data. See <https://en.wikipedia.org/wiki/Glitch_art> for
+//! more info
+
+use image::{Rgba, RgbaImage};
+
+/// The size of the blocks that get corrupted, the same as the blocks in a JPEG
+const BLOCK_SIZE: u32 = 8;
+
+/// Moves the red channel left and the blue channel right by the offset in pixels,
+/// leaving green where it is
+pub fn offset_channels(image: &RgbaImage, offset: u32) -> RgbaImage {
+    let width = image.width() as i64;
+    let get_x = |x: u32, shift: i64| (x as i64 + shift).clamp(0, width - 1) as u32;
+
+    RgbaImage::from_fn(image.width(), image.height(), |x, y| {
+        let [_, green, _, alpha] = image.get_pixel(x, y).0;
+        let red = image.get_pixel(get_x(x, offset as i64), y)[0];
+        let blue = image.get_pixel(get_x(x, -(offset as i64)), y)[2];
+        Rgba([red, green, blue, alpha])
+    })
+}
+
+/// Shifts random bands of rows sideways, wrapping round, with more and bigger bands as
+/// the amount goes from 0 to 1
+pub fn displace_rows(image: &mut RgbaImage, amount: f64, rng: &mut fastrand::Rng) {
+    let (width, height) = image.dimensions();
+    let band_num = (amount * 12.0).ceil() as u32;
+    let max_shift = ((amount * width as f64 / 4.0) as u32).max(1);
+
+    for _ in 0..band_num {
+        let band_height = rng.u32(1..=(height / 16).max(1));
+        let top = rng.u32(0..height);
+        let shift = rng.u32(1..=max_shift);
+        let shift = match rng.bool() {
+            true => shift,
+            false => width - shift % width,
+        };
+        for y in top..(top + band_height).min(height) {
+            let row: Vec<Rgba<u8>> = (0..width).map(|x| *image.get_pixel(x, y)).collect();
+            for (x, pixel) in row.into_iter().enumerate() {
+                image.put_pixel((x as u32 + shift) % width, y, pixel);
+            }
+        }
+    }
+}
+
+/// Corrupts random blocks, either flattening them to their average colour with one
+/// channel blown out or smearing the block to their left over them, with more blocks
+/// corrupted as the amount goes from 0 to 1
+pub fn corrupt_blocks(image: &mut RgbaImage, amount: f64, rng: &mut fastrand::Rng) {
+    let (width, height) = image.dimensions();
+    let chance = amount * 0.15;
+
+    for top in (0..height).step_by(BLOCK_SIZE as usize) {
+        for left in (0..width).step_by(BLOCK_SIZE as usize) {
+            if rng.f64() >= chance {
+                continue;
+            }
+            let (right, bottom) = (
+                (left + BLOCK_SIZE).min(width),
+                (top + BLOCK_SIZE).min(height),
+            );
+            let positions = (top..bottom).flat_map(|y| (left..right).map(move |x| (x, y)));
+
+            if rng.bool() || left == 0 {
+                let mut total = [0u64; 4];
+                let mut count = 0;
+                for (x, y) in positions.clone() {
+                    for (sum, channel) in total.iter_mut().zip(image.get_pixel(x, y).0) {
+                        *sum += channel as u64;
+                    }
+                    count += 1;
+                }
+                let mut average = total.map(|sum| (sum / count) as u8);
+                average[rng.usize(0..3)] = 255;
+                for (x, y) in positions {
+                    image.put_pixel(x, y, Rgba(average));
+                }
+            } else {
+                for (x, y) in positions {
+                    let pixel = *image.get_pixel(left - 1, y);
+                    image.put_pixel(x, y, pixel);
+                }
+            }
+        }
+    }
+}
+
+/// Adds the row displacement and block corruption, with the amount from 0 to 1 being how
+/// much of each there is
+pub fn add_glitches(image: &mut RgbaImage, amount: f64, seed: Option<u64>) {
+    let mut rng = match seed {
+        Some(seed) => fastrand::Rng::with_seed(seed),
+        None => fastrand::Rng::new(),
+    };
+
+    displace_rows(image, amount, &mut rng);
+    corrupt_blocks(image, amount, &mut rng);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glitch_effects() {
+        let image = RgbaImage::from_fn(20, 20, |x, y| Rgba([x as u8 * 10, y as u8 * 10, 100, 255]));
+
+        let offset = offset_channels(&image, 2);
+        assert_eq!(offset[(5, 5)], Rgba([70, 50, 100, 255]));
+        // the edges stretch out rather than going missing
+        assert_eq!(offset[(19, 0)][0], 190);
+
+        let mut glitched = image.clone();
+        let mut same_glitched = image.clone();
+        add_glitches(&mut glitched, 1.0, Some(4));
+        add_glitches(&mut same_glitched, 1.0, Some(4));
+        assert_eq!(glitched, same_glitched);
+        assert_ne!(glitched, image);
+
+        // nothing happens without any glitching
+        let mut untouched = image.clone();
+        add_glitches(&mut untouched, 0.0, Some(4));
+        assert_eq!(untouched, image);
+    }
+}
@@ -0,0 +1,41 @@
+//! Bloom, making the bright parts of the image glow like they do through a camera lens
+
+use image::{ImageBuffer, Rgba, RgbaImage, imageops};
+
+/// How bright a colour has to be before it starts to glow, from 0 to 1
+const GLOW_THRESHOLD: f32 = 0.5;
+
+/// Takes the bright pixels, blurs them by the radius and adds them back on top, which
+/// lights up dark and transparent areas next to them
+pub fn add_glow(image: &RgbaImage, radius: f32, intensity: f32) -> RgbaImage {
+    // the glow is kept premultiplied by alpha so transparent pixels don't add any colour
+    let bright: ImageBuffer<Rgba<f32>, Vec<f32>> =
+        ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+            let [red, green, blue, alpha] = image[(x, y)].0.map(|value| value as f32 / 255.0);
+            let luminance = 0.2126 * red + 0.7152 * green + 0.0722 * blue;
+            // ramps up from the threshold rather than cutting in, so there's no hard edge
+            let amount = ((luminance - GLOW_THRESHOLD) / (1.0 - GLOW_THRESHOLD)).max(0.0) * alpha;
+            Rgba([red * amount, green * amount, blue * amount, amount])
+        });
+    let glow = imageops::fast_blur(&bright, radius);
+
+    ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+        let [red, green, blue, alpha] = image[(x, y)].0.map(|value| value as f32 / 255.0);
+        let glow = glow[(x, y)].0.map(|value| value * intensity);
+        let new_alpha = (alpha + glow[3] * (1.0 - alpha)).min(1.0);
+        if new_alpha == 0.0 {
+            return Rgba([0, 0, 0, 0]);
+        }
+        let channel = |value: f32, glow: f32| {
+            let value = ((value * alpha + glow) / new_alpha).min(1.0);
+            (value * 255.0).round() as u8
+        };
+
+        Rgba([
+            channel(red, glow[0]),
+            channel(green, glow[1]),
+            channel(blue, glow[2]),
+            (new_alpha * 255.0).round() as u8,
+        ])
+    })
+}
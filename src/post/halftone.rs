@@ -0,0 +1,166 @@
+//! Halftone printing, redraws the image as a grid of dots which are bigger where the
+//! image is darker, see <https://en.wikipedia.org/wiki/Halftone>
+//!
+//! For CMYK each ink gets its own grid at a different angle, like in printing, so the
+//! dots don't line up into moiré patterns
+
+use std::f64;
+
+use csscolorparser::Color;
+use image::{GrayImage, Luma, Rgba, RgbaImage};
+use imageproc::drawing::draw_filled_circle_mut;
+
+use crate::HalftoneInk;
+
+/// An ink layer, with the angle of its grid and its colour
+struct Ink {
+    angle_degrees: f64,
+    color: [u8; 4],
+}
+
+/// The usual screen angles for CMYK printing
+const CMYK_INKS: [Ink; 4] = [
+    Ink {
+        angle_degrees: 15.0,
+        color: [0, 255, 255, 255],
+    },
+    Ink {
+        angle_degrees: 75.0,
+        color: [255, 0, 255, 255],
+    },
+    Ink {
+        angle_degrees: 0.0,
+        color: [255, 255, 0, 255],
+    },
+    Ink {
+        angle_degrees: 45.0,
+        color: [0, 0, 0, 255],
+    },
+];
+
+pub fn apply_halftone(
+    image: &RgbaImage,
+    ink_type: HalftoneInk,
+    cell_size: u32,
+    single_ink_color: &Color,
+) -> RgbaImage {
+    let cell_size = cell_size.max(2);
+    let layers: Vec<(GrayImage, [u8; 4])> = match ink_type {
+        HalftoneInk::Cmyk => CMYK_INKS
+            .iter()
+            .enumerate()
+            .map(|(channel, ink)| {
+                let layer = draw_ink_layer(image, ink.angle_degrees, cell_size, |pixel| {
+                    get_cmyk(pixel)[channel]
+                });
+                (layer, ink.color)
+            })
+            .collect(),
+        HalftoneInk::Single => {
+            let layer = draw_ink_layer(image, 45.0, cell_size, get_darkness);
+            vec![(layer, single_ink_color.to_rgba8())]
+        }
+    };
+
+    // the paper starts white and each ink multiplies it where its dots are
+    let mut output = RgbaImage::from_pixel(image.width(), image.height(), Rgba([255; 4]));
+    for (x, y, pixel) in output.enumerate_pixels_mut() {
+        for (layer, ink_color) in layers.iter() {
+            if layer[(x, y)].0[0] > 0 {
+                for (value, ink_value) in pixel.0.iter_mut().zip(ink_color).take(3) {
+                    *value = ((*value as u32 * *ink_value as u32) / 255) as u8;
+                }
+            }
+        }
+    }
+
+    output
+}
+
+/// Draws the dots for a single ink, the amount function gives how much ink a pixel
+/// needs from 0 to 1
+fn draw_ink_layer<F>(image: &RgbaImage, angle_degrees: f64, cell_size: u32, amount: F) -> GrayImage
+where
+    F: Fn(Rgba<u8>) -> f64,
+{
+    let (width, height) = image.dimensions();
+    let mut layer = GrayImage::new(width, height);
+
+    let angle = angle_degrees.to_radians();
+    let (sin, cos) = angle.sin_cos();
+    let cell = cell_size as f64;
+
+    // the rotated grid needs to cover the whole image, the diagonal is always enough
+    let diagonal = f64::hypot(width as f64, height as f64);
+    let cell_num = (diagonal / cell).ceil() as i32 + 1;
+    for grid_v in -cell_num..=cell_num {
+        for grid_u in -cell_num..=cell_num {
+            let u = (grid_u as f64 + 0.5) * cell;
+            let v = (grid_v as f64 + 0.5) * cell;
+            // rotate the grid position back to image coordinates
+            let x = u * cos - v * sin;
+            let y = u * sin + v * cos;
+            if x < -cell || y < -cell || x > width as f64 + cell || y > height as f64 + cell {
+                continue;
+            }
+
+            let average = get_average_amount(image, (x, y), cell_size, &amount);
+            // the area of the dot matches the amount of ink needed in the cell
+            let radius = cell * (average / f64::consts::PI).sqrt();
+            if radius >= 0.5 {
+                draw_filled_circle_mut(
+                    &mut layer,
+                    (x.round() as i32, y.round() as i32),
+                    radius.round() as i32,
+                    Luma([255]),
+                );
+            }
+        }
+    }
+
+    layer
+}
+
+/// Averages the amount over the cell around the centre, clamped to the image
+fn get_average_amount<F>(image: &RgbaImage, centre: (f64, f64), cell_size: u32, amount: &F) -> f64
+where
+    F: Fn(Rgba<u8>) -> f64,
+{
+    let (width, height) = image.dimensions();
+    let half = cell_size as f64 / 2.0;
+    let clamp_x = |x: f64| x.clamp(0.0, (width - 1) as f64) as u32;
+    let clamp_y = |y: f64| y.clamp(0.0, (height - 1) as f64) as u32;
+
+    let mut total = 0.0;
+    let mut count = 0;
+    for y in clamp_y(centre.1 - half)..=clamp_y(centre.1 + half) {
+        for x in clamp_x(centre.0 - half)..=clamp_x(centre.0 + half) {
+            total += amount(image[(x, y)]);
+            count += 1;
+        }
+    }
+
+    total / count as f64
+}
+
+/// How dark the pixel is from 0 to 1, transparent pixels are treated as the paper
+fn get_darkness(pixel: Rgba<u8>) -> f64 {
+    let [r, g, b, a] = pixel.0;
+    let luminance = (0.2126 * r as f64 + 0.7152 * g as f64 + 0.0722 * b as f64) / 255.0;
+    (1.0 - luminance) * a as f64 / 255.0
+}
+
+/// Converts the pixel to amounts of cyan, magenta, yellow and black ink
+/// <https://en.wikipedia.org/wiki/CMYK_color_model#Conversion>
+fn get_cmyk(pixel: Rgba<u8>) -> [f64; 4] {
+    let [r, g, b, a] = pixel.0;
+    let alpha = a as f64 / 255.0;
+    let (r, g, b) = (r as f64 / 255.0, g as f64 / 255.0, b as f64 / 255.0);
+
+    let black = 1.0 - r.max(g).max(b);
+    if black >= 1.0 {
+        return [0.0, 0.0, 0.0, alpha];
+    }
+    let ink = |channel: f64| (1.0 - channel - black) / (1.0 - black) * alpha;
+    [ink(r), ink(g), ink(b), black * alpha]
+}
@@ -0,0 +1,59 @@
+//! Kaleidoscope effect, which repeats a wedge of the image around its middle
+//!
+//! The wedge pointing up from the middle is kept, then mirrored back and forth around
+//! the rest of the circle like the mirrors in a kaleidoscope, so the image ends up with
+//! k-fold symmetry. See <https://en.wikipedia.org/wiki/Kaleidoscope> for more info
+
+use std::f32::consts::PI;
+
+use image::{Rgba, RgbaImage};
+use imageproc::geometric_transformations::{Interpolation, warp_with};
+
+/// Gets the angle in the wedge that an angle is mirrored from, with the angles starting
+/// at the top and the wedge going from 0 to π/k
+fn fold_angle(angle: f32, segment_num: u32) -> f32 {
+    let wedge = PI / segment_num as f32;
+    let angle = angle.rem_euclid(2.0 * wedge);
+    match angle > wedge {
+        true => 2.0 * wedge - angle,
+        false => angle,
+    }
+}
+
+/// Repeats the wedge at the top of the image k times around the middle, with every
+/// other copy mirrored so the edges line up
+pub fn apply_kaleidoscope(image: &RgbaImage, segment_num: u32) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let centre = ((width as f32 - 1.0) / 2.0, (height as f32 - 1.0) / 2.0);
+
+    warp_with(
+        image,
+        |x, y| {
+            let (dx, dy) = (x - centre.0, y - centre.1);
+            let radius = dx.hypot(dy);
+            // measured clockwise from straight up
+            let angle = fold_angle(dx.atan2(-dy), segment_num);
+            (
+                centre.0 + radius * angle.sin(),
+                centre.1 - radius * angle.cos(),
+            )
+        },
+        Interpolation::Bilinear,
+        Rgba([0, 0, 0, 0]),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fold_angle() {
+        assert!((fold_angle(0.1, 6) - 0.1).abs() < 1e-6);
+        // just past the first mirror comes back the same amount
+        assert!((fold_angle(PI / 6.0 + 0.1, 6) - (PI / 6.0 - 0.1)).abs() < 1e-5);
+        // a full segment round is the same again, either way round
+        assert!((fold_angle(PI / 3.0 + 0.1, 6) - 0.1).abs() < 1e-5);
+        assert!((fold_angle(-0.1, 6) - 0.1).abs() < 1e-5);
+    }
+}
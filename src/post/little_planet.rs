@@ -0,0 +1,82 @@
+//! Little planet effect, which wraps the image round into a tiny world
+//!
+//! The image is treated as a panorama going all the way round, with the bottom edge as
+//! the ground below and the top edge as the sky above. Looking straight down at it with
+//! a stereographic projection puts the bottom edge in the middle as a tiny planet with
+//! the rest of the image curving round it, and the left and right edges meeting.
+//! See <https://en.wikipedia.org/wiki/Stereographic_projection> for more info
+
+use std::f32::consts::{PI, TAU};
+
+use image::RgbaImage;
+
+use super::sample::sample_bilinear;
+
+/// Gets where in the image a point in the planet comes from, given how far it is from
+/// the middle, with 1 being the middle of the shorter edge, and its angle clockwise from
+/// the top. The field of view is how far the view goes from edge to edge, in degrees
+fn get_source(
+    radius: f32,
+    angle: f32,
+    field_of_view: f32,
+    (width, height): (u32, u32),
+) -> (f32, f32) {
+    // how far round from straight down the point is, which goes up as the tangent of
+    // half of it in a stereographic projection
+    let down_angle = 2.0 * (radius * (field_of_view.to_radians() / 4.0).tan()).atan();
+    let x = (angle / TAU).rem_euclid(1.0) * width as f32;
+    let y = (1.0 - down_angle / PI) * height as f32;
+
+    // measured from the middle of the top left pixel
+    (x - 0.5, y - 0.5)
+}
+
+pub fn apply_little_planet(image: &RgbaImage, field_of_view: f32) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let centre = ((width as f32 - 1.0) / 2.0, (height as f32 - 1.0) / 2.0);
+    let half_size = width.min(height) as f32 / 2.0;
+
+    RgbaImage::from_fn(width, height, |x, y| {
+        let (dx, dy) = (x as f32 - centre.0, y as f32 - centre.1);
+        let (source_x, source_y) = get_source(
+            dx.hypot(dy) / half_size,
+            dx.atan2(-dy),
+            field_of_view,
+            (width, height),
+        );
+        sample_bilinear(image, source_x, source_y)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+
+    #[test]
+    fn test_little_planet_source() {
+        let size = (360, 180);
+        // the middle is the bottom edge
+        let (_, y) = get_source(0.0, 0.0, 180.0, size);
+        assert!((y - 179.5).abs() < 1e-3);
+        // the edge of the view is half the field of view round from straight down, so
+        // with 180 degrees it's the horizon halfway up
+        let (x, y) = get_source(1.0, PI / 2.0, 180.0, size);
+        assert!((y - 89.5).abs() < 1e-3);
+        assert!((x - 89.5).abs() < 1e-3);
+        // and going left wraps round to the right of the image
+        let (x, _) = get_source(1.0, -PI / 2.0, 180.0, size);
+        assert!((x - 269.5).abs() < 1e-3);
+
+        let image = RgbaImage::from_fn(40, 20, |_, y| match y < 10 {
+            true => Rgba([0, 0, 255, 255]),
+            false => Rgba([0, 255, 0, 255]),
+        });
+        let planet = apply_little_planet(&image, 240.0);
+        assert_eq!(planet.dimensions(), (40, 20));
+        // the ground is in the middle with the sky round it
+        assert_eq!(planet[(20, 10)], Rgba([0, 255, 0, 255]));
+        assert_eq!(planet[(0, 0)], Rgba([0, 0, 255, 255]));
+    }
+}
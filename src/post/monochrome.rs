@@ -0,0 +1,201 @@
+//! Reduces the image to pure black and white, like newspaper print
+//!
+//! Each pixel is compared against a threshold to decide whether it's black or white.
+//! With one threshold for everything the greys are lost, so dithering gives every pixel
+//! in a small tile a different threshold, and the tile repeats across the image. More
+//! pixels in the tile come out white the brighter the area is, so the greys show up as
+//! how dense the dots are. The Bayer tile is ordered so the dots form a regular grid,
+//! and the blue noise tile is made with the void and cluster method so the dots are
+//! spread out evenly without any pattern.
+//! See <https://en.wikipedia.org/wiki/Ordered_dithering> for more info
+
+use std::sync::OnceLock;
+
+use image::{Rgba, RgbaImage};
+
+use crate::DitherMethod;
+
+/// How many times the Bayer tile is doubled in size from 2x2, giving an 8x8 tile
+const BAYER_LEVEL_NUM: u32 = 3;
+/// The width and height of the blue noise tile
+const BLUE_NOISE_SIZE: usize = 64;
+/// How spread out the energy of each dot is when making blue noise, in pixels
+const BLUE_NOISE_SIGMA: f64 = 1.5;
+/// Roughly how many of the pixels start off as dots when making blue noise
+const BLUE_NOISE_INITIAL_FRACTION: f64 = 0.1;
+
+fn get_luminance(pixel: Rgba<u8>) -> f64 {
+    let [red, green, blue, _] = pixel.0;
+    (0.2126 * red as f64 + 0.7152 * green as f64 + 0.0722 * blue as f64) / 255.0
+}
+
+/// Gets the rank of a position in the Bayer tile, where each level splits each cell of
+/// the one before into a 2x2 block ordered top left, bottom right, top right, bottom left
+fn get_bayer_rank(x: u32, y: u32, level_num: u32) -> u32 {
+    (0..level_num).fold(0, |rank, level| {
+        let (bit_x, bit_y) = ((x >> level) & 1, (y >> level) & 1);
+        let quadrant = match (bit_x, bit_y) {
+            (0, 0) => 0,
+            (1, 1) => 1,
+            (1, 0) => 2,
+            _ => 3,
+        };
+        rank + (quadrant << (2 * (level_num - 1 - level)))
+    })
+}
+
+/// Gets how much energy a dot gives to each pixel across and down from it, falling off
+/// with the distance wrapping round the edges of the tile
+fn get_energy_kernel() -> Vec<f64> {
+    let get_distance = |offset: usize| offset.min(BLUE_NOISE_SIZE - offset) as f64;
+    (0..BLUE_NOISE_SIZE * BLUE_NOISE_SIZE)
+        .map(|index| {
+            let dx = get_distance(index % BLUE_NOISE_SIZE);
+            let dy = get_distance(index / BLUE_NOISE_SIZE);
+            (-(dx * dx + dy * dy) / (2.0 * BLUE_NOISE_SIGMA * BLUE_NOISE_SIGMA)).exp()
+        })
+        .collect()
+}
+
+/// Adds or takes away the energy of a dot at the index
+fn spread_energy(energy: &mut [f64], kernel: &[f64], index: usize, sign: f64) {
+    let (dot_x, dot_y) = (index % BLUE_NOISE_SIZE, index / BLUE_NOISE_SIZE);
+    for (other, value) in energy.iter_mut().enumerate() {
+        let dx = (other % BLUE_NOISE_SIZE + BLUE_NOISE_SIZE - dot_x) % BLUE_NOISE_SIZE;
+        let dy = (other / BLUE_NOISE_SIZE + BLUE_NOISE_SIZE - dot_y) % BLUE_NOISE_SIZE;
+        *value += sign * kernel[dy * BLUE_NOISE_SIZE + dx];
+    }
+}
+
+/// Finds the dot in the tightest cluster, or the gap in the largest void
+fn find_extreme(energy: &[f64], dots: &[bool], is_dot: bool, is_cluster: bool) -> usize {
+    let candidates = (0..energy.len()).filter(|&index| dots[index] == is_dot);
+    let compare = |a: &usize, b: &usize| energy[*a].total_cmp(&energy[*b]);
+    match is_cluster {
+        true => candidates.max_by(compare),
+        false => candidates.min_by(compare),
+    }
+    .expect("there's always a dot and a gap while making blue noise")
+}
+
+/// Makes a tile of ranks spread out like blue noise with the void and cluster method,
+/// where every group of ranks in a row is spread out evenly over the tile
+fn make_blue_noise() -> Vec<u32> {
+    let pixel_num = BLUE_NOISE_SIZE * BLUE_NOISE_SIZE;
+    let kernel = get_energy_kernel();
+    // the same seed every time, so the pattern doesn't change between frames
+    let mut rng = fastrand::Rng::with_seed(0);
+    let mut dots = vec![false; pixel_num];
+    let mut energy = vec![0.0; pixel_num];
+    for (index, is_dot) in dots.iter_mut().enumerate() {
+        if rng.f64() < BLUE_NOISE_INITIAL_FRACTION {
+            *is_dot = true;
+            spread_energy(&mut energy, &kernel, index, 1.0);
+        }
+    }
+
+    // spreads out the starting dots by moving the tightest cluster into the largest void
+    // until it's already in the largest void
+    loop {
+        let cluster = find_extreme(&energy, &dots, true, true);
+        dots[cluster] = false;
+        spread_energy(&mut energy, &kernel, cluster, -1.0);
+        let void = find_extreme(&energy, &dots, false, false);
+        dots[void] = true;
+        spread_energy(&mut energy, &kernel, void, 1.0);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut ranks = vec![0; pixel_num];
+    let initial_dot_num = dots.iter().filter(|&&is_dot| is_dot).count();
+
+    // the starting dots get the lowest ranks, taking the most clustered away first so
+    // they get the highest of those ranks
+    let (mut removed_dots, mut removed_energy) = (dots.clone(), energy.clone());
+    for rank in (0..initial_dot_num).rev() {
+        let cluster = find_extreme(&removed_energy, &removed_dots, true, true);
+        removed_dots[cluster] = false;
+        spread_energy(&mut removed_energy, &kernel, cluster, -1.0);
+        ranks[cluster] = rank as u32;
+    }
+
+    // then the largest void is filled in each time until the tile is full
+    for rank in initial_dot_num..pixel_num {
+        let void = find_extreme(&energy, &dots, false, false);
+        dots[void] = true;
+        spread_energy(&mut energy, &kernel, void, 1.0);
+        ranks[void] = rank as u32;
+    }
+
+    ranks
+}
+
+fn blue_noise() -> &'static [u32] {
+    static BLUE_NOISE: OnceLock<Vec<u32>> = OnceLock::new();
+    BLUE_NOISE.get_or_init(make_blue_noise)
+}
+
+/// Gets the threshold from 0 to 1 that the brightness of a pixel has to be above to
+/// come out white
+fn get_threshold(x: u32, y: u32, method: DitherMethod) -> f64 {
+    let (rank, rank_num) = match method {
+        DitherMethod::Threshold => return 0.5,
+        DitherMethod::Bayer => {
+            let size = 1 << BAYER_LEVEL_NUM;
+            let rank = get_bayer_rank(x % size, y % size, BAYER_LEVEL_NUM);
+            (rank, size * size)
+        }
+        DitherMethod::BlueNoise => {
+            let size = BLUE_NOISE_SIZE as u32;
+            let index = (y % size) * size + x % size;
+            (blue_noise()[index as usize], size * size)
+        }
+    };
+
+    (rank as f64 + 0.5) / rank_num as f64
+}
+
+/// Turns every pixel black or white, keeping its transparency
+pub fn apply_monochrome(image: &mut RgbaImage, method: DitherMethod) {
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        let value = match get_luminance(*pixel) > get_threshold(x, y, method) {
+            true => 255,
+            false => 0,
+        };
+        *pixel = Rgba([value, value, value, pixel[3]]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dither_thresholds() {
+        // the 2x2 Bayer tile goes top left, bottom right, top right, bottom left
+        let ranks: Vec<u32> = [(0, 0), (1, 1), (1, 0), (0, 1)]
+            .into_iter()
+            .map(|(x, y)| get_bayer_rank(x, y, 1))
+            .collect();
+        assert_eq!(ranks, vec![0, 1, 2, 3]);
+
+        // every rank turns up exactly once in each tile
+        let mut bayer: Vec<u32> = (0..64)
+            .map(|index| get_bayer_rank(index % 8, index / 8, 3))
+            .collect();
+        bayer.sort();
+        assert_eq!(bayer, (0..64).collect::<Vec<_>>());
+        let mut blue_noise = blue_noise().to_vec();
+        blue_noise.sort();
+        assert_eq!(blue_noise, (0..4096).collect::<Vec<_>>());
+
+        // a mid grey comes out as about half white
+        let mut image = RgbaImage::from_pixel(64, 64, Rgba([128, 128, 128, 255]));
+        apply_monochrome(&mut image, DitherMethod::BlueNoise);
+        let white_num = image.pixels().filter(|pixel| pixel[0] == 255).count();
+        assert!((white_num as i64 - 2048).abs() < 64);
+        assert!(image.pixels().all(|pixel| pixel[0] == 0 || pixel[0] == 255));
+    }
+}
@@ -0,0 +1,133 @@
+//! Pixel sorting, a glitch art effect which puts runs of pixels in order
+//!
+//! Each row or column is split into runs of pixels whose brightness is between the
+//! two thresholds, which are then sorted by brightness or hue. The pixels outside the
+//! thresholds stay where they are, so the shapes in the image still show through the
+//! streaks. See <https://satyarth.me/articles/pixel-sorting/> for more info
+
+use image::{Rgba, RgbaImage};
+
+use crate::{SortDirection, SortKey};
+
+fn get_luminance(pixel: Rgba<u8>) -> f64 {
+    let [red, green, blue, _] = pixel.0;
+    (0.2126 * red as f64 + 0.7152 * green as f64 + 0.0722 * blue as f64) / 255.0
+}
+
+/// Gets the hue of a pixel from 0 to 1, with greys counting as red
+fn get_hue(pixel: Rgba<u8>) -> f64 {
+    let [red, green, blue, _] = pixel.0.map(|channel| channel as f64 / 255.0);
+    let highest = red.max(green).max(blue);
+    let range = highest - red.min(green).min(blue);
+    if range == 0.0 {
+        return 0.0;
+    }
+
+    let sixths = match highest {
+        _ if highest == red => ((green - blue) / range).rem_euclid(6.0),
+        _ if highest == green => (blue - red) / range + 2.0,
+        _ => (red - green) / range + 4.0,
+    };
+    sixths / 6.0
+}
+
+/// Sorts the runs of pixels along a line whose brightness is between the thresholds
+fn sort_line(line: &mut [Rgba<u8>], key: SortKey, (low, high): (f64, f64)) {
+    let get_key = |pixel: &Rgba<u8>| match key {
+        SortKey::Luminance => get_luminance(*pixel),
+        SortKey::Hue => get_hue(*pixel),
+    };
+
+    let is_sorted = |pixel: &Rgba<u8>| (low..=high).contains(&get_luminance(*pixel));
+
+    let mut start = 0;
+    while start < line.len() {
+        if !is_sorted(&line[start]) {
+            start += 1;
+            continue;
+        }
+        let end = line[start..]
+            .iter()
+            .position(|pixel| !is_sorted(pixel))
+            .map_or(line.len(), |length| start + length);
+        line[start..end].sort_by(|a, b| get_key(a).total_cmp(&get_key(b)));
+        start = end;
+    }
+}
+
+/// Sorts the runs of pixels along each row or column, with the thresholds being the
+/// brightness from 0 to 1 that pixels have to be between to get sorted
+pub fn apply_pixel_sort(
+    image: &mut RgbaImage,
+    direction: SortDirection,
+    key: SortKey,
+    thresholds: (f64, f64),
+) {
+    let (width, height) = image.dimensions();
+    let (line_num, line_length) = match direction {
+        SortDirection::Horizontal => (height, width),
+        SortDirection::Vertical => (width, height),
+    };
+    let get_position = |line: u32, index: u32| match direction {
+        SortDirection::Horizontal => (index, line),
+        SortDirection::Vertical => (line, index),
+    };
+
+    for line in 0..line_num {
+        let mut pixels: Vec<Rgba<u8>> = (0..line_length)
+            .map(|index| {
+                let (x, y) = get_position(line, index);
+                *image.get_pixel(x, y)
+            })
+            .collect();
+        sort_line(&mut pixels, key, thresholds);
+        for (index, pixel) in pixels.into_iter().enumerate() {
+            let (x, y) = get_position(line, index as u32);
+            image.put_pixel(x, y, pixel);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sort_line() {
+        let grey = |value: u8| Rgba([value, value, value, 255]);
+        // the black pixel in the middle splits it into two runs
+        let mut line = vec![
+            grey(200),
+            grey(100),
+            grey(150),
+            grey(0),
+            grey(180),
+            grey(120),
+        ];
+        sort_line(&mut line, SortKey::Luminance, (0.2, 1.0));
+        assert_eq!(
+            line,
+            vec![
+                grey(100),
+                grey(150),
+                grey(200),
+                grey(0),
+                grey(120),
+                grey(180)
+            ]
+        );
+
+        assert!(get_hue(Rgba([255, 0, 0, 255])) < get_hue(Rgba([0, 255, 0, 255])));
+        assert!(get_hue(Rgba([0, 255, 0, 255])) < get_hue(Rgba([0, 0, 255, 255])));
+
+        let mut image = RgbaImage::from_fn(1, 3, |_, y| grey(200 - y as u8 * 50));
+        apply_pixel_sort(
+            &mut image,
+            SortDirection::Vertical,
+            SortKey::Luminance,
+            (0.0, 1.0),
+        );
+        assert_eq!(image[(0, 0)], grey(100));
+        assert_eq!(image[(0, 2)], grey(200));
+    }
+}
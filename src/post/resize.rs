@@ -0,0 +1,117 @@
+//! Changing the size of the image, by cutting part of it out or scaling it
+
+use image::{RgbaImage, imageops};
+
+use crate::{Fit, tiles::Tile};
+
+const FILTER: imageops::FilterType = imageops::FilterType::CatmullRom;
+
+/// Cuts out the rectangle, which has to fit inside the image
+pub fn crop(image: &RgbaImage, rectangle: Tile) -> Result<RgbaImage, String> {
+    let right = rectangle.x as u64 + rectangle.width as u64;
+    let bottom = rectangle.y as u64 + rectangle.height as u64;
+    if right > image.width() as u64 || bottom > image.height() as u64 {
+        return Err(format!(
+            "the crop {},{},{},{} goes off the edge of the {}x{} image",
+            rectangle.x,
+            rectangle.y,
+            rectangle.width,
+            rectangle.height,
+            image.width(),
+            image.height()
+        ));
+    }
+
+    Ok(imageops::crop_imm(
+        image,
+        rectangle.x,
+        rectangle.y,
+        rectangle.width,
+        rectangle.height,
+    )
+    .to_image())
+}
+
+pub fn resize(image: &RgbaImage, (width, height): (u32, u32), fit: Fit) -> RgbaImage {
+    let width_scale = width as f64 / image.width() as f64;
+    let height_scale = height as f64 / image.height() as f64;
+    let scale_to = |scale: f64| {
+        let scaled_width = ((image.width() as f64 * scale).round() as u32).max(1);
+        let scaled_height = ((image.height() as f64 * scale).round() as u32).max(1);
+        imageops::resize(image, scaled_width, scaled_height, FILTER)
+    };
+
+    match fit {
+        Fit::Stretch => imageops::resize(image, width, height, FILTER),
+        Fit::Contain => {
+            let scaled = scale_to(width_scale.min(height_scale));
+            let mut contained = RgbaImage::new(width, height);
+            imageops::overlay(
+                &mut contained,
+                &scaled,
+                (width as i64 - scaled.width() as i64) / 2,
+                (height as i64 - scaled.height() as i64) / 2,
+            );
+            contained
+        }
+        Fit::Cover => {
+            let scaled = scale_to(width_scale.max(height_scale));
+            imageops::crop_imm(
+                &scaled,
+                scaled.width().saturating_sub(width) / 2,
+                scaled.height().saturating_sub(height) / 2,
+                width,
+                height,
+            )
+            .to_image()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resize_fits() {
+        let image = RgbaImage::from_pixel(200, 100, image::Rgba([255, 0, 0, 255]));
+
+        assert_eq!(
+            resize(&image, (50, 50), Fit::Stretch).dimensions(),
+            (50, 50)
+        );
+
+        let contained = resize(&image, (50, 50), Fit::Contain);
+        assert_eq!(contained.dimensions(), (50, 50));
+        assert_eq!(contained[(25, 0)][3], 0);
+        assert_eq!(contained[(25, 25)][3], 255);
+
+        let covered = resize(&image, (50, 50), Fit::Cover);
+        assert_eq!(covered.dimensions(), (50, 50));
+        assert_eq!(covered[(0, 0)][3], 255);
+
+        let cropped = crop(
+            &image,
+            Tile {
+                x: 150,
+                y: 50,
+                width: 50,
+                height: 50,
+            },
+        )
+        .unwrap();
+        assert_eq!(cropped.dimensions(), (50, 50));
+        // a crop off the edge is refused rather than cut down
+        let error = crop(
+            &image,
+            Tile {
+                x: 150,
+                y: 50,
+                width: 100,
+                height: 100,
+            },
+        )
+        .unwrap_err();
+        assert!(error.contains("200x100"));
+    }
+}
@@ -0,0 +1,34 @@
+//! Reading the image between pixels, for effects that move the pixels around
+
+use image::{Rgba, RgbaImage};
+
+/// Blends the 4 pixels around a point, wrapping round from the right edge to the left
+/// and stopping at the top and bottom edges, for effects that bend the image round so
+/// its left and right edges meet
+pub fn sample_bilinear(image: &RgbaImage, x: f32, y: f32) -> Rgba<u8> {
+    let (width, height) = (image.width() as i64, image.height() as i64);
+    let y = y.clamp(0.0, (height - 1) as f32);
+    let (left, top) = (x.floor(), y.floor());
+    let (x_amount, y_amount) = (x - left, y - top);
+    let get_pixel = |x: i64, y: i64| {
+        image
+            .get_pixel(x.rem_euclid(width) as u32, y.min(height - 1) as u32)
+            .0
+            .map(|channel| channel as f32)
+    };
+
+    let (left, top) = (left as i64, top as i64);
+    let corners = [
+        (get_pixel(left, top), (1.0 - x_amount) * (1.0 - y_amount)),
+        (get_pixel(left + 1, top), x_amount * (1.0 - y_amount)),
+        (get_pixel(left, top + 1), (1.0 - x_amount) * y_amount),
+        (get_pixel(left + 1, top + 1), x_amount * y_amount),
+    ];
+    Rgba(std::array::from_fn(|channel| {
+        corners
+            .iter()
+            .map(|(pixel, weight)| pixel[channel] * weight)
+            .sum::<f32>()
+            .round() as u8
+    }))
+}
@@ -0,0 +1,125 @@
+//! Turns the image into a random dot autostereogram, or magic eye picture
+//!
+//! The brightness of each pixel is used as a height, with bright parts coming out of the
+//! picture. Looking through the image so each eye lines up with a different copy of the
+//! repeating dots makes the heights stand out in 3D, as the dots repeat closer together
+//! where things are nearer. Each pixel is linked with the one it has to match for its
+//! height, then the dots are filled in so linked pixels get the same colour.
+//! See <https://en.wikipedia.org/wiki/Autostereogram> for more info
+
+use image::{Rgba, RgbaImage};
+
+/// Gets how far apart the two pixels the eyes see at a height from 0 to 1 are, with the
+/// depth scale being how far in front of the background the highest parts are as a
+/// fraction of the distance to the viewer
+fn get_separation(height: f64, eye_separation: u32, depth_scale: f64) -> u32 {
+    let depth = depth_scale * height;
+    ((1.0 - depth) * eye_separation as f64 / (2.0 - depth)).round() as u32
+}
+
+/// Links up the pixels along a row which have to be the same colour, each one pointing
+/// to a pixel to its right or to itself if it's free to be any colour
+fn get_links(heights: &[f64], eye_separation: u32, depth_scale: f64) -> Vec<usize> {
+    let width = heights.len();
+    let mut links: Vec<usize> = (0..width).collect();
+    for (x, &height) in heights.iter().enumerate() {
+        let separation = get_separation(height, eye_separation, depth_scale) as usize;
+        let Some(mut left) = x.checked_sub(separation / 2) else {
+            continue;
+        };
+        let mut right = left + separation;
+        if right >= width {
+            continue;
+        }
+
+        // the left pixel might already be linked, in which case the links are kept in
+        // order so each pixel only links to one other
+        let mut linked = links[left];
+        while linked != left && linked != right {
+            if linked < right {
+                left = linked;
+            } else {
+                left = right;
+                right = linked;
+            }
+            linked = links[left];
+        }
+        links[left] = right;
+    }
+
+    links
+}
+
+/// Replaces the image with random dots that show its brightness in 3D, with the eye
+/// separation being how far apart the viewer's eyes are in pixels, which makes the
+/// background repeat every half of it
+pub fn apply_stereogram(
+    image: &RgbaImage,
+    eye_separation: u32,
+    depth_scale: f64,
+    seed: Option<u64>,
+) -> RgbaImage {
+    let mut rng = match seed {
+        Some(seed) => fastrand::Rng::with_seed(seed),
+        None => fastrand::Rng::new(),
+    };
+    let (width, height) = image.dimensions();
+    let mut stereogram = RgbaImage::new(width, height);
+
+    for y in 0..height {
+        let heights: Vec<f64> = (0..width)
+            .map(|x| {
+                let [red, green, blue, alpha] = image.get_pixel(x, y).0;
+                // transparent parts are at the back
+                (0.2126 * red as f64 + 0.7152 * green as f64 + 0.0722 * blue as f64) / 255.0
+                    * alpha as f64
+                    / 255.0
+            })
+            .collect();
+        let links = get_links(&heights, eye_separation, depth_scale);
+
+        // filled in from the right so the pixel each one links to is already coloured
+        for x in (0..width as usize).rev() {
+            let color = match links[x] {
+                linked if linked == x => Rgba([rng.u8(..), rng.u8(..), rng.u8(..), 255]),
+                linked => *stereogram.get_pixel(linked as u32, y),
+            };
+            stereogram.put_pixel(x as u32, y, color);
+        }
+    }
+
+    stereogram
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stereogram_repeats() {
+        // a raised square in the middle of a flat background
+        let image = RgbaImage::from_fn(200, 40, |x, y| {
+            match (80..120).contains(&x) && (10..30).contains(&y) {
+                true => Rgba([255, 255, 255, 255]),
+                false => Rgba([0, 0, 0, 255]),
+            }
+        });
+        let stereogram = apply_stereogram(&image, 40, 0.5, Some(3));
+        let background = get_separation(0.0, 40, 0.5);
+        let raised = get_separation(1.0, 40, 0.5);
+        assert_eq!(background, 20);
+        assert!(raised < background);
+
+        // the background repeats at its separation, and the square closer together
+        for x in 0..150 {
+            assert_eq!(stereogram[(x, 0)], stereogram[(x + background, 0)]);
+        }
+        for x in 90..110 - raised / 2 {
+            assert_eq!(
+                stereogram[(x - raised / 2, 20)],
+                stereogram[(x - raised / 2 + raised, 20)]
+            );
+        }
+        assert_eq!(stereogram, apply_stereogram(&image, 40, 0.5, Some(3)));
+    }
+}
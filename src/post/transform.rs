@@ -0,0 +1,126 @@
+//! Coordinate transforms, which bend straight lines in the image into rings and
+//! vortices
+//!
+//! Polar wraps the image round its middle with the top edge in the middle and the
+//! bottom edge round the outside, so rows become rings and columns become spokes.
+//! Log-polar does the same with the rows getting further apart going out, which turns
+//! slanted lines into spirals. Swirl twists the middle of the image round, the most in
+//! the middle and fading out towards the edges.
+//! See <https://en.wikipedia.org/wiki/Log-polar_coordinates> for more info
+
+use std::f32::consts::TAU;
+
+use image::RgbaImage;
+
+use super::sample::sample_bilinear;
+
+/// How far out from the middle the top edge starts for log-polar, in pixels, as the
+/// middle itself would be infinitely far down the image
+const LOG_POLAR_INNER_RADIUS: f32 = 0.5;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CoordinateTransform {
+    Polar,
+    LogPolar,
+    /// Twists the middle round by this many degrees
+    Swirl(f32),
+}
+
+impl CoordinateTransform {
+    /// Gets where in the image a pixel comes from, given how far across and down it is
+    /// from the middle, along with the size of the image
+    fn get_source(&self, (dx, dy): (f32, f32), (width, height): (u32, u32)) -> (f32, f32) {
+        let centre = ((width as f32 - 1.0) / 2.0, (height as f32 - 1.0) / 2.0);
+        let radius = dx.hypot(dy);
+        let angle = dx.atan2(-dy);
+        // the bottom edge reaches the corners so the whole image is covered
+        let outer_radius = (width as f32).hypot(height as f32) / 2.0;
+        let x = (angle / TAU).rem_euclid(1.0) * width as f32 - 0.5;
+
+        match *self {
+            CoordinateTransform::Polar => (x, radius / outer_radius * height as f32 - 0.5),
+            CoordinateTransform::LogPolar => {
+                let amount = (radius.max(LOG_POLAR_INNER_RADIUS) / LOG_POLAR_INNER_RADIUS).ln()
+                    / (outer_radius / LOG_POLAR_INNER_RADIUS).ln();
+                (x, amount * height as f32 - 0.5)
+            }
+            CoordinateTransform::Swirl(degrees) => {
+                // twists the most in the middle, fading out to nothing at the edge of
+                // the biggest circle that fits
+                let swirl_radius = width.min(height) as f32 / 2.0;
+                let amount = (1.0 - radius / swirl_radius).max(0.0);
+                let twist = degrees.to_radians() * amount * amount;
+                let (sin, cos) = twist.sin_cos();
+                (
+                    centre.0 + dx * cos - dy * sin,
+                    centre.1 + dx * sin + dy * cos,
+                )
+            }
+        }
+    }
+}
+
+pub fn apply_transform(image: &RgbaImage, transform: CoordinateTransform) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let centre = ((width as f32 - 1.0) / 2.0, (height as f32 - 1.0) / 2.0);
+
+    RgbaImage::from_fn(width, height, |x, y| {
+        let offset = (x as f32 - centre.0, y as f32 - centre.1);
+        let (source_x, source_y) = transform.get_source(offset, (width, height));
+        sample_bilinear(image, source_x, source_y)
+    })
+}
+
+/// Parses a transform given as polar, log-polar or swirl with an optional angle in
+/// degrees like swirl(90), which is 180 if not given
+pub fn parse_transform(value: &str) -> Result<CoordinateTransform, String> {
+    let value = value.trim();
+    match value {
+        "polar" => Ok(CoordinateTransform::Polar),
+        "log-polar" => Ok(CoordinateTransform::LogPolar),
+        "swirl" => Ok(CoordinateTransform::Swirl(180.0)),
+        _ => {
+            let angle = value
+                .strip_prefix("swirl(")
+                .and_then(|rest| rest.strip_suffix(')'))
+                .ok_or_else(|| {
+                    "expected polar, log-polar, swirl or swirl(angle) like swirl(90)".to_string()
+                })?;
+            angle
+                .trim()
+                .parse::<f32>()
+                .map(CoordinateTransform::Swirl)
+                .map_err(|error| error.to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transforms() {
+        assert_eq!(parse_transform("polar"), Ok(CoordinateTransform::Polar));
+        assert_eq!(
+            parse_transform("swirl(-45)"),
+            Ok(CoordinateTransform::Swirl(-45.0))
+        );
+        assert!(parse_transform("swirl(a lot)").is_err());
+
+        let size = (100, 100);
+        // the top edge goes in the middle for polar, and straight up is the left edge
+        let (x, y) = CoordinateTransform::Polar.get_source((0.0, -1e-3), size);
+        assert!(x.abs() < 1.0 && y.abs() < 1.0);
+        // halfway round is halfway across
+        let (x, _) = CoordinateTransform::Polar.get_source((0.0, 30.0), size);
+        assert!((x - 49.5).abs() < 1e-3);
+
+        // swirling leaves the edges alone and twists the middle by the whole angle
+        let swirl = CoordinateTransform::Swirl(90.0);
+        let (x, y) = swirl.get_source((50.0, 0.0), size);
+        assert!((x - 99.5).abs() < 1e-3 && (y - 49.5).abs() < 1e-3);
+        let (x, y) = swirl.get_source((1e-3, 0.0), size);
+        assert!((x - 49.5).abs() < 1e-2 && (y - 49.5).abs() < 1e-2);
+    }
+}
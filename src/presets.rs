@@ -0,0 +1,372 @@
+//! Named sets of options for the image types, picked with --preset
+//!
+//! Each image type with presets gets a --preset option listing them. Once the command
+//! line is parsed, the options of the presets picked are added to it where they aren't
+//! given already and it's parsed again, so the presets are stored in recipes along with
+//! the options they stand for. Any option given alongside them wins over their own
+//! value for it, and later presets win over earlier ones
+
+use std::io::{self, Write};
+
+use clap::{
+    Arg, ArgAction, ArgMatches, Command, CommandFactory,
+    builder::{PossibleValue, PossibleValuesParser},
+    parser::ValueSource,
+};
+
+use crate::Args;
+
+/// A named set of options for an image type
+pub struct Preset {
+    pub image_type: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    /// The long names of the options with their values, where flags have no value
+    pub options: &'static [(&'static str, &'static str)],
+}
+
+impl Preset {
+    /// Gets the options as they'd be written on the command line
+    pub fn get_words(&self) -> impl Iterator<Item = String> + '_ {
+        self.options
+            .iter()
+            .map(|(name, value)| match value.is_empty() {
+                true => format!("--{}", name),
+                false => format!("--{}={}", name, value),
+            })
+    }
+}
+
+pub const PRESETS: &[Preset] = &[
+    Preset {
+        image_type: "mandelbrot",
+        name: "seahorse-valley",
+        description: "The curling tails in the gap between the main cardioid and the bulb on its left",
+        options: &[("center", "-0.745,0.11"), ("zoom", "200"), ("gradient", "")],
+    },
+    Preset {
+        image_type: "mandelbrot",
+        name: "elephant-valley",
+        description: "The rows of trunks in the gap at the right of the main cardioid",
+        options: &[("center", "0.275,0.006"), ("zoom", "100"), ("gradient", "")],
+    },
+    Preset {
+        image_type: "mandelbrot",
+        name: "mini-mandelbrot",
+        description: "A tiny copy of the whole set out on the antenna to the left",
+        options: &[("center", "-1.7497,0.0"), ("zoom", "150"), ("gradient", "")],
+    },
+    Preset {
+        image_type: "mandelbrot",
+        name: "field-lines",
+        description: "The whole set with binary decomposition showing the lines running into it",
+        options: &[
+            ("coloring", "binary-decomposition"),
+            ("color", "white"),
+            ("background-color", "black"),
+        ],
+    },
+    Preset {
+        image_type: "perlin",
+        name: "clouds",
+        description: "Soft white clouds on a blue sky",
+        options: &[("color1", "#3a7bd5"), ("color2", "white"), ("size", "800")],
+    },
+    Preset {
+        image_type: "perlin",
+        name: "terrain",
+        description: "Hills lit from the top left with contour lines like a map",
+        options: &[
+            ("color1", "#2d5a27"),
+            ("color2", "#e8dcb5"),
+            ("hillshade", "315,45"),
+            ("contours", "12"),
+            ("contour-color", "#5c4033"),
+            ("size", "800"),
+        ],
+    },
+    Preset {
+        image_type: "farey",
+        name: "dense",
+        description: "Fractions up to a denominator of 20, which packs the circles in tightly",
+        options: &[("n", "20")],
+    },
+    Preset {
+        image_type: "farey",
+        name: "stained-glass",
+        description: "Filled circles with dark outlines",
+        options: &[
+            ("n", "10"),
+            ("color", "#1a1a2e"),
+            ("fill-color", "#e94560"),
+            ("background-color", "#f5e6ca"),
+        ],
+    },
+    Preset {
+        image_type: "ulam-spiral",
+        name: "totient",
+        description: "A bigger spiral with every number coloured by its totient",
+        options: &[
+            ("size", "250000"),
+            ("value-fn", "totient"),
+            ("palette", "ocean"),
+        ],
+    },
+    Preset {
+        image_type: "ulam-spiral",
+        name: "divisors",
+        description: "Circles sized by how many divisors each number has",
+        options: &[("mode", "divisor"), ("size", "10000")],
+    },
+    Preset {
+        image_type: "times-table",
+        name: "nephroid",
+        description: "Times 3, which makes the kidney shape with two cusps",
+        options: &[("multiplier", "3")],
+    },
+    Preset {
+        image_type: "times-table",
+        name: "morph",
+        description: "Sweeps the multiplier from 2 to 10, growing a cusp for each one",
+        options: &[("multiplier", "2"), ("to", "10")],
+    },
+    Preset {
+        image_type: "flow-field",
+        name: "smoke",
+        description: "Wisps of grey smoke drifting smoothly",
+        options: &[
+            ("palette", "grayscale"),
+            ("turbulence", "0.5"),
+            ("scale", "400"),
+            ("particles", "10000"),
+            ("opacity", "0.05"),
+        ],
+    },
+    Preset {
+        image_type: "flow-field",
+        name: "storm",
+        description: "Tight swirls in fast changing colours",
+        options: &[
+            ("palette", "psychedelic"),
+            ("turbulence", "3"),
+            ("scale", "120"),
+        ],
+    },
+    Preset {
+        image_type: "maze",
+        name: "labyrinth",
+        description: "A big maze of thin corridors",
+        options: &[
+            ("columns", "100"),
+            ("rows", "100"),
+            ("cell-size", "8"),
+            ("wall-thickness", "2"),
+        ],
+    },
+    Preset {
+        image_type: "truchet",
+        name: "uniform",
+        description: "Tiles all the same size, the classic Truchet pattern",
+        options: &[("depth", "0"), ("tile-size", "40")],
+    },
+];
+
+/// Gets the presets for an image type
+pub fn get_presets(image_type: &str) -> impl Iterator<Item = &'static Preset> + '_ {
+    PRESETS
+        .iter()
+        .filter(move |preset| preset.image_type == image_type)
+}
+
+/// Adds --preset to the subcommand of each image type with presets, which have to be on
+/// the command already
+pub fn add_preset_args(mut command: Command) -> Command {
+    let mut image_types: Vec<_> = PRESETS.iter().map(|preset| preset.image_type).collect();
+    image_types.dedup();
+    for image_type in image_types {
+        let names: Vec<_> = get_presets(image_type)
+            .map(|preset| PossibleValue::new(preset.name).help(preset.description))
+            .collect();
+        command = command.mut_subcommand(image_type, |subcommand| {
+            subcommand.arg(
+                Arg::new("preset")
+                    .long("preset")
+                    .value_name("PRESET")
+                    .action(ArgAction::Append)
+                    .value_parser(PossibleValuesParser::new(names))
+                    .help(
+                        "Starts from a named set of options, see the presets command. Options \
+                         given alongside it win over the preset's",
+                    ),
+            )
+        });
+    }
+
+    command
+}
+
+/// Adds the options of the presets picked with --preset to the words, leaving out any
+/// given already and taking the value from the last preset with it. Returns whether
+/// any were added, when the words have to be parsed again
+pub fn add_preset_options(words: &mut Vec<String>, matches: &ArgMatches) -> bool {
+    let Some((image_type, sub_matches)) = matches.subcommand() else {
+        return false;
+    };
+    // reaction-diffusion has a --preset of its own
+    if get_presets(image_type).next().is_none() {
+        return false;
+    }
+    let Some(names) = sub_matches.get_many::<String>("preset") else {
+        return false;
+    };
+    let presets: Vec<_> = names
+        .filter_map(|name| get_presets(image_type).find(|preset| preset.name == name))
+        .collect();
+
+    let command = Args::command();
+    let subcommand = command
+        .find_subcommand(image_type)
+        .expect("presets are only for image types");
+    let mut added: Vec<&str> = Vec::new();
+    for preset in presets.iter().rev() {
+        for ((name, _), word) in preset.options.iter().zip(preset.get_words()) {
+            let is_given = get_argument(subcommand, name).is_some_and(|argument| {
+                sub_matches.value_source(argument.get_id().as_str())
+                    == Some(ValueSource::CommandLine)
+            });
+            if is_given || added.contains(name) {
+                continue;
+            }
+            added.push(name);
+            words.push(word);
+        }
+    }
+
+    !added.is_empty()
+}
+
+/// Parses the words with the options of any presets picked added in
+pub fn get_preset_matches(words: &[String]) -> Result<ArgMatches, clap::Error> {
+    let matches = Args::command().try_get_matches_from(words)?;
+    let mut words = words.to_vec();
+    match add_preset_options(&mut words, &matches) {
+        true => Args::command().try_get_matches_from(words),
+        false => Ok(matches),
+    }
+}
+
+fn get_argument<'a>(command: &'a Command, long: &str) -> Option<&'a Arg> {
+    command
+        .get_arguments()
+        .find(|argument| argument.get_long() == Some(long))
+}
+
+/// Prints the presets for an image type, or for all of them
+pub fn print_presets(image_type: Option<&str>) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    let presets: Vec<_> = PRESETS
+        .iter()
+        .filter(|preset| image_type.is_none_or(|image_type| preset.image_type == image_type))
+        .collect();
+    if let (Some(image_type), true) = (image_type, presets.is_empty()) {
+        let command = Args::command();
+        let has_own_preset = command
+            .find_subcommand(image_type)
+            .is_some_and(|subcommand| get_argument(subcommand, "preset").is_some());
+        return match has_own_preset {
+            true => writeln!(
+                stdout,
+                "{} picks its presets with its own --preset, see {} --help",
+                image_type, image_type
+            ),
+            false => writeln!(stdout, "{} doesn't have any presets", image_type),
+        };
+    }
+
+    for preset in presets {
+        writeln!(stdout, "{} --preset {}", preset.image_type, preset.name)?;
+        writeln!(stdout, "    {}", preset.description)?;
+        writeln!(
+            stdout,
+            "    {}",
+            preset.get_words().collect::<Vec<_>>().join(" ")
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_words(line: &str) -> Vec<String> {
+        line.split(' ').map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_presets() {
+        // every preset has to make a valid command line
+        for preset in PRESETS {
+            let words = to_words(&format!(
+                "fun-images {} --preset {}",
+                preset.image_type, preset.name
+            ));
+            let result = get_preset_matches(&words);
+            assert!(result.is_ok(), "{} {}", preset.image_type, preset.name);
+        }
+
+        // options given on the command line win, whatever they're written as
+        let mut words =
+            to_words("fun-images -o a.png mandelbrot --zoom 5 --preset seahorse-valley");
+        let matches = Args::command().try_get_matches_from(&words).unwrap();
+        assert!(add_preset_options(&mut words, &matches));
+        assert_eq!(
+            words,
+            to_words(
+                "fun-images -o a.png mandelbrot --zoom 5 --preset seahorse-valley \
+                 --center=-0.745,0.11 --gradient"
+            )
+        );
+        let mut words = to_words("fun-images farey --preset=stained-glass -cred");
+        let matches = Args::command().try_get_matches_from(&words).unwrap();
+        assert!(add_preset_options(&mut words, &matches));
+        assert_eq!(
+            words,
+            to_words(
+                "fun-images farey --preset=stained-glass -cred --n=10 --fill-color=#e94560 \
+                 --background-color=#f5e6ca"
+            )
+        );
+
+        // every preset picked is used, with the later ones winning
+        let matches = get_preset_matches(&to_words(
+            "fun-images mandelbrot --preset field-lines --preset seahorse-valley",
+        ))
+        .unwrap();
+        let (_, sub_matches) = matches.subcommand().unwrap();
+        assert_eq!(
+            sub_matches.value_source("coloring"),
+            Some(ValueSource::CommandLine)
+        );
+        assert_eq!(
+            sub_matches.value_source("center"),
+            Some(ValueSource::CommandLine)
+        );
+
+        assert!(get_preset_matches(&to_words("fun-images perlin --preset nope")).is_err());
+        // reaction-diffusion's own --preset is left alone
+        let mut words = to_words("fun-images reaction-diffusion --preset stripes");
+        let matches = Args::command().try_get_matches_from(&words).unwrap();
+        assert!(!add_preset_options(&mut words, &matches));
+
+        // and --preset shows up in the help
+        let mut command = Args::command();
+        let help = command
+            .find_subcommand_mut("mandelbrot")
+            .unwrap()
+            .render_help()
+            .to_string();
+        assert!(help.contains("--preset"));
+    }
+}
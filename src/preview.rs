@@ -0,0 +1,263 @@
+//! Shows the generated image straight in the terminal
+//!
+//! Uses the [kitty graphics protocol](https://sw.kovidgoyal.net/kitty/graphics-protocol/),
+//! iTerm's inline images or [sixel](https://en.wikipedia.org/wiki/Sixel) depending on
+//! which terminal we're running in, otherwise falls back to opening the system's image viewer
+
+use std::{
+    env,
+    fmt::Write as _,
+    io::{Cursor, Write},
+    process::Command,
+};
+
+use image::{ImageFormat, RgbaImage, imageops::FilterType};
+use log::error;
+
+/// Images wider than this get shrunk first so they fit on the screen
+const MAX_PREVIEW_WIDTH: u32 = 800;
+/// The kitty protocol wants the payload split into chunks of at most this many bytes
+const KITTY_CHUNK_SIZE: usize = 4096;
+/// Sixel colours are made from this many levels of each of red, green and blue
+const SIXEL_LEVELS: u32 = 6;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum GraphicsProtocol {
+    Kitty,
+    Iterm,
+    Sixel,
+}
+
+/// Previews the image in the terminal, or opens `file_path` in the system viewer if
+/// the terminal can't show images
+pub fn preview_image(image: &RgbaImage, file_path: &str) {
+    let Some(protocol) = detect_graphics_protocol() else {
+        open_in_system_viewer(file_path);
+        return;
+    };
+
+    let image = if image.width() > MAX_PREVIEW_WIDTH {
+        let height =
+            (image.height() as u64 * MAX_PREVIEW_WIDTH as u64 / image.width() as u64).max(1) as u32;
+        image::imageops::resize(image, MAX_PREVIEW_WIDTH, height, FilterType::Triangle)
+    } else {
+        image.clone()
+    };
+
+    let output = match protocol {
+        GraphicsProtocol::Kitty => encode_png(&image).map(|png| encode_kitty(&png)),
+        GraphicsProtocol::Iterm => encode_png(&image).map(|png| encode_iterm(&png)),
+        GraphicsProtocol::Sixel => Some(encode_sixel(&image)),
+    };
+    let Some(output) = output else {
+        open_in_system_viewer(file_path);
+        return;
+    };
+
+    let mut stdout = std::io::stdout();
+    let _ = stdout.write_all(output.as_bytes());
+    let _ = stdout.write_all(b"\n");
+    let _ = stdout.flush();
+}
+
+/// Guesses what the terminal supports from its environment variables
+fn detect_graphics_protocol() -> Option<GraphicsProtocol> {
+    let term = env::var("TERM").unwrap_or_default();
+    let term_program = env::var("TERM_PROGRAM").unwrap_or_default();
+
+    if term == "xterm-kitty"
+        || env::var_os("KITTY_WINDOW_ID").is_some()
+        || term_program == "ghostty"
+    {
+        Some(GraphicsProtocol::Kitty)
+    } else if term_program == "iTerm.app" || term_program == "WezTerm" {
+        Some(GraphicsProtocol::Iterm)
+    } else if term.contains("sixel")
+        || term.starts_with("foot")
+        || term.starts_with("mlterm")
+        || term.starts_with("yaft")
+    {
+        Some(GraphicsProtocol::Sixel)
+    } else {
+        None
+    }
+}
+
+fn open_in_system_viewer(file_path: &str) {
+    let result = if cfg!(target_os = "windows") {
+        Command::new("cmd")
+            .args(["/C", "start", "", file_path])
+            .spawn()
+    } else if cfg!(target_os = "macos") {
+        Command::new("open").arg(file_path).spawn()
+    } else {
+        Command::new("xdg-open").arg(file_path).spawn()
+    };
+
+    if let Err(error) = result {
+        error!("opening image viewer for {}: {:?}", file_path, error);
+    }
+}
+
+fn encode_png(image: &RgbaImage) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    match image.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png) {
+        Ok(()) => Some(bytes),
+        Err(image_error) => {
+            error!("encoding preview: {:?}", image_error);
+            None
+        }
+    }
+}
+
+/// Sends the PNG directly, split up over multiple escape codes
+fn encode_kitty(png: &[u8]) -> String {
+    let data = encode_base64(png);
+    let chunks: Vec<&[u8]> = data.as_bytes().chunks(KITTY_CHUNK_SIZE).collect();
+
+    let mut output = String::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = if i + 1 < chunks.len() { 1 } else { 0 };
+        // only the first chunk needs the format and action
+        let control = if i == 0 { "f=100,a=T," } else { "" };
+        let _ = write!(
+            output,
+            "\x1b_G{}m={};{}\x1b\\",
+            control,
+            more,
+            // base64 is always ascii
+            std::str::from_utf8(chunk).unwrap()
+        );
+    }
+    output
+}
+
+fn encode_iterm(png: &[u8]) -> String {
+    format!(
+        "\x1b]1337;File=inline=1;size={}:{}\x07",
+        png.len(),
+        encode_base64(png)
+    )
+}
+
+/// Encodes the image as sixels using a fixed 6x6x6 colour cube
+///
+/// Each sixel character covers a column of 6 pixels, so the image is drawn in bands
+/// of 6 rows, going over the band once for every colour used in it
+fn encode_sixel(image: &RgbaImage) -> String {
+    let (width, height) = image.dimensions();
+    let color_num = (SIXEL_LEVELS * SIXEL_LEVELS * SIXEL_LEVELS) as usize;
+
+    let mut output = String::from("\x1bPq");
+    let _ = write!(output, "\"1;1;{};{}", width, height);
+    for index in 0..color_num as u32 {
+        let [r, g, b] = [
+            index / (SIXEL_LEVELS * SIXEL_LEVELS),
+            index / SIXEL_LEVELS % SIXEL_LEVELS,
+            index % SIXEL_LEVELS,
+        ]
+        .map(|level| level * 100 / (SIXEL_LEVELS - 1));
+        let _ = write!(output, "#{};2;{};{};{}", index, r, g, b);
+    }
+
+    // the colour index of each pixel, or none if it's transparent
+    let indices: Vec<Option<usize>> = image
+        .pixels()
+        .map(|pixel| {
+            let [r, g, b, a] = pixel.0;
+            if a < 128 {
+                return None;
+            }
+            let [r, g, b] =
+                [r, g, b].map(|value| ((value as u32 * (SIXEL_LEVELS - 1) + 127) / 255) as usize);
+            Some(r * (SIXEL_LEVELS * SIXEL_LEVELS) as usize + g * SIXEL_LEVELS as usize + b)
+        })
+        .collect();
+
+    let mut band = vec![0u8; width as usize * color_num];
+    let mut used = vec![false; color_num];
+    for band_y in (0..height).step_by(6) {
+        band.fill(0);
+        used.fill(false);
+        for y in band_y..(band_y + 6).min(height) {
+            for x in 0..width {
+                if let Some(index) = indices[(y * width + x) as usize] {
+                    band[index * width as usize + x as usize] |= 1 << (y - band_y);
+                    used[index] = true;
+                }
+            }
+        }
+
+        for index in (0..color_num).filter(|&index| used[index]) {
+            let _ = write!(output, "#{}", index);
+            let row = &band[index * width as usize..(index + 1) * width as usize];
+            write_sixel_row(&mut output, row);
+            // go back to the start of the band for the next colour
+            output.push('$');
+        }
+        output.push('-');
+    }
+
+    output.push_str("\x1b\\");
+    output
+}
+
+/// Writes a row of sixels, using the repeat introducer for runs of the same sixel
+fn write_sixel_row(output: &mut String, row: &[u8]) {
+    let mut i = 0;
+    while i < row.len() {
+        let run = row[i..].iter().take_while(|&&bits| bits == row[i]).count();
+        let character = (63 + row[i]) as char;
+        if run > 3 {
+            let _ = write!(output, "!{}{}", run, character);
+        } else {
+            for _ in 0..run {
+                output.push(character);
+            }
+        }
+        i += run;
+    }
+}
+
+fn encode_base64(bytes: &[u8]) -> String {
+    let mut output = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let value = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                output.push(BASE64_ALPHABET[(value >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                output.push('=');
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(encode_base64(b""), "");
+        assert_eq!(encode_base64(b"f"), "Zg==");
+        assert_eq!(encode_base64(b"fo"), "Zm8=");
+        assert_eq!(encode_base64(b"foo"), "Zm9v");
+        assert_eq!(encode_base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn test_sixel() {
+        let image = RgbaImage::from_pixel(5, 6, Rgba([255, 0, 0, 255]));
+        // pure red is colour 180 in the cube, and all 6 bits are set in every column
+        assert!(encode_sixel(&image).ends_with("#180!5~$-\x1b\\"));
+    }
+}
@@ -0,0 +1,324 @@
+//! Module for drawing prime number races
+//!
+//! Apart from 2 and the factors of the modulus, every prime leaves a remainder that
+//! shares no factors with the modulus, and in the long run each of these remainders
+//! gets an equal share of the primes. Counting them as they go, the classes take turns
+//! in the lead, but the remainders that aren't squares, like 3 mod 4, are ahead almost
+//! all the time, which is Chebyshev's bias. The curve is how far the first class is
+//! ahead of the second, so for 1 and 3 mod 4 it stays below zero until 26861.
+//! See <https://en.wikipedia.org/wiki/Chebyshev%27s_bias> for more info
+
+use csscolorparser::Color;
+use image::{Rgba, RgbaImage};
+
+use crate::{
+    generator::{Generated, Generator, GeneratorError},
+    memory::MemoryEstimate,
+    parse_color, parse_size,
+    text::{draw_text_on_image, get_text_size},
+    ulam_spiral::get_prime_sieve,
+};
+
+/// The space above and below the curve, as a fraction of the image height
+const MARGIN: f64 = 0.08;
+/// The colour of the line through zero where the classes are tied
+const AXIS_COLOR: Rgba<u8> = Rgba([128, 128, 128, 255]);
+
+pub struct PrimeRaceOptions {
+    /// How far to count the primes up to
+    limit: u64,
+    modulus: u64,
+    /// The two remainders racing, with the curve going up when the first is ahead
+    residues: (u64, u64),
+    /// Whether the numbers are spread out by their logarithm, so the start of the race
+    /// gets as much room as the end
+    log_scale: bool,
+    /// The colour of the curve when the first class is ahead
+    color: Color,
+    /// The colour of the curve when the second class is ahead
+    second_color: Color,
+    background_color: Color,
+    width: u32,
+    height: u32,
+}
+
+impl PrimeRaceOptions {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        limit: u64,
+        modulus: u64,
+        residues: (u64, u64),
+        log_scale: bool,
+        color: Color,
+        second_color: Color,
+        background_color: Color,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        Self {
+            limit,
+            modulus,
+            residues,
+            log_scale,
+            color,
+            second_color,
+            background_color,
+            width,
+            height,
+        }
+    }
+
+    /// Gets which column of the image a number goes in
+    fn get_column(&self, number: u64) -> u32 {
+        let amount = match self.log_scale {
+            true => (number as f64 / 2.0).ln() / (self.limit as f64 / 2.0).ln(),
+            false => (number - 2) as f64 / (self.limit - 2) as f64,
+        };
+        ((amount * self.width as f64) as u32).min(self.width - 1)
+    }
+}
+
+fn is_coprime(num1: u64, num2: u64) -> bool {
+    let (mut num1, mut num2) = (num1, num2);
+    while num2 != 0 {
+        (num1, num2) = (num2, num1 % num2);
+    }
+    num1 == 1
+}
+
+/// Goes through the primes up to the limit in either class, giving each one along with
+/// how many more primes the first class has than the second up to it
+pub fn get_leads(limit: u64, modulus: u64, (first, second): (u64, u64)) -> Vec<(u64, i64)> {
+    let sieve = get_prime_sieve(limit);
+    let mut lead = 0;
+
+    sieve
+        .primes_from(0)
+        .map(|prime| prime as u64)
+        .take_while(|&prime| prime <= limit)
+        .filter_map(|prime| {
+            match prime % modulus {
+                remainder if remainder == first => lead += 1,
+                remainder if remainder == second => lead -= 1,
+                _ => return None,
+            }
+            Some((prime, lead))
+        })
+        .collect()
+}
+
+/// Gets the lowest and highest lead in each column of the image, including the lead
+/// carried over from the column before
+fn get_lead_ranges(options: &PrimeRaceOptions, leads: &[(u64, i64)]) -> Vec<(i64, i64)> {
+    let mut ranges = Vec::with_capacity(options.width as usize);
+    let mut leads = leads.iter().peekable();
+    let mut lead = 0;
+
+    for column in 0..options.width {
+        let (mut lowest, mut highest) = (lead, lead);
+        while let Some(&&(prime, next_lead)) = leads.peek()
+            && options.get_column(prime) == column
+        {
+            lead = next_lead;
+            lowest = lowest.min(lead);
+            highest = highest.max(lead);
+            leads.next();
+        }
+        ranges.push((lowest, highest));
+    }
+
+    ranges
+}
+
+pub fn generate_prime_race_image(options: PrimeRaceOptions) -> RgbaImage {
+    let (width, height) = (options.width, options.height);
+    let mut image = RgbaImage::from_pixel(width, height, Rgba(options.background_color.to_rgba8()));
+
+    let leads = get_leads(options.limit, options.modulus, options.residues);
+    let ranges = get_lead_ranges(&options, &leads);
+    // the same scale both ways so the zero line stays in the middle
+    let furthest = ranges
+        .iter()
+        .map(|&(lowest, highest)| lowest.abs().max(highest.abs()))
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let middle = height as f64 / 2.0;
+    let scale = middle * (1.0 - 2.0 * MARGIN) / furthest as f64;
+    let get_row = |lead: i64| {
+        (middle - lead as f64 * scale)
+            .round()
+            .clamp(0.0, height as f64) as u32
+    };
+
+    let (color, second_color) = (
+        Rgba(options.color.to_rgba8()),
+        Rgba(options.second_color.to_rgba8()),
+    );
+    for (x, &(lowest, highest)) in ranges.iter().enumerate() {
+        // filled in from zero out to the furthest each class got ahead in the column
+        if highest > 0 {
+            for y in get_row(highest)..get_row(0) {
+                image.put_pixel(x as u32, y, color);
+            }
+        }
+        if lowest < 0 {
+            for y in get_row(0)..get_row(lowest) {
+                image.put_pixel(x as u32, y, second_color);
+            }
+        }
+    }
+
+    let axis_row = get_row(0).min(height - 1);
+    for x in 0..width {
+        image.put_pixel(x, axis_row, AXIS_COLOR);
+    }
+
+    let font_size = height as f32 / 25.0;
+    let (first, second) = options.residues;
+    let margin = (height as f64 * MARGIN / 4.0) as i32;
+    let second_label = format!("{} mod {} ahead", second, options.modulus);
+    let (_, label_height) = get_text_size(&second_label, font_size);
+    draw_text_on_image(
+        &mut image,
+        &format!("{} mod {} ahead", first, options.modulus),
+        (margin, margin),
+        font_size,
+        color,
+    );
+    draw_text_on_image(
+        &mut image,
+        &second_label,
+        (margin, height as i32 - margin - label_height as i32),
+        font_size,
+        second_color,
+    );
+
+    image
+}
+
+/// Parses the two remainders racing, given as first,second
+fn parse_residues(value: &str) -> Result<(u64, u64), String> {
+    let (first, second) = value
+        .split_once(',')
+        .ok_or_else(|| "expected two remainders like 1,3".to_string())?;
+    let parse_part = |part: &str| {
+        part.trim()
+            .parse::<u64>()
+            .map_err(|error| error.to_string())
+    };
+
+    Ok((parse_part(first)?, parse_part(second)?))
+}
+
+#[derive(clap::Args, Debug)]
+pub struct PrimeRaceArgs {
+    /// How far to count the primes up to
+    #[arg(short = 'n', long, default_value = "100000", value_parser = clap::value_parser!(u64).range(3..))]
+    limit: u64,
+
+    /// The primes are split into classes by their remainder when divided by this
+    #[arg(short, long, default_value = "4", value_parser = clap::value_parser!(u64).range(3..))]
+    modulus: u64,
+
+    /// The two remainders racing, which can't share any factors with the modulus, with
+    /// the curve going up when the first one has more primes
+    #[arg(short, long, default_value = "1,3", value_parser = parse_residues)]
+    residues: (u64, u64),
+
+    /// Spreads the numbers out by their logarithm, so the start of the race isn't
+    /// squashed up at the left
+    #[arg(long)]
+    log: bool,
+
+    /// The colour of the curve when the first remainder is ahead
+    #[arg(short, long, default_value = "deepskyblue", value_parser = parse_color)]
+    color: Color,
+
+    /// The colour of the curve when the second remainder is ahead
+    #[arg(long, default_value = "deeppink", value_parser = parse_color)]
+    second_color: Color,
+
+    #[arg(short, long, alias = "background", default_value = "black", value_parser = parse_color)]
+    background_color: Color,
+
+    #[arg(long, default_value = "1200", value_parser = parse_size)]
+    width: u32,
+
+    #[arg(long, default_value = "600", value_parser = parse_size)]
+    height: u32,
+}
+
+pub struct PrimeRaceGenerator;
+
+impl Generator for PrimeRaceGenerator {
+    type Args = PrimeRaceArgs;
+
+    fn name(&self) -> &'static str {
+        "prime-race"
+    }
+
+    fn is_animated(&self, _args: &PrimeRaceArgs) -> bool {
+        false
+    }
+
+    fn estimate_memory(&self, args: &PrimeRaceArgs) -> Option<MemoryEstimate> {
+        // the sieve has a bit for each number, and every prime in the race keeps its lead
+        let prime_num = args.limit as f64 / (args.limit.max(3) as f64).ln();
+        Some(
+            MemoryEstimate::still(args.width, args.height)
+                .with_working(args.limit / 8 + prime_num as u64 * 16),
+        )
+    }
+
+    fn generate(&self, args: PrimeRaceArgs) -> Result<Generated, GeneratorError> {
+        let (first, second) = args.residues;
+        if first == second {
+            return Err(GeneratorError::InvalidOptions(
+                "the two remainders have to be different".to_string(),
+            ));
+        }
+        for residue in [first, second] {
+            if residue >= args.modulus || !is_coprime(residue, args.modulus) {
+                return Err(GeneratorError::InvalidOptions(format!(
+                    "{} can't be a remainder in the race, as the remainders have to be below \
+                    {} and share no factors with it",
+                    residue, args.modulus
+                )));
+            }
+        }
+
+        Ok(Generated::Still(generate_prime_race_image(
+            PrimeRaceOptions::new(
+                args.limit,
+                args.modulus,
+                args.residues,
+                args.log,
+                args.color,
+                args.second_color,
+                args.background_color,
+                args.width,
+                args.height,
+            ),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chebyshev_bias() {
+        // 1 mod 4 first takes the lead at 26861, and is never ahead before it
+        let leads = get_leads(26861, 4, (1, 3));
+        assert_eq!(leads.last(), Some(&(26861, 1)));
+        assert!(leads[..leads.len() - 1].iter().all(|&(_, lead)| lead <= 0));
+        // 2 isn't in either class
+        assert_eq!(leads[..3], [(3, -1), (5, 0), (7, -1)]);
+
+        assert!(is_coprime(3, 4) && !is_coprime(2, 4) && !is_coprime(0, 4));
+        assert_eq!(parse_residues("1, 3"), Ok((1, 3)));
+        assert!(parse_residues("1").is_err());
+    }
+}
@@ -0,0 +1,365 @@
+//! Module for rendering Julia sets of quaternions
+//!
+//! Quaternions are like complex numbers with three imaginary parts instead of one, so
+//! the Julia set of z² + c lives in 4D. Fixing the last part gives a 3D slice through
+//! it, which is raymarched like the Mandelbulb or cut again into a flat cross-section.
+//! Moving where the slice is makes the shape grow, split and melt back together.
+//! See <https://paulbourke.net/fractals/quatjulia/> for more info
+
+use std::f64::consts::TAU;
+
+use csscolorparser::Color;
+use image::{Rgba, RgbaImage};
+use rayon::prelude::*;
+
+use crate::{
+    PaletteType, QuaternionJuliaMode,
+    cancel::{is_cancelled, render_frames},
+    generator::{Generated, Generator, GeneratorError},
+    memory::MemoryEstimate,
+    palette::get_palette_color,
+    parse_size,
+    raymarch::{DistanceEstimator, RaymarchArgs, RaymarchOptions, Vec3},
+};
+
+type Quaternion = [f64; 4];
+
+/// Points whose length squared goes past this have escaped. It's much further out than
+/// needed so the distance estimate is more accurate
+const ESCAPE_LENGTH_SQUARED: f64 = 256.0;
+
+/// How far the cross-section shows from the middle to the top and bottom edges
+const SECTION_RADIUS: f64 = 1.6;
+
+fn square(q: Quaternion) -> Quaternion {
+    [
+        q[0] * q[0] - q[1] * q[1] - q[2] * q[2] - q[3] * q[3],
+        2.0 * q[0] * q[1],
+        2.0 * q[0] * q[2],
+        2.0 * q[0] * q[3],
+    ]
+}
+
+fn length_squared(q: Quaternion) -> f64 {
+    q.iter().map(|part| part * part).sum()
+}
+
+/// The 3D slice of the Julia set where the last part of each quaternion is fixed
+pub struct QuaternionJulia {
+    constant: Quaternion,
+    /// The last part of every point in the slice
+    slice: f64,
+    iteration_num: u32,
+}
+
+impl QuaternionJulia {
+    pub fn new(constant: Quaternion, slice: f64, iteration_num: u32) -> Self {
+        Self {
+            constant,
+            slice,
+            iteration_num,
+        }
+    }
+
+    /// Iterates a point, giving how many iterations it took to escape or None if it
+    /// didn't, how long it ended up, how fast that was growing as the point moves and
+    /// how close the orbit got to the middle
+    fn iterate(&self, point: Vec3) -> (Option<u32>, f64, f64, f64) {
+        let mut z = [point[0], point[1], point[2], self.slice];
+        let mut z_length = length_squared(z);
+        let mut derivative_length = 1.0;
+        let mut trap = z_length;
+        for iteration in 0..self.iteration_num {
+            // the derivative of z² is 2z, so its length squared gets
+            // multiplied by 4|z|² each time
+            derivative_length *= 4.0 * z_length;
+            let squared = square(z);
+            z = [0, 1, 2, 3].map(|part| squared[part] + self.constant[part]);
+            z_length = length_squared(z);
+            trap = trap.min(z_length);
+            if z_length > ESCAPE_LENGTH_SQUARED {
+                return (Some(iteration), z_length, derivative_length, trap);
+            }
+        }
+
+        (None, z_length, derivative_length, trap)
+    }
+
+    /// Gets the colour of a point in the cross-section, with the inside coloured by how
+    /// close its orbit got to the middle and the outside fading from the background by
+    /// how long it took to escape
+    fn get_section_color(
+        &self,
+        point: Vec3,
+        color: &Color,
+        palette: Option<PaletteType>,
+        background_color: [u8; 4],
+    ) -> [u8; 4] {
+        let (escape, z_length, _, trap) = self.iterate(point);
+        match escape {
+            None => match palette {
+                Some(palette) => get_palette_color(palette, trap.sqrt().clamp(0.0, 1.0) * 0.8),
+                None => color.to_rgba8(),
+            },
+            Some(iteration) => {
+                // smoothed so the bands between iterations don't show
+                let smooth = iteration as f64 + 1.0 - (z_length.ln() / 2.0).ln() / 2f64.ln();
+                let amount = (smooth / self.iteration_num as f64).clamp(0.0, 1.0);
+                let edge_color = match palette {
+                    Some(palette) => get_palette_color(palette, amount * 0.8),
+                    None => color.to_rgba8(),
+                };
+                let blend = amount.sqrt() * 0.8;
+                [0, 1, 2, 3].map(|channel| {
+                    (background_color[channel] as f64 * (1.0 - blend)
+                        + edge_color[channel] as f64 * blend)
+                        .round() as u8
+                })
+            }
+        }
+    }
+}
+
+impl DistanceEstimator for QuaternionJulia {
+    /// Gets an estimate of how far a point is from the surface along with how close its
+    /// orbit got to the middle
+    fn get_distance(&self, point: Vec3) -> (f64, f64) {
+        let (_, z_length, derivative_length, trap) = self.iterate(point);
+        // points that never escape are inside, which only happens very near the surface
+        // after the ray's already counted as a hit, so they count as on it
+        let distance = match z_length > 1.0 {
+            true => 0.25 * (z_length / derivative_length).sqrt() * z_length.ln(),
+            false => 0.0,
+        };
+
+        (distance, trap.sqrt())
+    }
+}
+
+pub struct QuaternionJuliaOptions {
+    constant: Quaternion,
+    /// Where the slice is, along the last part
+    slice: f64,
+    iteration_num: u32,
+    mode: QuaternionJuliaMode,
+    raymarch: RaymarchOptions,
+    /// How many frames the animation has, a still image if not given
+    frame_num: Option<u32>,
+    /// How far the slice moves either side of where it is through the animation
+    sweep: f64,
+}
+
+impl QuaternionJuliaOptions {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        constant: Quaternion,
+        slice: f64,
+        iteration_num: u32,
+        mode: QuaternionJuliaMode,
+        raymarch: RaymarchOptions,
+        frame_num: Option<u32>,
+        sweep: f64,
+    ) -> Self {
+        Self {
+            constant,
+            slice,
+            iteration_num,
+            mode,
+            raymarch,
+            frame_num,
+            sweep,
+        }
+    }
+
+    /// Gets where the slice is on a frame, going out and back so the animation loops
+    fn get_slice(&self, frame: u32) -> f64 {
+        match self.frame_num {
+            Some(frame_num) => {
+                self.slice + self.sweep * (TAU * frame as f64 / frame_num as f64).sin()
+            }
+            None => self.slice,
+        }
+    }
+}
+
+/// Draws the cross-section through the middle of the 3D slice facing the camera
+pub fn generate_section_image(julia: &QuaternionJulia, raymarch: &RaymarchOptions) -> RgbaImage {
+    let (width, height) = raymarch.size();
+    let camera = raymarch.camera();
+    let background_color = raymarch.background_color().to_rgba8();
+    let pixel_size = 2.0 * SECTION_RADIUS / height as f64;
+
+    let pixels: Vec<[u8; 4]> = (0..width * height)
+        .into_par_iter()
+        .map(|index| {
+            if is_cancelled() {
+                return background_color;
+            }
+            let across = ((index % width) as f64 + 0.5 - width as f64 / 2.0) * pixel_size;
+            let down = (height as f64 / 2.0 - (index / width) as f64 - 0.5) * pixel_size;
+            let point = [0, 1, 2].map(|axis| camera.right[axis] * across + camera.up[axis] * down);
+            julia.get_section_color(
+                point,
+                raymarch.color(),
+                raymarch.palette(),
+                background_color,
+            )
+        })
+        .collect();
+
+    RgbaImage::from_fn(width, height, |x, y| Rgba(pixels[(y * width + x) as usize]))
+}
+
+fn generate_frame(options: &QuaternionJuliaOptions, frame: u32) -> RgbaImage {
+    let raymarch = &options.raymarch;
+    let julia = QuaternionJulia::new(
+        options.constant,
+        options.get_slice(frame),
+        options.iteration_num,
+    );
+    match options.mode {
+        QuaternionJuliaMode::Surface => raymarch
+            .render_views(|raymarch| raymarch.render(&julia, raymarch.get_distances(&julia))),
+        QuaternionJuliaMode::Section => generate_section_image(&julia, raymarch),
+    }
+}
+
+pub fn generate_quat_julia_images(options: QuaternionJuliaOptions) -> Vec<RgbaImage> {
+    render_frames(0..options.frame_num.unwrap_or(1), |frame| {
+        generate_frame(&options, frame)
+    })
+}
+
+/// Parses a quaternion given as its 4 parts, like -0.2,0.6,0.2,0
+fn parse_quaternion(value: &str) -> Result<Quaternion, String> {
+    let parts: Vec<f64> = value
+        .split(',')
+        .map(|part| {
+            part.trim()
+                .parse::<f64>()
+                .map_err(|error| error.to_string())
+        })
+        .collect::<Result<_, _>>()?;
+    parts
+        .try_into()
+        .map_err(|_| "expected 4 numbers like -0.2,0.6,0.2,0".to_string())
+}
+
+#[derive(clap::Args, Debug)]
+pub struct QuaternionJuliaArgs {
+    /// The constant added each iteration, as the real part then the 3 imaginary parts
+    #[arg(long = "c", default_value = "-0.2,0.6,0.2,0.2", value_parser = parse_quaternion, allow_hyphen_values = true)]
+    constant: Quaternion,
+
+    /// Where the 3D slice is taken through the 4D set, along the last imaginary part
+    #[arg(long, default_value = "0.0", allow_negative_numbers = true)]
+    slice: f64,
+
+    /// How many times each point is iterated, more gives finer detail
+    #[arg(short, long, default_value = "10", value_parser = parse_size)]
+    iterations: u32,
+
+    /// Whether to raymarch the slice or draw a flat cross-section through it, which
+    /// faces the camera
+    #[arg(long, default_value = "surface")]
+    mode: QuaternionJuliaMode,
+
+    #[command(flatten)]
+    view: RaymarchArgs,
+
+    /// Animates with this many frames, sweeping the slice through the set and back
+    #[arg(short, long, value_parser = parse_size)]
+    frames: Option<u32>,
+
+    /// How far the slice moves either side of --slice through the animation
+    #[arg(long, default_value = "0.5")]
+    sweep: f64,
+}
+
+pub struct QuaternionJuliaGenerator;
+
+impl Generator for QuaternionJuliaGenerator {
+    type Args = QuaternionJuliaArgs;
+
+    fn name(&self) -> &'static str {
+        "quat-julia"
+    }
+
+    fn is_animated(&self, args: &QuaternionJuliaArgs) -> bool {
+        args.frames.is_some()
+    }
+
+    fn estimate_memory(&self, args: &QuaternionJuliaArgs) -> Option<MemoryEstimate> {
+        Some(args.view.estimate_memory(args.frames.unwrap_or(1)))
+    }
+
+    fn generate(&self, args: QuaternionJuliaArgs) -> Result<Generated, GeneratorError> {
+        args.view.check().map_err(GeneratorError::InvalidOptions)?;
+        let raymarch = args.view.into_options();
+        if matches!(args.mode, QuaternionJuliaMode::Section) && raymarch.eye_distance().is_some() {
+            return Err(GeneratorError::InvalidOptions(
+                "the cross-section is flat, so it can't be an anaglyph".to_string(),
+            ));
+        }
+
+        let is_animated = args.frames.is_some();
+        let mut images = generate_quat_julia_images(QuaternionJuliaOptions::new(
+            args.constant,
+            args.slice,
+            args.iterations,
+            args.mode,
+            raymarch,
+            args.frames,
+            args.sweep,
+        ));
+        match is_animated {
+            true => Ok(Generated::Frames(images)),
+            false => Ok(Generated::Still(images.remove(0))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raymarch::Camera;
+
+    #[test]
+    fn test_quaternion_julia() {
+        // with c = 0 the set is the ball of radius 1 in every slice, so the distance
+        // estimate should be close to the real distance
+        let julia = QuaternionJulia::new([0.0; 4], 0.0, 12);
+        let (distance, _) = julia.get_distance([1.5, 0.0, 0.0]);
+        assert!(distance > 0.0 && distance <= 0.5);
+        assert!(distance > 0.2);
+        assert_eq!(julia.iterate([0.5, 0.5, 0.0]).0, None);
+        assert!(julia.iterate([0.0, 0.9, 0.9]).0.is_some());
+
+        // moving the slice past the edge leaves nothing in it
+        let julia = QuaternionJulia::new([0.0; 4], 1.2, 12);
+        assert!(julia.iterate([0.0, 0.0, 0.0]).0.is_some());
+
+        let options = QuaternionJuliaOptions::new(
+            [0.0; 4],
+            0.2,
+            10,
+            QuaternionJuliaMode::Surface,
+            RaymarchOptions::new(
+                64,
+                64,
+                Camera::orbit(0.0, 0.0, 3.0, 40.0),
+                [1.0, 2.0, 2.0],
+                Color::new(1.0, 1.0, 1.0, 1.0),
+                None,
+                Color::new(0.0, 0.0, 0.0, 1.0),
+                None,
+            ),
+            Some(4),
+            0.5,
+        );
+        // the sweep loops back round to where it started
+        assert_eq!(options.get_slice(0), 0.2);
+        assert!((options.get_slice(1) - 0.7).abs() < 1e-9);
+        assert!((options.get_slice(3) + 0.3).abs() < 1e-9);
+    }
+}
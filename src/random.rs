@@ -0,0 +1,282 @@
+//! Surprise images, with the image type and its options picked at random
+//!
+//! The options come from ranges that tend to look good rather than everything that's
+//! allowed. The result is a recipe, so it can be shown as the command line that makes
+//! the same image again. Daily images use the date as the seed
+
+use std::{
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use csscolorparser::Color;
+use fastrand::Rng;
+
+/// Places in the Mandelbrot set with lots of detail, as the center and the zoom up to
+/// which they still look interesting
+const MANDELBROT_VIEWS: &[(&str, f64)] = &[
+    ("-0.745,0.11", 200.0),
+    ("0.275,0.006", 100.0),
+    ("-0.7269,0.1889", 300.0),
+    ("-1.7497,0.0", 150.0),
+    ("-0.088,0.654", 200.0),
+    ("-0.7,0.0", 1.5),
+];
+
+/// Gets the recipe for a random image from the seed, only picking animations if asked
+pub fn get_random_recipe(seed: u64, animated: bool) -> Vec<String> {
+    let mut rng = Rng::with_seed(seed);
+    let image_types: &[&str] = match animated {
+        false => &[
+            "mandelbrot",
+            "perlin",
+            "farey",
+            "maze",
+            "reaction-diffusion",
+            "ulam-spiral",
+            "sierpinski",
+        ],
+        true => &[
+            "plasma-effect",
+            "tunnel",
+            "starfield",
+            "fire",
+            "ising",
+            "wave",
+        ],
+    };
+    let image_type = image_types[rng.usize(..image_types.len())];
+
+    let mut recipe = vec![image_type.to_string()];
+    let mut option = |name: &str, value: String| recipe.push(format!("--{}={}", name, value));
+    let (dark, light) = get_colors(&mut rng);
+    match image_type {
+        "mandelbrot" => {
+            let (center, max_zoom) = MANDELBROT_VIEWS[rng.usize(..MANDELBROT_VIEWS.len())];
+            option("center", center.to_string());
+            option(
+                "zoom",
+                format_number(get_log_uniform(&mut rng, 1.0, max_zoom)),
+            );
+            option("color", dark);
+            option("background-color", light);
+            recipe.push("--gradient".to_string());
+        }
+        "perlin" => {
+            option("size", "800".to_string());
+            option("color1", dark);
+            option("color2", light);
+            option("seed", rng.u32(..).to_string());
+        }
+        "farey" => {
+            option("n", rng.i32(4..=12).to_string());
+            option("color", dark);
+            option("fill-color", light);
+        }
+        "maze" => {
+            let algorithms = ["recursive-backtracker", "prims", "kruskals"];
+            option(
+                "algorithm",
+                algorithms[rng.usize(..algorithms.len())].to_string(),
+            );
+            let cells = rng.u32(15..=60);
+            option("columns", cells.to_string());
+            option("rows", cells.to_string());
+            option("cell-size", (800 / cells).to_string());
+            option("wall-color", dark);
+            option("path-color", light);
+            option("seed", rng.u32(..).to_string());
+        }
+        "reaction-diffusion" => {
+            let presets = ["spots", "stripes", "maze", "waves"];
+            option("preset", presets[rng.usize(..presets.len())].to_string());
+            option("size", "300".to_string());
+            option("color", dark);
+            option("background-color", light);
+            option("seed", rng.u32(..).to_string());
+        }
+        "ulam-spiral" => {
+            let modes = ["prime-only", "divisor"];
+            option("mode", modes[rng.usize(..modes.len())].to_string());
+            let side = rng.u32(101..=301) | 1;
+            option("size", (side * side).to_string());
+            option("color", dark);
+            option("background-color", light);
+        }
+        "sierpinski" => {
+            option("size", "800".to_string());
+            option("color", dark);
+            option("background-color", light);
+        }
+        "plasma-effect" => {
+            let palettes = ["rainbow", "fire", "ocean", "psychedelic", "grayscale"];
+            option("palette", palettes[rng.usize(..palettes.len())].to_string());
+            option("speed", rng.u32(1..=3).to_string());
+        }
+        "tunnel" => {
+            let textures = ["checkerboard", "noise"];
+            option("texture", textures[rng.usize(..textures.len())].to_string());
+            option("color1", light);
+            option("color2", dark);
+            option("seed", rng.u32(..).to_string());
+        }
+        "starfield" => {
+            option("stars", rng.u32(200..=1500).to_string());
+            option("speed", rng.u32(1..=3).to_string());
+            option("color", light);
+            option("background-color", dark);
+            option("seed", rng.u32(..).to_string());
+        }
+        "fire" => {
+            option("intensity", format_number(rng.f64() * 0.5 + 0.5));
+            option("seed", rng.u32(..).to_string());
+        }
+        "ising" => {
+            // around the critical temperature of about 2.27 is where the patterns are
+            option("temperature", format_number(rng.f64() * 1.2 + 1.8));
+            option("up-color", dark);
+            option("down-color", light);
+            option("seed", rng.u32(..).to_string());
+        }
+        _ => {
+            let waves = ["sine", "cosine"];
+            option("mode", "parametric".to_string());
+            option("x-wave", waves[rng.usize(..waves.len())].to_string());
+            option("y-wave", waves[rng.usize(..waves.len())].to_string());
+            option("x-frequency", rng.u32(1..=5).to_string());
+            option("y-frequency", rng.u32(1..=5).to_string());
+            option("color", dark);
+        }
+    }
+
+    recipe
+}
+
+/// A day in the calendar, used to pick the image for that day
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Date {
+    year: i32,
+    month: u32,
+    day: u32,
+}
+
+impl Date {
+    /// Gets the date today in UTC, so it's the same wherever it's run
+    pub fn today() -> Self {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        Self::from_days((seconds / 86400) as i64)
+    }
+
+    /// Works out the date from the days since 1970-01-01, using Howard Hinnant's
+    /// civil_from_days algorithm
+    fn from_days(days: i64) -> Self {
+        let days = days + 719468;
+        let era = days.div_euclid(146097);
+        let day_of_era = days.rem_euclid(146097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+        let month = if shifted_month < 10 {
+            shifted_month + 3
+        } else {
+            shifted_month - 9
+        } as u32;
+        let year = (year_of_era + era * 400 + (month <= 2) as i64) as i32;
+
+        Self { year, month, day }
+    }
+
+    /// Gets the seed for the day's image, which is the date written as a number
+    pub fn get_seed(&self) -> u64 {
+        // mixed so days next to each other don't start the generator in similar states
+        let date = self.year as u64 * 10000 + self.month as u64 * 100 + self.day as u64;
+        date.wrapping_mul(0x9E37_79B9_7F4A_7C15)
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "{:04}-{:02}-{:02}",
+            self.year, self.month, self.day
+        )
+    }
+}
+
+/// Parses a date written as year-month-day, like 2024-07-01
+pub fn parse_date(value: &str) -> Result<Date, String> {
+    let error = || "expected a date like 2024-07-01".to_string();
+    let mut parts = value.trim().splitn(3, '-');
+    let mut next_part = || parts.next().ok_or_else(error);
+    let year = next_part()?.parse::<i32>().map_err(|_| error())?;
+    let month = next_part()?.parse::<u32>().map_err(|_| error())?;
+    let day = next_part()?.parse::<u32>().map_err(|_| error())?;
+
+    let is_leap_year = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+    let days_in_month = match month {
+        2 if is_leap_year => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    };
+    if !(1..=12).contains(&month) || !(1..=days_in_month).contains(&day) {
+        return Err(format!("{} isn't a day in the calendar", value));
+    }
+
+    Ok(Date { year, month, day })
+}
+
+/// Gets a dark and a light colour with hues far enough apart to stand out
+fn get_colors(rng: &mut Rng) -> (String, String) {
+    let hue = rng.f32() * 360.0;
+    let other_hue = (hue + 90.0 + rng.f32() * 180.0) % 360.0;
+    let dark = Color::from_hsla(hue, 0.4 + rng.f32() * 0.4, 0.1 + rng.f32() * 0.15, 1.0);
+    let light = Color::from_hsla(other_hue, 0.5 + rng.f32() * 0.4, 0.7 + rng.f32() * 0.2, 1.0);
+
+    (dark.to_css_hex(), light.to_css_hex())
+}
+
+/// Picks a number where each doubling is as likely, which suits zooms
+fn get_log_uniform(rng: &mut Rng, min: f64, max: f64) -> f64 {
+    (min.ln() + rng.f64() * (max.ln() - min.ln())).exp()
+}
+
+/// Rounds to a few decimal places so the command line stays readable
+fn format_number(value: f64) -> String {
+    ((value * 1000.0).round() / 1000.0).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recipe::parse_recipe;
+
+    #[test]
+    fn test_random_recipes_parse() {
+        for seed in 0..50 {
+            for animated in [false, true] {
+                let recipe = get_random_recipe(seed, animated);
+                assert_eq!(recipe, get_random_recipe(seed, animated));
+                assert!(parse_recipe(&recipe).is_ok(), "{:?}", recipe);
+            }
+        }
+    }
+
+    #[test]
+    fn test_dates() {
+        assert_eq!(Date::from_days(0).to_string(), "1970-01-01");
+        assert_eq!(Date::from_days(19905).to_string(), "2024-07-01");
+        assert_eq!(Date::from_days(11016).to_string(), "2000-02-29");
+        assert_eq!(parse_date("2024-07-01"), Ok(Date::from_days(19905)));
+
+        for value in ["2023-02-29", "2024-13-01", "2024-7", "yesterday"] {
+            assert!(parse_date(value).is_err(), "{}", value);
+        }
+    }
+}
@@ -0,0 +1,493 @@
+//! Module for rendering 3D fractals by raymarching
+//!
+//! Fractals don't have a surface that a ray can be tested against, but they can give an
+//! estimate of how far a point is from them that's never too far. A ray from the
+//! camera is marched towards the fractal by going that far each step, as nothing can
+//! be closer, until it gets close enough to count as a hit. Hits are shaded with a
+//! light and with how tucked away the spot is.
+//! See <https://iquilezles.org/articles/distancefractals/> for more info
+
+use csscolorparser::Color;
+use image::{Rgba, RgbaImage};
+use rayon::prelude::*;
+
+use crate::{
+    PaletteType,
+    anaglyph::combine_views,
+    cancel::is_cancelled,
+    memory::{BYTES_PER_PIXEL, MemoryEstimate},
+    palette::get_palette_color,
+    parse_color,
+};
+
+pub(crate) type Vec3 = [f64; 3];
+
+/// The most steps a ray takes before it's counted as missing
+pub(crate) const MAX_STEP_NUM: u32 = 256;
+/// The fractals all fit inside a sphere this big, so rays start from it
+pub(crate) const BOUNDING_RADIUS: f64 = 2.0;
+/// How close to the surface a ray has to get to hit it, as a fraction of the size of a
+/// pixel at that distance
+pub(crate) const HIT_FRACTION: f64 = 0.5;
+
+/// How far apart each sample for ambient occlusion is, and how many there are
+const OCCLUSION_STEP: f64 = 0.03;
+const OCCLUSION_SAMPLE_NUM: u32 = 5;
+
+pub(crate) fn add(a: Vec3, b: Vec3) -> Vec3 {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+pub(crate) fn scale(a: Vec3, amount: f64) -> Vec3 {
+    a.map(|value| value * amount)
+}
+
+pub(crate) fn dot(a: Vec3, b: Vec3) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+pub(crate) fn cross(a: Vec3, b: Vec3) -> Vec3 {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+pub(crate) fn normalize(a: Vec3) -> Vec3 {
+    scale(a, 1.0 / dot(a, a).sqrt())
+}
+
+/// A shape that can be raymarched
+pub trait DistanceEstimator: Sync {
+    /// Gets an estimate of how far a point is from the surface, which is never more
+    /// than the real distance, along with a value that picks the colour from a palette
+    fn get_distance(&self, point: Vec3) -> (f64, f64);
+}
+
+/// Where the camera is and which ways it faces, with y going up
+#[derive(Clone, Copy, Debug)]
+pub struct Camera {
+    pub(crate) position: Vec3,
+    pub(crate) forward: Vec3,
+    pub(crate) right: Vec3,
+    pub(crate) up: Vec3,
+    /// How far the edge of the view is from the middle at one unit in front
+    pub(crate) view_scale: f64,
+}
+
+impl Camera {
+    /// A camera looking at the middle from a distance, turned around it by the yaw and
+    /// up over it by the pitch, both in degrees, with the field of view going from the
+    /// top of the image to the bottom
+    pub fn orbit(yaw: f64, pitch: f64, distance: f64, field_of_view: f64) -> Self {
+        let (yaw, pitch) = (yaw.to_radians(), pitch.to_radians());
+        let position = [
+            distance * pitch.cos() * yaw.sin(),
+            distance * pitch.sin(),
+            distance * pitch.cos() * yaw.cos(),
+        ];
+        let forward = normalize(scale(position, -1.0));
+        // straight up or down has no right, so any way will do
+        let right = match cross(forward, [0.0, 1.0, 0.0]) {
+            right if dot(right, right) > 1e-12 => normalize(right),
+            _ => [1.0, 0.0, 0.0],
+        };
+
+        Self {
+            position,
+            forward,
+            right,
+            up: cross(right, forward),
+            view_scale: (field_of_view.to_radians() / 2.0).tan(),
+        }
+    }
+
+    /// The same camera moved sideways and turned to keep looking at the middle, which
+    /// is how each eye sees it
+    fn shifted(&self, offset: f64) -> Self {
+        let position = add(self.position, scale(self.right, offset));
+        let forward = normalize(scale(position, -1.0));
+        let right = normalize(cross(forward, self.up));
+
+        Self {
+            position,
+            forward,
+            right,
+            up: cross(right, forward),
+            view_scale: self.view_scale,
+        }
+    }
+
+    /// Gets the direction of the ray through the middle of a pixel
+    fn get_ray(&self, x: u32, y: u32, width: u32, height: u32) -> Vec3 {
+        let aspect = width as f64 / height as f64;
+        let across = (2.0 * (x as f64 + 0.5) / width as f64 - 1.0) * self.view_scale * aspect;
+        let down = (1.0 - 2.0 * (y as f64 + 0.5) / height as f64) * self.view_scale;
+
+        normalize(add(
+            self.forward,
+            add(scale(self.right, across), scale(self.up, down)),
+        ))
+    }
+}
+
+#[derive(Clone)]
+pub struct RaymarchOptions {
+    width: u32,
+    height: u32,
+    camera: Camera,
+    /// The direction the light comes from
+    light: Vec3,
+    color: Color,
+    /// Colours the surface with the value the shape gives instead of using color when
+    /// given
+    palette: Option<PaletteType>,
+    background_color: Color,
+    /// Renders a red and cyan anaglyph with the eyes this far apart when given
+    eye_distance: Option<f64>,
+}
+
+impl RaymarchOptions {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        width: u32,
+        height: u32,
+        camera: Camera,
+        light: Vec3,
+        color: Color,
+        palette: Option<PaletteType>,
+        background_color: Color,
+        eye_distance: Option<f64>,
+    ) -> Self {
+        Self {
+            width,
+            height,
+            camera,
+            light: normalize(light),
+            color,
+            palette,
+            background_color,
+            eye_distance,
+        }
+    }
+
+    /// Renders a view with these options, or one from each eye combined into an
+    /// anaglyph if there's an eye distance
+    pub fn render_views(&self, render_view: impl Fn(&RaymarchOptions) -> RgbaImage) -> RgbaImage {
+        let Some(eye_distance) = self.eye_distance else {
+            return render_view(self);
+        };
+        let get_eye = |offset: f64| Self {
+            camera: self.camera.shifted(offset),
+            eye_distance: None,
+            ..self.clone()
+        };
+
+        combine_views(
+            &render_view(&get_eye(-eye_distance / 2.0)),
+            &render_view(&get_eye(eye_distance / 2.0)),
+        )
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    pub fn color(&self) -> &Color {
+        &self.color
+    }
+
+    pub fn palette(&self) -> Option<PaletteType> {
+        self.palette
+    }
+
+    pub fn background_color(&self) -> &Color {
+        &self.background_color
+    }
+
+    pub fn eye_distance(&self) -> Option<f64> {
+        self.eye_distance
+    }
+
+    /// How big a pixel is at one unit in front of the camera
+    fn get_pixel_size(&self) -> f64 {
+        2.0 * self.camera.view_scale / self.height as f64
+    }
+
+    /// Marches a ray from the camera until it hits the surface, giving how far it went
+    pub fn march(&self, shape: &impl DistanceEstimator, direction: Vec3) -> Option<f64> {
+        let origin = self.camera.position;
+        // starts where the ray meets the sphere everything's inside of
+        let middle = -dot(origin, direction);
+        let closest = dot(origin, origin) - middle * middle;
+        if closest > BOUNDING_RADIUS * BOUNDING_RADIUS {
+            return None;
+        }
+        let half_chord = (BOUNDING_RADIUS * BOUNDING_RADIUS - closest).sqrt();
+        let (mut distance, end) = ((middle - half_chord).max(0.0), middle + half_chord);
+
+        let pixel_size = self.get_pixel_size();
+        for _ in 0..MAX_STEP_NUM {
+            let point = add(origin, scale(direction, distance));
+            let (step, _) = shape.get_distance(point);
+            if step < distance * pixel_size * HIT_FRACTION {
+                return Some(distance);
+            }
+            distance += step;
+            if distance > end {
+                return None;
+            }
+        }
+
+        None
+    }
+
+    /// Gets how far each pixel's ray went before hitting the surface in row order, None
+    /// if it missed
+    pub fn get_distances(&self, shape: &impl DistanceEstimator) -> Vec<Option<f64>> {
+        let (width, height) = (self.width, self.height);
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .collect::<Vec<_>>()
+            .into_par_iter()
+            .map(|(x, y)| match is_cancelled() {
+                true => None,
+                false => self.march(shape, self.camera.get_ray(x, y, width, height)),
+            })
+            .collect()
+    }
+
+    /// Shades the pixels given how far each one's ray went, which can come from
+    /// get_distances or from the GPU
+    pub fn render(&self, shape: &impl DistanceEstimator, distances: Vec<Option<f64>>) -> RgbaImage {
+        let (width, height) = (self.width, self.height);
+        let background_color = self.background_color.to_rgba8();
+        let pixels: Vec<[u8; 4]> = distances
+            .into_par_iter()
+            .enumerate()
+            .map(|(index, distance)| match distance {
+                Some(distance) => {
+                    let (x, y) = (index as u32 % width, index as u32 / width);
+                    self.shade(shape, self.camera.get_ray(x, y, width, height), distance)
+                }
+                None => background_color,
+            })
+            .collect();
+
+        RgbaImage::from_fn(width, height, |x, y| Rgba(pixels[(y * width + x) as usize]))
+    }
+
+    /// Gets how the distance changes around a point, which points the way the surface
+    /// faces
+    fn get_gradient(&self, shape: &impl DistanceEstimator, point: Vec3, offset: f64) -> Vec3 {
+        let get_slope = |axis: usize| {
+            let (mut before, mut after) = (point, point);
+            before[axis] -= offset;
+            after[axis] += offset;
+            (shape.get_distance(after).0 - shape.get_distance(before).0) / (2.0 * offset)
+        };
+
+        [get_slope(0), get_slope(1), get_slope(2)]
+    }
+
+    /// Gets how much light gets to a point, from 0 if it's tucked away to 1 if it's out
+    /// in the open, by checking how close the surface is going out from it compared to
+    /// how far out it's checking. The estimate grows slower than the real distance, by
+    /// the length of the gradient out in the open, so it's compared to that
+    fn get_occlusion(&self, shape: &impl DistanceEstimator, point: Vec3, gradient: Vec3) -> f64 {
+        let growth = dot(gradient, gradient).sqrt();
+        let normal = scale(gradient, 1.0 / growth);
+        let openness: f64 = (1..=OCCLUSION_SAMPLE_NUM)
+            .map(|sample| {
+                let distance = sample as f64 * OCCLUSION_STEP;
+                let (surface_distance, _) = shape.get_distance(add(point, scale(normal, distance)));
+                (surface_distance / (distance * growth)).clamp(0.0, 1.0)
+            })
+            .sum();
+
+        openness / OCCLUSION_SAMPLE_NUM as f64
+    }
+
+    fn shade(&self, shape: &impl DistanceEstimator, direction: Vec3, distance: f64) -> [u8; 4] {
+        let point = add(self.camera.position, scale(direction, distance));
+        let offset = (distance * self.get_pixel_size() * HIT_FRACTION).max(1e-6);
+        let gradient = self.get_gradient(shape, point, offset);
+        let normal = normalize(gradient);
+        let occlusion = self.get_occlusion(shape, point, gradient);
+
+        let base_color = match self.palette {
+            Some(palette) => {
+                let (_, position) = shape.get_distance(point);
+                get_palette_color(palette, position.clamp(0.0, 1.0) * 0.8)
+            }
+            None => self.color.to_rgba8(),
+        };
+        // diffuse light from the light, a little everywhere so the shadows aren't black,
+        // and a shine where the light reflects towards the camera
+        let diffuse = dot(normal, self.light).max(0.0);
+        let halfway = normalize(add(self.light, scale(direction, -1.0)));
+        let shine = dot(normal, halfway).max(0.0).powi(32) * 0.4;
+        let brightness = (0.2 + 0.8 * diffuse) * occlusion;
+
+        let [red, green, blue, alpha] = base_color;
+        let light_channel = |channel: u8| {
+            (channel as f64 * brightness + 255.0 * shine * occlusion)
+                .round()
+                .clamp(0.0, 255.0) as u8
+        };
+        [
+            light_channel(red),
+            light_channel(green),
+            light_channel(blue),
+            alpha,
+        ]
+    }
+}
+
+/// Parses a direction given as x,y,z, with y going up
+fn parse_direction(value: &str) -> Result<Vec3, String> {
+    let parts: Vec<f64> = value
+        .split(',')
+        .map(|part| {
+            part.trim()
+                .parse::<f64>()
+                .map_err(|error| error.to_string())
+        })
+        .collect::<Result<_, _>>()?;
+    match parts[..] {
+        [x, y, z] if x != 0.0 || y != 0.0 || z != 0.0 => Ok([x, y, z]),
+        [_, _, _] => Err("the direction can't be 0,0,0".to_string()),
+        _ => Err("expected x,y,z like 1,2,1".to_string()),
+    }
+}
+
+// args for the camera, light and colours shared by the raymarched fractals, as a plain
+// comment so it doesn't become the about text of the subcommands flattening it
+#[derive(clap::Args, Debug)]
+pub struct RaymarchArgs {
+    #[arg(long, default_value = "800", value_parser = crate::parse_size)]
+    width: u32,
+
+    #[arg(long, default_value = "800", value_parser = crate::parse_size)]
+    height: u32,
+
+    /// How far round the fractal the camera is, in degrees
+    #[arg(long, default_value = "30.0", allow_negative_numbers = true)]
+    yaw: f64,
+
+    /// How far up over the fractal the camera is, in degrees
+    #[arg(long, default_value = "20.0", allow_negative_numbers = true)]
+    pitch: f64,
+
+    /// How far the camera is from the middle of the fractal, which is about 2 across
+    #[arg(long, default_value = "3.6")]
+    distance: f64,
+
+    /// How much the camera sees from the top of the image to the bottom, in degrees
+    #[arg(long, default_value = "40.0")]
+    fov: f64,
+
+    /// The direction the light comes from, as x,y,z with y going up
+    #[arg(long, default_value = "1,2,2", value_parser = parse_direction, allow_hyphen_values = true)]
+    light: Vec3,
+
+    #[arg(short, long, default_value = "#e0b080", value_parser = parse_color)]
+    color: Color,
+
+    /// Colours the surface by how close each point's orbit gets to the middle instead
+    /// of using the colour
+    #[arg(short, long)]
+    palette: Option<PaletteType>,
+
+    /// The colour behind the fractal, which can be transparent
+    #[arg(short, long, alias = "background", default_value = "black", value_parser = parse_color)]
+    background_color: Color,
+
+    /// Renders a red and cyan anaglyph to be seen in 3D through the glasses
+    #[arg(long)]
+    anaglyph: bool,
+
+    /// How far apart the eyes are for the anaglyph, with the fractal being about 2
+    /// across
+    #[arg(long, default_value = "0.12")]
+    eye_distance: f64,
+}
+
+impl RaymarchArgs {
+    /// Checks the camera can see something, giving what's wrong if it can't
+    pub fn check(&self) -> Result<(), String> {
+        match self.distance > 0.0 && (1.0..180.0).contains(&self.fov) {
+            true => Ok(()),
+            false => Err(
+                "the distance has to be more than 0 and the fov between 1 and 180 degrees"
+                    .to_string(),
+            ),
+        }
+    }
+
+    /// The frames of the view, with the anaglyph rendering both eyes before joining them
+    pub fn estimate_memory(&self, frame_num: u32) -> MemoryEstimate {
+        let estimate = MemoryEstimate::frames(self.width, self.height, frame_num);
+        match self.anaglyph {
+            true => estimate.with_pixel_buffer(self.width, self.height, 2 * BYTES_PER_PIXEL),
+            false => estimate,
+        }
+    }
+
+    pub fn into_options(self) -> RaymarchOptions {
+        RaymarchOptions::new(
+            self.width,
+            self.height,
+            Camera::orbit(self.yaw, self.pitch, self.distance, self.fov),
+            self.light,
+            self.color,
+            self.palette,
+            self.background_color,
+            self.anaglyph.then_some(self.eye_distance),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A ball with a radius of 1, which the distance is exact for
+    struct Ball;
+
+    impl DistanceEstimator for Ball {
+        fn get_distance(&self, point: Vec3) -> (f64, f64) {
+            (dot(point, point).sqrt() - 1.0, 0.0)
+        }
+    }
+
+    #[test]
+    fn test_march() {
+        let options = RaymarchOptions::new(
+            64,
+            64,
+            Camera::orbit(0.0, 0.0, 3.0, 40.0),
+            [1.0, 2.0, 2.0],
+            Color::new(1.0, 1.0, 1.0, 1.0),
+            None,
+            Color::new(0.0, 0.0, 0.0, 1.0),
+            None,
+        );
+        // straight at the middle hits the front of the ball
+        let hit = options.march(&Ball, [0.0, 0.0, -1.0]).unwrap();
+        assert!((hit - 2.0).abs() < 0.01);
+        // and pointing away or past it misses
+        assert!(options.march(&Ball, [0.0, 0.0, 1.0]).is_none());
+        assert!(options.march(&Ball, normalize([1.0, 0.0, -1.0])).is_none());
+
+        let camera = Camera::orbit(90.0, 0.0, 3.0, 40.0);
+        assert!((camera.position[0] - 3.0).abs() < 1e-9);
+        assert!((dot(camera.forward, [-1.0, 0.0, 0.0]) - 1.0).abs() < 1e-9);
+        assert!((dot(camera.up, [0.0, 1.0, 0.0]) - 1.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,341 @@
+//! Gray-Scott reaction diffusion, which makes the patterns Turing described
+//!
+//! Two chemicals A and B diffuse across the grid at different rates, with B being made
+//! from A and slowly removed. Depending on the feed and kill rates this makes spots,
+//! stripes, mazes and waves. See <https://karlsims.com/rd.html> for a great explanation
+//! and <http://mrob.com/pub/comp/xmorphia/> for a map of the parameters.
+
+use csscolorparser::Color;
+use image::{DynamicImage, ImageError, Rgba, RgbaImage};
+
+use crate::{
+    TuringPreset,
+    cancel::is_cancelled,
+    generator::{Generated, Generator, GeneratorError},
+    memory::MemoryEstimate,
+    palette::interpolate_color,
+    parse_color, parse_size,
+};
+
+const DIFFUSION_A: f64 = 1.0;
+const DIFFUSION_B: f64 = 0.5;
+/// The number of random squares of B to start off with when there's no seed image
+const SEED_SQUARE_NUM: u32 = 20;
+
+impl TuringPreset {
+    /// The feed and kill rates for the preset
+    fn get_rates(self) -> (f64, f64) {
+        match self {
+            TuringPreset::Spots => (0.0367, 0.0649),
+            TuringPreset::Stripes => (0.0545, 0.062),
+            TuringPreset::Maze => (0.029, 0.057),
+            TuringPreset::Waves => (0.018, 0.051),
+        }
+    }
+}
+
+pub struct ReactionDiffusionOptions {
+    size: u32,
+    steps: u32,
+    feed_rate: f64,
+    kill_rate: f64,
+    seed_image: Option<DynamicImage>,
+    seed: Option<u64>,
+    color: Color,
+    background_color: Color,
+}
+
+impl ReactionDiffusionOptions {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        size: u32,
+        steps: u32,
+        preset: TuringPreset,
+        feed_rate: Option<f64>,
+        kill_rate: Option<f64>,
+        seed_image: Option<DynamicImage>,
+        seed: Option<u64>,
+        color: Color,
+        background_color: Color,
+    ) -> Self {
+        let (preset_feed_rate, preset_kill_rate) = preset.get_rates();
+        Self {
+            size,
+            steps,
+            feed_rate: feed_rate.unwrap_or(preset_feed_rate),
+            kill_rate: kill_rate.unwrap_or(preset_kill_rate),
+            seed_image,
+            seed,
+            color,
+            background_color,
+        }
+    }
+}
+
+struct Grid {
+    size: usize,
+    a: Vec<f64>,
+    b: Vec<f64>,
+}
+
+impl Grid {
+    fn new(options: &ReactionDiffusionOptions) -> Self {
+        let size = options.size as usize;
+        let mut grid = Self {
+            size,
+            a: vec![1.0; size * size],
+            b: vec![0.0; size * size],
+        };
+
+        match &options.seed_image {
+            Some(seed_image) => {
+                // dark pixels of the image are where B starts off
+                let luma = seed_image
+                    .resize_exact(
+                        options.size,
+                        options.size,
+                        image::imageops::FilterType::Triangle,
+                    )
+                    .to_luma_alpha8();
+                for (index, pixel) in luma.pixels().enumerate() {
+                    if pixel.0[0] < 128 && pixel.0[1] >= 128 {
+                        grid.b[index] = 1.0;
+                    }
+                }
+            }
+            None => {
+                let mut rng = match options.seed {
+                    Some(seed) => fastrand::Rng::with_seed(seed),
+                    None => fastrand::Rng::new(),
+                };
+                let square_size = (size / 20).max(1);
+                for _ in 0..SEED_SQUARE_NUM {
+                    let x = rng.usize(0..size - square_size + 1);
+                    let y = rng.usize(0..size - square_size + 1);
+                    for square_y in y..y + square_size {
+                        for square_x in x..x + square_size {
+                            grid.b[square_y * size + square_x] = 1.0;
+                        }
+                    }
+                }
+            }
+        }
+
+        grid
+    }
+
+    /// 3x3 laplacian with the weights from the Karl Sims article, wrapping at the edges
+    fn laplacian(&self, values: &[f64], x: usize, y: usize) -> f64 {
+        let size = self.size;
+        let left = (x + size - 1) % size;
+        let right = (x + 1) % size;
+        let up = (y + size - 1) % size;
+        let down = (y + 1) % size;
+
+        let adjacent = values[y * size + left]
+            + values[y * size + right]
+            + values[up * size + x]
+            + values[down * size + x];
+        let diagonal = values[up * size + left]
+            + values[up * size + right]
+            + values[down * size + left]
+            + values[down * size + right];
+
+        0.2 * adjacent + 0.05 * diagonal - values[y * size + x]
+    }
+
+    fn step(&mut self, feed_rate: f64, kill_rate: f64) {
+        let size = self.size;
+        let mut next_a = vec![0.0; size * size];
+        let mut next_b = vec![0.0; size * size];
+        for y in 0..size {
+            for x in 0..size {
+                let index = y * size + x;
+                let a = self.a[index];
+                let b = self.b[index];
+                let reaction = a * b * b;
+
+                next_a[index] = a + DIFFUSION_A * self.laplacian(&self.a, x, y) - reaction
+                    + feed_rate * (1.0 - a);
+                next_b[index] = b + DIFFUSION_B * self.laplacian(&self.b, x, y) + reaction
+                    - (kill_rate + feed_rate) * b;
+            }
+        }
+        self.a = next_a;
+        self.b = next_b;
+    }
+
+    fn draw(&self, color: [u8; 4], background_color: [u8; 4]) -> RgbaImage {
+        let mut image = RgbaImage::new(self.size as u32, self.size as u32);
+        for (pixel, (a, b)) in image.pixels_mut().zip(self.a.iter().zip(self.b.iter())) {
+            // a - b goes from about 1 where there's no B down to about 0.3 where there's
+            // lots of it, so stretch that out to use the full range of colours
+            let amount = ((1.0 - (a - b)) * 1.5).clamp(0.0, 1.0);
+            *pixel = Rgba(interpolate_color(color, background_color, amount));
+        }
+        image
+    }
+}
+
+pub fn generate_reaction_diffusion_image(options: ReactionDiffusionOptions) -> DynamicImage {
+    let mut grid = Grid::new(&options);
+    for _ in 0..options.steps {
+        grid.step(options.feed_rate, options.kill_rate);
+    }
+
+    DynamicImage::ImageRgba8(grid.draw(
+        options.color.to_rgba8(),
+        options.background_color.to_rgba8(),
+    ))
+}
+
+/// Generates frames of the pattern forming, evenly spaced through the steps
+pub fn generate_reaction_diffusion_images(
+    options: ReactionDiffusionOptions,
+    frame_num: u32,
+) -> Vec<RgbaImage> {
+    let color = options.color.to_rgba8();
+    let background_color = options.background_color.to_rgba8();
+    let steps_per_frame = (options.steps / frame_num.max(1)).max(1);
+
+    let mut grid = Grid::new(&options);
+    let mut images = vec![grid.draw(color, background_color)];
+    for step in 1..=options.steps {
+        if is_cancelled() {
+            break;
+        }
+        grid.step(options.feed_rate, options.kill_rate);
+        if step % steps_per_frame == 0 {
+            images.push(grid.draw(color, background_color));
+        }
+    }
+
+    images
+}
+
+#[derive(clap::Args, Debug)]
+pub struct ReactionDiffusionArgs {
+    #[arg(short, long, default_value = "256", value_parser = parse_size)]
+    size: u32,
+
+    /// The number of simulation steps to run
+    #[arg(long, default_value = "5000")]
+    steps: u32,
+
+    /// The kind of pattern to make, sets the feed and kill rates
+    #[arg(short, long, default_value = "spots")]
+    preset: TuringPreset,
+
+    /// Overrides the feed rate of the preset
+    #[arg(long)]
+    feed_rate: Option<f64>,
+
+    /// Overrides the kill rate of the preset
+    #[arg(long)]
+    kill_rate: Option<f64>,
+
+    /// An image whose dark areas are used as the starting pattern
+    #[arg(long)]
+    seed_image: Option<String>,
+
+    /// The seed used for the starting pattern, random if not given
+    #[arg(long)]
+    seed: Option<u64>,
+
+    #[arg(short, long, default_value = "black", value_parser = parse_color)]
+    color: Color,
+
+    #[arg(short, long, default_value = "white", value_parser = parse_color)]
+    background_color: Color,
+
+    /// Animate the pattern forming
+    #[arg(long, default_value = "false")]
+    animate: bool,
+
+    /// The number of frames when animating
+    #[arg(short, long, default_value = "100", value_parser = parse_size)]
+    frames: u32,
+}
+
+pub struct ReactionDiffusionGenerator;
+
+impl Generator for ReactionDiffusionGenerator {
+    type Args = ReactionDiffusionArgs;
+
+    fn name(&self) -> &'static str {
+        "reaction-diffusion"
+    }
+
+    fn is_animated(&self, args: &ReactionDiffusionArgs) -> bool {
+        args.animate
+    }
+
+    fn estimate_memory(&self, args: &ReactionDiffusionArgs) -> Option<MemoryEstimate> {
+        let frame_num = match args.animate {
+            true => args.frames,
+            false => 1,
+        };
+        // the two chemicals for every pixel, twice over to step from one to the next
+        Some(
+            MemoryEstimate::frames(args.size, args.size, frame_num)
+                .with_pixel_buffer(args.size, args.size, 32),
+        )
+    }
+
+    fn generate(&self, args: ReactionDiffusionArgs) -> Result<Generated, GeneratorError> {
+        let seed_image = match &args.seed_image {
+            Some(path) => Some(image::open(path).map_err(|error| match error {
+                ImageError::IoError(source) => GeneratorError::ReadFile {
+                    path: path.clone(),
+                    source,
+                },
+                error => GeneratorError::Failed(format!("opening {}: {}", path, error)),
+            })?),
+            None => None,
+        };
+        let options = ReactionDiffusionOptions::new(
+            args.size,
+            args.steps,
+            args.preset,
+            args.feed_rate,
+            args.kill_rate,
+            seed_image,
+            args.seed,
+            args.color,
+            args.background_color,
+        );
+
+        Ok(match args.animate {
+            true => Generated::Frames(generate_reaction_diffusion_images(options, args.frames)),
+            false => Generated::Still(generate_reaction_diffusion_image(options).into_rgba8()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{GrayImage, Luma};
+
+    use super::*;
+
+    #[test]
+    fn test_seed_image() {
+        // the dark left half is where B starts off
+        let seed_image = GrayImage::from_fn(8, 8, |x, _| Luma([if x < 4 { 0 } else { 255 }]));
+        let options = ReactionDiffusionOptions::new(
+            4,
+            0,
+            TuringPreset::Spots,
+            None,
+            None,
+            Some(DynamicImage::ImageLuma8(seed_image)),
+            None,
+            Color::new(0.0, 0.0, 0.0, 1.0),
+            Color::new(1.0, 1.0, 1.0, 1.0),
+        );
+        let grid = Grid::new(&options);
+        for (index, &b) in grid.b.iter().enumerate() {
+            assert_eq!(b, if index % 4 < 2 { 1.0 } else { 0.0 });
+        }
+    }
+}
@@ -0,0 +1,292 @@
+//! The args an image was made with, stored in its PNG file so it can be made again
+//!
+//! A recipe is the image type followed by each option given on the command line as
+//! `--name=value`, or just `--name` for flags. Options about where the image goes,
+//! like the output file, are left out
+
+use std::{fs::File, io::BufReader};
+
+use clap::{ArgMatches, CommandFactory, FromArgMatches, parser::ValueSource};
+
+use fun_images::post::PostArgs;
+
+use crate::{Args, error::Error, presets::add_preset_options};
+
+/// The keyword of the PNG text chunk the recipe is kept in
+pub const RECIPE_KEYWORD: &str = "fun-images";
+
+/// Options that don't change what the image looks like
+pub const OUTPUT_OPTIONS: &[&str] = &[
+    "output",
+    "frames_dir",
+    "force",
+    "preview",
+    "verbose",
+    "quiet",
+    "set_wallpaper",
+    "save_partial",
+    "checkpoint",
+    "resume",
+    "threads",
+    "memory_budget",
+    "yes",
+    "tiles",
+    "output_format",
+    "ascii_width",
+    "ascii_color",
+    "gui",
+    "report",
+    "report_file",
+];
+
+/// Gets the recipe for the image the args will make
+pub fn get_recipe(matches: &ArgMatches) -> Vec<String> {
+    let Some((image_type, sub_matches)) = matches.subcommand() else {
+        return Vec::new();
+    };
+    // building the command copies the global options into the subcommands
+    let mut command = Args::command();
+    command.build();
+    let Some(subcommand) = command.find_subcommand(image_type) else {
+        return Vec::new();
+    };
+
+    let mut recipe = vec![image_type.to_string()];
+    // positional args like a script's path go after the options
+    let mut positionals = Vec::new();
+    for id in sub_matches.ids() {
+        let id = id.as_str();
+        if OUTPUT_OPTIONS.contains(&id)
+            || sub_matches.value_source(id) != Some(ValueSource::CommandLine)
+        {
+            continue;
+        }
+        let Some(arg) = subcommand.get_arguments().find(|arg| arg.get_id() == id) else {
+            continue;
+        };
+        let occurrences = sub_matches.get_raw_occurrences(id).into_iter().flatten();
+        for values in occurrences {
+            let values: Vec<_> = values
+                .map(|value| value.to_string_lossy().into_owned())
+                .collect();
+            match (arg.is_positional(), arg.get_long(), values.as_slice()) {
+                (true, _, _) => positionals.extend(values),
+                (false, Some(long), [value]) => recipe.extend(get_option_arg(long, value)),
+                // options taking more than one value like --glow 12 1.5 can't use =
+                (false, Some(long), _) => {
+                    recipe.push(format!("--{}", long));
+                    recipe.extend(values);
+                }
+                (false, None, _) => {}
+            }
+        }
+    }
+    recipe.extend(positionals);
+
+    recipe
+}
+
+/// Turns an option into an arg, where flags are turned on with true and left off
+/// with false
+pub fn get_option_arg(name: &str, value: &str) -> Option<String> {
+    match value {
+        "true" => Some(format!("--{}", name)),
+        "false" => None,
+        value => Some(format!("--{}={}", name, value)),
+    }
+}
+
+pub fn read_recipe(path: &str) -> Result<Vec<String>, Error> {
+    let file = File::open(path).map_err(|io_error| Error::ReadRecipe {
+        path: path.to_string(),
+        message: io_error.to_string(),
+    })?;
+    let reader = png::Decoder::new(BufReader::new(file))
+        .read_info()
+        .map_err(|decoding_error| Error::ReadRecipe {
+            path: path.to_string(),
+            message: decoding_error.to_string(),
+        })?;
+
+    let recipe = reader
+        .info()
+        .utf8_text
+        .iter()
+        .find(|chunk| chunk.keyword == RECIPE_KEYWORD)
+        .and_then(|chunk| chunk.get_text().ok())
+        .ok_or_else(|| Error::ReadRecipe {
+            path: path.to_string(),
+            message: "no recipe found, only PNG files saved by fun-images have one".to_string(),
+        })?;
+
+    Ok(recipe.lines().map(str::to_string).collect())
+}
+
+/// Parses the recipe the same way as the command line
+pub fn parse_recipe(recipe: &[String]) -> Result<ArgMatches, Error> {
+    let mut words: Vec<String> = std::iter::once("fun-images".to_string())
+        .chain(recipe.iter().cloned())
+        .collect();
+    get_seeded_matches(&mut words).map_err(get_clap_error)
+}
+
+/// Parses the words, adding the options of any presets picked and seeds for the image
+/// type and random effects when they aren't given. These are added to the words so they
+/// end up in the recipe and the report, letting the image be made the same again
+pub fn get_seeded_matches(words: &mut Vec<String>) -> Result<ArgMatches, clap::Error> {
+    let command = Args::command();
+    let matches = command.clone().try_get_matches_from(words.iter())?;
+    let Some((name, sub_matches)) = matches.subcommand() else {
+        return Ok(matches);
+    };
+    let added_presets = add_preset_options(words, &matches);
+    // looked up on the command, as asking the matches about an option the image type
+    // doesn't have only fails with debug assertions on
+    let takes_seed = command
+        .find_subcommand(name)
+        .is_some_and(|subcommand| subcommand.get_arguments().any(|arg| arg.get_id() == "seed"));
+    let mut seed_options = match takes_seed && !sub_matches.contains_id("seed") {
+        true => vec!["seed"],
+        false => Vec::new(),
+    };
+    // the effects' options are global, so they're in the top level matches too
+    if let Ok(post_args) = PostArgs::from_arg_matches(&matches) {
+        seed_options.extend(post_args.get_missing_seeds());
+    }
+    for option in seed_options.iter() {
+        words.push(format!("--{}={}", option, fastrand::u64(..)));
+    }
+
+    match added_presets || !seed_options.is_empty() {
+        true => Args::command().try_get_matches_from(words.iter()),
+        false => Ok(matches),
+    }
+}
+
+/// Keeps just the first line of clap's message, as the usage it shows is for the
+/// recipe rather than what was typed
+pub fn get_clap_error(clap_error: clap::Error) -> Error {
+    let message = clap_error.to_string();
+    let message = message.lines().next().unwrap_or_default();
+    Error::Reproduce(message.trim_start_matches("error: ").to_string())
+}
+
+/// Changes an option in the recipe given as name=value, replacing it if it's already there
+pub fn apply_override(recipe: &mut Vec<String>, option: &str) -> Result<(), Error> {
+    let (name, value) = option
+        .split_once('=')
+        .ok_or_else(|| Error::Reproduce(format!("expected name=value, got {}", option)))?;
+    let name = name.trim_start_matches("--").replace('_', "-");
+
+    let flag = format!("--{}", name);
+    let prefix = format!("--{}=", name);
+    recipe.retain(|arg| *arg != flag && !arg.starts_with(&prefix));
+    recipe.extend(get_option_arg(&name, value));
+
+    Ok(())
+}
+
+/// Sets the width, or size for square images, keeping the aspect ratio if there's also
+/// a height
+pub fn apply_width(recipe: &mut Vec<String>, width: u32) -> Result<(), Error> {
+    let image_type = recipe
+        .first()
+        .ok_or_else(|| Error::Reproduce("the recipe is empty".to_string()))?;
+    let matches = parse_recipe(recipe)?;
+    let Some(sub_matches) = matches.subcommand_matches(image_type) else {
+        return Err(Error::Reproduce("the recipe has no image type".to_string()));
+    };
+    let get_value = |id: &str| sub_matches.try_get_one::<u32>(id).ok().flatten().copied();
+
+    if let Some(old_width) = get_value("width") {
+        if let Some(old_height) = get_value("height") {
+            let height = (old_height as u64 * width as u64 / old_width as u64).max(1);
+            apply_override(recipe, &format!("height={}", height))?;
+        }
+        apply_override(recipe, &format!("width={}", width))
+    } else if get_value("size").is_some() {
+        apply_override(recipe, &format!("size={}", width))
+    } else {
+        Err(Error::Reproduce(format!(
+            "{} images don't have a width or size to change",
+            image_type
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_test_recipe(args: &[&str]) -> Vec<String> {
+        get_recipe(&Args::command().get_matches_from(args))
+    }
+
+    #[test]
+    fn test_recipe() {
+        let mut recipe = get_test_recipe(&[
+            "fun-images",
+            "-o",
+            "out.png",
+            "mandelbrot",
+            "-c",
+            "red",
+            "--gradient",
+            "--zoom",
+            "20",
+        ]);
+        recipe[1..].sort();
+        assert_eq!(
+            recipe,
+            vec!["mandelbrot", "--color=red", "--gradient", "--zoom=20"]
+        );
+
+        apply_override(&mut recipe, "zoom=40").unwrap();
+        apply_override(&mut recipe, "gradient=false").unwrap();
+        apply_width(&mut recipe, 3200).unwrap();
+        recipe[1..].sort();
+        assert_eq!(
+            recipe,
+            vec![
+                "mandelbrot",
+                "--color=red",
+                "--height=2400",
+                "--width=3200",
+                "--zoom=40"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_seeded_matches() {
+        let get_seeded_recipe = |args: &[&str]| {
+            let mut words = args.iter().map(|arg| arg.to_string()).collect();
+            get_recipe(&get_seeded_matches(&mut words).unwrap())
+        };
+
+        let recipe = get_seeded_recipe(&["fun-images", "perlin", "--size", "32"]);
+        assert!(recipe.iter().any(|arg| arg.starts_with("--seed=")));
+        let recipe = get_seeded_recipe(&["fun-images", "perlin", "--seed", "7"]);
+        assert_eq!(recipe, vec!["perlin", "--seed=7"]);
+        // image types without a seed are left alone, which has to hold in release
+        // builds too where clap doesn't check the ids asked about
+        let recipe = get_seeded_recipe(&["fun-images", "mandelbrot"]);
+        assert_eq!(recipe, vec!["mandelbrot"]);
+        let recipe = get_seeded_recipe(&["fun-images", "ulam-spiral", "--size", "100"]);
+        assert_eq!(recipe, vec!["ulam-spiral", "--size=100"]);
+        // and the random effects get one each when they're on
+        let recipe = get_seeded_recipe(&["fun-images", "mandelbrot", "--grain", "0.5"]);
+        assert!(recipe.iter().any(|arg| arg.starts_with("--grain-seed=")));
+        assert!(!recipe.iter().any(|arg| arg.starts_with("--glitch-seed=")));
+        let recipe = get_seeded_recipe(&["fun-images", "mandelbrot", "--stereogram"]);
+        assert!(
+            recipe
+                .iter()
+                .any(|arg| arg.starts_with("--stereogram-seed="))
+        );
+
+        let mut words = vec!["fun-images".to_string(), "schema".to_string()];
+        get_seeded_matches(&mut words).unwrap();
+        assert_eq!(words, vec!["fun-images", "schema"]);
+    }
+}
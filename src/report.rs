@@ -0,0 +1,161 @@
+//! A machine readable report of what was made, for scripts which run fun-images
+//!
+//! The report is a JSON object with the image type, every option it was made with
+//! including the defaults, the seed, how long it took, where it was saved and a SHA-256
+//! hash of the saved file
+
+use std::{fmt::Write, fs, time::Instant};
+
+use clap::{ArgMatches, CommandFactory, ValueEnum};
+use serde_json::{Map, Value, json};
+use sha2::{Digest, Sha256};
+
+use crate::{Args, OutputFormat, error::Error, recipe::OUTPUT_OPTIONS};
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum ReportFormat {
+    /// A single line JSON object
+    Json,
+}
+
+/// Gets every option of the image type with the value it ended up with, keyed by the
+/// option's name
+pub fn get_parameters(matches: &ArgMatches) -> Map<String, Value> {
+    let mut parameters = Map::new();
+    let Some((image_type, sub_matches)) = matches.subcommand() else {
+        return parameters;
+    };
+    // building the command copies the global options into the subcommands
+    let mut command = Args::command();
+    command.build();
+    let Some(subcommand) = command.find_subcommand(image_type) else {
+        return parameters;
+    };
+
+    for arg in subcommand.get_arguments() {
+        let id = arg.get_id().as_str();
+        if OUTPUT_OPTIONS.contains(&id) {
+            continue;
+        }
+        let Ok(Some(values)) = sub_matches.try_get_raw(id) else {
+            continue;
+        };
+        let mut values: Vec<Value> = values
+            .map(|value| Value::String(value.to_string_lossy().into_owned()))
+            .collect();
+        let name = arg.get_long().unwrap_or(id).to_string();
+        let value = match values.len() {
+            1 => values.remove(0),
+            _ => Value::Array(values),
+        };
+        parameters.insert(name, value);
+    }
+
+    parameters
+}
+
+/// What's known about the image before it's made, with the timer running from when
+/// it was created
+pub struct Report {
+    image_type: String,
+    parameters: Map<String, Value>,
+    output: Option<String>,
+    report_file: Option<String>,
+    start: Instant,
+}
+
+impl Report {
+    pub fn start(args: &Args) -> Self {
+        Self {
+            image_type: args.recipe.first().cloned().unwrap_or_default(),
+            parameters: args.parameters.clone(),
+            output: match args.output_format {
+                OutputFormat::File => Some(args.get_output()),
+                OutputFormat::Ascii => None,
+            },
+            report_file: args.report_file.clone(),
+            start: Instant::now(),
+        }
+    }
+
+    /// Writes the report once the image has been saved, to the report file if there is
+    /// one and otherwise on its own line of the output
+    pub fn finish(self) -> Result<(), Error> {
+        let elapsed = self.start.elapsed();
+        let hash = match &self.output {
+            Some(output) => Some(hash_file(output)?),
+            None => None,
+        };
+        let seed = self
+            .parameters
+            .get("seed")
+            .and_then(Value::as_str)
+            .and_then(|seed| seed.parse::<u64>().ok());
+
+        let report = json!({
+            "image_type": self.image_type,
+            "parameters": self.parameters,
+            "seed": seed,
+            "milliseconds": elapsed.as_secs_f64() * 1000.0,
+            "output": self.output,
+            "sha256": hash,
+        });
+        match self.report_file {
+            Some(path) => fs::write(&path, format!("{}\n", report))
+                .map_err(|source| Error::CreateFile { path, source }),
+            None => {
+                println!("{}", report);
+                Ok(())
+            }
+        }
+    }
+}
+
+fn hash_file(path: &str) -> Result<String, Error> {
+    let bytes = fs::read(path).map_err(|source| Error::Report {
+        path: path.to_string(),
+        source,
+    })?;
+
+    let mut hash = String::new();
+    for byte in Sha256::digest(&bytes) {
+        let _ = write!(hash, "{:02x}", byte);
+    }
+    Ok(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recipe::get_seeded_matches;
+
+    #[test]
+    fn test_parameters() {
+        let matches = Args::command().get_matches_from([
+            "fun-images",
+            "-o",
+            "out.png",
+            "perlin",
+            "--seed",
+            "7",
+            "--glow",
+            "12",
+            "1.5",
+        ]);
+        let parameters = get_parameters(&matches);
+
+        assert_eq!(parameters["seed"], "7");
+        assert_eq!(parameters["size"], "500");
+        assert_eq!(parameters["glow"], json!(["12", "1.5"]));
+        assert!(!parameters.contains_key("output"));
+    }
+
+    #[test]
+    fn test_picked_seed() {
+        // a seed picked because none was given is reported the same as a given one
+        let mut words = vec!["fun-images".to_string(), "maze".to_string()];
+        let parameters = get_parameters(&get_seeded_matches(&mut words).unwrap());
+        let seed = parameters["seed"].as_str().unwrap();
+        assert!(seed.parse::<u64>().is_ok());
+    }
+}
@@ -0,0 +1,421 @@
+//! Describes every subcommand's options, for generating GUIs and scripts against the
+//! command line
+//!
+//! Everything comes from the clap command itself, so the registered generators are
+//! described along with the rest. Clap doesn't say what range a value parser accepts,
+//! so the ends of the ranges are found by trying values on the parser and narrowing in
+//! on where it stops accepting them
+
+use std::any::TypeId;
+
+use clap::{Arg, ArgAction, Command, CommandFactory};
+use csscolorparser::Color;
+use num_complex::Complex64;
+use serde_json::{Map, Value, json};
+
+use crate::Args;
+
+/// Prints the schema of the whole command line, as JSON or one line per option
+pub fn print_schema(json: bool) {
+    let command = Args::command();
+    match json {
+        true => println!("{}", get_command_schema(&command)),
+        false => print_command_summary(&command, &[]),
+    }
+}
+
+/// Gets the name, description, options and subcommands of a command as JSON
+pub fn get_command_schema(command: &Command) -> Value {
+    let options: Vec<Value> = get_described_arguments(command)
+        .map(get_argument_schema)
+        .collect();
+    let subcommands: Vec<Value> = command
+        .get_subcommands()
+        .filter(|subcommand| !subcommand.is_hide_set())
+        .map(get_command_schema)
+        .collect();
+
+    let mut schema = Map::new();
+    schema.insert("name".to_string(), json!(command.get_name()));
+    if let Some(version) = command.get_version() {
+        schema.insert("version".to_string(), json!(version));
+    }
+    if let Some(about) = command.get_about() {
+        schema.insert("about".to_string(), json!(about.to_string()));
+    }
+    schema.insert("options".to_string(), json!(options));
+    if !subcommands.is_empty() {
+        schema.insert("subcommands".to_string(), json!(subcommands));
+    }
+    Value::Object(schema)
+}
+
+/// The arguments worth describing, leaving out --help, --version and hidden ones
+fn get_described_arguments(command: &Command) -> impl Iterator<Item = &Arg> {
+    command.get_arguments().filter(|argument| {
+        !argument.is_hide_set()
+            && !matches!(
+                argument.get_action(),
+                ArgAction::Help | ArgAction::HelpShort | ArgAction::HelpLong | ArgAction::Version
+            )
+    })
+}
+
+fn get_argument_schema(argument: &Arg) -> Value {
+    let mut schema = Map::new();
+    schema.insert("name".to_string(), json!(argument.get_id().as_str()));
+    if let Some(long) = argument.get_long() {
+        schema.insert("long".to_string(), json!(long));
+    }
+    if let Some(short) = argument.get_short() {
+        schema.insert("short".to_string(), json!(short.to_string()));
+    }
+    if let Some(aliases) = argument.get_visible_aliases() {
+        schema.insert("aliases".to_string(), json!(aliases));
+    }
+    if let Some(help) = argument.get_help() {
+        schema.insert("help".to_string(), json!(help.to_string()));
+    }
+
+    let value_type = get_value_type(argument);
+    schema.insert("type".to_string(), json!(value_type.name()));
+    schema.insert("required".to_string(), json!(argument.is_required_set()));
+    if argument.is_positional() {
+        schema.insert("positional".to_string(), json!(true));
+    }
+    if let ArgAction::Append = argument.get_action() {
+        schema.insert("multiple".to_string(), json!(true));
+    }
+    if let Some(value_names) = argument.get_value_names()
+        && value_type != ValueType::Flag
+    {
+        let value_names: Vec<String> = value_names.iter().map(ToString::to_string).collect();
+        schema.insert("value_names".to_string(), json!(value_names));
+    }
+    let defaults = get_defaults(argument);
+    match defaults.len() {
+        0 => {}
+        1 => {
+            schema.insert("default".to_string(), json!(defaults[0]));
+        }
+        _ => {
+            schema.insert("default".to_string(), json!(defaults));
+        }
+    }
+
+    if value_type == ValueType::Enum {
+        let values: Vec<Value> = argument
+            .get_possible_values()
+            .iter()
+            .filter(|value| !value.is_hide_set())
+            .map(|value| match value.get_help() {
+                Some(help) => json!({ "name": value.get_name(), "help": help.to_string() }),
+                None => json!({ "name": value.get_name() }),
+            })
+            .collect();
+        schema.insert("values".to_string(), json!(values));
+    }
+    if let Some(range) = get_value_range(argument, value_type) {
+        if let Some((minimum, exclusive)) = range.minimum {
+            let key = if exclusive {
+                "exclusive_minimum"
+            } else {
+                "minimum"
+            };
+            schema.insert(key.to_string(), minimum);
+        }
+        if let Some((maximum, exclusive)) = range.maximum {
+            let key = if exclusive {
+                "exclusive_maximum"
+            } else {
+                "maximum"
+            };
+            schema.insert(key.to_string(), maximum);
+        }
+    }
+
+    Value::Object(schema)
+}
+
+fn get_defaults(argument: &Arg) -> Vec<String> {
+    argument
+        .get_default_values()
+        .iter()
+        .map(|value| value.to_string_lossy().into_owned())
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ValueType {
+    Flag,
+    Count,
+    Enum,
+    /// A whole number, with the smallest and largest the type can hold
+    Integer(i128, i128),
+    Number,
+    Color,
+    Complex,
+    String,
+}
+
+impl ValueType {
+    fn name(&self) -> &'static str {
+        match self {
+            ValueType::Flag => "flag",
+            ValueType::Count => "count",
+            ValueType::Enum => "enum",
+            ValueType::Integer(..) => "integer",
+            ValueType::Number => "number",
+            ValueType::Color => "color",
+            ValueType::Complex => "complex",
+            ValueType::String => "string",
+        }
+    }
+}
+
+fn get_value_type(argument: &Arg) -> ValueType {
+    match argument.get_action() {
+        ArgAction::SetTrue | ArgAction::SetFalse => return ValueType::Flag,
+        ArgAction::Count => return ValueType::Count,
+        _ => {}
+    }
+    if !argument.get_possible_values().is_empty() {
+        return ValueType::Enum;
+    }
+
+    let type_id = argument.get_value_parser().type_id();
+    let integer_types = [
+        (TypeId::of::<u8>(), u8::MIN as i128, u8::MAX as i128),
+        (TypeId::of::<u16>(), u16::MIN as i128, u16::MAX as i128),
+        (TypeId::of::<u32>(), u32::MIN as i128, u32::MAX as i128),
+        (TypeId::of::<u64>(), u64::MIN as i128, u64::MAX as i128),
+        (
+            TypeId::of::<usize>(),
+            usize::MIN as i128,
+            usize::MAX as i128,
+        ),
+        (TypeId::of::<i32>(), i32::MIN as i128, i32::MAX as i128),
+        (TypeId::of::<i64>(), i64::MIN as i128, i64::MAX as i128),
+    ];
+    if let Some((_, minimum, maximum)) = integer_types.iter().find(|(id, ..)| type_id == *id) {
+        return ValueType::Integer(*minimum, *maximum);
+    }
+    if type_id == TypeId::of::<f64>() || type_id == TypeId::of::<f32>() {
+        ValueType::Number
+    } else if type_id == TypeId::of::<Color>() {
+        ValueType::Color
+    } else if type_id == TypeId::of::<Complex64>() {
+        ValueType::Complex
+    } else {
+        ValueType::String
+    }
+}
+
+/// The smallest and largest values accepted, with whether they're left out themselves
+#[derive(Debug, Default)]
+struct ValueRange {
+    minimum: Option<(Value, bool)>,
+    maximum: Option<(Value, bool)>,
+}
+
+fn get_value_range(argument: &Arg, value_type: ValueType) -> Option<ValueRange> {
+    // the value is parsed on its own, so it isn't held up by the other options
+    let accepts = |value: &str| {
+        let value_argument = Arg::new("value")
+            .value_parser(argument.get_value_parser().clone())
+            .allow_hyphen_values(true);
+        Command::new("value")
+            .arg(value_argument)
+            .try_get_matches_from(["value", value])
+            .is_ok()
+    };
+    // somewhere to start narrowing in from, which has to be inside the range
+    let start = get_defaults(argument)
+        .into_iter()
+        .chain(["1", "0"].map(str::to_string))
+        .find(|value| accepts(value))?;
+
+    match value_type {
+        ValueType::Integer(type_minimum, type_maximum) => {
+            let start: i128 = start.parse().ok()?;
+            let accepts = |value: i128| accepts(&value.to_string());
+            let minimum = match accepts(type_minimum) {
+                true => type_minimum,
+                false => find_edge(start, type_minimum, accepts),
+            };
+            let maximum = match accepts(type_maximum) {
+                true => type_maximum,
+                false => find_edge(start, type_maximum, accepts),
+            };
+            // the ends of the type itself aren't worth saying, apart from not going
+            // below 0
+            Some(ValueRange {
+                minimum: (minimum != type_minimum || minimum == 0)
+                    .then(|| (json!(minimum as i64), false)),
+                maximum: (maximum != type_maximum).then(|| (json!(maximum as i64), false)),
+            })
+        }
+        ValueType::Number => {
+            let start: f64 = start.parse().ok()?;
+            let accepts_key = |key: i128| accepts(&from_ordered_key(key).to_string());
+            let get_end = |limit: f64| {
+                if accepts(&limit.to_string()) {
+                    return None;
+                }
+                let edge = from_ordered_key(find_edge(
+                    to_ordered_key(start),
+                    to_ordered_key(limit),
+                    accepts_key,
+                ));
+                // the edges are round numbers, found up to floating point error, and
+                // the range leaves the edge out if the rounded number isn't accepted
+                let rounded = (edge * 1e6).round() / 1e6;
+                Some((json!(rounded), !accepts(&rounded.to_string())))
+            };
+            Some(ValueRange {
+                minimum: get_end(f64::MIN),
+                maximum: get_end(f64::MAX),
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Finds the last accepted value going from one that's accepted towards one that isn't,
+/// by halving the gap between them
+fn find_edge(mut accepted: i128, mut rejected: i128, accepts: impl Fn(i128) -> bool) -> i128 {
+    while (rejected - accepted).abs() > 1 {
+        let middle = accepted + (rejected - accepted) / 2;
+        match accepts(middle) {
+            true => accepted = middle,
+            false => rejected = middle,
+        }
+    }
+    accepted
+}
+
+/// Turns a float into a number that goes up in the same order, so halving the gap
+/// between two of them takes at most 64 steps however far apart they are
+fn to_ordered_key(value: f64) -> i128 {
+    let bits = value.to_bits() as i64;
+    match bits < 0 {
+        true => (i64::MIN - bits) as i128,
+        false => bits as i128,
+    }
+}
+
+fn from_ordered_key(key: i128) -> f64 {
+    let bits = match key < 0 {
+        true => i64::MIN - key as i64,
+        false => key as i64,
+    };
+    f64::from_bits(bits as u64)
+}
+
+/// Prints a line for every option of the command and its subcommands, starting with
+/// the subcommands it's under
+fn print_command_summary(command: &Command, parents: &[&str]) {
+    for argument in get_described_arguments(command) {
+        let schema = get_argument_schema(argument);
+        let name = match argument.get_long() {
+            Some(long) => format!("--{}", long),
+            None => format!("<{}>", argument.get_id()),
+        };
+        let mut description = vec![schema["type"].as_str().unwrap_or_default().to_string()];
+        if let Some(values) = schema["values"].as_array() {
+            let names: Vec<&str> = values
+                .iter()
+                .filter_map(|value| value["name"].as_str())
+                .collect();
+            description.push(names.join("|"));
+        }
+        for (key, text) in [
+            ("minimum", "at least"),
+            ("exclusive_minimum", "more than"),
+            ("maximum", "at most"),
+            ("exclusive_maximum", "less than"),
+        ] {
+            if let Some(value) = schema.get(key) {
+                description.push(format!("{} {}", text, value));
+            }
+        }
+        if let Some(default) = schema.get("default") {
+            description.push(format!("default {}", default));
+        }
+        if argument.is_required_set() {
+            description.push("required".to_string());
+        }
+
+        let path: Vec<&str> = parents.iter().copied().chain([name.as_str()]).collect();
+        println!("{}  {}", path.join(" "), description.join(", "));
+    }
+
+    for subcommand in command.get_subcommands() {
+        if subcommand.is_hide_set() {
+            continue;
+        }
+        let parents: Vec<&str> = parents
+            .iter()
+            .copied()
+            .chain([subcommand.get_name()])
+            .collect();
+        print_command_summary(subcommand, &parents);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find_option<'a>(schema: &'a Value, subcommand: &str, name: &str) -> &'a Value {
+        schema["subcommands"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|command| command["name"] == subcommand)
+            .and_then(|command| {
+                command["options"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .find(|option| option["name"] == name)
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_schema() {
+        let schema = get_command_schema(&Args::command());
+
+        // registered generators are there along with the rest
+        let modulus = find_option(&schema, "xor-pattern", "modulus");
+        assert_eq!(modulus["type"], "integer");
+        assert_eq!(modulus["short"], "k");
+        assert_eq!(modulus["default"], "9");
+        assert_eq!(modulus["minimum"], 2);
+        assert!(modulus.get("maximum").is_none());
+
+        let opacity = find_option(&schema, "times-table", "opacity");
+        assert_eq!(opacity["type"], "number");
+        assert_eq!(opacity["minimum"], 0.0);
+        assert_eq!(opacity["maximum"], 1.0);
+        // global options are only listed at the top
+        let gamma = schema["options"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|option| option["name"] == "gamma")
+            .unwrap();
+        assert_eq!(gamma["exclusive_minimum"], 0.0);
+
+        let size = find_option(&schema, "ulam-spiral", "size");
+        assert_eq!(size["minimum"], 1);
+        let palette = find_option(&schema, "times-table", "palette");
+        assert_eq!(palette["type"], "enum");
+        assert!(palette["values"].as_array().unwrap().len() > 1);
+        let background = find_option(&schema, "times-table", "background_color");
+        assert_eq!(background["type"], "color");
+        let to = find_option(&schema, "times-table", "to");
+        assert!(to.get("minimum").is_none() && to.get("maximum").is_none());
+    }
+}
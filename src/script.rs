@@ -0,0 +1,435 @@
+//! Module for running [Rhai](https://rhai.rs) scripts that draw their own images
+//!
+//! The script is run once for each frame with a fresh canvas. It can read `WIDTH`,
+//! `HEIGHT`, `FRAME`, `FRAMES` and `TIME`, which goes from 0 up to 1 over an animation,
+//! and draw with:
+//! - `background(color)`, `pixel(x, y, color)` and `line(x1, y1, x2, y2, color)`
+//! - `rect(x, y, width, height, color)`, `circle(x, y, radius, color)` and
+//!   `ring(x, y, radius, color)`
+//! - `text(x, y, size, text, color)`
+//!
+//! Colours can be CSS strings like "#ff8800" or made with `rgb(r, g, b)`,
+//! `rgba(r, g, b, alpha)`, `hsl(hue, saturation, lightness)`, `palette(name, position)`
+//! and `mix(color1, color2, amount)`. `noise(x, y)` gives smooth noise from -1 to 1 and
+//! `random()` or `random(min, max)` give random numbers, both from the seed
+
+use std::{cell::RefCell, rc::Rc};
+
+use clap::ValueEnum;
+use csscolorparser::Color;
+use image::{Rgba, RgbaImage};
+use imageproc::{
+    drawing::{
+        Blend, draw_filled_circle_mut, draw_filled_rect_mut, draw_hollow_circle_mut,
+        draw_line_segment_mut,
+    },
+    rect::Rect,
+};
+use rhai::{Dynamic, Engine, EvalAltResult, Scope};
+
+use crate::{
+    PaletteType,
+    cancel::is_cancelled,
+    generator::{Generated, Generator, GeneratorError},
+    memory::MemoryEstimate,
+    palette::{get_palette_color, interpolate_color},
+    parse_color, parse_size,
+    perlin::Noise,
+    text::draw_text_on_image,
+};
+
+pub struct ScriptOptions {
+    source: String,
+    width: u32,
+    height: u32,
+    frames: u32,
+    background_color: Color,
+    seed: Option<u64>,
+}
+
+impl ScriptOptions {
+    pub fn new(
+        source: String,
+        width: u32,
+        height: u32,
+        frames: u32,
+        background_color: Color,
+        seed: Option<u64>,
+    ) -> Self {
+        Self {
+            source,
+            width,
+            height,
+            frames,
+            background_color,
+            seed,
+        }
+    }
+}
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// Runs the script for each frame, giving back the error from the script with where it
+/// happened if it fails
+pub fn generate_script_images(options: ScriptOptions) -> Result<Vec<RgbaImage>, String> {
+    let background = Rgba(options.background_color.to_rgba8());
+    let canvas = Rc::new(RefCell::new(Blend(RgbaImage::from_pixel(
+        options.width,
+        options.height,
+        background,
+    ))));
+    let mut rng = match options.seed {
+        Some(seed) => fastrand::Rng::with_seed(seed),
+        None => fastrand::Rng::new(),
+    };
+    let noise = Noise::new(&mut rng);
+
+    let mut engine = Engine::new();
+    register_colors(&mut engine);
+    register_drawing(&mut engine, &canvas);
+    register_randomness(&mut engine, noise, rng);
+
+    let ast = engine
+        .compile(&options.source)
+        .map_err(|error| error.to_string())?;
+
+    let mut images = Vec::with_capacity(options.frames as usize);
+    for frame in 0..options.frames {
+        if is_cancelled() {
+            break;
+        }
+        canvas.borrow_mut().0 = RgbaImage::from_pixel(options.width, options.height, background);
+
+        let mut scope = Scope::new();
+        scope.push_constant("WIDTH", options.width as i64);
+        scope.push_constant("HEIGHT", options.height as i64);
+        scope.push_constant("FRAME", frame as i64);
+        scope.push_constant("FRAMES", options.frames as i64);
+        scope.push_constant("TIME", frame as f64 / options.frames as f64);
+        engine
+            .run_ast_with_scope(&mut scope, &ast)
+            .map_err(|error| match options.frames {
+                1 => error.to_string(),
+                _ => format!("{} on frame {}", error, frame),
+            })?;
+
+        images.push(canvas.borrow().0.clone());
+    }
+
+    Ok(images)
+}
+
+fn register_colors(engine: &mut Engine) {
+    engine.register_type_with_name::<Rgba<u8>>("Color");
+    engine.register_fn("color", |value: Dynamic| get_color(value));
+    engine.register_fn("rgb", |red: Dynamic, green: Dynamic, blue: Dynamic| {
+        rgba(red, green, blue, Dynamic::from_float(1.0))
+    });
+    engine.register_fn("rgba", rgba);
+    engine.register_fn(
+        "hsl",
+        |hue: Dynamic, saturation: Dynamic, lightness: Dynamic| -> ScriptResult<Rgba<u8>> {
+            let color = Color::from_hsla(
+                get_number(hue)? as f32,
+                get_number(saturation)? as f32,
+                get_number(lightness)? as f32,
+                1.0,
+            );
+            Ok(Rgba(color.to_rgba8()))
+        },
+    );
+    engine.register_fn(
+        "palette",
+        |name: &str, position: Dynamic| -> ScriptResult<Rgba<u8>> {
+            let palette_type = PaletteType::from_str(name, true)
+                .map_err(|_| format!("unknown palette {}", name))?;
+            Ok(Rgba(get_palette_color(palette_type, get_number(position)?)))
+        },
+    );
+    engine.register_fn(
+        "mix",
+        |color1: Dynamic, color2: Dynamic, amount: Dynamic| -> ScriptResult<Rgba<u8>> {
+            // interpolate_color gives the first colour at 1, so swap them round
+            Ok(Rgba(interpolate_color(
+                get_color(color2)?.0,
+                get_color(color1)?.0,
+                get_number(amount)?.clamp(0.0, 1.0),
+            )))
+        },
+    );
+}
+
+fn rgba(red: Dynamic, green: Dynamic, blue: Dynamic, alpha: Dynamic) -> ScriptResult<Rgba<u8>> {
+    let channel = |value: f64| value.clamp(0.0, 255.0).round() as u8;
+    Ok(Rgba([
+        channel(get_number(red)?),
+        channel(get_number(green)?),
+        channel(get_number(blue)?),
+        channel(get_number(alpha)? * 255.0),
+    ]))
+}
+
+fn register_drawing(engine: &mut Engine, canvas: &Rc<RefCell<Blend<RgbaImage>>>) {
+    let drawing = canvas.clone();
+    engine.register_fn("background", move |color: Dynamic| -> ScriptResult<()> {
+        let color = get_color(color)?;
+        drawing
+            .borrow_mut()
+            .0
+            .pixels_mut()
+            .for_each(|pixel| *pixel = color);
+        Ok(())
+    });
+
+    let drawing = canvas.clone();
+    engine.register_fn(
+        "pixel",
+        move |x: Dynamic, y: Dynamic, color: Dynamic| -> ScriptResult<()> {
+            let (x, y) = (get_number(x)?.floor(), get_number(y)?.floor());
+            let mut drawing = drawing.borrow_mut();
+            let (width, height) = drawing.0.dimensions();
+            if x >= 0.0 && y >= 0.0 && x < width as f64 && y < height as f64 {
+                imageproc::drawing::Canvas::draw_pixel(
+                    &mut *drawing,
+                    x as u32,
+                    y as u32,
+                    get_color(color)?,
+                );
+            }
+            Ok(())
+        },
+    );
+
+    let drawing = canvas.clone();
+    engine.register_fn(
+        "line",
+        move |x1: Dynamic,
+              y1: Dynamic,
+              x2: Dynamic,
+              y2: Dynamic,
+              color: Dynamic|
+              -> ScriptResult<()> {
+            draw_line_segment_mut(
+                &mut *drawing.borrow_mut(),
+                (get_number(x1)? as f32, get_number(y1)? as f32),
+                (get_number(x2)? as f32, get_number(y2)? as f32),
+                get_color(color)?,
+            );
+            Ok(())
+        },
+    );
+
+    let drawing = canvas.clone();
+    engine.register_fn(
+        "rect",
+        move |x: Dynamic,
+              y: Dynamic,
+              width: Dynamic,
+              height: Dynamic,
+              color: Dynamic|
+              -> ScriptResult<()> {
+            let (width, height) = (get_number(width)?.round(), get_number(height)?.round());
+            if width >= 1.0 && height >= 1.0 {
+                draw_filled_rect_mut(
+                    &mut *drawing.borrow_mut(),
+                    Rect::at(get_number(x)?.round() as i32, get_number(y)?.round() as i32)
+                        .of_size(width as u32, height as u32),
+                    get_color(color)?,
+                );
+            }
+            Ok(())
+        },
+    );
+
+    let drawing = canvas.clone();
+    engine.register_fn(
+        "circle",
+        move |x: Dynamic, y: Dynamic, radius: Dynamic, color: Dynamic| -> ScriptResult<()> {
+            draw_filled_circle_mut(
+                &mut *drawing.borrow_mut(),
+                (get_number(x)?.round() as i32, get_number(y)?.round() as i32),
+                get_number(radius)?.round() as i32,
+                get_color(color)?,
+            );
+            Ok(())
+        },
+    );
+
+    let drawing = canvas.clone();
+    engine.register_fn(
+        "ring",
+        move |x: Dynamic, y: Dynamic, radius: Dynamic, color: Dynamic| -> ScriptResult<()> {
+            draw_hollow_circle_mut(
+                &mut *drawing.borrow_mut(),
+                (get_number(x)?.round() as i32, get_number(y)?.round() as i32),
+                get_number(radius)?.round() as i32,
+                get_color(color)?,
+            );
+            Ok(())
+        },
+    );
+
+    let drawing = canvas.clone();
+    engine.register_fn(
+        "text",
+        move |x: Dynamic,
+              y: Dynamic,
+              size: Dynamic,
+              text: &str,
+              color: Dynamic|
+              -> ScriptResult<()> {
+            draw_text_on_image(
+                &mut drawing.borrow_mut().0,
+                text,
+                (get_number(x)?.round() as i32, get_number(y)?.round() as i32),
+                get_number(size)?.max(1.0) as f32,
+                get_color(color)?,
+            );
+            Ok(())
+        },
+    );
+}
+
+fn register_randomness(engine: &mut Engine, noise: Noise, rng: fastrand::Rng) {
+    engine.register_fn(
+        "noise",
+        move |x: Dynamic, y: Dynamic| -> ScriptResult<f64> {
+            Ok(noise.get(get_number(x)?, get_number(y)?))
+        },
+    );
+
+    // shared between both versions of random so they give one sequence
+    let rng = Rc::new(RefCell::new(rng));
+    let random = rng.clone();
+    engine.register_fn("random", move || random.borrow_mut().f64());
+    engine.register_fn(
+        "random",
+        move |min: Dynamic, max: Dynamic| -> ScriptResult<f64> {
+            let (min, max) = (get_number(min)?, get_number(max)?);
+            Ok(min + rng.borrow_mut().f64() * (max - min))
+        },
+    );
+}
+
+/// Reads a number from the script, which could be an integer or a float
+fn get_number(value: Dynamic) -> ScriptResult<f64> {
+    value
+        .as_float()
+        .or_else(|_| value.as_int().map(|value| value as f64))
+        .map_err(|type_name| format!("expected a number but got {}", type_name).into())
+}
+
+/// Reads a colour from the script, either one made by a colour function or a CSS string
+fn get_color(value: Dynamic) -> ScriptResult<Rgba<u8>> {
+    if let Some(color) = value.clone().try_cast::<Rgba<u8>>() {
+        return Ok(color);
+    }
+    let text = value
+        .into_immutable_string()
+        .map_err(|type_name| format!("expected a colour but got {}", type_name))?;
+    let color = parse_color(&text)?;
+
+    Ok(Rgba(color.to_rgba8()))
+}
+
+/// Run a Rhai script which draws the image. See the README for the functions it can
+/// use
+#[derive(clap::Args, Debug)]
+pub struct ScriptArgs {
+    /// The script file to run
+    path: String,
+
+    #[arg(long, default_value = "800", value_parser = parse_size)]
+    width: u32,
+
+    #[arg(long, default_value = "800", value_parser = parse_size)]
+    height: u32,
+
+    /// The number of frames, with more than 1 making an animation
+    #[arg(short, long, default_value = "1", value_parser = parse_size)]
+    frames: u32,
+
+    #[arg(short, long, alias = "background", default_value = "white", value_parser = parse_color)]
+    background_color: Color,
+
+    /// The seed used for noise and random in the script, random if not given
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+pub struct ScriptGenerator;
+
+impl Generator for ScriptGenerator {
+    type Args = ScriptArgs;
+
+    fn name(&self) -> &'static str {
+        "script"
+    }
+
+    fn is_animated(&self, args: &ScriptArgs) -> bool {
+        args.frames > 1
+    }
+
+    fn estimate_memory(&self, args: &ScriptArgs) -> Option<MemoryEstimate> {
+        Some(MemoryEstimate::frames(args.width, args.height, args.frames))
+    }
+
+    fn generate(&self, args: ScriptArgs) -> Result<Generated, GeneratorError> {
+        let source =
+            std::fs::read_to_string(&args.path).map_err(|source| GeneratorError::ReadFile {
+                path: args.path.clone(),
+                source,
+            })?;
+        let options = ScriptOptions::new(
+            source,
+            args.width,
+            args.height,
+            args.frames,
+            args.background_color,
+            args.seed,
+        );
+        let mut frames = generate_script_images(options)
+            .map_err(|message| GeneratorError::Failed(format!("{}: {}", args.path, message)))?;
+
+        Ok(match args.frames {
+            1 => Generated::Still(frames.remove(0)),
+            _ => Generated::Frames(frames),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script() {
+        let source = r#"
+            background("black");
+            rect(0, 0, WIDTH / 2, HEIGHT, rgb(255, 0, 0));
+            pixel(WIDTH - 1, 0, mix("black", "white", 1.0));
+            if noise(0.5, 0.5) > 1.5 || random() > 1.0 { throw "out of range"; }
+        "#;
+        let images = generate_script_images(ScriptOptions::new(
+            source.to_string(),
+            10,
+            4,
+            2,
+            Color::new(1.0, 1.0, 1.0, 1.0),
+            Some(1),
+        ))
+        .unwrap();
+
+        assert_eq!(images.len(), 2);
+        assert_eq!(images[1][(0, 0)], Rgba([255, 0, 0, 255]));
+        assert_eq!(images[1][(5, 0)], Rgba([0, 0, 0, 255]));
+        assert_eq!(images[1][(9, 0)], Rgba([255, 255, 255, 255]));
+
+        let options = |source: &str| {
+            ScriptOptions::new(source.to_string(), 10, 10, 1, Color::default(), None)
+        };
+        assert!(generate_script_images(options("circle(1, 2, \"red\");")).is_err());
+        assert!(generate_script_images(options("background(\"not a colour\");")).is_err());
+        assert!(generate_script_images(options("let x = ;")).is_err());
+    }
+}
@@ -0,0 +1,346 @@
+//! A small HTTP server that generates images on request
+//!
+//! Requests map straight onto the command line, so `/mandelbrot?zoom=20&gradient=true`
+//! gives the same image as `fun-images mandelbrot --zoom 20 --gradient`
+
+use std::collections::{HashMap, VecDeque};
+
+use clap::{Args as _, Command, CommandFactory, FromArgMatches};
+use log::{error, info};
+use tiny_http::{Header, Response, Server};
+
+use fun_images::post::{PostArgs, apply_post_processing};
+
+use crate::{
+    Args, ImageFormat, check_memory,
+    error::Error,
+    generate_animated_images, generate_static_image,
+    output::{write_animated_png, write_png},
+    overlay::OverlayArgs,
+    recipe::{get_option_arg, get_recipe, get_seeded_matches},
+};
+
+/// The image types a request can make, leaving out those that read files, run code or
+/// render other command lines given to them
+const ALLOWED_IMAGE_TYPES: &[&str] = &[
+    "ulam-spiral",
+    "mandelbrot",
+    "phoenix",
+    "magnet",
+    "sierpinski",
+    "perlin",
+    "farey",
+    "wave",
+    "plasma-effect",
+    "tunnel",
+    "starfield",
+    "matrix-rain",
+    "fire",
+    "ising",
+    "hyperbolic",
+    "circle-packing",
+    "theodorus",
+    "euler-spiral",
+    "prime-race",
+    "aliquot",
+    "goldbach",
+    "collatz",
+    "digit-walk",
+    "bit-pattern",
+    "xor-pattern",
+    "times-table",
+    "mandala",
+    "truchet",
+    "flow-field",
+    "particles",
+    "vector-field",
+    "isometric",
+    "mandelbulb",
+    "quat-julia",
+    "maze",
+    "reaction-diffusion",
+    "conformal",
+];
+
+/// Options of the allowed image types that would read a file or render another command
+/// line, so can't be requested
+const PATH_OPTIONS: &[&str] = &["file", "seed-image", "input", "pattern"];
+
+pub struct ServeOptions {
+    host: String,
+    port: u16,
+    cache_size: usize,
+}
+
+impl ServeOptions {
+    pub fn new(host: String, port: u16, cache_size: usize) -> Self {
+        Self {
+            host,
+            port,
+            cache_size,
+        }
+    }
+}
+
+/// Keeps the most recently rendered images, dropping the oldest when full
+struct RenderCache {
+    images: HashMap<String, Vec<u8>>,
+    order: VecDeque<String>,
+    capacity: usize,
+}
+
+impl RenderCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            images: HashMap::new(),
+            order: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    fn get(&self, url: &str) -> Option<&Vec<u8>> {
+        self.images.get(url)
+    }
+
+    fn insert(&mut self, url: String, image: Vec<u8>) {
+        if self.capacity == 0 {
+            return;
+        }
+        while self.order.len() >= self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.images.remove(&oldest);
+            }
+        }
+        self.order.push_back(url.clone());
+        self.images.insert(url, image);
+    }
+}
+
+pub fn run_server(options: ServeOptions) -> Result<(), Error> {
+    let address = format!("{}:{}", options.host, options.port);
+    let server = Server::http(&address).map_err(|server_error| Error::StartServer {
+        address: address.clone(),
+        message: server_error.to_string(),
+    })?;
+    info!("Serving images on http://{}", address);
+
+    let mut cache = RenderCache::new(options.cache_size);
+    for request in server.incoming_requests() {
+        let url = request.url().to_string();
+
+        let response = match cache.get(&url) {
+            Some(image) => Ok(image.clone()),
+            None => render(&url).inspect(|image| cache.insert(url.clone(), image.clone())),
+        };
+        let response = match response {
+            Ok(image) => Response::from_data(image)
+                .with_header(Header::from_bytes("Content-Type", "image/png").unwrap()),
+            Err(message) => Response::from_string(message)
+                .with_status_code(400)
+                .with_header(Header::from_bytes("Content-Type", "text/plain").unwrap()),
+        };
+
+        if let Err(io_error) = request.respond(response) {
+            error!("responding to {}: {:?}", url, io_error);
+        }
+    }
+
+    Ok(())
+}
+
+/// Generates the image for a request as a PNG, or an animated PNG for animations
+fn render(url: &str) -> Result<Vec<u8>, String> {
+    let mut request_args = get_request_args(url);
+    check_request_args(&request_args)?;
+    let matches = get_seeded_matches(&mut request_args).map_err(|error| error.to_string())?;
+    let args = Args::from_arg_matches(&matches).map_err(|error| error.to_string())?;
+    // stored in the PNG so a downloaded image can be reproduced on the command line
+    let recipe = get_recipe(&matches);
+
+    check_memory(&args).map_err(|error| error.to_string())?;
+
+    let mut bytes = Vec::new();
+    match args.image_type.get_format() {
+        ImageFormat::Static => {
+            let image =
+                generate_static_image(args.image_type).map_err(|error| error.to_string())?;
+            let image = apply_post_processing(image.into_rgba8(), &args.post)?;
+            write_png(&mut bytes, &image, &recipe)
+                .map_err(|encoding_error| encoding_error.to_string())?;
+        }
+        ImageFormat::Animated => {
+            let playback = args.image_type.get_playback();
+            let images: Vec<_> = generate_animated_images(args.image_type)
+                .map_err(|error| error.to_string())?
+                .into_iter()
+                .map(|image| apply_post_processing(image, &args.post))
+                .collect::<Result<_, _>>()?;
+            write_animated_png(&mut bytes, &images, &recipe, playback)
+                .map_err(|encoding_error| encoding_error.to_string())?;
+        }
+        ImageFormat::Interactive
+        | ImageFormat::Server
+        | ImageFormat::Reproduce
+        | ImageFormat::Gallery
+        | ImageFormat::Presets
+        | ImageFormat::Completions
+        | ImageFormat::Schema
+        | ImageFormat::Random => {
+            return Err("Only images can be requested".to_string());
+        }
+    }
+
+    Ok(bytes)
+}
+
+/// Makes sure the request only makes an allowed image type, with its own options or
+/// post effects, so it can't read files or run code on the server
+fn check_request_args(request_args: &[String]) -> Result<(), String> {
+    let image_type = request_args.get(1).map(String::as_str).unwrap_or_default();
+    let command = Args::command();
+    let subcommand = command
+        .find_subcommand(image_type)
+        .filter(|_| ALLOWED_IMAGE_TYPES.contains(&image_type))
+        .ok_or_else(|| format!("{} images can't be requested", image_type))?;
+    let post_command = PostArgs::augment_args(Command::new("post"));
+    let overlay_command = OverlayArgs::augment_args(Command::new("overlay"));
+    let is_overlay_option = |name: &str| {
+        overlay_command
+            .get_arguments()
+            .any(|arg| arg.get_long() == Some(name))
+    };
+    let is_allowed = |name: &str| {
+        let is_own_option = subcommand
+            .get_arguments()
+            .any(|arg| arg.get_long() == Some(name))
+            && !PATH_OPTIONS.contains(&name);
+        let is_post_option = post_command
+            .get_arguments()
+            .any(|arg| arg.get_long() == Some(name));
+        is_own_option || is_post_option
+    };
+
+    for arg in &request_args[2..] {
+        let name = arg
+            .trim_start_matches("--")
+            .split('=')
+            .next()
+            .unwrap_or_default();
+        if is_overlay_option(name) {
+            return Err(format!(
+                "--{} can't be used in requests, the server doesn't mix images from files \
+                 into the ones it makes",
+                name
+            ));
+        }
+        if !is_allowed(name) {
+            return Err(format!("--{} can't be used in requests", name));
+        }
+    }
+
+    Ok(())
+}
+
+/// Turns the request into command line args, with the path as the image type and
+/// each query parameter as an option
+fn get_request_args(url: &str) -> Vec<String> {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+
+    let mut args = vec![
+        "fun-images".to_string(),
+        percent_decode(path.trim_matches('/')),
+    ];
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, "true"));
+        let key = percent_decode(key).replace('_', "-");
+        args.extend(get_option_arg(&key, &percent_decode(value)));
+    }
+
+    args
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len()
+                && bytes[i + 1].is_ascii_hexdigit()
+                && bytes[i + 2].is_ascii_hexdigit() =>
+            {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap();
+                decoded.push(u8::from_str_radix(hex, 16).unwrap());
+                i += 2;
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_args() {
+        assert_eq!(
+            get_request_args(
+                "/mandelbrot?zoom=20&gradient=true&center=-0.745%2C0.11&color=%23ff0000"
+            ),
+            vec![
+                "fun-images",
+                "mandelbrot",
+                "--zoom=20",
+                "--gradient",
+                "--center=-0.745,0.11",
+                "--color=#ff0000",
+            ]
+        );
+        assert_eq!(
+            get_request_args("/maze?wall_color=red&animate=false"),
+            vec!["fun-images", "maze", "--wall-color=red"]
+        );
+    }
+
+    #[test]
+    fn test_forbidden_requests() {
+        for url in [
+            concat!(
+                "/conformal?input=",
+                env!("CARGO_MANIFEST_DIR"),
+                "/showcase/sierpinski-zoom.png&width=64&height=64"
+            ),
+            "/conformal?pattern=digit-walk%20--file%20/etc/passwd&width=64&height=64",
+            "/compose?layer=digit-walk%20--file%20/etc/passwd",
+            "/sweep?x=file=/etc/passwd,/etc/hosts",
+            "/digit-walk?file=/etc/passwd",
+            "/reaction-diffusion?seed_image=/etc/passwd",
+            "/stylize?file=/etc/passwd",
+            "/frames-to-anim?dir=/etc",
+            "/mandelbrot?blend_with=/etc/passwd",
+            "/mandelbrot?watermark=/etc/passwd",
+            "/mandelbrot?memory_budget=1TB&yes=true",
+            "/mandelbrot?report_file=/tmp/report.json",
+        ] {
+            assert!(
+                check_request_args(&get_request_args(url)).is_err(),
+                "{} should be refused",
+                url
+            );
+        }
+        assert!(
+            check_request_args(&get_request_args("/mandelbrot?blend_opacity=0.5"))
+                .unwrap_err()
+                .contains("doesn't mix images")
+        );
+        assert!(check_request_args(&get_request_args("/maze?columns=21&gamma=1.5")).is_ok());
+        assert!(render("/phoenix?width=16&height=16&invert=true").is_ok());
+        assert!(render("/digit-walk?digits=100000000&size=10").is_err());
+        assert!(render("/bit-pattern?pattern=pi&rows=3000&columns=3000").is_err());
+    }
+}
@@ -6,64 +6,123 @@ use csscolorparser::Color;
 use image::{DynamicImage, GenericImage, Rgba, RgbaImage};
 use imageproc::drawing::draw_line_segment_mut;
 
+use crate::cancel::{is_cancelled, render_frames};
+
+/// Triangles are split into smaller ones until they're shorter than this
+const MIN_DIVIDE_HEIGHT: f32 = 10.0;
+
+/// The smallest image the triangle fits in with a few levels of smaller triangles
+/// inside it, even when it's shrunk to fit while turning
+const MIN_SIZE: u32 = 32;
+
+/// The smallest image the chaos game's dots show the gaps between the triangles in
+const MIN_CHAOS_GAME_SIZE: u32 = 16;
+
+/// A triangle pointing up, with the width the same as the height
 #[derive(Clone, Copy, Debug)]
-enum TriangleDirection {
-    Up,
-    Down,
-}
-
-impl TriangleDirection {
-    // for some reason i thought the triangles switched direction each iteration
-    // which did generate some cool images so leaving this here in case i want to use it
-    // for something in the future
-    #[allow(unused)]
-    fn other(self) -> Self {
-        match self {
-            TriangleDirection::Up => TriangleDirection::Down,
-            TriangleDirection::Down => TriangleDirection::Up,
-        }
+struct Triangle {
+    centre: (f32, f32),
+    height: f32,
+}
+
+impl Triangle {
+    fn get_points(&self) -> [(f32, f32); 3] {
+        let Triangle { centre, height } = *self;
+        [
+            (centre.0, centre.1 - height / 2.0),
+            (centre.0 + height / 2.0, centre.1 + height / 2.0),
+            (centre.0 - height / 2.0, centre.1 + height / 2.0),
+        ]
+    }
+
+    /// Splits the triangle into the three half size triangles in its corners
+    fn divide(&self) -> [Self; 3] {
+        let height = self.height / 2.0;
+        [
+            (self.centre.0 + height / 2.0, self.centre.1 + height / 2.0),
+            (self.centre.0 - height / 2.0, self.centre.1 + height / 2.0),
+            (self.centre.0, self.centre.1 - height / 2.0),
+        ]
+        .map(|centre| Triangle { centre, height })
+    }
+}
+
+/// Turning the whole image around a point, done to the corners of each triangle as
+/// it's drawn so the triangles can be worked out upright
+#[derive(Clone, Copy, Debug)]
+struct Rotation {
+    pivot: (f32, f32),
+    sin: f32,
+    cos: f32,
+}
+
+impl Rotation {
+    /// Turns clockwise by the angle in radians
+    fn new(pivot: (f32, f32), angle: f32) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Self { pivot, sin, cos }
+    }
+
+    fn apply(&self, point: (f32, f32)) -> (f32, f32) {
+        let (x, y) = (point.0 - self.pivot.0, point.1 - self.pivot.1);
+        (
+            self.pivot.0 + x * self.cos - y * self.sin,
+            self.pivot.1 + x * self.sin + y * self.cos,
+        )
     }
 }
 
-fn draw_triangle_mut<I>(image: &mut I, color: I::Pixel, triangle: Triangle)
-where
+fn draw_triangle_mut<I>(
+    image: &mut I,
+    color: I::Pixel,
+    triangle: Triangle,
+    rotation: Option<Rotation>,
+) where
     I: GenericImage,
 {
-    let Triangle {
-        direction,
-        centre,
-        height,
-    } = triangle;
-    // width of the triangle will be the same as the height
-    // need to draw 3 lines
-    let factor = match direction {
-        TriangleDirection::Down => 1.0,
-        TriangleDirection::Up => -1.0,
-    };
-    let pos1 = (centre.0, centre.1 - factor * height / 2.0);
-    let pos2 = (
-        centre.0 + factor * height / 2.0,
-        centre.1 + factor * height / 2.0,
-    );
-    let pos3 = (
-        centre.0 - factor * height / 2.0,
-        centre.1 + factor * height / 2.0,
-    );
+    let mut points = triangle.get_points();
+    if let Some(rotation) = rotation {
+        points = points.map(|point| rotation.apply(point));
+    }
+    let [point1, point2, point3] = points;
 
-    draw_line_segment_mut(image, pos1, pos2, color);
-    draw_line_segment_mut(image, pos2, pos3, color);
-    draw_line_segment_mut(image, pos3, pos1, color);
+    draw_line_segment_mut(image, point1, point2, color);
+    draw_line_segment_mut(image, point2, point3, color);
+    draw_line_segment_mut(image, point3, point1, color);
 }
 
-#[derive(Clone, Copy, Debug)]
-struct Triangle {
-    centre: (f32, f32),
-    height: f32,
-    direction: TriangleDirection,
+/// Draws the triangle and the triangles inside it until they're too small to see
+fn draw_sierpinski_mut(
+    image: &mut RgbaImage,
+    color: &Color,
+    triangle: Triangle,
+    rotation: Option<Rotation>,
+) {
+    let color = Rgba(color.to_rgba8());
+    let mut triangles = vec![triangle];
+    while let Some(triangle) = triangles.pop() {
+        draw_triangle_mut(image, color, triangle, rotation);
+        if triangle.height >= MIN_DIVIDE_HEIGHT {
+            triangles.extend(triangle.divide());
+        }
+    }
 }
 
-pub fn generate_sierpinski_image(color: Color, size: u32) -> DynamicImage {
-    let sierpinski_image = generate_sierpinski_image_with_zoom(color, size, 0.0);
+/// Checks the triangle fits in an image of the size, giving what to change if it doesn't
+pub fn check_sierpinski_size(size: u32) -> Result<(), String> {
+    match size >= MIN_SIZE {
+        true => Ok(()),
+        false => Err(format!(
+            "the triangle needs an image at least {} pixels across to have smaller triangles \
+             inside it, use a bigger --size than {}",
+            MIN_SIZE, size
+        )),
+    }
+}
+
+pub fn generate_sierpinski_image(color: Color, background_color: Color, size: u32) -> DynamicImage {
+    let sierpinski_image =
+        generate_sierpinski_image_with_zoom(&color, &background_color, size, 0.0);
 
     DynamicImage::ImageRgba8(sierpinski_image)
 }
@@ -72,16 +131,20 @@ fn lerp(point1: f32, point2: f32, amount: f32) -> f32 {
     point1 * (1.0 - amount) + point2 * amount
 }
 
-fn generate_sierpinski_image_with_zoom(color: Color, size: u32, zoom: f32) -> RgbaImage {
-    let mut image = RgbaImage::new(size, size);
+fn generate_sierpinski_image_with_zoom(
+    color: &Color,
+    background_color: &Color,
+    size: u32,
+    zoom: f32,
+) -> RgbaImage {
+    let mut image = RgbaImage::from_pixel(size, size, Rgba(background_color.to_rgba8()));
 
     let centre = size as f32 / 2.0;
     let main_triangle_height = centre * 2.0;
-    let mut triangles = vec![];
     // zoom towards bottom left
     let zoom_point = (
-        centre - -1.0 * main_triangle_height / 2.0,
-        centre + -1.0 * main_triangle_height / 2.0,
+        centre + main_triangle_height / 2.0,
+        centre - main_triangle_height / 2.0,
     );
 
     let new_centre = (
@@ -89,62 +152,142 @@ fn generate_sierpinski_image_with_zoom(color: Color, size: u32, zoom: f32) -> Rg
         lerp(centre, zoom_point.1, zoom),
     );
     let new_height = lerp(main_triangle_height, main_triangle_height * 2.0, zoom);
-    triangles.push(Triangle {
+    let triangle = Triangle {
         centre: new_centre,
         height: new_height,
-        direction: TriangleDirection::Down,
-    });
+    };
+    draw_sierpinski_mut(&mut image, color, triangle, None);
 
-    let color = color.to_rgba8();
-    while let Some(triangle) = triangles.pop() {
-        draw_triangle_mut(&mut image, Rgba(color), triangle);
-        if triangle.height >= 10.0 {
-            let factor = match triangle.direction {
-                TriangleDirection::Up => 1.0,
-                TriangleDirection::Down => -1.0,
-            };
-            let new_height = triangle.height / 2.0;
-            let triangle1 = Triangle {
-                centre: (
-                    triangle.centre.0 - factor * new_height / 2.0,
-                    triangle.centre.1 - factor * new_height / 2.0,
-                ),
-                height: new_height,
-                direction: triangle.direction,
-            };
-            let triangle2 = Triangle {
-                centre: (
-                    triangle.centre.0 + factor * new_height / 2.0,
-                    triangle.centre.1 - factor * new_height / 2.0,
-                ),
-                height: new_height,
-                direction: triangle.direction,
-            };
-            let triangle3 = Triangle {
-                centre: (
-                    triangle.centre.0,
-                    triangle.centre.1 + factor * new_height / 2.0,
-                ),
-                height: new_height,
-                direction: triangle.direction,
-            };
-
-            triangles.push(triangle1);
-            triangles.push(triangle2);
-            triangles.push(triangle3);
+    image
+}
+
+pub fn generate_sierpinski_zoom_images(
+    color: Color,
+    background_color: Color,
+    size: u32,
+) -> Vec<RgbaImage> {
+    render_frames(0..=20, |i| {
+        let zoom = i as f32 / 20.0;
+        generate_sierpinski_image_with_zoom(&color, &background_color, size, zoom)
+    })
+}
+
+/// Turns the triangle a full circle around its centroid over the frames, shrunk so its
+/// corners stay inside the image as it turns
+pub fn generate_sierpinski_rotate_images(
+    color: Color,
+    background_color: Color,
+    size: u32,
+    frame_num: u32,
+) -> Vec<RgbaImage> {
+    let centre = size as f32 / 2.0;
+    // the top corner is furthest from the centroid at two thirds of the height, which
+    // has to fit in half the image
+    let height = size as f32 * 0.75;
+    // the centroid is a sixth of the height below the middle of the triangle, so it's
+    // moved up by that to put the centroid in the middle of the image
+    let triangle = Triangle {
+        centre: (centre, centre - height / 6.0),
+        height,
+    };
+
+    render_frames(0..frame_num, |frame| {
+        let angle = std::f32::consts::TAU * frame as f32 / frame_num as f32;
+        let rotation = Rotation::new((centre, centre), angle);
+        let mut image = RgbaImage::from_pixel(size, size, Rgba(background_color.to_rgba8()));
+        draw_sierpinski_mut(&mut image, &color, triangle, Some(rotation));
+        image
+    })
+}
+
+pub struct ChaosGameOptions {
+    color: Color,
+    background_color: Color,
+    size: u32,
+    /// How many points to plot in total
+    point_num: u32,
+    seed: Option<u64>,
+}
+
+impl ChaosGameOptions {
+    pub fn new(
+        color: Color,
+        background_color: Color,
+        size: u32,
+        point_num: u32,
+        seed: Option<u64>,
+    ) -> Self {
+        Self {
+            color,
+            background_color,
+            size,
+            point_num,
+            seed,
         }
     }
 
-    image
+    /// Checks the game can be played, giving what to change if it can't
+    pub fn check(&self) -> Result<(), String> {
+        if self.size < MIN_CHAOS_GAME_SIZE {
+            return Err(format!(
+                "the chaos game needs an image at least {} pixels across for the gaps between \
+                 its dots to show, use a bigger --size than {}",
+                MIN_CHAOS_GAME_SIZE, self.size
+            ));
+        }
+        match self.point_num > 0 {
+            true => Ok(()),
+            false => {
+                Err("the chaos game needs at least 1 point, use --points 1 or more".to_string())
+            }
+        }
+    }
+}
+
+pub fn generate_chaos_game_image(options: ChaosGameOptions) -> DynamicImage {
+    let point_num = options.point_num;
+    let mut images = generate_chaos_game_images(options, point_num);
+
+    DynamicImage::ImageRgba8(images.pop().unwrap())
 }
 
-pub fn generate_sierpinski_zoom_images(color: Color, size: u32) -> Vec<RgbaImage> {
+/// Plays the chaos game in the same triangle the lines are drawn in, saving a frame
+/// every so many points so the triangle can be seen coming out of the noise
+pub fn generate_chaos_game_images(
+    options: ChaosGameOptions,
+    points_per_frame: u32,
+) -> Vec<RgbaImage> {
+    let mut rng = match options.seed {
+        Some(seed) => fastrand::Rng::with_seed(seed),
+        None => fastrand::Rng::new(),
+    };
+    let size = options.size;
+    let corners = Triangle {
+        centre: (size as f32 / 2.0, size as f32 / 2.0),
+        height: size as f32,
+    }
+    .get_points();
+    let color = Rgba(options.color.to_rgba8());
+
+    let mut image = RgbaImage::from_pixel(size, size, Rgba(options.background_color.to_rgba8()));
     let mut images = Vec::new();
+    // the first few points land outside the triangle, but they're soon pulled into it
+    let mut point = (rng.f32() * size as f32, rng.f32() * size as f32);
+    for point_index in 1..=options.point_num {
+        let corner = corners[rng.usize(..corners.len())];
+        point = ((point.0 + corner.0) / 2.0, (point.1 + corner.1) / 2.0);
+        let (x, y) = (point.0 as u32, point.1 as u32);
+        // the corners are on the far edges of the image
+        if x < size && y < size {
+            image.put_pixel(x, y, color);
+        }
 
-    for i in 0..=20 {
-        let zoom = i as f32 / 20.0;
-        let image = generate_sierpinski_image_with_zoom(color.clone(), size, zoom);
-        images.push(image);
+        if point_index % points_per_frame == 0 || point_index == options.point_num {
+            images.push(image.clone());
+            if is_cancelled() {
+                break;
+            }
+        }
     }
 
     images
@@ -0,0 +1,185 @@
+//! A starfield fly-through like the old screensavers
+//!
+//! Stars are placed randomly in a box in front of the viewer and move towards them,
+//! being projected onto the screen by dividing by their depth. Once a star passes
+//! the viewer it wraps back around to the far end of the box.
+
+use csscolorparser::Color;
+use image::{Rgba, RgbaImage};
+use imageproc::drawing::{draw_filled_circle_mut, draw_line_segment_mut};
+
+use crate::{
+    cancel::render_frames,
+    generator::{Generated, Generator, GeneratorError},
+    memory::MemoryEstimate,
+    palette::interpolate_color,
+    parse_color, parse_size,
+};
+
+/// Stars closer than this are skipped since they'd be projected way off the screen
+const MIN_DEPTH: f64 = 0.02;
+
+pub struct StarfieldOptions {
+    size: u32,
+    frame_num: u32,
+    star_num: u32,
+    speed: u32,
+    streak_length: f64,
+    seed: Option<u64>,
+    color: Color,
+    background_color: Color,
+}
+
+impl StarfieldOptions {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        size: u32,
+        frame_num: u32,
+        star_num: u32,
+        speed: u32,
+        streak_length: f64,
+        seed: Option<u64>,
+        color: Color,
+        background_color: Color,
+    ) -> Self {
+        Self {
+            size,
+            frame_num,
+            star_num,
+            speed,
+            streak_length,
+            seed,
+            color,
+            background_color,
+        }
+    }
+}
+
+struct Star {
+    x: f64,
+    y: f64,
+    /// depth at the start of the animation, from 0 to 1
+    z: f64,
+}
+
+pub fn generate_starfield_images(options: StarfieldOptions) -> Vec<RgbaImage> {
+    let mut rng = match options.seed {
+        Some(seed) => fastrand::Rng::with_seed(seed),
+        None => fastrand::Rng::new(),
+    };
+    let stars: Vec<Star> = (0..options.star_num)
+        .map(|_| Star {
+            x: rng.f64() * 2.0 - 1.0,
+            y: rng.f64() * 2.0 - 1.0,
+            z: rng.f64(),
+        })
+        .collect();
+
+    let centre = options.size as f64 / 2.0;
+    let project = |star: &Star, z: f64| {
+        (
+            (centre + star.x / z * centre) as f32,
+            (centre + star.y / z * centre) as f32,
+        )
+    };
+
+    let color = options.color.to_rgba8();
+    let background_color = Rgba(options.background_color.to_rgba8());
+    let render_frame = |frame: u32| {
+        let mut image = RgbaImage::from_pixel(options.size, options.size, background_color);
+        // each star travels the whole depth a whole number of times so the animation loops
+        let travelled = frame as f64 / options.frame_num as f64 * options.speed as f64;
+
+        for star in stars.iter() {
+            let z = (star.z - travelled).rem_euclid(1.0);
+            if z < MIN_DEPTH {
+                continue;
+            }
+
+            // closer stars are brighter and bigger
+            let brightness = 1.0 - z;
+            let star_color = Rgba(interpolate_color(color, background_color.0, brightness));
+            let position = project(star, z);
+            // streak back to where the star was slightly further away, clamped so stars
+            // which have just wrapped around don't streak across the whole screen
+            let streak_z = (z + options.streak_length).min(1.0);
+            draw_line_segment_mut(&mut image, project(star, streak_z), position, star_color);
+
+            let radius = (brightness * brightness * 3.0) as i32;
+            draw_filled_circle_mut(
+                &mut image,
+                (position.0 as i32, position.1 as i32),
+                radius,
+                star_color,
+            );
+        }
+
+        image
+    };
+
+    render_frames(0..options.frame_num, render_frame)
+}
+
+#[derive(clap::Args, Debug)]
+pub struct StarfieldArgs {
+    #[arg(short, long, default_value = "500", value_parser = parse_size)]
+    size: u32,
+
+    /// The number of frames in the loop
+    #[arg(short, long, default_value = "60", value_parser = parse_size)]
+    frames: u32,
+
+    /// The number of stars
+    #[arg(long, default_value = "400")]
+    stars: u32,
+
+    /// How many times each star flies past per loop
+    #[arg(long, default_value = "1")]
+    speed: u32,
+
+    /// The length of the streak behind each star, as a fraction of the depth of the field
+    #[arg(long, default_value = "0.05")]
+    streak_length: f64,
+
+    /// The seed used to place the stars, random if not given
+    #[arg(long)]
+    seed: Option<u64>,
+
+    #[arg(short, long, default_value = "white", value_parser = parse_color)]
+    color: Color,
+
+    #[arg(short, long, default_value = "black", value_parser = parse_color)]
+    background_color: Color,
+}
+
+pub struct StarfieldGenerator;
+
+impl Generator for StarfieldGenerator {
+    type Args = StarfieldArgs;
+
+    fn name(&self) -> &'static str {
+        "starfield"
+    }
+
+    fn is_animated(&self, _args: &StarfieldArgs) -> bool {
+        true
+    }
+
+    fn estimate_memory(&self, args: &StarfieldArgs) -> Option<MemoryEstimate> {
+        Some(MemoryEstimate::frames(args.size, args.size, args.frames))
+    }
+
+    fn generate(&self, args: StarfieldArgs) -> Result<Generated, GeneratorError> {
+        let options = StarfieldOptions::new(
+            args.size,
+            args.frames,
+            args.stars,
+            args.speed,
+            args.streak_length,
+            args.seed,
+            args.color,
+            args.background_color,
+        );
+        Ok(Generated::Frames(generate_starfield_images(options)))
+    }
+}
@@ -0,0 +1,383 @@
+//! Module for redrawing photos with the shapes from the other generators
+//!
+//! The photo is turned into how bright it is at each point and which way that's
+//! changing fastest, found with a Sobel filter after blurring it a bit. The dots are
+//! bigger where it's brighter, the Truchet tiles are turned so their bands run along
+//! the edges rather than across them, and the flow field strokes follow the edges
+//! round. Each shape takes its colour from the photo under it.
+//! See <https://en.wikipedia.org/wiki/Sobel_operator> for more info
+
+use std::f64::consts::PI;
+
+use csscolorparser::Color;
+use image::{GrayImage, ImageBuffer, ImageError, Luma, Rgba, RgbaImage, imageops::FilterType};
+use imageproc::{
+    drawing::{draw_antialiased_line_segment_mut, draw_filled_circle_mut},
+    filter::gaussian_blur_f32,
+    gradients::{horizontal_sobel, vertical_sobel},
+};
+
+use crate::{
+    BlendMode, StylizeMode,
+    cancel::is_cancelled,
+    compose::blend_over,
+    flow_field::follow_angles,
+    generator::{Generated, Generator, GeneratorError},
+    parse_color, parse_fraction, parse_size,
+    truchet::draw_quarter_band,
+};
+
+/// The photo with how bright it is and which way that's changing at each pixel
+pub struct ImageGuide {
+    image: RgbaImage,
+    gradient_x: ImageBuffer<Luma<i16>, Vec<i16>>,
+    gradient_y: ImageBuffer<Luma<i16>, Vec<i16>>,
+}
+
+impl ImageGuide {
+    /// Works out the gradients after blurring the photo by the amount, so the small
+    /// details and noise don't send the shapes every which way
+    pub fn new(image: RgbaImage, blur: f32) -> Self {
+        let luminance = GrayImage::from_fn(image.width(), image.height(), |x, y| {
+            Luma([(get_brightness(image[(x, y)]) * 255.0).round() as u8])
+        });
+        let luminance = gaussian_blur_f32(&luminance, blur.max(0.5));
+        Self {
+            gradient_x: horizontal_sobel(&luminance),
+            gradient_y: vertical_sobel(&luminance),
+            image,
+        }
+    }
+
+    /// Gets which way the brightness goes up fastest at a point, and by how much
+    fn get_gradient(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let x = (x.max(0.0) as u32).min(self.image.width() - 1);
+        let y = (y.max(0.0) as u32).min(self.image.height() - 1);
+        (
+            self.gradient_x[(x, y)].0[0] as f64,
+            self.gradient_y[(x, y)].0[0] as f64,
+        )
+    }
+
+    /// Gets the average colour and brightness of a square of the photo, cut off at the
+    /// edges
+    fn get_average(&self, (left, top): (u32, u32), size: u32) -> (Rgba<u8>, f64) {
+        let (right, bottom) = (
+            (left + size).min(self.image.width()),
+            (top + size).min(self.image.height()),
+        );
+        let mut sums = [0.0; 4];
+        let mut brightness_sum = 0.0;
+        for y in top..bottom {
+            for x in left..right {
+                let pixel = self.image[(x, y)];
+                for (sum, channel) in sums.iter_mut().zip(pixel.0) {
+                    *sum += channel as f64;
+                }
+                brightness_sum += get_brightness(pixel);
+            }
+        }
+        let pixel_num = ((right - left) * (bottom - top)).max(1) as f64;
+        (
+            Rgba(sums.map(|sum| (sum / pixel_num).round() as u8)),
+            brightness_sum / pixel_num,
+        )
+    }
+}
+
+fn get_brightness(pixel: Rgba<u8>) -> f64 {
+    let [red, green, blue, alpha] = pixel.0;
+    (0.2126 * red as f64 + 0.7152 * green as f64 + 0.0722 * blue as f64) / 255.0
+        * (alpha as f64 / 255.0)
+}
+
+pub struct StylizeOptions {
+    mode: StylizeMode,
+    /// How far apart the dots are and how big the tiles are in pixels
+    cell_size: u32,
+    /// How many flow field strokes there are
+    stroke_num: u32,
+    /// How many pixels long each stroke can be
+    stroke_length: u32,
+    /// How much each stroke covers what's under it, from 0 to 1
+    opacity: f64,
+    /// Draws every shape this colour rather than the colour of the photo under it
+    color: Option<Color>,
+    background_color: Color,
+    seed: Option<u64>,
+}
+
+impl StylizeOptions {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        mode: StylizeMode,
+        cell_size: u32,
+        stroke_num: u32,
+        stroke_length: u32,
+        opacity: f64,
+        color: Option<Color>,
+        background_color: Color,
+        seed: Option<u64>,
+    ) -> Self {
+        Self {
+            mode,
+            cell_size,
+            stroke_num,
+            stroke_length,
+            opacity,
+            color,
+            background_color,
+            seed,
+        }
+    }
+
+    fn get_color(&self, photo_color: Rgba<u8>) -> Rgba<u8> {
+        match &self.color {
+            Some(color) => Rgba(color.to_rgba8()),
+            None => photo_color,
+        }
+    }
+}
+
+/// Whether a Truchet tile should be flipped so its bands run along the edges, from the
+/// gradients over it. Unflipped bands go from top right to bottom left, which is along
+/// edges where the gradient points down and right or up and left, so the product of
+/// its parts is positive. Gives nothing when there's no clear way for the edges to go
+pub fn get_truchet_flip(gradients: impl Iterator<Item = (f64, f64)>) -> Option<bool> {
+    let (mut product_sum, mut size_sum) = (0.0, 0.0);
+    for (x, y) in gradients {
+        product_sum += x * y;
+        size_sum += x * x + y * y;
+    }
+    // the products add up to half the squared size at most, for diagonal edges
+    (product_sum.abs() > 0.1 * size_sum && size_sum > 0.0).then_some(product_sum < 0.0)
+}
+
+fn draw_dots(image: &mut RgbaImage, guide: &ImageGuide, options: &StylizeOptions) {
+    let cell_size = options.cell_size;
+    for top in (0..image.height()).step_by(cell_size as usize) {
+        for left in (0..image.width()).step_by(cell_size as usize) {
+            let (color, brightness) = guide.get_average((left, top), cell_size);
+            // the area of the dot goes with the brightness rather than its radius
+            let radius = cell_size as f64 / 2.0 * brightness.sqrt();
+            if radius < 0.5 {
+                continue;
+            }
+            let centre = (left + cell_size / 2, top + cell_size / 2);
+            draw_filled_circle_mut(
+                image,
+                (centre.0 as i32, centre.1 as i32),
+                radius.round() as i32,
+                options.get_color(color),
+            );
+        }
+    }
+}
+
+fn draw_truchet(
+    image: &mut RgbaImage,
+    guide: &ImageGuide,
+    options: &StylizeOptions,
+    rng: &mut fastrand::Rng,
+) {
+    let cell_size = options.cell_size;
+    let size = cell_size as f64;
+    for top in (0..image.height()).step_by(cell_size as usize) {
+        for left in (0..image.width()).step_by(cell_size as usize) {
+            let gradients = (0..cell_size).flat_map(|y| {
+                (0..cell_size)
+                    .map(move |x| guide.get_gradient(((left + x) as f64, (top + y) as f64)))
+            });
+            let flip = get_truchet_flip(gradients).unwrap_or_else(|| rng.bool());
+            let color = options.get_color(guide.get_average((left, top), cell_size).0);
+
+            let (left, top) = (left as f64, top as f64);
+            let (right, bottom) = (left + size, top + size);
+            // the same bands as the truchet generator
+            let corners = match flip {
+                false => [((left, top), 0.0), ((right, bottom), PI)],
+                true => [((right, top), PI / 2.0), ((left, bottom), 3.0 * PI / 2.0)],
+            };
+            for (corner, start_angle) in corners {
+                draw_quarter_band(
+                    image,
+                    corner,
+                    start_angle,
+                    size / 3.0,
+                    size * 2.0 / 3.0,
+                    color,
+                );
+            }
+        }
+    }
+}
+
+fn draw_flow(
+    image: &mut RgbaImage,
+    guide: &ImageGuide,
+    options: &StylizeOptions,
+    rng: &mut fastrand::Rng,
+) {
+    let (width, height) = image.dimensions();
+    for _ in 0..options.stroke_num {
+        if is_cancelled() {
+            break;
+        }
+        let start = (rng.f64() * width as f64, rng.f64() * height as f64);
+        let color = options.get_color(guide.image[(start.0 as u32, start.1 as u32)]);
+        // a quarter turn from the gradient goes along the edge
+        let path = follow_angles(start, options.stroke_length, (width, height), |point| {
+            let (x, y) = guide.get_gradient(point);
+            y.atan2(x) + PI / 2.0
+        });
+
+        for step in path.windows(2) {
+            draw_antialiased_line_segment_mut(
+                image,
+                (step[0].0.round() as i32, step[0].1.round() as i32),
+                (step[1].0.round() as i32, step[1].1.round() as i32),
+                color,
+                |line, below, amount| {
+                    Rgba(blend_over(
+                        below.0,
+                        line.0,
+                        amount as f64 * options.opacity,
+                        BlendMode::Normal,
+                    ))
+                },
+            );
+        }
+    }
+}
+
+pub fn generate_stylize_image(photo: RgbaImage, options: StylizeOptions) -> RgbaImage {
+    let mut rng = match options.seed {
+        Some(seed) => fastrand::Rng::with_seed(seed),
+        None => fastrand::Rng::new(),
+    };
+    let (width, height) = photo.dimensions();
+    let mut image = RgbaImage::from_pixel(width, height, Rgba(options.background_color.to_rgba8()));
+    let guide = ImageGuide::new(photo, options.cell_size as f32 / 2.0);
+
+    match options.mode {
+        StylizeMode::Dots => draw_dots(&mut image, &guide, &options),
+        StylizeMode::Truchet => draw_truchet(&mut image, &guide, &options, &mut rng),
+        StylizeMode::Flow => draw_flow(&mut image, &guide, &options, &mut rng),
+    }
+
+    image
+}
+
+#[derive(clap::Args, Debug)]
+pub struct StylizeArgs {
+    /// The photo to redraw
+    #[arg(long)]
+    file: String,
+
+    #[arg(short, long, default_value = "flow")]
+    mode: StylizeMode,
+
+    /// How far apart the dots are and how big the tiles are in pixels, which is also
+    /// how much the photo is blurred before finding its edges
+    #[arg(long, default_value = "12", value_parser = parse_size)]
+    cell_size: u32,
+
+    /// How many flow field strokes there are
+    #[arg(long, default_value = "20000", value_parser = parse_size)]
+    strokes: u32,
+
+    /// How many pixels long each stroke can be
+    #[arg(long, default_value = "40", value_parser = parse_size)]
+    stroke_length: u32,
+
+    /// How see-through each stroke is, from just above 0 to 1 for solid
+    #[arg(long, default_value = "0.5", value_parser = parse_fraction)]
+    opacity: f64,
+
+    /// Draws every shape this colour rather than the colour of the photo under it
+    #[arg(short, long, value_parser = parse_color)]
+    color: Option<Color>,
+
+    #[arg(short, long, alias = "background", default_value = "black", value_parser = parse_color)]
+    background_color: Color,
+
+    /// Scales the photo to this width first, keeping its shape
+    #[arg(long, value_parser = parse_size)]
+    width: Option<u32>,
+
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+pub struct StylizeGenerator;
+
+impl Generator for StylizeGenerator {
+    type Args = StylizeArgs;
+
+    fn name(&self) -> &'static str {
+        "stylize"
+    }
+
+    fn is_animated(&self, _args: &StylizeArgs) -> bool {
+        false
+    }
+
+    fn generate(&self, args: StylizeArgs) -> Result<Generated, GeneratorError> {
+        let photo = image::open(&args.file)
+            .map_err(|error| match error {
+                ImageError::IoError(source) => GeneratorError::ReadFile {
+                    path: args.file.clone(),
+                    source,
+                },
+                error => GeneratorError::Failed(format!("opening {}: {}", args.file, error)),
+            })?
+            .into_rgba8();
+        let photo = match args.width {
+            Some(width) => {
+                let height = (photo.height() as f64 * width as f64 / photo.width() as f64)
+                    .round()
+                    .max(1.0) as u32;
+                image::imageops::resize(&photo, width, height, FilterType::Triangle)
+            }
+            None => photo,
+        };
+
+        Ok(Generated::Still(generate_stylize_image(
+            photo,
+            StylizeOptions::new(
+                args.mode,
+                args.cell_size,
+                args.strokes,
+                args.stroke_length,
+                args.opacity,
+                args.color,
+                args.background_color,
+                args.seed,
+            ),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truchet_flip() {
+        // a photo going from dark at the top left to bright at the bottom right has
+        // edges going from top right to bottom left, which is the way unflipped bands go
+        let photo = RgbaImage::from_fn(40, 40, |x, y| {
+            let value = ((x + y) * 3) as u8;
+            Rgba([value, value, value, 255])
+        });
+        let guide = ImageGuide::new(photo, 1.0);
+        let gradients = (10..30).flat_map(|y| (10..30).map(move |x| (x as f64, y as f64)));
+        let gradients: Vec<(f64, f64)> = gradients.map(|point| guide.get_gradient(point)).collect();
+        assert_eq!(get_truchet_flip(gradients.iter().copied()), Some(false));
+        // and flipped the other way round
+        let flipped = gradients.iter().map(|&(x, y)| (-x, y));
+        assert_eq!(get_truchet_flip(flipped), Some(true));
+        // a flat photo has no edges to follow
+        assert_eq!(get_truchet_flip([(0.0, 0.0)].into_iter()), None);
+    }
+}
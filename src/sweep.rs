@@ -0,0 +1,319 @@
+//! Rendering an image over a range of values for its options, as a contact sheet
+//!
+//! The image is written like its command line, then one or two of its options are
+//! changed across the columns and rows of the sheet, with the values written along
+//! the top and left edges
+
+use clap::FromArgMatches;
+use fun_images::{
+    Fit,
+    memory::MemoryEstimate,
+    post::{apply_post_processing, resize},
+    text::{draw_text_on_image, get_text_size},
+};
+use image::{Rgba, RgbaImage, imageops};
+use rayon::prelude::*;
+
+use crate::{
+    Args, ImageFormat, ImageType,
+    error::Error,
+    gallery::{flatten_image, render_image_type},
+    presets::get_preset_matches,
+    recipe::{apply_override, get_recipe},
+};
+
+const LABEL_FONT_SIZE: f32 = 16.0;
+const SPACING: u32 = 8;
+
+/// An option to change across the sheet, given as name=start..end or name=a,b,c
+#[derive(Clone, Debug)]
+pub struct SweepAxis {
+    name: String,
+    values: SweepValues,
+}
+
+#[derive(Clone, Debug)]
+enum SweepValues {
+    List(Vec<String>),
+    /// Numbers to go between, with more than one for options like center=x,y
+    Range {
+        start: Vec<f64>,
+        end: Vec<f64>,
+        whole_numbers: bool,
+    },
+}
+
+impl SweepAxis {
+    /// Gets the values along the axis, with ranges split into the number of steps
+    pub fn get_values(&self, steps: u32) -> Vec<String> {
+        let (start, end, whole_numbers) = match &self.values {
+            SweepValues::List(values) => return values.clone(),
+            SweepValues::Range {
+                start,
+                end,
+                whole_numbers,
+            } => (start, end, *whole_numbers),
+        };
+
+        (0..steps)
+            .map(|step| {
+                let amount = match steps {
+                    1 => 0.0,
+                    _ => step as f64 / (steps - 1) as f64,
+                };
+                let parts: Vec<_> = start
+                    .iter()
+                    .zip(end.iter())
+                    .map(|(start, end)| {
+                        let value = start + (end - start) * amount;
+                        match whole_numbers {
+                            true => value.round().to_string(),
+                            // rounded so the labels don't show floating point noise
+                            false => ((value * 1e6).round() / 1e6).to_string(),
+                        }
+                    })
+                    .collect();
+                parts.join(",")
+            })
+            .collect()
+    }
+
+    pub fn get_override(&self, value: &str) -> String {
+        format!("{}={}", self.name, value)
+    }
+}
+
+pub fn parse_sweep_axis(value: &str) -> Result<SweepAxis, String> {
+    let (name, values) = value
+        .split_once('=')
+        .ok_or_else(|| "expected name=start..end or name=a,b,c".to_string())?;
+    let name = name.trim().trim_start_matches("--").to_string();
+    if name.is_empty() {
+        return Err("the option name is missing".to_string());
+    }
+
+    let values = match values.split_once("..") {
+        Some((start, end)) => {
+            let parse_numbers = |numbers: &str| {
+                numbers
+                    .split(',')
+                    .map(|number| number.trim().parse::<f64>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| format!("{} isn't a number, or numbers split by commas", numbers))
+            };
+            let (start_numbers, end_numbers) = (parse_numbers(start)?, parse_numbers(end)?);
+            if start_numbers.len() != end_numbers.len() {
+                return Err("the start and end of the range need as many numbers".to_string());
+            }
+            let is_whole = |numbers: &str| !numbers.contains(['.', 'e', 'E']);
+            SweepValues::Range {
+                start: start_numbers,
+                end: end_numbers,
+                whole_numbers: is_whole(start) && is_whole(end),
+            }
+        }
+        None => SweepValues::List(
+            values
+                .split(',')
+                .map(|value| value.trim().to_string())
+                .collect(),
+        ),
+    };
+
+    Ok(SweepAxis { name, values })
+}
+
+/// Renders the image for every value along the axes and puts them in a labelled grid
+pub fn render_sweep(
+    image: &[String],
+    x: &SweepAxis,
+    y: Option<&SweepAxis>,
+    steps: u32,
+    cell_size: u32,
+) -> Result<RgbaImage, Error> {
+    let images = parse_cells(image, x, y, steps)?
+        .into_par_iter()
+        .map(|args| {
+            let image = render_image_type(args.image_type)?;
+            let image = apply_post_processing(image, &args.post).map_err(Error::PostEffects)?;
+            let image = flatten_image(&image);
+            Ok(resize(&image, (cell_size, cell_size), Fit::Contain))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let x_labels: Vec<_> = x
+        .get_values(steps)
+        .iter()
+        .map(|value| x.get_override(value))
+        .collect();
+    let y_labels: Vec<_> = get_y_overrides(y, steps).into_iter().flatten().collect();
+    Ok(make_sheet(&images, &x_labels, &y_labels, cell_size))
+}
+
+/// Roughly how much memory the sweep takes, counting every cell as if they were all
+/// made at once, or None when the image can't be parsed
+pub fn estimate_sweep_memory(
+    image: &[String],
+    x: &SweepAxis,
+    y: Option<&SweepAxis>,
+    steps: u32,
+    cell_size: u32,
+) -> Option<MemoryEstimate> {
+    let cells = parse_cells(image, x, y, steps).ok()?;
+    let working = cells
+        .iter()
+        .filter_map(|args| args.image_type.estimate_memory())
+        .fold(0u64, |total, estimate| {
+            total.saturating_add(estimate.total())
+        });
+    // leaving out the labels, which only add a little to the edges
+    let columns = x.get_values(steps).len() as u32;
+    let rows = (cells.len() as u32).div_ceil(columns.max(1));
+    let sheet = MemoryEstimate::still(
+        columns.saturating_mul(cell_size + SPACING),
+        rows.saturating_mul(cell_size + SPACING),
+    );
+    Some(sheet.with_working(working))
+}
+
+/// Parses the image for every cell of the sheet, going along the rows
+fn parse_cells(
+    image: &[String],
+    x: &SweepAxis,
+    y: Option<&SweepAxis>,
+    steps: u32,
+) -> Result<Vec<Args>, Error> {
+    // parsing first puts the options in the same form the overrides use
+    let base_recipe = get_recipe(&parse_image(image)?.1);
+    let x_values = x.get_values(steps);
+
+    let mut cells = Vec::new();
+    for y_override in get_y_overrides(y, steps).iter() {
+        for x_value in x_values.iter() {
+            let mut recipe = base_recipe.clone();
+            apply_override(&mut recipe, &x.get_override(x_value))?;
+            if let Some(y_override) = y_override {
+                apply_override(&mut recipe, y_override)?;
+            }
+            cells.push(parse_image(&recipe)?.0);
+        }
+    }
+
+    Ok(cells)
+}
+
+/// The override for each row, or a single row without one when there's no y axis
+fn get_y_overrides(y: Option<&SweepAxis>, steps: u32) -> Vec<Option<String>> {
+    match y {
+        Some(y) => y
+            .get_values(steps)
+            .iter()
+            .map(|value| Some(y.get_override(value)))
+            .collect(),
+        None => vec![None],
+    }
+}
+
+/// Parses the words of an image's command line, which has to make a still image or
+/// an animation
+fn parse_image(words: &[String]) -> Result<(Args, clap::ArgMatches), Error> {
+    let command_line: Vec<String> = std::iter::once("fun-images".to_string())
+        .chain(words.iter().cloned())
+        .collect();
+    let matches = get_preset_matches(&command_line).map_err(|clap_error| {
+        let message = clap_error.to_string();
+        let message = message.lines().next().unwrap_or_default();
+        Error::Sweep(message.trim_start_matches("error: ").to_string())
+    })?;
+    let args = Args::from_arg_matches(&matches)
+        .map_err(|clap_error| Error::Sweep(clap_error.to_string()))?;
+    match args.image_type.get_format() {
+        ImageFormat::Static | ImageFormat::Animated
+            if !matches!(args.image_type, ImageType::Sweep { .. }) =>
+        {
+            Ok((args, matches))
+        }
+        _ => Err(Error::Sweep(format!(
+            "{} can't be swept, only images can",
+            words.first().map(String::as_str).unwrap_or_default()
+        ))),
+    }
+}
+
+/// Lays the images out in rows, with the x labels above the columns and the y labels
+/// to the left of the rows
+fn make_sheet(
+    images: &[RgbaImage],
+    x_labels: &[String],
+    y_labels: &[String],
+    cell_size: u32,
+) -> RgbaImage {
+    let label_height = LABEL_FONT_SIZE.ceil() as u32;
+    let y_label_width = y_labels
+        .iter()
+        .map(|label| get_text_size(label, LABEL_FONT_SIZE).0 + SPACING)
+        .max()
+        .unwrap_or(0);
+    let columns = x_labels.len() as u32;
+    let rows = (images.len() as u32).div_ceil(columns);
+    let left = SPACING + y_label_width;
+    let top = SPACING + label_height + SPACING;
+
+    let mut sheet = RgbaImage::from_pixel(
+        left + columns * (cell_size + SPACING),
+        top + rows * (cell_size + SPACING),
+        Rgba([24, 24, 28, 255]),
+    );
+    let text_color = Rgba([230, 230, 230, 255]);
+    for (column, label) in x_labels.iter().enumerate() {
+        let (width, _) = get_text_size(label, LABEL_FONT_SIZE);
+        let x = left + column as u32 * (cell_size + SPACING);
+        let x = x as i32 + (cell_size as i32 - width as i32) / 2;
+        draw_text_on_image(
+            &mut sheet,
+            label,
+            (x, SPACING as i32),
+            LABEL_FONT_SIZE,
+            text_color,
+        );
+    }
+    for (row, label) in y_labels.iter().enumerate() {
+        let y = top + row as u32 * (cell_size + SPACING) + (cell_size - label_height) / 2;
+        draw_text_on_image(
+            &mut sheet,
+            label,
+            (SPACING as i32, y as i32),
+            LABEL_FONT_SIZE,
+            text_color,
+        );
+    }
+    for (index, image) in images.iter().enumerate() {
+        let x = left + (index as u32 % columns) * (cell_size + SPACING);
+        let y = top + (index as u32 / columns) * (cell_size + SPACING);
+        imageops::overlay(&mut sheet, image, x as i64, y as i64);
+    }
+
+    sheet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sweep_values() {
+        let axis = parse_sweep_axis("size=100..300").unwrap();
+        assert_eq!(axis.get_values(3), vec!["100", "200", "300"]);
+
+        let axis = parse_sweep_axis("center=-1,0..0,0.5").unwrap();
+        assert_eq!(axis.get_values(3), vec!["-1,0", "-0.5,0.25", "0,0.5"]);
+
+        let axis = parse_sweep_axis("--color=red, green,blue").unwrap();
+        assert_eq!(axis.name, "color");
+        assert_eq!(axis.get_values(10), vec!["red", "green", "blue"]);
+
+        assert!(parse_sweep_axis("size").is_err());
+        assert!(parse_sweep_axis("zoom=1..x").is_err());
+        assert!(parse_sweep_axis("center=0,0..1").is_err());
+    }
+}
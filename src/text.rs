@@ -0,0 +1,41 @@
+//! Text and glyph drawing shared by anything that needs to put characters on an image
+//!
+//! Uses a bundled copy of DejaVu Sans Mono so the output doesn't depend on the fonts
+//! installed on the system, see `assets/DejaVuSansMono-LICENSE.txt` for its license
+
+use std::sync::OnceLock;
+
+use ab_glyph::FontRef;
+use image::{Rgba, RgbaImage};
+use imageproc::drawing::{draw_text_mut, text_size};
+
+static FONT_DATA: &[u8] = include_bytes!("../assets/DejaVuSansMono.ttf");
+
+fn font() -> &'static FontRef<'static> {
+    static FONT: OnceLock<FontRef<'static>> = OnceLock::new();
+    FONT.get_or_init(|| FontRef::try_from_slice(FONT_DATA).expect("Bundled font should be valid"))
+}
+
+/// Draws text with its top left corner at the position, blending it onto the image
+pub fn draw_text_on_image(
+    image: &mut RgbaImage,
+    text: &str,
+    position: (i32, i32),
+    font_size: f32,
+    color: Rgba<u8>,
+) {
+    draw_text_mut(
+        image,
+        color,
+        position.0,
+        position.1,
+        font_size,
+        font(),
+        text,
+    );
+}
+
+/// Gets the width and height the text takes up when drawn at the font size
+pub fn get_text_size(text: &str, font_size: f32) -> (u32, u32) {
+    text_size(font_size, font(), text)
+}
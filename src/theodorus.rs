@@ -0,0 +1,291 @@
+//! Module for drawing the spiral of Theodorus
+//!
+//! Starting from a right triangle with two sides of length 1, each triangle is built on
+//! the long side of the one before with another side of length 1, so the long sides go
+//! √2, √3, √4 and so on. The triangles wind round without overlapping for the first 16.
+//! See <https://en.wikipedia.org/wiki/Spiral_of_Theodorus> for more info
+
+use csscolorparser::Color;
+use image::{Rgba, RgbaImage};
+use imageproc::{drawing::draw_polygon_mut, point::Point};
+
+use crate::{
+    farey::draw_thick_line,
+    generator::{Generated, Generator, GeneratorError},
+    memory::MemoryEstimate,
+    palette::interpolate_color,
+    parse_color, parse_size,
+    text::{draw_text_on_image, get_text_size},
+};
+
+/// The space around the spiral, as a fraction of the image size
+const MARGIN: f64 = 0.08;
+
+/// The shortest the outer side of a triangle can be in pixels, so that its corners
+/// never round to the same pixel
+const MIN_SIDE_LENGTH: f64 = 2.0;
+
+pub struct TheodorusOptions {
+    /// How many triangles to draw
+    term_num: u32,
+    color: Color,
+    /// The colour of the last triangle, with the ones between fading from color to it
+    end_color: Color,
+    line_color: Color,
+    line_thickness: u32,
+    /// Labels each spoke with its length when given
+    label_color: Option<Color>,
+    background_color: Color,
+    size: u32,
+}
+
+impl TheodorusOptions {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        term_num: u32,
+        color: Color,
+        end_color: Color,
+        line_color: Color,
+        line_thickness: u32,
+        label_color: Option<Color>,
+        background_color: Color,
+        size: u32,
+    ) -> Self {
+        Self {
+            term_num,
+            color,
+            end_color,
+            line_color,
+            line_thickness,
+            label_color,
+            background_color,
+            size,
+        }
+    }
+
+    /// Checks every triangle's outer side is long enough to be drawn, giving what to
+    /// change if not
+    pub fn check(&self) -> Result<(), String> {
+        match get_scale(self.size, self.term_num) >= MIN_SIDE_LENGTH {
+            true => Ok(()),
+            false => {
+                let min_size = (MIN_SIDE_LENGTH * 2.0 * (self.term_num as f64 + 1.0).sqrt()
+                    / (1.0 - 2.0 * MARGIN))
+                    .ceil();
+                Err(format!(
+                    "the triangles of {} terms are too small to draw at a size of {}, use a \
+                     --size of at least {} or fewer --terms",
+                    self.term_num, self.size, min_size
+                ))
+            }
+        }
+    }
+}
+
+/// How many pixels the spokes are stretched by, which is also the length of the outer
+/// side of every triangle as they're all 1 long
+fn get_scale(size: u32, term_num: u32) -> f64 {
+    size as f64 / 2.0 * (1.0 - 2.0 * MARGIN) / (term_num as f64 + 1.0).sqrt()
+}
+
+/// Gets the outer ends of the spokes from the centre, the nth one being √n long. The
+/// first goes along the positive x axis and each one after is a right angle turn from
+/// the end of the one before
+pub fn get_spoke_ends(term_num: u32) -> Vec<(f64, f64)> {
+    let mut angle: f64 = 0.0;
+    (1..=term_num + 1)
+        .map(|n| {
+            let length = (n as f64).sqrt();
+            let point = (length * angle.cos(), length * angle.sin());
+            angle += (1.0 / length).atan();
+            point
+        })
+        .collect()
+}
+
+pub fn generate_theodorus_image(options: TheodorusOptions) -> RgbaImage {
+    let size = options.size;
+    let mut image = RgbaImage::from_pixel(size, size, Rgba(options.background_color.to_rgba8()));
+
+    let spoke_ends = get_spoke_ends(options.term_num);
+    let centre = size as f64 / 2.0;
+    let scale = get_scale(size, options.term_num);
+    // the y axis goes up so it winds anticlockwise
+    let to_pixel = |(x, y): (f64, f64)| {
+        Point::new(
+            (centre + x * scale).round() as i32,
+            (centre - y * scale).round() as i32,
+        )
+    };
+    let origin = to_pixel((0.0, 0.0));
+    let points: Vec<Point<i32>> = spoke_ends.iter().copied().map(to_pixel).collect();
+
+    let (start_color, end_color) = (options.color.to_rgba8(), options.end_color.to_rgba8());
+    for (index, spoke) in points.windows(2).enumerate() {
+        let amount = index as f64 / (options.term_num.max(2) - 1) as f64;
+        let color = Rgba(interpolate_color(end_color, start_color, amount));
+        draw_polygon_mut(&mut image, &[origin, spoke[0], spoke[1]], color);
+    }
+
+    let line_color = Rgba(options.line_color.to_rgba8());
+    if options.line_thickness > 0 {
+        // draw_thick_line goes out by the thickness on both sides
+        let thickness = (options.line_thickness as i32 / 2).max(1);
+        for (index, &point) in points.iter().enumerate() {
+            draw_thick_line(&mut image, line_color, origin, point, thickness);
+            if let Some(&next) = points.get(index + 1) {
+                draw_thick_line(&mut image, line_color, point, next, thickness);
+            }
+        }
+    }
+
+    if let Some(label_color) = options.label_color {
+        let font_size = size as f32 / 40.0;
+        for (index, &(x, y)) in spoke_ends.iter().enumerate() {
+            let square = index + 1;
+            let length = (square as f64).sqrt();
+            let label = match length.fract() == 0.0 {
+                true => length.to_string(),
+                false => format!("√{}", square),
+            };
+            // just past the end of the spoke, centred on it
+            let push = 1.0 + font_size as f64 / scale / length;
+            let position = to_pixel((x * push, y * push));
+            let (width, height) = get_text_size(&label, font_size);
+            draw_text_on_image(
+                &mut image,
+                &label,
+                (
+                    position.x - width as i32 / 2,
+                    position.y - height as i32 / 2,
+                ),
+                font_size,
+                Rgba(label_color.to_rgba8()),
+            );
+        }
+    }
+
+    image
+}
+
+#[derive(clap::Args, Debug)]
+pub struct TheodorusArgs {
+    /// How many triangles to draw, they start to overlap after 16
+    #[arg(short, long, default_value = "16", value_parser = parse_size)]
+    terms: u32,
+
+    /// The colour of the first triangle
+    #[arg(short, long, default_value = "midnightblue", value_parser = parse_color)]
+    color: Color,
+
+    /// The colour of the last triangle, the ones between fade from the first colour
+    #[arg(short, long, default_value = "lightskyblue", value_parser = parse_color)]
+    end_color: Color,
+
+    #[arg(long, default_value = "white", value_parser = parse_color)]
+    line_color: Color,
+
+    /// How thick the lines around the triangles are in pixels, 0 leaves them out
+    #[arg(long, default_value = "4")]
+    line_thickness: u32,
+
+    /// Labels each spoke with its length
+    #[arg(long)]
+    labels: bool,
+
+    #[arg(long, default_value = "black", value_parser = parse_color)]
+    label_color: Color,
+
+    /// The colour behind the spiral, which can be transparent
+    #[arg(short, long, alias = "background", default_value = "white", value_parser = parse_color)]
+    background_color: Color,
+
+    #[arg(short, long, default_value = "1000", value_parser = parse_size)]
+    size: u32,
+}
+
+pub struct TheodorusGenerator;
+
+impl Generator for TheodorusGenerator {
+    type Args = TheodorusArgs;
+
+    fn name(&self) -> &'static str {
+        "theodorus"
+    }
+
+    fn is_animated(&self, _args: &TheodorusArgs) -> bool {
+        false
+    }
+
+    fn estimate_memory(&self, args: &TheodorusArgs) -> Option<MemoryEstimate> {
+        Some(MemoryEstimate::still(args.size, args.size))
+    }
+
+    fn generate(&self, args: TheodorusArgs) -> Result<Generated, GeneratorError> {
+        let options = TheodorusOptions::new(
+            args.terms,
+            args.color,
+            args.end_color,
+            args.line_color,
+            args.line_thickness,
+            args.labels.then_some(args.label_color),
+            args.background_color,
+            args.size,
+        );
+        options.check().map_err(GeneratorError::InvalidOptions)?;
+
+        Ok(Generated::Still(generate_theodorus_image(options)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spoke_ends() {
+        let spoke_ends = get_spoke_ends(16);
+        assert_eq!(spoke_ends.len(), 17);
+        assert_eq!(spoke_ends[0], (1.0, 0.0));
+
+        for (n, window) in spoke_ends.windows(2).enumerate() {
+            let (spoke, next) = (window[0], window[1]);
+            // the outer side of each triangle is 1 long and at right angles to the spoke
+            let side = (next.0 - spoke.0, next.1 - spoke.1);
+            assert!((f64::hypot(side.0, side.1) - 1.0).abs() < 1e-9);
+            assert!((side.0 * spoke.0 + side.1 * spoke.1).abs() < 1e-9);
+            assert!((f64::hypot(next.0, next.1) - ((n + 2) as f64).sqrt()).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        let options = |term_num, size| {
+            let black = Color::new(0.0, 0.0, 0.0, 1.0);
+            TheodorusOptions::new(
+                term_num,
+                black.clone(),
+                black.clone(),
+                black.clone(),
+                1,
+                None,
+                black,
+                size,
+            )
+        };
+        assert!(options(16, 1000).check().is_ok());
+        let error = options(100_000, 1000).check().unwrap_err();
+        // the size it suggests is big enough
+        let min_size: u32 = error
+            .split("at least ")
+            .nth(1)
+            .unwrap()
+            .split(' ')
+            .next()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(options(100_000, min_size).check().is_ok());
+        assert!(options(100_000, min_size - 1).check().is_err());
+    }
+}
@@ -0,0 +1,65 @@
+//! Splitting an image into tiles so huge images can be made a piece at a time
+//!
+//! Generators which support tiles can render any rectangle of the full image on its
+//! own, giving exactly the pixels the full render would have there
+
+/// A rectangle of the full image
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// How many tiles across and down to split the image into
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TileGrid {
+    columns: u32,
+    rows: u32,
+}
+
+impl TileGrid {
+    pub fn new(columns: u32, rows: u32) -> Self {
+        Self { columns, rows }
+    }
+
+    /// Splits an image into rows of tiles, with any leftover pixels going to the
+    /// last tile in each direction. Tiles are never empty, so small images get fewer
+    pub fn get_tile_rows(&self, width: u32, height: u32) -> Vec<Vec<Tile>> {
+        let columns = split(width, self.columns);
+        let rows = split(height, self.rows);
+
+        rows.iter()
+            .map(|&(y, tile_height)| {
+                columns
+                    .iter()
+                    .map(|&(x, tile_width)| Tile {
+                        x,
+                        y,
+                        width: tile_width,
+                        height: tile_height,
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// Splits a length into the starts and lengths of up to the given number of parts
+fn split(length: u32, parts: u32) -> Vec<(u32, u32)> {
+    let parts = parts.clamp(1, length.max(1));
+    let part_length = length / parts;
+
+    (0..parts)
+        .map(|part| {
+            let start = part * part_length;
+            let end = if part + 1 == parts {
+                length
+            } else {
+                start + part_length
+            };
+            (start, end - start)
+        })
+        .collect()
+}
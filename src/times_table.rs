@@ -0,0 +1,200 @@
+//! Module for drawing times tables round a circle
+//!
+//! Numbers are spaced out round a circle, and each number n gets a line to k times n,
+//! wrapping round past the last number. Times 2 makes a cardioid, times 3 a nephroid, and
+//! the whole number multipliers in between make more cusps. Sweeping the multiplier
+//! smoothly from frame to frame, with the ends of the lines sliding round the circle,
+//! morphs from one to the next. See <https://en.wikipedia.org/wiki/Cardioid> for more info
+
+use std::f64::consts::TAU;
+
+use csscolorparser::Color;
+use image::{Rgba, RgbaImage};
+use imageproc::drawing::draw_antialiased_line_segment_mut;
+
+use crate::{
+    BlendMode, PaletteType,
+    cancel::render_frames,
+    compose::blend_over,
+    generator::{Generated, Generator, GeneratorError},
+    memory::MemoryEstimate,
+    palette::get_palette_color,
+    parse_color, parse_fraction, parse_size,
+};
+
+/// The space around the circle, as a fraction of the image size
+const MARGIN: f64 = 0.05;
+
+pub struct TimesTableOptions {
+    /// How many numbers are spaced out round the circle
+    point_num: u32,
+    palette: PaletteType,
+    /// How much each line covers the ones drawn before it, from 0 to 1
+    opacity: f64,
+    background_color: Color,
+    size: u32,
+}
+
+impl TimesTableOptions {
+    pub fn new(
+        point_num: u32,
+        palette: PaletteType,
+        opacity: f64,
+        background_color: Color,
+        size: u32,
+    ) -> Self {
+        Self {
+            point_num,
+            palette,
+            opacity,
+            background_color,
+            size,
+        }
+    }
+}
+
+/// Gets the lines from each number to the multiplier times it, as the amounts of the
+/// way round the circle they start and end at
+pub fn get_times_table_lines(point_num: u32, multiplier: f64) -> Vec<(f64, f64)> {
+    (0..point_num)
+        .map(|number| {
+            let product = (number as f64 * multiplier).rem_euclid(point_num as f64);
+            (number as f64 / point_num as f64, product / point_num as f64)
+        })
+        .collect()
+}
+
+pub fn generate_times_table_image(options: &TimesTableOptions, multiplier: f64) -> RgbaImage {
+    let size = options.size;
+    let mut image = RgbaImage::from_pixel(size, size, Rgba(options.background_color.to_rgba8()));
+
+    let radius = size as f64 * (0.5 - MARGIN);
+    // starting from the left going clockwise, so the lines are mirrored top to bottom
+    let to_pixel = |amount: f64| {
+        let angle = amount * TAU;
+        (
+            (size as f64 / 2.0 - radius * angle.cos()).round() as i32,
+            (size as f64 / 2.0 - radius * angle.sin()).round() as i32,
+        )
+    };
+
+    for (start, end) in get_times_table_lines(options.point_num, multiplier) {
+        draw_antialiased_line_segment_mut(
+            &mut image,
+            to_pixel(start),
+            to_pixel(end),
+            Rgba(get_palette_color(options.palette, start * 0.8)),
+            |line, below, amount| {
+                Rgba(blend_over(
+                    below.0,
+                    line.0,
+                    amount as f64 * options.opacity,
+                    BlendMode::Normal,
+                ))
+            },
+        );
+    }
+
+    image
+}
+
+/// Renders a frame for each multiplier going evenly from the first to the last
+pub fn generate_times_table_images(
+    options: &TimesTableOptions,
+    (first, last): (f64, f64),
+    frame_num: u32,
+) -> Vec<RgbaImage> {
+    render_frames(0..frame_num, |frame| {
+        let amount = frame as f64 / (frame_num - 1).max(1) as f64;
+        generate_times_table_image(options, first + (last - first) * amount)
+    })
+}
+
+#[derive(clap::Args, Debug)]
+pub struct TimesTableArgs {
+    /// How many numbers are spaced out round the circle
+    #[arg(short = 'n', long, default_value = "200", value_parser = parse_size)]
+    points: u32,
+
+    /// What each number is multiplied by, which doesn't have to be a whole number
+    #[arg(short = 'k', long, default_value = "2")]
+    multiplier: f64,
+
+    /// Animates the multiplier going smoothly from the first one to this
+    #[arg(long)]
+    to: Option<f64>,
+
+    /// The number of frames the multiplier takes to get to the last one
+    #[arg(short, long, default_value = "300", value_parser = parse_size)]
+    frames: u32,
+
+    /// The palette the lines are coloured by the number they start from
+    #[arg(short, long, default_value = "rainbow")]
+    palette: PaletteType,
+
+    /// How see-through each line is, from just above 0 to 1 for solid
+    #[arg(long, default_value = "0.7", value_parser = parse_fraction)]
+    opacity: f64,
+
+    #[arg(short, long, alias = "background", default_value = "black", value_parser = parse_color)]
+    background_color: Color,
+
+    #[arg(short, long, default_value = "800", value_parser = parse_size)]
+    size: u32,
+}
+
+pub struct TimesTableGenerator;
+
+impl Generator for TimesTableGenerator {
+    type Args = TimesTableArgs;
+
+    fn name(&self) -> &'static str {
+        "times-table"
+    }
+
+    fn is_animated(&self, args: &TimesTableArgs) -> bool {
+        args.to.is_some()
+    }
+
+    fn estimate_memory(&self, args: &TimesTableArgs) -> Option<MemoryEstimate> {
+        Some(match args.to {
+            Some(_) => MemoryEstimate::frames(args.size, args.size, args.frames),
+            None => MemoryEstimate::still(args.size, args.size),
+        })
+    }
+
+    fn generate(&self, args: TimesTableArgs) -> Result<Generated, GeneratorError> {
+        let options = TimesTableOptions::new(
+            args.points,
+            args.palette,
+            args.opacity,
+            args.background_color,
+            args.size,
+        );
+
+        Ok(match args.to {
+            Some(last) => Generated::Frames(generate_times_table_images(
+                &options,
+                (args.multiplier, last),
+                args.frames,
+            )),
+            None => Generated::Still(generate_times_table_image(&options, args.multiplier)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_times_table_lines() {
+        // 3 times 4 wraps round to 2 out of 10
+        let lines = get_times_table_lines(10, 4.0);
+        assert_eq!(lines.len(), 10);
+        assert!((lines[3].0 - 0.3).abs() < 1e-9 && (lines[3].1 - 0.2).abs() < 1e-9);
+        // part way between multipliers the ends slide round
+        let lines = get_times_table_lines(10, 2.5);
+        assert!((lines[3].1 - 0.75).abs() < 1e-9);
+    }
+}
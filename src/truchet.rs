@@ -0,0 +1,453 @@
+//! Module for generating Truchet tiles, including the multi-scale ones
+//!
+//! Each tile has two quarter circle bands around opposite corners, turned one of two ways
+//! at random, which join up across the edges into long winding paths. Tiles can also be
+//! split into 4 tiles half the size with the colours swapped. With the bands a third of
+//! the tile wide and a circle a sixth of the tile wide on each corner, the edge of a big
+//! tile looks exactly like the edges of the two small tiles next to it, so the paths
+//! carry on between sizes. That only works for tiles a single split apart, so tiles next
+//! to much smaller ones get split as well.
+//! See <https://en.wikipedia.org/wiki/Truchet_tiles> for more info
+
+use std::f64::consts::PI;
+
+use csscolorparser::Color;
+use image::{Rgba, RgbaImage};
+use imageproc::{
+    drawing::{draw_filled_circle_mut, draw_filled_rect_mut, draw_polygon_mut},
+    point::Point,
+    rect::Rect,
+};
+
+use crate::{
+    generator::{Generated, Generator, GeneratorError},
+    memory::MemoryEstimate,
+    parse_color, parse_size,
+};
+
+/// How many points each quarter circle is made of
+const ARC_POINT_NUM: usize = 32;
+
+pub struct TruchetOptions {
+    width: u32,
+    height: u32,
+    /// The size of the biggest tiles in pixels
+    tile_size: u32,
+    /// How many times a tile can be split, 0 giving plain Truchet tiles
+    max_depth: u32,
+    /// The chance of each tile being split in 4
+    split_chance: f64,
+    color: Color,
+    background_color: Color,
+    seed: Option<u64>,
+}
+
+impl TruchetOptions {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        width: u32,
+        height: u32,
+        tile_size: u32,
+        max_depth: u32,
+        split_chance: f64,
+        color: Color,
+        background_color: Color,
+        seed: Option<u64>,
+    ) -> Self {
+        Self {
+            width,
+            height,
+            tile_size,
+            max_depth,
+            split_chance,
+            color,
+            background_color,
+            seed,
+        }
+    }
+}
+
+/// The tiles split into a grid of the smallest tiles possible, each cell holding how many
+/// times the tile covering it was split
+pub struct TileGrid {
+    columns: u32,
+    rows: u32,
+    max_depth: u32,
+    levels: Vec<u32>,
+}
+
+/// A tile in the grid, with the position of its top left cell
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TruchetTile {
+    pub level: u32,
+    pub x: u32,
+    pub y: u32,
+}
+
+impl TileGrid {
+    /// Splits the biggest tiles at random, then splits any tile next to a tile more than
+    /// one split smaller than it so every edge lines up
+    pub fn new(
+        big_columns: u32,
+        big_rows: u32,
+        max_depth: u32,
+        split_chance: f64,
+        rng: &mut fastrand::Rng,
+    ) -> Self {
+        let cell_num = 1 << max_depth;
+        let mut grid = Self {
+            columns: big_columns * cell_num,
+            rows: big_rows * cell_num,
+            max_depth,
+            levels: vec![0; (big_columns * big_rows * cell_num * cell_num) as usize],
+        };
+        for y in 0..big_rows {
+            for x in 0..big_columns {
+                grid.split_randomly(
+                    TruchetTile {
+                        level: 0,
+                        x: x * cell_num,
+                        y: y * cell_num,
+                    },
+                    split_chance,
+                    rng,
+                );
+            }
+        }
+        grid.balance();
+
+        grid
+    }
+
+    fn split_randomly(&mut self, tile: TruchetTile, split_chance: f64, rng: &mut fastrand::Rng) {
+        if tile.level < self.max_depth && rng.f64() < split_chance {
+            for child in self.split(tile) {
+                self.split_randomly(child, split_chance, rng);
+            }
+        }
+    }
+
+    /// How many cells wide a tile at a level is
+    fn get_span(&self, level: u32) -> u32 {
+        1 << (self.max_depth - level)
+    }
+
+    fn get_level(&self, x: u32, y: u32) -> u32 {
+        self.levels[(y * self.columns + x) as usize]
+    }
+
+    /// Splits a tile into 4, giving the new tiles
+    fn split(&mut self, tile: TruchetTile) -> [TruchetTile; 4] {
+        let span = self.get_span(tile.level);
+        for y in tile.y..tile.y + span {
+            for x in tile.x..tile.x + span {
+                self.levels[(y * self.columns + x) as usize] = tile.level + 1;
+            }
+        }
+        let (level, half) = (tile.level + 1, span / 2);
+        [(0, 0), (half, 0), (0, half), (half, half)].map(|(x, y)| TruchetTile {
+            level,
+            x: tile.x + x,
+            y: tile.y + y,
+        })
+    }
+
+    /// The levels of the cells just outside a tile's edges
+    fn get_neighbour_levels(&self, tile: TruchetTile) -> impl Iterator<Item = u32> + '_ {
+        let span = self.get_span(tile.level);
+        let (left, top) = (tile.x as i64 - 1, tile.y as i64 - 1);
+        let (right, bottom) = ((tile.x + span) as i64, (tile.y + span) as i64);
+        (0..span as i64)
+            .flat_map(move |offset| {
+                [
+                    (tile.x as i64 + offset, top),
+                    (tile.x as i64 + offset, bottom),
+                    (left, tile.y as i64 + offset),
+                    (right, tile.y as i64 + offset),
+                ]
+            })
+            .filter(|&(x, y)| x >= 0 && y >= 0 && x < self.columns as i64 && y < self.rows as i64)
+            .map(|(x, y)| self.get_level(x as u32, y as u32))
+    }
+
+    /// Splits tiles until no tile is next to one more than a level below it. Splitting
+    /// can make a tile too small for its other neighbours, so it goes until nothing
+    /// changes
+    fn balance(&mut self) {
+        loop {
+            let unbalanced: Vec<TruchetTile> = self
+                .get_tiles()
+                .into_iter()
+                .filter(|&tile| {
+                    self.get_neighbour_levels(tile)
+                        .any(|level| level > tile.level + 1)
+                })
+                .collect();
+            if unbalanced.is_empty() {
+                break;
+            }
+            for tile in unbalanced {
+                self.split(tile);
+            }
+        }
+    }
+
+    /// Gets the tiles from the biggest to the smallest, going across then down
+    pub fn get_tiles(&self) -> Vec<TruchetTile> {
+        let mut tiles: Vec<TruchetTile> = (0..self.rows)
+            .flat_map(|y| (0..self.columns).map(move |x| (x, y)))
+            .filter_map(|(x, y)| {
+                let level = self.get_level(x, y);
+                let span = self.get_span(level);
+                (x % span == 0 && y % span == 0).then_some(TruchetTile { level, x, y })
+            })
+            .collect();
+        tiles.sort_by_key(|tile| tile.level);
+
+        tiles
+    }
+}
+
+pub fn generate_truchet_image(options: TruchetOptions) -> RgbaImage {
+    let mut rng = match options.seed {
+        Some(seed) => fastrand::Rng::with_seed(seed),
+        None => fastrand::Rng::new(),
+    };
+    let (width, height) = (options.width, options.height);
+    let grid = TileGrid::new(
+        width.div_ceil(options.tile_size),
+        height.div_ceil(options.tile_size),
+        options.max_depth,
+        options.split_chance,
+        &mut rng,
+    );
+    let cell_size = options.tile_size as f64 / (1 << options.max_depth) as f64;
+    let colors = [
+        Rgba(options.color.to_rgba8()),
+        Rgba(options.background_color.to_rgba8()),
+    ];
+
+    let mut image = RgbaImage::from_pixel(width, height, colors[1]);
+    let tiles = grid.get_tiles();
+    // which way round each tile's bands go
+    let flips: Vec<bool> = tiles.iter().map(|_| rng.bool()).collect();
+    for level in 0..=options.max_depth {
+        // the colours swap every split
+        let (color, background_color) =
+            (colors[level as usize % 2], colors[1 - level as usize % 2]);
+        let level_tiles = || {
+            tiles
+                .iter()
+                .zip(flips.iter())
+                .filter(move |(tile, _)| tile.level == level)
+        };
+
+        for (tile, &flip) in level_tiles() {
+            let size = grid.get_span(tile.level) as f64 * cell_size;
+            let (left, top) = (tile.x as f64 * cell_size, tile.y as f64 * cell_size);
+            let (right, bottom) = (left + size, top + size);
+            // rounding both edges rather than the size so there are no gaps between tiles
+            let (pixel_left, pixel_top) = (left.round() as i32, top.round() as i32);
+            let pixel_size = (
+                right.round() as i32 - pixel_left,
+                bottom.round() as i32 - pixel_top,
+            );
+            if pixel_size.0 > 0 && pixel_size.1 > 0 {
+                draw_filled_rect_mut(
+                    &mut image,
+                    Rect::at(pixel_left, pixel_top)
+                        .of_size(pixel_size.0 as u32, pixel_size.1 as u32),
+                    background_color,
+                );
+            }
+
+            // the angle each band starts at goes towards the inside of the tile
+            let corners = match flip {
+                false => [((left, top), 0.0), ((right, bottom), PI)],
+                true => [((right, top), PI / 2.0), ((left, bottom), 3.0 * PI / 2.0)],
+            };
+            for (corner, start_angle) in corners {
+                draw_quarter_band(
+                    &mut image,
+                    corner,
+                    start_angle,
+                    size / 3.0,
+                    size * 2.0 / 3.0,
+                    color,
+                );
+            }
+        }
+
+        // the corner circles go over the edges into the tiles around them, so they're
+        // drawn after all the tiles of the same size
+        for (tile, _) in level_tiles() {
+            let size = grid.get_span(tile.level) as f64 * cell_size;
+            let (left, top) = (tile.x as f64 * cell_size, tile.y as f64 * cell_size);
+            for (x, y) in [
+                (left, top),
+                (left + size, top),
+                (left, top + size),
+                (left + size, top + size),
+            ] {
+                let radius = (size / 6.0).round() as i32;
+                if radius > 0 {
+                    draw_filled_circle_mut(
+                        &mut image,
+                        (x.round() as i32, y.round() as i32),
+                        radius,
+                        color,
+                    );
+                }
+            }
+        }
+    }
+
+    image
+}
+
+/// Draws a quarter of a ring around a corner, going a quarter turn from the start angle
+pub(crate) fn draw_quarter_band(
+    image: &mut RgbaImage,
+    (x, y): (f64, f64),
+    start_angle: f64,
+    inner_radius: f64,
+    outer_radius: f64,
+    color: Rgba<u8>,
+) {
+    let get_point = |radius: f64, step: usize| {
+        let angle = start_angle + PI / 2.0 * step as f64 / ARC_POINT_NUM as f64;
+        Point::new(
+            (x + radius * angle.cos()).round() as i32,
+            (y + radius * angle.sin()).round() as i32,
+        )
+    };
+    let mut points: Vec<Point<i32>> = (0..=ARC_POINT_NUM)
+        .map(|step| get_point(outer_radius, step))
+        .chain(
+            (0..=ARC_POINT_NUM)
+                .rev()
+                .map(|step| get_point(inner_radius, step)),
+        )
+        .collect();
+    // small tiles can round neighbouring points to the same pixel, and the polygon can't
+    // start and end on the same point
+    points.dedup();
+    while points.len() > 1 && points.first() == points.last() {
+        points.pop();
+    }
+    if points.len() >= 3 {
+        draw_polygon_mut(image, &points, color);
+    }
+}
+
+/// Parses the chance of splitting, from 0 for never to 1 for always
+fn parse_chance(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(chance) if (0.0..=1.0).contains(&chance) => Ok(chance),
+        Ok(_) => Err("must be from 0 to 1".to_string()),
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+#[derive(clap::Args, Debug)]
+pub struct TruchetArgs {
+    #[arg(long, default_value = "800", value_parser = parse_size)]
+    width: u32,
+
+    #[arg(long, default_value = "800", value_parser = parse_size)]
+    height: u32,
+
+    /// The size of the biggest tiles in pixels
+    #[arg(short, long, default_value = "160", value_parser = parse_size)]
+    tile_size: u32,
+
+    /// How many times a tile can be split into 4 smaller ones, 0 for tiles all the same
+    /// size
+    #[arg(short, long, default_value = "2")]
+    depth: u32,
+
+    /// The chance of each tile being split, from 0 to 1. Tiles next to much smaller ones
+    /// get split whatever the chance so the paths join up
+    #[arg(long, default_value = "0.4", value_parser = parse_chance)]
+    split_chance: f64,
+
+    /// The colour of the bands on the biggest tiles, which swaps with the background
+    /// colour on each smaller size
+    #[arg(short, long, default_value = "black", value_parser = parse_color)]
+    color: Color,
+
+    #[arg(short, long, alias = "background", default_value = "white", value_parser = parse_color)]
+    background_color: Color,
+
+    /// The seed used for the tiles, random if not given
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+pub struct TruchetGenerator;
+
+impl Generator for TruchetGenerator {
+    type Args = TruchetArgs;
+
+    fn name(&self) -> &'static str {
+        "truchet"
+    }
+
+    fn is_animated(&self, _args: &TruchetArgs) -> bool {
+        false
+    }
+
+    fn estimate_memory(&self, args: &TruchetArgs) -> Option<MemoryEstimate> {
+        Some(MemoryEstimate::still(args.width, args.height))
+    }
+
+    fn generate(&self, args: TruchetArgs) -> Result<Generated, GeneratorError> {
+        // the corner circles of the smallest tiles have to be at least a pixel across
+        if args.tile_size >> args.depth.min(31) < 6 {
+            return Err(GeneratorError::InvalidOptions(format!(
+                "the tile size {} is too small to split {} times",
+                args.tile_size, args.depth
+            )));
+        }
+
+        Ok(Generated::Still(generate_truchet_image(
+            TruchetOptions::new(
+                args.width,
+                args.height,
+                args.tile_size,
+                args.depth,
+                args.split_chance,
+                args.color,
+                args.background_color,
+                args.seed,
+            ),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tiles_balanced() {
+        let mut rng = fastrand::Rng::with_seed(5);
+        let grid = TileGrid::new(4, 3, 3, 0.5, &mut rng);
+        let tiles = grid.get_tiles();
+        assert!(tiles.iter().any(|tile| tile.level == 3));
+
+        // every cell is covered by exactly one tile
+        let covered: u32 = tiles
+            .iter()
+            .map(|tile| grid.get_span(tile.level).pow(2))
+            .sum();
+        assert_eq!(covered, grid.columns * grid.rows);
+
+        for tile in tiles {
+            assert!(
+                grid.get_neighbour_levels(tile)
+                    .all(|level| level.abs_diff(tile.level) <= 1)
+            );
+        }
+    }
+}
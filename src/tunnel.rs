@@ -0,0 +1,209 @@
+//! The classic demoscene tunnel effect
+//!
+//! Each pixel is converted to polar coordinates around the centre, the distance gives
+//! the depth into the tunnel and the angle goes around it, and these are used to look up
+//! a texture. Shifting the lookup over time makes the tunnel move and rotate.
+//! See <https://lodev.org/cgtutor/tunnel.html>
+
+use std::f64;
+
+use csscolorparser::Color;
+use image::{Rgba, RgbaImage};
+
+use crate::{
+    TunnelTexture,
+    cancel::render_frames,
+    generator::{Generated, Generator, GeneratorError},
+    memory::MemoryEstimate,
+    parse_color, parse_size,
+};
+
+/// The amount of checks across the texture
+const TEXTURE_REPEATS: f64 = 16.0;
+/// How quickly the texture recedes into the distance
+const DEPTH_SCALE: f64 = 0.25;
+/// Size of the grid for the noise texture
+const NOISE_GRID_SIZE: usize = 16;
+
+pub struct TunnelOptions {
+    size: u32,
+    frame_num: u32,
+    texture: TunnelTexture,
+    color1: Color,
+    color2: Color,
+    seed: Option<u64>,
+}
+
+impl TunnelOptions {
+    pub fn new(
+        size: u32,
+        frame_num: u32,
+        texture: TunnelTexture,
+        color1: Color,
+        color2: Color,
+        seed: Option<u64>,
+    ) -> Self {
+        Self {
+            size,
+            frame_num,
+            texture,
+            color1,
+            color2,
+            seed,
+        }
+    }
+}
+
+pub fn generate_tunnel_images(options: TunnelOptions) -> Vec<RgbaImage> {
+    let TunnelOptions {
+        size,
+        frame_num,
+        texture,
+        color1,
+        color2,
+        seed,
+    } = options;
+
+    let mut rng = match seed {
+        Some(seed) => fastrand::Rng::with_seed(seed),
+        None => fastrand::Rng::new(),
+    };
+    let noise_grid: Vec<f64> = (0..NOISE_GRID_SIZE * NOISE_GRID_SIZE)
+        .map(|_| rng.f64())
+        .collect();
+    let sample_texture = |u: f64, v: f64| match texture {
+        TunnelTexture::Checkerboard => {
+            let check_u = (u * TEXTURE_REPEATS).floor() as i64;
+            let check_v = (v * TEXTURE_REPEATS).floor() as i64;
+            if (check_u + check_v).rem_euclid(2) == 0 {
+                1.0
+            } else {
+                0.0
+            }
+        }
+        TunnelTexture::Noise => sample_tiled_noise(&noise_grid, u, v),
+    };
+
+    let centre = size as f64 / 2.0;
+    // the furthest the corners are from the centre, used for shading
+    let max_distance = centre * f64::consts::SQRT_2;
+    let render_frame = |frame: u32| {
+        let mut image = RgbaImage::new(size, size);
+        let loop_amount = frame as f64 / frame_num as f64;
+
+        for y in 0..size {
+            for x in 0..size {
+                let offset_x = x as f64 - centre;
+                let offset_y = y as f64 - centre;
+                let distance = f64::hypot(offset_x, offset_y).max(1.0);
+
+                // texture coords go from 0 to 1, moving through a whole texture per loop
+                // means the animation loops seamlessly
+                let depth = DEPTH_SCALE * centre / distance + loop_amount;
+                let angle = f64::atan2(offset_y, offset_x) / (2.0 * f64::consts::PI) + loop_amount;
+
+                let value = sample_texture(depth.rem_euclid(1.0), angle.rem_euclid(1.0));
+                // darken towards the centre to give the feeling of depth
+                let shade = (distance / max_distance).sqrt() as f32;
+                let color = Color {
+                    r: (color1.r * value as f32 + color2.r * (1.0 - value as f32)) * shade,
+                    g: (color1.g * value as f32 + color2.g * (1.0 - value as f32)) * shade,
+                    b: (color1.b * value as f32 + color2.b * (1.0 - value as f32)) * shade,
+                    a: 1.0,
+                };
+
+                image[(x, y)] = Rgba(color.to_rgba8());
+            }
+        }
+
+        image
+    };
+
+    render_frames(0..frame_num, render_frame)
+}
+
+/// Value noise which wraps around at the edges so the texture tiles seamlessly
+fn sample_tiled_noise(grid: &[f64], u: f64, v: f64) -> f64 {
+    let grid_u = u * NOISE_GRID_SIZE as f64;
+    let grid_v = v * NOISE_GRID_SIZE as f64;
+    let left = grid_u.floor() as usize % NOISE_GRID_SIZE;
+    let top = grid_v.floor() as usize % NOISE_GRID_SIZE;
+    let right = (left + 1) % NOISE_GRID_SIZE;
+    let bottom = (top + 1) % NOISE_GRID_SIZE;
+
+    let frac_u = smoothstep(grid_u.fract());
+    let frac_v = smoothstep(grid_v.fract());
+
+    let top_value = interpolate(
+        grid[top * NOISE_GRID_SIZE + left],
+        grid[top * NOISE_GRID_SIZE + right],
+        frac_u,
+    );
+    let bottom_value = interpolate(
+        grid[bottom * NOISE_GRID_SIZE + left],
+        grid[bottom * NOISE_GRID_SIZE + right],
+        frac_u,
+    );
+    interpolate(top_value, bottom_value, frac_v)
+}
+
+fn interpolate(a: f64, b: f64, x: f64) -> f64 {
+    a * (1.0 - x) + b * x
+}
+
+fn smoothstep(x: f64) -> f64 {
+    x * x * (3.0 - 2.0 * x)
+}
+
+#[derive(clap::Args, Debug)]
+pub struct TunnelArgs {
+    #[arg(short, long, default_value = "400", value_parser = parse_size)]
+    size: u32,
+
+    /// The number of frames in the loop
+    #[arg(short, long, default_value = "60", value_parser = parse_size)]
+    frames: u32,
+
+    #[arg(short, long, default_value = "checkerboard")]
+    texture: TunnelTexture,
+
+    #[arg(long, default_value = "white", value_parser = parse_color)]
+    color1: Color,
+
+    #[arg(long, default_value = "black", value_parser = parse_color)]
+    color2: Color,
+
+    /// The seed used for the noise texture, random if not given
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+pub struct TunnelGenerator;
+
+impl Generator for TunnelGenerator {
+    type Args = TunnelArgs;
+
+    fn name(&self) -> &'static str {
+        "tunnel"
+    }
+
+    fn is_animated(&self, _args: &TunnelArgs) -> bool {
+        true
+    }
+
+    fn estimate_memory(&self, args: &TunnelArgs) -> Option<MemoryEstimate> {
+        Some(MemoryEstimate::frames(args.size, args.size, args.frames))
+    }
+
+    fn generate(&self, args: TunnelArgs) -> Result<Generated, GeneratorError> {
+        let options = TunnelOptions::new(
+            args.size,
+            args.frames,
+            args.texture,
+            args.color1,
+            args.color2,
+            args.seed,
+        );
+        Ok(Generated::Frames(generate_tunnel_images(options)))
+    }
+}
@@ -1,13 +1,32 @@
 //! Module for generating a ulam spiral
 //!
-//! Can generate either the typical prime spiral or a spiral which shows the number of divisors
+//! Can generate either the typical prime spiral or a spiral which shows the number of divisors,
+//! or colour every number by a function of it like Euler's totient. It can also be animated
+//! zooming in on a number, or panning out along one of the diagonals the primes line up on
 //!
 
 use csscolorparser::Color;
-use image::{DynamicImage, ImageBuffer, Rgba};
+use image::{DynamicImage, ImageBuffer, Rgba, RgbaImage};
 use imageproc::drawing::draw_filled_circle_mut;
 
-use crate::UlamSpiralMode;
+use crate::{
+    ArithmeticFunction, PaletteType, UlamSpiralMode,
+    cancel::{is_cancelled, render_frames},
+    multiplicative::get_function_values,
+    palette::get_palette_color,
+    tiles::Tile,
+};
+
+/// The distance between the centres of the circles in the divisor spiral
+const DEFAULT_CIRCLE_SIZE: u32 = 10;
+
+/// How many cells go across the image at the end of a zoom, and all the way through a
+/// pan, which is close enough to pick out the numbers around the one zoomed in on
+const ZOOM_CELL_NUM: f64 = 25.0;
+
+/// The most numbers a spiral coloured by a value function can have, as the value and
+/// colour of every number are held in memory at once, 12 bytes each
+const MAX_VALUE_FUNCTION_SIZE: u32 = 1 << 28;
 
 #[derive(Clone, Debug)]
 pub struct UlamSpiralOptions {
@@ -15,16 +34,45 @@ pub struct UlamSpiralOptions {
     color: Color,
     mode: UlamSpiralMode,
     background_color: Color,
+    /// Colours every number by this function of it instead of drawing the mode
+    value_function: Option<ArithmeticFunction>,
+    palette: PaletteType,
 }
 
 impl UlamSpiralOptions {
-    pub fn new(size: u32, color: Color, mode: UlamSpiralMode, background_color: Color) -> Self {
+    pub fn new(
+        size: u32,
+        color: Color,
+        mode: UlamSpiralMode,
+        background_color: Color,
+        value_function: Option<ArithmeticFunction>,
+        palette: PaletteType,
+    ) -> Self {
         Self {
             size,
             color,
             mode,
             background_color,
+            value_function,
+            palette,
+        }
+    }
+
+    /// Checks the spiral can be made, giving what to change if it can't
+    pub fn check(&self) -> Result<(), String> {
+        if self.size == 0 {
+            return Err(
+                "the spiral has to have at least 1 number, use a --size of 1 or more".to_string(),
+            );
         }
+        if self.value_function.is_some() && self.size > MAX_VALUE_FUNCTION_SIZE {
+            return Err(format!(
+                "a spiral coloured by --value-fn can have at most {} numbers, as every number's \
+                 value is kept in memory, so use a smaller --size than {}",
+                MAX_VALUE_FUNCTION_SIZE, self.size
+            ));
+        }
+        Ok(())
     }
 
     fn get_image_size(&self) -> u32 {
@@ -39,26 +87,117 @@ impl UlamSpiralOptions {
         }
         image_size
     }
+
+    /// Gets the width and height of the image, which is always square
+    pub fn get_image_dimension(&self) -> u32 {
+        match (self.value_function, self.mode) {
+            (Some(_), _) | (None, UlamSpiralMode::PrimeOnly) => self.get_image_size(),
+            (None, UlamSpiralMode::Divisor) => self.get_image_size() * DEFAULT_CIRCLE_SIZE,
+        }
+    }
+}
+
+pub struct UlamZoomOptions {
+    /// The number the zoom ends up centred on
+    number: u32,
+    /// Slides out from the centre along the line through the number instead of zooming
+    pan: bool,
+    frame_num: u32,
+}
+
+impl UlamZoomOptions {
+    pub fn new(number: u32, pan: bool, frame_num: u32) -> Self {
+        Self {
+            number,
+            pan,
+            frame_num,
+        }
+    }
+}
+
+/// Gets the colour of every number in the spiral from the function, with the background
+/// for 0. The values are scaled by the biggest one so the whole palette gets used
+fn get_value_colors(options: &UlamSpiralOptions, function: ArithmeticFunction) -> Vec<Rgba<u8>> {
+    let values = get_function_values(function, options.size as usize);
+    // the totient and divisor sum grow with the number, so they're compared to it
+    let positions: Vec<f64> = values
+        .iter()
+        .enumerate()
+        .map(|(number, &value)| match function {
+            _ if number == 0 => 0.0,
+            ArithmeticFunction::Totient => value as f64 / number as f64,
+            ArithmeticFunction::Sigma => value as f64 / number as f64 - 1.0,
+            ArithmeticFunction::Omega => value as f64,
+            ArithmeticFunction::Mobius => (value as f64 + 1.0) / 2.0,
+        })
+        .collect();
+    let highest = positions.iter().copied().fold(0.0, f64::max);
+    let highest = if highest > 0.0 { highest } else { 1.0 };
+
+    let background_color = Rgba(options.background_color.to_rgba8());
+    positions
+        .into_iter()
+        .enumerate()
+        .map(|(number, position)| match number {
+            0 => background_color,
+            _ => Rgba(get_palette_color(options.palette, position / highest * 0.8)),
+        })
+        .collect()
+}
+
+/// Sieves out the primes below the size, which is a lot quicker than checking each
+/// number on its own when going through all of them
+pub(crate) fn get_prime_sieve(size: u64) -> primal::Sieve {
+    primal::Sieve::new(size as usize)
 }
 
 pub fn generate_ulam_spiral_image(options: UlamSpiralOptions) -> DynamicImage {
-    match options.mode {
-        UlamSpiralMode::PrimeOnly => generate_prime_ulam_spiral(options),
-        UlamSpiralMode::Divisor => generate_divisor_ulam_spiral(options),
+    match (options.value_function, options.mode) {
+        (Some(function), _) => generate_value_ulam_spiral(options, function),
+        (None, UlamSpiralMode::PrimeOnly) => generate_prime_ulam_spiral(options),
+        (None, UlamSpiralMode::Divisor) => generate_divisor_ulam_spiral(options),
     }
 }
 
-fn generate_prime_ulam_spiral(options: UlamSpiralOptions) -> DynamicImage {
+fn generate_value_ulam_spiral(
+    options: UlamSpiralOptions,
+    function: ArithmeticFunction,
+) -> DynamicImage {
     let image_size = options.get_image_size();
-    let mut image = ImageBuffer::<Rgba<u8>, _>::new(image_size, image_size);
+    let mut image = ImageBuffer::from_pixel(
+        image_size,
+        image_size,
+        Rgba(options.background_color.to_rgba8()),
+    );
+    let colors = get_value_colors(&options, function);
 
     let spiral_pattern = SpiralPatternIterator::new(options.size, image_size);
+    for (value, (x, y)) in spiral_pattern.enumerate() {
+        if is_cancelled() {
+            break;
+        }
+        image[(x, y)] = colors[value];
+    }
+
+    DynamicImage::ImageRgba8(image)
+}
 
+fn generate_prime_ulam_spiral(options: UlamSpiralOptions) -> DynamicImage {
+    let image_size = options.get_image_size();
     let converted_color = options.color.to_rgba8();
     let converted_background_color = options.background_color.to_rgba8();
+    // the spiral doesn't always fill the square, so the background goes everywhere first
+    let mut image =
+        ImageBuffer::from_pixel(image_size, image_size, Rgba(converted_background_color));
+
+    let spiral_pattern = SpiralPatternIterator::new(options.size, image_size);
+    let sieve = get_prime_sieve(options.size as u64);
 
     for (value, (x, y)) in spiral_pattern.enumerate() {
-        let colour = if primal::is_prime(value as u64) {
+        if is_cancelled() {
+            break;
+        }
+        let colour = if sieve.is_prime(value) {
             Rgba(converted_color)
         } else {
             Rgba(converted_background_color)
@@ -70,8 +209,6 @@ fn generate_prime_ulam_spiral(options: UlamSpiralOptions) -> DynamicImage {
 }
 
 fn generate_divisor_ulam_spiral(options: UlamSpiralOptions) -> DynamicImage {
-    const DEFAULT_CIRCLE_SIZE: u32 = 10;
-
     let image_size = options.get_image_size();
     let image_dimension = image_size * DEFAULT_CIRCLE_SIZE;
     let mut image = ImageBuffer::<Rgba<u8>, _>::new(image_dimension, image_dimension);
@@ -87,6 +224,9 @@ fn generate_divisor_ulam_spiral(options: UlamSpiralOptions) -> DynamicImage {
     let spiral_pattern = SpiralPatternIterator::new(options.size, image_size);
 
     for (value, (x, y)) in spiral_pattern.enumerate() {
+        if is_cancelled() {
+            break;
+        }
         let square_root = (value as u32).isqrt();
         if square_root == 0 {
             continue;
@@ -108,6 +248,264 @@ fn generate_divisor_ulam_spiral(options: UlamSpiralOptions) -> DynamicImage {
     DynamicImage::ImageRgba8(image)
 }
 
+/// Renders one tile of the image generate_ulam_spiral_image would make, working out
+/// the number for each cell straight from its position rather than walking the spiral
+pub fn generate_ulam_spiral_tile(options: &UlamSpiralOptions, tile: Tile) -> RgbaImage {
+    match (options.value_function, options.mode) {
+        (Some(function), _) => generate_value_ulam_spiral_tile(options, function, tile),
+        (None, UlamSpiralMode::PrimeOnly) => generate_prime_ulam_spiral_tile(options, tile),
+        (None, UlamSpiralMode::Divisor) => generate_divisor_ulam_spiral_tile(options, tile),
+    }
+}
+
+fn generate_value_ulam_spiral_tile(
+    options: &UlamSpiralOptions,
+    function: ArithmeticFunction,
+    tile: Tile,
+) -> RgbaImage {
+    let centre = (options.get_image_size() / 2) as i64;
+    // every tile needs the biggest value to scale by, so it sieves the whole spiral
+    let colors = get_value_colors(options, function);
+    let background_color = Rgba(options.background_color.to_rgba8());
+
+    ImageBuffer::from_fn(tile.width, tile.height, |x, y| {
+        let value = get_spiral_index((tile.x + x) as i64 - centre, (tile.y + y) as i64 - centre);
+        colors
+            .get(value as usize)
+            .copied()
+            .unwrap_or(background_color)
+    })
+}
+
+fn generate_prime_ulam_spiral_tile(options: &UlamSpiralOptions, tile: Tile) -> RgbaImage {
+    let centre = (options.get_image_size() / 2) as i64;
+    let converted_color = options.color.to_rgba8();
+    let converted_background_color = options.background_color.to_rgba8();
+
+    ImageBuffer::from_fn(tile.width, tile.height, |x, y| {
+        let value = get_spiral_index((tile.x + x) as i64 - centre, (tile.y + y) as i64 - centre);
+        if value < options.size as u64 && primal::is_prime(value) {
+            Rgba(converted_color)
+        } else {
+            Rgba(converted_background_color)
+        }
+    })
+}
+
+fn generate_divisor_ulam_spiral_tile(options: &UlamSpiralOptions, tile: Tile) -> RgbaImage {
+    let image_size = options.get_image_size();
+    let centre = (image_size / 2) as i64;
+    let converted_color = options.color.to_rgba8();
+    let mut image = ImageBuffer::from_pixel(
+        tile.width,
+        tile.height,
+        Rgba(options.background_color.to_rgba8()),
+    );
+
+    // circles from cells outside the tile can reach into it, at most as far as the
+    // biggest circle, which is a third of the factors up to the square root
+    let margin = options.size.isqrt() / 3;
+    let first_cell = |start: u32| start.saturating_sub(margin) / DEFAULT_CIRCLE_SIZE;
+    let last_cell = |end: u32| ((end + margin) / DEFAULT_CIRCLE_SIZE + 1).min(image_size - 1);
+
+    for cell_y in first_cell(tile.y)..=last_cell(tile.y + tile.height) {
+        for cell_x in first_cell(tile.x)..=last_cell(tile.x + tile.width) {
+            let value = get_spiral_index(cell_x as i64 - centre, cell_y as i64 - centre);
+            if value >= options.size as u64 {
+                continue;
+            }
+            let value = value as u32;
+            let square_root = value.isqrt();
+            if square_root == 0 {
+                continue;
+            }
+            let circle_size = get_factor_num(value, square_root) / 3;
+            let x = (cell_x * DEFAULT_CIRCLE_SIZE) as i32 - tile.x as i32;
+            let y = (cell_y * DEFAULT_CIRCLE_SIZE) as i32 - tile.y as i32;
+            draw_filled_circle_mut(
+                &mut image,
+                (x, y),
+                circle_size as i32,
+                Rgba(converted_color),
+            );
+        }
+    }
+
+    image
+}
+
+/// Gets the number at a position relative to the centre of the spiral
+///
+/// Each ring of the spiral starts just above its bottom right corner with the next odd
+/// square, then runs up the right side, along the top, down the left and along the bottom
+pub(crate) fn get_spiral_index(x: i64, y: i64) -> u64 {
+    let ring = x.abs().max(y.abs());
+    if ring == 0 {
+        return 0;
+    }
+    let start = (2 * ring - 1) * (2 * ring - 1);
+
+    let offset = if x == ring && y < ring {
+        ring - 1 - y
+    } else if y == -ring {
+        2 * ring + (ring - 1 - x)
+    } else if x == -ring {
+        4 * ring + (y + ring - 1)
+    } else {
+        6 * ring + (x + ring - 1)
+    };
+
+    (start + offset) as u64
+}
+
+/// Gets the position of a number relative to the centre of the spiral, which is the
+/// other way round to get_spiral_index
+pub(crate) fn get_spiral_position(value: u64) -> (i64, i64) {
+    // ring n holds the numbers from (2n - 1)^2 up to just below (2n + 1)^2
+    let ring = (value.isqrt() as i64 + 1) / 2;
+    if ring == 0 {
+        return (0, 0);
+    }
+    let offset = value as i64 - (2 * ring - 1) * (2 * ring - 1);
+
+    match offset / (2 * ring) {
+        0 => (ring, ring - 1 - offset),
+        1 => (ring - 1 - (offset - 2 * ring), -ring),
+        2 => (-ring, offset - 4 * ring - ring + 1),
+        _ => (offset - 6 * ring - ring + 1, ring),
+    }
+}
+
+/// What the numbers in the spiral are drawn with, worked out once for all the frames
+enum SpiralCells {
+    Primes(primal::Sieve),
+    Values(Vec<Rgba<u8>>),
+    Divisors,
+}
+
+/// Renders the spiral with its centre at a position in cells, which don't have to be
+/// whole, and each cell a number of pixels across. The cell for every pixel is worked
+/// out from scratch so the frames of a zoom can be at any scale
+fn generate_ulam_spiral_view(
+    options: &UlamSpiralOptions,
+    cells: &SpiralCells,
+    (centre_x, centre_y): (f64, f64),
+    cell_size: f64,
+) -> RgbaImage {
+    let dimension = options.get_image_dimension();
+    let half = dimension as f64 / 2.0;
+    let color = Rgba(options.color.to_rgba8());
+    let background_color = Rgba(options.background_color.to_rgba8());
+    // the cells are centred on whole positions
+    let to_cell = |pixel: f64, centre: f64| (centre + (pixel - half) / cell_size).round() as i64;
+    let get_value = |x: i64, y: i64| {
+        let value = get_spiral_index(x, y);
+        (value < options.size as u64).then_some(value)
+    };
+    let get_pixel_value = |x: u32, y: u32| {
+        get_value(
+            to_cell(x as f64 + 0.5, centre_x),
+            to_cell(y as f64 + 0.5, centre_y),
+        )
+    };
+
+    match cells {
+        SpiralCells::Primes(sieve) => {
+            ImageBuffer::from_fn(dimension, dimension, |x, y| match get_pixel_value(x, y) {
+                Some(value) if sieve.is_prime(value as usize) => color,
+                _ => background_color,
+            })
+        }
+        SpiralCells::Values(colors) => ImageBuffer::from_fn(dimension, dimension, |x, y| {
+            get_pixel_value(x, y).map_or(background_color, |value| colors[value as usize])
+        }),
+        SpiralCells::Divisors => {
+            let mut image = ImageBuffer::from_pixel(dimension, dimension, background_color);
+            // circles from cells just off the edges can reach in, by as many cells as the
+            // biggest circle covers
+            let margin = (options.size.isqrt() / 3 / DEFAULT_CIRCLE_SIZE) as i64 + 1;
+            let cell_range = |centre: f64| {
+                to_cell(0.0, centre) - margin..=to_cell(dimension as f64, centre) + margin
+            };
+
+            for cell_y in cell_range(centre_y) {
+                for cell_x in cell_range(centre_x) {
+                    let Some(value) = get_value(cell_x, cell_y) else {
+                        continue;
+                    };
+                    let value = value as u32;
+                    let square_root = value.isqrt();
+                    if square_root == 0 {
+                        continue;
+                    }
+                    // the same size as the still image when a cell is the default size
+                    let radius = (get_factor_num(value, square_root) / 3) as f64 * cell_size
+                        / DEFAULT_CIRCLE_SIZE as f64;
+                    let x = half + (cell_x as f64 - centre_x) * cell_size;
+                    let y = half + (cell_y as f64 - centre_y) * cell_size;
+                    draw_filled_circle_mut(
+                        &mut image,
+                        (x.round() as i32, y.round() as i32),
+                        radius.round() as i32,
+                        color,
+                    );
+                }
+            }
+
+            image
+        }
+    }
+}
+
+/// Renders the frames of a zoom from the whole spiral down to the numbers around the
+/// chosen one, or of a pan out along the line from the centre through it
+pub fn generate_ulam_spiral_zoom_images(
+    options: &UlamSpiralOptions,
+    zoom: &UlamZoomOptions,
+) -> Vec<RgbaImage> {
+    let cells = match (options.value_function, options.mode) {
+        (Some(function), _) => SpiralCells::Values(get_value_colors(options, function)),
+        (None, UlamSpiralMode::PrimeOnly) => {
+            SpiralCells::Primes(get_prime_sieve(options.size as u64))
+        }
+        (None, UlamSpiralMode::Divisor) => SpiralCells::Divisors,
+    };
+    let dimension = options.get_image_dimension() as f64;
+    let whole_cell_num = options.get_image_size() as f64;
+    let close_cell_num = ZOOM_CELL_NUM.min(whole_cell_num);
+    let (target_x, target_y) = get_spiral_position(zoom.number as u64);
+    let (target_x, target_y) = (target_x as f64, target_y as f64);
+
+    render_frames(0..zoom.frame_num, |frame| {
+        let amount = frame as f64 / (zoom.frame_num - 1).max(1) as f64;
+        let (moved, cell_num) = match zoom.pan {
+            true => {
+                // carries on past the number until the view reaches the edge of the spiral
+                let ring = target_x.abs().max(target_y.abs()).max(1.0);
+                let edge = (whole_cell_num - close_cell_num) / 2.0 / ring;
+                (edge * amount, close_cell_num)
+            }
+            false => {
+                // shrinking by the same factor every frame makes the zoom look steady, and
+                // moving the centre as far as the zoom has gone keeps the number from
+                // sliding out of view on the way in
+                let cell_num = whole_cell_num * (close_cell_num / whole_cell_num).powf(amount);
+                let moved = match whole_cell_num > close_cell_num {
+                    true => (whole_cell_num - cell_num) / (whole_cell_num - close_cell_num),
+                    false => amount,
+                };
+                (moved, cell_num)
+            }
+        };
+        generate_ulam_spiral_view(
+            options,
+            &cells,
+            (target_x * moved, target_y * moved),
+            dimension / cell_num,
+        )
+    })
+}
+
 /// gets half the factors, searches up to the num which should be the square root
 fn get_factor_num(num: u32, search_num: u32) -> u32 {
     1 + (2..=search_num).filter(|&x| num.is_multiple_of(x)).count() as u32
@@ -272,4 +670,61 @@ mod tests {
         assert_eq!(Some((centre - 2, centre + 2)), spiral_pattern.next());
         assert_eq!(None, spiral_pattern.next());
     }
+
+    #[test]
+    fn test_zoom() {
+        for value in 0..200 {
+            let (x, y) = get_spiral_position(value);
+            assert_eq!(get_spiral_index(x, y), value);
+        }
+
+        // the first frame of the zoom is the whole spiral
+        let options = UlamSpiralOptions::new(
+            2500,
+            Color::new(1.0, 0.0, 0.0, 1.0),
+            UlamSpiralMode::PrimeOnly,
+            Color::new(0.0, 0.0, 0.0, 1.0),
+            None,
+            PaletteType::Rainbow,
+        );
+        let frames =
+            generate_ulam_spiral_zoom_images(&options, &UlamZoomOptions::new(41, false, 3));
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0], generate_ulam_spiral_image(options).to_rgba8());
+        // and the last is centred on the number, which is prime
+        let centre = frames[2].width() / 2;
+        assert_eq!(frames[2][(centre, centre)], Rgba([255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn test_tiles_match_image() {
+        for (mode, value_function) in [
+            (UlamSpiralMode::PrimeOnly, None),
+            (UlamSpiralMode::Divisor, None),
+            (UlamSpiralMode::PrimeOnly, Some(ArithmeticFunction::Totient)),
+        ] {
+            let options = UlamSpiralOptions::new(
+                600,
+                Color::new(1.0, 0.0, 0.0, 1.0),
+                mode,
+                Color::new(0.0, 0.0, 0.0, 1.0),
+                value_function,
+                PaletteType::Rainbow,
+            );
+            let image = generate_ulam_spiral_image(options.clone()).to_rgba8();
+            let dimension = options.get_image_dimension();
+            assert_eq!(image.dimensions(), (dimension, dimension));
+
+            for tile in crate::tiles::TileGrid::new(3, 2)
+                .get_tile_rows(dimension, dimension)
+                .into_iter()
+                .flatten()
+            {
+                let tile_image = generate_ulam_spiral_tile(&options, tile);
+                let expected =
+                    image::imageops::crop_imm(&image, tile.x, tile.y, tile.width, tile.height);
+                assert_eq!(tile_image, expected.to_image());
+            }
+        }
+    }
 }
@@ -0,0 +1,529 @@
+//! Module for drawing vector fields given as formulas
+//!
+//! A vector field gives an arrow at every point, here worked out from formulas for its
+//! x and y parts like `-y` and `x` for a field going round the middle. It can be drawn
+//! as arrows on a grid, as streamlines following the arrows, or with line integral
+//! convolution, which smears random noise along the field so the whole image flows.
+//! See <https://en.wikipedia.org/wiki/Vector_field> and
+//! <https://en.wikipedia.org/wiki/Line_integral_convolution> for more info
+
+use csscolorparser::Color;
+use image::{Rgba, RgbaImage};
+use imageproc::{
+    drawing::{draw_antialiased_line_segment_mut, draw_polygon_mut},
+    pixelops::interpolate,
+    point::Point,
+};
+use num_complex::Complex64;
+
+use crate::{
+    PaletteType, VectorFieldMode,
+    formula::Formula,
+    generator::{Generated, Generator, GeneratorError},
+    memory::MemoryEstimate,
+    palette::{get_palette_color, interpolate_color},
+    parse_color, parse_size,
+};
+
+/// How many pixels apart the field is sampled to find how strong it usually is
+const SAMPLE_SPACING: u32 = 4;
+
+pub struct VectorFieldOptions {
+    x_formula: Formula,
+    y_formula: Formula,
+    mode: VectorFieldMode,
+    /// The point of the field in the middle of the image
+    centre: (f64, f64),
+    /// How far the field goes from the middle to the left and right edges
+    range: f64,
+    width: u32,
+    height: u32,
+    /// How far apart the arrows and the starts of the streamlines are in pixels
+    spacing: u32,
+    /// How many pixels each streamline goes each way, or how far the noise is smeared
+    length: u32,
+    color: Color,
+    /// Colours by how strong the field is instead of using color when given
+    palette: Option<PaletteType>,
+    background_color: Color,
+    seed: Option<u64>,
+}
+
+impl VectorFieldOptions {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        x_formula: Formula,
+        y_formula: Formula,
+        mode: VectorFieldMode,
+        centre: (f64, f64),
+        range: f64,
+        width: u32,
+        height: u32,
+        spacing: u32,
+        length: u32,
+        color: Color,
+        palette: Option<PaletteType>,
+        background_color: Color,
+        seed: Option<u64>,
+    ) -> Self {
+        Self {
+            x_formula,
+            y_formula,
+            mode,
+            centre,
+            range,
+            width,
+            height,
+            spacing,
+            length,
+            color,
+            palette,
+            background_color,
+            seed,
+        }
+    }
+
+    /// Gets the point of the field at a pixel, with y going up
+    fn to_field(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let scale = 2.0 * self.range / self.width as f64;
+        (
+            self.centre.0 + (x - self.width as f64 / 2.0) * scale,
+            self.centre.1 + (self.height as f64 / 2.0 - y) * scale,
+        )
+    }
+
+    /// Gets the field at a pixel, turned the right way up for the image, or None where
+    /// it can't be worked out
+    pub fn get_vector(&self, pixel: (f64, f64)) -> Option<(f64, f64)> {
+        let (x, y) = self.to_field(pixel);
+        let values = [Complex64::new(x, 0.0), Complex64::new(y, 0.0)];
+        let vector = (
+            self.x_formula.evaluate_with(&values).re,
+            -self.y_formula.evaluate_with(&values).re,
+        );
+
+        (vector.0.is_finite() && vector.1.is_finite()).then_some(vector)
+    }
+
+    /// Gets the direction of the field at a pixel as a vector a pixel long
+    fn get_direction(&self, pixel: (f64, f64)) -> Option<(f64, f64)> {
+        let (x, y) = self.get_vector(pixel)?;
+        let length = f64::hypot(x, y);
+        (length > 0.0).then_some((x / length, y / length))
+    }
+
+    fn contains(&self, (x, y): (f64, f64)) -> bool {
+        x >= 0.0 && y >= 0.0 && x < self.width as f64 && y < self.height as f64
+    }
+}
+
+/// Picks the colours for how strong the field is, compared to how strong it usually is
+/// rather than the strongest it gets, as fields like 1/x shoot off to infinity
+struct Coloring {
+    color: [u8; 4],
+    palette: Option<PaletteType>,
+    typical_strength: f64,
+}
+
+impl Coloring {
+    fn new(options: &VectorFieldOptions) -> Self {
+        let mut strengths: Vec<f64> = (0..options.height)
+            .step_by(SAMPLE_SPACING as usize)
+            .flat_map(|y| {
+                (0..options.width)
+                    .step_by(SAMPLE_SPACING as usize)
+                    .map(move |x| (x, y))
+            })
+            .filter_map(|(x, y)| options.get_vector((x as f64, y as f64)))
+            .map(|(x, y)| f64::hypot(x, y))
+            .collect();
+        strengths.sort_by(f64::total_cmp);
+        let typical_strength = strengths
+            .get(strengths.len() * 9 / 10)
+            .copied()
+            .unwrap_or(1.0);
+
+        Self {
+            color: options.color.to_rgba8(),
+            palette: options.palette,
+            typical_strength,
+        }
+    }
+
+    fn get_amount(&self, vector: (f64, f64)) -> f64 {
+        match self.typical_strength > 0.0 {
+            true => (f64::hypot(vector.0, vector.1) / self.typical_strength).min(1.0),
+            false => 0.0,
+        }
+    }
+
+    fn get_color(&self, vector: (f64, f64)) -> [u8; 4] {
+        match self.palette {
+            // not quite all the way round, as palettes like rainbow wrap back to the
+            // same colour at the end
+            Some(palette) => get_palette_color(palette, self.get_amount(vector) * 0.8),
+            None => self.color,
+        }
+    }
+}
+
+pub fn generate_vector_field_image(options: VectorFieldOptions) -> RgbaImage {
+    let coloring = Coloring::new(&options);
+    let mut image = RgbaImage::from_pixel(
+        options.width,
+        options.height,
+        Rgba(options.background_color.to_rgba8()),
+    );
+
+    match options.mode {
+        VectorFieldMode::Arrows => draw_arrows(&mut image, &options, &coloring),
+        VectorFieldMode::Streamlines => draw_streamlines(&mut image, &options, &coloring),
+        VectorFieldMode::Lic => draw_lic(&mut image, &options, &coloring),
+    }
+
+    image
+}
+
+/// Gets the middles of the squares of a grid across the image
+fn get_grid_points(options: &VectorFieldOptions) -> impl Iterator<Item = (f64, f64)> + '_ {
+    let spacing = options.spacing as f64;
+    let columns = options.width / options.spacing;
+    let rows = options.height / options.spacing;
+    // centred, so any space left over is split between the edges
+    let offset = (
+        (options.width as f64 - columns as f64 * spacing + spacing) / 2.0,
+        (options.height as f64 - rows as f64 * spacing + spacing) / 2.0,
+    );
+    (0..rows).flat_map(move |row| {
+        (0..columns).map(move |column| {
+            (
+                offset.0 + column as f64 * spacing,
+                offset.1 + row as f64 * spacing,
+            )
+        })
+    })
+}
+
+fn draw_segment(image: &mut RgbaImage, start: (f64, f64), end: (f64, f64), color: [u8; 4]) {
+    draw_antialiased_line_segment_mut(
+        image,
+        (start.0.round() as i32, start.1.round() as i32),
+        (end.0.round() as i32, end.1.round() as i32),
+        Rgba(color),
+        interpolate,
+    );
+}
+
+fn draw_arrows(image: &mut RgbaImage, options: &VectorFieldOptions, coloring: &Coloring) {
+    let spacing = options.spacing as f64;
+    for point in get_grid_points(options) {
+        let Some(vector) = options.get_vector(point) else {
+            continue;
+        };
+        let Some(direction) = options.get_direction(point) else {
+            continue;
+        };
+        // the arrows are as long as the space between them where the field is typically
+        // strong, shorter where it's weaker, and centred on their point
+        let length = (spacing * 0.9 * coloring.get_amount(vector)).max(3.0);
+        let half = (direction.0 * length / 2.0, direction.1 * length / 2.0);
+        let (tail, tip) = (
+            (point.0 - half.0, point.1 - half.1),
+            (point.0 + half.0, point.1 + half.1),
+        );
+        let color = coloring.get_color(vector);
+        draw_segment(image, tail, tip, color);
+
+        let head_length = (length * 0.35).max(3.0);
+        let head_width = head_length * 0.5;
+        let base = (
+            tip.0 - direction.0 * head_length,
+            tip.1 - direction.1 * head_length,
+        );
+        let head = [
+            tip,
+            (
+                base.0 - direction.1 * head_width,
+                base.1 + direction.0 * head_width,
+            ),
+            (
+                base.0 + direction.1 * head_width,
+                base.1 - direction.0 * head_width,
+            ),
+        ]
+        .map(|(x, y)| Point::new(x.round() as i32, y.round() as i32));
+        if head[0] != head[2] && head[0] != head[1] {
+            draw_polygon_mut(image, &head, Rgba(color));
+        }
+    }
+}
+
+/// Follows the field from a pixel a pixel at a time, forwards or backwards, stopping at
+/// the edge of the image or where the field stops
+pub fn trace_streamline(
+    options: &VectorFieldOptions,
+    start: (f64, f64),
+    forwards: bool,
+) -> Vec<(f64, f64)> {
+    let sign = match forwards {
+        true => 1.0,
+        false => -1.0,
+    };
+    let mut point = start;
+    let mut points = vec![point];
+    for _ in 0..options.length {
+        // the direction halfway along the step follows curves much better
+        let Some(direction) = options.get_direction(point) else {
+            break;
+        };
+        let middle = (
+            point.0 + direction.0 * sign / 2.0,
+            point.1 + direction.1 * sign / 2.0,
+        );
+        let Some(direction) = options.get_direction(middle) else {
+            break;
+        };
+        point = (point.0 + direction.0 * sign, point.1 + direction.1 * sign);
+        if !options.contains(point) {
+            break;
+        }
+        points.push(point);
+    }
+
+    points
+}
+
+fn draw_streamlines(image: &mut RgbaImage, options: &VectorFieldOptions, coloring: &Coloring) {
+    for start in get_grid_points(options) {
+        for forwards in [true, false] {
+            let points = trace_streamline(options, start, forwards);
+            for segment in points.windows(2) {
+                let Some(vector) = options.get_vector(segment[0]) else {
+                    continue;
+                };
+                draw_segment(image, segment[0], segment[1], coloring.get_color(vector));
+            }
+        }
+    }
+}
+
+/// Smears random noise along the field, each pixel being the average of the noise along
+/// the streamline through it
+fn draw_lic(image: &mut RgbaImage, options: &VectorFieldOptions, coloring: &Coloring) {
+    let mut rng = match options.seed {
+        Some(seed) => fastrand::Rng::with_seed(seed),
+        None => fastrand::Rng::new(),
+    };
+    let (width, height) = (options.width, options.height);
+    let noise: Vec<f64> = (0..width * height).map(|_| rng.f64()).collect();
+    // the field is worked out once for each pixel, then followed from pixel to pixel
+    let vectors: Vec<Option<(f64, f64)>> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| options.get_vector((x as f64 + 0.5, y as f64 + 0.5)))
+        .collect();
+    let get_index = |(x, y): (f64, f64)| {
+        options
+            .contains((x, y))
+            .then(|| y as usize * width as usize + x as usize)
+    };
+
+    let values: Vec<f64> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let start = (x as f64 + 0.5, y as f64 + 0.5);
+            let (mut total, mut count) = (0.0, 0);
+            for sign in [1.0, -1.0] {
+                let mut point = start;
+                for _ in 0..options.length {
+                    let Some(index) = get_index(point) else {
+                        break;
+                    };
+                    total += noise[index];
+                    count += 1;
+                    let Some((vector_x, vector_y)) = vectors[index] else {
+                        break;
+                    };
+                    let length = f64::hypot(vector_x, vector_y);
+                    if length == 0.0 {
+                        break;
+                    }
+                    point = (
+                        point.0 + sign * vector_x / length,
+                        point.1 + sign * vector_y / length,
+                    );
+                }
+            }
+            match count {
+                0 => 0.5,
+                count => total / count as f64,
+            }
+        })
+        .collect();
+
+    // averaging flattens the noise towards grey, so the contrast is stretched back out
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    let deviation = (values
+        .iter()
+        .map(|value| (value - mean).powi(2))
+        .sum::<f64>()
+        / values.len() as f64)
+        .sqrt()
+        .max(f64::EPSILON);
+    let background_color = options.background_color.to_rgba8();
+    for ((pixel, value), vector) in image.pixels_mut().zip(values).zip(vectors) {
+        let amount = ((value - mean) / (3.0 * deviation) + 0.5).clamp(0.0, 1.0);
+        let color = coloring.get_color(vector.unwrap_or_default());
+        *pixel = Rgba(interpolate_color(color, background_color, amount));
+    }
+}
+
+/// Parses a formula for one part of the field, using x and y
+fn parse_field_formula(value: &str) -> Result<Formula, String> {
+    Formula::parse_with_variables(value, &["x", "y"])
+}
+
+/// Parses a point given as x,y
+fn parse_point(value: &str) -> Result<(f64, f64), String> {
+    let (x, y) = value
+        .split_once(',')
+        .ok_or_else(|| "expected x,y like 0,0".to_string())?;
+    let parse_part = |part: &str| {
+        part.trim()
+            .parse::<f64>()
+            .map_err(|error| error.to_string())
+    };
+
+    Ok((parse_part(x)?, parse_part(y)?))
+}
+
+#[derive(clap::Args, Debug)]
+pub struct VectorFieldArgs {
+    /// The x part of the field, a formula using x, y and the same numbers and functions
+    /// as --formula for the mandelbrot
+    #[arg(long, default_value = "y", value_parser = parse_field_formula, allow_hyphen_values = true)]
+    fx: Formula,
+
+    /// The y part of the field, like the x part
+    #[arg(long, default_value = "sin(x) - 0.3y", value_parser = parse_field_formula, allow_hyphen_values = true)]
+    fy: Formula,
+
+    #[arg(long, default_value = "streamlines")]
+    mode: VectorFieldMode,
+
+    /// The point in the middle of the image, as x,y
+    #[arg(long, default_value = "0,0", value_parser = parse_point, allow_hyphen_values = true)]
+    center: (f64, f64),
+
+    /// How far the field goes from the middle to the left and right edges
+    #[arg(long, default_value = "4.0")]
+    range: f64,
+
+    #[arg(long, default_value = "800", value_parser = parse_size)]
+    width: u32,
+
+    #[arg(long, default_value = "800", value_parser = parse_size)]
+    height: u32,
+
+    /// How far apart the arrows and the starts of the streamlines are in pixels
+    #[arg(long, default_value = "32", value_parser = parse_size)]
+    spacing: u32,
+
+    /// How many pixels each streamline goes each way from its start, or how far the
+    /// noise is smeared each way for lic
+    #[arg(long, default_value = "40", value_parser = parse_size)]
+    length: u32,
+
+    #[arg(short, long, default_value = "black", value_parser = parse_color)]
+    color: Color,
+
+    /// Colours by how strong the field is, from the start of the palette where it's
+    /// weakest, instead of using the colour
+    #[arg(short, long)]
+    palette: Option<PaletteType>,
+
+    /// The colour behind the field, which can be transparent
+    #[arg(short, long, alias = "background", default_value = "white", value_parser = parse_color)]
+    background_color: Color,
+
+    /// The seed for the noise smeared by lic, random if not given
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+pub struct VectorFieldGenerator;
+
+impl Generator for VectorFieldGenerator {
+    type Args = VectorFieldArgs;
+
+    fn name(&self) -> &'static str {
+        "vector-field"
+    }
+
+    fn is_animated(&self, _args: &VectorFieldArgs) -> bool {
+        false
+    }
+
+    fn estimate_memory(&self, args: &VectorFieldArgs) -> Option<MemoryEstimate> {
+        Some(MemoryEstimate::still(args.width, args.height))
+    }
+
+    fn generate(&self, args: VectorFieldArgs) -> Result<Generated, GeneratorError> {
+        if args.range <= 0.0 {
+            return Err(GeneratorError::Failed(
+                "the range has to be more than 0".to_string(),
+            ));
+        }
+
+        Ok(Generated::Still(generate_vector_field_image(
+            VectorFieldOptions::new(
+                args.fx,
+                args.fy,
+                args.mode,
+                args.center,
+                args.range,
+                args.width,
+                args.height,
+                args.spacing,
+                args.length,
+                args.color,
+                args.palette,
+                args.background_color,
+                args.seed,
+            ),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_streamlines_follow_field() {
+        // the field going round the middle, so streamlines are circles
+        let options = VectorFieldOptions::new(
+            parse_field_formula("-y").unwrap(),
+            parse_field_formula("x").unwrap(),
+            VectorFieldMode::Streamlines,
+            (0.0, 0.0),
+            2.0,
+            200,
+            200,
+            20,
+            300,
+            Color::new(0.0, 0.0, 0.0, 1.0),
+            None,
+            Color::new(1.0, 1.0, 1.0, 1.0),
+            None,
+        );
+        // straight up from the middle the field points left, with y going up
+        let vector = options.get_vector((100.0, 50.0)).unwrap();
+        assert!((vector.0 + 1.0).abs() < 1e-9 && vector.1.abs() < 1e-9);
+
+        let points = trace_streamline(&options, (100.0, 50.0), true);
+        assert_eq!(points.len(), 301);
+        for (x, y) in points {
+            assert!((f64::hypot(x - 100.0, y - 100.0) - 50.0).abs() < 0.1);
+        }
+    }
+}
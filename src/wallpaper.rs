@@ -0,0 +1,84 @@
+//! Setting the saved image as the desktop wallpaper
+//!
+//! There's no standard way to do this, so it runs whatever tool the desktop uses: the
+//! desktop's settings on GNOME like desktops and KDE Plasma, feh for other Linux window
+//! managers, System Events on macOS and the Windows API through PowerShell
+
+use std::{env, path::Path, process::Command};
+
+use log::debug;
+
+use crate::error::Error;
+
+/// Sets the image file as the wallpaper, which needs the full path to it
+pub fn set_wallpaper(file_path: &str) -> Result<(), Error> {
+    let path = Path::new(file_path)
+        .canonicalize()
+        .map_err(|io_error| Error::SetWallpaper(format!("finding {}: {}", file_path, io_error)))?;
+    let path = path.to_string_lossy();
+
+    if cfg!(target_os = "windows") {
+        // SPI_SETDESKWALLPAPER, saved to the user's profile and sent to every window
+        let script = format!(
+            "Add-Type -TypeDefinition 'using System.Runtime.InteropServices; \
+             public class Wallpaper {{ [DllImport(\"user32.dll\", CharSet = CharSet.Unicode)] \
+             public static extern int SystemParametersInfo(int action, int param, string value, int flags); }}'; \
+             [Wallpaper]::SystemParametersInfo(20, 0, '{}', 3)",
+            path.replace('\'', "''")
+        );
+        run("powershell", &["-NoProfile", "-Command", &script])
+    } else if cfg!(target_os = "macos") {
+        let script = format!(
+            "tell application \"System Events\" to tell every desktop to set picture to \"{}\"",
+            path.replace('\\', "\\\\").replace('"', "\\\"")
+        );
+        run("osascript", &["-e", &script])
+    } else {
+        set_linux_wallpaper(&path)
+    }
+}
+
+/// Picks the tool from the desktop that's running, falling back to feh which works with
+/// most window managers
+fn set_linux_wallpaper(path: &str) -> Result<(), Error> {
+    let desktop = env::var("XDG_CURRENT_DESKTOP")
+        .unwrap_or_default()
+        .to_lowercase();
+
+    if ["gnome", "unity", "budgie", "pantheon", "cinnamon"]
+        .iter()
+        .any(|name| desktop.contains(name))
+    {
+        let uri = format!("file://{}", path);
+        let schema = match desktop.contains("cinnamon") {
+            true => "org.cinnamon.desktop.background",
+            false => "org.gnome.desktop.background",
+        };
+        run("gsettings", &["set", schema, "picture-uri", &uri])?;
+        // newer GNOME versions keep a separate wallpaper for dark mode, which older
+        // versions don't have so it failing is fine
+        let _ = run("gsettings", &["set", schema, "picture-uri-dark", &uri]);
+        Ok(())
+    } else if desktop.contains("kde") {
+        run("plasma-apply-wallpaperimage", &[path])
+    } else {
+        run("feh", &["--bg-fill", path])
+    }
+}
+
+fn run(program: &str, args: &[&str]) -> Result<(), Error> {
+    debug!("Running {} {}", program, args.join(" "));
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|io_error| Error::SetWallpaper(format!("running {}: {}", program, io_error)))?;
+    if !output.status.success() {
+        return Err(Error::SetWallpaper(format!(
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(())
+}
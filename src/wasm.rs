@@ -0,0 +1,196 @@
+//! Bindings for running the generators in the browser
+//!
+//! Build with `wasm-pack build --target web --features wasm`, each function returns the
+//! raw RGBA pixels which can go straight into an `ImageData`
+
+use clap::ValueEnum;
+use csscolorparser::Color;
+use image::RgbaImage;
+use num_complex::Complex64;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    FractalColoring, FractalVariant, MazeAlgorithm, TuringPreset,
+    mandelbrot::{FractalSet, MandelbrotImageOptions, ViewPort, render_fractal},
+    maze::{MazeOptions, generate_maze_image},
+    perlin::{PerlinNoiseOptions, generate_perlin_noise},
+    reaction_diffusion::{ReactionDiffusionOptions, generate_reaction_diffusion_image},
+};
+
+/// The pixels of a generated image, 4 bytes per pixel
+#[wasm_bindgen]
+pub struct RgbaBuffer {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl RgbaBuffer {
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn pixels(&self) -> Vec<u8> {
+        self.pixels.clone()
+    }
+}
+
+impl From<RgbaImage> for RgbaBuffer {
+    fn from(image: RgbaImage) -> Self {
+        Self {
+            width: image.width(),
+            height: image.height(),
+            pixels: image.into_raw(),
+        }
+    }
+}
+
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn mandelbrot(
+    width: u32,
+    height: u32,
+    centre_real: f64,
+    centre_imaginary: f64,
+    zoom: f64,
+    color: &str,
+    background_color: &str,
+    gradient: bool,
+) -> Result<RgbaBuffer, JsError> {
+    let options = MandelbrotImageOptions::new(
+        parse_color(color)?,
+        parse_color(background_color)?,
+        gradient,
+        false,
+        None,
+        FractalColoring::Iterations,
+        FractalVariant::Standard,
+        None,
+    );
+    let mut viewport = ViewPort::new(Complex64::new(centre_real, centre_imaginary), zoom);
+    viewport.fit_to_size(width, height);
+
+    Ok(render_fractal(&options, FractalSet::Mandelbrot, &viewport, width, height).into())
+}
+
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn julia(
+    width: u32,
+    height: u32,
+    constant_real: f64,
+    constant_imaginary: f64,
+    zoom: f64,
+    color: &str,
+    background_color: &str,
+    gradient: bool,
+) -> Result<RgbaBuffer, JsError> {
+    let options = MandelbrotImageOptions::new(
+        parse_color(color)?,
+        parse_color(background_color)?,
+        gradient,
+        false,
+        None,
+        FractalColoring::Iterations,
+        FractalVariant::Standard,
+        None,
+    );
+    let mut viewport = ViewPort::normal_julia();
+    viewport.zoom(zoom);
+    viewport.fit_to_size(width, height);
+    let set = FractalSet::Julia(Complex64::new(constant_real, constant_imaginary));
+
+    Ok(render_fractal(&options, set, &viewport, width, height).into())
+}
+
+#[wasm_bindgen]
+pub fn perlin_noise(
+    size: u32,
+    color1: &str,
+    color2: &str,
+    seed: u64,
+) -> Result<RgbaBuffer, JsError> {
+    let options = PerlinNoiseOptions::new(
+        size,
+        parse_color(color1)?,
+        parse_color(color2)?,
+        Color::new(0.0, 0.0, 0.0, 0.0),
+        Some(seed),
+        false,
+        None,
+        None,
+        None,
+    );
+
+    Ok(generate_perlin_noise(options).into_rgba8().into())
+}
+
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn maze(
+    columns: u32,
+    rows: u32,
+    cell_size: u32,
+    wall_thickness: u32,
+    algorithm: &str,
+    seed: u64,
+    wall_color: &str,
+    path_color: &str,
+) -> Result<RgbaBuffer, JsError> {
+    let options = MazeOptions::new(
+        columns,
+        rows,
+        cell_size,
+        wall_thickness,
+        parse_value::<MazeAlgorithm>(algorithm)?,
+        Some(seed),
+        parse_color(wall_color)?,
+        parse_color(path_color)?,
+    );
+    options.check().map_err(|message| JsError::new(&message))?;
+
+    Ok(generate_maze_image(options).into_rgba8().into())
+}
+
+#[wasm_bindgen]
+pub fn reaction_diffusion(
+    size: u32,
+    steps: u32,
+    preset: &str,
+    seed: u64,
+    color: &str,
+    background_color: &str,
+) -> Result<RgbaBuffer, JsError> {
+    let options = ReactionDiffusionOptions::new(
+        size,
+        steps,
+        parse_value::<TuringPreset>(preset)?,
+        None,
+        None,
+        None,
+        Some(seed),
+        parse_color(color)?,
+        parse_color(background_color)?,
+    );
+
+    Ok(generate_reaction_diffusion_image(options)
+        .into_rgba8()
+        .into())
+}
+
+fn parse_color(value: &str) -> Result<Color, JsError> {
+    csscolorparser::parse(value).map_err(|error| JsError::new(&error.to_string()))
+}
+
+/// Parses one of the options using the same names as the command line
+fn parse_value<T: ValueEnum>(value: &str) -> Result<T, JsError> {
+    T::from_str(value, true).map_err(|error| JsError::new(&error))
+}
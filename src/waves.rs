@@ -1,6 +1,10 @@
 //! Module for generating waves
 //!
 //! This is more experimental
+//!
+//! Either sweeps a dot along y = f(x) across the image, or traces it parametrically
+//! with x(t) and y(t) each being their own wave, which gives circles, ellipses and
+//! [Lissajous curves](https://en.wikipedia.org/wiki/Lissajous_curve)
 
 use core::f64;
 
@@ -8,50 +12,223 @@ use csscolorparser::Color;
 use image::{ImageBuffer, Rgba, RgbaImage};
 use imageproc::drawing::draw_filled_circle_mut;
 
-use crate::WaveType;
+use crate::{
+    WaveMode, WaveType,
+    cancel::render_frames,
+    generator::{Generated, Generator, GeneratorError},
+    memory::MemoryEstimate,
+    parse_color,
+};
+
+const DOT_RADIUS: i32 = 30;
+const PARAMETRIC_FRAME_NUM: u32 = 125;
 
 pub struct WaveOptions {
     color: Color,
-    wave_type: WaveType,
+    background_color: Color,
+    motion: WaveMotion,
     width: u32,
     height: u32,
 }
 
 impl WaveOptions {
-    pub fn new(color: Color, wave_type: WaveType, width: u32, height: u32) -> Self {
+    pub fn new(
+        color: Color,
+        background_color: Color,
+        motion: WaveMotion,
+        width: u32,
+        height: u32,
+    ) -> Self {
         Self {
             color,
-            wave_type,
+            background_color,
+            motion,
             width,
             height,
         }
     }
 }
 
+/// How the dot moves through the image
+#[derive(Clone, Copy, Debug)]
+pub enum WaveMotion {
+    /// Sweeps the dot left to right following y = f(x)
+    Sweep(WaveType),
+    /// Moves the dot along x(t), y(t)
+    Parametric {
+        x: ParametricWave,
+        y: ParametricWave,
+    },
+}
+
+/// A single wave used for one axis of a parametric motion
+#[derive(Clone, Copy, Debug)]
+pub struct ParametricWave {
+    wave_type: WaveType,
+    frequency: f64,
+    phase: f64,
+}
+
+impl ParametricWave {
+    pub fn new(wave_type: WaveType, frequency: f64, phase: f64) -> Self {
+        Self {
+            wave_type,
+            frequency,
+            phase,
+        }
+    }
+
+    fn value(&self, t: f64) -> f64 {
+        get_wave_function(self.wave_type)(self.frequency * t + self.phase)
+    }
+}
+
 pub fn generate_wave_images(options: WaveOptions) -> Vec<RgbaImage> {
-    let mut images = Vec::new();
+    match options.motion {
+        WaveMotion::Sweep(wave_type) => generate_sweep_images(&options, wave_type),
+        WaveMotion::Parametric { x, y } => generate_parametric_images(&options, x, y),
+    }
+}
 
+fn generate_sweep_images(options: &WaveOptions, wave_type: WaveType) -> Vec<RgbaImage> {
     let half_y = options.height / 2;
+    let wave_function = get_wave_function(wave_type);
+    let dots: Vec<(i32, i32)> = (0..options.width)
+        .step_by(4)
+        .map(|x| {
+            let distance_through_radians = 2.0 * f64::consts::PI * x as f64 / options.width as f64;
+
+            let y = wave_function(distance_through_radians) * half_y as f64 * 0.5;
+            (x as i32, half_y as i32 + y as i32)
+        })
+        .collect();
+
+    draw_dot_trail_images(options, &dots)
+}
+
+/// Traces t over a single 2π period, so integer frequencies always give a closed curve
+fn generate_parametric_images(
+    options: &WaveOptions,
+    x_wave: ParametricWave,
+    y_wave: ParametricWave,
+) -> Vec<RgbaImage> {
+    let half_x = options.width as f64 / 2.0;
+    let half_y = options.height as f64 / 2.0;
+    // leave room for the dot so it doesn't go off the edge at the extremes
+    let amplitude_x = half_x - DOT_RADIUS as f64;
+    let amplitude_y = half_y - DOT_RADIUS as f64;
+    let dots: Vec<(i32, i32)> = (0..PARAMETRIC_FRAME_NUM)
+        .map(|frame| {
+            let t = 2.0 * f64::consts::PI * frame as f64 / PARAMETRIC_FRAME_NUM as f64;
+
+            let x = half_x + x_wave.value(t) * amplitude_x;
+            // image y goes downwards so flip it to match the usual maths orientation
+            let y = half_y - y_wave.value(t) * amplitude_y;
+            (x as i32, y as i32)
+        })
+        .collect();
+
+    draw_dot_trail_images(options, &dots)
+}
+
+/// Makes a frame per dot, with each frame showing that dot and all the ones before it
+///
+/// Every frame draws its whole trail from scratch so the frames can be drawn in parallel
+fn draw_dot_trail_images(options: &WaveOptions, dots: &[(i32, i32)]) -> Vec<RgbaImage> {
     let color_pixel = options.color.to_rgba8();
-    for x in (0..options.width).step_by(4) {
-        // copy last image or create new one
-        let mut image = images
-            .last()
-            .map(|i: &ImageBuffer<Rgba<u8>, Vec<u8>>| i.clone())
-            .unwrap_or_else(|| ImageBuffer::new(options.width, options.height));
-        let distance_through_radians = 2.0 * f64::consts::PI * x as f64 / options.width as f64;
-
-        let wave_function = match options.wave_type {
-            WaveType::Sine => f64::sin,
-            WaveType::Cosine => f64::cos,
-            WaveType::Tangent => f64::tan,
-        };
-        let y = wave_function(distance_through_radians) * half_y as f64 * 0.5;
-        let y = half_y as i32 + y as i32;
-        draw_filled_circle_mut(&mut image, (x as i32, y), 30, Rgba(color_pixel));
+    let background_pixel = options.background_color.to_rgba8();
 
-        images.push(image);
+    render_frames(1..=dots.len(), |dot_num| {
+        let mut image =
+            ImageBuffer::from_pixel(options.width, options.height, Rgba(background_pixel));
+        for &dot in &dots[..dot_num] {
+            draw_filled_circle_mut(&mut image, dot, DOT_RADIUS, Rgba(color_pixel));
+        }
+        image
+    })
+}
+
+fn get_wave_function(wave_type: WaveType) -> fn(f64) -> f64 {
+    match wave_type {
+        WaveType::Sine => f64::sin,
+        WaveType::Cosine => f64::cos,
+        WaveType::Tangent => f64::tan,
+    }
+}
+
+#[derive(clap::Args, Debug)]
+pub struct WaveArgs {
+    #[arg(short, long, default_value = "black", value_parser = parse_color)]
+    color: Color,
+
+    #[arg(short, long, default_value = "sine")]
+    wave_type: WaveType,
+
+    #[arg(short, long, default_value = "sweep")]
+    mode: WaveMode,
+
+    /// The wave used for x(t) in parametric mode
+    #[arg(long, default_value = "cosine")]
+    x_wave: WaveType,
+
+    /// The frequency of x(t) in parametric mode
+    #[arg(long, default_value = "1.0")]
+    x_frequency: f64,
+
+    /// The phase of x(t) in parametric mode, in radians
+    #[arg(long, default_value = "0.0")]
+    x_phase: f64,
+
+    /// The wave used for y(t) in parametric mode
+    #[arg(long, default_value = "sine")]
+    y_wave: WaveType,
+
+    /// The frequency of y(t) in parametric mode
+    #[arg(long, default_value = "1.0")]
+    y_frequency: f64,
+
+    /// The phase of y(t) in parametric mode, in radians
+    #[arg(long, default_value = "0.0")]
+    y_phase: f64,
+
+    /// The colour behind the image, which can be transparent
+    #[arg(short, long, alias = "background", default_value = "transparent", value_parser = parse_color)]
+    background_color: Color,
+}
+
+pub struct WaveGenerator;
+
+impl Generator for WaveGenerator {
+    type Args = WaveArgs;
+
+    fn name(&self) -> &'static str {
+        "wave"
     }
 
-    images
+    fn is_animated(&self, _args: &WaveArgs) -> bool {
+        true
+    }
+
+    fn estimate_memory(&self, _args: &WaveArgs) -> Option<MemoryEstimate> {
+        // the dots are a frame each, and both motions draw the same number of them
+        Some(MemoryEstimate::frames(500, 500, PARAMETRIC_FRAME_NUM))
+    }
+
+    fn generate(&self, args: WaveArgs) -> Result<Generated, GeneratorError> {
+        let motion = match args.mode {
+            WaveMode::Sweep => WaveMotion::Sweep(args.wave_type),
+            WaveMode::Parametric => WaveMotion::Parametric {
+                x: ParametricWave::new(args.x_wave, args.x_frequency, args.x_phase),
+                y: ParametricWave::new(args.y_wave, args.y_frequency, args.y_phase),
+            },
+        };
+
+        Ok(Generated::Frames(generate_wave_images(WaveOptions::new(
+            args.color,
+            args.background_color,
+            motion,
+            500,
+            500,
+        ))))
+    }
 }
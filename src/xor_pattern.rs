@@ -0,0 +1,206 @@
+//! The xor pattern and munching squares
+//!
+//! Taking x xor y for every pixel gives a pattern of nested squares, and taking it
+//! modulo a number breaks that up into diagonal weaves. Munching squares is an old
+//! display hack from the PDP-1, which fills in the pixels where x xor y is below a
+//! threshold that keeps going up, so squares get eaten away in a pattern that repeats
+//! at every scale. See <https://en.wikipedia.org/wiki/Munching_square> for more info
+
+use csscolorparser::Color;
+use image::{Rgba, RgbaImage};
+
+use crate::{
+    PaletteType, XorPatternMode,
+    cancel::render_frames,
+    generator::{Generated, Generator, GeneratorError},
+    memory::MemoryEstimate,
+    palette::get_palette_color,
+    parse_color, parse_size,
+};
+
+pub struct XorPatternOptions {
+    mode: XorPatternMode,
+    /// The number x xor y is taken modulo for the modulo mode
+    modulus: u32,
+    /// How many cells go across and down
+    size: u32,
+    /// The width and height of each cell in pixels
+    cell_size: u32,
+    frame_num: u32,
+    palette: PaletteType,
+    /// How many times the palette cycles through per loop
+    speed: u32,
+    /// The colour of the pixels that haven't been munched
+    background_color: Color,
+}
+
+impl XorPatternOptions {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        mode: XorPatternMode,
+        modulus: u32,
+        size: u32,
+        cell_size: u32,
+        frame_num: u32,
+        palette: PaletteType,
+        speed: u32,
+        background_color: Color,
+    ) -> Self {
+        Self {
+            mode,
+            modulus,
+            size,
+            cell_size,
+            frame_num,
+            palette,
+            speed,
+            background_color,
+        }
+    }
+}
+
+/// Whether a cell has been munched when the threshold is part way through its loop,
+/// which goes up to the span filling everything in and then up to twice the span
+/// eating it away again, so the last frame runs into the first
+fn is_munched(value: u32, threshold: u32, span: u32) -> bool {
+    value < threshold && value >= threshold.saturating_sub(span)
+}
+
+/// Renders a frame of the loop, where the modulo mode is the same every frame apart
+/// from the palette cycling
+pub fn generate_xor_pattern_image(options: &XorPatternOptions, frame: u32) -> RgbaImage {
+    let (size, cell_size) = (options.size, options.cell_size);
+    let background_color = Rgba(options.background_color.to_rgba8());
+    // x xor y goes up to just below the power of 2 above the size
+    let span = size.next_power_of_two();
+    let loop_amount = frame as f64 / options.frame_num as f64;
+    let threshold = (loop_amount * 2.0 * span as f64).round() as u32;
+    let mut image = RgbaImage::new(size * cell_size, size * cell_size);
+
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        let value = (x / cell_size) ^ (y / cell_size);
+        let position = match options.mode {
+            XorPatternMode::Modulo => (value % options.modulus) as f64 / options.modulus as f64,
+            XorPatternMode::Munch if is_munched(value, threshold, span) => {
+                value as f64 / span as f64
+            }
+            XorPatternMode::Munch => {
+                *pixel = background_color;
+                continue;
+            }
+        };
+        *pixel = Rgba(get_palette_color(
+            options.palette,
+            position + loop_amount * options.speed as f64,
+        ));
+    }
+
+    image
+}
+
+pub fn generate_xor_pattern_images(options: XorPatternOptions) -> Vec<RgbaImage> {
+    render_frames(0..options.frame_num, |frame| {
+        generate_xor_pattern_image(&options, frame)
+    })
+}
+
+#[derive(clap::Args, Debug)]
+pub struct XorPatternArgs {
+    #[arg(short, long, default_value = "modulo")]
+    mode: XorPatternMode,
+
+    /// The number x xor y is taken modulo for the modulo mode, where small primes make
+    /// the most interesting weaves
+    #[arg(short = 'k', long, default_value = "9", value_parser = clap::value_parser!(u32).range(2..))]
+    modulus: u32,
+
+    /// How many cells go across and down, with powers of 2 making the squares line up
+    /// with the edges
+    #[arg(short, long, default_value = "256", value_parser = parse_size)]
+    size: u32,
+
+    /// The width and height of each cell in pixels
+    #[arg(long, default_value = "2", value_parser = parse_size)]
+    cell_size: u32,
+
+    /// The number of frames in the loop, with the modulo mode being a still image if not
+    /// given and munching squares taking 128
+    #[arg(short, long, value_parser = parse_size)]
+    frames: Option<u32>,
+
+    #[arg(short, long, default_value = "rainbow")]
+    palette: PaletteType,
+
+    /// How many times the palette cycles through per loop
+    #[arg(long, default_value = "1")]
+    speed: u32,
+
+    /// The colour of the cells that haven't been munched
+    #[arg(short, long, alias = "background", default_value = "black", value_parser = parse_color)]
+    background_color: Color,
+}
+
+pub struct XorPatternGenerator;
+
+impl Generator for XorPatternGenerator {
+    type Args = XorPatternArgs;
+
+    fn name(&self) -> &'static str {
+        "xor-pattern"
+    }
+
+    fn is_animated(&self, args: &XorPatternArgs) -> bool {
+        args.mode == XorPatternMode::Munch || args.frames.is_some_and(|frames| frames > 1)
+    }
+
+    fn estimate_memory(&self, args: &XorPatternArgs) -> Option<MemoryEstimate> {
+        let image_size = args.size.saturating_mul(args.cell_size);
+        let frame_num = match (args.frames, args.mode) {
+            (Some(frames), _) => frames,
+            (None, XorPatternMode::Modulo) => 1,
+            (None, XorPatternMode::Munch) => 128,
+        };
+        Some(MemoryEstimate::frames(image_size, image_size, frame_num))
+    }
+
+    fn generate(&self, args: XorPatternArgs) -> Result<Generated, GeneratorError> {
+        let frame_num = match (args.frames, args.mode) {
+            (Some(frames), _) => frames,
+            (None, XorPatternMode::Modulo) => 1,
+            (None, XorPatternMode::Munch) => 128,
+        };
+        let is_animated = self.is_animated(&args);
+        let options = XorPatternOptions::new(
+            args.mode,
+            args.modulus,
+            args.size,
+            args.cell_size,
+            frame_num,
+            args.palette,
+            args.speed,
+            args.background_color,
+        );
+
+        Ok(match is_animated {
+            true => Generated::Frames(generate_xor_pattern_images(options)),
+            false => Generated::Still(generate_xor_pattern_image(&options, 0)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_munching() {
+        // fills in up to the threshold, then eats away from the bottom
+        assert!(!is_munched(5, 0, 8));
+        assert!(is_munched(5, 6, 8));
+        assert!(!is_munched(6, 6, 8));
+        assert!(is_munched(7, 8, 8));
+        assert!(!is_munched(5, 14, 8));
+        assert!(is_munched(6, 14, 8));
+        assert!(!is_munched(7, 16, 8));
+    }
+}